@@ -0,0 +1,188 @@
+//! Approvazione esterna dei piani
+//!
+//! Negli ambienti regolamentati un piano può richiedere l'approvazione di un sistema
+//! esterno prima di essere eseguito. Con la sezione `approval` della configurazione,
+//! `galatea apply PLAN` invia il piano all'endpoint indicato e attende la risposta:
+//!
+//! ```yaml
+//! approval:
+//!   url: https://change.example.com/api/galatea
+//!   token: "..."          # inviato come Bearer token, anche cifrato
+//!   timeout: 3600         # attesa massima della decisione, in secondi
+//!   poll_interval: 15     # intervallo tra due richieste di stato, in secondi
+//! ```
+//!
+//! L'endpoint riceve in POST un documento JSON con host, stack, impronta e piano, e
+//! risponde con `{"status": "approved" | "denied" | "pending", "id": "...", "reason": "..."}`.
+//! Finché la richiesta è `pending` lo stato viene letto con GET da `<url>/<id>`.
+
+use std::thread;
+use std::time::{Duration, Instant};
+use anyhow::{Context, Result, anyhow};
+use log::{info, warn};
+use reqwest::blocking::{Client, RequestBuilder};
+use serde::{Serialize, Deserialize};
+
+use crate::config::Config;
+use crate::plan::Plan;
+use crate::redact;
+use crate::secrets;
+use crate::source;
+
+/// Configurazione dell'endpoint di approvazione
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApprovalConfig {
+    /// URL a cui inviare le richieste di approvazione
+    pub url: String,
+
+    /// Token inviato nell'intestazione `Authorization: Bearer`
+    #[serde(default)]
+    pub token: Option<String>,
+
+    /// Attesa massima della decisione, in secondi
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+
+    /// Intervallo tra due richieste di stato, in secondi
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval: u64,
+}
+
+fn default_timeout() -> u64 {
+    3600
+}
+
+fn default_poll_interval() -> u64 {
+    15
+}
+
+/// Stato di una richiesta di approvazione
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApprovalStatus {
+    /// In attesa di una decisione
+    Pending,
+
+    /// Piano approvato
+    Approved,
+
+    /// Piano respinto
+    Denied,
+}
+
+/// Risposta dell'endpoint di approvazione
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApprovalResponse {
+    /// Stato della richiesta
+    pub status: ApprovalStatus,
+
+    /// Identificativo della richiesta, per leggerne lo stato
+    #[serde(default)]
+    pub id: Option<String>,
+
+    /// Chi ha preso la decisione
+    #[serde(default)]
+    pub approver: Option<String>,
+
+    /// Motivazione della decisione
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Richiesta inviata all'endpoint di approvazione
+#[derive(Debug, Serialize)]
+struct ApprovalRequest<'a> {
+    host: &'a str,
+    stack: &'a str,
+    /// Impronta SHA-256 del piano, per legare l'approvazione al piano esatto
+    plan_digest: String,
+    plan: &'a Plan,
+}
+
+/// Invia il piano all'endpoint di approvazione e ne attende la decisione
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+/// * `approval` - La configurazione dell'endpoint
+/// * `plan` - Il piano da approvare
+///
+/// # Returns
+///
+/// La risposta finale (`approved` o `denied`); un errore se l'endpoint non risponde o
+/// se la decisione non arriva entro il timeout
+pub fn request_approval(config: &Config, approval: &ApprovalConfig, plan: &Plan) -> Result<ApprovalResponse> {
+    let token = match &approval.token {
+        Some(token) => Some(secrets::reveal(config, token).context("Failed to decrypt approval token")?),
+        None => None,
+    };
+    if let Some(token) = &token {
+        redact::register(token);
+    }
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(config.download_timeout))
+        .build()
+        .context("Failed to create HTTP client")?;
+
+    let body = serde_json::to_string(&ApprovalRequest {
+        host: &plan.host,
+        stack: &plan.stack,
+        plan_digest: source::sha256_hex(plan.to_json()?.as_bytes()),
+        plan,
+    }).context("Failed to serialize approval request")?;
+
+    info!("Requesting approval of the plan for stack {} from {}", plan.stack, approval.url);
+    let request = client.post(&approval.url)
+        .header("Content-Type", "application/json")
+        .body(body);
+    let mut response = send(request, token.as_deref(), &approval.url)?;
+
+    let started = Instant::now();
+    while response.status == ApprovalStatus::Pending {
+        let id = response.id.clone()
+            .ok_or_else(|| anyhow!("Approval endpoint returned a pending request without an id"))?;
+
+        if started.elapsed() >= Duration::from_secs(approval.timeout) {
+            return Err(anyhow!("No approval decision for request {} within {} seconds", id, approval.timeout));
+        }
+        thread::sleep(Duration::from_secs(approval.poll_interval.max(1)));
+
+        let status_url = format!("{}/{}", approval.url.trim_end_matches('/'), id);
+        response = match send(client.get(&status_url), token.as_deref(), &status_url) {
+            Ok(response) => response,
+            // Un errore temporaneo non annulla la richiesta già inviata
+            Err(e) => {
+                warn!("Failed to read the status of approval request {}: {:#}", id, e);
+                ApprovalResponse { status: ApprovalStatus::Pending, id: Some(id), approver: None, reason: None }
+            },
+        };
+    }
+
+    info!(
+        "Plan for stack {} {} by {}",
+        plan.stack,
+        if response.status == ApprovalStatus::Approved { "approved" } else { "denied" },
+        response.approver.as_deref().unwrap_or("unknown approver")
+    );
+    Ok(response)
+}
+
+/// Invia una richiesta all'endpoint e ne interpreta la risposta
+fn send(request: RequestBuilder, token: Option<&str>, url: &str) -> Result<ApprovalResponse> {
+    let request = match token {
+        Some(token) => request.bearer_auth(token),
+        None => request,
+    };
+
+    let response = request.send()
+        .context(format!("Failed to contact approval endpoint {}", url))?;
+    if !response.status().is_success() {
+        return Err(anyhow!("Approval endpoint {} returned HTTP {}", url, response.status()));
+    }
+
+    let text = response.text()
+        .context(format!("Failed to read response from {}", url))?;
+    serde_json::from_str(&text)
+        .context(format!("Invalid response from approval endpoint {}", url))
+}
@@ -0,0 +1,188 @@
+//! Script di bootstrap per nuove macchine
+//!
+//! Questo modulo genera uno snippet autonomo (script shell o user-data cloud-init) che,
+//! al primo avvio di una VM, installa Galatea, scrive la configurazione con un ruolo
+//! composto dagli stack richiesti ed esegue `galatea apply`.
+
+use std::collections::BTreeMap;
+use anyhow::{Context, Result, anyhow};
+use serde_yaml::{Mapping, Value};
+
+use crate::config::Config;
+use crate::stack::load_stacks;
+use crate::task::load_tasks;
+
+/// URL predefinito dell'archivio di Galatea; `{arch}` viene sostituito con `uname -m`
+pub const DEFAULT_RELEASE_URL: &str =
+    "https://github.com/matteokutufa/galatea/releases/latest/download/galatea-linux-{arch}.tar.gz";
+
+/// Ruolo assegnato alla macchina dalla configurazione di bootstrap
+pub const BOOTSTRAP_ROLE: &str = "bootstrap";
+
+/// Percorso della configurazione scritta sulla nuova macchina
+pub const REMOTE_CONFIG_PATH: &str = "/etc/galatea/galatea.yaml";
+
+/// Directory dei dati di Galatea sulla nuova macchina
+pub const REMOTE_DATA_DIR: &str = "/var/lib/galatea";
+
+/// Percorso dell'eseguibile installato sulla nuova macchina
+pub const REMOTE_EXECUTABLE: &str = "/usr/local/bin/galatea";
+
+/// Formato dello snippet di bootstrap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootstrapFormat {
+    /// Script shell POSIX, da usare come user-data o con Terraform
+    Shell,
+
+    /// Documento `#cloud-config` per cloud-init
+    CloudInit,
+}
+
+impl BootstrapFormat {
+    /// Converte una stringa nel formato corrispondente
+    pub fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "shell" | "sh" => Ok(BootstrapFormat::Shell),
+            "cloud-init" | "cloudinit" => Ok(BootstrapFormat::CloudInit),
+            _ => Err(anyhow!("Invalid bootstrap format: {}", s)),
+        }
+    }
+}
+
+/// Opzioni per la generazione dello snippet di bootstrap
+#[derive(Debug, Clone)]
+pub struct BootstrapOptions {
+    /// Stack da applicare al primo avvio
+    pub stacks: Vec<String>,
+
+    /// Formato dello snippet
+    pub format: BootstrapFormat,
+
+    /// URL dell'archivio di Galatea da installare
+    pub release_url: String,
+
+    /// Installa anche il servizio systemd della modalità demone
+    pub install_service: bool,
+}
+
+/// Genera lo snippet di bootstrap
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente, da cui vengono riprese le sorgenti del catalogo
+/// * `options` - Le opzioni dello snippet
+///
+/// # Returns
+///
+/// Il contenuto dello script o del documento cloud-init
+pub fn bootstrap_script(config: &Config, options: &BootstrapOptions) -> Result<String> {
+    if options.stacks.is_empty() {
+        return Err(anyhow!("At least one stack is required for the bootstrap script"));
+    }
+
+    let config_yaml = bootstrap_config(config, &options.stacks)?;
+    let script = shell_script(&config_yaml, options);
+
+    match options.format {
+        BootstrapFormat::Shell => Ok(script),
+        BootstrapFormat::CloudInit => cloud_init(&script),
+    }
+}
+
+/// Costruisce la configurazione da scrivere sulla nuova macchina
+///
+/// Le sorgenti e le opzioni restano quelle della configurazione corrente; le directory
+/// vengono spostate in /var/lib/galatea e il ruolo `bootstrap` raccoglie gli stack richiesti.
+pub fn bootstrap_config(config: &Config, stack_refs: &[String]) -> Result<String> {
+    // La configurazione SOPS è stata decifrata in memoria: serializzarla ne scriverebbe i
+    // segreti in chiaro nello script o nell'immagine
+    if config.sops_encrypted {
+        return Err(anyhow!("The configuration file is encrypted with SOPS: its secrets would be written in clear text to the bootstrap configuration"));
+    }
+
+    // Gli stack devono esistere nel catalogo, altrimenti il primo avvio fallirebbe
+    let tasks = load_tasks(config)?;
+    let stacks = load_stacks(config, &tasks)?;
+    let names = stack_refs.iter()
+        .map(|reference| stacks.iter()
+            .find(|s| s.matches_ref(reference))
+            .map(|s| s.name.clone())
+            .ok_or_else(|| anyhow!("Stack not found: {}", reference)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut remote = config.clone();
+    remote.tasks_dir = format!("{}/tasks", REMOTE_DATA_DIR);
+    remote.stacks_dir = format!("{}/stacks", REMOTE_DATA_DIR);
+    remote.state_dir = format!("{}/state", REMOTE_DATA_DIR);
+    remote.role = Some(BOOTSTRAP_ROLE.to_string());
+    remote.roles = BTreeMap::from([(BOOTSTRAP_ROLE.to_string(), names)]);
+
+    serde_yaml::to_string(&remote)
+        .context("Failed to serialize bootstrap configuration")
+}
+
+/// Genera lo script shell di bootstrap
+fn shell_script(config_yaml: &str, options: &BootstrapOptions) -> String {
+    let mut script = String::new();
+    script.push_str("#!/bin/sh\n");
+    script.push_str(&format!("# Bootstrap generato da galatea: applica {} al primo avvio\n", options.stacks.join(", ")));
+    script.push_str("set -eu\n\n");
+
+    script.push_str(&format!("GALATEA_URL=\"{}\"\n", options.release_url.replace('"', "\\\"")));
+    script.push_str(&format!("GALATEA_CONFIG=\"{}\"\n\n", REMOTE_CONFIG_PATH));
+
+    script.push_str("# Configurazione con il ruolo da applicare\n");
+    script.push_str("mkdir -p \"$(dirname \"$GALATEA_CONFIG\")\"\n");
+    script.push_str("umask 077\n");
+    script.push_str("cat > \"$GALATEA_CONFIG\" <<'GALATEA_EOF'\n");
+    script.push_str(config_yaml);
+    if !config_yaml.ends_with('\n') {
+        script.push('\n');
+    }
+    script.push_str("GALATEA_EOF\n");
+    script.push_str("umask 022\n\n");
+
+    script.push_str("# Installazione di Galatea, se non è già presente\n");
+    script.push_str(&format!("if [ ! -x {} ]; then\n", REMOTE_EXECUTABLE));
+    script.push_str("    url=$(printf '%s' \"$GALATEA_URL\" | sed \"s/{arch}/$(uname -m)/g\")\n");
+    script.push_str("    tmp=$(mktemp -d)\n");
+    script.push_str("    if command -v curl >/dev/null 2>&1; then\n");
+    script.push_str("        curl -fsSL --retry 5 -o \"$tmp/galatea.tar.gz\" \"$url\"\n");
+    script.push_str("    else\n");
+    script.push_str("        wget -q -O \"$tmp/galatea.tar.gz\" \"$url\"\n");
+    script.push_str("    fi\n");
+    script.push_str("    tar -xzf \"$tmp/galatea.tar.gz\" -C \"$tmp\"\n");
+    script.push_str(&format!("    install -m 0755 \"$tmp/galatea\" {}\n", REMOTE_EXECUTABLE));
+    script.push_str("    rm -rf \"$tmp\"\n");
+    script.push_str("fi\n\n");
+
+    script.push_str("# Applicazione degli stack del ruolo\n");
+    script.push_str(&format!("{} --config \"$GALATEA_CONFIG\" apply --yes\n", REMOTE_EXECUTABLE));
+
+    if options.install_service {
+        script.push_str(&format!("{} --config \"$GALATEA_CONFIG\" install-service\n", REMOTE_EXECUTABLE));
+    }
+
+    script
+}
+
+/// Incapsula lo script in un documento cloud-init
+fn cloud_init(script: &str) -> Result<String> {
+    let script_path = format!("{}/bootstrap.sh", REMOTE_DATA_DIR);
+
+    let mut file = Mapping::new();
+    file.insert("path".into(), script_path.as_str().into());
+    file.insert("permissions".into(), "0700".into());
+    file.insert("owner".into(), "root:root".into());
+    file.insert("content".into(), script.into());
+
+    let mut document = Mapping::new();
+    document.insert("write_files".into(), Value::Sequence(vec![Value::Mapping(file)]));
+    document.insert("runcmd".into(), Value::Sequence(vec![
+        Value::Sequence(vec!["sh".into(), script_path.as_str().into()]),
+    ]));
+
+    let yaml = serde_yaml::to_string(&Value::Mapping(document))
+        .context("Failed to serialize cloud-init document")?;
+    Ok(format!("#cloud-config\n{}", yaml))
+}
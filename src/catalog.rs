@@ -0,0 +1,150 @@
+//! Ricaricamento dei cataloghi
+//!
+//! L'interfaccia carica task e stack all'avvio. Per vedere i cataloghi aggiornati (sorgenti
+//! scaricate di nuovo, file `.conf` modificati) senza riavviarla, i cataloghi vengono letti
+//! di nuovo per intero e sostituiti a quelli in memoria in un solo passaggio: se la lettura
+//! fallisce restano quelli precedenti, altrimenti task e stack vengono sostituiti insieme.
+
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::stack::{self, Stack, load_stacks};
+use crate::task::{Task, load_tasks};
+
+/// Differenze tra due versioni di un catalogo
+#[derive(Debug, Clone, Default)]
+pub struct ItemChanges {
+    /// Elementi nuovi
+    pub added: Vec<String>,
+
+    /// Elementi non più presenti
+    pub removed: Vec<String>,
+
+    /// Elementi la cui definizione è cambiata
+    pub changed: Vec<String>,
+}
+
+impl ItemChanges {
+    /// Confronta due elenchi di elementi per nome qualificato
+    ///
+    /// Sono confrontate solo le definizioni lette dai cataloghi: lo stato di installazione
+    /// e gli esiti delle ultime operazioni non contano come modifiche.
+    fn between<T: Serialize>(old: &[T], new: &[T], name: impl Fn(&T) -> String) -> Self {
+        let definition = |item: &T| serde_json::to_value(item).ok();
+        let old_items: HashMap<String, &T> = old.iter().map(|item| (name(item), item)).collect();
+        let new_names: Vec<String> = new.iter().map(&name).collect();
+
+        let mut changes = ItemChanges::default();
+        for (item, item_name) in new.iter().zip(&new_names) {
+            match old_items.get(item_name) {
+                None => changes.added.push(item_name.clone()),
+                Some(old_item) if definition(old_item) != definition(item) => changes.changed.push(item_name.clone()),
+                Some(_) => {},
+            }
+        }
+        changes.removed = old.iter()
+            .map(&name)
+            .filter(|item_name| !new_names.contains(item_name))
+            .collect();
+        changes
+    }
+
+    /// Indica se non ci sono differenze
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl Display for ItemChanges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "+{} -{} ~{}", self.added.len(), self.removed.len(), self.changed.len())
+    }
+}
+
+/// Risultato del ricaricamento dei cataloghi
+#[derive(Debug, Clone, Default)]
+pub struct CatalogChanges {
+    /// Differenze nel catalogo dei task
+    pub tasks: ItemChanges,
+
+    /// Differenze nel catalogo degli stack
+    pub stacks: ItemChanges,
+}
+
+impl CatalogChanges {
+    /// Indica se i cataloghi sono rimasti invariati
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty() && self.stacks.is_empty()
+    }
+
+    /// Restituisce il riepilogo delle differenze, un elemento per riga
+    pub fn details(&self) -> String {
+        let mut lines = Vec::new();
+        for (kind, changes) in [("Task", &self.tasks), ("Stack", &self.stacks)] {
+            lines.extend(changes.added.iter().map(|name| format!("+ {} {}", kind, name)));
+            lines.extend(changes.removed.iter().map(|name| format!("- {} {}", kind, name)));
+            lines.extend(changes.changed.iter().map(|name| format!("~ {} {}", kind, name)));
+        }
+        lines.join("\n")
+    }
+}
+
+impl Display for CatalogChanges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            write!(f, "Cataloghi invariati")
+        } else {
+            write!(f, "Cataloghi aggiornati: task {}, stack {}", self.tasks, self.stacks)
+        }
+    }
+}
+
+/// Legge di nuovo i cataloghi, scaricando le sorgenti configurate
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+///
+/// # Returns
+///
+/// I task e gli stack letti, con i task bloccati da uno stack prerequisito già segnati
+pub fn load(config: &Config) -> Result<(Vec<Task>, Vec<Stack>)> {
+    let mut tasks = load_tasks(config)?;
+    let stacks = load_stacks(config, &tasks)?;
+    stack::mark_blocked_tasks(&mut tasks, &stacks);
+    Ok((tasks, stacks))
+}
+
+/// Sostituisce i cataloghi in memoria con quelli letti da [`load`]
+///
+/// I risultati dell'ultima operazione sugli stack rimasti nel catalogo vengono conservati.
+///
+/// # Arguments
+///
+/// * `tasks` - I task in memoria
+/// * `stacks` - Gli stack in memoria
+/// * `new_tasks` - I task letti
+/// * `new_stacks` - Gli stack letti
+///
+/// # Returns
+///
+/// Le differenze rispetto ai cataloghi precedenti
+pub fn replace(tasks: &mut Vec<Task>, stacks: &mut Vec<Stack>, new_tasks: Vec<Task>, mut new_stacks: Vec<Stack>) -> CatalogChanges {
+    let changes = CatalogChanges {
+        tasks: ItemChanges::between(tasks, &new_tasks, Task::qualified_name),
+        stacks: ItemChanges::between(stacks, &new_stacks, Stack::qualified_name),
+    };
+
+    for stack in new_stacks.iter_mut() {
+        if let Some(old) = stacks.iter_mut().find(|old| old.qualified_name() == stack.qualified_name()) {
+            stack.last_report = old.last_report.take();
+        }
+    }
+
+    *tasks = new_tasks;
+    *stacks = new_stacks;
+    changes
+}
@@ -0,0 +1,464 @@
+//! Schema JSON dei cataloghi
+//!
+//! I file `.conf` dei task e degli stack sono descritti da uno JSON Schema, pubblicato con
+//! `galatea schema` per l'autocompletamento negli editor e la verifica dei cataloghi in CI.
+//! Lo stesso schema viene usato al caricamento: le opzioni sconosciute (spesso un errore di
+//! battitura) e i valori del tipo sbagliato, che altrimenti verrebbero ignorati in silenzio,
+//! sono segnalati nel log senza impedire il caricamento del catalogo.
+
+use std::fmt::{self, Display};
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result, anyhow};
+use regex::Regex;
+use serde_json::{Value, json};
+
+use crate::config::Config;
+use crate::secrets;
+use crate::source::{self, Source};
+
+/// Indirizzo della specifica dello schema
+const SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Tipo di catalogo descritto dallo schema
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CatalogKind {
+    /// Catalogo dei task
+    Tasks,
+    /// Catalogo degli stack
+    Stacks,
+}
+
+impl CatalogKind {
+    /// Converte una stringa nel tipo di catalogo corrispondente
+    pub fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "tasks" | "task" => Ok(CatalogKind::Tasks),
+            "stacks" | "stack" => Ok(CatalogKind::Stacks),
+            _ => Err(anyhow!("Unknown catalog kind: {} (expected tasks or stacks)", s)),
+        }
+    }
+
+    /// Converte il tipo di catalogo in una stringa, che è anche la chiave radice dei file
+    pub fn to_str(self) -> &'static str {
+        match self {
+            CatalogKind::Tasks => "tasks",
+            CatalogKind::Stacks => "stacks",
+        }
+    }
+
+    /// Restituisce le sorgenti e la directory principale del catalogo
+    fn locations<'a>(&self, config: &'a Config) -> (&'a [Source], &'a str) {
+        match self {
+            CatalogKind::Tasks => (&config.task_sources, &config.tasks_dir),
+            CatalogKind::Stacks => (&config.stack_sources, &config.stacks_dir),
+        }
+    }
+}
+
+/// Scostamento di un documento dallo schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaIssue {
+    /// Posizione del valore nel documento, es. `tasks[2].retries`
+    pub path: String,
+
+    /// Descrizione del problema
+    pub message: String,
+}
+
+impl Display for SchemaIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+/// Restituisce lo schema di un file `.conf` del catalogo
+///
+/// # Arguments
+///
+/// * `kind` - Il tipo di catalogo
+///
+/// # Returns
+///
+/// Lo JSON Schema (draft 2020-12) del file
+pub fn schema(kind: CatalogKind) -> Value {
+    let (title, item) = match kind {
+        CatalogKind::Tasks => ("Catalogo dei task di Galatea", task_schema()),
+        CatalogKind::Stacks => ("Catalogo degli stack di Galatea", stack_schema()),
+    };
+
+    json!({
+        "$schema": SCHEMA_DIALECT,
+        "title": title,
+        "type": "object",
+        "required": [kind.to_str()],
+        "properties": {
+            kind.to_str(): {
+                "type": "array",
+                "items": item,
+            },
+        },
+    })
+}
+
+/// Lista di stringhe
+fn string_list(description: &str) -> Value {
+    json!({ "type": "array", "items": { "type": "string" }, "description": description })
+}
+
+/// Schema di un task del catalogo
+fn task_schema() -> Value {
+    json!({
+        "type": "object",
+        "required": ["name", "type", "url"],
+        "additionalProperties": false,
+        "properties": {
+            "name": { "type": "string", "description": "Nome del task" },
+            "type": {
+                "enum": ["bash", "ansible", "mixed", "wasm", "b", "a", "m", "w"],
+                "description": "Tipo di script del bundle",
+            },
+            "description": { "type": "string", "description": "Descrizione mostrata nell'interfaccia" },
+            "url": { "type": "string", "description": "URL o percorso del bundle" },
+            "sha256": {
+                "type": "string",
+                "pattern": "^[0-9a-fA-F]{64}$",
+                "description": "Checksum SHA-256 atteso del bundle",
+            },
+            "size": { "type": "integer", "minimum": 0, "description": "Dimensione attesa del bundle in byte" },
+            "cleanup_command": { "type": "string", "description": "Comando eseguito dopo la disinstallazione" },
+            "dependencies": string_list("Task da installare prima di questo"),
+            "requires_stack": { "type": "string", "description": "Stack che deve essere installato prima del task" },
+            "tags": string_list("Tag del task"),
+            "locks": string_list("Risorse usate in modo esclusivo, es. apt o docker"),
+            "requires_reboot": { "type": "boolean", "description": "Il task richiede un riavvio dopo l'installazione" },
+            "detect": {
+                "type": "object",
+                "additionalProperties": false,
+                "description": "Evidenze che il task è già applicato, per import-state",
+                "properties": {
+                    "packages": string_list("Pacchetti che devono risultare installati"),
+                    "services": string_list("Servizi systemd che devono risultare attivi o abilitati"),
+                    "files": string_list("File o directory che devono esistere"),
+                    "command": { "type": "string", "description": "Comando che deve terminare con successo" },
+                },
+            },
+            "sudo": { "type": "boolean", "description": "Esegue script e comandi tramite sudo quando Galatea non è root" },
+            "interactive": { "type": "boolean", "description": "Esegue lo script bash in un terminale collegato all'operatore" },
+            "runner": { "type": "string", "description": "Modulo WebAssembly dei task wasm (predefinito: runner.wasm nel bundle)" },
+            "when": { "type": "string", "description": "Condizione rhai: se è falsa il task viene saltato" },
+            "deprecated": { "type": "boolean", "description": "Il task è deprecato" },
+            "replaced_by": { "type": "string", "description": "Task che sostituisce quello deprecato" },
+            "version": { "type": ["string", "number"], "description": "Versione del task" },
+            "versions": {
+                "type": "array",
+                "description": "Versioni pubblicate del task",
+                "items": {
+                    "type": "object",
+                    "required": ["version", "url"],
+                    "additionalProperties": false,
+                    "properties": {
+                        "version": { "type": "string", "description": "Numero di versione" },
+                        "url": { "type": "string", "description": "URL del bundle di questa versione" },
+                        "channel": { "enum": ["stable", "testing"], "description": "Canale di pubblicazione" },
+                        "sha256": { "type": "string", "pattern": "^[0-9a-fA-F]{64}$" },
+                        "size": { "type": "integer", "minimum": 0 },
+                    },
+                },
+            },
+            "progress_pattern": { "type": "string", "description": "Espressione regolare dei marcatori di avanzamento" },
+            "retries": { "type": "integer", "minimum": 0, "description": "Tentativi aggiuntivi in caso di fallimento" },
+            "retry_delay": { "type": "integer", "minimum": 0, "description": "Attesa tra due tentativi, in secondi" },
+            "min_interval": { "type": "integer", "minimum": 0, "description": "Intervallo minimo tra remediate e verifica, in secondi" },
+            "resources": {
+                "type": "object",
+                "additionalProperties": false,
+                "description": "Limiti di risorse dell'esecuzione",
+                "properties": {
+                    "nice": { "type": "integer", "minimum": -20, "maximum": 19 },
+                    "io_class": { "enum": ["realtime", "best-effort", "idle"] },
+                    "io_priority": { "type": "integer", "minimum": 0, "maximum": 7 },
+                    "memory_max": { "type": "string", "description": "Limite di memoria (es. 512M)" },
+                    "cpu_quota": { "type": "string", "description": "Quota di CPU (es. 50%)" },
+                    "timeout": { "type": "integer", "minimum": 1, "description": "Durata massima dell'esecuzione, in secondi" },
+                },
+            },
+            "healthcheck": {
+                "description": "Controllo di salute dopo l'installazione, come comando o come mappa",
+                "anyOf": [
+                    { "type": "string" },
+                    {
+                        "type": "object",
+                        "required": ["command"],
+                        "additionalProperties": false,
+                        "properties": {
+                            "command": { "type": "string" },
+                            "timeout": { "type": "integer", "minimum": 0 },
+                            "rollback": { "type": "boolean" },
+                        },
+                    },
+                ],
+            },
+        },
+    })
+}
+
+/// Schema di uno stack del catalogo
+fn stack_schema() -> Value {
+    json!({
+        "type": "object",
+        "required": ["name"],
+        "additionalProperties": false,
+        "properties": {
+            "name": { "type": "string", "description": "Nome dello stack" },
+            "description": { "type": "string", "description": "Descrizione mostrata nell'interfaccia" },
+            "tasks": string_list("Task dello stack, come nome o sorgente:nome"),
+            "requires_reboot": { "type": "boolean", "description": "Lo stack richiede un riavvio dopo l'installazione" },
+            "tags": string_list("Tag dello stack"),
+            "snapshot": { "type": "boolean", "description": "Crea uno snapshot prima dell'installazione" },
+            "variables": {
+                "type": "array",
+                "description": "Variabili richieste all'installazione",
+                "items": {
+                    "type": "object",
+                    "required": ["name"],
+                    "additionalProperties": false,
+                    "properties": {
+                        "name": { "type": "string" },
+                        "prompt": { "type": "string" },
+                        "default": { "type": "string" },
+                        "secret": { "type": "boolean" },
+                    },
+                },
+            },
+            "task_variables": {
+                "type": "object",
+                "description": "Variabili passate ai singoli task",
+                "additionalProperties": {
+                    "type": "object",
+                    "additionalProperties": { "type": "string" },
+                },
+            },
+            "maintenance": { "type": "boolean", "description": "Togliere la macchina dal bilanciatore durante l'installazione" },
+            "pre_install": {
+                "description": "Comandi eseguiti una volta prima di installare i task",
+                "anyOf": [
+                    { "type": "string" },
+                    { "type": "array", "items": { "type": "string" } },
+                ],
+            },
+            "post_install": {
+                "description": "Comandi eseguiti una volta dopo l'installazione dei task, anche se è fallita",
+                "anyOf": [
+                    { "type": "string" },
+                    { "type": "array", "items": { "type": "string" } },
+                ],
+            },
+            "derived": {
+                "type": "object",
+                "description": "Variabili calcolate con espressioni rhai",
+                "additionalProperties": { "type": "string" },
+            },
+            "channel": { "enum": ["stable", "testing"], "description": "Canale di rilascio dei task" },
+            "pins": {
+                "type": "object",
+                "description": "Versioni fissate dei task",
+                "additionalProperties": { "type": ["string", "number"] },
+            },
+            "canary": {
+                "description": "Task installati per primi, come singolo riferimento o lista",
+                "anyOf": [
+                    { "type": "string" },
+                    { "type": "array", "items": { "type": "string" } },
+                ],
+            },
+            "parallel": { "type": "integer", "minimum": 1, "description": "Task installati contemporaneamente" },
+            "auto_remediate": { "type": "string", "description": "Pianificazione del ripristino (hourly, daily o cron)" },
+            "extends": { "type": "string", "description": "Stack base da cui ereditare" },
+            "add_tasks": string_list("Task aggiunti a quelli ereditati"),
+            "remove_tasks": string_list("Task tolti da quelli ereditati"),
+        },
+    })
+}
+
+/// Verifica un documento del catalogo rispetto allo schema
+///
+/// # Arguments
+///
+/// * `kind` - Il tipo di catalogo
+/// * `document` - Il documento letto dal file `.conf`
+///
+/// # Returns
+///
+/// Gli scostamenti dallo schema; vuoto se il documento è valido
+pub fn validate(kind: CatalogKind, document: &serde_yaml::Value) -> Vec<SchemaIssue> {
+    let instance = match serde_json::to_value(document) {
+        Ok(instance) => instance,
+        Err(e) => return vec![SchemaIssue { path: String::new(), message: format!("not representable as JSON: {}", e) }],
+    };
+
+    let mut issues = Vec::new();
+    check(&schema(kind), &instance, "", &mut issues);
+    issues
+}
+
+/// Verifica un valore rispetto a uno schema, accumulando gli scostamenti
+///
+/// Sono supportate solo le parole chiave usate dagli schemi dei cataloghi.
+fn check(schema: &Value, instance: &Value, path: &str, issues: &mut Vec<SchemaIssue>) {
+    let mut report = |message: String| issues.push(SchemaIssue { path: path.to_string(), message });
+
+    if let Some(options) = schema.get("anyOf").and_then(Value::as_array) {
+        let matched = options.iter().any(|option| {
+            let mut nested = Vec::new();
+            check(option, instance, path, &mut nested);
+            nested.is_empty()
+        });
+        if !matched {
+            let expected: Vec<String> = options.iter().filter_map(|option| option.get("type")).map(type_names).collect();
+            report(format!("expected {}, found {}", expected.join(" or "), type_of(instance)));
+        }
+        return;
+    }
+
+    if let Some(expected) = schema.get("type") {
+        if !matches_type(expected, instance) {
+            report(format!("expected {}, found {}", type_names(expected), type_of(instance)));
+            return;
+        }
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(instance) {
+            let allowed: Vec<String> = allowed.iter().map(Value::to_string).collect();
+            report(format!("invalid value {}, expected one of {}", instance, allowed.join(", ")));
+            return;
+        }
+    }
+
+    if let Some(number) = instance.as_f64() {
+        if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64).filter(|minimum| number < *minimum) {
+            report(format!("{} is less than the minimum {}", instance, minimum));
+        }
+        if let Some(maximum) = schema.get("maximum").and_then(Value::as_f64).filter(|maximum| number > *maximum) {
+            report(format!("{} is greater than the maximum {}", instance, maximum));
+        }
+    }
+
+    if let (Some(text), Some(pattern)) = (instance.as_str(), schema.get("pattern").and_then(Value::as_str)) {
+        if Regex::new(pattern).is_ok_and(|regex| !regex.is_match(text)) {
+            report(format!("{:?} does not match {}", text, pattern));
+        }
+    }
+
+    match instance {
+        Value::Object(fields) => {
+            for required in schema.get("required").and_then(Value::as_array).into_iter().flatten() {
+                if let Some(field) = required.as_str().filter(|field| !fields.contains_key(*field)) {
+                    report(format!("missing required field '{}'", field));
+                }
+            }
+
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (field, value) in fields {
+                let field_path = if path.is_empty() { field.clone() } else { format!("{}.{}", path, field) };
+                match (properties.and_then(|properties| properties.get(field)), schema.get("additionalProperties")) {
+                    (Some(field_schema), _) => check(field_schema, value, &field_path, issues),
+                    (None, Some(Value::Bool(false))) => issues.push(SchemaIssue {
+                        path: path.to_string(),
+                        message: format!("unknown field '{}'", field),
+                    }),
+                    (None, Some(extra_schema @ Value::Object(_))) => check(extra_schema, value, &field_path, issues),
+                    (None, _) => {},
+                }
+            }
+        },
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (idx, item) in items.iter().enumerate() {
+                    check(item_schema, item, &format!("{}[{}]", path, idx), issues);
+                }
+            }
+        },
+        _ => {},
+    }
+}
+
+/// Verifica se un valore è di uno dei tipi indicati dallo schema
+fn matches_type(expected: &Value, instance: &Value) -> bool {
+    let matches = |name: &str| match name {
+        "integer" => instance.is_i64() || instance.is_u64(),
+        "number" => instance.is_number(),
+        other => type_of(instance) == other,
+    };
+
+    match expected {
+        Value::String(name) => matches(name),
+        Value::Array(names) => names.iter().filter_map(Value::as_str).any(matches),
+        _ => true,
+    }
+}
+
+/// Restituisce i tipi indicati dallo schema in forma leggibile
+fn type_names(expected: &Value) -> String {
+    match expected {
+        Value::Array(names) => names.iter().filter_map(Value::as_str).collect::<Vec<_>>().join(" or "),
+        Value::String(name) => name.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Restituisce il tipo JSON di un valore
+fn type_of(instance: &Value) -> &'static str {
+    match instance {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Verifica tutti i file `.conf` dei cataloghi di task e stack
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+///
+/// # Returns
+///
+/// Gli scostamenti dallo schema, con il file in cui sono stati trovati
+pub fn check_catalogs(config: &Config) -> Result<Vec<(PathBuf, SchemaIssue)>> {
+    let mut issues = Vec::new();
+
+    for kind in [CatalogKind::Tasks, CatalogKind::Stacks] {
+        let (sources, base_dir) = kind.locations(config);
+        for (dir, _) in source::catalog_dirs(sources, Path::new(base_dir)) {
+            if !dir.is_dir() {
+                continue;
+            }
+
+            for entry in fs::read_dir(&dir).context(format!("Failed to read catalog directory: {:?}", dir))? {
+                let path = entry.context("Failed to read directory entry")?.path();
+                if !path.is_file() || path.extension().is_none_or(|ext| ext != "conf") {
+                    continue;
+                }
+
+                let content = fs::read_to_string(&path)
+                    .context(format!("Failed to read catalog file: {:?}", path))?;
+                let document: serde_yaml::Value = serde_yaml::from_str(&content)
+                    .context(format!("Failed to parse YAML from: {:?}", path))?;
+                let document = secrets::open_document(&path, document, config.age_identity.as_deref())?;
+
+                issues.extend(validate(kind, &document).into_iter().map(|issue| (path.clone(), issue)));
+            }
+        }
+    }
+
+    Ok(issues)
+}
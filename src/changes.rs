@@ -0,0 +1,152 @@
+//! Riepilogo delle modifiche al sistema
+//!
+//! Prima e dopo lo script di installazione di un task Galatea fotografa i pacchetti
+//! installati, i servizi in esecuzione e le porte in ascolto (con le funzioni di
+//! [`utils`]); la differenza tra le due fotografie dice all'operatore cosa è cambiato
+//! davvero sulla macchina. Il riepilogo viene mostrato nell'output del job e al termine di
+//! `galatea apply`, e conservato nella directory di stato per la vista del task.
+//!
+//! Le fotografie riguardano l'intera macchina: con gli stack installati in parallelo le
+//! modifiche di task contemporanei possono comparire nel riepilogo di entrambi.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
+use chrono::Local;
+use lazy_static::lazy_static;
+use serde::{Serialize, Deserialize};
+
+use crate::utils;
+
+/// Riepiloghi conservati al più per il comando corrente, es. in un demone che non li mostra
+const MAX_RECORDED: usize = 100;
+
+lazy_static! {
+    /// Riepiloghi non ancora mostrati al termine del comando, per task
+    static ref RECORDED: Mutex<Vec<(String, ChangeSummary)>> = Mutex::new(Vec::new());
+}
+
+/// Stato della macchina in un dato momento
+#[derive(Debug, Clone, Default)]
+pub struct SystemSnapshot {
+    /// Pacchetti installati con la loro versione
+    pub packages: BTreeMap<String, String>,
+
+    /// Servizi systemd in esecuzione
+    pub services: BTreeSet<String>,
+
+    /// Porte in ascolto, es. `tcp 0.0.0.0:22`
+    pub ports: BTreeSet<String>,
+}
+
+impl SystemSnapshot {
+    /// Fotografa lo stato corrente della macchina
+    pub fn capture() -> Self {
+        SystemSnapshot {
+            packages: utils::get_installed_packages(),
+            services: utils::get_running_services(),
+            ports: utils::get_listening_ports(),
+        }
+    }
+
+    /// Confronta questa fotografia con una successiva
+    ///
+    /// # Arguments
+    ///
+    /// * `after` - La fotografia scattata dopo l'operazione
+    ///
+    /// # Returns
+    ///
+    /// Le modifiche tra le due fotografie
+    pub fn diff(&self, after: &SystemSnapshot) -> ChangeSummary {
+        let mut summary = ChangeSummary {
+            captured_at: Local::now().to_rfc3339(),
+            ..ChangeSummary::default()
+        };
+
+        for (name, version) in &after.packages {
+            match self.packages.get(name) {
+                None => summary.packages_added.push(format!("{} {}", name, version)),
+                Some(old) if old != version => summary.packages_changed.push(format!("{} {} -> {}", name, old, version)),
+                Some(_) => {},
+            }
+        }
+        summary.packages_removed = self.packages.iter()
+            .filter(|(name, _)| !after.packages.contains_key(*name))
+            .map(|(name, version)| format!("{} {}", name, version))
+            .collect();
+
+        summary.services_started = after.services.difference(&self.services).cloned().collect();
+        summary.services_stopped = self.services.difference(&after.services).cloned().collect();
+        summary.ports_opened = after.ports.difference(&self.ports).cloned().collect();
+        summary.ports_closed = self.ports.difference(&after.ports).cloned().collect();
+        summary
+    }
+}
+
+/// Modifiche al sistema rilevate durante un'operazione
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChangeSummary {
+    /// Momento della seconda fotografia (RFC 3339)
+    pub captured_at: String,
+
+    /// Pacchetti installati, con la versione
+    pub packages_added: Vec<String>,
+
+    /// Pacchetti rimossi, con la versione che avevano
+    pub packages_removed: Vec<String>,
+
+    /// Pacchetti aggiornati o riportati a una versione precedente
+    pub packages_changed: Vec<String>,
+
+    /// Servizi avviati
+    pub services_started: Vec<String>,
+
+    /// Servizi fermati
+    pub services_stopped: Vec<String>,
+
+    /// Porte aperte
+    pub ports_opened: Vec<String>,
+
+    /// Porte chiuse
+    pub ports_closed: Vec<String>,
+}
+
+impl ChangeSummary {
+    /// Verifica se non è stata rilevata alcuna modifica
+    pub fn is_empty(&self) -> bool {
+        self.lines().is_empty()
+    }
+
+    /// Restituisce il riepilogo, una riga per tipo di modifica
+    pub fn lines(&self) -> Vec<String> {
+        [
+            ("Pacchetti installati", &self.packages_added),
+            ("Pacchetti rimossi", &self.packages_removed),
+            ("Pacchetti aggiornati", &self.packages_changed),
+            ("Servizi avviati", &self.services_started),
+            ("Servizi fermati", &self.services_stopped),
+            ("Porte aperte", &self.ports_opened),
+            ("Porte chiuse", &self.ports_closed),
+        ]
+        .into_iter()
+        .filter(|(_, items)| !items.is_empty())
+        .map(|(label, items)| format!("{}: {}", label, items.join(", ")))
+        .collect()
+    }
+}
+
+/// Conserva il riepilogo di un task, da mostrare al termine del comando
+pub fn record(task: &str, summary: &ChangeSummary) {
+    if let Ok(mut recorded) = RECORDED.lock() {
+        if recorded.len() >= MAX_RECORDED {
+            recorded.remove(0);
+        }
+        recorded.push((task.to_string(), summary.clone()));
+    }
+}
+
+/// Restituisce e dimentica i riepiloghi conservati con [`record`]
+pub fn take_recorded() -> Vec<(String, ChangeSummary)> {
+    RECORDED.lock().map(|mut recorded| std::mem::take(&mut *recorded)).unwrap_or_default()
+}
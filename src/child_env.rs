@@ -0,0 +1,124 @@
+//! Ambiente dei processi avviati da Galatea
+//!
+//! Script, playbook, comandi di pulizia e controlli di salute provengono da bundle di
+//! terze parti: per non esporre loro i segreti dell'operatore (token, chiavi cloud,
+//! agent SSH) vengono avviati con un ambiente pulito, che contiene solo le variabili di
+//! [`DEFAULT_ALLOW`] e quelle aggiunte in `child_env.allow`. Le variabili in
+//! `child_env.deny` non vengono mai passate. Le variabili impostate da Galatea stessa
+//! (`GALATEA_*`, variabili del task, `ansible_env`) sono sempre presenti.
+//!
+//! ```yaml
+//! child_env:
+//!   allow: [ANSIBLE_*, SSH_AUTH_SOCK]
+//!   deny: [AWS_*]
+//! ```
+
+use std::env;
+use std::process::Command;
+use log::debug;
+use serde::{Serialize, Deserialize};
+
+/// Variabili passate ai processi se non escluse da `child_env.deny`
+pub const DEFAULT_ALLOW: &[&str] = &[
+    "PATH", "HOME", "USER", "LOGNAME", "SHELL", "TERM", "TZ", "TMPDIR",
+    "LANG", "LANGUAGE", "LC_*",
+    "http_proxy", "https_proxy", "no_proxy", "HTTP_PROXY", "HTTPS_PROXY", "NO_PROXY",
+    // Necessarie a systemd-run --user per i limiti di risorse da utente normale
+    "XDG_RUNTIME_DIR", "DBUS_SESSION_BUS_ADDRESS",
+];
+
+/// Variabili d'ambiente ereditate dai processi avviati
+///
+/// Una regola è il nome di una variabile oppure un prefisso seguito da `*` (es. `LC_*`);
+/// `*` da solo in `allow` ripristina l'ereditarietà dell'intero ambiente.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnvPolicy {
+    /// Variabili passate in aggiunta a quelle predefinite
+    pub allow: Vec<String>,
+
+    /// Variabili mai passate, anche se consentite
+    pub deny: Vec<String>,
+}
+
+impl EnvPolicy {
+    /// Verifica se una variabile dell'ambiente di Galatea può essere passata ai processi
+    pub fn allows(&self, name: &str) -> bool {
+        if self.deny.iter().any(|rule| name_matches(rule, name)) {
+            return false;
+        }
+        DEFAULT_ALLOW.iter().any(|rule| name_matches(rule, name))
+            || self.allow.iter().any(|rule| name_matches(rule, name))
+    }
+
+    /// Sostituisce l'ambiente di un comando con quello consentito
+    ///
+    /// Va chiamata subito dopo aver creato il comando: le variabili impostate prima vengono
+    /// rimosse insieme all'ambiente ereditato.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - Il comando da avviare
+    pub fn apply(&self, command: &mut Command) {
+        command.env_clear();
+
+        let mut filtered = Vec::new();
+        for (name, value) in env::vars_os() {
+            let key = name.to_string_lossy();
+            if self.allows(&key) {
+                command.env(&name, value);
+            } else {
+                filtered.push(key.to_string());
+            }
+        }
+        if !filtered.is_empty() {
+            debug!("Environment variables not passed to {:?}: {}", command.get_program(), filtered.join(", "));
+        }
+    }
+}
+
+/// Verifica se il nome di una variabile corrisponde a una regola di `child_env`
+fn name_matches(rule: &str, name: &str) -> bool {
+    let rule = rule.trim();
+    match rule.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => name == rule,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(allow: &[&str], deny: &[&str]) -> EnvPolicy {
+        EnvPolicy {
+            allow: allow.iter().map(|rule| rule.to_string()).collect(),
+            deny: deny.iter().map(|rule| rule.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn default_policy_passes_only_default_variables() {
+        let env = EnvPolicy::default();
+        assert!(env.allows("PATH"));
+        assert!(env.allows("LC_ALL"));
+        assert!(!env.allows("AWS_SECRET_ACCESS_KEY"));
+        assert!(!env.allows("path"));
+    }
+
+    #[test]
+    fn allow_adds_names_and_prefixes() {
+        let env = policy(&["ANSIBLE_*", "SSH_AUTH_SOCK"], &[]);
+        assert!(env.allows("ANSIBLE_CONFIG"));
+        assert!(env.allows("SSH_AUTH_SOCK"));
+        assert!(!env.allows("SSH_AGENT_PID"));
+    }
+
+    #[test]
+    fn deny_wins_over_defaults_and_allow() {
+        let env = policy(&["*"], &["AWS_*", "HOME"]);
+        assert!(env.allows("ANYTHING"));
+        assert!(!env.allows("AWS_PROFILE"));
+        assert!(!env.allows("HOME"));
+    }
+}
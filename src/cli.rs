@@ -0,0 +1,1633 @@
+//! Sottocomandi da linea di comando per Galatea
+//!
+//! Questo modulo definisce i sottocomandi disponibili oltre all'interfaccia TUI
+//! e ne gestisce l'esecuzione in modalità non interattiva.
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Local};
+
+use crate::approval::{self, ApprovalStatus};
+use crate::bootstrap::{self, BootstrapFormat, BootstrapOptions};
+use crate::catalog;
+use crate::catalog_schema::{self, CatalogKind};
+use crate::changes;
+use crate::config::Config;
+use crate::container::{self, ContainerEngine, ImageOptions};
+use crate::drift;
+use crate::events::{self, Event, ProgressUpdate};
+use crate::export;
+use crate::fleet;
+use crate::importer;
+use crate::inventory;
+use crate::layout::{self, Layout};
+use crate::lint;
+use crate::logger;
+use crate::mirror::{self, MirrorOptions};
+use crate::plan::{self, Plan};
+use crate::plugins;
+use crate::preflight;
+use crate::purge::{self, PurgeOptions};
+use crate::reboot;
+use crate::replay::{self, Manifest, Step};
+use crate::role;
+use crate::rollout::{self, BatchSize, HostPreflight, HostStatus, PreflightPolicy, RolloutOptions};
+use crate::scaffold;
+use crate::scheduler;
+use crate::stack;
+use crate::state_crypt;
+use crate::systemd::{self, ServiceOptions};
+use crate::task::{ScriptType, load_tasks};
+
+/// Stampa un messaggio informativo, soppresso con `-q`
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if !logger::verbosity().is_quiet() {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Restituisce la definizione dei sottocomandi disponibili
+pub fn subcommands() -> Vec<Command> {
+    let mut commands = recordable_subcommands();
+    commands.push(Command::new("record")
+        .about("Esegue un sottocomando registrando comandi, ambiente e output in un bundle")
+        .arg(Arg::new("output")
+            .short('o')
+            .long("output")
+            .value_name("FILE")
+            .help("Bundle da creare (predefinito: galatea-replay-<data>.tar.gz nella directory corrente)"))
+        .subcommand_required(true)
+        .subcommands(recordable_subcommands()));
+    commands.push(Command::new("plugins")
+        .about("Elenca i plugin galatea-<nome> trovati nel PATH, eseguibili come sottocomandi"));
+    commands.push(Command::new("replay")
+        .about("Mostra passo per passo un'esecuzione registrata con 'galatea record'")
+        .arg(Arg::new("bundle")
+            .value_name("BUNDLE")
+            .required(true)
+            .help("Bundle creato con 'galatea record'"))
+        .arg(Arg::new("step")
+            .short('s')
+            .long("step")
+            .value_name("N")
+            .value_parser(clap::value_parser!(usize))
+            .help("Mostra i dettagli del passo indicato"))
+        .arg(Arg::new("interactive")
+            .short('i')
+            .long("interactive")
+            .action(ArgAction::SetTrue)
+            .conflicts_with("step")
+            .help("Mostra i dettagli dei passi uno alla volta, premendo Invio per proseguire"))
+        .arg(Arg::new("log")
+            .long("log")
+            .action(ArgAction::SetTrue)
+            .help("Mostra anche il log dell'esecuzione"))
+        .arg(Arg::new("key")
+            .long("key")
+            .value_name("FILE")
+            .help("Chiave dello stato con cui è stato cifrato il bundle (encrypt_state)")));
+    commands
+}
+
+/// Restituisce i sottocomandi che possono essere eseguiti e registrati con `record`
+fn recordable_subcommands() -> Vec<Command> {
+    vec![
+        Command::new("new-task")
+            .about("Genera lo scheletro di un nuovo bundle di task")
+            .arg(Arg::new("name")
+                .value_name("NAME")
+                .required(true)
+                .help("Nome del task da creare"))
+            .arg(Arg::new("type")
+                .short('t')
+                .long("type")
+                .value_name("TYPE")
+                .default_value("bash")
+                .help("Tipo di script del task (bash, ansible, mixed)"))
+            .arg(Arg::new("output")
+                .short('o')
+                .long("output")
+                .value_name("DIR")
+                .default_value(".")
+                .help("Directory in cui creare il bundle")),
+        Command::new("lint")
+            .about("Verifica un bundle di task e segnala i problemi trovati")
+            .arg(Arg::new("bundle")
+                .value_name("BUNDLE")
+                .required(true)
+                .help("Directory, archivio o script del bundle da verificare"))
+            .arg(Arg::new("type")
+                .short('t')
+                .long("type")
+                .value_name("TYPE")
+                .help("Tipo di script atteso (bash, ansible, mixed, wasm); dedotto se assente"))
+            .arg(Arg::new("strict")
+                .long("strict")
+                .action(ArgAction::SetTrue)
+                .help("Considera gli avvisi come errori")),
+        Command::new("validate")
+            .about("Verifica i cataloghi rispetto allo schema e segnala gli stack che fanno riferimento a task inesistenti"),
+        Command::new("schema")
+            .about("Stampa lo JSON Schema dei file .conf dei cataloghi, per editor e CI")
+            .arg(Arg::new("kind")
+                .value_name("KIND")
+                .required(true)
+                .value_parser(["tasks", "stacks"])
+                .help("Catalogo di cui stampare lo schema"))
+            .arg(Arg::new("output")
+                .short('o')
+                .long("output")
+                .value_name("FILE")
+                .help("File in cui scrivere lo schema invece dello standard output")),
+        Command::new("purge")
+            .about("Rimuove stato, bundle scaricati e log gestiti da Galatea")
+            .arg(Arg::new("uninstall")
+                .long("uninstall")
+                .action(ArgAction::SetTrue)
+                .help("Disinstalla i task installati prima della rimozione"))
+            .arg(Arg::new("keep-logs")
+                .long("keep-logs")
+                .action(ArgAction::SetTrue)
+                .help("Mantiene i file di log"))
+            .arg(Arg::new("force")
+                .long("force")
+                .action(ArgAction::SetTrue)
+                .help("Rimuove i dati anche se alcune disinstallazioni falliscono"))
+            .arg(Arg::new("yes")
+                .short('y')
+                .long("yes")
+                .action(ArgAction::SetTrue)
+                .help("Non chiede conferma")),
+        Command::new("import-state")
+            .about("Segna come installati i task già applicati sul sistema")
+            .arg(Arg::new("from")
+                .long("from")
+                .value_name("FILE")
+                .help("Elenco di task (uno per riga) da considerare installati, es. esportato da un altro host"))
+            .arg(Arg::new("dry-run")
+                .long("dry-run")
+                .action(ArgAction::SetTrue)
+                .help("Mostra i task rilevati senza modificare lo stato"))
+            .arg(Arg::new("yes")
+                .short('y')
+                .long("yes")
+                .action(ArgAction::SetTrue)
+                .help("Non chiede conferma")),
+        Command::new("plan")
+            .about("Calcola il piano di installazione di uno stack, da rivedere prima di applicarlo")
+            .arg(Arg::new("stack")
+                .long("stack")
+                .value_name("STACK")
+                .required(true)
+                .help("Stack da pianificare (nome o sorgente:nome)"))
+            .arg(Arg::new("output")
+                .short('o')
+                .long("output")
+                .value_name("FILE")
+                .help("File in cui salvare il piano (predefinito: JSON sullo standard output)")),
+        Command::new("apply")
+            .about("Porta la macchina allo stato previsto dal suo ruolo, o esegue un piano")
+            .arg(Arg::new("plan")
+                .value_name("PLAN")
+                .conflicts_with("role")
+                .help("File di piano creato con 'galatea plan' da eseguire al posto del ruolo"))
+            .arg(Arg::new("role")
+                .long("role")
+                .value_name("ROLE")
+                .help("Ruolo da applicare al posto di quello assegnato in configurazione"))
+            .arg(Arg::new("dry-run")
+                .long("dry-run")
+                .action(ArgAction::SetTrue)
+                .help("Mostra gli stack da installare senza modificare il sistema"))
+            .arg(Arg::new("yes")
+                .short('y')
+                .long("yes")
+                .action(ArgAction::SetTrue)
+                .help("Non chiede conferma"))
+            .arg(Arg::new("format")
+                .short('f')
+                .long("format")
+                .value_name("FORMAT")
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .help("Formato del rapporto del ruolo (text, json)")),
+        Command::new("drift")
+            .about("Verifica i task installati e riporta le derive rispetto allo stato atteso")
+            .arg(Arg::new("format")
+                .short('f')
+                .long("format")
+                .value_name("FORMAT")
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .help("Formato del rapporto (text, json)")),
+        Command::new("report")
+            .about("Stampa lo stato della macchina in JSON, come inviato all'endpoint phone_home")
+            .arg(Arg::new("output")
+                .short('o')
+                .long("output")
+                .value_name("FILE")
+                .help("Salva il rapporto nel file indicato invece di stamparlo"))
+            .arg(Arg::new("upload")
+                .long("upload")
+                .action(ArgAction::SetTrue)
+                .help("Invia il rapporto all'endpoint phone_home configurato")),
+        Command::new("fleet")
+            .about("Riassume i rapporti di stato di più macchine")
+            .subcommand_required(true)
+            .subcommand(Command::new("status")
+                .about("Mostra una tabella di conformità della flotta a partire dai rapporti inviati")
+                .arg(Arg::new("from")
+                    .long("from")
+                    .value_name("DIR|FILE|URL")
+                    .required(true)
+                    .action(ArgAction::Append)
+                    .help("Directory o file di rapporti JSON, o URL che restituisce l'elenco dei rapporti (ripetibile)"))
+                .arg(Arg::new("stale-after")
+                    .long("stale-after")
+                    .value_name("HOURS")
+                    .value_parser(clap::value_parser!(i64))
+                    .default_value("24")
+                    .help("Ore dopo cui il rapporto di una macchina è considerato non aggiornato"))
+                .arg(Arg::new("format")
+                    .short('f')
+                    .long("format")
+                    .value_name("FORMAT")
+                    .value_parser(["text", "json"])
+                    .default_value("text")
+                    .help("Formato del riepilogo (text, json)"))),
+        Command::new("quarantine")
+            .about("Elenca i task in quarantena dopo troppi fallimenti consecutivi e ne rimuove la quarantena")
+            .arg(Arg::new("clear")
+                .long("clear")
+                .value_name("TASK")
+                .action(ArgAction::Append)
+                .help("Rimuove la quarantena del task indicato (ripetibile)"))
+            .arg(Arg::new("clear-all")
+                .long("clear-all")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("clear")
+                .help("Rimuove la quarantena di tutti i task")),
+        Command::new("scheduled-run")
+            .about("Applica il ruolo, verifica le derive e invia il riepilogo per email (per cron o timer)")
+            .arg(Arg::new("no-email")
+                .long("no-email")
+                .action(ArgAction::SetTrue)
+                .help("Non invia il riepilogo per email")),
+        Command::new("daemon")
+            .about("Ripete le esecuzioni pianificate ogni schedule_interval minuti (servizio systemd)"),
+        Command::new("install-service")
+            .about("Installa e avvia l'unità systemd della modalità demone")
+            .arg(Arg::new("watchdog")
+                .long("watchdog")
+                .value_name("SECS")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("1800")
+                .help("Intervallo del watchdog di systemd in secondi (0 per disabilitarlo)"))
+            .arg(Arg::new("no-enable")
+                .long("no-enable")
+                .action(ArgAction::SetTrue)
+                .help("Installa l'unità senza abilitarla né avviarla"))
+            .arg(Arg::new("print")
+                .long("print")
+                .action(ArgAction::SetTrue)
+                .help("Stampa l'unità senza installarla")),
+        Command::new("inventory")
+            .about("Espone lo stato di task e stack ad Ansible (inventario dinamico o fatto locale)")
+            .arg(Arg::new("list")
+                .long("list")
+                .action(ArgAction::SetTrue)
+                .help("Stampa l'inventario dinamico completo in JSON"))
+            .arg(Arg::new("host")
+                .long("host")
+                .value_name("HOST")
+                .help("Stampa le variabili di un host in JSON"))
+            .arg(Arg::new("facts")
+                .long("facts")
+                .action(ArgAction::SetTrue)
+                .help("Stampa lo stato della macchina come fatto locale di Ansible"))
+            .arg(Arg::new("install-fact")
+                .long("install-fact")
+                .value_name("DIR")
+                .num_args(0..=1)
+                .default_missing_value(inventory::DEFAULT_FACTS_DIR)
+                .help("Installa il fatto locale galatea.fact (predefinito: /etc/ansible/facts.d)"))
+            .group(clap::ArgGroup::new("mode")
+                .args(["list", "host", "facts", "install-fact"])
+                .required(true)),
+        Command::new("export-ansible")
+            .about("Esporta task e stack come ruoli e playbook Ansible autonomi")
+            .arg(Arg::new("output")
+                .short('o')
+                .long("output")
+                .value_name("DIR")
+                .default_value("galatea-ansible")
+                .help("Directory in cui scrivere ruoli e playbook"))
+            .arg(Arg::new("stack")
+                .short('s')
+                .long("stack")
+                .value_name("STACK")
+                .action(ArgAction::Append)
+                .help("Stack da esportare (ripetibile; predefinito: tutti)"))
+            .arg(Arg::new("task")
+                .short('t')
+                .long("task")
+                .value_name("TASK")
+                .action(ArgAction::Append)
+                .help("Task da esportare singolarmente (ripetibile)")),
+        Command::new("mirror")
+            .about("Scarica cataloghi e bundle delle sorgenti in una directory servibile come mirror interno")
+            .arg(Arg::new("dest")
+                .short('d')
+                .long("dest")
+                .value_name("DIR")
+                .required(true)
+                .help("Directory del mirror"))
+            .arg(Arg::new("base-url")
+                .long("base-url")
+                .value_name("URL")
+                .help("URL da cui sarà servito il mirror, per riscrivere gli URL dei bundle nei cataloghi")),
+        Command::new("bootstrap-script")
+            .about("Genera uno script shell o cloud-init che installa Galatea e applica gli stack al primo avvio")
+            .arg(Arg::new("stack")
+                .short('s')
+                .long("stack")
+                .value_name("STACK")
+                .action(ArgAction::Append)
+                .required(true)
+                .help("Stack da applicare (ripetibile)"))
+            .arg(Arg::new("format")
+                .short('f')
+                .long("format")
+                .value_name("FORMAT")
+                .value_parser(["shell", "cloud-init"])
+                .default_value("shell")
+                .help("Formato dello snippet (shell, cloud-init)"))
+            .arg(Arg::new("release-url")
+                .long("release-url")
+                .value_name("URL")
+                .default_value(bootstrap::DEFAULT_RELEASE_URL)
+                .help("URL dell'archivio di Galatea; {arch} viene sostituito con l'architettura"))
+            .arg(Arg::new("service")
+                .long("service")
+                .action(ArgAction::SetTrue)
+                .help("Installa anche il servizio systemd della modalità demone"))
+            .arg(Arg::new("output")
+                .short('o')
+                .long("output")
+                .value_name("FILE")
+                .help("Scrive lo snippet su file invece che sullo standard output")),
+        Command::new("build-image")
+            .about("Applica gli stack in un container di build e lo salva come immagine")
+            .arg(Arg::new("stack")
+                .short('s')
+                .long("stack")
+                .value_name("STACK")
+                .action(ArgAction::Append)
+                .required(true)
+                .help("Stack da applicare (ripetibile)"))
+            .arg(Arg::new("from")
+                .long("from")
+                .value_name("IMAGE")
+                .required(true)
+                .help("Immagine di partenza"))
+            .arg(Arg::new("tag")
+                .short('t')
+                .long("tag")
+                .value_name("TAG")
+                .required(true)
+                .help("Nome dell'immagine prodotta"))
+            .arg(Arg::new("engine")
+                .long("engine")
+                .value_name("ENGINE")
+                .value_parser(["buildah", "docker", "podman"])
+                .help("Motore dei container (predefinito: il primo installato tra buildah, podman e docker)"))
+            .arg(Arg::new("keep-galatea")
+                .long("keep-galatea")
+                .action(ArgAction::SetTrue)
+                .help("Lascia Galatea, la configurazione e lo stato nell'immagine")),
+        Command::new("rollout")
+            .about("Applica gli stack a più host via SSH, a lotti")
+            .arg(Arg::new("stack")
+                .short('s')
+                .long("stack")
+                .value_name("STACK")
+                .action(ArgAction::Append)
+                .required(true)
+                .help("Stack da applicare (ripetibile)"))
+            .arg(Arg::new("hosts")
+                .long("hosts")
+                .value_name("HOST,...")
+                .value_delimiter(',')
+                .action(ArgAction::Append)
+                .help("Host di destinazione ([utente@]host), separati da virgola"))
+            .arg(Arg::new("hosts-file")
+                .short('i')
+                .long("hosts-file")
+                .value_name("FILE")
+                .help("File con un host per riga (accetta anche inventari INI di Ansible)"))
+            .arg(Arg::new("batch-size")
+                .short('b')
+                .long("batch-size")
+                .value_name("N|N%")
+                .default_value("1")
+                .help("Host gestiti in parallelo per lotto, in numero o in percentuale"))
+            .arg(Arg::new("max-fail-percent")
+                .long("max-fail-percent")
+                .value_name("PERCENT")
+                .value_parser(clap::value_parser!(u8).range(0..=100))
+                .default_value("0")
+                .help("Percentuale di host falliti oltre la quale il rollout si interrompe"))
+            .arg(Arg::new("pause")
+                .long("pause")
+                .value_name("SECS")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("0")
+                .help("Pausa in secondi tra un lotto e il successivo"))
+            .arg(Arg::new("ssh-option")
+                .long("ssh-option")
+                .value_name("OPTION")
+                .action(ArgAction::Append)
+                .allow_hyphen_values(true)
+                .help("Opzione aggiuntiva per ssh (ripetibile, es. --ssh-option=-p2222)"))
+            .arg(Arg::new("release-url")
+                .long("release-url")
+                .value_name("URL")
+                .default_value(bootstrap::DEFAULT_RELEASE_URL)
+                .help("URL dell'archivio di Galatea per gli host che non lo hanno"))
+            .arg(Arg::new("preflight")
+                .long("preflight")
+                .value_name("POLICY")
+                .value_parser(["abort", "skip", "off"])
+                .default_value("abort")
+                .help("Host che non superano il controllo preliminare: interrompe (abort), li salta (skip) o non controlla (off)"))
+            .arg(Arg::new("check")
+                .long("check")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("preflight")
+                .help("Esegue solo il controllo preliminare e ne mostra il rapporto"))
+            .arg(Arg::new("yes")
+                .short('y')
+                .long("yes")
+                .action(ArgAction::SetTrue)
+                .help("Non chiede conferma"))
+            .group(clap::ArgGroup::new("targets")
+                .args(["hosts", "hosts-file"])
+                .multiple(true)
+                .required(true)),
+        Command::new("migrate-layout")
+            .about("Sposta configurazione e dati nella disposizione di sistema o in quella XDG dell'utente")
+            .arg(Arg::new("to")
+                .long("to")
+                .value_name("LAYOUT")
+                .value_parser(["system", "user"])
+                .required(true)
+                .help("Disposizione di destinazione: system (/etc, /var/lib) o user (XDG)"))
+            .arg(Arg::new("dry-run")
+                .long("dry-run")
+                .action(ArgAction::SetTrue)
+                .help("Mostra gli spostamenti senza eseguirli"))
+            .arg(Arg::new("yes")
+                .short('y')
+                .long("yes")
+                .action(ArgAction::SetTrue)
+                .help("Non chiede conferma")),
+    ]
+}
+
+/// Verifica se un sottocomando richiede i privilegi di root
+pub fn requires_root(name: &str) -> bool {
+    // La migrazione verso la disposizione dell'utente non tocca i file di sistema; i plugin
+    // verificano da soli i privilegi di cui hanno bisogno
+    is_builtin(name) && !matches!(name, "new-task" | "lint" | "migrate-layout" | "replay" | "fleet" | "plugins" | "mirror")
+}
+
+/// Verifica se un sottocomando è predefinito, e non fornito da un plugin
+pub fn is_builtin(name: &str) -> bool {
+    subcommands().iter().any(|command| command.get_name() == name)
+}
+
+/// Verifica se un sottocomando modifica il sistema o lo stato di Galatea
+///
+/// Le esecuzioni di prova (`--dry-run`) non modificano nulla e restano ammesse anche in
+/// modalità sola lettura.
+pub fn modifies_system(name: &str, matches: &ArgMatches) -> bool {
+    let dry_run = matches.try_get_one::<bool>("dry-run").ok().flatten().copied().unwrap_or(false);
+    match name {
+        "purge" | "scheduled-run" | "daemon" | "install-service" | "rollout" => true,
+        "import-state" | "apply" | "migrate-layout" => !dry_run,
+        "inventory" => matches.get_flag("install-fact"),
+        "quarantine" => matches.contains_id("clear") || matches.get_flag("clear-all"),
+        "record" => matches.subcommand().is_some_and(|(name, matches)| modifies_system(name, matches)),
+        _ => false,
+    }
+}
+
+/// Esegue un sottocomando
+///
+/// # Arguments
+///
+/// * `name` - Il nome del sottocomando
+/// * `matches` - Gli argomenti del sottocomando
+/// * `config_path` - Il percorso del file di configurazione specificato con --config
+/// * `read_only` - Indica se è stato specificato --read-only
+///
+/// # Returns
+///
+/// Il codice di uscita del processo
+pub fn run(name: &str, matches: &ArgMatches, config_path: Option<&str>, read_only: bool) -> Result<i32> {
+    // I sottocomandi sconosciuti sono forniti dai plugin galatea-<nome>
+    if !is_builtin(name) {
+        let args: Vec<OsString> = matches.get_many::<OsString>("")
+            .map(|args| args.cloned().collect())
+            .unwrap_or_default();
+        return plugins::run(name, &args, config_path, read_only);
+    }
+
+    // In sola lettura (da linea di comando o da configurazione) sono ammesse solo le consultazioni
+    if modifies_system(name, matches) && (read_only || Config::load(config_path)?.read_only) {
+        return Err(anyhow!("Subcommand {} modifies the system and is not allowed in read-only mode", name));
+    }
+
+    // Directory non scrivibili vanno segnalate prima di iniziare, non a metà dell'operazione
+    if modifies_system(name, matches) {
+        preflight::check(&Config::load(config_path)?)?;
+    }
+
+    // Barra di avanzamento, solo se l'output è un terminale (per `record` la attiva il
+    // sottocomando registrato)
+    if name != "record" && io::stderr().is_terminal() && !logger::verbosity().is_quiet() {
+        events::subscribe(|event| {
+            if let Event::Progress(progress) = event {
+                print_progress(progress);
+            }
+        });
+    }
+
+    let code = match name {
+        "new-task" => run_new_task(matches),
+        "lint" => run_lint(matches),
+        "validate" => run_validate(config_path),
+        "schema" => run_schema(matches),
+        "purge" => run_purge(matches, config_path),
+        "import-state" => run_import_state(matches, config_path),
+        "plan" => run_plan(matches, config_path),
+        "apply" => run_apply(matches, config_path),
+        "drift" => run_drift(matches, config_path),
+        "report" => run_report(matches, config_path),
+        "fleet" => run_fleet(matches, config_path),
+        "quarantine" => run_quarantine(matches, config_path),
+        "scheduled-run" => run_scheduled(matches, config_path),
+        "daemon" => run_daemon(config_path),
+        "install-service" => run_install_service(matches, config_path),
+        "inventory" => run_inventory(matches, config_path),
+        "export-ansible" => run_export_ansible(matches, config_path),
+        "mirror" => run_mirror(matches, config_path),
+        "bootstrap-script" => run_bootstrap_script(matches, config_path),
+        "build-image" => run_build_image(matches, config_path),
+        "rollout" => run_rollout(matches, config_path),
+        "migrate-layout" => run_migrate_layout(matches, config_path),
+        "record" => run_record(matches, config_path, read_only),
+        "replay" => run_replay(matches),
+        "plugins" => run_plugins(),
+        _ => Err(anyhow!("Unknown subcommand: {}", name)),
+    }?;
+
+    // Dopo un'installazione riassume cosa è cambiato e segnala se la macchina deve essere riavviata
+    if matches!(name, "apply" | "scheduled-run") && modifies_system(name, matches) {
+        report_system_changes();
+        report_pending_reboot(config_path);
+    }
+
+    Ok(code)
+}
+
+/// Stampa le modifiche al sistema dei task installati dal comando
+fn report_system_changes() {
+    for (task, summary) in changes::take_recorded() {
+        if summary.is_empty() {
+            continue;
+        }
+        say!("\nModifiche al sistema di {}:", task);
+        for line in summary.lines() {
+            say!("  {}", line);
+        }
+    }
+}
+
+/// Stampa i motivi del riavvio in sospeso, se presenti
+fn report_pending_reboot(config_path: Option<&str>) {
+    let Ok(config) = Config::load(config_path) else {
+        return;
+    };
+    let tasks = load_tasks(&config).unwrap_or_default();
+
+    let pending = reboot::pending_reboot(&config, &tasks);
+    if !pending.is_empty() {
+        say!("Riavvio necessario: {}", pending.summary());
+    }
+}
+
+/// Chiede conferma di un'azione secondo la politica configurata
+///
+/// In modalità non interattiva (stdin non è un terminale) un'azione che richiede
+/// conferma viene rifiutata, a meno che non sia stato indicato `--yes`.
+///
+/// # Arguments
+///
+/// * `config` - La configurazione con la politica di conferma
+/// * `destructive` - Indica se l'azione è distruttiva
+/// * `assume_yes` - Indica se è stato specificato `--yes`
+/// * `prompt` - La domanda da porre all'utente
+///
+/// # Returns
+///
+/// `true` se l'azione può procedere
+fn confirm_action(config: &Config, destructive: bool, assume_yes: bool, prompt: &str) -> Result<bool> {
+    if assume_yes || !config.confirm_actions.requires_confirmation(destructive) {
+        return Ok(true);
+    }
+
+    if !io::stdin().is_terminal() {
+        return Err(anyhow!(
+            "Confirmation required by confirm_actions policy ({}) but stdin is not a terminal: rerun with --yes",
+            config.confirm_actions.to_str()
+        ));
+    }
+
+    Ok(confirm(prompt))
+}
+
+/// Chiede conferma all'utente sulla console
+fn confirm(prompt: &str) -> bool {
+    print!("{} [s/N] ", prompt);
+    let _ = io::stdout().flush();
+
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+
+    matches!(answer.trim().to_lowercase().as_str(), "s" | "si" | "sì" | "y" | "yes")
+}
+
+/// Larghezza della barra di avanzamento
+const PROGRESS_BAR_WIDTH: usize = 30;
+
+/// Stampa su stderr la barra di avanzamento di un'attività
+fn print_progress(progress: &ProgressUpdate) {
+    let bar = match progress.percent {
+        Some(percent) => {
+            let filled = ((percent / 100.0) * PROGRESS_BAR_WIDTH as f64).round() as usize;
+            let filled = filled.min(PROGRESS_BAR_WIDTH);
+            format!("[{}{}] ", "#".repeat(filled), " ".repeat(PROGRESS_BAR_WIDTH - filled))
+        },
+        None => String::new(),
+    };
+
+    let mut stderr = io::stderr();
+    let _ = write!(stderr, "\r\x1b[K{} {}{}", progress.label, bar, progress.detail);
+    if progress.finished {
+        let _ = writeln!(stderr);
+    }
+    let _ = stderr.flush();
+}
+
+/// Genera lo scheletro di un nuovo bundle di task
+fn run_new_task(matches: &ArgMatches) -> Result<i32> {
+    let name = matches.get_one::<String>("name")
+        .ok_or_else(|| anyhow!("Missing task name"))?;
+    let type_str = matches.get_one::<String>("type")
+        .map(|s| s.as_str())
+        .unwrap_or("bash");
+    let output = matches.get_one::<String>("output")
+        .map(|s| s.as_str())
+        .unwrap_or(".");
+
+    let script_type = ScriptType::from_str(type_str)?;
+    let bundle_dir = scaffold::create_task_bundle(name, script_type, Path::new(output))?;
+
+    say!("Bundle del task {} creato in: {}", name, bundle_dir.display());
+    say!("Copia {}.conf nella directory dei task o pubblicalo in una sorgente per renderlo disponibile.", name);
+
+    Ok(0)
+}
+
+/// Verifica un bundle di task e stampa le segnalazioni
+fn run_lint(matches: &ArgMatches) -> Result<i32> {
+    let bundle = matches.get_one::<String>("bundle")
+        .ok_or_else(|| anyhow!("Missing bundle path"))?;
+    let script_type = match matches.get_one::<String>("type") {
+        Some(type_str) => Some(ScriptType::from_str(type_str)?),
+        None => None,
+    };
+    let strict = matches.get_flag("strict");
+
+    let report = lint::lint_bundle(Path::new(bundle), script_type)?;
+
+    for finding in &report.findings {
+        println!("{}", finding);
+    }
+
+    let errors = report.error_count();
+    let warnings = report.warning_count();
+    println!("\n{}: {} errori, {} avvisi", bundle, errors, warnings);
+
+    if errors > 0 || (strict && warnings > 0) {
+        Ok(1)
+    } else {
+        Ok(0)
+    }
+}
+
+/// Verifica i cataloghi rispetto allo schema e i riferimenti tra stack e task
+fn run_validate(config_path: Option<&str>) -> Result<i32> {
+    let config = Config::load(config_path)?;
+
+    let schema_issues = catalog_schema::check_catalogs(&config)?;
+    for (path, issue) in &schema_issues {
+        println!("{}: {}", path.display(), issue);
+    }
+
+    let (tasks, stacks) = catalog::load(&config)?;
+
+    let problems = stack::missing_task_references(&stacks, &tasks);
+    for (stack, missing) in &problems {
+        println!("Stack {}: task non trovati: {}", stack, missing.join(", "));
+    }
+
+    say!("\n{} task, {} stack: {} problemi di schema, {} stack con riferimenti non validi",
+         tasks.len(), stacks.len(), schema_issues.len(), problems.len());
+
+    Ok(if problems.is_empty() && schema_issues.is_empty() { 0 } else { 1 })
+}
+
+/// Elenca i plugin disponibili come sottocomandi
+fn run_plugins() -> Result<i32> {
+    let plugins = plugins::discover();
+    if plugins.is_empty() {
+        say!("Nessun plugin {}<nome> trovato nel PATH", plugins::PLUGIN_PREFIX);
+        return Ok(0);
+    }
+
+    for (name, path) in &plugins {
+        let shadowed = if is_builtin(name) { " (nascosto dal sottocomando predefinito)" } else { "" };
+        println!("{:<20} {}{}", name, path.display(), shadowed);
+    }
+    Ok(0)
+}
+
+/// Stampa lo schema dei file di un catalogo
+fn run_schema(matches: &ArgMatches) -> Result<i32> {
+    let kind = matches.get_one::<String>("kind")
+        .ok_or_else(|| anyhow!("Missing catalog kind"))?;
+    let schema = serde_json::to_string_pretty(&catalog_schema::schema(CatalogKind::from_str(kind)?))?;
+
+    match matches.get_one::<String>("output") {
+        Some(output) => {
+            std::fs::write(output, format!("{}\n", schema))
+                .context(format!("Failed to write schema to {}", output))?;
+            say!("Schema del catalogo {} scritto in {}", kind, output);
+        },
+        None => println!("{}", schema),
+    }
+
+    Ok(0)
+}
+
+/// Rimuove i dati gestiti da Galatea
+fn run_purge(matches: &ArgMatches, config_path: Option<&str>) -> Result<i32> {
+    let config = Config::load(config_path)?;
+    let options = PurgeOptions {
+        uninstall_tasks: matches.get_flag("uninstall"),
+        keep_logs: matches.get_flag("keep-logs"),
+        force: matches.get_flag("force"),
+    };
+
+    say!("Verranno rimossi:");
+    for dir in purge::purge_targets(&config, options.keep_logs) {
+        say!("  - {}", dir.display());
+    }
+    if options.uninstall_tasks {
+        say!("  - tutti i task installati (disinstallazione)");
+    }
+
+    if !confirm_action(&config, true, matches.get_flag("yes"), "Procedere con la rimozione?")? {
+        println!("Operazione annullata");
+        return Ok(1);
+    }
+
+    let report = purge::purge(&config, &options)?;
+
+    for name in &report.uninstalled {
+        say!("Disinstallato: {}", name);
+    }
+    for (name, error) in &report.failed {
+        eprintln!("Disinstallazione fallita: {}: {}", name, error);
+    }
+    for dir in &report.removed_dirs {
+        say!("Rimosso: {}", dir.display());
+    }
+
+    Ok(if report.failed.is_empty() { 0 } else { 1 })
+}
+
+/// Importa lo stato dei task da un sistema già configurato
+fn run_import_state(matches: &ArgMatches, config_path: Option<&str>) -> Result<i32> {
+    let config = Config::load(config_path)?;
+    let dry_run = matches.get_flag("dry-run");
+    let list_file = matches.get_one::<String>("from").map(Path::new);
+
+    // Se serve una conferma i task vengono prima solo rilevati
+    let needs_confirmation = !dry_run && !matches.get_flag("yes")
+        && config.confirm_actions.requires_confirmation(false);
+    let preview = dry_run || needs_confirmation;
+
+    let mut tasks = load_tasks(&config)?;
+    let mut report = importer::import_state(&config, &mut tasks, list_file, preview)?;
+
+    for task in &report.imported {
+        let prefix = if preview { "Rilevato" } else { "Importato" };
+        say!("{}: {} ({})", prefix, task.name, task.evidence.join(", "));
+    }
+    for name in &report.unknown {
+        eprintln!("Task non presente nel catalogo: {}", name);
+    }
+
+    say!(
+        "\n{} task rilevati, {} già installati, {} senza regole di rilevamento",
+        report.imported.len(),
+        report.already_installed.len(),
+        report.skipped.len()
+    );
+    if dry_run && !report.imported.is_empty() {
+        say!("Nessuna modifica effettuata (--dry-run)");
+    }
+
+    if needs_confirmation && !report.imported.is_empty() {
+        if !confirm_action(&config, false, false, "Registrare come installati i task rilevati?")? {
+            println!("Operazione annullata");
+            return Ok(1);
+        }
+
+        report = importer::import_state(&config, &mut tasks, list_file, false)?;
+        say!("Importati {} task", report.imported.len());
+    }
+
+    Ok(0)
+}
+
+/// Applica il ruolo della macchina installando gli stack mancanti
+fn run_apply(matches: &ArgMatches, config_path: Option<&str>) -> Result<i32> {
+    if let Some(plan_path) = matches.get_one::<String>("plan") {
+        return run_apply_plan(matches, config_path, Path::new(plan_path));
+    }
+
+    let config = Config::load(config_path)?;
+    let role_name = matches.get_one::<String>("role").map(|s| s.as_str());
+    let dry_run = matches.get_flag("dry-run");
+
+    // Se serve una conferma gli stack mancanti vengono prima solo elencati
+    let needs_confirmation = !dry_run && !matches.get_flag("yes")
+        && config.confirm_actions.requires_confirmation(false);
+
+    let mut report = role::apply_role(&config, role_name, dry_run || needs_confirmation)?;
+
+    if needs_confirmation && !report.installed.is_empty() {
+        say!("Stack da installare per il ruolo {}:", report.role);
+        for name in &report.installed {
+            say!("  - {}", name);
+        }
+
+        if !confirm_action(&config, false, false, "Installare gli stack mancanti?")? {
+            println!("Operazione annullata");
+            return Ok(1);
+        }
+
+        report = role::apply_role(&config, role_name, false)?;
+    }
+
+    if matches.get_one::<String>("format").is_some_and(|format| format == "json") {
+        println!("{}", report.to_json()?);
+        return Ok(if report.is_converged() { 0 } else { 1 });
+    }
+
+    let installed_prefix = if dry_run { "Da installare" } else { "Installato" };
+    for name in &report.installed {
+        say!("{}: {}", installed_prefix, name);
+    }
+    for name in &report.already_installed {
+        say!("Già installato: {}", name);
+    }
+    for (name, error) in &report.failed {
+        eprintln!("Installazione fallita: {}: {}", name, error);
+    }
+    for name in &report.unknown {
+        eprintln!("Stack non presente nel catalogo: {}", name);
+    }
+    for name in &report.extra_stacks {
+        say!("Stack non previsto dal ruolo: {}", name);
+    }
+    for name in &report.extra_tasks {
+        say!("Task non previsto dal ruolo: {}", name);
+    }
+    for name in &report.denied_installed {
+        eprintln!("Task installato ma escluso su questa macchina: {}", name);
+    }
+    for stack_report in &report.stack_reports {
+        say!("\nStack {}:\n{}", stack_report.stack, stack_report.to_table().trim_end());
+    }
+
+    say!(
+        "\nRuolo {}: {} stack {}, {} già installati, {} falliti, {} extra",
+        report.role,
+        report.installed.len(),
+        if dry_run { "da installare" } else { "installati" },
+        report.already_installed.len(),
+        report.failed.len(),
+        report.extra_stacks.len() + report.extra_tasks.len()
+    );
+    if dry_run && !report.installed.is_empty() {
+        say!("Nessuna modifica effettuata (--dry-run)");
+    }
+
+    Ok(if report.is_converged() { 0 } else { 1 })
+}
+
+/// Calcola il piano di installazione di uno stack e lo salva o lo stampa
+fn run_plan(matches: &ArgMatches, config_path: Option<&str>) -> Result<i32> {
+    let config = Config::load(config_path)?;
+    let stack = matches.get_one::<String>("stack")
+        .ok_or_else(|| anyhow!("--stack is required"))?;
+
+    let plan = plan::create_plan(&config, stack)?;
+
+    match matches.get_one::<String>("output") {
+        Some(output) => {
+            plan.save(Path::new(output))?;
+            say!("{}", plan.to_text());
+            say!("Piano salvato in {}: applicalo con 'galatea apply {}'", output, output);
+        },
+        None => println!("{}", plan.to_json()?),
+    }
+
+    Ok(0)
+}
+
+/// Esegue un piano creato con `galatea plan`
+fn run_apply_plan(matches: &ArgMatches, config_path: Option<&str>, plan_path: &Path) -> Result<i32> {
+    let config = Config::load(config_path)?;
+    let plan = Plan::load(plan_path)?;
+
+    say!("{}", plan.to_text());
+    if matches.get_flag("dry-run") {
+        say!("Nessuna modifica effettuata (--dry-run)");
+        return Ok(0);
+    }
+    if plan.install_count() == 0 {
+        say!("Nessun task da installare");
+        return Ok(0);
+    }
+
+    if !confirm_action(&config, false, matches.get_flag("yes"), "Applicare il piano?")? {
+        println!("Operazione annullata");
+        return Ok(1);
+    }
+
+    // Negli ambienti regolamentati il piano deve essere approvato da un sistema esterno
+    if let Some(approval) = &config.approval {
+        say!("In attesa dell'approvazione da {}...", approval.url);
+        let decision = approval::request_approval(&config, approval, &plan)?;
+        let approver = decision.approver.as_deref().unwrap_or("approvatore sconosciuto");
+
+        if decision.status != ApprovalStatus::Approved {
+            eprintln!("Piano respinto da {}: {}", approver, decision.reason.as_deref().unwrap_or("nessuna motivazione"));
+            return Ok(1);
+        }
+        say!("Piano approvato da {}", approver);
+    }
+
+    let report = plan::apply_plan(&config, &plan)?;
+
+    for name in &report.installed {
+        say!("Installato: {}", name);
+    }
+    for name in &report.skipped {
+        say!("Già installato: {}", name);
+    }
+    for name in &report.excluded {
+        say!("Condizione non soddisfatta: {}", name);
+    }
+    for (name, error) in &report.failed {
+        eprintln!("Installazione fallita: {}: {}", name, error);
+    }
+    if let Some(canary) = &report.aborted_by {
+        eprintln!("Piano interrotto: il canary {} è fallito", canary);
+    }
+
+    say!(
+        "\nPiano di {}: {} installati, {} già installati, {} esclusi, {} falliti",
+        plan.stack,
+        report.installed.len(),
+        report.skipped.len(),
+        report.excluded.len(),
+        report.failed.len()
+    );
+
+    Ok(if report.is_ok() { 0 } else { 1 })
+}
+
+/// Codice di uscita di `drift` quando viene rilevata una deriva
+const DRIFT_EXIT_CODE: i32 = 2;
+
+/// Stampa il rapporto di conformità dei task installati
+fn run_drift(matches: &ArgMatches, config_path: Option<&str>) -> Result<i32> {
+    let config = Config::load(config_path)?;
+    let format = matches.get_one::<String>("format")
+        .map(|s| s.as_str())
+        .unwrap_or("text");
+
+    let report = drift::check_drift(&config)?;
+
+    match format {
+        "json" => println!("{}", report.to_json()?),
+        _ => print!("{}", report.to_text()),
+    }
+
+    Ok(if report.has_drift() { DRIFT_EXIT_CODE } else { 0 })
+}
+
+/// Stampa, salva o invia il rapporto di stato della macchina
+fn run_report(matches: &ArgMatches, config_path: Option<&str>) -> Result<i32> {
+    let config = Config::load(config_path)?;
+    let report = fleet::collect(&config)?;
+
+    if matches.get_flag("upload") {
+        let phone_home = config.phone_home.as_ref()
+            .ok_or_else(|| anyhow!("No phone_home endpoint configured"))?;
+        fleet::upload(&config, phone_home, &report)?;
+        say!("Rapporto inviato a {}", phone_home.url);
+    }
+
+    match matches.get_one::<String>("output") {
+        Some(path) => {
+            std::fs::write(path, report.to_json()?)
+                .context(format!("Failed to write report to {}", path))?;
+            say!("Rapporto salvato in {}", path);
+        },
+        None if !matches.get_flag("upload") => println!("{}", report.to_json()?),
+        None => {},
+    }
+
+    Ok(0)
+}
+
+/// Riassume i rapporti di stato delle macchine della flotta
+fn run_fleet(matches: &ArgMatches, config_path: Option<&str>) -> Result<i32> {
+    let Some(("status", matches)) = matches.subcommand() else {
+        return Err(anyhow!("Unknown fleet subcommand"));
+    };
+
+    let timeout = Config::load(config_path).map(|config| config.download_timeout).unwrap_or(60);
+    let mut reports = Vec::new();
+    for from in matches.get_many::<String>("from").into_iter().flatten() {
+        reports.extend(fleet::load_reports(from, timeout)?);
+    }
+    if reports.is_empty() {
+        return Err(anyhow!("No host reports found"));
+    }
+
+    let stale_after = chrono::Duration::hours(*matches.get_one::<i64>("stale-after").unwrap_or(&24));
+    let summary = fleet::FleetSummary::new(reports, stale_after);
+
+    match matches.get_one::<String>("format").map(|s| s.as_str()) {
+        Some("json") => println!("{}", summary.to_json()?),
+        _ => print!("{}", summary.to_table()),
+    }
+
+    Ok(if summary.is_compliant() { 0 } else { DRIFT_EXIT_CODE })
+}
+
+/// Elenca i task in quarantena o ne rimuove la quarantena
+fn run_quarantine(matches: &ArgMatches, config_path: Option<&str>) -> Result<i32> {
+    let config = Config::load(config_path)?;
+    let mut tasks = load_tasks(&config)?;
+
+    let names: Vec<&String> = matches.get_many::<String>("clear").map(|v| v.collect()).unwrap_or_default();
+    if names.is_empty() && !matches.get_flag("clear-all") {
+        let quarantined: Vec<_> = tasks.iter().filter(|t| t.is_quarantined()).collect();
+        if quarantined.is_empty() {
+            say!("Nessun task in quarantena");
+        }
+        for task in quarantined {
+            if let Some(failure) = &task.last_failure {
+                println!("{}: {} fallimenti consecutivi, ultimo {} ({}): {}",
+                         task.qualified_name(), failure.consecutive, failure.operation, failure.failed_at, failure.error);
+            }
+        }
+        return Ok(0);
+    }
+
+    for name in &names {
+        if !tasks.iter().any(|t| t.matches_ref(name)) {
+            return Err(anyhow!("Task not found: {}", name));
+        }
+    }
+
+    let mut cleared = 0;
+    for task in tasks.iter_mut() {
+        if names.is_empty() || names.iter().any(|name| task.matches_ref(name)) {
+            if task.clear_quarantine(&config)? {
+                say!("Quarantena rimossa: {}", task.qualified_name());
+                cleared += 1;
+            }
+        }
+    }
+    say!("{} task rimossi dalla quarantena", cleared);
+
+    Ok(0)
+}
+
+/// Esegue un'esecuzione pianificata e ne invia il riepilogo
+fn run_scheduled(matches: &ArgMatches, config_path: Option<&str>) -> Result<i32> {
+    let config = Config::load(config_path)?;
+    let report = scheduler::run_scheduled(&config)?;
+
+    say!("{}", report.summary());
+
+    if !matches.get_flag("no-email") {
+        match scheduler::notify_run(&config, &report) {
+            Ok(true) => say!("Riepilogo inviato per email"),
+            Ok(false) => {},
+            Err(e) => {
+                eprintln!("Invio del riepilogo per email fallito: {:#}", e);
+                return Ok(1);
+            }
+        }
+    }
+
+    match fleet::phone_home(&config) {
+        Ok(true) => say!("Stato inviato all'endpoint phone_home"),
+        Ok(false) => {},
+        Err(e) => {
+            eprintln!("Invio dello stato all'endpoint phone_home fallito: {:#}", e);
+            return Ok(1);
+        }
+    }
+
+    Ok(if report.is_ok() { 0 } else { 1 })
+}
+
+/// Avvia la modalità demone fino alla ricezione di SIGTERM o SIGINT
+fn run_daemon(config_path: Option<&str>) -> Result<i32> {
+    let stop = Arc::new(AtomicBool::new(false));
+
+    #[cfg(unix)]
+    {
+        use signal_hook::consts::{SIGINT, SIGTERM};
+        for signal in [SIGTERM, SIGINT] {
+            signal_hook::flag::register(signal, Arc::clone(&stop))
+                .map_err(|e| anyhow!("Failed to register signal handler: {}", e))?;
+        }
+    }
+
+    scheduler::run_daemon(config_path, &stop)?;
+    Ok(0)
+}
+
+/// Genera e installa l'unità systemd della modalità demone
+fn run_install_service(matches: &ArgMatches, config_path: Option<&str>) -> Result<i32> {
+    let executable = std::env::current_exe()
+        .map_err(|e| anyhow!("Failed to determine the executable path: {}", e))?;
+
+    // Il demone deve usare la stessa configurazione, indicata con un percorso assoluto
+    let config = Config::load(config_path)?;
+    let config_file = match config_path {
+        Some(path) => Some(std::fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path))),
+        None => config.config_file_path.clone(),
+    };
+
+    let options = ServiceOptions {
+        executable,
+        config_path: config_file,
+        watchdog_secs: matches.get_one::<u64>("watchdog").copied().unwrap_or(0),
+    };
+
+    if matches.get_flag("print") {
+        print!("{}", systemd::unit_file(&options));
+        return Ok(0);
+    }
+
+    let enable = !matches.get_flag("no-enable");
+    let unit_path = systemd::install_service(&options, enable)?;
+
+    say!("Unità systemd installata in {}", unit_path.display());
+    if enable {
+        say!("Servizio {} abilitato e avviato", systemd::SERVICE_NAME);
+    } else {
+        say!("Avvia il servizio con: systemctl enable --now {}", systemd::SERVICE_NAME);
+    }
+
+    Ok(0)
+}
+
+/// Espone lo stato di Galatea ad Ansible
+fn run_inventory(matches: &ArgMatches, config_path: Option<&str>) -> Result<i32> {
+    let config = Config::load(config_path)?;
+
+    if let Some(facts_dir) = matches.get_one::<String>("install-fact") {
+        let config_file = config_path
+            .map(|path| std::fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path)))
+            .or_else(|| config.config_file_path.clone());
+        let fact_path = inventory::install_fact(Path::new(facts_dir), config_file.as_deref())?;
+        say!("Fatto locale installato in {}: lo stato sarà disponibile come ansible_local.galatea", fact_path.display());
+        return Ok(0);
+    }
+
+    let value = if let Some(host) = matches.get_one::<String>("host") {
+        inventory::inventory_host(&config, host)?
+    } else if matches.get_flag("facts") {
+        serde_json::to_value(inventory::host_vars(&config)?)?
+    } else {
+        inventory::inventory_list(&config)?
+    };
+
+    println!("{}", serde_json::to_string_pretty(&value)?);
+    Ok(0)
+}
+
+/// Esporta task e stack come ruoli e playbook Ansible
+fn run_export_ansible(matches: &ArgMatches, config_path: Option<&str>) -> Result<i32> {
+    let config = Config::load(config_path)?;
+    let output = matches.get_one::<String>("output").map(String::as_str).unwrap_or("galatea-ansible");
+    let stacks: Vec<String> = matches.get_many::<String>("stack").unwrap_or_default().cloned().collect();
+    let tasks: Vec<String> = matches.get_many::<String>("task").unwrap_or_default().cloned().collect();
+
+    let report = export::export_ansible(&config, Path::new(output), &stacks, &tasks)?;
+
+    say!("Ruoli esportati: {}", report.roles.join(", "));
+    for playbook in &report.playbooks {
+        say!("Playbook: {}", playbook.display());
+    }
+    for warning in &report.warnings {
+        eprintln!("Attenzione: {}", warning);
+    }
+    say!("Esegui con: ansible-playbook -i <inventario> {}", Path::new(output).join("site.yml").display());
+
+    Ok(0)
+}
+
+/// Sincronizza un mirror interno delle sorgenti configurate
+fn run_mirror(matches: &ArgMatches, config_path: Option<&str>) -> Result<i32> {
+    let config = Config::load(config_path)?;
+    let options = MirrorOptions {
+        dest: PathBuf::from(matches.get_one::<String>("dest").map(String::as_str).unwrap_or_default()),
+        base_url: matches.get_one::<String>("base-url").cloned(),
+    };
+
+    let report = mirror::sync(&config, &options)?;
+
+    for catalog in &report.catalogs {
+        say!("Catalogo: {} (sha256: {})", catalog.path, catalog.sha256);
+    }
+    say!("Bundle scaricati: {}, invariati: {}, falliti: {}", report.downloaded, report.unchanged, report.failures.len());
+    for (bundle, reason) in &report.failures {
+        eprintln!("Errore: {}: {}", bundle, reason);
+    }
+    for warning in &report.warnings {
+        eprintln!("Attenzione: {}", warning);
+    }
+    if options.base_url.is_none() {
+        eprintln!("Attenzione: senza --base-url i cataloghi puntano ancora agli URL originali dei bundle");
+    }
+
+    Ok(if report.failures.is_empty() { 0 } else { 1 })
+}
+
+/// Genera lo snippet di bootstrap per una nuova macchina
+fn run_bootstrap_script(matches: &ArgMatches, config_path: Option<&str>) -> Result<i32> {
+    let config = Config::load(config_path)?;
+
+    let options = BootstrapOptions {
+        stacks: matches.get_many::<String>("stack").unwrap_or_default().cloned().collect(),
+        format: BootstrapFormat::from_str(matches.get_one::<String>("format").map(String::as_str).unwrap_or("shell"))?,
+        release_url: matches.get_one::<String>("release-url").cloned()
+            .unwrap_or_else(|| bootstrap::DEFAULT_RELEASE_URL.to_string()),
+        install_service: matches.get_flag("service"),
+    };
+
+    let script = bootstrap::bootstrap_script(&config, &options)?;
+
+    match matches.get_one::<String>("output") {
+        Some(output) => {
+            std::fs::write(output, &script)
+                .context(format!("Failed to write bootstrap script: {}", output))?;
+            say!("Script di bootstrap scritto in {}", output);
+        },
+        None => print!("{}", script),
+    }
+
+    Ok(0)
+}
+
+/// Costruisce un'immagine container applicando gli stack
+fn run_build_image(matches: &ArgMatches, config_path: Option<&str>) -> Result<i32> {
+    let config = Config::load(config_path)?;
+
+    let engine = match matches.get_one::<String>("engine") {
+        Some(engine) => ContainerEngine::from_str(engine)?,
+        None => ContainerEngine::detect()?,
+    };
+
+    let options = ImageOptions {
+        engine,
+        base_image: matches.get_one::<String>("from").cloned().unwrap_or_default(),
+        tag: matches.get_one::<String>("tag").cloned().unwrap_or_default(),
+        stacks: matches.get_many::<String>("stack").unwrap_or_default().cloned().collect(),
+        keep_galatea: matches.get_flag("keep-galatea"),
+    };
+
+    say!("Costruzione di {} da {} con {}", options.tag, options.base_image, engine.to_str());
+    container::build_image(&config, &options)?;
+    say!("Immagine {} creata", options.tag);
+
+    Ok(0)
+}
+
+/// Applica gli stack a più host via SSH, a lotti
+fn run_rollout(matches: &ArgMatches, config_path: Option<&str>) -> Result<i32> {
+    let config = Config::load(config_path)?;
+
+    let mut hosts: Vec<String> = matches.get_many::<String>("hosts").unwrap_or_default()
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+        .collect();
+    if let Some(file) = matches.get_one::<String>("hosts-file") {
+        for host in rollout::read_hosts_file(Path::new(file))? {
+            if !hosts.contains(&host) {
+                hosts.push(host);
+            }
+        }
+    }
+
+    let options = RolloutOptions {
+        hosts,
+        stacks: matches.get_many::<String>("stack").unwrap_or_default().cloned().collect(),
+        batch_size: BatchSize::from_str(matches.get_one::<String>("batch-size").map(String::as_str).unwrap_or("1"))?,
+        max_fail_percent: matches.get_one::<u8>("max-fail-percent").copied().unwrap_or(0),
+        pause: Duration::from_secs(matches.get_one::<u64>("pause").copied().unwrap_or(0)),
+        release_url: matches.get_one::<String>("release-url").cloned()
+            .unwrap_or_else(|| bootstrap::DEFAULT_RELEASE_URL.to_string()),
+        ssh_options: matches.get_many::<String>("ssh-option").unwrap_or_default().cloned().collect(),
+        preflight: PreflightPolicy::from_str(matches.get_one::<String>("preflight").map(String::as_str).unwrap_or("abort"))?,
+    };
+
+    if matches.get_flag("check") {
+        let requires_ansible = rollout::stacks_require_ansible(&config, &options.stacks)?;
+        let preflight = rollout::preflight(&options.hosts, &options.ssh_options, requires_ansible);
+        print_preflight(&preflight);
+        return Ok(if preflight.iter().all(|p| p.passed()) { 0 } else { 1 });
+    }
+
+    let per_batch = options.batch_size.hosts_per_batch(options.hosts.len());
+    say!("Rollout di {} su {} host, {} per lotto", options.stacks.join(", "), options.hosts.len(), per_batch);
+    let prompt = format!("Applicare gli stack a {} host?", options.hosts.len());
+    if !confirm_action(&config, false, matches.get_flag("yes"), &prompt)? {
+        say!("Operazione annullata");
+        return Ok(1);
+    }
+
+    let report = rollout::rollout(&config, &options)?;
+
+    if !report.preflight.is_empty() {
+        print_preflight(&report.preflight);
+    }
+
+    say!("");
+    for host in &report.hosts {
+        let batch = if host.batch == 0 { "-".to_string() } else { host.batch.to_string() };
+        let line = format!("  [lotto {}] {:<30} {:<12} {:>5}s", batch, host.host, host.status.label(), host.duration.as_secs());
+        match &host.error {
+            Some(error) => say!("{}  {}", line, error),
+            None => say!("{}", line),
+        }
+    }
+    say!("");
+    say!("Completati: {}, falliti: {}, saltati: {}",
+         report.count(HostStatus::Succeeded), report.count(HostStatus::Failed), report.count(HostStatus::Skipped));
+    if report.aborted && report.preflight.iter().any(|p| !p.passed()) && options.preflight == PreflightPolicy::Abort {
+        eprintln!("Rollout interrotto: alcuni host non hanno superato il controllo preliminare (usa --preflight skip per saltarli)");
+    } else if report.aborted {
+        eprintln!("Rollout interrotto: superata la percentuale massima di host falliti ({}%)", options.max_fail_percent);
+    }
+
+    Ok(if report.is_ok() { 0 } else { 1 })
+}
+
+/// Stampa il rapporto del controllo preliminare degli host
+fn print_preflight(preflight: &[HostPreflight]) {
+    let mark = |ok: bool| if ok { "sì" } else { "no" };
+
+    say!("Controllo preliminare:");
+    say!("  {:<30} {:<6} {:<6} {:<7} {:<8} {}", "HOST", "SSH", "SUDO", "PYTHON", "ANSIBLE", "ESITO");
+    for host in preflight {
+        let outcome = if host.passed() { "ok".to_string() } else { host.problems.join(", ") };
+        say!("  {:<30} {:<6} {:<6} {:<7} {:<8} {}",
+             host.host,
+             mark(host.reachable),
+             mark(host.sudo),
+             mark(host.python.is_some()),
+             mark(host.ansible.is_some()),
+             outcome);
+    }
+}
+
+/// Sposta configurazione e dati in un'altra disposizione
+fn run_migrate_layout(matches: &ArgMatches, config_path: Option<&str>) -> Result<i32> {
+    let config = Config::load(config_path)?;
+    let target = Layout::from_str(matches.get_one::<String>("to").map(String::as_str).unwrap_or("user"))?;
+    let plan = layout::plan_migration(&config, target);
+
+    if plan.is_empty() {
+        say!("La configurazione usa già la disposizione {}", target.to_str());
+        return Ok(0);
+    }
+
+    say!("Migrazione alla disposizione {}:", target.to_str());
+    for m in &plan.moves {
+        say!("  {} -> {}", m.from.display(), m.to.display());
+    }
+    match &plan.config_from {
+        Some(from) => say!("  {} -> {}", from.display(), plan.config_to.display()),
+        None => say!("  configurazione -> {}", plan.config_to.display()),
+    }
+
+    if matches.get_flag("dry-run") {
+        return Ok(0);
+    }
+
+    if !confirm_action(&config, true, matches.get_flag("yes"), "Procedere con la migrazione?")? {
+        println!("Operazione annullata");
+        return Ok(1);
+    }
+
+    layout::migrate(&config, &plan)?;
+    say!("Migrazione completata: configurazione in {}", plan.config_to.display());
+    say!("I log esistenti non sono stati spostati; i nuovi vengono scritti in {}", target.log_dir().display());
+    Ok(0)
+}
+
+/// Esegue un sottocomando registrandone comandi, ambiente e output in un bundle
+fn run_record(matches: &ArgMatches, config_path: Option<&str>, read_only: bool) -> Result<i32> {
+    let (name, sub_matches) = matches.subcommand()
+        .ok_or_else(|| anyhow!("Missing subcommand to record"))?;
+    let output = matches.get_one::<String>("output")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(format!("galatea-replay-{}.tar.gz", Local::now().format("%Y%m%d-%H%M%S"))));
+
+    replay::start(&output, std::env::args().collect())?;
+    let result = run(name, sub_matches, config_path, read_only);
+
+    // Il bundle viene creato anche se il sottocomando fallisce: è il caso più utile
+    match replay::finish(&result) {
+        Ok(path) => say!("Esecuzione registrata in {}", path.display()),
+        Err(e) => eprintln!("Creazione del bundle di registrazione fallita: {:#}", e),
+    }
+
+    result
+}
+
+/// Mostra un'esecuzione registrata con `galatea record`
+fn run_replay(matches: &ArgMatches) -> Result<i32> {
+    let path = matches.get_one::<String>("bundle")
+        .ok_or_else(|| anyhow!("Missing replay bundle"))?;
+    if let Some(key) = matches.get_one::<String>("key") {
+        state_crypt::set_key_path(PathBuf::from(key));
+    }
+    let bundle = replay::load(Path::new(path))?;
+
+    print_replay_header(&bundle.manifest);
+
+    if let Some(index) = matches.get_one::<usize>("step") {
+        let step = bundle.steps.iter()
+            .find(|step| step.index == *index)
+            .ok_or_else(|| anyhow!("Step {} not found: the bundle has {} steps", index, bundle.steps.len()))?;
+        print_replay_step(step, bundle.steps.len());
+    } else if matches.get_flag("interactive") {
+        for step in &bundle.steps {
+            print_replay_step(step, bundle.steps.len());
+            if step.index == bundle.steps.len() {
+                break;
+            }
+
+            eprint!("Invio per il passo successivo, q per uscire: ");
+            let _ = io::stderr().flush();
+            let mut answer = String::new();
+            if io::stdin().lock().read_line(&mut answer).is_err() || answer.trim().eq_ignore_ascii_case("q") {
+                break;
+            }
+        }
+    } else {
+        println!();
+        for step in &bundle.steps {
+            println!("{:>4}  {}  {:<8} {:>8}  {:<18} {}",
+                     step.index,
+                     replay_time(&step.started_at),
+                     step.kind,
+                     format_duration_ms(step.duration_ms),
+                     replay_outcome(step),
+                     step.command_line());
+        }
+        if !bundle.steps.is_empty() {
+            println!("\nDettagli di un passo: galatea replay {} --step N", path);
+        }
+    }
+
+    if matches.get_flag("log") {
+        match &bundle.log {
+            Some(log) => println!("\n--- Log ---\n{}", log.trim_end()),
+            None => println!("\nIl bundle non contiene il log dell'esecuzione"),
+        }
+    }
+
+    Ok(0)
+}
+
+/// Stampa le informazioni generali di un'esecuzione registrata
+fn print_replay_header(manifest: &Manifest) {
+    println!("Esecuzione: {}", manifest.command.join(" "));
+    println!("Host: {} (Galatea {})", manifest.hostname, manifest.version);
+    println!("Dalle {} alle {}", replay_time(&manifest.started_at), replay_time(&manifest.finished_at));
+    match (&manifest.error, manifest.exit_code) {
+        (Some(error), _) => println!("Esito: errore ({}): {}", manifest.error_kind.as_deref().unwrap_or("other"), error),
+        (None, Some(code)) => println!("Esito: codice di uscita {}", code),
+        (None, None) => println!("Esito: sconosciuto"),
+    }
+    println!("Passi registrati: {}", manifest.steps);
+}
+
+/// Stampa i dettagli di un passo registrato
+fn print_replay_step(step: &Step, total: usize) {
+    println!("\n=== Passo {}/{} - {} ({}) ===", step.index, total, step.kind, replay_outcome(step));
+    println!("Comando: {}", step.command_line());
+    if let Some(cwd) = &step.cwd {
+        println!("Directory: {}", cwd);
+    }
+    println!("Avvio: {}, durata {}", step.started_at, format_duration_ms(step.duration_ms));
+    if let Some(run_id) = &step.run_id {
+        println!("Esecuzione: {}", run_id);
+    }
+    if !step.env.is_empty() {
+        println!("Ambiente:");
+        for (name, value) in &step.env {
+            println!("  {}={}", name, value);
+        }
+    }
+    if !step.extra_vars.is_empty() {
+        println!("Extra vars:");
+        for (name, value) in &step.extra_vars {
+            println!("  {}: {}", name, value);
+        }
+    }
+    if let Some(error) = &step.error {
+        println!("Errore ({}): {}", step.error_kind.as_deref().unwrap_or("other"), error);
+    }
+    if !step.stdout.is_empty() {
+        println!("--- stdout ---\n{}", step.stdout.trim_end());
+    }
+    if !step.stderr.is_empty() {
+        println!("--- stderr ---\n{}", step.stderr.trim_end());
+    }
+}
+
+/// Descrive l'esito di un passo registrato
+fn replay_outcome(step: &Step) -> String {
+    match (step.succeeded(), step.exit_code) {
+        (true, _) => "ok".to_string(),
+        (false, Some(code)) => format!("fallito, codice {}", code),
+        (false, None) => "fallito".to_string(),
+    }
+}
+
+/// Restituisce l'ora di un istante registrato in RFC 3339
+fn replay_time(timestamp: &str) -> String {
+    DateTime::parse_from_rfc3339(timestamp)
+        .map(|time| time.format("%H:%M:%S").to_string())
+        .unwrap_or_else(|_| timestamp.to_string())
+}
+
+/// Formatta una durata in millisecondi, es. "850ms" o "12.3s"
+fn format_duration_ms(ms: u64) -> String {
+    if ms < 1000 {
+        format!("{}ms", ms)
+    } else {
+        format!("{:.1}s", ms as f64 / 1000.0)
+    }
+}
@@ -1,17 +1,54 @@
 //! Gestione della configurazione per Galatea
 //!
 //! Questo modulo gestisce il caricamento e il salvataggio della configurazione dell'applicazione
-//! utilizzando YAML.
+//! in YAML oppure in TOML, riconosciuto dall'estensione `.toml` del file.
+//!
+//! Ogni opzione può essere sovrascritta da una variabile d'ambiente `GALATEA_<OPZIONE>`
+//! (es. `GALATEA_TASKS_DIR`, `GALATEA_DOWNLOAD_TIMEOUT`), applicata sopra il file: utile
+//! in container e pipeline CI, dove modificare il file è scomodo.
 
 use std::path::{Path, PathBuf};
 use std::fs;
-use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::env;
+use anyhow::{Context, Result, anyhow};
 use serde::{Serialize, Deserialize};
-use log::{info, warn};
+use serde_yaml::Value;
+use log::{debug, info, warn};
+
+use crate::layout::Layout;
+use crate::approval::ApprovalConfig;
+use crate::child_env::EnvPolicy;
+use crate::config_migration::{self, MigrationReport, UnsupportedVersion};
+use crate::fleet::PhoneHomeConfig;
+use crate::maintenance::MaintenanceConfig;
+use crate::error::ResultExt;
+use crate::notify::SmtpConfig;
+use crate::preflight;
+use crate::secrets;
+use crate::source::{HostPolicy, Source};
+use crate::state_crypt;
+use crate::watchdog::WatchdogConfig;
+
+/// Prefisso delle variabili d'ambiente che sovrascrivono le opzioni
+pub const ENV_PREFIX: &str = "GALATEA_";
+
+/// Variabile d'ambiente con il percorso del file di configurazione
+pub const CONFIG_ENV: &str = "GALATEA_CONFIG";
+
+/// Nomi brevi accettati per le opzioni più usate
+const ENV_ALIASES: &[(&str, &str)] = &[
+    ("TIMEOUT", "download_timeout"),
+    ("THEME", "ui_theme"),
+];
 
 /// Struttura principale di configurazione per Galatea
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
+    /// Versione dello schema con cui è scritto il file (vedi `config_migration`)
+    #[serde(default)]
+    pub config_version: u32,
+
     /// Directory per i task
     pub tasks_dir: String,
 
@@ -24,34 +61,402 @@ pub struct Config {
     /// Timeout per il download in secondi
     pub download_timeout: u64,
 
+    /// Conserva i file scaricati per riusarli con richieste condizionali (ETag) e per
+    /// riprendere i download interrotti
+    #[serde(default = "default_download_cache")]
+    pub download_cache: bool,
+
+    /// Directory in cui creare le copie di lavoro dei task per ogni esecuzione
+    /// (predefinita: `runs` nella directory di stato)
+    #[serde(default)]
+    pub runs_dir: Option<String>,
+
     /// Tema dell'interfaccia utente
     pub ui_theme: String,
 
-    /// URL delle sorgenti dei task
-    pub task_sources: Vec<String>,
+    /// Modalità di visualizzazione delle liste di task e stack (compact, detailed)
+    #[serde(default)]
+    pub list_mode: ListMode,
+
+    /// Sorgenti dei task (URL semplici o con opzioni di verifica)
+    pub task_sources: Vec<Source>,
+
+    /// Sorgenti degli stack (URL semplici o con opzioni di verifica)
+    pub stack_sources: Vec<Source>,
 
-    /// URL delle sorgenti degli stack
-    pub stack_sources: Vec<String>,
+    /// Backend per gli snapshot di sistema (auto, lvm, btrfs, zfs, timeshift, none)
+    #[serde(default = "default_snapshot_backend")]
+    pub snapshot_backend: String,
+
+    /// Dimensione riservata agli snapshot LVM (formato lvcreate, es. "2G")
+    #[serde(default = "default_snapshot_lvm_size")]
+    pub snapshot_lvm_size: String,
+
+    /// Azioni che richiedono una conferma (none, destructive-only, all)
+    #[serde(default = "default_confirm_actions")]
+    pub confirm_actions: ConfirmPolicy,
+
+    /// Modalità di sola lettura: stati, log e drift restano consultabili ma le azioni
+    /// che modificano il sistema o la configurazione sono disabilitate
+    #[serde(default)]
+    pub read_only: bool,
+
+    /// Ruolo assegnato a questa macchina (uno dei ruoli definiti in `roles`)
+    #[serde(default)]
+    pub role: Option<String>,
+
+    /// Ruoli disponibili: per ogni ruolo gli stack che devono essere installati
+    #[serde(default)]
+    pub roles: BTreeMap<String, Vec<String>>,
+
+    /// Intervallo in minuti tra le esecuzioni pianificate in modalità demone
+    #[serde(default = "default_schedule_interval")]
+    pub schedule_interval: u64,
+
+    /// Fallimenti consecutivi dopo cui un task va in quarantena (0 disattiva la quarantena)
+    #[serde(default = "default_quarantine_after")]
+    pub quarantine_after: u32,
+
+    /// Server SMTP per l'invio dei riepiloghi delle esecuzioni pianificate
+    #[serde(default)]
+    pub smtp: Option<SmtpConfig>,
+
+    /// Endpoint che deve approvare i piani prima della loro esecuzione
+    #[serde(default)]
+    pub approval: Option<ApprovalConfig>,
+
+    /// Endpoint centrale a cui inviare periodicamente lo stato della macchina
+    #[serde(default)]
+    pub phone_home: Option<PhoneHomeConfig>,
+
+    /// Azioni che tolgono la macchina dal bilanciatore di carico durante l'installazione
+    /// degli stack con `maintenance: true`
+    #[serde(default)]
+    pub maintenance: Option<MaintenanceConfig>,
+
+    /// File con l'identità age per decifrare i valori cifrati
+    #[serde(default)]
+    pub age_identity: Option<String>,
+
+    /// Destinatari age per cifrare i valori delle variabili segrete salvati
+    #[serde(default)]
+    pub age_recipients: Vec<String>,
+
+    /// File con la password per decifrare i valori Ansible Vault
+    #[serde(default)]
+    pub vault_password_file: Option<String>,
+
+    /// Cifra a riposo i file di stato e gli output catturati con la chiave della macchina
+    #[serde(default)]
+    pub encrypt_state: bool,
+
+    /// File della chiave con cui viene cifrato lo stato, predefinito `state.key` accanto al
+    /// file di configurazione
+    #[serde(default)]
+    pub state_key: Option<String>,
+
+    /// Host da cui è consentito scaricare bundle e cataloghi
+    #[serde(default)]
+    pub download_hosts: HostPolicy,
+
+    /// Token per scaricare gli asset delle release private, per host (es. `github.com`);
+    /// i valori possono essere cifrati
+    #[serde(default)]
+    pub release_tokens: BTreeMap<String, String>,
+
+    /// Variabili d'ambiente di ansible-playbook, che si aggiungono o sostituiscono quelle
+    /// predefinite (es. `ANSIBLE_STDOUT_CALLBACK`, `ANSIBLE_NO_LOG`)
+    #[serde(default)]
+    pub ansible_env: BTreeMap<String, String>,
+
+    /// Variabili d'ambiente di Galatea passate a script, playbook e comandi dei task, che
+    /// altrimenti partono con un ambiente pulito
+    #[serde(default)]
+    pub child_env: EnvPolicy,
+
+    /// Sorveglianza di script e playbook: battito nel log e segnalazione di quelli che
+    /// restano senza output
+    #[serde(default)]
+    pub watchdog: WatchdogConfig,
+
+    /// Confronta pacchetti, servizi e porte prima e dopo ogni installazione e ne mostra
+    /// le differenze
+    #[serde(default = "default_change_summary")]
+    pub change_summary: bool,
+
+    /// Directory degli hook eseguiti dopo ogni operazione (`post-<operazione>.d`), predefinita
+    /// `hooks` accanto al file di configurazione
+    #[serde(default)]
+    pub hooks_dir: Option<String>,
+
+    /// Durata massima di ogni hook in secondi
+    #[serde(default = "default_hook_timeout")]
+    pub hook_timeout: u64,
+
+    /// Script rhai che modifica i fatti della macchina usati da `when` e dalle variabili
+    /// derivate degli stack
+    #[serde(default)]
+    pub fact_transform: Option<String>,
+
+    /// Directory di cui ripristinare le etichette SELinux (restorecon) dopo ogni installazione
+    #[serde(default)]
+    pub restorecon_dirs: Vec<String>,
+
+    /// Task che non devono mai essere installati su questa macchina (`nome` o `sorgente:nome`)
+    #[serde(default)]
+    pub denied_tasks: Vec<String>,
+
+    /// Tag dei task che non devono mai essere installati su questa macchina
+    #[serde(default)]
+    pub denied_tags: Vec<String>,
+
+    /// Indica se il file di configurazione è cifrato con SOPS
+    #[serde(skip)]
+    pub sops_encrypted: bool,
+
+    /// Migrazioni dello schema applicate al caricamento del file
+    #[serde(skip)]
+    pub migration: Option<MigrationReport>,
 
     /// Percorso del file di configurazione caricato
     #[serde(skip)]
     pub config_file_path: Option<PathBuf>,
+
+    /// Opzioni sovrascritte dall'ambiente, con il valore originale del file
+    #[serde(skip)]
+    pub env_overrides: BTreeMap<String, Value>,
+}
+
+/// Formato del file di configurazione
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Yaml,
+    Toml,
+}
+
+impl ConfigFormat {
+    /// Determina il formato dall'estensione del file (`.toml`, altrimenti YAML)
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => ConfigFormat::Toml,
+            _ => ConfigFormat::Yaml,
+        }
+    }
+
+    /// Restituisce il nome del formato
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            ConfigFormat::Yaml => "YAML",
+            ConfigFormat::Toml => "TOML",
+        }
+    }
+
+    /// Legge il documento di configurazione senza interpretarlo
+    pub fn parse_document(&self, content: &str) -> Result<Value> {
+        match self {
+            ConfigFormat::Yaml => serde_yaml::from_str(content).config_context("Invalid YAML configuration"),
+            ConfigFormat::Toml => toml::from_str(content).config_context("Invalid TOML configuration"),
+        }
+    }
+
+    /// Serializza un documento di configurazione nel formato
+    ///
+    /// TOML non ha un valore nullo: le opzioni non impostate vengono omesse.
+    pub fn serialize(&self, document: &Value) -> Result<String> {
+        match self {
+            ConfigFormat::Yaml => serde_yaml::to_string(document).context("Failed to serialize configuration as YAML"),
+            ConfigFormat::Toml => toml::to_string(&without_nulls(document)).context("Failed to serialize configuration as TOML"),
+        }
+    }
+}
+
+/// Legge un file di configurazione, decifrandolo se è cifrato con SOPS
+///
+/// Il documento viene portato alla versione corrente dello schema prima di essere interpretato.
+fn read_config(path: &Path, format: ConfigFormat, content: &str) -> Result<Config> {
+    let document = format.parse_document(content)?;
+    let sops_encrypted = secrets::is_sops_document(&document);
+
+    // L'identità age per SOPS può essere indicata solo fuori dal file cifrato
+    let mut document = if sops_encrypted {
+        secrets::open_document(path, document, None)?
+    } else {
+        secrets::untag(document)
+    };
+    let migration = config_migration::migrate(&mut document)?;
+
+    let mut config: Config = if sops_encrypted {
+        serde_yaml::from_value(document).config_context("Invalid SOPS-encrypted configuration")?
+    } else {
+        serde_yaml::from_value(document).config_context(format!("Invalid {} configuration", format.to_str()))?
+    };
+    config.sops_encrypted = sops_encrypted;
+    config.migration = migration;
+    Ok(config)
+}
+
+/// Riscrive un file di configurazione migrato alla versione corrente dello schema
+///
+/// Il file originale viene conservato accanto al nuovo come `<file>.v<versione>.bak`. I
+/// file cifrati con SOPS e quelli in sola lettura non vengono riscritti: la migrazione
+/// viene ripetuta a ogni caricamento.
+fn save_migrated(config: &Config, path: &PathBuf, migration: &MigrationReport) -> Result<()> {
+    if config.read_only {
+        return Ok(());
+    }
+    if config.sops_encrypted {
+        warn!("La configurazione cifrata {:?} è della versione {}: aggiornala con `sops` alla versione {}",
+              path, migration.from, migration.to);
+        return Ok(());
+    }
+
+    let mut backup = path.clone().into_os_string();
+    backup.push(format!(".v{}.bak", migration.from));
+    fs::copy(path, &backup)
+        .context(format!("Impossibile salvare la copia della configurazione in: {:?}", backup))?;
+    config.save(path)?;
+
+    info!("Configurazione {:?} migrata dalla versione {} alla {} (originale in {:?})",
+          path, migration.from, migration.to, backup);
+    Ok(())
+}
+
+/// Rimuove ricorsivamente le voci nulle da un documento
+fn without_nulls(value: &Value) -> Value {
+    match value {
+        Value::Mapping(fields) => Value::Mapping(fields.iter()
+            .filter(|(_, v)| !v.is_null())
+            .map(|(k, v)| (k.clone(), without_nulls(v)))
+            .collect()),
+        Value::Sequence(items) => Value::Sequence(items.iter()
+            .filter(|v| !v.is_null())
+            .map(without_nulls)
+            .collect()),
+        other => other.clone(),
+    }
+}
+
+/// Politica di conferma delle azioni
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConfirmPolicy {
+    /// Nessuna conferma richiesta
+    None,
+
+    /// Conferma solo per le azioni distruttive (reinstallazione, ripristino, rimozione)
+    DestructiveOnly,
+
+    /// Conferma per tutte le azioni che modificano il sistema
+    All,
+}
+
+impl ConfirmPolicy {
+    /// Verifica se un'azione richiede conferma
+    ///
+    /// # Arguments
+    ///
+    /// * `destructive` - Indica se l'azione è distruttiva
+    pub fn requires_confirmation(&self, destructive: bool) -> bool {
+        match self {
+            ConfirmPolicy::None => false,
+            ConfirmPolicy::DestructiveOnly => destructive,
+            ConfirmPolicy::All => true,
+        }
+    }
+
+    /// Restituisce il nome della politica come in configurazione
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            ConfirmPolicy::None => "none",
+            ConfirmPolicy::DestructiveOnly => "destructive-only",
+            ConfirmPolicy::All => "all",
+        }
+    }
+}
+
+/// Modalità di visualizzazione delle liste nella TUI
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ListMode {
+    /// Solo stato e nome
+    Compact,
+
+    /// Stato, tipo, nome, sorgente, versione e tag in colonne
+    #[default]
+    Detailed,
+}
+
+impl ListMode {
+    /// Restituisce il nome della modalità come in configurazione
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            ListMode::Compact => "compact",
+            ListMode::Detailed => "detailed",
+        }
+    }
+
+    /// Restituisce l'altra modalità
+    pub fn toggle(&self) -> Self {
+        match self {
+            ListMode::Compact => ListMode::Detailed,
+            ListMode::Detailed => ListMode::Compact,
+        }
+    }
 }
 
 impl Config {
-    /// Crea una nuova configurazione con valori di default relativi alla directory dell'eseguibile
+    /// Crea una nuova configurazione con valori di default
+    ///
+    /// Le directory sono quelle della disposizione dell'utente corrente: di sistema
+    /// (`/var/lib/galatea`) come root, XDG da utente normale.
     pub fn default() -> Self {
-        let base_dir = get_base_directory();
+        let dirs = Layout::current().data_dirs();
 
         Config {
-            tasks_dir: base_dir.join("tasks").to_string_lossy().to_string(),
-            stacks_dir: base_dir.join("stacks").to_string_lossy().to_string(),
-            state_dir: base_dir.join("state").to_string_lossy().to_string(),
+            config_version: config_migration::CURRENT_VERSION,
+            tasks_dir: dirs.tasks_dir.to_string_lossy().to_string(),
+            stacks_dir: dirs.stacks_dir.to_string_lossy().to_string(),
+            state_dir: dirs.state_dir.to_string_lossy().to_string(),
             download_timeout: 60,
+            download_cache: true,
+            runs_dir: None,
             ui_theme: "default".to_string(),
+            list_mode: ListMode::default(),
             task_sources: Vec::new(),
             stack_sources: Vec::new(),
+            snapshot_backend: default_snapshot_backend(),
+            snapshot_lvm_size: default_snapshot_lvm_size(),
+            confirm_actions: default_confirm_actions(),
+            read_only: false,
+            role: None,
+            roles: BTreeMap::new(),
+            schedule_interval: default_schedule_interval(),
+            quarantine_after: default_quarantine_after(),
+            smtp: None,
+            approval: None,
+            phone_home: None,
+            maintenance: None,
+            age_identity: None,
+            age_recipients: Vec::new(),
+            vault_password_file: None,
+            encrypt_state: false,
+            state_key: None,
+            download_hosts: HostPolicy::default(),
+            release_tokens: BTreeMap::new(),
+            ansible_env: BTreeMap::new(),
+            child_env: EnvPolicy::default(),
+            watchdog: WatchdogConfig::default(),
+            change_summary: default_change_summary(),
+            hooks_dir: None,
+            hook_timeout: default_hook_timeout(),
+            fact_transform: None,
+            restorecon_dirs: Vec::new(),
+            denied_tasks: Vec::new(),
+            denied_tags: Vec::new(),
+            sops_encrypted: false,
+            migration: None,
             config_file_path: None,
+            env_overrides: BTreeMap::new(),
         }
     }
 
@@ -61,11 +466,24 @@ impl Config {
     }
 
     /// Carica la configurazione da un file
+    ///
+    /// Senza un percorso esplicito viene usato quello in `GALATEA_CONFIG`, se impostato.
+    /// Le variabili d'ambiente `GALATEA_*` sono applicate sopra il file caricato.
     pub fn load(path: Option<&str>) -> Result<Self> {
+        let env_path = env::var(CONFIG_ENV).ok().filter(|p| !p.is_empty());
+        let path = path.or(env_path.as_deref());
+
         // Definisci i percorsi possibili da cui caricare la configurazione
-        let config_paths = if let Some(explicit_path) = path {
+        let config_paths: Vec<PathBuf> = if let Some(explicit_path) = path {
             // Se è stato specificato un percorso, usa solo quello
             vec![PathBuf::from(explicit_path)]
+        } else if Layout::current() == Layout::User {
+            // Da utente normale la configurazione XDG ha la precedenza
+            vec![
+                Layout::User.config_path(),  // ~/.config/galatea/galatea.yaml
+                get_system_config_path(),    // /etc/galatea/galatea.yaml
+                get_binary_config_path(),    // ./galatea.yaml
+            ]
         } else {
             // Altrimenti, cerca nei percorsi predefiniti
             vec![
@@ -74,6 +492,15 @@ impl Config {
             ]
         };
 
+        // Accanto a ogni galatea.yaml predefinito viene cercato anche galatea.toml
+        let config_paths: Vec<PathBuf> = if path.is_some() {
+            config_paths
+        } else {
+            config_paths.into_iter()
+                .flat_map(|p| [p.clone(), p.with_extension("toml")])
+                .collect()
+        };
+
         // Prova a caricare da ogni percorso nell'ordine specificato
         let mut config_loaded = false;
         let mut config = Config::default();
@@ -82,18 +509,28 @@ impl Config {
         for config_path in config_paths {
             if config_path.exists() {
                 info!("Tentativo di caricamento della configurazione da: {:?}", config_path);
+                let format = ConfigFormat::from_path(&config_path);
                 match fs::read_to_string(&config_path) {
-                    Ok(yaml_content) => {
-                        match serde_yaml::from_str::<Config>(&yaml_content) {
+                    Ok(content) => {
+                        match read_config(&config_path, format, &content) {
                             Ok(loaded_config) => {
                                 config = loaded_config;
                                 info!("Configurazione caricata da: {:?}", &config_path);
+                                if let Some(migration) = &config.migration {
+                                    info!("Migrazioni della configurazione applicate: {}", migration.applied.join(", "));
+                                    if let Err(e) = save_migrated(&config, &config_path, migration) {
+                                        warn!("Impossibile riscrivere la configurazione migrata {:?}: {:#}", config_path, e);
+                                    }
+                                }
                                 config_file_path = Some(config_path);
                                 config_loaded = true;
                                 break;
                             },
+                            Err(e) if e.downcast_ref::<UnsupportedVersion>().is_some() => {
+                                return Err(anyhow!("Impossibile caricare la configurazione da {:?}: {}", config_path, e));
+                            },
                             Err(e) => {
-                                warn!("Errore nel parsing della configurazione YAML da {:?}: {}", config_path, e);
+                                warn!("Errore nel parsing della configurazione {} da {:?}: {:#}", format.to_str(), config_path, e);
                             }
                         }
                     },
@@ -109,7 +546,10 @@ impl Config {
             let default_config = Config::default();
             
             // Determina dove salvare la configurazione di default
-            let default_config_path = get_binary_config_path();
+            let default_config_path = match Layout::current() {
+                Layout::System => get_binary_config_path(),
+                Layout::User => Layout::User.config_path(),
+            };
             
             if let Err(e) = default_config.save(&default_config_path) {
                 warn!("Impossibile salvare la configurazione di default in {:?}: {}", default_config_path, e);
@@ -125,14 +565,24 @@ impl Config {
         // Imposta il percorso del file di configurazione
         config.config_file_path = config_file_path;
 
+        // Le variabili d'ambiente hanno la precedenza sul file
+        config.apply_overrides(env::vars())?;
+
         // Crea le directory se non esistono
         create_directories(&config)?;
 
+        state_crypt::configure(&config);
+
         Ok(config)
     }
 
     /// Salva la configurazione in un file
     pub fn save(&self, path: &PathBuf) -> Result<()> {
+        // Riscrivere un file SOPS lo salverebbe in chiaro
+        if self.sops_encrypted {
+            return Err(anyhow!("The configuration file is encrypted with SOPS: edit it with `sops` instead"));
+        }
+
         // Assicurati che la directory esista
         if let Some(parent) = path.parent() {
             if !parent.exists() {
@@ -141,18 +591,83 @@ impl Config {
             }
         }
 
-        // Serializza la configurazione in YAML
-        let yaml_content = serde_yaml::to_string(self)
+        // Le opzioni sovrascritte dall'ambiente mantengono nel file il valore originale
+        let mut document = serde_yaml::to_value(self)
             .context("Impossibile serializzare la configurazione in YAML")?;
+        if let Value::Mapping(fields) = &mut document {
+            for (key, original) in &self.env_overrides {
+                fields.insert(Value::String(key.clone()), original.clone());
+            }
+        }
+
+        // Serializza la configurazione nel formato indicato dall'estensione
+        let content = ConfigFormat::from_path(path).serialize(&document)
+            .context("Impossibile serializzare la configurazione")?;
 
         // Salva la configurazione
-        fs::write(path, yaml_content)
+        fs::write(path, content)
             .context(format!("Impossibile salvare la configurazione in: {:?}", path))?;
 
         info!("Configurazione salvata in: {:?}", path);
         Ok(())
     }
 
+    /// Applica le sovrascritture `GALATEA_*` alle opzioni della configurazione
+    ///
+    /// Il nome dell'opzione si ottiene togliendo il prefisso e convertendo in minuscolo
+    /// (`GALATEA_STATE_DIR` → `state_dir`); sono accettati anche gli alias `GALATEA_TIMEOUT`
+    /// e `GALATEA_THEME`. Il valore è letto come YAML, quindi numeri, liste (`[a, b]`) e
+    /// mappe sono ammessi; le opzioni di tipo stringa lo usano così com'è. Le variabili che
+    /// non corrispondono a un'opzione vengono ignorate.
+    ///
+    /// # Arguments
+    ///
+    /// * `vars` - Le variabili d'ambiente, come coppie nome-valore
+    pub fn apply_overrides<I: IntoIterator<Item = (String, String)>>(&mut self, vars: I) -> Result<()> {
+        let mut document = serde_yaml::to_value(&*self)
+            .context("Failed to serialize configuration")?;
+        let Value::Mapping(fields) = &mut document else {
+            return Err(anyhow!("Configuration is not a mapping"));
+        };
+
+        let mut originals = BTreeMap::new();
+        for (name, raw) in vars {
+            let Some(key) = env_option(&name) else { continue };
+            let key_value = Value::String(key.clone());
+
+            let Some(current) = fields.get(&key_value).cloned() else {
+                debug!("Ignoring environment variable {}: no configuration option {}", name, key);
+                continue;
+            };
+
+            let value = match current {
+                Value::String(_) => Value::String(raw),
+                _ => serde_yaml::from_str(&raw)
+                    .config_context(format!("Invalid value for {}: {}", name, raw))?,
+            };
+
+            info!("Configuration option {} overridden by {}", key, name);
+            fields.insert(key_value, value);
+            originals.entry(key).or_insert(current);
+        }
+
+        if originals.is_empty() {
+            return Ok(());
+        }
+
+        let mut config: Config = serde_yaml::from_value(document)
+            .config_context(format!("Invalid configuration after environment overrides ({})",
+                             originals.keys().cloned().collect::<Vec<_>>().join(", ")))?;
+        config.config_file_path = self.config_file_path.take();
+        config.env_overrides = std::mem::take(&mut self.env_overrides);
+        for (key, original) in originals {
+            config.env_overrides.entry(key).or_insert(original);
+        }
+
+        *self = config;
+        Ok(())
+    }
+
     /// Risolve un percorso relativo alle directory di configurazione
     pub fn resolve_path(&self, path: &str, base_dir: &str) -> PathBuf {
         let base = match base_dir {
@@ -165,10 +680,42 @@ impl Config {
         base.join(path)
     }
 
+    /// Restituisce la directory della cache dei download, se attiva
+    pub fn download_cache_dir(&self) -> Option<PathBuf> {
+        self.download_cache.then(|| self.resolve_path("downloads", "state"))
+    }
+
+    /// Restituisce la directory delle copie di lavoro delle esecuzioni
+    pub fn runs_dir(&self) -> PathBuf {
+        match &self.runs_dir {
+            Some(dir) => PathBuf::from(dir),
+            None => self.resolve_path("runs", "state"),
+        }
+    }
+
+    /// Restituisce la directory degli hook
+    pub fn hooks_dir(&self) -> PathBuf {
+        match &self.hooks_dir {
+            Some(dir) => PathBuf::from(dir),
+            None => Layout::current().config_path()
+                .parent()
+                .map(|dir| dir.join("hooks"))
+                .unwrap_or_else(|| PathBuf::from("hooks")),
+        }
+    }
+
+    /// Restituisce il file della chiave con cui viene cifrato lo stato
+    pub fn state_key_path(&self) -> PathBuf {
+        match &self.state_key {
+            Some(path) => PathBuf::from(path),
+            None => state_crypt::default_key_path(),
+        }
+    }
+
     /// Aggiunge una nuova sorgente di task
     pub fn add_task_source(&mut self, url: &str) -> bool {
-        if !self.task_sources.contains(&url.to_string()) {
-            self.task_sources.push(url.to_string());
+        if !self.task_sources.iter().any(|s| s.url == url) {
+            self.task_sources.push(Source::new(url));
             true
         } else {
             false
@@ -177,8 +724,8 @@ impl Config {
 
     /// Aggiunge una nuova sorgente di stack
     pub fn add_stack_source(&mut self, url: &str) -> bool {
-        if !self.stack_sources.contains(&url.to_string()) {
-            self.stack_sources.push(url.to_string());
+        if !self.stack_sources.iter().any(|s| s.url == url) {
+            self.stack_sources.push(Source::new(url));
             true
         } else {
             false
@@ -188,18 +735,73 @@ impl Config {
     /// Rimuove una sorgente di task
     pub fn remove_task_source(&mut self, url: &str) -> bool {
         let len = self.task_sources.len();
-        self.task_sources.retain(|u| u != url);
+        self.task_sources.retain(|s| s.url != url);
         self.task_sources.len() < len
     }
 
     /// Rimuove una sorgente di stack
     pub fn remove_stack_source(&mut self, url: &str) -> bool {
         let len = self.stack_sources.len();
-        self.stack_sources.retain(|u| u != url);
+        self.stack_sources.retain(|s| s.url != url);
         self.stack_sources.len() < len
     }
 }
 
+/// Backend di snapshot predefinito
+/// Restituisce l'opzione indicata da una variabile d'ambiente `GALATEA_*`
+fn env_option(name: &str) -> Option<String> {
+    if name == CONFIG_ENV {
+        return None;
+    }
+
+    let option = name.strip_prefix(ENV_PREFIX)?;
+    let option = ENV_ALIASES.iter()
+        .find(|(alias, _)| *alias == option)
+        .map(|(_, key)| key.to_string())
+        .unwrap_or_else(|| option.to_lowercase());
+
+    (!option.is_empty()).then_some(option)
+}
+
+fn default_snapshot_backend() -> String {
+    "auto".to_string()
+}
+
+/// Dimensione predefinita degli snapshot LVM
+fn default_snapshot_lvm_size() -> String {
+    "2G".to_string()
+}
+
+/// Politica di conferma predefinita
+fn default_confirm_actions() -> ConfirmPolicy {
+    ConfirmPolicy::DestructiveOnly
+}
+
+/// Cache dei download attiva per impostazione predefinita
+fn default_download_cache() -> bool {
+    true
+}
+
+/// Riepilogo delle modifiche al sistema attivo per impostazione predefinita
+fn default_change_summary() -> bool {
+    true
+}
+
+/// Durata massima predefinita di un hook (1 minuto)
+fn default_hook_timeout() -> u64 {
+    60
+}
+
+/// Intervallo predefinito tra le esecuzioni pianificate (6 ore)
+fn default_schedule_interval() -> u64 {
+    360
+}
+
+/// Fallimenti consecutivi predefiniti prima della quarantena di un task
+fn default_quarantine_after() -> u32 {
+    3
+}
+
 /// Crea le directory necessarie basate sulla configurazione
 fn create_directories(config: &Config) -> Result<()> {
     let dirs = [
@@ -209,9 +811,15 @@ fn create_directories(config: &Config) -> Result<()> {
     ];
 
     for dir in dirs.iter() {
-        if !Path::new(dir).exists() {
-            fs::create_dir_all(dir)
-                .context(format!("Impossibile creare la directory: {}", dir))?;
+        if Path::new(dir).exists() {
+            continue;
+        }
+        if let Err(e) = fs::create_dir_all(dir) {
+            // Il motivo, come un montaggio in sola lettura, viene indicato esplicitamente
+            return Err(match preflight::check_dir(Path::new(dir)) {
+                Some(problem) => anyhow!("Impossibile creare la directory: {}", problem),
+                None => anyhow!(e).context(format!("Impossibile creare la directory: {}", dir)),
+            });
         }
     }
 
@@ -259,12 +867,14 @@ pub fn create_example_config(path: &Path) -> Result<()> {
     config.add_task_source("https://example.com/tasks/monitoring.zip");
     config.add_stack_source("https://example.com/stacks/web_server.zip");
 
-    // Serializza la configurazione in YAML
-    let yaml_content = serde_yaml::to_string(&config)
-        .context("Impossibile serializzare la configurazione di esempio in YAML")?;
+    // Serializza la configurazione nel formato indicato dall'estensione
+    let document = serde_yaml::to_value(&config)
+        .context("Impossibile serializzare la configurazione di esempio")?;
+    let content = ConfigFormat::from_path(path).serialize(&document)
+        .context("Impossibile serializzare la configurazione di esempio")?;
 
     // Salva la configurazione di esempio
-    fs::write(path, yaml_content)
+    fs::write(path, content)
         .context(format!("Impossibile salvare la configurazione di esempio in: {:?}", path))?;
 
     info!("Configurazione di esempio creata in: {:?}", path);
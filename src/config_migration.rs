@@ -0,0 +1,108 @@
+//! Migrazioni dello schema della configurazione
+//!
+//! Ogni file di configurazione indica in `config_version` la versione dello schema con
+//! cui è stato scritto; i file senza questa opzione sono della versione 0. Al caricamento
+//! il documento viene portato alla versione corrente applicando in ordine le migrazioni
+//! di [`MIGRATIONS`], prima di essere interpretato: una modifica allo schema (un'opzione
+//! rinominata, un formato cambiato) si aggiunge come nuova migrazione e i file esistenti
+//! continuano a funzionare.
+
+use anyhow::{Result, anyhow};
+use serde_yaml::{Mapping, Value};
+use thiserror::Error;
+
+/// Versione corrente dello schema della configurazione
+pub const CURRENT_VERSION: u32 = 1;
+
+/// Nome dell'opzione con la versione dello schema
+pub const VERSION_KEY: &str = "config_version";
+
+/// Migrazione del documento di configurazione da una versione alla successiva
+struct Migration {
+    /// Versione a cui si applica la migrazione
+    from: u32,
+
+    /// Descrizione della modifica, riportata nel log
+    description: &'static str,
+
+    /// Funzione che modifica il documento
+    apply: fn(&mut Mapping) -> Result<()>,
+}
+
+/// Migrazioni dello schema, in ordine di versione
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: 0,
+        description: "aggiunta dell'opzione config_version",
+        apply: |_| Ok(()),
+    },
+];
+
+/// Configurazione scritta da una versione di Galatea più recente
+///
+/// Interpretare il file ignorando le opzioni sconosciute potrebbe cambiarne il significato:
+/// il caricamento si interrompe invece di ripiegare sulla configurazione predefinita.
+#[derive(Debug, Error)]
+#[error("Configuration version {found} is newer than the supported version {}: upgrade Galatea", CURRENT_VERSION)]
+pub struct UnsupportedVersion {
+    /// Versione indicata nel file
+    pub found: u32,
+}
+
+/// Risultato della migrazione di un documento di configurazione
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationReport {
+    /// Versione del documento prima della migrazione
+    pub from: u32,
+
+    /// Versione del documento dopo la migrazione
+    pub to: u32,
+
+    /// Descrizioni delle migrazioni applicate
+    pub applied: Vec<&'static str>,
+}
+
+/// Porta un documento di configurazione alla versione corrente dello schema
+///
+/// # Arguments
+///
+/// * `document` - Il documento di configurazione letto dal file
+///
+/// # Returns
+///
+/// Il riepilogo delle migrazioni applicate, oppure `None` se il documento era già
+/// alla versione corrente
+pub fn migrate(document: &mut Value) -> Result<Option<MigrationReport>> {
+    let Value::Mapping(fields) = document else {
+        return Err(anyhow!("Configuration is not a mapping"));
+    };
+
+    let from = document_version(fields)?;
+    if from > CURRENT_VERSION {
+        return Err(UnsupportedVersion { found: from }.into());
+    }
+    if from == CURRENT_VERSION {
+        return Ok(None);
+    }
+
+    let mut applied = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|m| m.from >= from) {
+        (migration.apply)(fields)
+            .map_err(|e| anyhow!("Configuration migration from version {} failed ({}): {:#}",
+                                 migration.from, migration.description, e))?;
+        applied.push(migration.description);
+    }
+    fields.insert(Value::String(VERSION_KEY.to_string()), Value::Number(CURRENT_VERSION.into()));
+
+    Ok(Some(MigrationReport { from, to: CURRENT_VERSION, applied }))
+}
+
+/// Legge la versione dello schema di un documento (0 se assente)
+fn document_version(fields: &Mapping) -> Result<u32> {
+    match fields.get(VERSION_KEY) {
+        None | Some(Value::Null) => Ok(0),
+        Some(value) => value.as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| anyhow!("Invalid {}: expected a non-negative integer", VERSION_KEY)),
+    }
+}
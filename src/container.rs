@@ -0,0 +1,273 @@
+//! Costruzione di immagini container
+//!
+//! Questo modulo applica uno o più stack dentro un container di build, così lo stesso
+//! catalogo di task produce sia host configurati sia immagini. Galatea viene copiato nel
+//! container con una configurazione di bootstrap ed esegue `galatea apply`; il container
+//! viene poi salvato come immagine con buildah, docker o podman.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use anyhow::{Context, Result, anyhow};
+use log::{info, warn};
+
+use crate::bootstrap::{self, REMOTE_CONFIG_PATH, REMOTE_DATA_DIR, REMOTE_EXECUTABLE};
+use crate::config::Config;
+use crate::executor;
+use crate::utils;
+
+/// Motori di container supportati
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerEngine {
+    Buildah,
+    Docker,
+    Podman,
+}
+
+impl ContainerEngine {
+    /// Converte una stringa nel motore corrispondente
+    pub fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "buildah" => Ok(ContainerEngine::Buildah),
+            "docker" => Ok(ContainerEngine::Docker),
+            "podman" => Ok(ContainerEngine::Podman),
+            _ => Err(anyhow!("Invalid container engine: {}", s)),
+        }
+    }
+
+    /// Restituisce il comando del motore
+    pub fn to_str(self) -> &'static str {
+        match self {
+            ContainerEngine::Buildah => "buildah",
+            ContainerEngine::Docker => "docker",
+            ContainerEngine::Podman => "podman",
+        }
+    }
+
+    /// Individua il primo motore installato, nell'ordine buildah, podman, docker
+    pub fn detect() -> Result<Self> {
+        [ContainerEngine::Buildah, ContainerEngine::Podman, ContainerEngine::Docker]
+            .into_iter()
+            .find(|engine| executor::is_command_available(engine.to_str()))
+            .ok_or_else(|| anyhow!("No container engine found (buildah, podman or docker)"))
+    }
+}
+
+/// Opzioni per la costruzione di un'immagine
+#[derive(Debug, Clone)]
+pub struct ImageOptions {
+    /// Motore da usare
+    pub engine: ContainerEngine,
+
+    /// Immagine di partenza
+    pub base_image: String,
+
+    /// Nome dell'immagine prodotta
+    pub tag: String,
+
+    /// Stack da applicare nell'immagine
+    pub stacks: Vec<String>,
+
+    /// Lascia Galatea, la configurazione e lo stato nell'immagine
+    pub keep_galatea: bool,
+}
+
+/// Costruisce un'immagine applicando gli stack a un container di build
+///
+/// L'eseguibile corrente viene copiato nel container: deve essere compatibile con
+/// l'immagine di partenza (architettura e librerie di sistema).
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente, da cui vengono riprese sorgenti e catalogo locale
+/// * `options` - Le opzioni della build
+pub fn build_image(config: &Config, options: &ImageOptions) -> Result<()> {
+    if options.stacks.is_empty() {
+        return Err(anyhow!("At least one stack is required to build an image"));
+    }
+
+    info!("Building image {} from {} with {} (stacks: {})",
+          options.tag, options.base_image, options.engine.to_str(), options.stacks.join(", "));
+
+    let staging = prepare_staging(config, &options.stacks)?;
+    let container = start_container(options.engine, &options.base_image)?;
+    info!("Build container {} started", container);
+
+    let result = provision(options, &container, &staging)
+        .and_then(|_| commit(options.engine, &container, &options.base_image, &options.tag));
+
+    // Il container e i file temporanei vengono rimossi anche se la build fallisce
+    if let Err(e) = remove_container(options.engine, &container) {
+        warn!("Failed to remove build container {}: {}", container, e);
+    }
+    if let Err(e) = fs::remove_dir_all(&staging) {
+        warn!("Failed to remove staging directory {:?}: {}", staging, e);
+    }
+
+    result?;
+    info!("Image {} built", options.tag);
+    Ok(())
+}
+
+/// Prepara i file da copiare nel container: eseguibile, configurazione e cataloghi locali
+fn prepare_staging(config: &Config, stacks: &[String]) -> Result<PathBuf> {
+    // La configurazione viene generata prima di creare la directory, che resterebbe orfana
+    let config_yaml = bootstrap::bootstrap_config(config, stacks)?;
+
+    let staging = utils::get_temp_dir().join(format!("galatea-build-{}", std::process::id()));
+    for dir in ["tasks", "stacks"] {
+        fs::create_dir_all(staging.join(dir))
+            .context(format!("Failed to create staging directory: {:?}", staging))?;
+    }
+
+    let executable = std::env::current_exe()
+        .context("Failed to locate the galatea executable")?;
+    fs::copy(&executable, staging.join("galatea"))
+        .context(format!("Failed to copy executable {:?}", executable))?;
+
+    fs::write(staging.join("galatea.yaml"), config_yaml)
+        .context("Failed to write build configuration")?;
+
+    // I cataloghi locali (.conf) servono anche senza sorgenti configurate
+    for (dir, name) in [(&config.tasks_dir, "tasks"), (&config.stacks_dir, "stacks")] {
+        for file in utils::get_files_with_extension(Path::new(dir), "conf")? {
+            if let Some(file_name) = file.file_name() {
+                fs::copy(&file, staging.join(name).join(file_name))
+                    .context(format!("Failed to copy catalog {:?}", file))?;
+            }
+        }
+    }
+
+    Ok(staging)
+}
+
+/// Copia Galatea nel container ed esegue l'applicazione degli stack
+fn provision(options: &ImageOptions, container: &str, staging: &Path) -> Result<()> {
+    let engine = options.engine;
+    let config_dir = Path::new(REMOTE_CONFIG_PATH).parent()
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|| "/etc/galatea".to_string());
+
+    let tasks_dir = format!("{}/tasks", REMOTE_DATA_DIR);
+    let stacks_dir = format!("{}/stacks", REMOTE_DATA_DIR);
+
+    run_in_container(engine, container, &["mkdir", "-p", &config_dir, &tasks_dir, &stacks_dir, "/usr/local/bin"])?;
+    copy_into(engine, container, &staging.join("galatea"), REMOTE_EXECUTABLE)?;
+    copy_into(engine, container, &staging.join("galatea.yaml"), REMOTE_CONFIG_PATH)?;
+    copy_into(engine, container, &staging.join("tasks"), &tasks_dir)?;
+    copy_into(engine, container, &staging.join("stacks"), &stacks_dir)?;
+
+    info!("Applying stacks inside build container {}", container);
+    run_in_container(engine, container, &[REMOTE_EXECUTABLE, "--config", REMOTE_CONFIG_PATH, "apply", "--yes"])
+        .context("Stack application inside the build container failed")?;
+
+    if !options.keep_galatea {
+        run_in_container(engine, container, &["rm", "-rf", REMOTE_EXECUTABLE, &config_dir, REMOTE_DATA_DIR])?;
+    }
+
+    Ok(())
+}
+
+/// Avvia il container di build e ne restituisce l'identificativo
+fn start_container(engine: ContainerEngine, image: &str) -> Result<String> {
+    let args: Vec<&str> = match engine {
+        ContainerEngine::Buildah => vec!["from", image],
+        // Il container resta attivo finché non viene salvato
+        _ => vec!["run", "-d", "--entrypoint", "sleep", image, "infinity"],
+    };
+
+    let output = engine_output(engine, &args)?;
+    output.lines().last()
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+        .ok_or_else(|| anyhow!("{} did not return a container id", engine.to_str()))
+}
+
+/// Esegue un comando nel container, mostrando l'output all'utente
+fn run_in_container(engine: ContainerEngine, container: &str, command: &[&str]) -> Result<()> {
+    let mut args: Vec<&str> = match engine {
+        ContainerEngine::Buildah => vec!["run", container, "--"],
+        _ => vec!["exec", container],
+    };
+    args.extend_from_slice(command);
+    engine_status(engine, &args)
+}
+
+/// Copia un file o il contenuto di una directory nel container
+fn copy_into(engine: ContainerEngine, container: &str, source: &Path, destination: &str) -> Result<()> {
+    let source = source.to_string_lossy().to_string();
+    match engine {
+        ContainerEngine::Buildah => engine_status(engine, &["copy", container, &source, destination]),
+        _ => {
+            // "dir/." copia il contenuto della directory, non la directory stessa
+            let source = if Path::new(&source).is_dir() { format!("{}/.", source) } else { source };
+            let target = format!("{}:{}", container, destination);
+            engine_status(engine, &["cp", &source, &target])
+        },
+    }
+}
+
+/// Salva il container come immagine
+fn commit(engine: ContainerEngine, container: &str, base_image: &str, tag: &str) -> Result<()> {
+    match engine {
+        ContainerEngine::Buildah => engine_status(engine, &["commit", container, tag]),
+        _ => {
+            // Il container è stato avviato con "sleep": ripristina entrypoint e comando dell'immagine
+            let entrypoint = image_config(engine, base_image, "Entrypoint")?;
+            let cmd = image_config(engine, base_image, "Cmd")?;
+            let entrypoint_change = format!("ENTRYPOINT {}", entrypoint);
+            let cmd_change = format!("CMD {}", cmd);
+            engine_status(engine, &["commit", "--change", &entrypoint_change, "--change", &cmd_change, container, tag])
+        },
+    }
+}
+
+/// Legge un campo della configurazione di un'immagine come array JSON
+fn image_config(engine: ContainerEngine, image: &str, field: &str) -> Result<String> {
+    let format = format!("{{{{json .Config.{}}}}}", field);
+    let value = engine_output(engine, &["image", "inspect", "--format", &format, image])?;
+    let value = value.trim();
+    Ok(if value.is_empty() || value == "null" { "[]".to_string() } else { value.to_string() })
+}
+
+/// Rimuove il container di build
+fn remove_container(engine: ContainerEngine, container: &str) -> Result<()> {
+    match engine {
+        ContainerEngine::Buildah => engine_output(engine, &["rm", container]),
+        _ => engine_output(engine, &["rm", "-f", container]),
+    }.map(|_| ())
+}
+
+/// Esegue il motore lasciando l'output sul terminale
+fn engine_status(engine: ContainerEngine, args: &[&str]) -> Result<()> {
+    let status = Command::new(engine.to_str())
+        .args(args)
+        .stdin(Stdio::null())
+        .status()
+        .context(format!("Failed to run {}", engine.to_str()))?;
+
+    if !status.success() {
+        return Err(anyhow!("{} {} failed with {}", engine.to_str(), args.join(" "), status));
+    }
+    Ok(())
+}
+
+/// Esegue il motore e ne restituisce l'output
+fn engine_output(engine: ContainerEngine, args: &[&str]) -> Result<String> {
+    let output = Command::new(engine.to_str())
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .context(format!("Failed to run {}", engine.to_str()))?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{} {} failed: {}",
+            engine.to_str(),
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
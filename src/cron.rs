@@ -0,0 +1,147 @@
+//! Espressioni cron
+//!
+//! Supporta il formato standard a cinque campi (`minuto ora giorno mese giorno-settimana`)
+//! con `*`, valori singoli, intervalli (`1-5`), liste (`1,15,30`) e passi (`*/15`, `0-30/10`).
+//! Come in cron, se sia il giorno del mese sia il giorno della settimana sono indicati basta
+//! che uno dei due corrisponda.
+
+use std::fmt;
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Datelike, Local, Timelike};
+
+/// Campo di un'espressione cron: l'insieme dei valori ammessi
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Field {
+    /// Valori ammessi, indicizzati a partire da 0
+    allowed: Vec<bool>,
+
+    /// Indica se il campo è `*` (nessuna restrizione)
+    any: bool,
+}
+
+impl Field {
+    /// Interpreta un campo con i valori compresi tra `min` e `max`
+    fn parse(field: &str, min: u32, max: u32) -> Result<Self> {
+        let mut allowed = vec![false; max as usize + 1];
+
+        for item in field.split(',') {
+            let (range, step) = match item.split_once('/') {
+                Some((range, step)) => {
+                    let step: u32 = step.parse().map_err(|_| anyhow!("invalid step {:?}", step))?;
+                    if step == 0 {
+                        return Err(anyhow!("step must be greater than 0"));
+                    }
+                    (range, step)
+                },
+                None => (item, 1),
+            };
+
+            let (start, end) = match range {
+                "*" => (min, max),
+                _ => match range.split_once('-') {
+                    Some((start, end)) => (parse_value(start, min, max)?, parse_value(end, min, max)?),
+                    // Un valore con passo (es. `5/10`) vale dal valore al massimo
+                    None if item.contains('/') => (parse_value(range, min, max)?, max),
+                    None => {
+                        let value = parse_value(range, min, max)?;
+                        (value, value)
+                    },
+                },
+            };
+            if start > end {
+                return Err(anyhow!("invalid range {:?}", range));
+            }
+
+            for value in (start..=end).step_by(step as usize) {
+                allowed[value as usize] = true;
+            }
+        }
+
+        Ok(Field { allowed, any: field == "*" })
+    }
+
+    /// Verifica se il valore è ammesso
+    fn contains(&self, value: u32) -> bool {
+        self.allowed.get(value as usize).copied().unwrap_or(false)
+    }
+}
+
+/// Interpreta un valore numerico di un campo
+fn parse_value(value: &str, min: u32, max: u32) -> Result<u32> {
+    let number: u32 = value.parse().map_err(|_| anyhow!("invalid value {:?}", value))?;
+    if number < min || number > max {
+        return Err(anyhow!("value {} out of range {}-{}", number, min, max));
+    }
+    Ok(number)
+}
+
+/// Espressione cron a cinque campi
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronExpr {
+    /// Testo originale dell'espressione
+    source: String,
+    minute: Field,
+    hour: Field,
+    day_of_month: Field,
+    month: Field,
+    day_of_week: Field,
+}
+
+impl CronExpr {
+    /// Interpreta un'espressione cron
+    ///
+    /// # Arguments
+    ///
+    /// * `expr` - L'espressione, es. `*/15 * * * *` o `0 3 * * 1-5`
+    ///
+    /// # Returns
+    ///
+    /// L'espressione interpretata, o un errore se non è valida
+    pub fn parse(expr: &str) -> Result<Self> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(anyhow!("Cron expression {:?} must have 5 fields, found {}", expr, fields.len()));
+        }
+
+        let field = |index: usize, name: &str, min: u32, max: u32| {
+            Field::parse(fields[index], min, max)
+                .context(format!("Invalid {} field in cron expression {:?}", name, expr))
+        };
+
+        let mut day_of_week = field(4, "day of week", 0, 7)?;
+        // 7 indica la domenica come 0
+        if day_of_week.contains(7) {
+            day_of_week.allowed[0] = true;
+        }
+
+        Ok(CronExpr {
+            source: expr.split_whitespace().collect::<Vec<_>>().join(" "),
+            minute: field(0, "minute", 0, 59)?,
+            hour: field(1, "hour", 0, 23)?,
+            day_of_month: field(2, "day of month", 1, 31)?,
+            month: field(3, "month", 1, 12)?,
+            day_of_week,
+        })
+    }
+
+    /// Verifica se l'espressione corrisponde al minuto indicato
+    pub fn matches(&self, time: &DateTime<Local>) -> bool {
+        let day_of_month = self.day_of_month.contains(time.day());
+        let day_of_week = self.day_of_week.contains(time.weekday().num_days_from_sunday());
+
+        let day = match (self.day_of_month.any, self.day_of_week.any) {
+            (false, false) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        };
+
+        day && self.minute.contains(time.minute())
+            && self.hour.contains(time.hour())
+            && self.month.contains(time.month())
+    }
+}
+
+impl fmt::Display for CronExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
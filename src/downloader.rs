@@ -2,32 +2,182 @@
 //!
 //! Questo modulo fornisce funzionalità per scaricare file da URL e
 //! estrarre archivi nei formati supportati (zip, tar.gz, tgz).
+//!
+//! Con la cache dei download attiva i file scaricati vengono conservati: al download
+//! successivo dello stesso URL la richiesta è condizionale (`If-None-Match` /
+//! `If-Modified-Since`) e un file non modificato non viene riscaricato, mentre un
+//! download interrotto riprende dal punto in cui si era fermato (`Range` / `If-Range`).
 
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::fs::{self, File};
-use std::io::{copy};
-use std::time::Duration;
+use std::io::{copy, Read, Write};
+use std::fs::OpenOptions;
+use std::time::{Duration, Instant};
 use anyhow::{Context, Result, anyhow};
 use log::{info, warn, debug};
-use reqwest::blocking::Client;
+use reqwest::StatusCode;
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::redirect::Policy;
+use rustls::{DigitallySignedStruct, SignatureScheme};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use reqwest::header::{CONTENT_RANGE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED, RANGE};
+use serde::{Serialize, Deserialize};
 use zip::ZipArchive;
 use tar::Archive;
 use flate2::read::GzDecoder;
 
+use crate::error::{GalateaError, ResultExt};
+use crate::events;
+use crate::release;
+use crate::source::{self, HostPolicy, Source};
+
+/// Dimensione dei blocchi letti dalla risposta HTTP
+const DOWNLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Numero massimo di reindirizzamenti seguiti in un download
+const MAX_REDIRECTS: usize = 10;
+
+/// Intervallo minimo tra due notifiche di avanzamento
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+/// File di blocco di una voce della cache dei download, nella sua directory
+const CACHE_LOCK_FILE: &str = ".lock";
+
+/// Validatori HTTP di un file nella cache dei download
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct CacheEntry {
+    /// URL da cui è stato scaricato il file
+    url: String,
+
+    /// Valore dell'header `ETag` della risposta
+    #[serde(default)]
+    etag: Option<String>,
+
+    /// Valore dell'header `Last-Modified` della risposta
+    #[serde(default)]
+    last_modified: Option<String>,
+}
+
+impl CacheEntry {
+    /// Ricava i validatori dagli header di una risposta
+    fn from_response(url: &str, response: &Response) -> Self {
+        let header = |name| response.headers().get(name)
+            .and_then(|value: &reqwest::header::HeaderValue| value.to_str().ok())
+            .map(str::to_string);
+
+        CacheEntry { url: url.to_string(), etag: header(ETAG), last_modified: header(LAST_MODIFIED) }
+    }
+
+    /// Restituisce il validatore per `If-Range`: un ETag forte o, in mancanza, la data
+    fn range_validator(&self) -> Option<&str> {
+        self.etag.as_deref()
+            .filter(|etag| !etag.starts_with("W/"))
+            .or(self.last_modified.as_deref())
+    }
+
+    /// Legge i validatori salvati, se appartengono allo stesso URL
+    fn load(path: &Path, url: &str) -> Option<Self> {
+        let content = fs::read_to_string(path).ok()?;
+        let entry: CacheEntry = serde_json::from_str(&content).ok()?;
+        (entry.url == url).then_some(entry)
+    }
+
+    /// Salva i validatori
+    fn save(&self, path: &Path) -> Result<()> {
+        let content = serde_json::to_string_pretty(self)
+            .context("Failed to serialize download cache entry")?;
+        fs::write(path, content)
+            .context(format!("Failed to write download cache entry: {:?}", path))
+    }
+}
 
+/// Stato di avanzamento di un download
+#[derive(Debug, Clone)]
+pub struct DownloadProgress {
+    /// URL in download
+    pub url: String,
 
-/// Scarica un file da un URL in una directory specifica
+    /// Byte scaricati finora
+    pub downloaded: u64,
+
+    /// Dimensione totale, se comunicata dal server
+    pub total: Option<u64>,
+
+    /// Velocità media in byte al secondo
+    pub bytes_per_sec: f64,
+
+    /// Indica se il download è terminato
+    pub finished: bool,
+}
+
+impl DownloadProgress {
+    /// Restituisce la percentuale completata, se la dimensione totale è nota
+    pub fn percent(&self) -> Option<f64> {
+        self.total
+            .filter(|total| *total > 0)
+            .map(|total| self.downloaded as f64 * 100.0 / total as f64)
+    }
+
+    /// Restituisce una descrizione sintetica, es. "1.2 MiB / 4.0 MiB (30%) - 512.0 KiB/s"
+    pub fn summary(&self) -> String {
+        let amount = match (self.total, self.percent()) {
+            (Some(total), Some(percent)) => format!("{} / {} ({:.0}%)", format_bytes(self.downloaded), format_bytes(total), percent),
+            _ => format_bytes(self.downloaded),
+        };
+
+        format!("{} - {}/s", amount, format_bytes(self.bytes_per_sec as u64))
+    }
+}
+
+/// Pubblica l'avanzamento di un download sul canale degli eventi
+fn report_progress(progress: &DownloadProgress) {
+    let file_name = progress.url.rsplit('/').next().unwrap_or(&progress.url);
+    events::progress(
+        format!("Download {}", file_name),
+        progress.summary(),
+        progress.percent(),
+        progress.finished,
+    );
+}
+
+/// Formatta una dimensione in byte in unità binarie leggibili
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Scarica un file verificando, se indicata, l'impronta del certificato TLS del server
 ///
 /// # Arguments
 ///
 /// * `url` - L'URL da cui scaricare il file
 /// * `dir` - La directory di destinazione
 /// * `timeout_secs` - Il timeout in secondi per la richiesta
+/// * `tls_pin` - L'impronta SHA-256 attesa del certificato del server
+/// * `token` - Il token per scaricare l'asset di una release privata
+/// * `hosts` - Gli host da cui è consentito scaricare
 ///
 /// # Returns
 ///
 /// Il percorso del file scaricato
-pub fn download_file(url: &str, dir: &Path, timeout_secs: u64) -> Result<PathBuf> {
+pub fn download_file_pinned(url: &str, dir: &Path, timeout_secs: u64, tls_pin: Option<&str>, token: Option<&str>, hosts: &HostPolicy) -> Result<PathBuf> {
+    hosts.check(url)?;
+
     // Crea la directory se non esiste
     if !dir.exists() {
         fs::create_dir_all(dir).context("Failed to create download directory")?;
@@ -35,53 +185,375 @@ pub fn download_file(url: &str, dir: &Path, timeout_secs: u64) -> Result<PathBuf
 
     // Ottieni il nome del file dall'URL
     let filename = url.split('/').last()
-        .ok_or_else(|| anyhow!("Invalid URL: {}", url))?;
+        .ok_or_else(|| GalateaError::download(url, None, format!("Invalid URL: {}", url)))?;
 
     let file_path = dir.join(filename);
 
-    // Crea un client HTTP con timeout
-    let client = Client::builder()
-        .timeout(Duration::from_secs(timeout_secs))
-        .build()
-        .context("Failed to create HTTP client")?;
-
-    // Effettua la richiesta
+    // Effettua la richiesta, all'indirizzo dell'asset per gli URL delle release
+    let download = release::locate(url, token, timeout_secs, tls_pin, hosts)?;
+    require_tls(&download.url, tls_pin)?;
     info!("Downloading {} to {:?}", url, file_path);
-    let mut response = client.get(url)
+    let mut response = prepare_request(&http_client(timeout_secs, tls_pin, hosts)?, &download)
         .send()
-        .context(format!("Failed to download file from {}", url))?;
+        .download_context(url, format!("Failed to download file from {}", url))?;
 
     // Verifica che la richiesta sia andata a buon fine
     if !response.status().is_success() {
-        return Err(anyhow!("HTTP error: {}", response.status()));
+        return Err(GalateaError::download(url, Some(response.status().as_u16()), format!("HTTP error: {}", response.status())).into());
     }
 
     // Crea il file di destinazione
     let mut file = File::create(&file_path)
         .context(format!("Failed to create file: {:?}", file_path))?;
 
-    // Copia il contenuto della risposta nel file
-    copy(&mut response, &mut file)
-        .context("Failed to write file content")?;
-
-    debug!("File downloaded to {:?}", file_path);
+    let total = response.content_length();
+    let summary = save_response(&mut response, &mut file, url, 0, total)?;
+    debug!("File downloaded to {:?} ({})", file_path, summary);
 
     Ok(file_path)
 }
 
-/// Scarica un file di configurazione da un URL
+/// Crea un client HTTP con timeout, che verifica il certificato del server se è indicata un'impronta
+///
+/// Con `tls_pin` il certificato viene confrontato con l'impronta durante l'handshake TLS,
+/// quindi prima di inviare la richiesta e i suoi header di autenticazione; una
+/// connessione non cifrata viene rifiutata. Anche i reindirizzamenti devono portare a
+/// host consentiti da `hosts`.
+pub fn http_client(timeout_secs: u64, tls_pin: Option<&str>, hosts: &HostPolicy) -> Result<Client> {
+    let hosts = hosts.clone();
+    let pinned = tls_pin.is_some();
+    let redirects = Policy::custom(move |attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            attempt.error("too many redirects")
+        } else if let Err(e) = hosts.check(attempt.url().as_str()) {
+            attempt.error(e.to_string())
+        } else if pinned && attempt.url().scheme() != "https" {
+            let message = format!("TLS pin configured but redirected to a non-HTTPS URL: {}", attempt.url());
+            attempt.error(message)
+        } else {
+            attempt.follow()
+        }
+    });
+
+    let mut builder = Client::builder()
+        .timeout(Duration::from_secs(timeout_secs))
+        .redirect(redirects);
+    if let Some(pin) = tls_pin {
+        builder = builder.use_preconfigured_tls(pinned_tls_config(pin)?);
+    }
+
+    builder.build().context("Failed to create HTTP client")
+}
+
+/// Crea la configurazione TLS che accetta solo il certificato con l'impronta indicata
+fn pinned_tls_config(pin: &str) -> Result<rustls::ClientConfig> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let verifier = PinnedCertVerifier {
+        fingerprint: source::normalize_fingerprint(pin),
+        provider: provider.clone(),
+    };
+
+    Ok(rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .context("Failed to configure TLS")?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth())
+}
+
+/// Rifiuta un URL non HTTPS quando è indicata un'impronta del certificato
+fn require_tls(url: &str, tls_pin: Option<&str>) -> Result<()> {
+    if tls_pin.is_some() && !url.starts_with("https://") {
+        return Err(GalateaError::download(url, None, format!("TLS pin configured but {} is not an HTTPS URL", url)).into());
+    }
+    Ok(())
+}
+
+/// Verifica del certificato del server basata sull'impronta SHA-256
+///
+/// Il certificato presentato deve avere esattamente l'impronta attesa, al posto della
+/// verifica della catena di certificazione; le firme dell'handshake sono comunque
+/// verificate con gli algoritmi del provider.
+#[derive(Debug)]
+struct PinnedCertVerifier {
+    /// L'impronta attesa, normalizzata
+    fingerprint: String,
+    /// Il provider crittografico usato per verificare le firme
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let fingerprint = source::sha256_hex(end_entity.as_ref());
+        if fingerprint != self.fingerprint {
+            return Err(rustls::Error::General(format!(
+                "TLS certificate pin mismatch: expected {}, got {}",
+                self.fingerprint, fingerprint
+            )));
+        }
+
+        debug!("TLS certificate pin verified");
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// Prepara la richiesta di un download, con gli header indicati
+fn prepare_request(client: &Client, download: &release::Download) -> RequestBuilder {
+    download.headers.iter()
+        .fold(client.get(&download.url), |request, (name, value)| request.header(*name, value))
+}
+
+/// Copia il contenuto di una risposta in un file a blocchi, notificando l'avanzamento
+///
+/// # Arguments
+///
+/// * `response` - La risposta da leggere
+/// * `file` - Il file di destinazione, già posizionato
+/// * `url` - L'URL scaricato
+/// * `offset` - I byte già presenti nel file (download ripreso)
+/// * `total` - La dimensione totale del file, se nota
+///
+/// # Returns
+///
+/// La descrizione sintetica del download
+fn save_response(response: &mut Response, file: &mut File, url: &str, offset: u64, total: Option<u64>) -> Result<String> {
+    let mut progress = DownloadProgress {
+        url: url.to_string(),
+        downloaded: offset,
+        total,
+        bytes_per_sec: 0.0,
+        finished: false,
+    };
+    let started = Instant::now();
+    let mut last_report = started;
+    let mut buffer = vec![0u8; DOWNLOAD_CHUNK_SIZE];
+
+    loop {
+        let read = response.read(&mut buffer)
+            .download_context(url, format!("Failed to read response from {}", url))?;
+        if read == 0 {
+            break;
+        }
+
+        file.write_all(&buffer[..read])
+            .context("Failed to write file content")?;
+        progress.downloaded += read as u64;
+
+        if last_report.elapsed() >= PROGRESS_INTERVAL {
+            progress.bytes_per_sec = (progress.downloaded - offset) as f64 / started.elapsed().as_secs_f64();
+            report_progress(&progress);
+            last_report = Instant::now();
+        }
+    }
+
+    progress.bytes_per_sec = (progress.downloaded - offset) as f64 / started.elapsed().as_secs_f64().max(0.001);
+    progress.finished = true;
+    report_progress(&progress);
+
+    Ok(progress.summary())
+}
+
+/// Scarica un file nella cache dei download, riusando la copia già presente se il server
+/// indica che non è cambiata e riprendendo un download interrotto
+///
+/// I file sono salvati in una sottodirectory ricavata dall'URL, così che file con lo stesso
+/// nome scaricati da URL diversi non si sovrascrivano. I validatori HTTP (`ETag`,
+/// `Last-Modified`) sono salvati accanto al file in `<file>.meta.json`, quelli di un
+/// download interrotto in `<file>.part.json`.
 ///
 /// # Arguments
 ///
 /// * `url` - L'URL da cui scaricare il file
-/// * `dir` - La directory di destinazione
+/// * `cache_dir` - La directory della cache
 /// * `timeout_secs` - Il timeout in secondi per la richiesta
+/// * `tls_pin` - L'impronta SHA-256 attesa del certificato del server
+/// * `token` - Il token per scaricare l'asset di una release privata
+/// * `hosts` - Gli host da cui è consentito scaricare
 ///
 /// # Returns
 ///
-/// Il percorso del file scaricato
-pub fn download_config_file(url: &str, dir: &str, timeout_secs: u64) -> Result<PathBuf> {
-    download_file(url, Path::new(dir), timeout_secs)
+/// Il percorso del file nella cache
+pub fn download_file_cached(url: &str, cache_dir: &Path, timeout_secs: u64, tls_pin: Option<&str>, token: Option<&str>, hosts: &HostPolicy) -> Result<PathBuf> {
+    hosts.check(url)?;
+
+    let (entry_dir, filename) = cache_location(url, cache_dir)
+        .ok_or_else(|| GalateaError::download(url, None, format!("Invalid URL: {}", url)))?;
+    fs::create_dir_all(&entry_dir)
+        .context(format!("Failed to create download cache directory: {:?}", entry_dir))?;
+    // Task installati in parallelo possono scaricare lo stesso URL: il secondo attende
+    // il primo e trova la copia completa
+    let _entry_lock = lock_cache_entry(&entry_dir)?;
+
+    let file_path = entry_dir.join(&filename);
+    let partial_path = entry_dir.join(format!("{}.part", filename));
+    let meta_path = entry_dir.join(format!("{}.meta.json", filename));
+    let partial_meta_path = entry_dir.join(format!("{}.part.json", filename));
+
+    let cached = CacheEntry::load(&meta_path, url).filter(|_| file_path.exists());
+    let partial_len = fs::metadata(&partial_path).map(|m| m.len()).unwrap_or(0);
+    let resumable = CacheEntry::load(&partial_meta_path, url)
+        .filter(|entry| entry.range_validator().is_some() && partial_len > 0);
+
+    // La voce della cache resta quella dell'URL del catalogo anche quando l'asset di una
+    // release viene scaricato da un altro indirizzo
+    let download = release::locate(url, token, timeout_secs, tls_pin, hosts)?;
+    require_tls(&download.url, tls_pin)?;
+    let mut request = prepare_request(&http_client(timeout_secs, tls_pin, hosts)?, &download);
+    if let Some(entry) = &cached {
+        // Il file completo c'è già: il server risponde 304 se non è cambiato
+        if let Some(etag) = &entry.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &entry.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    } else if let Some(entry) = &resumable {
+        // Riprende il download interrotto, purché il file sul server sia lo stesso
+        request = request
+            .header(RANGE, format!("bytes={}-", partial_len))
+            .header(IF_RANGE, entry.range_validator().unwrap_or_default());
+    }
+
+    info!("Downloading {} to the download cache {:?}", url, file_path);
+    let mut response = request.send()
+        .download_context(url, format!("Failed to download file from {}", url))?;
+
+    if response.status() == StatusCode::NOT_MODIFIED && cached.is_some() {
+        info!("{} not modified, using the cached copy {:?}", url, file_path);
+        let size = fs::metadata(&file_path).map(|m| m.len()).unwrap_or(0);
+        report_progress(&DownloadProgress {
+            url: url.to_string(),
+            downloaded: size,
+            total: Some(size),
+            bytes_per_sec: 0.0,
+            finished: true,
+        });
+        return Ok(file_path);
+    }
+
+    if !response.status().is_success() {
+        return Err(GalateaError::download(url, Some(response.status().as_u16()), format!("HTTP error: {}", response.status())).into());
+    }
+
+    // Con 206 il server invia solo la parte mancante, con 200 il file intero
+    let resumed = response.status() == StatusCode::PARTIAL_CONTENT && resumable.is_some();
+    let (mut file, offset) = if resumed {
+        info!("Resuming download of {} from byte {}", url, partial_len);
+        let file = OpenOptions::new().append(true).open(&partial_path)
+            .context(format!("Failed to open partial download: {:?}", partial_path))?;
+        (file, partial_len)
+    } else {
+        let file = File::create(&partial_path)
+            .context(format!("Failed to create file: {:?}", partial_path))?;
+        (file, 0)
+    };
+
+    // I validatori vengono salvati subito, per poter riprendere un download interrotto
+    let entry = if resumed {
+        resumable.unwrap_or_default()
+    } else {
+        CacheEntry::from_response(url, &response)
+    };
+    entry.save(&partial_meta_path)?;
+
+    let total = match response.headers().get(CONTENT_RANGE).and_then(|value| value.to_str().ok()) {
+        // Content-Range: bytes 100-999/1000
+        Some(range) if resumed => range.rsplit('/').next().and_then(|total| total.parse().ok()),
+        _ => response.content_length().map(|len| len + offset),
+    };
+    let summary = save_response(&mut response, &mut file, url, offset, total)?;
+
+    fs::rename(&partial_path, &file_path)
+        .context(format!("Failed to move {:?} to {:?}", partial_path, file_path))?;
+    fs::rename(&partial_meta_path, &meta_path)
+        .context(format!("Failed to move {:?} to {:?}", partial_meta_path, meta_path))?;
+    debug!("File downloaded to {:?} ({})", file_path, summary);
+
+    Ok(file_path)
+}
+
+/// Blocca una voce della cache dei download, anche rispetto ad altri processi
+///
+/// # Returns
+///
+/// Il file di blocco: la voce resta bloccata finché non viene chiuso
+fn lock_cache_entry(entry_dir: &Path) -> Result<File> {
+    let lock_path = entry_dir.join(CACHE_LOCK_FILE);
+    let file = OpenOptions::new().create(true).truncate(false).write(true).open(&lock_path)
+        .context(format!("Failed to open download cache lock {:?}", lock_path))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::io::AsRawFd;
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            return Err(anyhow!("Failed to lock download cache entry {:?}: {}", entry_dir, std::io::Error::last_os_error()));
+        }
+    }
+    Ok(file)
+}
+
+/// Restituisce la directory della cache dedicata a un URL e il nome del file scaricato
+fn cache_location(url: &str, cache_dir: &Path) -> Option<(PathBuf, String)> {
+    let filename = url.split('/').last().filter(|name| !name.is_empty())?;
+    Some((cache_dir.join(&source::sha256_hex(url.as_bytes())[..16]), filename.to_string()))
+}
+
+/// Restituisce la copia completa di un URL presente nella cache dei download
+///
+/// # Arguments
+///
+/// * `url` - L'URL del file
+/// * `cache_dir` - La directory della cache
+///
+/// # Returns
+///
+/// Il percorso del file nella cache, se è stato scaricato per intero
+pub fn cached_file(url: &str, cache_dir: &Path) -> Option<PathBuf> {
+    let (entry_dir, filename) = cache_location(url, cache_dir)?;
+    Some(entry_dir.join(filename)).filter(|path| path.is_file())
+}
+
+/// Rimuove un file dalla cache dei download, insieme ai suoi validatori
+fn remove_cached(file_path: &Path) {
+    let mut meta_path = file_path.as_os_str().to_owned();
+    meta_path.push(".meta.json");
+
+    for path in [file_path, Path::new(&meta_path)] {
+        if path.exists() {
+            if let Err(e) = fs::remove_file(path) {
+                warn!("Failed to remove cached file {:?}: {}", path, e);
+            }
+        }
+    }
 }
 
 /// Estrae un archivio in una directory specificata
@@ -221,19 +693,22 @@ fn extract_tar_gz(archive_path: &Path, extract_dir: &Path) -> Result<()> {
     Ok(())
 }
 
-/// Scarica ed estrae un file o un archivio
+/// Scarica ed estrae il contenuto di una sorgente del catalogo, applicando le verifiche
+/// configurate (pin TLS, checksum e firma) prima di copiare o estrarre il file
 ///
 /// # Arguments
 ///
-/// * `url` - L'URL da cui scaricare
+/// * `source` - La sorgente da scaricare
 /// * `extract_dir` - La directory in cui estrarre
 /// * `timeout_secs` - Il timeout in secondi per la richiesta
+/// * `cache_dir` - La directory della cache dei download, se attiva
+/// * `hosts` - Gli host da cui è consentito scaricare
 ///
 /// # Returns
 ///
-/// Il percorso della directory in cui è stato estratto il file o l'archivio
-/// Scarica e decomprime solo se è un archivio, altrimenti copia il file
-pub fn download_and_extract(url: &str, extract_dir: &Path, timeout_secs: u64) -> Result<PathBuf> {
+/// Il percorso del file di configurazione o della directory di estrazione
+pub fn download_and_extract_source(source: &Source, extract_dir: &Path, timeout_secs: u64, cache_dir: Option<&Path>, hosts: &HostPolicy) -> Result<PathBuf> {
+    let url = source.url.as_str();
     info!("Starting download_and_extract for URL: {}", url);
     info!("Extract directory: {:?}", extract_dir);
 
@@ -244,11 +719,34 @@ pub fn download_and_extract(url: &str, extract_dir: &Path, timeout_secs: u64) ->
         fs::create_dir_all(&temp_dir).context("Failed to create temp directory")?;
     }
 
-    // Scarica il file
+    // Scarica il file, nella cache se attiva
     info!("Downloading file...");
-    let downloaded_file = download_file(url, &temp_dir, timeout_secs)?;
+    let downloaded_file = match cache_dir {
+        Some(cache_dir) => download_file_cached(url, cache_dir, timeout_secs, source.tls_pin.as_deref(), source.token.as_deref(), hosts)?,
+        None => download_file_pinned(url, &temp_dir, timeout_secs, source.tls_pin.as_deref(), source.token.as_deref(), hosts)?,
+    };
     info!("File downloaded to: {:?}", downloaded_file);
 
+    // Un file che non supera le verifiche non deve mai raggiungere il catalogo
+    if let Err(e) = source.verify_file(&downloaded_file, timeout_secs, hosts) {
+        if cache_dir.is_some() {
+            remove_cached(&downloaded_file);
+        } else if let Err(remove_err) = fs::remove_file(&downloaded_file) {
+            warn!("Failed to remove unverified file {:?}: {}", downloaded_file, remove_err);
+        }
+        return Err(e);
+    }
+
+    // Il file scaricato nella cache viene conservato per i download successivi
+    let downloaded_file = downloaded_file.as_path();
+    let remove_download = || {
+        if cache_dir.is_none() && downloaded_file.exists() {
+            if let Err(e) = fs::remove_file(downloaded_file) {
+                warn!("Failed to remove temporary file {:?}: {}", downloaded_file, e);
+            }
+        }
+    };
+
     // Verifica se il file è un archivio
     let file_name = downloaded_file.file_name()
         .ok_or_else(|| anyhow!("Invalid file path"))?
@@ -272,11 +770,7 @@ pub fn download_and_extract(url: &str, extract_dir: &Path, timeout_secs: u64) ->
             .context(format!("Failed to copy config file to {:?}", dest_path))?;
 
         // Rimuovi il file scaricato nella directory temporanea
-        if downloaded_file.exists() {
-            if let Err(e) = fs::remove_file(&downloaded_file) {
-                warn!("Failed to remove temporary file {:?}: {}", downloaded_file, e);
-            }
-        }
+        remove_download();
 
         // Rimuovi la directory temporanea se è vuota
         if temp_dir.exists() {
@@ -299,11 +793,7 @@ pub fn download_and_extract(url: &str, extract_dir: &Path, timeout_secs: u64) ->
     info!("Archive extracted to: {:?}", extracted_dir);
 
     // Rimuovi il file scaricato nella directory temporanea
-    if downloaded_file.exists() {
-        if let Err(e) = fs::remove_file(&downloaded_file) {
-            warn!("Failed to remove temporary file {:?}: {}", downloaded_file, e);
-        }
-    }
+    remove_download();
 
     // Rimuovi la directory temporanea se è vuota
     if temp_dir.exists() {
@@ -320,20 +810,6 @@ pub fn download_and_extract(url: &str, extract_dir: &Path, timeout_secs: u64) ->
 }
 
 
-/// Legge un file e restituisce il suo contenuto come stringa
-///
-/// # Arguments
-///
-/// * `path` - Il percorso del file da leggere
-///
-/// # Returns
-///
-/// Il contenuto del file come stringa
-pub fn read_file_to_string(path: &Path) -> Result<String> {
-    fs::read_to_string(path)
-        .context(format!("Failed to read file: {:?}", path))
-}
-
 /// Scrive una stringa in un file
 ///
 /// # Arguments
@@ -0,0 +1,208 @@
+//! Rapporto di conformità della macchina
+//!
+//! Questo modulo verifica tutti i task installati confrontando le evidenze dichiarate
+//! nella sezione `detect` con lo stato reale del sistema, e produce un rapporto
+//! riassuntivo delle derive (drift) rilevate, in formato testo o JSON.
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use log::{info, warn};
+use serde::{Serialize, Deserialize};
+
+use crate::config::Config;
+use crate::importer;
+use crate::source;
+use crate::task::{Task, load_tasks};
+use crate::utils;
+
+/// Esito della verifica di un task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ComplianceStatus {
+    /// Tutte le evidenze sono presenti
+    Compliant,
+    /// Almeno un'evidenza manca: il sistema si è discostato dal task
+    Drifted,
+    /// Il task non dichiara evidenze e non può essere verificato
+    Unverifiable,
+}
+
+impl ComplianceStatus {
+    /// Restituisce l'etichetta da mostrare all'utente
+    pub fn label(&self) -> &'static str {
+        match self {
+            ComplianceStatus::Compliant => "conforme",
+            ComplianceStatus::Drifted => "DERIVA",
+            ComplianceStatus::Unverifiable => "non verificabile",
+        }
+    }
+}
+
+/// Esito della verifica di un singolo task installato
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskCompliance {
+    /// Nome del task
+    pub name: String,
+
+    /// Catalogo di provenienza del task
+    pub source: String,
+
+    /// Esito della verifica
+    pub status: ComplianceStatus,
+
+    /// Evidenze mancanti
+    pub missing: Vec<String>,
+
+    /// Data dell'ultima verifica riuscita, se la verifica è stata saltata per `min_interval`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verified_at: Option<String>,
+}
+
+/// Rapporto di conformità della macchina
+#[derive(Debug, Clone, Serialize)]
+pub struct DriftReport {
+    /// Nome host della macchina verificata
+    pub host: String,
+
+    /// Data e ora della verifica (RFC 3339)
+    pub checked_at: String,
+
+    /// Numero di task conformi
+    pub compliant: usize,
+
+    /// Numero di task in deriva
+    pub drifted: usize,
+
+    /// Numero di task non verificabili
+    pub unverifiable: usize,
+
+    /// Esito per ogni task installato
+    pub tasks: Vec<TaskCompliance>,
+}
+
+impl DriftReport {
+    /// Verifica se è stata rilevata almeno una deriva
+    pub fn has_drift(&self) -> bool {
+        self.drifted > 0
+    }
+
+    /// Serializza il rapporto in JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize drift report to JSON")
+    }
+
+    /// Formatta il rapporto come testo leggibile
+    pub fn to_text(&self) -> String {
+        let mut text = format!("Rapporto di conformità di {} ({})\n\n", self.host, self.checked_at);
+
+        for task in &self.tasks {
+            text.push_str(&format!("[{}] {}", task.status.label(), source::qualified_name(&task.source, &task.name)));
+            match &task.verified_at {
+                Some(verified_at) => {
+                    let date = chrono::DateTime::parse_from_rfc3339(verified_at)
+                        .map(|d| d.with_timezone(&Local).format("%d/%m/%Y %H:%M").to_string())
+                        .unwrap_or_else(|_| verified_at.clone());
+                    text.push_str(&format!(" (verifica saltata, ultima riuscita il {})\n", date));
+                },
+                None => text.push('\n'),
+            }
+            for missing in &task.missing {
+                text.push_str(&format!("    mancante: {}\n", missing));
+            }
+        }
+
+        text.push_str(&format!(
+            "\n{} task installati: {} conformi, {} in deriva, {} non verificabili\n",
+            self.tasks.len(), self.compliant, self.drifted, self.unverifiable
+        ));
+        text
+    }
+}
+
+/// Verifica tutti i task installati e produce il rapporto di conformità
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+///
+/// # Returns
+///
+/// Il rapporto di conformità
+pub fn check_drift(config: &Config) -> Result<DriftReport> {
+    info!("Checking installed tasks for drift");
+
+    let tasks = load_tasks(config)?;
+
+    let results: Vec<TaskCompliance> = tasks.iter()
+        .filter(|t| t.installed)
+        .map(|task| verify_task(config, task))
+        .collect();
+
+    let count = |status: ComplianceStatus| results.iter().filter(|t| t.status == status).count();
+    let report = DriftReport {
+        host: utils::get_hostname(),
+        checked_at: Local::now().to_rfc3339(),
+        compliant: count(ComplianceStatus::Compliant),
+        drifted: count(ComplianceStatus::Drifted),
+        unverifiable: count(ComplianceStatus::Unverifiable),
+        tasks: results,
+    };
+
+    info!(
+        "Drift check completed: {} compliant, {} drifted, {} unverifiable",
+        report.compliant, report.drifted, report.unverifiable
+    );
+
+    Ok(report)
+}
+
+/// Verifica un task installato confrontandone le evidenze con lo stato del sistema
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+/// * `task` - Il task da verificare
+///
+/// # Returns
+///
+/// L'esito della verifica del task
+pub fn verify_task(config: &Config, task: &Task) -> TaskCompliance {
+    // Le verifiche costose non vengono ripetute prima di min_interval
+    if let Some(last) = task.ran_recently(config, "verify") {
+        info!("Skipping verification of task {}: last successful check at {}", task.name, last.to_rfc3339());
+        return TaskCompliance {
+            name: task.name.clone(),
+            source: task.source.clone(),
+            status: ComplianceStatus::Compliant,
+            missing: Vec::new(),
+            verified_at: Some(last.to_rfc3339()),
+        };
+    }
+
+    let (status, missing) = match &task.detect {
+        Some(detect) if !detect.is_empty() => {
+            let (_, missing) = importer::verify_detection(detect);
+            if missing.is_empty() {
+                (ComplianceStatus::Compliant, missing)
+            } else {
+                warn!("Task {} has drifted: missing {}", task.name, missing.join(", "));
+                (ComplianceStatus::Drifted, missing)
+            }
+        },
+        _ => (ComplianceStatus::Unverifiable, Vec::new()),
+    };
+
+    if status == ComplianceStatus::Compliant && task.min_interval > 0 {
+        if let Err(e) = task.record_success(config, "verify") {
+            warn!("Failed to record verification of task {}: {}", task.name, e);
+        }
+    }
+
+    TaskCompliance {
+        name: task.name.clone(),
+        source: task.source.clone(),
+        status,
+        missing,
+        verified_at: None,
+    }
+}
@@ -0,0 +1,160 @@
+//! Tipi di errore strutturati di Galatea
+//!
+//! Le funzioni del core continuano a restituire `anyhow::Result`, ma gli errori che
+//! l'interfaccia o l'output JSON devono distinguere (download, script, stato, configurazione)
+//! sono valori di [`GalateaError`]: restano riconoscibili anche dopo i contesti aggiunti
+//! con `.context(...)`, tramite [`classify`], senza dover interpretare il testo del messaggio.
+
+use std::fmt::Display;
+use thiserror::Error;
+
+/// Errori di Galatea distinti per tipo
+#[derive(Debug, Error)]
+pub enum GalateaError {
+    /// Download di un bundle o di un file di configurazione fallito
+    #[error("{message}")]
+    Download {
+        /// URL richiesto
+        url: String,
+
+        /// Codice di stato HTTP, se il server ha risposto
+        status: Option<u16>,
+
+        /// Descrizione dell'errore
+        message: String,
+    },
+
+    /// Script o playbook terminato con errore
+    #[error("{message}")]
+    Script {
+        /// Codice di uscita del processo, se è terminato normalmente
+        exit_code: Option<i32>,
+
+        /// Descrizione dell'errore
+        message: String,
+    },
+
+    /// Stato locale di un task mancante, illeggibile o non scrivibile
+    #[error("{0}")]
+    State(String),
+
+    /// Configurazione o definizione di un task non valida
+    #[error("{0}")]
+    Config(String),
+}
+
+impl GalateaError {
+    /// Crea un errore di download
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - L'URL richiesto
+    /// * `status` - Il codice di stato HTTP, se il server ha risposto
+    /// * `message` - La descrizione dell'errore
+    pub fn download(url: &str, status: Option<u16>, message: impl Into<String>) -> Self {
+        GalateaError::Download { url: url.to_string(), status, message: message.into() }
+    }
+
+    /// Crea un errore di esecuzione di uno script
+    ///
+    /// # Arguments
+    ///
+    /// * `exit_code` - Il codice di uscita del processo, se noto
+    /// * `message` - La descrizione dell'errore
+    pub fn script(exit_code: Option<i32>, message: impl Into<String>) -> Self {
+        GalateaError::Script { exit_code, message: message.into() }
+    }
+
+    /// Restituisce il nome del tipo di errore, usato nell'output JSON
+    pub fn kind(&self) -> &'static str {
+        match self {
+            GalateaError::Download { .. } => "download",
+            GalateaError::Script { .. } => "script",
+            GalateaError::State(_) => "state",
+            GalateaError::Config(_) => "config",
+        }
+    }
+
+    /// Restituisce il codice di uscita dello script fallito, se presente
+    pub fn exit_code(&self) -> Option<i32> {
+        match self {
+            GalateaError::Script { exit_code, .. } => *exit_code,
+            _ => None,
+        }
+    }
+
+    /// Indica se ripetere l'operazione ha senso senza interventi dell'utente
+    ///
+    /// Un download o uno script possono fallire per cause transitorie; una configurazione
+    /// o uno stato non validi falliscono di nuovo finché non vengono corretti.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, GalateaError::Download { .. } | GalateaError::Script { .. })
+    }
+
+    /// Restituisce il suggerimento da mostrare all'utente per questo tipo di errore
+    pub fn hint(&self) -> String {
+        match self {
+            GalateaError::Download { status: Some(status), .. } if *status >= 500 =>
+                format!("Il server ha risposto con un errore ({}): riprovare più tardi.", status),
+            GalateaError::Download { status: Some(status), .. } =>
+                format!("Il server ha rifiutato la richiesta ({}): verificare l'URL del task.", status),
+            GalateaError::Download { .. } =>
+                "Verificare la connessione di rete e l'URL del task.".to_string(),
+            GalateaError::Script { exit_code: Some(code), .. } =>
+                format!("Lo script è terminato con codice {}: consultare i log per i dettagli.", code),
+            GalateaError::Script { .. } =>
+                "Lo script non è terminato correttamente: consultare i log per i dettagli.".to_string(),
+            GalateaError::State(_) =>
+                "Lo stato locale del task non è coerente: verificare la directory di stato.".to_string(),
+            GalateaError::Config(_) =>
+                "Correggere la configurazione prima di riprovare.".to_string(),
+        }
+    }
+}
+
+/// Restituisce l'errore strutturato contenuto in un errore, anche sotto altri contesti
+///
+/// # Arguments
+///
+/// * `err` - L'errore da esaminare
+///
+/// # Returns
+///
+/// Il [`GalateaError`] più esterno della catena, o `None` se l'errore non è classificato
+pub fn classify(err: &anyhow::Error) -> Option<&GalateaError> {
+    err.downcast_ref::<GalateaError>()
+}
+
+/// Restituisce il nome del tipo di un errore, o `"other"` se non è classificato
+pub fn kind_of(err: &anyhow::Error) -> &'static str {
+    classify(err).map_or("other", GalateaError::kind)
+}
+
+/// Aggiunge a un risultato un contesto che ne classifica l'errore
+pub trait ResultExt<T> {
+    /// Classifica l'errore come errore di download dell'URL indicato
+    fn download_context<M: Display>(self, url: &str, message: M) -> anyhow::Result<T>;
+
+    /// Classifica l'errore come errore di stato, con il messaggio indicato
+    fn state_context<M: Display>(self, message: M) -> anyhow::Result<T>;
+
+    /// Classifica l'errore come errore di configurazione, con il messaggio indicato
+    fn config_context<M: Display>(self, message: M) -> anyhow::Result<T>;
+}
+
+impl<T, E> ResultExt<T> for Result<T, E>
+where
+    E: Into<anyhow::Error>,
+{
+    fn download_context<M: Display>(self, url: &str, message: M) -> anyhow::Result<T> {
+        self.map_err(|e| e.into().context(GalateaError::download(url, None, message.to_string())))
+    }
+
+    fn state_context<M: Display>(self, message: M) -> anyhow::Result<T> {
+        self.map_err(|e| e.into().context(GalateaError::State(message.to_string())))
+    }
+
+    fn config_context<M: Display>(self, message: M) -> anyhow::Result<T> {
+        self.map_err(|e| e.into().context(GalateaError::Config(message.to_string())))
+    }
+}
@@ -0,0 +1,277 @@
+//! Canale di eventi tra il core e le interfacce
+//!
+//! Le operazioni del core (installazione, download, ecc.) pubblicano eventi strutturati
+//! su questo canale; l'interfaccia TUI, la CLI e le eventuali altre interfacce si
+//! iscrivono per mostrarli, senza che il core conosca chi li riceve.
+
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use anyhow::Result;
+use chrono::Local;
+use lazy_static::lazy_static;
+use log::Level;
+
+use crate::error;
+use crate::redact;
+
+/// Identificativo di un'operazione in corso
+pub type OperationId = u64;
+
+/// Identificativo di un'iscrizione al canale
+pub type SubscriptionId = u64;
+
+/// Funzione che riceve gli eventi
+pub type Subscriber = Arc<dyn Fn(&Event) + Send + Sync>;
+
+// Iscritti al canale e contatori degli identificativi
+lazy_static! {
+    static ref SUBSCRIBERS: Mutex<Vec<(SubscriptionId, Subscriber)>> = Mutex::new(Vec::new());
+    static ref NEXT_SUBSCRIPTION: AtomicU64 = AtomicU64::new(1);
+    static ref NEXT_OPERATION: AtomicU64 = AtomicU64::new(1);
+    static ref ACTIVE_RUNS: AtomicU64 = AtomicU64::new(0);
+}
+
+// Esecuzione in corso nel thread: identificativo e numero di operazioni annidate
+thread_local! {
+    static CURRENT_RUN: RefCell<Option<(String, usize)>> = const { RefCell::new(None) };
+}
+
+/// Tipi di operazione che pubblicano eventi
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Install,
+    Reinstall,
+    Uninstall,
+    Reset,
+    Remediate,
+    Migrate,
+}
+
+impl Operation {
+    /// Restituisce il nome dell'operazione per i log
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Operation::Install => "install",
+            Operation::Reinstall => "reinstall",
+            Operation::Uninstall => "uninstall",
+            Operation::Reset => "reset",
+            Operation::Remediate => "remediate",
+            Operation::Migrate => "migrate",
+        }
+    }
+
+    /// Restituisce il nome dell'operazione da mostrare all'utente
+    pub fn label(&self) -> &'static str {
+        match self {
+            Operation::Install => "installazione",
+            Operation::Reinstall => "reinstallazione",
+            Operation::Uninstall => "disinstallazione",
+            Operation::Reset => "reset",
+            Operation::Remediate => "ripristino dei servizi",
+            Operation::Migrate => "migrazione",
+        }
+    }
+}
+
+/// Avanzamento di un'attività (download, task di uno stack, ...)
+#[derive(Debug, Clone)]
+pub struct ProgressUpdate {
+    /// Descrizione dell'attività, es. "Download bundle.tgz"
+    pub label: String,
+
+    /// Dettaglio dell'avanzamento, es. "1.2 MiB / 4.0 MiB (30%)"
+    pub detail: String,
+
+    /// Percentuale completata, se nota
+    pub percent: Option<f64>,
+
+    /// Indica se l'attività è terminata
+    pub finished: bool,
+}
+
+/// Eventi pubblicati dal core
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// Un'operazione su un task o uno stack è iniziata
+    OperationStarted {
+        id: OperationId,
+        operation: Operation,
+        target: String,
+    },
+
+    /// Avanzamento di un'attività in corso
+    Progress(ProgressUpdate),
+
+    /// Messaggio da mostrare nel registro delle operazioni
+    LogLine {
+        level: Level,
+        message: String,
+    },
+
+    /// Un'operazione è terminata; `error` contiene il motivo del fallimento e
+    /// `error_kind` il suo tipo (vedi [`error::kind_of`])
+    Finished {
+        id: OperationId,
+        operation: Operation,
+        target: String,
+        error: Option<String>,
+        error_kind: Option<&'static str>,
+    },
+}
+
+/// Iscrive una funzione al canale degli eventi
+///
+/// # Arguments
+///
+/// * `subscriber` - La funzione chiamata per ogni evento, dal thread che lo pubblica
+///
+/// # Returns
+///
+/// L'identificativo da usare con [`unsubscribe`]
+pub fn subscribe<F>(subscriber: F) -> SubscriptionId
+where
+    F: Fn(&Event) + Send + Sync + 'static,
+{
+    let id = NEXT_SUBSCRIPTION.fetch_add(1, Ordering::SeqCst);
+    if let Ok(mut guard) = SUBSCRIBERS.lock() {
+        guard.push((id, Arc::new(subscriber)));
+    }
+    id
+}
+
+/// Annulla un'iscrizione al canale
+pub fn unsubscribe(id: SubscriptionId) {
+    if let Ok(mut guard) = SUBSCRIBERS.lock() {
+        guard.retain(|(sub_id, _)| *sub_id != id);
+    }
+}
+
+/// Pubblica un evento a tutti gli iscritti
+pub fn emit(event: Event) {
+    // Gli iscritti vengono chiamati senza tenere il lock, così possono a loro volta
+    // pubblicare eventi o scrivere log
+    let subscribers: Vec<Subscriber> = match SUBSCRIBERS.lock() {
+        Ok(guard) => guard.iter().map(|(_, subscriber)| Arc::clone(subscriber)).collect(),
+        Err(_) => return,
+    };
+
+    for subscriber in subscribers {
+        subscriber(&event);
+    }
+}
+
+/// Pubblica un messaggio per il registro delle operazioni
+pub fn log_line(level: Level, message: impl Into<String>) {
+    let message = message.into();
+    emit(Event::LogLine { level, message: redact::mask(&message).into_owned() });
+}
+
+/// Pubblica l'avanzamento di un'attività
+///
+/// Il dettaglio contiene spesso l'output catturato degli script, per cui viene filtrato
+/// dai valori segreti.
+pub fn progress(label: impl Into<String>, detail: impl Into<String>, percent: Option<f64>, finished: bool) {
+    let detail = detail.into();
+    emit(Event::Progress(ProgressUpdate {
+        label: label.into(),
+        detail: redact::mask(&detail).into_owned(),
+        percent,
+        finished,
+    }));
+}
+
+/// Esegue un'operazione pubblicandone l'inizio e la fine
+///
+/// # Arguments
+///
+/// * `operation` - Il tipo di operazione
+/// * `target` - Il nome del task o dello stack
+/// * `body` - Il corpo dell'operazione
+///
+/// # Returns
+///
+/// Il risultato del corpo dell'operazione
+pub fn track<T, F>(operation: Operation, target: &str, body: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T>,
+{
+    let id = NEXT_OPERATION.fetch_add(1, Ordering::SeqCst);
+
+    // L'operazione più esterna apre una nuova esecuzione, condivisa da quelle annidate
+    CURRENT_RUN.with(|run| {
+        let mut run = run.borrow_mut();
+        match run.as_mut() {
+            Some((_, depth)) => *depth += 1,
+            None => {
+                *run = Some((format!("{}-{}-{}", Local::now().format("%Y%m%dT%H%M%S"), std::process::id(), id), 1));
+                ACTIVE_RUNS.fetch_add(1, Ordering::SeqCst);
+            },
+        }
+    });
+
+    emit(Event::OperationStarted {
+        id,
+        operation,
+        target: target.to_string(),
+    });
+
+    let result = body();
+
+    CURRENT_RUN.with(|run| {
+        let mut run = run.borrow_mut();
+        if let Some((_, depth)) = run.as_mut() {
+            *depth -= 1;
+            if *depth == 0 {
+                *run = None;
+                ACTIVE_RUNS.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+    });
+
+    emit(Event::Finished {
+        id,
+        operation,
+        target: target.to_string(),
+        error: result.as_ref().err().map(|e| redact::mask(&format!("{:#}", e)).into_owned()),
+        error_kind: result.as_ref().err().map(error::kind_of),
+    });
+
+    result
+}
+
+/// Restituisce l'identificativo dell'esecuzione in corso nel thread
+///
+/// Un'esecuzione comprende l'operazione più esterna (es. l'installazione di uno stack) e
+/// tutte quelle annidate (i suoi task), così che gli script possano correlare i propri log.
+///
+/// # Returns
+///
+/// L'identificativo, o `None` se nessuna operazione è in corso
+pub fn current_run_id() -> Option<String> {
+    CURRENT_RUN.with(|run| run.borrow().as_ref().map(|(id, _)| id.clone()))
+}
+
+/// Esegue `body` nell'esecuzione indicata, es. in un thread che lavora per l'operazione di un altro
+///
+/// Le operazioni avviate da `body` vengono annidate nell'esecuzione, che resta aperta
+/// finché non termina l'operazione più esterna nel thread che l'ha avviata.
+///
+/// # Arguments
+///
+/// * `run_id` - L'esecuzione ottenuta con [`current_run_id`] nel thread di partenza
+/// * `body` - Il lavoro da eseguire
+pub fn join_run<T>(run_id: Option<String>, body: impl FnOnce() -> T) -> T {
+    let Some(run_id) = run_id else {
+        return body();
+    };
+    CURRENT_RUN.with(|run| *run.borrow_mut() = Some((run_id, 1)));
+    let result = body();
+    CURRENT_RUN.with(|run| *run.borrow_mut() = None);
+    result
+}
+
+/// Restituisce il numero di esecuzioni in corso, in tutti i thread
+pub fn active_runs() -> u64 {
+    ACTIVE_RUNS.load(Ordering::SeqCst)
+}
@@ -4,47 +4,561 @@
 //! playbook ansible e comandi generici.
 
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread::{self, JoinHandle};
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, RandomState};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, Ordering};
 use std::fs;
-use std::time::Duration;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::time::{Duration, Instant, SystemTime};
 use anyhow::{Context, Result, anyhow};
-use log::{info, warn};
+use lazy_static::lazy_static;
+use log::{debug, info, warn};
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+
+use crate::child_env::EnvPolicy;
+use crate::error::GalateaError;
+use crate::events;
+use crate::jobs;
+use crate::layout;
+use crate::logger;
+use crate::mac;
+use crate::pty;
+use crate::redact;
+use crate::replay::{self, CommandOutcome};
+use crate::resources::ResourceLimits;
+use crate::state_crypt;
+use crate::sudo;
+use crate::watchdog::{self, Monitor, Watch, WatchdogConfig};
+
+/// Nomi dello script bash cercati nella directory di un task
+pub const BASH_SCRIPT_NAMES: &[&str] = &["install.sh"];
+
+/// Nomi del playbook ansible cercati nella directory di un task, in ordine di priorità
+pub const PLAYBOOK_NAMES: &[&str] = &[
+    "playbook.yml", "playbook.yaml",
+    "main.yml", "main.yaml",
+    "site.yml", "site.yaml",
+    "local.yml", "local.yaml",
+    "install.yml", "install.yaml",
+    "entrypoint.yml", "entrypoint.yaml"
+];
+
+/// Azioni standard supportate dai task (argomento dello script o tag ansible)
+pub const TASK_ACTIONS: &[&str] = &["install", "uninstall", "reset", "remediate"];
+
+/// Variabile d'ambiente con il percorso in cui lo script può scrivere il proprio esito
+pub const RESULT_FILE_ENV: &str = "GALATEA_RESULT_FILE";
+
+/// Extra var con il percorso dell'esito per i playbook ansible
+pub const RESULT_FILE_VAR: &str = "galatea_result_file";
+
+/// Variabile d'ambiente con il nome del task eseguito
+pub const TASK_NAME_ENV: &str = "GALATEA_TASK_NAME";
+
+/// Variabile d'ambiente con l'azione eseguita (install, uninstall, reset, remediate)
+pub const ACTION_ENV: &str = "GALATEA_ACTION";
+
+/// Variabile d'ambiente con l'identificativo dell'esecuzione
+pub const RUN_ID_ENV: &str = "GALATEA_RUN_ID";
+
+/// Variabile d'ambiente con la directory di stato di Galatea
+pub const STATE_DIR_ENV: &str = "GALATEA_STATE_DIR";
+
+/// Variabile d'ambiente con il file in cui lo script registra i checkpoint raggiunti
+pub const CHECKPOINT_FILE_ENV: &str = "GALATEA_CHECKPOINT_FILE";
+
+/// Nome del file dei checkpoint, nella directory di lavoro dell'esecuzione
+const CHECKPOINT_FILE_NAME: &str = ".galatea-checkpoint";
+
+/// Argomento con cui uno script viene ripreso dall'ultimo checkpoint raggiunto
+pub const RESUME_FROM_ARG: &str = "--resume-from";
+
+/// Ambiente predefinito di ansible-playbook: nessun output, argomenti dei task nascosti
+const ANSIBLE_DEFAULT_ENV: &[(&str, &str)] = &[
+    ("ANSIBLE_DISPLAY_ARGS_TO_STDOUT", "no"),
+    ("ANSIBLE_NO_LOG", "true"),
+    ("ANSIBLE_STDOUT_CALLBACK", "null"),
+];
+
+/// Attesa tra SIGTERM e SIGKILL quando un processo viene interrotto
+const TERMINATE_GRACE: Duration = Duration::from_secs(10);
+
+/// Intervallo con cui viene controllato lo stato di un processo in esecuzione
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+lazy_static! {
+    // Interruzione richiesta dall'operatore (SIGINT): i processi avviati vivono in una
+    // sessione propria e non ricevono il Ctrl-C dal terminale
+    static ref INTERRUPTED: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+}
+
+// Contatore per i nomi dei file temporanei privati, distinti per i task eseguiti in parallelo
+static PRIVATE_FILE_COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+// Percentuale di operazioni fatte fallire di proposito (--simulate-failures)
+static SIMULATED_FAILURES: AtomicU8 = AtomicU8::new(0);
+
+// Contatore delle operazioni, usato per estrarre i fallimenti simulati
+static SIMULATION_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Imposta la percentuale di script, playbook e controlli di salute da far fallire
+///
+/// Serve agli autori dei cataloghi per provare tentativi, rollback e canary senza
+/// rompere il sistema: un'operazione estratta fallisce senza essere eseguita.
+///
+/// # Arguments
+///
+/// * `percent` - La percentuale di operazioni da far fallire (0 disattiva la simulazione)
+pub fn set_simulated_failures(percent: u8) {
+    SIMULATED_FAILURES.store(percent.min(100), Ordering::SeqCst);
+}
+
+/// Estrae se l'operazione deve fallire in modo simulato
+///
+/// # Arguments
+///
+/// * `kind` - Il tipo di operazione, es. "Script"
+/// * `target` - Lo script, il playbook o il comando
+fn simulated_failure(kind: &str, target: &str) -> Result<()> {
+    let percent = SIMULATED_FAILURES.load(Ordering::SeqCst);
+    if percent == 0 {
+        return Ok(());
+    }
+
+    // RandomState usa chiavi casuali per processo: basta come sorgente non crittografica
+    let draw = RandomState::new().hash_one(SIMULATION_COUNTER.fetch_add(1, Ordering::SeqCst)) % 100;
+    if draw >= percent as u64 {
+        return Ok(());
+    }
+
+    warn!("Simulated failure of {} {} (--simulate-failures {}%)", kind.to_lowercase(), target, percent);
+    Err(GalateaError::script(None, format!("{} failed: simulated failure (--simulate-failures {}%)", kind, percent)).into())
+}
+
+/// Stato riportato da uno script nel file di esito
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScriptStatus {
+    /// Azione completata
+    #[default]
+    Ok,
+
+    /// Azione non eseguita perché non necessaria o non applicabile
+    Skipped,
+
+    /// Azione fallita, anche se lo script è terminato con codice 0
+    Failed,
+}
+
+impl ScriptStatus {
+    /// Restituisce l'etichetta da mostrare all'utente
+    pub fn label(&self) -> &'static str {
+        match self {
+            ScriptStatus::Ok => "completato",
+            ScriptStatus::Skipped => "saltato",
+            ScriptStatus::Failed => "fallito",
+        }
+    }
+}
+
+/// Esito strutturato scritto da uno script nel file indicato da `GALATEA_RESULT_FILE`
+///
+/// ```json
+/// {"status": "ok", "changed": true, "message": "nginx 1.24 installato", "reboot_required": false}
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScriptResult {
+    /// Stato dell'azione
+    pub status: ScriptStatus,
+
+    /// Indica se l'azione ha modificato il sistema
+    pub changed: bool,
+
+    /// Messaggio per l'utente
+    pub message: Option<String>,
+
+    /// Indica se serve un riavvio per completare l'azione
+    pub reboot_required: bool,
+}
+
+/// Contesto di un'esecuzione, esportato a script e playbook
+///
+/// Gli script lo ricevono come variabili d'ambiente (`GALATEA_TASK_NAME`, `GALATEA_ACTION`,
+/// `GALATEA_RUN_ID`, `GALATEA_STATE_DIR`); i playbook anche come extra vars con lo stesso
+/// nome in minuscolo (es. `galatea_run_id`).
+#[derive(Debug, Clone)]
+pub struct RunContext {
+    /// Nome del task
+    pub task: String,
+
+    /// Azione eseguita
+    pub action: String,
+
+    /// Identificativo dell'esecuzione, condiviso dai task di uno stesso stack
+    pub run_id: String,
+
+    /// Directory di stato di Galatea
+    pub state_dir: PathBuf,
+
+    /// Variabili d'ambiente di ansible-playbook indicate in configurazione (`ansible_env`)
+    pub ansible_env: BTreeMap<String, String>,
+
+    /// Esegue script e comandi tramite sudo quando Galatea non è root (`sudo: true` del task)
+    pub sudo: bool,
+
+    /// Checkpoint da cui riprendere lo script, passato con `--resume-from`
+    pub resume_from: Option<String>,
+
+    /// File di stato in cui conservare l'ultimo checkpoint raggiunto dallo script
+    pub checkpoint_state: Option<PathBuf>,
+
+    /// Esegue lo script in uno pseudo-terminale collegato all'operatore (`interactive: true`)
+    pub interactive: bool,
+
+    /// Variabili dell'ambiente di Galatea passate ai processi (`child_env`)
+    pub child_env: EnvPolicy,
+
+    /// Sorveglianza di script e playbook che restano senza output (`watchdog`)
+    pub watchdog: WatchdogConfig,
+}
+
+impl RunContext {
+    /// Crea il contesto di un'azione, nell'esecuzione in corso
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - Il nome del task
+    /// * `action` - L'azione eseguita
+    /// * `state_dir` - La directory di stato di Galatea
+    pub fn new(task: &str, action: &str, state_dir: impl Into<PathBuf>) -> Self {
+        // Fuori da un'operazione tracciata (es. un controllo) l'esecuzione è il processo stesso
+        let run_id = events::current_run_id()
+            .unwrap_or_else(|| format!("{}-{}", chrono::Local::now().format("%Y%m%dT%H%M%S"), std::process::id()));
+
+        RunContext {
+            task: task.to_string(),
+            action: action.to_string(),
+            run_id,
+            state_dir: state_dir.into(),
+            ansible_env: BTreeMap::new(),
+            sudo: false,
+            resume_from: None,
+            checkpoint_state: None,
+            interactive: false,
+            child_env: EnvPolicy::default(),
+            watchdog: WatchdogConfig::default(),
+        }
+    }
+
+    /// Restituisce le variabili d'ambiente del contesto
+    pub fn env(&self) -> Vec<(&'static str, String)> {
+        vec![
+            (TASK_NAME_ENV, self.task.clone()),
+            (ACTION_ENV, self.action.clone()),
+            (RUN_ID_ENV, self.run_id.clone()),
+            (STATE_DIR_ENV, self.state_dir.to_string_lossy().to_string()),
+        ]
+    }
+}
+
+/// File temporaneo vuoto in una directory nuova accessibile solo al proprietario
+///
+/// Il file (permessi 0600) e la directory (0700) vengono creati senza sovrascrivere nulla:
+/// un file o un link simbolico preparato in anticipo da un altro utente fa fallire la
+/// creazione invece di ricevere i dati, o di fornire un esito contraffatto. File e
+/// directory vengono rimossi quando il valore viene eliminato, anche sui percorsi d'errore.
+struct PrivateFile {
+    path: PathBuf,
+}
+
+impl PrivateFile {
+    /// Crea il file `name` in una nuova directory temporanea con il prefisso indicato
+    fn create(prefix: &str, name: &str) -> Result<Self> {
+        let counter = PRIVATE_FILE_COUNTER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = std::env::temp_dir().join(format!("{}_{}_{}_{:016x}",
+            prefix, std::process::id(), counter, RandomState::new().hash_one(counter)));
+
+        let mut builder = fs::DirBuilder::new();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::DirBuilderExt;
+            builder.mode(0o700);
+        }
+        builder.create(&dir)
+            .context(format!("Failed to create private temporary directory: {:?}", dir))?;
+
+        let file = PrivateFile { path: dir.join(name) };
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options.open(&file.path)
+            .context(format!("Failed to create private temporary file: {:?}", file.path))?;
+        Ok(file)
+    }
+
+    /// Restituisce il percorso del file
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for PrivateFile {
+    fn drop(&mut self) {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                warn!("Failed to remove temporary file {:?}: {}", self.path, e);
+            },
+            _ => {},
+        }
+        if let Err(e) = self.path.parent().map_or(Ok(()), fs::remove_dir) {
+            warn!("Failed to remove temporary directory {:?}: {}", self.path.parent(), e);
+        }
+    }
+}
+
+/// Legge l'esito scritto dallo script, se presente
+fn take_script_result(path: &Path) -> Option<ScriptResult> {
+    let content = fs::read_to_string(path).ok()?;
+
+    if content.trim().is_empty() {
+        return None;
+    }
+
+    match serde_json::from_str::<ScriptResult>(&content) {
+        Ok(result) => {
+            debug!("Script result: {:?}", result);
+            Some(result)
+        },
+        Err(e) => {
+            warn!("Ignoring invalid script result {:?}: {}", path, e);
+            None
+        }
+    }
+}
+
+/// Verifica il codice di uscita insieme all'esito riportato dallo script
+fn check_script_outcome(kind: &str, code: Option<i32>, success: bool, result: Option<ScriptResult>) -> Result<Option<ScriptResult>> {
+    let message = result.as_ref().and_then(|r| r.message.clone());
+
+    if !success {
+        return Err(GalateaError::script(code, match message {
+            Some(message) => format!("{} failed with exit code {}: {}", kind, code.unwrap_or(-1), message),
+            None => format!("{} failed with exit code: {}", kind, code.unwrap_or(-1)),
+        }).into());
+    }
+
+    if result.as_ref().is_some_and(|r| r.status == ScriptStatus::Failed) {
+        return Err(GalateaError::script(code, format!("{} reported failure: {}", kind, message.unwrap_or_else(|| "no message".to_string()))).into());
+    }
+
+    Ok(result)
+}
+
+/// Marcatore di avanzamento riconosciuto nell'output di uno script
+///
+/// L'espressione regolare indica la percentuale con il gruppo `percent`, oppure con i
+/// gruppi `current` e `total` (es. "passo 3/10"), oppure con il primo gruppo catturato;
+/// il gruppo facoltativo `message` fornisce il dettaglio mostrato accanto alla barra.
+#[derive(Debug, Clone)]
+pub struct ProgressMarker {
+    /// Etichetta dell'attività, es. "Task nginx"
+    label: String,
+
+    /// Espressione che riconosce le righe di avanzamento
+    pattern: Regex,
+}
+
+impl ProgressMarker {
+    /// Crea un marcatore da un'espressione regolare
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - L'etichetta dell'attività mostrata nella barra di avanzamento
+    /// * `pattern` - L'espressione regolare, con almeno un gruppo catturato
+    pub fn new(label: impl Into<String>, pattern: &str) -> Result<Self> {
+        let pattern = Regex::new(pattern)
+            .context(format!("Invalid progress pattern: {}", pattern))?;
+
+        if pattern.captures_len() < 2 {
+            return Err(anyhow!("Progress pattern must capture the percentage: {}", pattern));
+        }
+
+        Ok(ProgressMarker { label: label.into(), pattern })
+    }
+
+    /// Estrae percentuale e dettaglio da una riga di output, se è un marcatore
+    pub fn parse(&self, line: &str) -> Option<(f64, String)> {
+        let captures = self.pattern.captures(line)?;
+        let number = |name: &str| captures.name(name).and_then(|m| m.as_str().trim().parse::<f64>().ok());
+
+        let percent = match (number("percent"), number("current"), number("total")) {
+            (Some(percent), _, _) => percent,
+            (None, Some(current), Some(total)) if total > 0.0 => current * 100.0 / total,
+            _ => captures.get(1)?.as_str().trim().parse::<f64>().ok()?,
+        };
+
+        let message = captures.name("message")
+            .map(|m| m.as_str().trim().to_string())
+            .unwrap_or_else(|| format!("{:.0}%", percent));
+
+        Some((percent.clamp(0.0, 100.0), message))
+    }
+
+    /// Legge l'output di uno script pubblicando l'avanzamento per ogni marcatore
+    ///
+    /// # Arguments
+    ///
+    /// * `output` - L'output dello script
+    /// * `echo` - Se `true` le righe vengono riportate sull'output di Galatea
+    ///
+    /// # Returns
+    ///
+    /// L'output letto, con i valori segreti mascherati
+    fn follow(&self, output: impl Read, echo: bool) -> String {
+        let mut captured = String::new();
+        for line in BufReader::new(output).lines().map_while(Result::ok) {
+            captured.push_str(&redact::mask(&line));
+            captured.push('\n');
+            match self.parse(&line) {
+                Some((percent, message)) => {
+                    debug!("{}: progress {:.0}% {}", self.label, percent, message);
+                    events::progress(self.label.as_str(), message, Some(percent), false);
+                },
+                None if echo => println!("{}", redact::mask(&line)),
+                None => {}
+            }
+        }
+        captured
+    }
+
+    /// Pubblica la fine dell'attività
+    fn finish(&self, success: bool) {
+        let detail = if success { "completato" } else { "fallito" };
+        events::progress(self.label.as_str(), detail, success.then_some(100.0), true);
+    }
+}
+
+/// Riporta l'output di un processo sull'output di Galatea, mascherando i valori segreti
+///
+/// # Arguments
+///
+/// * `output` - L'output del processo
+/// * `to_stderr` - Se `true` le righe vengono scritte su stderr invece che su stdout
+///
+/// # Returns
+///
+/// L'output riportato, per la registrazione dell'esecuzione
+fn echo_masked(output: impl Read, to_stderr: bool) -> String {
+    let mut captured = String::new();
+    for line in BufReader::new(output).lines().map_while(Result::ok) {
+        let line = redact::mask(&line);
+        let _ = if to_stderr {
+            writeln!(io::stderr(), "{}", line)
+        } else {
+            writeln!(io::stdout(), "{}", line)
+        };
+        captured.push_str(&line);
+        captured.push('\n');
+    }
+    captured
+}
+
+/// Avvia la lettura di stderr di un processo in un thread separato, se catturato
+///
+/// # Arguments
+///
+/// * `child` - Il processo
+/// * `watch` - L'operazione sorvegliata, di cui anche stderr indica l'attività
+fn echo_masked_stderr(child: &mut Child, watch: Option<&Arc<Watch>>) -> Option<JoinHandle<String>> {
+    let watch = watch.cloned();
+    child.stderr.take().map(|stderr| thread::spawn(move || echo_masked(watchdog::track(stderr, watch.as_ref()), true)))
+}
+
+/// Attende la fine della lettura di un output catturato in un thread separato
+fn join_output(handle: Option<JoinHandle<String>>) -> String {
+    handle.and_then(|handle| handle.join().ok()).unwrap_or_default()
+}
+
+/// Restituisce il flag di interruzione dei processi in esecuzione
+///
+/// Il flag va impostato dal gestore di SIGINT: da quel momento script, playbook e comandi
+/// in esecuzione o avviati in seguito vengono terminati come allo scadere del timeout.
+pub fn interrupt_flag() -> Arc<AtomicBool> {
+    Arc::clone(&INTERRUPTED)
+}
+
+/// Avvia un comando in una nuova sessione
+///
+/// Il processo diventa leader di un gruppo di processi proprio, che comprende anche i
+/// processi che avvia (es. apt o pip lanciati da uno script): quando viene interrotto,
+/// i segnali raggiungono l'intero gruppo e nessun figlio resta in esecuzione.
+pub fn spawn_in_session(command: &mut Command) -> io::Result<Child> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+
+        // setsid è async-signal-safe e può essere chiamata tra fork ed exec
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+    command.spawn()
+}
 
 /// Esegue un comando generico
 ///
 /// # Arguments
 ///
 /// * `command` - Il comando da eseguire
+/// * `timeout_secs` - La durata massima del comando in secondi, se limitata
+/// * `env` - Le variabili dell'ambiente di Galatea passate al comando
 ///
 /// # Returns
 ///
 /// `Ok(())` in caso di successo, altrimenti un errore
-pub fn run_command(command: &str) -> Result<()> {
+pub fn run_command(command: &str, timeout_secs: Option<u64>, env: &EnvPolicy) -> Result<()> {
     info!("Running command: {}", command);
 
-    let mut child = if cfg!(target_os = "windows") {
-        Command::new("cmd")
-            .args(&["/C", command])
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit())
-            .spawn()
+    simulated_failure("Command", command)?;
+
+    let mut process = if cfg!(target_os = "windows") {
+        let mut process = Command::new("cmd");
+        process.args(&["/C", command]);
+        process
     } else {
-        Command::new("sh")
-            .args(&["-c", command])
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit())
-            .spawn()
-    }.context(format!("Failed to execute command: {}", command))?;
+        let mut process = Command::new("sh");
+        process.args(&["-c", command]);
+        process
+    };
+    env.apply(&mut process);
+    process.stdout(Stdio::inherit()).stderr(Stdio::inherit());
+
+    let child = spawn_in_session(&mut process)
+        .context(format!("Failed to execute command: {}", command))?;
 
     // Attendi la terminazione del processo e verifica il codice di uscita
-    let status = child.wait()
+    let status = join_supervisor(supervise(child, "Command", timeout_secs, None))
         .context(format!("Failed to wait for command: {}", command))?;
 
     if !status.success() {
-        return Err(anyhow!(
+        return Err(GalateaError::script(status.code(), format!(
             "Command failed with exit code: {}",
             status.code().unwrap_or(-1)
-        ));
+        )).into());
     }
 
     Ok(())
@@ -56,63 +570,162 @@ pub fn run_command(command: &str) -> Result<()> {
 ///
 /// * `command` - Il comando da eseguire
 /// * `timeout_secs` - Timeout in secondi
+/// * `env` - Le variabili dell'ambiente di Galatea passate al comando
 ///
 /// # Returns
 ///
 /// `Ok(())` in caso di successo, altrimenti un errore
-pub fn run_command_with_timeout(command: &str, timeout_secs: u64) -> Result<()> {
+pub fn run_command_with_timeout(command: &str, timeout_secs: u64, env: &EnvPolicy) -> Result<()> {
     info!("Running command with timeout {}: {}", timeout_secs, command);
 
-    let mut child = if cfg!(target_os = "windows") {
-        Command::new("cmd")
-            .args(&["/C", command])
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit())
-            .spawn()
+    simulated_failure("Command", command)?;
+
+    let mut process = if cfg!(target_os = "windows") {
+        let mut process = Command::new("cmd");
+        process.args(&["/C", command]);
+        process
     } else {
-        Command::new("sh")
-            .args(&["-c", command])
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit())
-            .spawn()
-    }.context(format!("Failed to execute command: {}", command))?;
-
-    // Implementa un timeout manuale
-    let start = std::time::Instant::now();
-    loop {
-        match child.try_wait() {
-            Ok(Some(status)) => {
-                if !status.success() {
-                    return Err(anyhow!(
-                        "Command failed with exit code: {}",
-                        status.code().unwrap_or(-1)
-                    ));
-                }
-                return Ok(());
+        let mut process = Command::new("sh");
+        process.args(&["-c", command]);
+        process
+    };
+    env.apply(&mut process);
+
+    // Durante una registrazione l'output viene catturato, riportandolo comunque
+    let capture = replay::is_recording();
+    if capture {
+        process.stdout(Stdio::piped()).stderr(Stdio::piped());
+    } else {
+        process.stdout(Stdio::inherit()).stderr(Stdio::inherit());
+    }
+
+    let started = SystemTime::now();
+    let mut child = spawn_in_session(&mut process)
+        .context(format!("Failed to execute command: {}", command))?;
+
+    let stdout = child.stdout.take().map(|stdout| thread::spawn(move || echo_masked(stdout, false)));
+    let stderr = echo_masked_stderr(&mut child, None);
+
+    let status = join_supervisor(supervise(child, "Command", Some(timeout_secs), None));
+    let exit_code = status.as_ref().ok().and_then(ExitStatus::code);
+    let outcome = status.and_then(|status| {
+        if !status.success() {
+            return Err(GalateaError::script(status.code(), format!(
+                "Command failed with exit code: {}",
+                status.code().unwrap_or(-1)
+            )).into());
+        }
+        Ok(())
+    });
+    if capture {
+        replay::record_command("command", &process, None, started, CommandOutcome {
+            exit_code,
+            stdout: &join_output(stdout),
+            stderr: &join_output(stderr),
+            error: outcome.as_ref().err(),
+        });
+    }
+    outcome
+}
+
+/// Sorveglia un processo in un thread separato fino alla sua terminazione
+///
+/// Allo scadere del timeout, se il job in esecuzione nel thread corrente viene annullato o
+/// se l'operatore interrompe Galatea, il gruppo di processi riceve SIGTERM e, se non
+/// termina entro [`TERMINATE_GRACE`], SIGKILL. Il thread chiamante resta libero di
+/// leggere l'output del processo.
+///
+/// Un processo sorvegliato viene anche segnalato se resta senza output (vedi
+/// [`watchdog`]) e terminato allo stesso modo se l'operatore lo chiede.
+///
+/// # Arguments
+///
+/// * `child` - Il processo da sorvegliare, avviato con [`spawn_in_session`], senza gli
+///   output già presi dal chiamante
+/// * `kind` - Il tipo di processo riportato negli errori (es. "Script")
+/// * `timeout_secs` - La durata massima del processo in secondi, se limitata
+/// * `watch` - L'operazione sorvegliata, se il processo va segnalato quando resta senza output
+///
+/// # Returns
+///
+/// Il thread di sorveglianza, che restituisce lo stato di uscita del processo oppure un
+/// errore se il processo è stato interrotto
+fn supervise(mut child: Child, kind: &str, timeout_secs: Option<u64>, watch: Option<Arc<Watch>>) -> JoinHandle<Result<ExitStatus>> {
+    // Il job e l'esecuzione correnti sono noti solo nel thread chiamante
+    let cancel = jobs::cancel_flag();
+    let job = jobs::current();
+    let run_id = events::current_run_id();
+    let kind = kind.to_string();
+
+    thread::spawn(move || events::join_run(run_id, || jobs::join(job, || {
+        let start = Instant::now();
+        let mut monitor = watch.map(Monitor::new);
+        loop {
+            if let Some(status) = child.try_wait().context(format!("Error waiting for {}", kind.to_lowercase()))? {
+                return Ok(status);
             }
-            Ok(None) => {
-                // Processo ancora in esecuzione
-                if start.elapsed() > Duration::from_secs(timeout_secs) {
-                    // Timeout raggiunto, termina il processo
-                    info!("Timeout reached for command: {}", command);
-                    #[cfg(unix)]
-                    {
-                        // Su Unix, invia un SIGTERM
-                        unsafe {
-                            libc::kill(child.id() as i32, libc::SIGTERM);
-                        }
-                    }
-                    #[cfg(windows)]
-                    {
-                        child.kill().ok();
-                    }
-                    return Err(anyhow!("Command timed out after {} seconds", timeout_secs));
-                }
-                std::thread::sleep(Duration::from_millis(100));
+
+            let reason = match timeout_secs {
+                Some(timeout) if start.elapsed() >= Duration::from_secs(timeout) =>
+                    Some(format!("{} timed out after {} seconds", kind, timeout)),
+                _ if cancel.as_ref().is_some_and(|flag| flag.load(Ordering::SeqCst)) =>
+                    Some(format!("{} cancelled", kind)),
+                _ if INTERRUPTED.load(Ordering::SeqCst) =>
+                    Some(format!("{} interrupted", kind)),
+                _ => monitor.as_mut().and_then(Monitor::check).map(|idle|
+                    format!("{} stalled with no output for {} seconds and was terminated by the operator", kind, idle.as_secs())),
+            };
+            if let Some(reason) = reason {
+                warn!("{}: terminating process {}", reason, child.id());
+                terminate(&mut child);
+                return Err(GalateaError::script(None, reason).into());
             }
-            Err(e) => return Err(anyhow!("Error waiting for command: {}", e)),
+
+            thread::sleep(POLL_INTERVAL);
+        }
+    })))
+}
+
+/// Attende il risultato di un thread di sorveglianza
+fn join_supervisor(handle: JoinHandle<Result<ExitStatus>>) -> Result<ExitStatus> {
+    handle.join().unwrap_or_else(|_| Err(anyhow!("Process supervisor terminated unexpectedly")))
+}
+
+/// Termina il gruppo di processi di un processo avviato con [`spawn_in_session`]
+///
+/// Il gruppo riceve SIGTERM e, se qualche processo è ancora in esecuzione dopo
+/// [`TERMINATE_GRACE`], SIGKILL: così anche i figli che ignorano SIGTERM o sopravvivono
+/// al processo principale vengono terminati.
+fn terminate(child: &mut Child) {
+    #[cfg(unix)]
+    {
+        // Il processo è leader della propria sessione: l'id del gruppo è il suo pid
+        let group = child.id() as i32;
+        unsafe {
+            libc::killpg(group, libc::SIGTERM);
+        }
+
+        let deadline = Instant::now() + TERMINATE_GRACE;
+        while Instant::now() < deadline {
+            // Il leader va raccolto, altrimenti resta nel gruppo come zombie
+            let _ = child.try_wait();
+            if unsafe { libc::killpg(group, 0) } == -1 {
+                return;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+
+        warn!("Process group {} still running {}s after SIGTERM, sending SIGKILL", group, TERMINATE_GRACE.as_secs());
+        unsafe {
+            libc::killpg(group, libc::SIGKILL);
         }
     }
+
+    #[cfg(not(unix))]
+    if let Err(e) = child.kill() {
+        warn!("Failed to kill process {}: {}", child.id(), e);
+    }
+    let _ = child.wait();
 }
 
 /// Esegue uno script bash
@@ -121,14 +734,19 @@ pub fn run_command_with_timeout(command: &str, timeout_secs: u64) -> Result<()>
 ///
 /// * `script_path` - Il percorso dello script o della directory contenente lo script
 /// * `args` - Gli argomenti da passare allo script
+/// * `vars` - Le variabili del task, esportate come variabili d'ambiente (nome in maiuscolo)
+/// * `context` - Il contesto dell'esecuzione, esportato come variabili `GALATEA_*`
+/// * `progress` - Il marcatore di avanzamento da cercare nell'output, se dichiarato dal task
+/// * `limits` - I limiti di risorse con cui eseguire lo script
 ///
 /// # Returns
 ///
-/// `Ok(())` in caso di successo, altrimenti un errore
-pub fn run_bash_script(script_path: &Path, args: &[&str]) -> Result<()> {
+/// L'esito scritto dallo script in `GALATEA_RESULT_FILE`, se presente; un errore se lo
+/// script termina con un codice diverso da 0 o riporta lo stato `failed`
+pub fn run_bash_script(script_path: &Path, args: &[&str], vars: &HashMap<String, String>, context: &RunContext, progress: Option<&ProgressMarker>, limits: &ResourceLimits) -> Result<Option<ScriptResult>> {
     // Determina il percorso dello script
     let script = if script_path.is_dir() {
-        find_script_in_dir(script_path, &["install.sh"])?
+        find_script_in_dir(script_path, BASH_SCRIPT_NAMES)?
     } else {
         script_path.to_path_buf()
     };
@@ -140,6 +758,8 @@ pub fn run_bash_script(script_path: &Path, args: &[&str]) -> Result<()> {
         return Err(anyhow!("Script not found: {:?}", script));
     }
 
+    simulated_failure("Script", &format!("{:?} {}", script, args.join(" ")))?;
+
     // Imposta i permessi di esecuzione per lo script
     #[cfg(unix)]
     {
@@ -155,28 +775,98 @@ pub fn run_bash_script(script_path: &Path, args: &[&str]) -> Result<()> {
             .context(format!("Failed to set file permissions: {:?}", script))?;
     }
 
+    // Lo script registra i checkpoint raggiunti in un file della propria directory di lavoro
+    let run_dir = script.parent().unwrap_or(Path::new("."));
+    let checkpoint_file = run_dir.join(CHECKPOINT_FILE_NAME);
+    let _ = fs::remove_file(&checkpoint_file);
+
     // Esegui lo script
-    let mut child = Command::new(&script)
+    let result_file = PrivateFile::create("galatea_result", "result.json")?;
+    let mut command = limits.command(&script, context.sudo);
+    context.child_env.apply(&mut command);
+    command
         .args(args)
-        .current_dir(script.parent().unwrap_or(Path::new(".")))
-        //.stdout(Stdio::inherit())
-        //.stderr(Stdio::inherit())
-        .spawn()
-        .context(format!("Failed to execute script: {:?}", script))?;
+        .envs(vars.iter().map(|(name, value)| (env_var_name(name), value)))
+        .envs(context.env())
+        .env(RESULT_FILE_ENV, result_file.path())
+        .env(CHECKPOINT_FILE_ENV, &checkpoint_file)
+        .current_dir(run_dir);
+    if let Some(checkpoint) = &context.resume_from {
+        info!("Resuming script {:?} from checkpoint {}", script, checkpoint);
+        command.arg(RESUME_FROM_ARG).arg(checkpoint);
+    }
+    let credentials = sudo::prepare(context.sudo, &mut command)?;
 
-    // Attendi la terminazione del processo e verifica il codice di uscita
-    let status = child.wait()
-        .context(format!("Failed to wait for script: {:?}", script))?;
+    let started = SystemTime::now();
+    let (supervisor, stdout, stderr) = if context.interactive {
+        // Lo script usa il terminale: l'output, unico, non passa dal marcatore di avanzamento
+        let terminal = pty::spawn(&mut command, &context.task)
+            .context(format!("Failed to execute script in a terminal: {:?}", script))?;
+        credentials.feed_terminal(&terminal.input);
+        // L'operatore vede già l'output nel terminale: non serve sorvegliarlo
+        let supervisor = supervise(terminal.child, "Script", limits.timeout, None);
+        (supervisor, join_output(Some(terminal.output)), None)
+    } else {
+        // Con un marcatore di avanzamento l'output viene letto riga per riga; se ci sono valori
+        // segreti da mascherare, l'esecuzione è registrata o va sorvegliata viene sempre catturato
+        let watch = context.watchdog.watch(&format!("Task {}", context.task));
+        let capture = redact::is_active() || replay::is_recording() || watch.is_some();
+        if progress.is_some() || capture {
+            command.stdout(Stdio::piped());
+        }
+        if capture {
+            command.stderr(Stdio::piped());
+        }
 
-    if !status.success() {
+        let mut child = spawn_in_session(&mut command)
+            .context(format!("Failed to execute script: {:?}", script))?;
+        credentials.feed(&mut child);
 
-        return Err(anyhow!(
-            "Script failed with exit code: {}",
-            status.code().unwrap_or(-1)
-        ));
+        let stderr = echo_masked_stderr(&mut child, watch.as_ref());
+        let output = child.stdout.take().map(|stdout| watchdog::track(stdout, watch.as_ref()));
+        let supervisor = supervise(child, "Script", limits.timeout, watch);
+        let stdout = match (progress, output) {
+            (Some(marker), Some(stdout)) => marker.follow(stdout, true),
+            (None, Some(stdout)) => echo_masked(stdout, false),
+            _ => String::new(),
+        };
+        (supervisor, stdout, stderr)
+    };
+
+    // Attendi la terminazione del processo e verifica il codice di uscita
+    let status = join_supervisor(supervisor)
+        .context(format!("Failed to wait for script: {:?}", script));
+    let stderr = join_output(stderr);
+
+    if let Some(marker) = progress {
+        marker.finish(status.as_ref().is_ok_and(ExitStatus::success));
     }
+    // Il checkpoint conta soprattutto se lo script è fallito o è stato interrotto
+    track_checkpoint(&checkpoint_file, context);
 
-    Ok(())
+    let status = match status {
+        Ok(status) => status,
+        Err(e) => {
+            replay::record_command("script", &command, None, started, CommandOutcome {
+                exit_code: None,
+                stdout: &stdout,
+                stderr: &stderr,
+                error: Some(&e),
+            });
+            return Err(e);
+        },
+    };
+
+    let result = take_script_result(result_file.path());
+    let outcome = check_script_outcome("Script", status.code(), status.success(), result)
+        .map_err(|e| mac::explain_failure(e, &script, started));
+    replay::record_command("script", &command, None, started, CommandOutcome {
+        exit_code: status.code(),
+        stdout: &stdout,
+        stderr: &stderr,
+        error: outcome.as_ref().err(),
+    });
+    outcome
 }
 
 /// Esegue un playbook ansible
@@ -185,25 +875,22 @@ pub fn run_bash_script(script_path: &Path, args: &[&str]) -> Result<()> {
 ///
 /// * `playbook_path` - Il percorso del playbook o della directory contenente il playbook
 /// * `tag` - Il tag ansible da usare (install, uninstall, reset, remediate)
+/// * `vars` - Le variabili del task, passate al playbook come extra vars
+/// * `context` - Il contesto dell'esecuzione, esportato nell'ambiente e come extra vars
+/// * `progress` - Il marcatore di avanzamento da cercare nell'output, se dichiarato dal task
+/// * `limits` - I limiti di risorse con cui eseguire il playbook
 ///
 /// # Returns
 ///
-/// `Ok(())` in caso di successo, altrimenti un errore
-pub fn run_ansible_playbook(playbook_path: &Path, tag: &str) -> Result<()> {
+/// L'esito scritto dal playbook nel file `galatea_result_file`, se presente; un errore se
+/// ansible-playbook termina con un codice diverso da 0 o l'esito riporta lo stato `failed`
+pub fn run_ansible_playbook(playbook_path: &Path, tag: &str, vars: &HashMap<String, String>, context: &RunContext, progress: Option<&ProgressMarker>, limits: &ResourceLimits) -> Result<Option<ScriptResult>> {
     info!("Attempting to run ansible playbook at path: {:?}", playbook_path);
     
     // Determina il percorso del playbook
     let playbook = if playbook_path.is_dir() {
         // Cerca playbook con diverse estensioni
-        let possible_playbooks = &[
-            "playbook.yml", "playbook.yaml", 
-            "main.yml", "main.yaml", 
-            "site.yml", "site.yaml",
-            "local.yml", "local.yaml",
-            "install.yml", "install.yaml",
-            "entrypoint.yml", "entrypoint.yaml"
-        ];
-        find_script_in_dir(playbook_path, possible_playbooks)?
+        find_script_in_dir(playbook_path, PLAYBOOK_NAMES)?
     } else {
         // Usa direttamente il file se non è una directory
         playbook_path.to_path_buf()
@@ -216,6 +903,8 @@ pub fn run_ansible_playbook(playbook_path: &Path, tag: &str) -> Result<()> {
         return Err(anyhow!("Playbook not found: {:?}", playbook));
     }
 
+    simulated_failure("Ansible playbook", &format!("{:?} --tags {}", playbook, tag))?;
+
     // Comandi di debug per verificare il contenuto del playbook
     info!("Playbook content preview:");
     if let Ok(content) = fs::read_to_string(&playbook) {
@@ -226,37 +915,207 @@ pub fn run_ansible_playbook(playbook_path: &Path, tag: &str) -> Result<()> {
 
     // Esegui il playbook
     info!("Executing ansible-playbook with command: ansible-playbook -i localhost, --connection=local --tags={} {:?}", tag, playbook);
-    unsafe {
-        std::env::set_var("ANSIBLE_LOG_PATH", "/var/log/galatea/ansible.log");
-        std::env::set_var("ANSIBLE_DISPLAY_ARGS_TO_STDOUT", "no");
-        std::env::set_var("ANSIBLE_NO_LOG", "true");
-        std::env::set_var("ANSIBLE_STDOUT_CALLBACK", "null");
-    }
-    let mut child = Command::new("ansible-playbook")
+    let mut command = limits.command("ansible-playbook", context.sudo);
+    context.child_env.apply(&mut command);
+    command
         .arg("-i")
         .arg("localhost,")
         .arg("--connection=local")
-        .arg(format!("--tags={}", tag))
+        .arg(format!("--tags={}", tag));
+
+    // L'ambiente di ansible vale solo per questo processo, non per Galatea
+    let verbose = ansible_verbosity();
+    command.envs(ansible_env(context, progress.is_some() || verbose.is_some()));
+    if let Some(flag) = verbose {
+        command.arg(flag);
+    }
+
+    // Le variabili vengono passate tramite file per evitare problemi di quoting, insieme
+    // al percorso in cui il playbook può scrivere il proprio esito
+    let result_file = PrivateFile::create("galatea_result", "result.json")?;
+    let mut vars = vars.clone();
+    vars.insert(RESULT_FILE_VAR.to_string(), result_file.path().to_string_lossy().to_string());
+    command.env(RESULT_FILE_ENV, result_file.path());
+
+    // Il contesto dell'esecuzione è disponibile sia nell'ambiente sia come extra vars
+    for (name, value) in context.env() {
+        vars.insert(name.to_lowercase(), value.clone());
+        command.env(name, value);
+    }
+
+    let vars_file = write_extra_vars_file(&vars)?;
+    command
+        .arg("--extra-vars")
+        .arg(format!("@{}", vars_file.path().display()))
         .arg(&playbook)
-        .current_dir(playbook.parent().unwrap_or(Path::new(".")))
-        //.stdout(Stdio::inherit())
-        //.stderr(Stdio::inherit())
-        .spawn()
-        .context(format!("Failed to execute ansible playbook: {:?}", playbook))?;
+        .current_dir(playbook.parent().unwrap_or(Path::new(".")));
+    let credentials = sudo::prepare(context.sudo, &mut command)?;
+
+    // Con un marcatore di avanzamento l'output dei task (es. i messaggi di debug) viene
+    // letto senza essere mostrato
+    if progress.is_some() {
+        command.stdout(Stdio::piped());
+    }
+
+    // Se ci sono valori segreti da mascherare, l'esecuzione è registrata o va sorvegliata
+    // l'output viene catturato e filtrato
+    let watch = context.watchdog.watch(&format!("Task {}", context.task));
+    if redact::is_active() || replay::is_recording() || watch.is_some() {
+        command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    }
+
+    let started = SystemTime::now();
+    let child = spawn_in_session(&mut command)
+        .context(format!("Failed to execute ansible playbook: {:?}", playbook));
 
     // Attendi la terminazione del processo e verifica il codice di uscita
-    let status = child.wait()
-        .context(format!("Failed to wait for ansible playbook: {:?}", playbook))?;
+    let mut stdout = String::new();
+    let mut stderr = String::new();
+    let status = child.and_then(|mut child| {
+        credentials.feed(&mut child);
+        let stderr_handle = echo_masked_stderr(&mut child, watch.as_ref());
+        let output = child.stdout.take().map(|output| watchdog::track(output, watch.as_ref()));
+        let supervisor = supervise(child, "Ansible playbook", limits.timeout, watch);
+        stdout = match (progress, output) {
+            (Some(marker), Some(output)) => marker.follow(output, false),
+            (None, Some(output)) => echo_masked(output, false),
+            _ => String::new(),
+        };
+        let status = join_supervisor(supervisor)
+            .context(format!("Failed to wait for ansible playbook: {:?}", playbook));
+        stderr = join_output(stderr_handle);
+        status
+    });
 
-    if !status.success() {
-        return Err(anyhow!(
-            "Ansible playbook failed with exit code: {}",
-            status.code().unwrap_or(-1)
-        ));
+    if let Some(marker) = progress {
+        marker.finish(status.as_ref().is_ok_and(ExitStatus::success));
     }
 
+    drop(vars_file);
+
+    let status = match status {
+        Ok(status) => status,
+        Err(e) => {
+            replay::record_command("playbook", &command, Some(&vars), started, CommandOutcome {
+                exit_code: None,
+                stdout: &stdout,
+                stderr: &stderr,
+                error: Some(&e),
+            });
+            return Err(e);
+        },
+    };
+    let result = take_script_result(result_file.path());
+    let outcome = check_script_outcome("Ansible playbook", status.code(), status.success(), result)
+        .map_err(|e| mac::explain_failure(e, &playbook, started));
+    replay::record_command("playbook", &command, Some(&vars), started, CommandOutcome {
+        exit_code: status.code(),
+        stdout: &stdout,
+        stderr: &stderr,
+        error: outcome.as_ref().err(),
+    });
+    let result = outcome?;
+
     info!("Ansible playbook executed successfully");
-    Ok(())
+    Ok(result)
+}
+
+/// Conserva nello stato del task l'ultimo checkpoint raggiunto da uno script
+///
+/// Il file dei checkpoint contiene un nome per riga; vale l'ultima riga non vuota.
+///
+/// # Arguments
+///
+/// * `checkpoint_file` - Il file dei checkpoint scritto dallo script
+/// * `context` - Il contesto dell'esecuzione, con il file di stato dei checkpoint
+fn track_checkpoint(checkpoint_file: &Path, context: &RunContext) {
+    let Some(state_file) = &context.checkpoint_state else {
+        return;
+    };
+    let Some(checkpoint) = fs::read_to_string(checkpoint_file).ok()
+        .and_then(|content| content.lines().rev().map(str::trim).find(|line| !line.is_empty()).map(str::to_string))
+    else {
+        return;
+    };
+
+    info!("Script of task {} reached checkpoint {}", context.task, checkpoint);
+    let written = state_file.parent().map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| state_crypt::write(state_file, format!("{}\n", checkpoint)));
+    if let Err(e) = written {
+        warn!("Failed to record checkpoint {} of task {} in {:?}: {}", checkpoint, context.task, state_file, e);
+    }
+}
+
+/// Restituisce l'ambiente con cui eseguire ansible-playbook
+///
+/// All'ambiente predefinito ([`ANSIBLE_DEFAULT_ENV`] e il log in `ansible.log` accanto a
+/// quelli di Galatea) si applicano, in ordine, l'output dei task quando serve e le
+/// variabili indicate in `ansible_env`, che hanno sempre la precedenza.
+///
+/// # Arguments
+///
+/// * `context` - Il contesto dell'esecuzione, con le variabili della configurazione
+/// * `show_output` - Se `true` ansible scrive l'output dei task (marcatori di avanzamento o `-v`)
+fn ansible_env(context: &RunContext, show_output: bool) -> BTreeMap<String, String> {
+    let ansible_log_dir = logger::get_log_directory()
+        .unwrap_or_else(|| layout::SYSTEM_LOG_DIR.to_string());
+
+    let mut env: BTreeMap<String, String> = ANSIBLE_DEFAULT_ENV.iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    env.insert("ANSIBLE_LOG_PATH".to_string(), Path::new(&ansible_log_dir).join("ansible.log").to_string_lossy().to_string());
+    if show_output {
+        env.insert("ANSIBLE_STDOUT_CALLBACK".to_string(), "default".to_string());
+    }
+
+    for (name, value) in &context.ansible_env {
+        debug!("Ansible environment override: {}={}", name, redact::mask(value));
+        env.insert(name.clone(), value.clone());
+    }
+    env
+}
+
+/// Restituisce l'opzione di verbosità di ansible-playbook per il livello di log corrente
+///
+/// Con `-v` e `-vv` da linea di comando l'output di ansible viene mostrato, con il
+/// dettaglio corrispondente; nell'interfaccia TUI resta nascosto per non sporcare lo schermo.
+fn ansible_verbosity() -> Option<&'static str> {
+    if !logger::console_output() {
+        return None;
+    }
+    match logger::verbosity() {
+        logger::Verbosity::Verbose => Some("-v"),
+        logger::Verbosity::Debug => Some("-vvv"),
+        _ => None,
+    }
+}
+
+/// Converte il nome di una variabile del task nel nome della variabile d'ambiente
+///
+/// # Arguments
+///
+/// * `name` - Il nome della variabile (es. `domain_name`)
+///
+/// # Returns
+///
+/// Il nome in maiuscolo con i caratteri non alfanumerici sostituiti da '_' (es. `DOMAIN_NAME`)
+pub fn env_var_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect()
+}
+
+/// Scrive le variabili del task in un file YAML temporaneo leggibile solo dal proprietario
+///
+/// Le variabili possono contenere segreti decifrati: il file è un [`PrivateFile`].
+fn write_extra_vars_file(vars: &HashMap<String, String>) -> Result<PrivateFile> {
+    let yaml = serde_yaml::to_string(vars)
+        .context("Failed to serialize task variables")?;
+
+    let file = PrivateFile::create("galatea_vars", "vars.yml")?;
+    fs::write(file.path(), yaml)
+        .context(format!("Failed to write extra vars file: {:?}", file.path()))?;
+    Ok(file)
 }
 
 /// Cerca uno script all'interno di una directory
@@ -269,7 +1128,7 @@ pub fn run_ansible_playbook(playbook_path: &Path, tag: &str) -> Result<()> {
 /// # Returns
 ///
 /// Il percorso dello script, se trovato
-fn find_script_in_dir(dir: &Path, script_names: &[&str]) -> Result<PathBuf> {
+pub fn find_script_in_dir(dir: &Path, script_names: &[&str]) -> Result<PathBuf> {
     // Verifica che la directory esista
     if !dir.exists() || !dir.is_dir() {
         return Err(anyhow!("Directory not found: {:?}", dir));
@@ -357,33 +1216,140 @@ pub fn is_ansible_available() -> bool {
 
 /// Esegue un comando con privilegi elevati
 ///
+/// Da root il comando viene eseguito direttamente; altrimenti tramite sudo, chiedendo la
+/// password se necessario (vedi [`sudo`]).
+///
 /// # Arguments
 ///
 /// * `command` - Il comando da eseguire
+/// * `timeout_secs` - La durata massima del comando in secondi, se limitata
+/// * `env` - Le variabili dell'ambiente di Galatea passate al comando
 ///
 /// # Returns
 ///
 /// `Ok(())` in caso di successo, altrimenti un errore
-pub fn run_with_sudo(command: &str) -> Result<()> {
+pub fn run_with_sudo(command: &str, timeout_secs: Option<u64>, env: &EnvPolicy) -> Result<()> {
     info!("Running command with sudo: {}", command);
 
-    let mut child = Command::new("sudo")
-        .args(&["-S", "sh", "-c", command])
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
-        .spawn()
+    let mut process = Command::new("sh");
+    env.apply(&mut process);
+    process.args(&["-c", command]);
+    let credentials = sudo::prepare(true, &mut process)?;
+    process.stdout(Stdio::inherit()).stderr(Stdio::inherit());
+
+    let mut child = spawn_in_session(&mut process)
         .context(format!("Failed to execute command with sudo: {}", command))?;
+    credentials.feed(&mut child);
 
     // Attendi la terminazione del processo e verifica il codice di uscita
-    let status = child.wait()
+    let status = join_supervisor(supervise(child, "Command", timeout_secs, None))
         .context(format!("Failed to wait for command with sudo: {}", command))?;
 
     if !status.success() {
-        return Err(anyhow!(
+        return Err(GalateaError::script(status.code(), format!(
             "Command with sudo failed with exit code: {}",
             status.code().unwrap_or(-1)
-        ));
+        )).into());
     }
 
     Ok(())
 }
+
+/// Esegue un programma passandogli un documento sullo standard input
+///
+/// L'output del programma non viene mostrato: ogni riga, mascherata, finisce nel log.
+///
+/// # Arguments
+///
+/// * `program` - Il programma da eseguire
+/// * `input` - Il testo scritto sullo standard input, che viene poi chiuso
+/// * `timeout_secs` - La durata massima del programma in secondi, se limitata
+/// * `env` - Le variabili dell'ambiente di Galatea passate al programma
+///
+/// # Returns
+///
+/// `Ok(())` in caso di successo, altrimenti un errore
+pub fn run_with_input(program: &Path, input: &str, timeout_secs: Option<u64>, env: &EnvPolicy) -> Result<()> {
+    info!("Running {}", program.display());
+
+    let mut process = Command::new(program);
+    env.apply(&mut process);
+    process.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped());
+
+    let mut child = spawn_in_session(&mut process)
+        .context(format!("Failed to execute {}", program.display()))?;
+
+    // Il documento viene scritto in un thread: un programma che non legge lo standard input
+    // non deve bloccare Galatea
+    if let Some(mut stdin) = child.stdin.take() {
+        let input = input.to_string();
+        thread::spawn(move || {
+            let _ = stdin.write_all(input.as_bytes());
+        });
+    }
+
+    let name = program.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let readers: Vec<JoinHandle<()>> = [
+        child.stdout.take().map(|output| Box::new(output) as Box<dyn Read + Send>),
+        child.stderr.take().map(|output| Box::new(output) as Box<dyn Read + Send>),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|output| {
+        let name = name.clone();
+        thread::spawn(move || {
+            for line in BufReader::new(output).lines().map_while(Result::ok) {
+                info!("{}: {}", name, redact::mask(&line));
+            }
+        })
+    })
+    .collect();
+
+    let status = join_supervisor(supervise(child, "Program", timeout_secs, None))
+        .context(format!("Failed to wait for {}", program.display()));
+    for reader in readers {
+        let _ = reader.join();
+    }
+
+    let status = status?;
+    if !status.success() {
+        return Err(GalateaError::script(status.code(), format!(
+            "{} failed with exit code: {}",
+            program.display(),
+            status.code().unwrap_or(-1)
+        )).into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_pattern_needs_a_capture_group() {
+        assert!(ProgressMarker::new("Task", r"progress \d+").is_err());
+        assert!(ProgressMarker::new("Task", r"progress (").is_err());
+    }
+
+    #[test]
+    fn parses_named_percentage_and_message() {
+        let marker = ProgressMarker::new("Task", r"^PROGRESS (?P<percent>\d+) (?P<message>.*)$").unwrap();
+        assert_eq!(marker.parse("PROGRESS 40 downloading"), Some((40.0, "downloading".to_string())));
+        assert_eq!(marker.parse("unrelated line"), None);
+    }
+
+    #[test]
+    fn parses_current_and_total() {
+        let marker = ProgressMarker::new("Task", r"step (?P<current>\d+)/(?P<total>\d+)").unwrap();
+        assert_eq!(marker.parse("step 3/4"), Some((75.0, "75%".to_string())));
+    }
+
+    #[test]
+    fn parses_first_group_and_clamps() {
+        let marker = ProgressMarker::new("Task", r"(\d+)%").unwrap();
+        assert_eq!(marker.parse("at 12%"), Some((12.0, "12%".to_string())));
+        assert_eq!(marker.parse("at 250%"), Some((100.0, "250%".to_string())));
+    }
+}
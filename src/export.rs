@@ -0,0 +1,427 @@
+//! Esportazione di task e stack come playbook Ansible
+//!
+//! Questo modulo converte il catalogo di Galatea in una struttura Ansible autonoma,
+//! per chi passa a una gestione della configurazione completa:
+//!
+//! ```text
+//! <output>/
+//!   roles/<task>/tasks/main.yml     # azione install del task
+//!   roles/<task>/handlers/main.yml  # handler del playbook originale (task ansible)
+//!   roles/<task>/defaults/main.yml  # variabili del play originale (task ansible)
+//!   roles/<task>/files/             # file del bundle
+//!   <stack>.yml                     # un playbook per stack
+//!   site.yml                        # importa tutti i playbook esportati
+//! ```
+//!
+//! I task ansible vengono convertiti estraendo i task con il tag `install` (o `always`);
+//! i task bash diventano un ruolo che copia il bundle ed esegue `install.sh install`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result, anyhow};
+use log::{info, warn};
+use serde_yaml::{Mapping, Value};
+
+use crate::config::Config;
+use crate::executor::{self, BASH_SCRIPT_NAMES, PLAYBOOK_NAMES};
+use crate::stack::{Stack, load_stacks};
+use crate::task::{ScriptType, Task, load_tasks};
+
+/// Directory in cui i ruoli bash copiano il bundle sull'host gestito
+const REMOTE_BUNDLE_DIR: &str = "/opt/galatea";
+
+/// Risultato dell'esportazione
+#[derive(Debug, Clone, Default)]
+pub struct ExportReport {
+    /// Ruoli generati
+    pub roles: Vec<String>,
+
+    /// Playbook generati
+    pub playbooks: Vec<PathBuf>,
+
+    /// Elementi del playbook originale non convertiti
+    pub warnings: Vec<String>,
+}
+
+/// Esporta stack e task come playbook e ruoli Ansible
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+/// * `output_dir` - La directory in cui scrivere il risultato
+/// * `stack_refs` - Gli stack da esportare; tutti se vuoto e non sono indicati task
+/// * `task_refs` - I task da esportare singolarmente, raccolti in `tasks.yml`
+///
+/// # Returns
+///
+/// Il report dell'esportazione
+pub fn export_ansible(config: &Config, output_dir: &Path, stack_refs: &[String], task_refs: &[String]) -> Result<ExportReport> {
+    info!("Exporting catalog as Ansible playbooks to {:?}", output_dir);
+
+    let mut tasks = load_tasks(config)?;
+    let stacks = load_stacks(config, &tasks)?;
+
+    let selected_stacks: Vec<&Stack> = if stack_refs.is_empty() && task_refs.is_empty() {
+        stacks.iter().collect()
+    } else {
+        stack_refs.iter()
+            .map(|reference| stacks.iter()
+                .find(|s| s.matches_ref(reference))
+                .ok_or_else(|| anyhow!("Stack not found: {}", reference)))
+            .collect::<Result<_>>()?
+    };
+
+    fs::create_dir_all(output_dir.join("roles"))
+        .context(format!("Failed to create output directory: {:?}", output_dir))?;
+
+    let mut report = ExportReport::default();
+
+    for stack in selected_stacks {
+        let mut roles = Vec::new();
+
+        for task_name in &stack.task_names {
+            let Some(task) = tasks.iter_mut().find(|t| t.matches_ref(task_name)) else {
+                report.warnings.push(format!("task {} dello stack {} non trovato", task_name, stack.name));
+                continue;
+            };
+
+            stack.select_task_version(task_name, task)?;
+            let role = export_task_role(config, task, output_dir, &mut report);
+            task.pinned = None;
+
+            let role = role?;
+            roles.push(role_entry(&role, task, stack.task_variables.get(task_name), &stack_variable_names(stack)));
+        }
+
+        let playbook = stack_playbook(stack, roles);
+        let path = output_dir.join(format!("{}.yml", role_name(&stack.name)));
+        write_yaml(&path, &playbook)?;
+        report.playbooks.push(path);
+    }
+
+    if !task_refs.is_empty() {
+        let mut roles = Vec::new();
+        for reference in task_refs {
+            let task = tasks.iter_mut()
+                .find(|t| t.matches_ref(reference))
+                .ok_or_else(|| anyhow!("Task not found: {}", reference))?;
+
+            let role = export_task_role(config, task, output_dir, &mut report)?;
+            roles.push(role_entry(&role, task, None, &[]));
+        }
+
+        let playbook = play("Task esportati da Galatea", Mapping::new(), Vec::new(), roles);
+        let path = output_dir.join("tasks.yml");
+        write_yaml(&path, &playbook)?;
+        report.playbooks.push(path);
+    }
+
+    // Playbook principale che importa tutti gli altri
+    let site: Vec<Value> = report.playbooks.iter()
+        .filter_map(|path| path.file_name())
+        .map(|name| {
+            let mut entry = Mapping::new();
+            entry.insert("import_playbook".into(), name.to_string_lossy().to_string().into());
+            Value::Mapping(entry)
+        })
+        .collect();
+    write_yaml(&output_dir.join("site.yml"), &Value::Sequence(site))?;
+
+    for warning in &report.warnings {
+        warn!("Ansible export: {}", warning);
+    }
+    info!("Ansible export completed: {} roles, {} playbooks", report.roles.len(), report.playbooks.len());
+
+    Ok(report)
+}
+
+/// Genera il ruolo di un task (una sola volta per ruolo) e ne restituisce il nome
+fn export_task_role(config: &Config, task: &mut Task, output_dir: &Path, report: &mut ExportReport) -> Result<String> {
+    let role = match task.effective_version() {
+        Some(version) if task.pinned.is_some() => role_name(&format!("{}_{}", task.name, version)),
+        _ => role_name(&task.name),
+    };
+
+    if report.roles.contains(&role) {
+        return Ok(role);
+    }
+
+    let bundle = task.download(config)
+        .context(format!("Failed to download task {} for export", task.name))?;
+    let role_dir = output_dir.join("roles").join(&role);
+    if role_dir.exists() {
+        fs::remove_dir_all(&role_dir)
+            .context(format!("Failed to clean role directory: {:?}", role_dir))?;
+    }
+
+    // I task mixed vengono esportati come ansible se il bundle contiene un playbook
+    let playbook = if bundle.is_dir() {
+        executor::find_script_in_dir(&bundle, PLAYBOOK_NAMES).ok()
+    } else {
+        Some(bundle.clone()).filter(|_| task.script_type == ScriptType::Ansible)
+    };
+
+    match (task.script_type, playbook) {
+        (ScriptType::Ansible, Some(playbook)) | (ScriptType::Mixed, Some(playbook)) => {
+            export_ansible_role(task, &bundle, &playbook, &role_dir, report)?
+        },
+        (ScriptType::Ansible, None) => {
+            return Err(anyhow!("No playbook found in bundle of task {}", task.name));
+        },
+        (ScriptType::Wasm, _) => {
+            return Err(anyhow!("Task {} uses a WASM runner and cannot be exported as a role", task.name));
+        },
+        _ => export_bash_role(task, &bundle, &role_dir)?,
+    }
+
+    info!("Exported task {} as role {}", task.name, role);
+    report.roles.push(role.clone());
+    Ok(role)
+}
+
+/// Converte il playbook di un task ansible in un ruolo
+fn export_ansible_role(task: &Task, bundle: &Path, playbook: &Path, role_dir: &Path, report: &mut ExportReport) -> Result<()> {
+    let content = fs::read_to_string(playbook)
+        .context(format!("Failed to read playbook: {:?}", playbook))?;
+    let yaml: Value = serde_yaml::from_str(&content)
+        .context(format!("Failed to parse playbook: {:?}", playbook))?;
+    let plays = yaml.as_sequence()
+        .ok_or_else(|| anyhow!("Playbook of task {} is not a list of plays", task.name))?;
+
+    let mut tasks = Vec::new();
+    let mut handlers = Vec::new();
+    let mut defaults = Mapping::new();
+
+    for play in plays {
+        let play_tags = tags_of(play);
+
+        for key in ["pre_tasks", "roles", "post_tasks", "vars_files", "import_playbook"] {
+            if play.get(key).is_some() {
+                report.warnings.push(format!("'{}' nel playbook del task {} non è stato convertito", key, task.name));
+            }
+        }
+
+        if let Some(vars) = play.get("vars").and_then(|v| v.as_mapping()) {
+            defaults.extend(vars.clone());
+        }
+
+        if let Some(play_handlers) = play.get("handlers").and_then(|h| h.as_sequence()) {
+            handlers.extend(play_handlers.iter().cloned());
+        }
+
+        for item in play.get("tasks").and_then(|t| t.as_sequence()).into_iter().flatten() {
+            let tags: Vec<String> = play_tags.iter().cloned().chain(tags_of(item)).collect();
+            if tags.iter().any(|t| t == "install" || t == "always") {
+                tasks.push(without_never_tag(item));
+            }
+        }
+    }
+
+    if tasks.is_empty() {
+        report.warnings.push(format!("nessun task con il tag 'install' nel playbook del task {}", task.name));
+    }
+
+    write_yaml(&role_dir.join("tasks").join("main.yml"), &Value::Sequence(tasks))?;
+    if !handlers.is_empty() {
+        write_yaml(&role_dir.join("handlers").join("main.yml"), &Value::Sequence(handlers))?;
+    }
+    if !defaults.is_empty() {
+        write_yaml(&role_dir.join("defaults").join("main.yml"), &Value::Mapping(defaults))?;
+    }
+
+    // File e template usati dal playbook
+    if bundle.is_dir() {
+        for dir in ["files", "templates"] {
+            let source = bundle.join(dir);
+            if source.is_dir() {
+                copy_dir(&source, &role_dir.join(dir))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Genera il ruolo di un task bash: copia il bundle ed esegue l'azione install
+fn export_bash_role(task: &Task, bundle: &Path, role_dir: &Path) -> Result<()> {
+    let files_dir = role_dir.join("files").join("bundle");
+    let script = if bundle.is_dir() {
+        copy_dir(bundle, &files_dir)?;
+        executor::find_script_in_dir(bundle, BASH_SCRIPT_NAMES)?
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .ok_or_else(|| anyhow!("Invalid script path for task {}", task.name))?
+    } else {
+        let name = bundle.file_name()
+            .ok_or_else(|| anyhow!("Invalid bundle path for task {}", task.name))?;
+        fs::create_dir_all(&files_dir)
+            .context(format!("Failed to create directory: {:?}", files_dir))?;
+        fs::copy(bundle, files_dir.join(name))
+            .context(format!("Failed to copy bundle of task {}", task.name))?;
+        name.to_string_lossy().to_string()
+    };
+
+    let remote_dir = format!("{}/{}", REMOTE_BUNDLE_DIR, task.name);
+    let mut content = format!(
+        "---\n# Ruolo generato da Galatea dal task bash {}\n\
+         - name: Copy {} bundle\n  ansible.builtin.copy:\n    src: bundle/\n    dest: \"{}/\"\n    mode: preserve\n\n\
+         - name: Install {}\n  ansible.builtin.command:\n    cmd: bash {} install\n    chdir: \"{}\"\n",
+        task.name, task.name, remote_dir, task.name, script, remote_dir
+    );
+
+    // Le evidenze di rilevamento rendono l'esecuzione idempotente
+    if let Some(file) = task.detect.as_ref().and_then(|d| d.files.first()) {
+        content.push_str(&format!("    creates: \"{}\"\n", file));
+    }
+    content.push_str("  environment: \"{{ galatea_env | default({}) }}\"\n");
+
+    let tasks_dir = role_dir.join("tasks");
+    fs::create_dir_all(&tasks_dir)
+        .context(format!("Failed to create directory: {:?}", tasks_dir))?;
+    fs::write(tasks_dir.join("main.yml"), content)
+        .context(format!("Failed to write role of task {}", task.name))
+}
+
+/// Costruisce la voce di un ruolo nel playbook con le variabili del task
+///
+/// I riferimenti `{{ nome }}` delle variabili di Galatea sono già espressioni Jinja valide.
+fn role_entry(role: &str, task: &Task, task_variables: Option<&HashMap<String, String>>, stack_variables: &[String]) -> Value {
+    let mut entry = Mapping::new();
+    entry.insert("role".into(), role.into());
+
+    let mut vars = Mapping::new();
+    let mut names: Vec<String> = stack_variables.to_vec();
+    if let Some(task_variables) = task_variables {
+        let mut sorted: Vec<_> = task_variables.iter().collect();
+        sorted.sort();
+        for (name, template) in sorted {
+            vars.insert(name.as_str().into(), template.as_str().into());
+            names.push(name.clone());
+        }
+    }
+
+    // Gli script bash ricevono le variabili come variabili d'ambiente in maiuscolo
+    if task.script_type == ScriptType::Bash && !names.is_empty() {
+        let mut env = Mapping::new();
+        for name in names {
+            env.insert(name.to_uppercase().into(), format!("{{{{ {} }}}}", name).into());
+        }
+        vars.insert("galatea_env".into(), Value::Mapping(env));
+    }
+
+    if !vars.is_empty() {
+        entry.insert("vars".into(), Value::Mapping(vars));
+    }
+    Value::Mapping(entry)
+}
+
+/// Costruisce il playbook di uno stack
+fn stack_playbook(stack: &Stack, roles: Vec<Value>) -> Value {
+    let mut vars = Mapping::new();
+    let mut prompts = Vec::new();
+
+    for variable in &stack.variables {
+        match &variable.default {
+            Some(default) => {
+                vars.insert(variable.name.as_str().into(), default.as_str().into());
+            },
+            None => {
+                let mut prompt = Mapping::new();
+                prompt.insert("name".into(), variable.name.as_str().into());
+                prompt.insert("prompt".into(), variable.prompt_text().into());
+                prompt.insert("private".into(), false.into());
+                prompts.push(Value::Mapping(prompt));
+            }
+        }
+    }
+
+    let name = if stack.description.is_empty() { stack.name.clone() } else { stack.description.clone() };
+    play(&name, vars, prompts, roles)
+}
+
+/// Costruisce un playbook con un solo play
+fn play(name: &str, vars: Mapping, prompts: Vec<Value>, roles: Vec<Value>) -> Value {
+    let mut play = Mapping::new();
+    play.insert("name".into(), name.into());
+    play.insert("hosts".into(), "all".into());
+    play.insert("become".into(), true.into());
+    if !prompts.is_empty() {
+        play.insert("vars_prompt".into(), Value::Sequence(prompts));
+    }
+    if !vars.is_empty() {
+        play.insert("vars".into(), Value::Mapping(vars));
+    }
+    play.insert("roles".into(), Value::Sequence(roles));
+    Value::Sequence(vec![Value::Mapping(play)])
+}
+
+/// Restituisce i nomi delle variabili di uno stack
+fn stack_variable_names(stack: &Stack) -> Vec<String> {
+    stack.variables.iter().map(|v| v.name.clone()).collect()
+}
+
+/// Restituisce i tag di un task o di un play (stringa o lista)
+fn tags_of(value: &Value) -> Vec<String> {
+    match value.get("tags") {
+        Some(Value::String(tag)) => vec![tag.clone()],
+        Some(Value::Sequence(tags)) => tags.iter()
+            .filter_map(|t| t.as_str().map(|s| s.to_string()))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Rimuove il tag `never`, che nel ruolo impedirebbe l'esecuzione senza --tags
+fn without_never_tag(item: &Value) -> Value {
+    let mut item = item.clone();
+    if let Some(map) = item.as_mapping_mut() {
+        let tags: Vec<Value> = tags_of(&Value::Mapping(map.clone())).into_iter()
+            .filter(|t| t != "never")
+            .map(Value::from)
+            .collect();
+        if map.contains_key("tags") {
+            map.insert("tags".into(), Value::Sequence(tags));
+        }
+    }
+    item
+}
+
+/// Converte un nome in un nome di ruolo valido per Ansible
+fn role_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c.to_ascii_lowercase() } else { '_' })
+        .collect()
+}
+
+/// Scrive un documento YAML creando le directory necessarie
+fn write_yaml(path: &Path, value: &Value) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .context(format!("Failed to create directory: {:?}", parent))?;
+    }
+
+    let yaml = serde_yaml::to_string(value)
+        .context(format!("Failed to serialize {:?}", path))?;
+    fs::write(path, format!("---\n{}", yaml))
+        .context(format!("Failed to write {:?}", path))
+}
+
+/// Copia ricorsivamente una directory
+fn copy_dir(source: &Path, destination: &Path) -> Result<()> {
+    fs::create_dir_all(destination)
+        .context(format!("Failed to create directory: {:?}", destination))?;
+
+    for entry in fs::read_dir(source).context(format!("Failed to read directory: {:?}", source))? {
+        let entry = entry.context("Failed to read directory entry")?;
+        let target = destination.join(entry.file_name());
+        if entry.path().is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)
+                .context(format!("Failed to copy {:?}", entry.path()))?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,477 @@
+//! Rapporti di stato per la flotta
+//!
+//! Ogni macchina può inviare periodicamente il proprio stato (stack installati, derive,
+//! versioni dei task, riavvii in sospeso) a un endpoint HTTPS centrale, così che una
+//! semplice dashboard possa mostrare lo stato dell'intera flotta. Con la sezione
+//! `phone_home` della configurazione il demone invia il rapporto ogni `interval` minuti,
+//! e `galatea scheduled-run` al termine di ogni esecuzione:
+//!
+//! ```yaml
+//! phone_home:
+//!   url: https://fleet.example.com/api/hosts
+//!   token: "..."          # inviato come Bearer token, anche cifrato
+//!   interval: 60          # minuti tra due invii in modalità demone
+//! ```
+//!
+//! L'endpoint riceve in POST il rapporto in JSON ([`HostReport`]); `galatea report`
+//! stampa lo stesso documento o lo salva su file.
+//!
+//! `galatea fleet status` legge i rapporti di più macchine (da una directory di file JSON
+//! o da un URL che restituisce l'elenco dei rapporti) e li riassume in una tabella di
+//! conformità della flotta ([`FleetSummary`]).
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Local};
+use log::info;
+use reqwest::blocking::Client;
+use serde::{Serialize, Deserialize};
+
+use crate::config::Config;
+use crate::drift::{self, ComplianceStatus};
+use crate::reboot;
+use crate::redact;
+use crate::secrets;
+use crate::source;
+use crate::stack::load_stacks;
+use crate::task::load_tasks;
+use crate::utils;
+
+/// Configurazione dell'invio periodico dello stato
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhoneHomeConfig {
+    /// URL a cui inviare i rapporti
+    pub url: String,
+
+    /// Token inviato nell'intestazione `Authorization: Bearer`
+    #[serde(default)]
+    pub token: Option<String>,
+
+    /// Minuti tra due invii in modalità demone
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+}
+
+fn default_interval() -> u64 {
+    60
+}
+
+/// Stato di un task nel rapporto
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskReport {
+    /// Indica se il task è installato
+    pub installed: bool,
+
+    /// Versione del bundle, se dichiarata
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+
+    /// Esito della verifica delle evidenze, per i task installati
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compliance: Option<ComplianceStatus>,
+
+    /// Indica se l'ultima operazione sul task è fallita
+    #[serde(default)]
+    pub failed: bool,
+
+    /// Indica se il task è in quarantena
+    #[serde(default)]
+    pub quarantined: bool,
+}
+
+/// Stato di una macchina inviato all'endpoint centrale
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostReport {
+    /// Nome host della macchina
+    pub host: String,
+
+    /// Data e ora del rapporto (RFC 3339)
+    pub reported_at: String,
+
+    /// Versione di Galatea
+    pub galatea_version: String,
+
+    /// Ruolo assegnato alla macchina
+    #[serde(default)]
+    pub role: Option<String>,
+
+    /// Stato degli stack: installed, partial o absent
+    #[serde(default)]
+    pub stacks: BTreeMap<String, String>,
+
+    /// Stato dei task del catalogo, per nome qualificato
+    #[serde(default)]
+    pub tasks: BTreeMap<String, TaskReport>,
+
+    /// Numero di task installati conformi
+    #[serde(default)]
+    pub compliant: usize,
+
+    /// Numero di task installati in deriva
+    #[serde(default)]
+    pub drifted: usize,
+
+    /// Numero di task installati non verificabili
+    #[serde(default)]
+    pub unverifiable: usize,
+
+    /// Indica se la macchina deve essere riavviata
+    #[serde(default)]
+    pub reboot_required: bool,
+}
+
+impl HostReport {
+    /// Serializza il rapporto in JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize host report")
+    }
+
+    /// Restituisce la data del rapporto, se valida
+    fn reported_at(&self) -> Option<DateTime<Local>> {
+        DateTime::parse_from_rfc3339(&self.reported_at).ok()
+            .map(|date| date.with_timezone(&Local))
+    }
+}
+
+/// Riepilogo di una macchina nella tabella della flotta
+#[derive(Debug, Clone, Serialize)]
+pub struct HostSummary {
+    /// Nome host della macchina
+    pub host: String,
+
+    /// Ruolo assegnato alla macchina
+    pub role: Option<String>,
+
+    /// Data e ora del rapporto (RFC 3339)
+    pub reported_at: String,
+
+    /// Indica se il rapporto è più vecchio della soglia indicata
+    pub stale: bool,
+
+    /// Numero di stack completamente installati
+    pub installed_stacks: usize,
+
+    /// Numero di task installati conformi
+    pub compliant: usize,
+
+    /// Numero di task installati in deriva
+    pub drifted: usize,
+
+    /// Numero di task installati non verificabili
+    pub unverifiable: usize,
+
+    /// Task la cui ultima operazione è fallita
+    pub failed_tasks: Vec<String>,
+
+    /// Task in quarantena
+    pub quarantined_tasks: Vec<String>,
+
+    /// Indica se la macchina deve essere riavviata
+    pub reboot_required: bool,
+}
+
+impl HostSummary {
+    /// Verifica se la macchina è conforme: nessuna deriva, nessun fallimento, rapporto recente
+    pub fn is_compliant(&self) -> bool {
+        self.drifted == 0 && self.failed_tasks.is_empty() && !self.stale
+    }
+
+    /// Restituisce l'esito da mostrare nella tabella
+    fn label(&self) -> &'static str {
+        if self.stale {
+            "non aggiornato"
+        } else if !self.failed_tasks.is_empty() {
+            "errori"
+        } else if self.drifted > 0 {
+            "deriva"
+        } else {
+            "conforme"
+        }
+    }
+}
+
+/// Riepilogo di conformità della flotta
+#[derive(Debug, Clone, Serialize)]
+pub struct FleetSummary {
+    /// Macchine della flotta, in ordine di nome
+    pub hosts: Vec<HostSummary>,
+}
+
+impl FleetSummary {
+    /// Riassume i rapporti delle macchine
+    ///
+    /// Per ogni macchina viene considerato solo il rapporto più recente.
+    ///
+    /// # Arguments
+    ///
+    /// * `reports` - I rapporti letti
+    /// * `stale_after` - Età oltre la quale un rapporto è considerato non aggiornato
+    pub fn new(reports: Vec<HostReport>, stale_after: chrono::Duration) -> Self {
+        let mut latest: BTreeMap<String, HostReport> = BTreeMap::new();
+        for report in reports {
+            let newer = latest.get(&report.host)
+                .is_none_or(|current| report.reported_at() > current.reported_at());
+            if newer {
+                latest.insert(report.host.clone(), report);
+            }
+        }
+
+        let now = Local::now();
+        let hosts = latest.into_values()
+            .map(|report| {
+                let tasks_where = |check: fn(&TaskReport) -> bool| report.tasks.iter()
+                    .filter(|(_, task)| check(task))
+                    .map(|(name, _)| name.clone())
+                    .collect::<Vec<_>>();
+                HostSummary {
+                    stale: report.reported_at().is_none_or(|date| now - date > stale_after),
+                    installed_stacks: report.stacks.values().filter(|status| *status == "installed").count(),
+                    compliant: report.compliant,
+                    drifted: report.drifted,
+                    unverifiable: report.unverifiable,
+                    failed_tasks: tasks_where(|task| task.failed),
+                    quarantined_tasks: tasks_where(|task| task.quarantined),
+                    reboot_required: report.reboot_required,
+                    host: report.host,
+                    role: report.role,
+                    reported_at: report.reported_at,
+                }
+            })
+            .collect();
+
+        FleetSummary { hosts }
+    }
+
+    /// Verifica se tutte le macchine sono conformi
+    pub fn is_compliant(&self) -> bool {
+        self.hosts.iter().all(HostSummary::is_compliant)
+    }
+
+    /// Formatta il riepilogo come tabella di testo
+    pub fn to_table(&self) -> String {
+        let width = self.hosts.iter()
+            .map(|h| h.host.chars().count())
+            .chain(std::iter::once("Host".len()))
+            .max()
+            .unwrap_or(0);
+        let role_width = self.hosts.iter()
+            .map(|h| h.role.as_deref().unwrap_or("-").chars().count())
+            .chain(std::iter::once("Ruolo".len()))
+            .max()
+            .unwrap_or(0);
+
+        let mut table = format!(
+            "{:<width$}  {:<role_width$}  {:<14}  {:>5}  {:>8}  {:>6}  {:>8}  {:>7}  {:<7}  Rapporto\n",
+            "Host", "Ruolo", "Esito", "Stack", "Conformi", "Deriva", "Non ver.", "Falliti", "Riavvio"
+        );
+        for host in &self.hosts {
+            let reported_at = DateTime::parse_from_rfc3339(&host.reported_at)
+                .map(|d| d.with_timezone(&Local).format("%d/%m/%Y %H:%M").to_string())
+                .unwrap_or_else(|_| host.reported_at.clone());
+            table.push_str(&format!(
+                "{:<width$}  {:<role_width$}  {:<14}  {:>5}  {:>8}  {:>6}  {:>8}  {:>7}  {:<7}  {}\n",
+                host.host,
+                host.role.as_deref().unwrap_or("-"),
+                host.label(),
+                host.installed_stacks,
+                host.compliant,
+                host.drifted,
+                host.unverifiable,
+                host.failed_tasks.len(),
+                if host.reboot_required { "sì" } else { "no" },
+                reported_at,
+            ));
+        }
+
+        let compliant = self.hosts.iter().filter(|h| h.is_compliant()).count();
+        table.push_str(&format!(
+            "\n{} host: {} conformi, {} in deriva, {} con errori, {} non aggiornati, {} da riavviare\n",
+            self.hosts.len(),
+            compliant,
+            self.hosts.iter().filter(|h| !h.stale && h.failed_tasks.is_empty() && h.drifted > 0).count(),
+            self.hosts.iter().filter(|h| !h.stale && !h.failed_tasks.is_empty()).count(),
+            self.hosts.iter().filter(|h| h.stale).count(),
+            self.hosts.iter().filter(|h| h.reboot_required).count(),
+        ));
+        table
+    }
+
+    /// Serializza il riepilogo in JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize fleet summary")
+    }
+}
+
+/// Raccoglie lo stato della macchina locale
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+pub fn collect(config: &Config) -> Result<HostReport> {
+    let tasks = load_tasks(config)?;
+    let stacks = load_stacks(config, &tasks)?;
+    let drift = drift::check_drift(config)?;
+
+    let compliance: BTreeMap<String, ComplianceStatus> = drift.tasks.iter()
+        .map(|task| (source::qualified_name(&task.source, &task.name), task.status))
+        .collect();
+
+    let task_reports = tasks.iter()
+        .map(|task| {
+            let name = task.qualified_name();
+            (name.clone(), TaskReport {
+                installed: task.installed,
+                version: task.version.clone(),
+                compliance: compliance.get(&name).copied(),
+                failed: task.has_failed(),
+                quarantined: task.is_quarantined(),
+            })
+        })
+        .collect();
+
+    let stack_statuses = stacks.iter()
+        .map(|stack| {
+            let status = if stack.fully_installed {
+                "installed"
+            } else if stack.partially_installed {
+                "partial"
+            } else {
+                "absent"
+            };
+            (stack.name.clone(), status.to_string())
+        })
+        .collect();
+
+    Ok(HostReport {
+        host: utils::get_hostname(),
+        reported_at: Local::now().to_rfc3339(),
+        galatea_version: env!("CARGO_PKG_VERSION").to_string(),
+        role: config.role.clone(),
+        stacks: stack_statuses,
+        tasks: task_reports,
+        compliant: drift.compliant,
+        drifted: drift.drifted,
+        unverifiable: drift.unverifiable,
+        reboot_required: !reboot::pending_reboot(config, &tasks).is_empty(),
+    })
+}
+
+/// Invia un rapporto all'endpoint configurato
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+/// * `phone_home` - La configurazione dell'endpoint
+/// * `report` - Il rapporto da inviare
+pub fn upload(config: &Config, phone_home: &PhoneHomeConfig, report: &HostReport) -> Result<()> {
+    let token = match &phone_home.token {
+        Some(token) => Some(secrets::reveal(config, token).context("Failed to decrypt phone home token")?),
+        None => None,
+    };
+    if let Some(token) = &token {
+        redact::register(token);
+    }
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(config.download_timeout))
+        .build()
+        .context("Failed to create HTTP client")?;
+
+    let request = client.post(&phone_home.url)
+        .header("Content-Type", "application/json")
+        .body(report.to_json()?);
+    let request = match &token {
+        Some(token) => request.bearer_auth(token),
+        None => request,
+    };
+
+    let response = request.send()
+        .context(format!("Failed to contact phone home endpoint {}", phone_home.url))?;
+    if !response.status().is_success() {
+        return Err(anyhow!("Phone home endpoint {} returned HTTP {}", phone_home.url, response.status()));
+    }
+
+    info!("Host report sent to {}", phone_home.url);
+    Ok(())
+}
+
+/// Raccoglie e invia lo stato della macchina, se l'invio è configurato
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+///
+/// # Returns
+///
+/// `true` se il rapporto è stato inviato
+pub fn phone_home(config: &Config) -> Result<bool> {
+    let Some(phone_home) = &config.phone_home else {
+        return Ok(false);
+    };
+
+    let report = collect(config)?;
+    upload(config, phone_home, &report)?;
+    Ok(true)
+}
+
+/// Legge i rapporti delle macchine da una directory, da un file o da un URL
+///
+/// Una directory viene letta per intero (file `.json`); un file o la risposta di un URL
+/// possono contenere un singolo rapporto o un elenco di rapporti.
+///
+/// # Arguments
+///
+/// * `from` - Directory, file o URL `http(s)://` da cui leggere i rapporti
+/// * `timeout_secs` - Timeout della richiesta HTTP
+pub fn load_reports(from: &str, timeout_secs: u64) -> Result<Vec<HostReport>> {
+    if from.starts_with("http://") || from.starts_with("https://") {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(timeout_secs))
+            .build()
+            .context("Failed to create HTTP client")?;
+        let response = client.get(from).send()
+            .context(format!("Failed to fetch host reports from {}", from))?;
+        if !response.status().is_success() {
+            return Err(anyhow!("Fleet endpoint {} returned HTTP {}", from, response.status()));
+        }
+        let text = response.text()
+            .context(format!("Failed to read host reports from {}", from))?;
+        return parse_reports(&text).context(format!("Invalid host reports from {}", from));
+    }
+
+    let path = Path::new(from);
+    if !path.is_dir() {
+        let content = fs::read_to_string(path)
+            .context(format!("Failed to read host reports from {}", from))?;
+        return parse_reports(&content).context(format!("Invalid host reports in {}", from));
+    }
+
+    let mut entries: Vec<_> = fs::read_dir(path)
+        .context(format!("Failed to read directory {}", from))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    entries.sort();
+
+    let mut reports = Vec::new();
+    for entry in entries {
+        let content = fs::read_to_string(&entry)
+            .context(format!("Failed to read host report {}", entry.display()))?;
+        reports.extend(parse_reports(&content)
+            .context(format!("Invalid host report {}", entry.display()))?);
+    }
+    Ok(reports)
+}
+
+/// Interpreta un documento con un rapporto o un elenco di rapporti
+fn parse_reports(content: &str) -> Result<Vec<HostReport>> {
+    let value: serde_json::Value = serde_json::from_str(content)?;
+    if value.is_array() {
+        Ok(serde_json::from_value(value)?)
+    } else {
+        Ok(vec![serde_json::from_value(value)?])
+    }
+}
@@ -0,0 +1,191 @@
+//! Hook di integrazione
+//!
+//! Dopo ogni operazione su un task o uno stack Galatea esegue i programmi presenti in
+//! `<hooks_dir>/post-<operazione>.d/` (es. `/etc/galatea/hooks/post-install.d/`), in ordine
+//! alfabetico, passando sullo standard input un documento JSON con i metadati
+//! dell'esecuzione ([`HookPayload`]). Così le personalizzazioni locali (inventari, ticket,
+//! metriche, ...) non richiedono di modificare Galatea.
+//!
+//! Vengono eseguiti solo i file eseguibili; i file nascosti e le copie lasciate dai gestori
+//! di pacchetti o dagli editor (`.dpkg-old`, `.rpmnew`, `~`, ...) sono ignorati. Un hook che
+//! fallisce o supera `hook_timeout` viene riportato nel log senza cambiare l'esito
+//! dell'operazione.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use log::{debug, warn};
+use serde::Serialize;
+
+use crate::changes::ChangeSummary;
+use crate::config::Config;
+use crate::error;
+use crate::events::{self, Operation};
+use crate::executor::{self, ScriptResult};
+use crate::redact;
+use crate::utils;
+
+/// Suffissi dei file ignorati nelle directory degli hook
+const IGNORED_SUFFIXES: &[&str] = &["~", ".bak", ".disabled", ".dpkg-old", ".dpkg-new", ".dpkg-dist", ".rpmnew", ".rpmsave"];
+
+/// Metadati di un'operazione passati agli hook
+#[derive(Debug, Clone, Serialize)]
+pub struct HookPayload {
+    /// Nome dell'hook, es. `post-install`
+    pub hook: String,
+
+    /// Tipo di elemento: `task` o `stack`
+    pub kind: &'static str,
+
+    /// Nome del task o dello stack
+    pub target: String,
+
+    /// Operazione eseguita (install, reinstall, uninstall, reset, remediate, migrate)
+    pub operation: &'static str,
+
+    /// Esecuzione di cui fa parte l'operazione, condivisa dai task di uno stesso stack
+    pub run_id: Option<String>,
+
+    /// Indica se l'operazione è riuscita
+    pub success: bool,
+
+    /// Motivo del fallimento, mascherato
+    pub error: Option<String>,
+
+    /// Tipo del fallimento (vedi [`error::kind_of`])
+    pub error_kind: Option<&'static str>,
+
+    /// Inizio dell'operazione (RFC 3339)
+    pub started_at: String,
+
+    /// Fine dell'operazione (RFC 3339)
+    pub finished_at: String,
+
+    /// Durata dell'operazione in secondi
+    pub duration_secs: f64,
+
+    /// Nome host della macchina
+    pub host: String,
+
+    /// Esito riportato dallo script del task, se presente
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<ScriptResult>,
+
+    /// Modifiche al sistema rilevate durante l'installazione del task
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changes: Option<ChangeSummary>,
+}
+
+impl HookPayload {
+    /// Descrive un'operazione terminata
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - Il tipo di elemento (`task` o `stack`)
+    /// * `operation` - L'operazione eseguita
+    /// * `target` - Il nome del task o dello stack
+    /// * `run_id` - L'esecuzione di cui fa parte l'operazione
+    /// * `started` - L'inizio dell'operazione
+    /// * `error` - L'errore dell'operazione, se è fallita
+    pub fn new(kind: &'static str, operation: Operation, target: &str, run_id: Option<String>,
+               started: DateTime<Local>, error: Option<&anyhow::Error>) -> Self {
+        let finished = Local::now();
+        HookPayload {
+            hook: hook_name(operation),
+            kind,
+            target: target.to_string(),
+            operation: operation.to_str(),
+            run_id,
+            success: error.is_none(),
+            error: error.map(|e| redact::mask(&format!("{:#}", e)).into_owned()),
+            error_kind: error.map(error::kind_of),
+            started_at: started.to_rfc3339(),
+            finished_at: finished.to_rfc3339(),
+            duration_secs: (finished - started).num_milliseconds() as f64 / 1000.0,
+            host: utils::get_hostname(),
+            result: None,
+            changes: None,
+        }
+    }
+}
+
+/// Restituisce il nome dell'hook eseguito dopo un'operazione, es. `post-install`
+pub fn hook_name(operation: Operation) -> String {
+    format!("post-{}", operation.to_str())
+}
+
+/// Esegue un'operazione su uno stack pubblicandone gli eventi, poi ne esegue gli hook
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+/// * `operation` - Il tipo di operazione
+/// * `target` - Il nome dello stack
+/// * `body` - Il corpo dell'operazione
+///
+/// # Returns
+///
+/// Il risultato del corpo dell'operazione
+pub fn track<T, F>(config: &Config, operation: Operation, target: &str, body: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T>,
+{
+    let started = Local::now();
+    let mut run_id = None;
+    let result = events::track(operation, target, || {
+        run_id = events::current_run_id();
+        body()
+    });
+
+    run(config, &HookPayload::new("stack", operation, target, run_id, started, result.as_ref().err()));
+    result
+}
+
+/// Esegue gli hook di un'operazione terminata
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+/// * `payload` - I metadati dell'operazione, passati a ogni hook in JSON
+pub fn run(config: &Config, payload: &HookPayload) {
+    let dir = config.hooks_dir().join(format!("{}.d", payload.hook));
+    let hooks = list_hooks(&dir);
+    if hooks.is_empty() {
+        return;
+    }
+
+    let input = match serde_json::to_string(payload) {
+        Ok(input) => input,
+        Err(e) => {
+            warn!("Failed to serialize {} hook input for {}: {}", payload.hook, payload.target, e);
+            return;
+        },
+    };
+
+    for hook in hooks {
+        debug!("Running {} hook {} for {}", payload.hook, hook.display(), payload.target);
+        if let Err(e) = executor::run_with_input(&hook, &input, Some(config.hook_timeout), &config.child_env) {
+            warn!("Hook {} failed for {}: {:#}", hook.display(), payload.target, e);
+        }
+    }
+}
+
+/// Restituisce gli hook eseguibili di una directory, in ordine alfabetico
+pub fn list_hooks(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut hooks: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+            !name.starts_with('.') && !IGNORED_SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+        })
+        .filter(|path| utils::is_executable(path))
+        .collect();
+    hooks.sort();
+    hooks
+}
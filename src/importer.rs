@@ -0,0 +1,248 @@
+//! Importazione dello stato da sistemi già configurati
+//!
+//! Questo modulo permette di adottare Galatea su server configurati in precedenza
+//! (manualmente o con altri strumenti): i task vengono segnati come installati in
+//! base alle evidenze presenti sul sistema (pacchetti, servizi, file, comandi) o a
+//! un elenco di task esportato da un altro host.
+
+use std::path::Path;
+use std::fs;
+use std::process::{Command, Stdio};
+use anyhow::{Context, Result};
+use log::{info, warn};
+
+use crate::config::Config;
+use crate::executor;
+use crate::task::{Detection, Task};
+
+/// Esito dell'importazione per un singolo task
+#[derive(Debug, Clone)]
+pub struct ImportedTask {
+    /// Nome del task
+    pub name: String,
+
+    /// Evidenze che hanno portato a considerare il task installato
+    pub evidence: Vec<String>,
+}
+
+/// Risultato dell'importazione dello stato
+#[derive(Debug, Clone, Default)]
+pub struct ImportReport {
+    /// Task riconosciuti come installati
+    pub imported: Vec<ImportedTask>,
+
+    /// Task già registrati come installati
+    pub already_installed: Vec<String>,
+
+    /// Task privi di regole di rilevamento e non presenti nell'elenco
+    pub skipped: Vec<String>,
+
+    /// Nomi presenti nell'elenco ma non nel catalogo
+    pub unknown: Vec<String>,
+}
+
+/// Gestori di pacchetti supportati per il rilevamento
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PackageManager {
+    Dpkg,
+    Rpm,
+    Pacman,
+}
+
+/// Rileva i task già applicati sul sistema e, se richiesto, ne registra lo stato
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+/// * `tasks` - I task del catalogo
+/// * `list_file` - Elenco opzionale di task (uno per riga) da considerare installati
+/// * `dry_run` - Se `true` non scrive alcun file di stato
+///
+/// # Returns
+///
+/// Il report dei task riconosciuti
+pub fn import_state(config: &Config, tasks: &mut [Task], list_file: Option<&Path>, dry_run: bool) -> Result<ImportReport> {
+    info!("Importing state from existing system (dry run: {})", dry_run);
+
+    let mut report = ImportReport::default();
+
+    let listed = match list_file {
+        Some(path) => read_task_list(path)?,
+        None => Vec::new(),
+    };
+
+    for name in &listed {
+        if !tasks.iter().any(|t| t.matches_ref(name)) {
+            warn!("Task {} listed for import is not in the catalog", name);
+            report.unknown.push(name.clone());
+        }
+    }
+
+    let package_manager = detect_package_manager();
+
+    for task in tasks.iter_mut() {
+        if task.check_installed(config)? {
+            report.already_installed.push(task.name.clone());
+            continue;
+        }
+
+        let evidence = if listed.iter().any(|reference| task.matches_ref(reference)) {
+            Some(vec![format!("elencato in {}", list_file.map(|p| p.display().to_string()).unwrap_or_default())])
+        } else {
+            match &task.detect {
+                Some(detect) if !detect.is_empty() => check_detection(detect, package_manager),
+                _ => {
+                    report.skipped.push(task.name.clone());
+                    continue;
+                }
+            }
+        };
+
+        if let Some(evidence) = evidence {
+            if !dry_run {
+                task.mark_installed(config)?;
+            }
+            info!("Task {} detected as installed: {}", task.name, evidence.join(", "));
+            report.imported.push(ImportedTask { name: task.name.clone(), evidence });
+        }
+    }
+
+    info!("State import completed: {} tasks detected", report.imported.len());
+    Ok(report)
+}
+
+/// Legge un elenco di nomi di task, ignorando righe vuote e commenti
+fn read_task_list(path: &Path) -> Result<Vec<String>> {
+    let content = fs::read_to_string(path)
+        .context(format!("Failed to read task list: {:?}", path))?;
+
+    Ok(content.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_string())
+        .collect())
+}
+
+/// Verifica le evidenze di un task
+///
+/// # Returns
+///
+/// Le evidenze trovate se sono tutte soddisfatte, altrimenti `None`
+fn check_detection(detect: &Detection, package_manager: Option<PackageManager>) -> Option<Vec<String>> {
+    let (evidence, missing) = evaluate_detection(detect, package_manager, true);
+    if missing.is_empty() {
+        Some(evidence)
+    } else {
+        None
+    }
+}
+
+/// Verifica tutte le evidenze di un task, ad esempio per un controllo di conformità
+///
+/// # Arguments
+///
+/// * `detect` - Le evidenze da verificare
+///
+/// # Returns
+///
+/// Le evidenze soddisfatte e quelle mancanti
+pub fn verify_detection(detect: &Detection) -> (Vec<String>, Vec<String>) {
+    evaluate_detection(detect, detect_package_manager(), false)
+}
+
+/// Valuta le evidenze di un task
+///
+/// # Arguments
+///
+/// * `detect` - Le evidenze da verificare
+/// * `package_manager` - Il gestore di pacchetti del sistema
+/// * `stop_at_first_missing` - Se `true` interrompe la verifica alla prima evidenza mancante
+///
+/// # Returns
+///
+/// Le evidenze soddisfatte e quelle mancanti
+fn evaluate_detection(
+    detect: &Detection,
+    package_manager: Option<PackageManager>,
+    stop_at_first_missing: bool,
+) -> (Vec<String>, Vec<String>) {
+    let mut checks: Vec<(String, Box<dyn Fn() -> bool + '_>)> = Vec::new();
+
+    for package in &detect.packages {
+        checks.push((format!("pacchetto {}", package), Box::new(move || {
+            package_manager.is_some_and(|manager| is_package_installed(manager, package))
+        })));
+    }
+
+    for service in &detect.services {
+        checks.push((format!("servizio {}", service), Box::new(move || is_service_present(service))));
+    }
+
+    for file in &detect.files {
+        checks.push((format!("file {}", file), Box::new(move || Path::new(file).exists())));
+    }
+
+    if let Some(command) = &detect.command {
+        checks.push((format!("comando '{}'", command), Box::new(move || run_quiet("sh", &["-c", command]))));
+    }
+
+    let mut evidence = Vec::new();
+    let mut missing = Vec::new();
+
+    for (description, check) in checks {
+        if check() {
+            evidence.push(description);
+        } else {
+            missing.push(description);
+            if stop_at_first_missing {
+                break;
+            }
+        }
+    }
+
+    (evidence, missing)
+}
+
+/// Rileva il gestore di pacchetti del sistema
+fn detect_package_manager() -> Option<PackageManager> {
+    if executor::is_command_available("dpkg-query") {
+        Some(PackageManager::Dpkg)
+    } else if executor::is_command_available("rpm") {
+        Some(PackageManager::Rpm)
+    } else if executor::is_command_available("pacman") {
+        Some(PackageManager::Pacman)
+    } else {
+        None
+    }
+}
+
+/// Verifica se un pacchetto è installato
+fn is_package_installed(manager: PackageManager, package: &str) -> bool {
+    match manager {
+        PackageManager::Dpkg => Command::new("dpkg-query")
+            .args(["-W", "-f=${Status}", package])
+            .stderr(Stdio::null())
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).contains("install ok installed"))
+            .unwrap_or(false),
+        PackageManager::Rpm => run_quiet("rpm", &["-q", package]),
+        PackageManager::Pacman => run_quiet("pacman", &["-Q", package]),
+    }
+}
+
+/// Verifica se un servizio systemd è attivo o abilitato
+fn is_service_present(service: &str) -> bool {
+    run_quiet("systemctl", &["is-active", "--quiet", service])
+        || run_quiet("systemctl", &["is-enabled", "--quiet", service])
+}
+
+/// Esegue un comando senza output e restituisce se è terminato con successo
+fn run_quiet(program: &str, args: &[&str]) -> bool {
+    Command::new(program)
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
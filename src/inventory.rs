@@ -0,0 +1,187 @@
+//! Esposizione dello stato di Galatea ad Ansible
+//!
+//! Questo modulo presenta i task e gli stack di Galatea in due forme leggibili da Ansible:
+//!
+//! - un inventario dinamico (`--list` / `--host`) con la macchina locale raggruppata per
+//!   ruolo e per stack installati, e lo stato dei task come variabili dell'host;
+//! - un fatto locale (`/etc/ansible/facts.d/galatea.fact`) che rende lo stato disponibile
+//!   ai nodi di controllo come `ansible_local.galatea` a ogni raccolta dei fatti.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use log::info;
+use serde::Serialize;
+use serde_json::{Value, json};
+
+use crate::config::Config;
+use crate::stack::load_stacks;
+use crate::task::load_tasks;
+use crate::utils;
+
+/// Directory predefinita dei fatti locali di Ansible
+pub const DEFAULT_FACTS_DIR: &str = "/etc/ansible/facts.d";
+
+/// Nome del file del fatto locale (diventa `ansible_local.galatea`)
+const FACT_FILE_NAME: &str = "galatea.fact";
+
+/// Stato di un task esposto ad Ansible
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskState {
+    /// Catalogo di provenienza
+    pub source: String,
+
+    /// Indica se il task è installato
+    pub installed: bool,
+
+    /// Versione del bundle predefinito, se dichiarata
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+
+    /// Indica se il task è deprecato
+    pub deprecated: bool,
+}
+
+/// Variabili dell'host con lo stato di Galatea
+#[derive(Debug, Clone, Serialize)]
+pub struct HostVars {
+    /// Ruolo assegnato alla macchina
+    pub galatea_role: Option<String>,
+
+    /// Stato di tutti i task del catalogo
+    pub galatea_tasks: BTreeMap<String, TaskState>,
+
+    /// Stato degli stack: installed, partial o absent
+    pub galatea_stacks: BTreeMap<String, String>,
+
+    /// Nomi dei task installati
+    pub galatea_installed_tasks: Vec<String>,
+}
+
+/// Raccoglie lo stato di task e stack della macchina locale
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+pub fn host_vars(config: &Config) -> Result<HostVars> {
+    let tasks = load_tasks(config)?;
+    let stacks = load_stacks(config, &tasks)?;
+
+    let galatea_tasks = tasks.iter()
+        .map(|task| (task.name.clone(), TaskState {
+            source: task.source.clone(),
+            installed: task.installed,
+            version: task.version.clone(),
+            deprecated: task.deprecated,
+        }))
+        .collect();
+
+    let galatea_stacks = stacks.iter()
+        .map(|stack| {
+            let status = if stack.fully_installed {
+                "installed"
+            } else if stack.partially_installed {
+                "partial"
+            } else {
+                "absent"
+            };
+            (stack.name.clone(), status.to_string())
+        })
+        .collect();
+
+    let galatea_installed_tasks = tasks.iter()
+        .filter(|task| task.installed)
+        .map(|task| task.name.clone())
+        .collect();
+
+    Ok(HostVars {
+        galatea_role: config.role.clone(),
+        galatea_tasks,
+        galatea_stacks,
+        galatea_installed_tasks,
+    })
+}
+
+/// Genera l'inventario dinamico completo (`--list`)
+///
+/// La macchina locale appartiene al gruppo `galatea_role_<ruolo>` e a un gruppo
+/// `galatea_stack_<stack>` per ogni stack completamente installato.
+pub fn inventory_list(config: &Config) -> Result<Value> {
+    let host = utils::get_hostname();
+    let vars = host_vars(config)?;
+
+    let mut groups: Vec<String> = Vec::new();
+    if let Some(role) = &vars.galatea_role {
+        groups.push(format!("galatea_role_{}", group_name(role)));
+    }
+    for (stack, status) in &vars.galatea_stacks {
+        if status == "installed" {
+            groups.push(format!("galatea_stack_{}", group_name(stack)));
+        }
+    }
+
+    let mut inventory = serde_json::Map::new();
+    inventory.insert("_meta".to_string(), json!({ "hostvars": { host.clone(): vars } }));
+    inventory.insert("all".to_string(), json!({ "hosts": [host.clone()], "children": groups.clone() }));
+    for group in groups {
+        inventory.insert(group, json!({ "hosts": [host.clone()] }));
+    }
+
+    Ok(Value::Object(inventory))
+}
+
+/// Restituisce le variabili di un host (`--host`); vuote per host diversi da quello locale
+pub fn inventory_host(config: &Config, host: &str) -> Result<Value> {
+    if host != utils::get_hostname() {
+        return Ok(json!({}));
+    }
+
+    serde_json::to_value(host_vars(config)?)
+        .context("Failed to serialize host variables")
+}
+
+/// Installa il fatto locale di Ansible che esegue `galatea inventory --facts`
+///
+/// # Arguments
+///
+/// * `facts_dir` - La directory dei fatti locali
+/// * `config_path` - Il file di configurazione da usare
+///
+/// # Returns
+///
+/// Il percorso del file installato
+pub fn install_fact(facts_dir: &Path, config_path: Option<&Path>) -> Result<PathBuf> {
+    let executable = std::env::current_exe()
+        .context("Failed to determine the executable path")?;
+
+    fs::create_dir_all(facts_dir)
+        .context(format!("Failed to create facts directory: {:?}", facts_dir))?;
+
+    let mut command = format!("exec {} --quiet", executable.display());
+    if let Some(config_path) = config_path {
+        command.push_str(&format!(" --config {}", config_path.display()));
+    }
+    command.push_str(" inventory --facts");
+
+    let fact_path = facts_dir.join(FACT_FILE_NAME);
+    fs::write(&fact_path, format!("#!/bin/sh\n# Generato da galatea inventory --install-fact\n{}\n", command))
+        .context(format!("Failed to write Ansible fact: {:?}", fact_path))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&fact_path, fs::Permissions::from_mode(0o755))
+            .context(format!("Failed to set permissions on {:?}", fact_path))?;
+    }
+
+    info!("Ansible local fact installed in {:?}", fact_path);
+    Ok(fact_path)
+}
+
+/// Converte un nome in un nome di gruppo valido per Ansible
+fn group_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .collect()
+}
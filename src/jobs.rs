@@ -0,0 +1,403 @@
+//! Registro dei job
+//!
+//! Ogni operazione eseguita dall'interfaccia è un job: quelle inviate con [`submit`]
+//! vengono accodate ed eseguite in background una alla volta, quelle avviate direttamente
+//! (es. dalla CLI o da un'installazione singola) vengono registrate quando pubblicano il
+//! proprio inizio sul canale degli eventi. Per ogni job il registro conserva stato,
+//! avanzamento e output, così che la schermata dei job possa mostrarli e seguirli dal vivo.
+//! Un job può essere annullato con [`cancel`]: se è in coda non viene avviato, se è in
+//! corso lo script o il playbook in esecuzione viene interrotto.
+
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use lazy_static::lazy_static;
+
+use crate::events::{self, Event, OperationId};
+
+/// Identificativo di un job
+pub type JobId = u64;
+
+/// Numero massimo di righe di output conservate per job
+const MAX_OUTPUT_LINES: usize = 500;
+
+/// Numero massimo di job terminati conservati nel registro
+const MAX_FINISHED_JOBS: usize = 50;
+
+/// Corpo di un job accodato
+type JobBody = Box<dyn FnOnce() -> Result<()> + Send>;
+
+lazy_static! {
+    static ref JOBS: Mutex<Vec<Job>> = Mutex::new(Vec::new());
+    static ref QUEUE: Mutex<Option<Sender<(JobId, JobBody)>>> = Mutex::new(None);
+    static ref NEXT_JOB: AtomicU64 = AtomicU64::new(1);
+}
+
+// Job in esecuzione nel thread, con l'operazione che lo ha creato se è stato creato da un
+// evento: il job va chiuso al termine di quell'operazione
+thread_local! {
+    static CURRENT_JOB: RefCell<Option<(JobId, Option<OperationId>)>> = const { RefCell::new(None) };
+}
+
+/// Stato di un job
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobState {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl JobState {
+    /// Restituisce il nome dello stato da mostrare all'utente
+    pub fn label(&self) -> &'static str {
+        match self {
+            JobState::Queued => "in coda",
+            JobState::Running => "in corso",
+            JobState::Succeeded => "completato",
+            JobState::Failed => "fallito",
+        }
+    }
+
+    /// Indica se il job è terminato
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobState::Succeeded | JobState::Failed)
+    }
+}
+
+/// Un job registrato
+#[derive(Debug, Clone)]
+pub struct Job {
+    /// Identificativo del job
+    pub id: JobId,
+
+    /// Descrizione del job, es. "Installazione di 3 elementi"
+    pub label: String,
+
+    /// Stato del job
+    pub state: JobState,
+
+    /// Percentuale completata dell'attività in corso, se nota
+    pub percent: Option<f64>,
+
+    /// Ultimo dettaglio di avanzamento ricevuto
+    pub detail: String,
+
+    /// Data di accodamento
+    pub queued_at: DateTime<Local>,
+
+    /// Data di avvio
+    pub started_at: Option<DateTime<Local>>,
+
+    /// Data di termine
+    pub finished_at: Option<DateTime<Local>>,
+
+    /// Motivo del fallimento
+    pub error: Option<String>,
+
+    /// Output del job: messaggi di log e avanzamento delle attività
+    pub output: Vec<String>,
+
+    /// Richiesta di annullamento, condivisa con i processi avviati dal job
+    cancel: Arc<AtomicBool>,
+}
+
+impl Job {
+    fn new(label: String, state: JobState) -> Self {
+        let now = Local::now();
+        Job {
+            id: NEXT_JOB.fetch_add(1, Ordering::SeqCst),
+            label,
+            state,
+            percent: None,
+            detail: String::new(),
+            queued_at: now,
+            started_at: (state == JobState::Running).then_some(now),
+            finished_at: None,
+            error: None,
+            output: Vec::new(),
+            cancel: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Indica se è stato chiesto l'annullamento del job
+    pub fn cancel_requested(&self) -> bool {
+        self.cancel.load(Ordering::SeqCst)
+    }
+
+    /// Restituisce la durata del job, fino a ora se è ancora in corso
+    pub fn elapsed(&self) -> Option<chrono::Duration> {
+        let started = self.started_at?;
+        Some(self.finished_at.unwrap_or_else(Local::now) - started)
+    }
+
+    fn push_output(&mut self, line: String) {
+        self.output.push(line);
+        if self.output.len() > MAX_OUTPUT_LINES {
+            let excess = self.output.len() - MAX_OUTPUT_LINES;
+            self.output.drain(..excess);
+        }
+    }
+}
+
+/// Iscrive il registro al canale degli eventi
+///
+/// Da chiamare una volta all'avvio dell'interfaccia.
+pub fn init() {
+    events::subscribe(record_event);
+}
+
+/// Accoda un job da eseguire in background
+///
+/// I job accodati vengono eseguiti uno alla volta, nell'ordine di invio, da un thread
+/// dedicato: due operazioni sulla stessa macchina non devono sovrapporsi.
+///
+/// # Arguments
+///
+/// * `label` - La descrizione del job
+/// * `body` - Il corpo del job
+///
+/// # Returns
+///
+/// L'identificativo del job
+pub fn submit<F>(label: impl Into<String>, body: F) -> JobId
+where
+    F: FnOnce() -> Result<()> + Send + 'static,
+{
+    let job = Job::new(label.into(), JobState::Queued);
+    let id = job.id;
+    insert(job);
+
+    let mut queue = match QUEUE.lock() {
+        Ok(queue) => queue,
+        Err(_) => {
+            finish(id, Some("Coda dei job non disponibile".to_string()));
+            return id;
+        },
+    };
+    let sender = queue.get_or_insert_with(start_worker);
+    if sender.send((id, Box::new(body))).is_err() {
+        finish(id, Some("Coda dei job non disponibile".to_string()));
+    }
+    id
+}
+
+/// Restituisce una copia dei job registrati, dal più recente
+pub fn list() -> Vec<Job> {
+    JOBS.lock()
+        .map(|jobs| jobs.iter().rev().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Restituisce una copia di un job
+pub fn get(id: JobId) -> Option<Job> {
+    JOBS.lock().ok()?.iter().find(|job| job.id == id).cloned()
+}
+
+/// Restituisce il numero di job in corso e in coda
+pub fn counts() -> (usize, usize) {
+    JOBS.lock()
+        .map(|jobs| (
+            jobs.iter().filter(|job| job.state == JobState::Running).count(),
+            jobs.iter().filter(|job| job.state == JobState::Queued).count(),
+        ))
+        .unwrap_or((0, 0))
+}
+
+/// Chiede l'annullamento di un job
+///
+/// Un job in coda viene scartato senza essere avviato; per un job in corso lo script o
+/// il playbook in esecuzione viene terminato e i tentativi successivi vengono saltati.
+///
+/// # Arguments
+///
+/// * `id` - L'identificativo del job
+///
+/// # Returns
+///
+/// `true` se il job era in coda o in corso, `false` se era già terminato o non esiste
+pub fn cancel(id: JobId) -> bool {
+    let mut requested = false;
+    update(id, |job| {
+        if !job.state.is_finished() && !job.cancel_requested() {
+            job.cancel.store(true, Ordering::SeqCst);
+            job.push_output("Annullamento richiesto".to_string());
+            requested = true;
+        }
+    });
+    requested
+}
+
+/// Restituisce la richiesta di annullamento del job in esecuzione nel thread corrente
+///
+/// Il flag può essere passato a un altro thread che sorveglia un processo del job.
+pub fn cancel_flag() -> Option<Arc<AtomicBool>> {
+    let (id, _) = CURRENT_JOB.with(|current| *current.borrow())?;
+    JOBS.lock().ok()?.iter().find(|job| job.id == id).map(|job| Arc::clone(&job.cancel))
+}
+
+/// Indica se è stato chiesto l'annullamento del job in esecuzione nel thread corrente
+pub fn cancel_requested() -> bool {
+    cancel_flag().is_some_and(|flag| flag.load(Ordering::SeqCst))
+}
+
+/// Restituisce il job in esecuzione nel thread corrente, se presente
+pub fn current() -> Option<JobId> {
+    CURRENT_JOB.with(|current| current.borrow().map(|(id, _)| id))
+}
+
+/// Esegue `body` come parte del job indicato, es. in un thread che lavora per il job di un altro
+///
+/// Output, avanzamento e richiesta di annullamento di `body` sono quelli del job, che
+/// resta aperto: viene chiuso dal thread che lo ha avviato.
+///
+/// # Arguments
+///
+/// * `id` - Il job ottenuto con [`current`] nel thread di partenza
+/// * `body` - Il lavoro da eseguire
+pub fn join<T>(id: Option<JobId>, body: impl FnOnce() -> T) -> T {
+    let Some(id) = id else {
+        return body();
+    };
+    CURRENT_JOB.with(|current| *current.borrow_mut() = Some((id, None)));
+    let result = body();
+    CURRENT_JOB.with(|current| *current.borrow_mut() = None);
+    result
+}
+
+/// Avvia il thread che esegue i job accodati
+fn start_worker() -> Sender<(JobId, JobBody)> {
+    let (sender, receiver) = mpsc::channel::<(JobId, JobBody)>();
+    thread::spawn(move || {
+        for (id, body) in receiver {
+            if get(id).is_some_and(|job| job.cancel_requested()) {
+                finish(id, Some("Job annullato prima dell'avvio".to_string()));
+                continue;
+            }
+
+            update(id, |job| {
+                job.state = JobState::Running;
+                job.started_at = Some(Local::now());
+            });
+            CURRENT_JOB.with(|current| *current.borrow_mut() = Some((id, None)));
+
+            // Un panic nel corpo del job non deve fermare la coda
+            let error = match panic::catch_unwind(AssertUnwindSafe(body)) {
+                Ok(Ok(())) => None,
+                Ok(Err(e)) => Some(format!("{:#}", e)),
+                Err(_) => Some("Il job è terminato in modo anomalo".to_string()),
+            };
+
+            CURRENT_JOB.with(|current| *current.borrow_mut() = None);
+            finish(id, error);
+        }
+    });
+    sender
+}
+
+/// Aggiorna il registro con un evento del core
+fn record_event(event: &Event) {
+    match event {
+        Event::OperationStarted { id: operation_id, operation, target } => {
+            let line = format!("Avvio {} di {}", operation.label(), target);
+            let current = CURRENT_JOB.with(|current| *current.borrow());
+            match current {
+                Some((id, _)) => update(id, |job| job.push_output(line)),
+                None => {
+                    // Operazione avviata fuori dalla coda: diventa un job a sé
+                    let mut job = Job::new(format!("{} di {}", capitalize(operation.label()), target), JobState::Running);
+                    job.push_output(line);
+                    CURRENT_JOB.with(|current| *current.borrow_mut() = Some((job.id, Some(*operation_id))));
+                    insert(job);
+                },
+            }
+        },
+        Event::Progress(progress) => {
+            if let Some((id, _)) = CURRENT_JOB.with(|current| *current.borrow()) {
+                update(id, |job| {
+                    job.percent = progress.percent;
+                    if job.detail != progress.detail && !progress.detail.is_empty() {
+                        job.push_output(format!("{}: {}", progress.label, progress.detail));
+                    }
+                    job.detail = progress.detail.clone();
+                });
+            }
+        },
+        Event::LogLine { level, message } => {
+            if let Some((id, _)) = CURRENT_JOB.with(|current| *current.borrow()) {
+                update(id, |job| job.push_output(format!("[{}] {}", level, message)));
+            }
+        },
+        Event::Finished { id: operation_id, operation, target, error, error_kind } => {
+            let current = CURRENT_JOB.with(|current| *current.borrow());
+            if let Some((id, opened_by)) = current {
+                let line = match error {
+                    Some(error) => format!("{} di {} fallita [{}]: {}", capitalize(operation.label()), target,
+                                           error_kind.unwrap_or("other"), error),
+                    None => format!("{} di {} completata", capitalize(operation.label()), target),
+                };
+                update(id, |job| job.push_output(line));
+
+                // Il termine dell'operazione che ha creato il job lo chiude
+                if opened_by == Some(*operation_id) {
+                    CURRENT_JOB.with(|current| *current.borrow_mut() = None);
+                    finish(id, error.clone());
+                }
+            }
+        },
+    }
+}
+
+/// Aggiunge un job al registro, scartando i job terminati più vecchi
+fn insert(job: Job) {
+    if let Ok(mut jobs) = JOBS.lock() {
+        jobs.push(job);
+        let finished = jobs.iter().filter(|job| job.state.is_finished()).count();
+        if finished > MAX_FINISHED_JOBS {
+            let mut excess = finished - MAX_FINISHED_JOBS;
+            jobs.retain(|job| {
+                if excess > 0 && job.state.is_finished() {
+                    excess -= 1;
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+    }
+}
+
+/// Modifica un job del registro
+fn update<F: FnOnce(&mut Job)>(id: JobId, change: F) {
+    if let Ok(mut jobs) = JOBS.lock() {
+        if let Some(job) = jobs.iter_mut().find(|job| job.id == id) {
+            change(job);
+        }
+    }
+}
+
+/// Segna un job come terminato
+fn finish(id: JobId, error: Option<String>) {
+    update(id, |job| {
+        job.state = if error.is_some() { JobState::Failed } else { JobState::Succeeded };
+        job.finished_at = Some(Local::now());
+        if let Some(error) = &error {
+            job.push_output(format!("Job fallito: {}", error));
+        }
+        job.error = error;
+    });
+}
+
+/// Rende maiuscola la prima lettera di un testo
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
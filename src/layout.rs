@@ -0,0 +1,308 @@
+//! Disposizione dei file di Galatea
+//!
+//! Questo modulo stabilisce dove si trovano configurazione, catalogo, stato e log.
+//! Eseguito come root Galatea usa le directory di sistema (`/etc/galatea`,
+//! `/var/lib/galatea`, `/var/log/galatea`); da utente normale segue la specifica XDG
+//! (`XDG_CONFIG_HOME`, `XDG_DATA_HOME`, `XDG_STATE_HOME`). La migrazione sposta
+//! configurazione e dati da una disposizione all'altra.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result, anyhow};
+use log::{info, warn};
+
+use crate::config::{Config, ConfigFormat, get_system_config_path};
+use crate::utils;
+
+/// Directory dei dati nella disposizione di sistema
+pub const SYSTEM_DATA_DIR: &str = "/var/lib/galatea";
+
+/// Directory dei log nella disposizione di sistema
+pub const SYSTEM_LOG_DIR: &str = "/var/log/galatea";
+
+/// Disposizione dei file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// Directory di sistema, per l'esecuzione come root
+    System,
+
+    /// Directory XDG dell'utente
+    User,
+}
+
+impl Layout {
+    /// Converte una stringa nella disposizione corrispondente
+    pub fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "system" => Ok(Layout::System),
+            "user" | "xdg" => Ok(Layout::User),
+            _ => Err(anyhow!("Invalid layout: {}", s)),
+        }
+    }
+
+    /// Restituisce il nome della disposizione
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Layout::System => "system",
+            Layout::User => "user",
+        }
+    }
+
+    /// Restituisce la disposizione adatta all'utente corrente
+    pub fn current() -> Self {
+        if utils::is_running_as_root() {
+            Layout::System
+        } else {
+            Layout::User
+        }
+    }
+
+    /// Restituisce il percorso del file di configurazione
+    pub fn config_path(&self) -> PathBuf {
+        match self {
+            Layout::System => get_system_config_path(),
+            Layout::User => xdg_dir("XDG_CONFIG_HOME", ".config").join("galatea").join("galatea.yaml"),
+        }
+    }
+
+    /// Restituisce le directory di task, stack e stato
+    pub fn data_dirs(&self) -> DataDirs {
+        match self {
+            Layout::System => {
+                let base = Path::new(SYSTEM_DATA_DIR);
+                DataDirs {
+                    tasks_dir: base.join("tasks"),
+                    stacks_dir: base.join("stacks"),
+                    state_dir: base.join("state"),
+                }
+            },
+            Layout::User => {
+                let data = xdg_dir("XDG_DATA_HOME", ".local/share").join("galatea");
+                DataDirs {
+                    tasks_dir: data.join("tasks"),
+                    stacks_dir: data.join("stacks"),
+                    state_dir: xdg_dir("XDG_STATE_HOME", ".local/state").join("galatea").join("state"),
+                }
+            },
+        }
+    }
+
+    /// Restituisce la directory dei log
+    pub fn log_dir(&self) -> PathBuf {
+        match self {
+            Layout::System => PathBuf::from(SYSTEM_LOG_DIR),
+            Layout::User => xdg_dir("XDG_STATE_HOME", ".local/state").join("galatea").join("logs"),
+        }
+    }
+}
+
+/// Directory dei dati di una disposizione
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataDirs {
+    /// Directory dei task
+    pub tasks_dir: PathBuf,
+
+    /// Directory degli stack
+    pub stacks_dir: PathBuf,
+
+    /// Directory dello stato
+    pub state_dir: PathBuf,
+}
+
+/// Spostamento previsto da una migrazione
+#[derive(Debug, Clone)]
+pub struct Move {
+    /// Percorso di partenza
+    pub from: PathBuf,
+
+    /// Percorso di destinazione
+    pub to: PathBuf,
+}
+
+/// Piano di migrazione verso un'altra disposizione
+#[derive(Debug, Clone)]
+pub struct MigrationPlan {
+    /// Disposizione di destinazione
+    pub target: Layout,
+
+    /// Directory dei dati da spostare
+    pub moves: Vec<Move>,
+
+    /// File di configurazione attuale, se presente
+    pub config_from: Option<PathBuf>,
+
+    /// File di configurazione nella nuova disposizione
+    pub config_to: PathBuf,
+}
+
+impl MigrationPlan {
+    /// Indica se la configurazione è già nella disposizione di destinazione
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty() && self.config_from.as_deref() == Some(self.config_to.as_path())
+    }
+}
+
+/// Calcola gli spostamenti necessari per passare a un'altra disposizione
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+/// * `target` - La disposizione di destinazione
+pub fn plan_migration(config: &Config, target: Layout) -> MigrationPlan {
+    let dirs = target.data_dirs();
+    let moves = [
+        (&config.tasks_dir, dirs.tasks_dir),
+        (&config.stacks_dir, dirs.stacks_dir),
+        (&config.state_dir, dirs.state_dir),
+    ].into_iter()
+        .map(|(from, to)| Move { from: PathBuf::from(from), to })
+        .filter(|m| m.from != m.to)
+        .collect();
+
+    // Il file migrato mantiene il formato di quello attuale
+    let mut config_to = target.config_path();
+    if config.config_file_path.as_deref().is_some_and(|from| ConfigFormat::from_path(from) == ConfigFormat::Toml) {
+        config_to.set_extension("toml");
+    }
+
+    MigrationPlan {
+        target,
+        moves,
+        config_from: config.config_file_path.clone(),
+        config_to,
+    }
+}
+
+/// Esegue la migrazione: sposta i dati e scrive la configurazione nella nuova posizione
+///
+/// Le directory di destinazione devono essere assenti o vuote: i dati non vengono mai
+/// uniti a quelli di un'altra installazione. Il vecchio file di configurazione viene
+/// rimosso solo dopo aver scritto il nuovo. I log non vengono spostati: restano nella
+/// directory attuale e i successivi vengono scritti in quella della nuova disposizione.
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+/// * `plan` - Il piano calcolato con `plan_migration`
+pub fn migrate(config: &Config, plan: &MigrationPlan) -> Result<()> {
+    // Controlla tutte le destinazioni prima di spostare qualcosa
+    for m in &plan.moves {
+        if !is_empty_or_missing(&m.to)? {
+            return Err(anyhow!("Destination {:?} already contains data; move or remove it first", m.to));
+        }
+    }
+    if plan.config_from.as_deref() != Some(plan.config_to.as_path()) && plan.config_to.exists() {
+        return Err(anyhow!("Configuration file {:?} already exists; move or remove it first", plan.config_to));
+    }
+
+    for m in &plan.moves {
+        if m.from.exists() {
+            move_dir(&m.from, &m.to)?;
+            info!("Moved {:?} to {:?}", m.from, m.to);
+        } else {
+            fs::create_dir_all(&m.to)
+                .context(format!("Failed to create directory {:?}", m.to))?;
+        }
+    }
+
+    let dirs = plan.target.data_dirs();
+    let mut migrated = config.clone();
+    migrated.tasks_dir = dirs.tasks_dir.to_string_lossy().to_string();
+    migrated.stacks_dir = dirs.stacks_dir.to_string_lossy().to_string();
+    migrated.state_dir = dirs.state_dir.to_string_lossy().to_string();
+    // Le directory sovrascritte dall'ambiente vengono comunque migrate nel file
+    for key in ["tasks_dir", "stacks_dir", "state_dir"] {
+        migrated.env_overrides.remove(key);
+    }
+    migrated.save(&plan.config_to)?;
+
+    let old = plan.config_from.as_ref().filter(|old| **old != plan.config_to);
+    if let Some((old, Err(e))) = old.map(|old| (old, fs::remove_file(old))) {
+        warn!("Failed to remove old configuration file {:?}: {}", old, e);
+    }
+
+    info!("Migrated to the {} layout, configuration in {:?}", plan.target.to_str(), plan.config_to);
+    Ok(())
+}
+
+/// Restituisce una directory XDG, con il percorso predefinito nella home se non impostata
+///
+/// Come previsto dalla specifica, i valori relativi vengono ignorati.
+fn xdg_dir(var: &str, default: &str) -> PathBuf {
+    env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| utils::get_home_dir().map(|home| home.join(default)))
+        .unwrap_or_else(|| utils::get_temp_dir().join("galatea").join(default))
+}
+
+/// Verifica se una directory è assente o vuota
+fn is_empty_or_missing(dir: &Path) -> Result<bool> {
+    if !dir.exists() {
+        return Ok(true);
+    }
+    Ok(fs::read_dir(dir)
+        .context(format!("Failed to read directory {:?}", dir))?
+        .next()
+        .is_none())
+}
+
+/// Sposta una directory, copiandola se la destinazione è su un altro filesystem
+fn move_dir(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)
+            .context(format!("Failed to create directory {:?}", parent))?;
+    }
+
+    // Una destinazione vuota creata in precedenza non deve impedire il rename
+    if to.exists() {
+        fs::remove_dir(to).context(format!("Failed to remove empty directory {:?}", to))?;
+    }
+
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    copy_dir(from, to)?;
+    fs::remove_dir_all(from).context(format!("Failed to remove {:?} after copying it", from))
+}
+
+/// Copia ricorsivamente una directory, mantenendo i permessi dei file
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to).context(format!("Failed to create directory {:?}", to))?;
+
+    for entry in fs::read_dir(from).context(format!("Failed to read directory {:?}", from))? {
+        let entry = entry.context("Failed to read directory entry")?;
+        let source = entry.path();
+        let destination = to.join(entry.file_name());
+        let file_type = entry.file_type().context(format!("Failed to read file type of {:?}", source))?;
+
+        if file_type.is_dir() {
+            copy_dir(&source, &destination)?;
+        } else if file_type.is_symlink() {
+            copy_link(&source, &destination)?;
+        } else {
+            fs::copy(&source, &destination)
+                .context(format!("Failed to copy {:?} to {:?}", source, destination))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Ricrea un link simbolico con la stessa destinazione
+#[cfg(unix)]
+fn copy_link(from: &Path, to: &Path) -> Result<()> {
+    let target = fs::read_link(from).context(format!("Failed to read link {:?}", from))?;
+    std::os::unix::fs::symlink(&target, to)
+        .context(format!("Failed to create link {:?}", to))
+}
+
+/// Copia il file a cui punta un link simbolico, dove i link non sono supportati
+#[cfg(not(unix))]
+fn copy_link(from: &Path, to: &Path) -> Result<()> {
+    fs::copy(from, to)
+        .map(|_| ())
+        .context(format!("Failed to copy {:?} to {:?}", from, to))
+}
@@ -0,0 +1,367 @@
+//! Verifica statica dei bundle di task
+//!
+//! Questo modulo controlla che un bundle rispetti le convenzioni attese da Galatea
+//! (punti di ingresso, shebang, permessi, azioni e tag ansible) e produce un elenco
+//! di segnalazioni con i relativi suggerimenti di correzione.
+
+use std::path::{Path, PathBuf};
+use std::fs;
+use std::fmt;
+use anyhow::{Context, Result, anyhow};
+use log::{info, warn};
+
+use crate::downloader;
+use crate::executor::{self, BASH_SCRIPT_NAMES, PLAYBOOK_NAMES, TASK_ACTIONS};
+use crate::task::ScriptType;
+use crate::wasm_runner;
+
+/// Gravità di una segnalazione
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// Il bundle non funzionerà correttamente
+    Error,
+    /// Il bundle funziona ma potrebbe comportarsi in modo inatteso
+    Warning,
+}
+
+impl Severity {
+    /// Converte la gravità in una stringa
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Severity::Error => "ERRORE",
+            Severity::Warning => "AVVISO",
+        }
+    }
+}
+
+/// Singola segnalazione prodotta dal lint
+#[derive(Debug, Clone)]
+pub struct Finding {
+    /// Gravità della segnalazione
+    pub severity: Severity,
+
+    /// File a cui si riferisce la segnalazione (relativo al bundle)
+    pub file: Option<PathBuf>,
+
+    /// Descrizione del problema
+    pub message: String,
+
+    /// Suggerimento per la correzione
+    pub hint: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "[{}] {}: {}", self.severity.to_str(), file.display(), self.message)?,
+            None => write!(f, "[{}] {}", self.severity.to_str(), self.message)?,
+        }
+        write!(f, "\n         Suggerimento: {}", self.hint)
+    }
+}
+
+/// Risultato del lint di un bundle
+#[derive(Debug, Clone, Default)]
+pub struct LintReport {
+    /// Segnalazioni trovate
+    pub findings: Vec<Finding>,
+}
+
+impl LintReport {
+    /// Aggiunge una segnalazione
+    fn add(&mut self, severity: Severity, file: Option<&Path>, message: String, hint: &str) {
+        self.findings.push(Finding {
+            severity,
+            file: file.map(|f| f.to_path_buf()),
+            message,
+            hint: hint.to_string(),
+        });
+    }
+
+    /// Conta le segnalazioni di tipo errore
+    pub fn error_count(&self) -> usize {
+        self.findings.iter().filter(|f| f.severity == Severity::Error).count()
+    }
+
+    /// Conta le segnalazioni di tipo avviso
+    pub fn warning_count(&self) -> usize {
+        self.findings.iter().filter(|f| f.severity == Severity::Warning).count()
+    }
+}
+
+/// Esegue il lint di un bundle
+///
+/// # Arguments
+///
+/// * `path` - La directory del bundle, un archivio (zip, tar.gz, tgz) o un singolo script/playbook
+/// * `script_type` - Il tipo di script atteso; se assente viene dedotto dai file presenti
+///
+/// # Returns
+///
+/// Il report con le segnalazioni trovate
+pub fn lint_bundle(path: &Path, script_type: Option<ScriptType>) -> Result<LintReport> {
+    if !path.exists() {
+        return Err(anyhow!("Bundle not found: {:?}", path));
+    }
+
+    info!("Linting bundle: {:?}", path);
+
+    // Gli archivi vengono estratti in una directory temporanea
+    let file_name = path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let is_archive = file_name.ends_with(".zip") || file_name.ends_with(".tar.gz") || file_name.ends_with(".tgz");
+
+    if path.is_file() && is_archive {
+        let extract_dir = std::env::temp_dir().join(format!("galatea_lint_{}", std::process::id()));
+        downloader::extract_archive(path, &extract_dir)?;
+        let result = lint_directory(&extract_dir, script_type);
+        if let Err(e) = fs::remove_dir_all(&extract_dir) {
+            warn!("Failed to remove temporary lint directory {:?}: {}", extract_dir, e);
+        }
+        return result;
+    }
+
+    if path.is_dir() {
+        return lint_directory(path, script_type);
+    }
+
+    // Singolo file: script bash o playbook in base all'estensione
+    let mut report = LintReport::default();
+    if file_name.ends_with(".yml") || file_name.ends_with(".yaml") {
+        lint_playbook(path, path.parent().unwrap_or(Path::new(".")), &mut report)?;
+    } else {
+        lint_bash_script(path, path.parent().unwrap_or(Path::new(".")), &mut report)?;
+    }
+
+    Ok(report)
+}
+
+/// Esegue il lint di una directory di bundle
+fn lint_directory(dir: &Path, script_type: Option<ScriptType>) -> Result<LintReport> {
+    let mut report = LintReport::default();
+
+    let script = executor::find_script_in_dir(dir, BASH_SCRIPT_NAMES).ok();
+    let playbook = executor::find_script_in_dir(dir, PLAYBOOK_NAMES).ok();
+
+    // Determina i punti di ingresso attesi
+    let (expect_bash, expect_ansible) = match script_type {
+        Some(ScriptType::Bash) => (true, false),
+        Some(ScriptType::Ansible) => (false, true),
+        Some(ScriptType::Mixed) => (true, true),
+        // I task wasm non hanno script: le azioni sono decise dal runner
+        Some(ScriptType::Wasm) => (false, false),
+        None => (script.is_some() || playbook.is_none(), playbook.is_some()),
+    };
+
+    if script_type == Some(ScriptType::Wasm) && !dir.join(wasm_runner::DEFAULT_RUNNER).is_file() {
+        report.add(
+            Severity::Warning,
+            None,
+            format!("nessun runner {} nel bundle", wasm_runner::DEFAULT_RUNNER),
+            "aggiungi il modulo del runner al bundle o indicalo con 'runner' nel catalogo",
+        );
+    }
+
+    if expect_bash {
+        match &script {
+            Some(script) => lint_bash_script(script, dir, &mut report)?,
+            None => report.add(
+                Severity::Error,
+                None,
+                format!("nessuno script di ingresso trovato (atteso: {})", BASH_SCRIPT_NAMES.join(", ")),
+                "aggiungi install.sh nella radice del bundle (vedi 'galatea new-task')",
+            ),
+        }
+    }
+
+    if expect_ansible {
+        match &playbook {
+            Some(playbook) => lint_playbook(playbook, dir, &mut report)?,
+            None => report.add(
+                Severity::Error,
+                None,
+                format!("nessun playbook trovato (atteso uno tra: {})", PLAYBOOK_NAMES.join(", ")),
+                "aggiungi playbook.yml nella radice del bundle (vedi 'galatea new-task')",
+            ),
+        }
+    }
+
+    Ok(report)
+}
+
+/// Verifica uno script bash
+fn lint_bash_script(script: &Path, base_dir: &Path, report: &mut LintReport) -> Result<()> {
+    let rel = script.strip_prefix(base_dir).unwrap_or(script);
+    let content = fs::read_to_string(script)
+        .context(format!("Failed to read script: {:?}", script))?;
+
+    // Shebang
+    if !content.starts_with("#!") {
+        report.add(
+            Severity::Error,
+            Some(rel),
+            "manca lo shebang nella prima riga".to_string(),
+            "inizia lo script con '#!/bin/bash'",
+        );
+    }
+
+    // Terminazioni di riga Windows (rompono lo shebang e i confronti sulle azioni)
+    if content.contains("\r\n") {
+        report.add(
+            Severity::Error,
+            Some(rel),
+            "lo script usa terminazioni di riga CRLF".to_string(),
+            "converti lo script con 'dos2unix' o salvalo con terminazioni LF",
+        );
+    }
+
+    // Permessi di esecuzione
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = fs::metadata(script)
+            .context(format!("Failed to get file permissions: {:?}", script))?
+            .permissions()
+            .mode();
+
+        if mode & 0o111 == 0 {
+            report.add(
+                Severity::Warning,
+                Some(rel),
+                "lo script non è eseguibile".to_string(),
+                "esegui 'chmod +x' prima di creare l'archivio del bundle",
+            );
+        }
+    }
+
+    // Azioni supportate
+    for action in TASK_ACTIONS {
+        if !contains_word(&content, action) {
+            let severity = if *action == "install" { Severity::Error } else { Severity::Warning };
+            report.add(
+                severity,
+                Some(rel),
+                format!("l'azione '{}' non è gestita dallo script", action),
+                "gestisci l'argomento $1 con un 'case' per install, uninstall, reset e remediate",
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Verifica un playbook ansible
+fn lint_playbook(playbook: &Path, base_dir: &Path, report: &mut LintReport) -> Result<()> {
+    let rel = playbook.strip_prefix(base_dir).unwrap_or(playbook);
+    let content = fs::read_to_string(playbook)
+        .context(format!("Failed to read playbook: {:?}", playbook))?;
+
+    let yaml: serde_yaml::Value = match serde_yaml::from_str(&content) {
+        Ok(yaml) => yaml,
+        Err(e) => {
+            report.add(
+                Severity::Error,
+                Some(rel),
+                format!("il playbook non è YAML valido: {}", e),
+                "correggi la sintassi del playbook (es. con 'ansible-playbook --syntax-check')",
+            );
+            return Ok(());
+        }
+    };
+
+    let plays = match yaml.as_sequence() {
+        Some(plays) => plays,
+        None => {
+            report.add(
+                Severity::Error,
+                Some(rel),
+                "il playbook deve essere una lista di play".to_string(),
+                "definisci il playbook come lista ('- name: ...' con 'hosts' e 'tasks')",
+            );
+            return Ok(());
+        }
+    };
+
+    // Galatea esegue i playbook con inventario 'localhost,'
+    for play in plays {
+        if play.get("import_playbook").is_some() {
+            continue;
+        }
+
+        match play.get("hosts").and_then(|h| h.as_str()) {
+            None => report.add(
+                Severity::Error,
+                Some(rel),
+                "un play non definisce 'hosts'".to_string(),
+                "imposta 'hosts: all' o 'hosts: localhost'",
+            ),
+            Some(hosts) if hosts != "all" && hosts != "localhost" => report.add(
+                Severity::Warning,
+                Some(rel),
+                format!("il play usa 'hosts: {}', che non corrisponde all'inventario locale", hosts),
+                "imposta 'hosts: all' o 'hosts: localhost'",
+            ),
+            Some(_) => {}
+        }
+    }
+
+    // Tag delle azioni standard
+    let mut tags = Vec::new();
+    collect_tags(&yaml, &mut tags);
+
+    for action in TASK_ACTIONS {
+        if !tags.iter().any(|t| t == action) {
+            let severity = if *action == "install" { Severity::Error } else { Severity::Warning };
+            report.add(
+                severity,
+                Some(rel),
+                format!("nessun task con il tag '{}'", action),
+                "Galatea esegue il playbook con --tags=<azione>: aggiungi il tag ai task corrispondenti",
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Raccoglie ricorsivamente i valori delle chiavi 'tags' di un documento YAML
+fn collect_tags(value: &serde_yaml::Value, tags: &mut Vec<String>) {
+    match value {
+        serde_yaml::Value::Mapping(map) => {
+            for (key, val) in map {
+                if key.as_str() == Some("tags") {
+                    match val {
+                        serde_yaml::Value::String(tag) => {
+                            tags.extend(tag.split(',').map(|t| t.trim().to_string()));
+                        },
+                        serde_yaml::Value::Sequence(seq) => {
+                            tags.extend(seq.iter().filter_map(|t| t.as_str()).map(|t| t.to_string()));
+                        },
+                        _ => {}
+                    }
+                } else {
+                    collect_tags(val, tags);
+                }
+            }
+        },
+        serde_yaml::Value::Sequence(seq) => {
+            for item in seq {
+                collect_tags(item, tags);
+            }
+        },
+        _ => {}
+    }
+}
+
+/// Verifica se il testo contiene una parola intera (es. 'install' ma non 'uninstall')
+fn contains_word(content: &str, word: &str) -> bool {
+    let is_word_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+
+    content.match_indices(word).any(|(idx, _)| {
+        let before = content[..idx].chars().next_back();
+        let after = content[idx + word.len()..].chars().next();
+        !before.map_or(false, is_word_char) && !after.map_or(false, is_word_char)
+    })
+}
@@ -0,0 +1,192 @@
+//! Risorse condivise tra i task
+//!
+//! Un task può dichiarare le risorse che usa in modo esclusivo (`locks: [apt, docker]`):
+//! due task che condividono una risorsa non vengono mai eseguiti insieme, né negli stack
+//! installati in parallelo né da processi di Galatea diversi (es. il demone e la CLI).
+//! Ogni risorsa è un file in `<state_dir>/locks` bloccato con `flock`; all'interno dello
+//! stesso thread i blocchi sono rientranti, così che il rollback di un controllo di salute
+//! possa disinstallare il task che li detiene.
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::Ordering;
+use std::thread::{self, ThreadId};
+use std::time::Duration;
+use anyhow::{Context, Result, anyhow};
+use lazy_static::lazy_static;
+use log::{Level, debug, info};
+
+use crate::events;
+use crate::executor;
+use crate::jobs;
+
+/// Directory dei file di blocco, nella directory di stato
+const LOCKS_DIR: &str = "locks";
+
+/// Intervallo tra un tentativo e l'altro mentre si attende una risorsa occupata
+const RETRY_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Risorsa bloccata da un thread di questo processo
+struct Held {
+    /// Il thread che detiene la risorsa
+    thread: ThreadId,
+
+    /// Quante volte il thread l'ha bloccata
+    count: usize,
+
+    /// Il file bloccato con `flock`, sbloccato alla chiusura
+    _file: File,
+}
+
+lazy_static! {
+    static ref HELD: Mutex<HashMap<String, Held>> = Mutex::new(HashMap::new());
+}
+
+/// Verifica che il nome di una risorsa possa essere usato come nome di file
+pub fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')) || name.starts_with('.') {
+        return Err(anyhow!("Invalid resource name '{}': use letters, digits, '-', '_' and '.'", name));
+    }
+    Ok(())
+}
+
+/// Risorse bloccate, rilasciate quando il valore viene eliminato
+#[derive(Debug)]
+pub struct ResourceGuard {
+    names: Vec<String>,
+}
+
+impl Drop for ResourceGuard {
+    fn drop(&mut self) {
+        release(&self.names);
+    }
+}
+
+/// Blocca le risorse indicate, attendendo che si liberino
+///
+/// L'attesa si interrompe se il job corrente viene annullato o se Galatea viene interrotto.
+///
+/// # Arguments
+///
+/// * `state_dir` - La directory di stato di Galatea
+/// * `owner` - Chi blocca le risorse, per i messaggi (es. il nome del task)
+/// * `names` - Le risorse da bloccare
+///
+/// # Returns
+///
+/// Il blocco delle risorse, oppure un errore se l'attesa è stata interrotta
+pub fn acquire(state_dir: &Path, owner: &str, names: &[String]) -> Result<ResourceGuard> {
+    let mut names = names.to_vec();
+    names.sort();
+    names.dedup();
+
+    let dir = state_dir.join(LOCKS_DIR);
+    if !names.is_empty() {
+        fs::create_dir_all(&dir).context(format!("Failed to create lock directory {:?}", dir))?;
+    }
+
+    let mut waiting = false;
+    loop {
+        if let Some(busy) = try_acquire(&dir, &names)? {
+            if !waiting {
+                info!("Task {} waiting for resource {} held by another task or process", owner, busy);
+                events::log_line(Level::Info, format!("{}: in attesa della risorsa {}", owner, busy));
+                waiting = true;
+            }
+        } else {
+            if waiting {
+                info!("Task {} acquired resources {}", owner, names.join(", "));
+            }
+            return Ok(ResourceGuard { names });
+        }
+
+        if jobs::cancel_requested() {
+            return Err(anyhow!("Cancelled while waiting for resources {}", names.join(", ")));
+        }
+        if executor::interrupt_flag().load(Ordering::SeqCst) {
+            return Err(anyhow!("Interrupted while waiting for resources {}", names.join(", ")));
+        }
+        thread::sleep(RETRY_INTERVAL);
+    }
+}
+
+/// Prova a bloccare tutte le risorse, senza attendere
+///
+/// # Returns
+///
+/// `None` se tutte le risorse sono state bloccate, altrimenti la prima risorsa occupata;
+/// in quel caso nessuna risorsa resta bloccata
+fn try_acquire(dir: &Path, names: &[String]) -> Result<Option<String>> {
+    let current = thread::current().id();
+    let mut held = HELD.lock().map_err(|_| anyhow!("Resource lock registry unavailable"))?;
+
+    let mut acquired: Vec<String> = Vec::new();
+    let mut busy = None;
+    for name in names {
+        if let Some(entry) = held.get_mut(name) {
+            // Rientrante nello stesso thread, occupata se la detiene un altro thread
+            if entry.thread == current {
+                entry.count += 1;
+                acquired.push(name.clone());
+                continue;
+            }
+            busy = Some(name.clone());
+            break;
+        }
+
+        let path = dir.join(format!("{}.lock", name));
+        let file = OpenOptions::new().create(true).truncate(false).write(true).open(&path)
+            .context(format!("Failed to open lock file {:?}", path))?;
+        if !lock_file(&file) {
+            busy = Some(name.clone());
+            break;
+        }
+        debug!("Resource {} locked", name);
+        held.insert(name.clone(), Held { thread: current, count: 1, _file: file });
+        acquired.push(name.clone());
+    }
+
+    if busy.is_some() {
+        release_held(&mut held, &acquired);
+    }
+    Ok(busy)
+}
+
+/// Blocca un file con `flock`, senza attendere
+#[cfg(unix)]
+fn lock_file(file: &File) -> bool {
+    use std::os::unix::io::AsRawFd;
+    unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) == 0 }
+}
+
+/// Senza `flock` le risorse sono escluse solo tra i thread di questo processo
+#[cfg(not(unix))]
+fn lock_file(_file: &File) -> bool {
+    true
+}
+
+/// Rilascia le risorse bloccate dal thread corrente
+fn release(names: &[String]) {
+    if let Ok(mut held) = HELD.lock() {
+        release_held(&mut held, names);
+    }
+}
+
+/// Rilascia le risorse nel registro; il file viene sbloccato quando il conteggio si azzera
+fn release_held(held: &mut HashMap<String, Held>, names: &[String]) {
+    for name in names {
+        let remove = match held.get_mut(name) {
+            Some(entry) => {
+                entry.count -= 1;
+                entry.count == 0
+            },
+            None => false,
+        };
+        if remove {
+            held.remove(name);
+            debug!("Resource {} released", name);
+        }
+    }
+}
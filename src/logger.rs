@@ -1,15 +1,21 @@
 //! Modulo per la gestione centralizzata dei log su file
 //!
 //! Questo modulo fornisce funzionalità per scrivere i log su file invece che su console.
+//! In modalità non interattiva i messaggi possono essere riportati anche su stderr, in
+//! base al livello di verbosità scelto con `-q`, `-v` e `-vv`.
 
 use std::path::{Path, PathBuf};
 use std::fs::{self, File, OpenOptions};
 use std::io::{Write, Read, BufReader, BufRead};
 use std::sync::Mutex;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 use anyhow::{Context, Result};
 use chrono::Local;
 use lazy_static::lazy_static;
+use log::LevelFilter;
+
+use crate::events;
+use crate::redact;
 
 // Singleton per il file di log e il percorso del file corrente
 lazy_static! {
@@ -17,16 +23,113 @@ lazy_static! {
     static ref LOG_PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
     static ref LOG_DIR: Mutex<Option<String>> = Mutex::new(None);
     static ref LOG_INITIALIZED: AtomicBool = AtomicBool::new(false);
+    static ref VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+    static ref CONSOLE_ENABLED: AtomicBool = AtomicBool::new(false);
+}
+
+/// Prefisso dei moduli di Galatea, usato per filtrare i log di debug delle dipendenze
+const CRATE_PREFIX: &str = "galatea";
+
+/// Livello di verbosità scelto da linea di comando
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Solo errori (`-q`)
+    Quiet = 0,
+
+    /// Output normale
+    Normal = 1,
+
+    /// Output dettagliato (`-v`)
+    Verbose = 2,
+
+    /// Output di debug (`-vv`)
+    Debug = 3,
+}
+
+impl Verbosity {
+    /// Determina la verbosità dalle opzioni da linea di comando
+    ///
+    /// # Arguments
+    ///
+    /// * `quiet` - Indica se è stato specificato `-q`
+    /// * `verbose` - Il numero di occorrenze di `-v`
+    pub fn from_flags(quiet: bool, verbose: u8) -> Self {
+        match (quiet, verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, 0) => Verbosity::Normal,
+            (false, 1) => Verbosity::Verbose,
+            (false, _) => Verbosity::Debug,
+        }
+    }
+
+    /// Livello dei messaggi scritti sul file di log
+    pub fn file_level(&self) -> LevelFilter {
+        match self {
+            Verbosity::Quiet | Verbosity::Normal => LevelFilter::Info,
+            Verbosity::Verbose => LevelFilter::Debug,
+            Verbosity::Debug => LevelFilter::Trace,
+        }
+    }
+
+    /// Livello dei messaggi riportati su stderr in modalità non interattiva
+    ///
+    /// Gli errori sono già mostrati dai sottocomandi, quindi senza `-v` i log non
+    /// vengono ripetuti su console.
+    pub fn console_level(&self) -> LevelFilter {
+        match self {
+            Verbosity::Quiet | Verbosity::Normal => LevelFilter::Off,
+            Verbosity::Verbose => LevelFilter::Info,
+            Verbosity::Debug => LevelFilter::Debug,
+        }
+    }
+
+    /// Indica se l'output informativo va soppresso
+    pub fn is_quiet(&self) -> bool {
+        *self == Verbosity::Quiet
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Verbosity::Quiet,
+            1 => Verbosity::Normal,
+            2 => Verbosity::Verbose,
+            _ => Verbosity::Debug,
+        }
+    }
+}
+
+/// Restituisce la verbosità corrente
+pub fn verbosity() -> Verbosity {
+    Verbosity::from_u8(VERBOSITY.load(Ordering::SeqCst))
+}
+
+/// Indica se la console è disponibile per l'output (falso mentre l'interfaccia TUI è attiva)
+pub fn console_output() -> bool {
+    CONSOLE_ENABLED.load(Ordering::SeqCst)
 }
 
-/// Inizializza il sistema di logging su file (solo su file, non su console)
-pub fn init_file_logger(log_dir: &str) -> Result<()> {
+/// Abilita o disabilita la copia dei log su stderr
+///
+/// Va disabilitata mentre l'interfaccia TUI è attiva, per non sporcare lo schermo.
+pub fn set_console_output(enabled: bool) {
+    CONSOLE_ENABLED.store(enabled, Ordering::SeqCst);
+}
+
+/// Inizializza il sistema di logging su file
+///
+/// # Arguments
+///
+/// * `log_dir` - La directory dei file di log
+/// * `verbosity` - Il livello di verbosità scelto da linea di comando
+pub fn init_file_logger(log_dir: &str, verbosity: Verbosity) -> Result<()> {
     // Verifica se il logger è già stato inizializzato
     if LOG_INITIALIZED.load(Ordering::SeqCst) {
         // Il logger è già inizializzato, non fare nulla
         return Ok(());
     }
 
+    VERBOSITY.store(verbosity as u8, Ordering::SeqCst);
+
     // Crea la directory dei log se non esiste
     fs::create_dir_all(log_dir).context("Failed to create log directory")?;
 
@@ -59,20 +162,15 @@ pub fn init_file_logger(log_dir: &str) -> Result<()> {
         *log_file_guard = Some(file);
     }
 
-    // Configura il logger per scrivere solo sul file (non su stdout)
-    env_logger::Builder::from_default_env()
-        .format(|buf, record| {
-            let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
-            writeln!(
-                buf,
-                "[{}] {} {}: {}",
-                timestamp,
-                record.level(),
-                record.module_path().unwrap_or("unknown"),
-                record.args()
-            )
-        })
-        .init();
+    // Configura il logger per scrivere sul file e, se abilitato, su stderr
+    let logger = FileAndConsoleLogger {
+        file_level: verbosity.file_level(),
+        console_level: verbosity.console_level(),
+    };
+    let max_level = logger.file_level.max(logger.console_level);
+    log::set_logger(Box::leak(Box::new(logger)))
+        .map_err(|e| anyhow::anyhow!("Failed to install logger: {}", e))?;
+    log::set_max_level(max_level);
 
     // Inizializza il logger
     log::info!("Logger initialized, writing to: {:?}", log_file_path);
@@ -99,7 +197,7 @@ pub fn get_current_log_path() -> Option<PathBuf> {
 /// Scrive un messaggio di log manualmente
 pub fn log_to_file(message: &str) -> Result<()> {
     let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
-    let formatted = format!("[{}] INFO: {}\n", timestamp, message);
+    let formatted = format!("[{}] INFO: {}\n", timestamp, redact::mask(message));
 
     // Scrivi sul file di log
     if let Ok(log_file_guard) = LOG_FILE.lock() {
@@ -185,23 +283,99 @@ pub fn read_log_file(path: &Path) -> Result<String> {
     Ok(content)
 }
 
-/// Implementazione di un logger personalizzato che scrive solo su file (non su console)
-pub struct FileAndConsoleLogger;
+/// Numero di righe di log incluse nei report di errore
+const ERROR_REPORT_LOG_LINES: usize = 100;
+
+/// Salva su file un report di errore da allegare alle richieste di supporto
+///
+/// Il report contiene l'operazione, l'elemento coinvolto, il messaggio di errore e
+/// le ultime righe del log corrente. Viene scritto nella directory dei log o, se il
+/// logger non è inizializzato, nella directory temporanea.
+///
+/// # Arguments
+///
+/// * `operation` - L'operazione fallita
+/// * `item_name` - Il nome dell'elemento coinvolto (vuoto se non applicabile)
+/// * `message` - Il messaggio di errore
+///
+/// # Returns
+///
+/// Il percorso del file creato
+pub fn write_error_report(operation: &str, item_name: &str, message: &str) -> Result<PathBuf> {
+    let now = Local::now();
+    let dir = get_log_directory()
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+    let path = dir.join(format!("galatea_error_{}.txt", now.format("%Y%m%d_%H%M%S")));
+
+    let mut report = String::new();
+    report.push_str(&format!("Galatea {} - error report\n", env!("CARGO_PKG_VERSION")));
+    report.push_str(&format!("Date: {}\n", now.format("%Y-%m-%d %H:%M:%S")));
+    report.push_str(&format!("Operation: {}\n", operation));
+    if !item_name.is_empty() {
+        report.push_str(&format!("Item: {}\n", item_name));
+    }
+    if let Some(log_path) = get_current_log_path() {
+        report.push_str(&format!("Log file: {}\n", log_path.display()));
+    }
+    report.push_str(&format!("\nError:\n{}\n", redact::mask(message)));
+
+    let recent = get_recent_logs(ERROR_REPORT_LOG_LINES).unwrap_or_default();
+    if !recent.is_empty() {
+        report.push_str(&format!("\nLast {} log lines:\n", recent.len()));
+        for line in recent {
+            report.push_str(&line);
+            report.push('\n');
+        }
+    }
+
+    fs::write(&path, report)
+        .context(format!("Failed to write error report: {:?}", path))?;
+
+    log::info!("Error report written to {:?}", path);
+    Ok(path)
+}
+
+/// Implementazione di un logger personalizzato che scrive su file e, in modalità
+/// non interattiva, su stderr
+pub struct FileAndConsoleLogger {
+    /// Livello minimo dei messaggi scritti sul file
+    file_level: LevelFilter,
+
+    /// Livello minimo dei messaggi riportati su stderr
+    console_level: LevelFilter,
+}
+
+impl FileAndConsoleLogger {
+    /// Applica il livello a un record: i messaggi di debug delle dipendenze vengono scartati
+    fn accepts(level_filter: LevelFilter, metadata: &log::Metadata) -> bool {
+        if metadata.level() > level_filter {
+            return false;
+        }
+
+        metadata.level() <= log::Level::Info || metadata.target().starts_with(CRATE_PREFIX)
+    }
+}
 
 impl log::Log for FileAndConsoleLogger {
     fn enabled(&self, metadata: &log::Metadata) -> bool {
-        metadata.level() <= log::Level::Debug
+        Self::accepts(self.file_level, metadata)
+            || (CONSOLE_ENABLED.load(Ordering::SeqCst) && Self::accepts(self.console_level, metadata))
     }
 
     fn log(&self, record: &log::Record) {
-        if self.enabled(record.metadata()) {
-            let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
+        let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
+        // I valori segreti non devono mai comparire nei log
+        let message = record.args().to_string();
+        let message = redact::mask(&message);
+
+        if Self::accepts(self.file_level, record.metadata()) {
             let formatted = format!(
                 "[{}] {} {}: {}\n",
                 timestamp,
                 record.level(),
                 record.module_path().unwrap_or("unknown"),
-                record.args()
+                message
             );
 
             // Scrivi sul file di log
@@ -211,9 +385,16 @@ impl log::Log for FileAndConsoleLogger {
                     let _ = file.flush();
                 }
             }
+        }
+
+        // Gli avvisi del core vengono mostrati anche nel registro delle operazioni
+        if record.level() == log::Level::Warn && record.target().starts_with(CRATE_PREFIX) {
+            events::log_line(record.level(), message.to_string());
+        }
 
-            // Non scrivere sulla console (rimosso)
-            // println!("{}", formatted.trim());
+        // Su stderr solo in modalità non interattiva
+        if CONSOLE_ENABLED.load(Ordering::SeqCst) && Self::accepts(self.console_level, record.metadata()) {
+            eprintln!("{}: {}", record.level(), message);
         }
     }
 
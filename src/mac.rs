@@ -0,0 +1,275 @@
+//! Controllo di accesso obbligatorio (SELinux e AppArmor)
+//!
+//! Su sistemi con SELinux o AppArmor attivo uno script può fallire per un diniego del
+//! modulo di sicurezza anche quando i permessi Unix sono corretti. Questo modulo rileva
+//! il sistema attivo, raccoglie i dinieghi registrati durante un'esecuzione fallita e,
+//! se configurato, ripristina le etichette SELinux dei file installati nelle directory
+//! gestite (`restorecon_dirs`).
+
+use std::ffi::CString;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::{Error, Result, anyhow};
+use log::{debug, info, warn};
+use regex::Regex;
+
+use crate::config::Config;
+use crate::executor;
+
+/// File con lo stato di SELinux (1 = enforcing, 0 = permissive)
+const SELINUX_ENFORCE: &str = "/sys/fs/selinux/enforce";
+
+/// File che indica se AppArmor è abilitato
+const APPARMOR_ENABLED: &str = "/sys/module/apparmor/parameters/enabled";
+
+/// Elenco dei profili AppArmor caricati, con la loro modalità
+const APPARMOR_PROFILES: &str = "/sys/kernel/security/apparmor/profiles";
+
+/// Log di auditd, in cui finiscono i dinieghi quando il demone è attivo
+const AUDIT_LOG: &str = "/var/log/audit/audit.log";
+
+/// Porzione finale del log di audit esaminata, in byte
+const AUDIT_LOG_TAIL: u64 = 4 * 1024 * 1024;
+
+/// Numero massimo di dinieghi riportati nell'errore
+const MAX_DENIALS: usize = 5;
+
+/// Modulo di sicurezza attivo
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MacSystem {
+    SELinux,
+    AppArmor,
+}
+
+impl fmt::Display for MacSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MacSystem::SELinux => write!(f, "SELinux"),
+            MacSystem::AppArmor => write!(f, "AppArmor"),
+        }
+    }
+}
+
+impl MacSystem {
+    /// Restituisce l'espressione che riconosce un diniego nei log
+    fn denial_pattern(&self) -> &'static str {
+        match self {
+            MacSystem::SELinux => r"avc:\s+denied",
+            MacSystem::AppArmor => r#"apparmor="DENIED""#,
+        }
+    }
+}
+
+/// Stato del modulo di sicurezza rilevato sul sistema
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MacStatus {
+    /// Modulo attivo
+    pub system: MacSystem,
+
+    /// Indica se i dinieghi vengono applicati (SELinux enforcing, profili AppArmor in enforce)
+    pub enforcing: bool,
+}
+
+impl fmt::Display for MacStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match (self.system, self.enforcing) {
+            (MacSystem::SELinux, true) => "enforcing",
+            (MacSystem::SELinux, false) => "permissive",
+            (MacSystem::AppArmor, true) => "enforce",
+            (MacSystem::AppArmor, false) => "complain",
+        };
+        write!(f, "{} ({})", self.system, mode)
+    }
+}
+
+/// Rileva il modulo di sicurezza attivo
+///
+/// # Returns
+///
+/// Lo stato di SELinux o AppArmor, o `None` se nessuno dei due è attivo
+pub fn detect() -> Option<MacStatus> {
+    if let Ok(enforce) = fs::read_to_string(SELINUX_ENFORCE) {
+        return Some(MacStatus { system: MacSystem::SELinux, enforcing: enforce.trim() == "1" });
+    }
+
+    let apparmor = fs::read_to_string(APPARMOR_ENABLED).ok()?;
+    if !apparmor.trim().eq_ignore_ascii_case("y") {
+        return None;
+    }
+
+    // Senza accesso all'elenco dei profili (serve root) si assume che alcuni siano in enforce
+    let enforcing = fs::read_to_string(APPARMOR_PROFILES)
+        .map_or(true, |profiles| profiles.lines().any(|line| line.ends_with("(enforce)")));
+    Some(MacStatus { system: MacSystem::AppArmor, enforcing })
+}
+
+/// Restituisce l'etichetta SELinux di un file
+fn file_label(path: &Path) -> Option<String> {
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let name = CString::new("security.selinux").ok()?;
+    let mut buffer = vec![0u8; 256];
+
+    let len = unsafe {
+        libc::getxattr(path.as_ptr(), name.as_ptr(), buffer.as_mut_ptr() as *mut libc::c_void, buffer.len())
+    };
+    if len <= 0 {
+        return None;
+    }
+
+    buffer.truncate(len as usize);
+    Some(String::from_utf8_lossy(&buffer).trim_end_matches('\0').to_string())
+}
+
+/// Restituisce il contesto (SELinux) o il profilo (AppArmor) con cui è eseguito Galatea
+fn process_label() -> Option<String> {
+    fs::read_to_string("/proc/self/attr/current").ok()
+        .map(|label| label.trim_end_matches(['\0', '\n']).to_string())
+        .filter(|label| !label.is_empty())
+}
+
+/// Raccoglie i dinieghi registrati a partire da un certo istante
+///
+/// I dinieghi vengono cercati nel log di auditd e, se non è disponibile, nel log del
+/// kernel tramite journalctl.
+fn recent_denials(system: MacSystem, since: SystemTime) -> Vec<String> {
+    let pattern = Regex::new(system.denial_pattern()).expect("valid denial pattern");
+    let since_secs = since.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+
+    let mut denials = match audit_log_denials(&pattern, since_secs) {
+        Ok(denials) => denials,
+        Err(e) => {
+            debug!("Audit log not available ({:#}), reading the kernel log", e);
+            journal_denials(&pattern, since_secs)
+        },
+    };
+
+    if denials.len() > MAX_DENIALS {
+        denials.drain(..denials.len() - MAX_DENIALS);
+    }
+    denials
+}
+
+/// Cerca i dinieghi nella parte finale del log di auditd
+fn audit_log_denials(pattern: &Regex, since_secs: u64) -> Result<Vec<String>> {
+    let mut file = File::open(AUDIT_LOG)?;
+    let len = file.metadata()?.len();
+    if len > AUDIT_LOG_TAIL {
+        file.seek(SeekFrom::Start(len - AUDIT_LOG_TAIL))?;
+    }
+
+    // Le righe riportano l'istante dell'evento come msg=audit(1700000000.123:456)
+    let timestamp = Regex::new(r"msg=audit\((\d+)\.").expect("valid audit timestamp pattern");
+    let denials = BufReader::new(file).lines()
+        .map_while(|line| line.ok())
+        .filter(|line| pattern.is_match(line))
+        .filter(|line| timestamp.captures(line)
+            .and_then(|c| c[1].parse::<u64>().ok())
+            .is_some_and(|secs| secs >= since_secs))
+        .collect();
+    Ok(denials)
+}
+
+/// Cerca i dinieghi nel log del kernel
+fn journal_denials(pattern: &Regex, since_secs: u64) -> Vec<String> {
+    if !executor::is_command_available("journalctl") {
+        return Vec::new();
+    }
+
+    let output = Command::new("journalctl")
+        .args(["-k", "-q", "--no-pager", "-o", "cat", "--since", &format!("@{}", since_secs)])
+        .output();
+    match output {
+        Ok(output) => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| pattern.is_match(line))
+            .map(str::to_string)
+            .collect(),
+        Err(e) => {
+            debug!("Failed to read the kernel log: {}", e);
+            Vec::new()
+        },
+    }
+}
+
+/// Arricchisce l'errore di uno script fallito con i dinieghi di SELinux o AppArmor
+///
+/// Se il modulo di sicurezza è in enforcing e durante l'esecuzione sono stati registrati
+/// dei dinieghi, l'errore riporta il modulo, le etichette coinvolte e i dinieghi.
+///
+/// # Arguments
+///
+/// * `error` - L'errore dello script
+/// * `script` - Lo script o il playbook eseguito
+/// * `since` - L'istante di avvio dell'esecuzione
+///
+/// # Returns
+///
+/// L'errore originale, o uno che include i dinieghi rilevati
+pub fn explain_failure(error: Error, script: &Path, since: SystemTime) -> Error {
+    let Some(status) = detect().filter(|status| status.enforcing) else {
+        return error;
+    };
+
+    let denials = recent_denials(status.system, since);
+    if denials.is_empty() {
+        debug!("{} is enforcing but no denials were logged for {:?}", status, script);
+        return error;
+    }
+
+    for denial in &denials {
+        warn!("{} denial while running {:?}: {}", status.system, script, denial);
+    }
+
+    let mut labels = Vec::new();
+    if let Some(label) = process_label() {
+        labels.push(format!("process {}", label));
+    }
+    if status.system == MacSystem::SELinux {
+        if let Some(label) = file_label(script) {
+            labels.push(format!("script {}", label));
+        }
+    }
+
+    let labels = if labels.is_empty() { String::new() } else { format!(" [{}]", labels.join(", ")) };
+    anyhow!("{:#}; {} denied {} operation(s){}: {}", error, status, denials.len(), labels, denials.join(" | "))
+}
+
+/// Ripristina le etichette SELinux dei file nelle directory gestite
+///
+/// Viene eseguito dopo un'installazione riuscita se `restorecon_dirs` è configurato e
+/// SELinux è attivo; un errore viene solo segnalato, senza far fallire l'installazione.
+///
+/// # Arguments
+///
+/// * `config` - La configurazione con le directory gestite
+pub fn restore_labels(config: &Config) {
+    if config.restorecon_dirs.is_empty() {
+        return;
+    }
+    if !detect().is_some_and(|status| status.system == MacSystem::SELinux) {
+        return;
+    }
+    if !executor::is_command_available("restorecon") {
+        warn!("restorecon not available: SELinux labels of {:?} are not restored", config.restorecon_dirs);
+        return;
+    }
+
+    let dirs: Vec<&String> = config.restorecon_dirs.iter()
+        .filter(|dir| Path::new(dir.as_str()).exists())
+        .collect();
+    if dirs.is_empty() {
+        return;
+    }
+
+    info!("Restoring SELinux labels in {:?}", dirs);
+    match Command::new("restorecon").arg("-R").args(&dirs).output() {
+        Ok(output) if output.status.success() => {},
+        Ok(output) => warn!("restorecon failed: {}", String::from_utf8_lossy(&output.stderr).trim()),
+        Err(e) => warn!("Failed to run restorecon: {}", e),
+    }
+}
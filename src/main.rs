@@ -1,19 +1,72 @@
+// Lo schema dei task nel catalogo supera la profondità predefinita della macro json!
+#![recursion_limit = "256"]
+
 use std::path::Path;
 use std::process;
-use std::fs;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use clap::{Arg, Command};
+use clap::{Arg, ArgAction, Command};
 use anyhow::{Result, Context, anyhow};
 
+mod approval;
+mod bootstrap;
+mod catalog;
+mod catalog_schema;
+mod changes;
+mod child_env;
+mod cli;
 mod config;
+mod config_migration;
+mod container;
+mod cron;
 mod downloader;
+mod drift;
+mod error;
+mod events;
+mod export;
+mod fleet;
+mod hooks;
 mod executor;
 mod stack;
 mod task;
 mod ui;
 mod utils;
+mod importer;
+mod inventory;
+mod jobs;
+mod layout;
+mod lint;
+mod locks;
 mod logger;
+mod mac;
+mod maintenance;
+mod mirror;
+mod notify;
+mod plan;
+mod plugins;
+mod preflight;
+mod pty;
+mod purge;
+mod reboot;
+mod redact;
+mod release;
+mod replay;
+mod resources;
+mod role;
+mod rollout;
+mod scaffold;
+mod scheduler;
+mod script;
+mod secrets;
+mod session;
+mod snapshot;
+mod source;
+mod state_crypt;
+mod sudo;
+mod systemd;
+mod wasm_runner;
+mod watchdog;
+mod workspace;
 
 use crate::config::{Config, create_example_config};
 use crate::ui::app::run_app;
@@ -43,19 +96,62 @@ fn main() -> Result<()> {
         .arg(Arg::new("no-root-check")
             .long("no-root-check")
             .help("Disabilita il controllo dei permessi di root"))
+        .arg(Arg::new("read-only")
+            .long("read-only")
+            .action(ArgAction::SetTrue)
+            .global(true)
+            .help("Modalità sola lettura: mostra stati, log e drift senza permettere modifiche"))
+        .arg(Arg::new("quiet")
+            .short('q')
+            .long("quiet")
+            .action(ArgAction::SetTrue)
+            .global(true)
+            .conflicts_with("verbose")
+            .help("Mostra solo gli errori"))
+        .arg(Arg::new("verbose")
+            .short('v')
+            .long("verbose")
+            .action(ArgAction::Count)
+            .global(true)
+            .help("Aumenta il dettaglio dell'output e dei log (-v, -vv)"))
+        .arg(Arg::new("simulate-failures")
+            .long("simulate-failures")
+            .value_name("PCT")
+            .value_parser(clap::value_parser!(u8).range(0..=100))
+            .global(true)
+            .hide(true)
+            .help("Fa fallire la percentuale indicata di script e controlli di salute, per provare i cataloghi"))
+        .subcommands(cli::subcommands())
+        // I sottocomandi non predefiniti vengono cercati tra i plugin galatea-<nome> nel PATH
+        .allow_external_subcommands(true)
         .get_matches();
 
     // Configura il logger il prima possibile
+    let default_log_dir = layout::Layout::current().log_dir().to_string_lossy().to_string();
     let log_dir = matches.get_one::<String>("log-dir")
         .map(|s| s.as_str())
-        .unwrap_or("/var/log/galatea");
+        .unwrap_or(&default_log_dir);
 
-    // Inizializza il logger
-    logger::init_file_logger(log_dir)?;
+    // Inizializza il logger con la verbosità richiesta
+    let verbosity = logger::Verbosity::from_flags(
+        matches.get_flag("quiet"),
+        matches.get_count("verbose"),
+    );
+    logger::init_file_logger(log_dir, verbosity)
+        .map_err(|e| match preflight::check_dir(Path::new(log_dir)) {
+            Some(problem) => e.context(format!("log_dir {}", problem)),
+            None => e,
+        })?;
     log::info!("Galatea è stata avviata");
 
+    // Senza interfaccia TUI i log possono essere riportati anche su stderr
+    logger::set_console_output(matches.subcommand_name().is_some() || matches.contains_id("create-example"));
+
+    // I sottocomandi che non modificano il sistema non richiedono i privilegi di root
+    let needs_root = matches.subcommand_name().is_none_or(cli::requires_root);
+
     // Verifica se l'applicazione è eseguita come root (a meno che --no-root-check sia specificato)
-    if !matches.contains_id("no-root-check") && !utils::is_running_as_root() {
+    if needs_root && !matches.contains_id("no-root-check") && !utils::is_running_as_root() {
         log::error!("Galatea deve essere eseguito con privilegi di root");
         eprintln!("Errore: Galatea deve essere eseguito con privilegi di root.");
         eprintln!("Riprova con 'sudo galatea'");
@@ -66,24 +162,14 @@ fn main() -> Result<()> {
     // Gestione dell'opzione per creare un file di configurazione di esempio
     if let Some(example_path) = matches.get_one::<String>("create-example") {
         log::info!("Tentativo di creare config di esempio in: {}", example_path);
-        println!("Tentativo di creare config in: {}", example_path);
-        
+
         let path = Path::new(example_path);
-        if let Some(parent) = path.parent() {
-            println!("Directory genitore: {:?}", parent);
-            println!("Esiste directory genitore: {}", parent.exists());
-            
-            // Tenta di creare manualmente la directory
-            match fs::create_dir_all(parent) {
-                Ok(_) => println!("Directory creata con successo"),
-                Err(e) => println!("Errore nella creazione directory: {}", e)
-            }
-        }
-        
         match create_example_config(path) {
             Ok(_) => {
                 log::info!("File di configurazione di esempio creato con successo in: {}", example_path);
-                println!("File di configurazione di esempio creato con successo in: {}", example_path);
+                if !verbosity.is_quiet() {
+                    println!("File di configurazione di esempio creato con successo in: {}", example_path);
+                }
                 process::exit(0);
             },
             Err(e) => {
@@ -94,9 +180,35 @@ fn main() -> Result<()> {
         }
     }
 
-    // Caricamento della configurazione
     let config_path = matches.get_one::<String>("config").map(|s| s.as_str());
-    let config = match Config::load(config_path) {
+    let read_only = matches.get_flag("read-only");
+
+    // Fallimenti simulati per provare tentativi e rollback dei cataloghi
+    if let Some(percent) = matches.get_one::<u8>("simulate-failures").copied().filter(|p| *p > 0) {
+        log::warn!("Simulating failures of {}% of scripts, playbooks and health checks", percent);
+        executor::set_simulated_failures(percent);
+    }
+
+    // Esecuzione di un sottocomando in modalità non interattiva
+    if let Some((name, sub_matches)) = matches.subcommand() {
+        log::info!("Esecuzione del sottocomando: {}", name);
+        match cli::run(name, sub_matches, config_path, read_only) {
+            Ok(code) => process::exit(code),
+            Err(e) => {
+                log::error!("Errore durante l'esecuzione del sottocomando {}: {}", name, e);
+                eprintln!("Errore durante l'esecuzione del sottocomando {}: {}", name, redact::mask(&e.to_string()));
+                // Uno script fallito restituisce il proprio codice di uscita
+                let code = error::classify(&e)
+                    .and_then(error::GalateaError::exit_code)
+                    .filter(|code| *code != 0)
+                    .unwrap_or(1);
+                process::exit(code);
+            }
+        }
+    }
+
+    // Caricamento della configurazione
+    let mut config = match Config::load(config_path) {
         Ok(config) => {
             log::info!("Configurazione caricata con successo");
             config
@@ -109,6 +221,10 @@ fn main() -> Result<()> {
         }
     };
 
+    if read_only {
+        config.read_only = true;
+    }
+
     // Avvio dell'applicazione
     log::info!("Avvio dell'interfaccia utente");
     match run_app(config) {
@@ -118,7 +234,7 @@ fn main() -> Result<()> {
         },
         Err(e) => {
             log::error!("Errore durante l'esecuzione dell'applicazione: {}", e);
-            eprintln!("Errore durante l'esecuzione dell'applicazione: {}", e);
+            eprintln!("Errore durante l'esecuzione dell'applicazione: {}", redact::mask(&e.to_string()));
             process::exit(1);
         }
     }
@@ -135,6 +251,10 @@ fn setup_signal_handlers() -> Result<()> {
         let r = running.clone();
         
         flag::register(SIGINT, r).map_err(|e| anyhow!("Failed to register signal handler: {}", e))?;
+
+        // Script e playbook girano in una sessione propria: il Ctrl-C li raggiunge tramite l'executor
+        flag::register(SIGINT, executor::interrupt_flag())
+            .map_err(|e| anyhow!("Failed to register signal handler: {}", e))?;
         
         // For custom handler behavior, use signal_hook::iterator
         std::thread::spawn(move || {
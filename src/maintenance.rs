@@ -0,0 +1,202 @@
+//! Modalità di manutenzione con i bilanciatori di carico
+//!
+//! Uno stack con `maintenance: true` viene installato con la macchina fuori dal
+//! bilanciatore di carico. La sezione `maintenance` della configurazione indica come
+//! toglierla (`drain`) e come rimetterla in servizio (`restore`), con una chiamata HTTP
+//! o con un comando:
+//!
+//! ```yaml
+//! maintenance:
+//!   drain:
+//!     url: https://lb.example.com/api/pools/web/members/{{ host }}/drain
+//!     method: POST          # predefinito
+//!     token: "..."          # inviato come Bearer token, anche cifrato
+//!   restore:
+//!     command: "haproxyctl enable server web/{{ host }}"
+//!   settle: 30              # attesa dopo drain per chiudere le connessioni, in secondi
+//!   healthcheck: "curl -fsS http://localhost/health"
+//! ```
+//!
+//! Nelle azioni `{{ host }}` e `{{ stack }}` vengono sostituiti con il nome host e lo stack.
+//! La macchina torna in servizio solo se i task, i loro controlli di salute, i comandi
+//! `post_install` e l'eventuale `healthcheck` sono riusciti: altrimenti resta fuori dal
+//! bilanciatore finché un'installazione successiva non riesce.
+
+use std::collections::HashMap;
+use std::thread;
+use std::time::Duration;
+use anyhow::{Context, Result, anyhow};
+use log::{Level, error, info, warn};
+use reqwest::Method;
+use reqwest::blocking::Client;
+use serde::{Serialize, Deserialize};
+
+use crate::config::Config;
+use crate::events;
+use crate::executor;
+use crate::redact;
+use crate::secrets;
+use crate::stack::expand_variables;
+use crate::utils;
+
+/// Configurazione della modalità di manutenzione
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceConfig {
+    /// Azione che toglie la macchina dal bilanciatore
+    pub drain: MaintenanceAction,
+
+    /// Azione che rimette la macchina nel bilanciatore
+    pub restore: MaintenanceAction,
+
+    /// Attesa dopo `drain`, per lasciar terminare le connessioni in corso, in secondi
+    #[serde(default)]
+    pub settle: u64,
+
+    /// Comando che deve riuscire prima di rimettere la macchina in servizio
+    #[serde(default)]
+    pub healthcheck: Option<String>,
+}
+
+/// Azione verso il bilanciatore: una chiamata HTTP (`url`) o un comando (`command`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaintenanceAction {
+    /// Comando shell da eseguire
+    pub command: Option<String>,
+
+    /// URL da chiamare
+    pub url: Option<String>,
+
+    /// Metodo HTTP della chiamata (predefinito POST)
+    pub method: Option<String>,
+
+    /// Token inviato nell'intestazione `Authorization: Bearer`
+    pub token: Option<String>,
+
+    /// Corpo JSON della chiamata
+    pub body: Option<String>,
+
+    /// Durata massima dell'azione in secondi (predefinita 60)
+    pub timeout: Option<u64>,
+}
+
+/// Durata massima predefinita di un'azione e del controllo di salute, in secondi
+const DEFAULT_TIMEOUT: u64 = 60;
+
+/// Toglie la macchina dal bilanciatore prima di installare uno stack
+///
+/// Senza la sezione `maintenance` nella configurazione lo stack viene installato
+/// comunque, con un avviso.
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+/// * `stack` - Il nome dello stack
+pub fn drain(config: &Config, stack: &str) -> Result<()> {
+    let Some(maintenance) = &config.maintenance else {
+        warn!("Stack {} requires maintenance mode, but no maintenance section is configured", stack);
+        return Ok(());
+    };
+
+    info!("Draining {} from the load balancer before installing stack {}", utils::get_hostname(), stack);
+    events::log_line(Level::Info, format!("{}: macchina tolta dal bilanciatore", stack));
+    run_action(config, &maintenance.drain, "drain", stack)?;
+
+    if maintenance.settle > 0 {
+        info!("Waiting {} seconds for connections to drain", maintenance.settle);
+        thread::sleep(Duration::from_secs(maintenance.settle));
+    }
+    Ok(())
+}
+
+/// Rimette la macchina nel bilanciatore dopo l'installazione di uno stack, se è riuscita
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+/// * `stack` - Il nome dello stack
+/// * `outcome` - L'esito dell'installazione
+///
+/// # Returns
+///
+/// L'esito dell'installazione, oppure l'errore del controllo di salute o del ripristino
+pub fn restore(config: &Config, stack: &str, outcome: Result<()>) -> Result<()> {
+    let Some(maintenance) = &config.maintenance else {
+        return outcome;
+    };
+
+    if let Err(e) = outcome {
+        error!("Installation of stack {} failed, {} stays out of the load balancer", stack, utils::get_hostname());
+        events::log_line(Level::Error, format!("{}: installazione fallita, la macchina resta fuori dal bilanciatore", stack));
+        return Err(e);
+    }
+
+    if let Some(check) = &maintenance.healthcheck {
+        info!("Running maintenance health check before restoring {}: {}", utils::get_hostname(), check);
+        executor::run_command_with_timeout(check, DEFAULT_TIMEOUT, &config.child_env)
+            .context(format!("Health check failed after installing stack {}, the host stays out of the load balancer", stack))?;
+    }
+
+    info!("Restoring {} in the load balancer after installing stack {}", utils::get_hostname(), stack);
+    run_action(config, &maintenance.restore, "restore", stack)?;
+    events::log_line(Level::Info, format!("{}: macchina rimessa nel bilanciatore", stack));
+    Ok(())
+}
+
+/// Esegue un'azione verso il bilanciatore
+fn run_action(config: &Config, action: &MaintenanceAction, kind: &str, stack: &str) -> Result<()> {
+    let values = HashMap::from([
+        ("host".to_string(), utils::get_hostname()),
+        ("stack".to_string(), stack.to_string()),
+    ]);
+    let timeout = action.timeout.unwrap_or(DEFAULT_TIMEOUT);
+
+    match (&action.command, &action.url) {
+        (Some(command), None) => {
+            executor::run_command_with_timeout(&expand_variables(command, &values), timeout, &config.child_env)
+                .context(format!("Maintenance {} command failed for stack {}", kind, stack))
+        },
+        (None, Some(url)) => {
+            let url = expand_variables(url, &values);
+            let body = action.body.as_deref().map(|body| expand_variables(body, &values));
+            call(config, action, &url, body, timeout)
+                .context(format!("Maintenance {} call failed for stack {}", kind, stack))
+        },
+        _ => Err(anyhow!("Maintenance {} action must have exactly one of 'command' and 'url'", kind)),
+    }
+}
+
+/// Esegue la chiamata HTTP di un'azione
+fn call(config: &Config, action: &MaintenanceAction, url: &str, body: Option<String>, timeout: u64) -> Result<()> {
+    let token = match &action.token {
+        Some(token) => Some(secrets::reveal(config, token).context("Failed to decrypt maintenance token")?),
+        None => None,
+    };
+    if let Some(token) = &token {
+        redact::register(token);
+    }
+
+    let method = action.method.as_deref().unwrap_or("POST").to_uppercase();
+    let method = Method::from_bytes(method.as_bytes())
+        .map_err(|_| anyhow!("Invalid HTTP method: {}", method))?;
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(timeout))
+        .build()
+        .context("Failed to create HTTP client")?;
+
+    let mut request = client.request(method, url);
+    if let Some(body) = body {
+        request = request.header("Content-Type", "application/json").body(body);
+    }
+    if let Some(token) = &token {
+        request = request.bearer_auth(token);
+    }
+
+    let response = request.send()
+        .context(format!("Failed to contact load balancer endpoint {}", url))?;
+    if !response.status().is_success() {
+        return Err(anyhow!("Load balancer endpoint {} returned HTTP {}", url, response.status()));
+    }
+    Ok(())
+}
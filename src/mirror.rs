@@ -0,0 +1,333 @@
+//! Mirror interni dei cataloghi
+//!
+//! `galatea mirror` scarica i cataloghi delle sorgenti configurate e tutti i bundle dei
+//! task (comprese le versioni dell'indice `versions`) in una directory che può essere
+//! servita così com'è da un server web interno:
+//!
+//! ```text
+//! <dest>/
+//!   tasks/<file>.conf                        # catalogo di task scaricato da un .conf
+//!   tasks/<etichetta>.tar.gz                 # catalogo di task scaricato da un archivio
+//!   stacks/...                               # cataloghi di stack, come i task
+//!   bundles/<etichetta>/<task>/<versione>/   # bundle dei task
+//! ```
+//!
+//! Con `--base-url` gli URL dei bundle nei cataloghi di task vengono riscritti verso il
+//! mirror, che diventa una sorgente autonoma: le macchine senza accesso a Internet usano
+//! `<base-url>/tasks/...` e `<base-url>/stacks/...` come `task_sources` e `stack_sources`.
+//! I checksum dichiarati vengono verificati durante il download e restano nel catalogo.
+//! I bundle già presenti con il checksum dichiarato non vengono scaricati di nuovo.
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result, anyhow};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use log::{info, warn};
+use serde_yaml::Value;
+
+use crate::config::Config;
+use crate::downloader;
+use crate::locks;
+use crate::release;
+use crate::secrets;
+use crate::source::{self, Source};
+use crate::utils;
+
+/// Directory dei bundle nel mirror
+const BUNDLES_DIR: &str = "bundles";
+
+/// Sottodirectory dei bundle senza versione dichiarata
+const UNVERSIONED_DIR: &str = "current";
+
+/// Opzioni del mirror
+#[derive(Debug, Clone)]
+pub struct MirrorOptions {
+    /// Directory del mirror
+    pub dest: PathBuf,
+
+    /// URL da cui il mirror sarà servito, per riscrivere gli URL dei bundle
+    pub base_url: Option<String>,
+}
+
+/// Catalogo scritto nel mirror
+#[derive(Debug, Clone)]
+pub struct MirroredCatalog {
+    /// Percorso relativo alla directory del mirror, es. `tasks/base.conf`
+    pub path: String,
+
+    /// Checksum SHA-256 del file scritto, da indicare nella sorgente che lo usa
+    pub sha256: String,
+}
+
+/// Risultato della sincronizzazione del mirror
+#[derive(Debug, Clone, Default)]
+pub struct MirrorReport {
+    /// Cataloghi scritti
+    pub catalogs: Vec<MirroredCatalog>,
+
+    /// Bundle scaricati
+    pub downloaded: usize,
+
+    /// Bundle già presenti e invariati
+    pub unchanged: usize,
+
+    /// Bundle non scaricati, con il motivo
+    pub failures: Vec<(String, String)>,
+
+    /// Avvisi sui cataloghi
+    pub warnings: Vec<String>,
+}
+
+/// Sincronizza il mirror con le sorgenti configurate
+///
+/// # Arguments
+///
+/// * `config` - La configurazione con le sorgenti
+/// * `options` - Le opzioni del mirror
+///
+/// # Returns
+///
+/// Il report della sincronizzazione
+pub fn sync(config: &Config, options: &MirrorOptions) -> Result<MirrorReport> {
+    if config.task_sources.is_empty() && config.stack_sources.is_empty() {
+        return Err(anyhow!("No task or stack sources configured: nothing to mirror"));
+    }
+
+    info!("Synchronizing mirror in {:?}", options.dest);
+    fs::create_dir_all(&options.dest)
+        .context(format!("Failed to create mirror directory: {:?}", options.dest))?;
+
+    let staging = utils::get_temp_dir().join(format!("galatea-mirror-{}", std::process::id()));
+    let mut report = MirrorReport::default();
+    let result = sync_sources(config, options, &staging, &mut report);
+
+    match fs::remove_dir_all(&staging) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            warn!("Failed to remove mirror staging directory {:?}: {}", staging, e);
+        },
+        _ => {},
+    }
+    result?;
+
+    info!("Mirror synchronized: {} catalogs, {} bundles downloaded, {} unchanged, {} failed",
+          report.catalogs.len(), report.downloaded, report.unchanged, report.failures.len());
+    Ok(report)
+}
+
+/// Scarica i cataloghi di tutte le sorgenti e i bundle dei task
+fn sync_sources(config: &Config, options: &MirrorOptions, staging: &Path, report: &mut MirrorReport) -> Result<()> {
+    for (kind, sources, base_dir) in [
+        ("tasks", &config.task_sources, &config.tasks_dir),
+        ("stacks", &config.stack_sources, &config.stacks_dir),
+    ] {
+        for source in sources {
+            let label = source.label();
+            let catalog_dir = staging.join(kind).join(&label);
+            fetch_catalog(config, source, Path::new(base_dir), &catalog_dir)
+                .context(format!("Failed to download {} catalog {}", kind, source))?;
+
+            if kind == "tasks" {
+                mirror_bundles(config, options, &label, &catalog_dir, report)?;
+            }
+
+            let path = write_catalog(source, &catalog_dir, &options.dest.join(kind))?;
+            let relative = path.strip_prefix(&options.dest).unwrap_or(&path).to_string_lossy().to_string();
+            info!("Mirrored {} catalog {} to {}", kind, source, relative);
+            report.catalogs.push(MirroredCatalog { sha256: source::sha256_file(&path)?, path: relative });
+        }
+    }
+    Ok(())
+}
+
+/// Copia nella directory indicata i file del catalogo di una sorgente, verificati
+fn fetch_catalog(config: &Config, source: &Source, base_dir: &Path, catalog_dir: &Path) -> Result<()> {
+    source.validate_layout()?;
+
+    // Un catalogo in sola lettura è già presente nella sua directory
+    if source.read_only {
+        fs::create_dir_all(catalog_dir)
+            .context(format!("Failed to create directory: {:?}", catalog_dir))?;
+        for path in catalog_files(&source.catalog_dir(base_dir))? {
+            fs::copy(&path, catalog_dir.join(path.file_name().unwrap_or_default()))
+                .context(format!("Failed to copy catalog file {:?}", path))?;
+        }
+        return Ok(());
+    }
+
+    downloader::download_and_extract_source(
+        &release::with_token(config, source)?,
+        catalog_dir,
+        config.download_timeout,
+        config.download_cache_dir().as_deref(),
+        &config.download_hosts,
+    )?;
+    Ok(())
+}
+
+/// Restituisce i file `.conf` di una directory del catalogo
+fn catalog_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .context(format!("Failed to read catalog directory: {:?}", dir))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "conf"))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Scarica i bundle dei task di un catalogo, riscrivendone gli URL se richiesto
+fn mirror_bundles(config: &Config, options: &MirrorOptions, label: &str, catalog_dir: &Path, report: &mut MirrorReport) -> Result<()> {
+    for path in catalog_files(catalog_dir)? {
+        let content = fs::read_to_string(&path)
+            .context(format!("Failed to read catalog file {:?}", path))?;
+        let mut document: Value = serde_yaml::from_str(&content)
+            .context(format!("Failed to parse catalog file {:?}", path))?;
+
+        // Un file SOPS non può essere riscritto senza invalidarne la cifratura
+        if secrets::is_sops_document(&document) {
+            report.warnings.push(format!("{}: encrypted with SOPS, bundles not mirrored", path.display()));
+            continue;
+        }
+
+        let Some(tasks) = document.get_mut("tasks").and_then(Value::as_sequence_mut) else {
+            continue;
+        };
+
+        let mut rewritten = false;
+        for task in tasks.iter_mut() {
+            let name = task.get("name").and_then(Value::as_str).unwrap_or_default().to_string();
+            if name.is_empty() {
+                continue;
+            }
+
+            rewritten |= mirror_bundle(config, options, label, &name, task, report);
+            if let Some(versions) = task.get_mut("versions").and_then(Value::as_sequence_mut) {
+                for version in versions.iter_mut() {
+                    rewritten |= mirror_bundle(config, options, label, &name, version, report);
+                }
+            }
+        }
+
+        if rewritten {
+            let yaml = serde_yaml::to_string(&document)
+                .context(format!("Failed to serialize catalog file {:?}", path))?;
+            fs::write(&path, yaml)
+                .context(format!("Failed to write catalog file {:?}", path))?;
+        }
+    }
+    Ok(())
+}
+
+/// Scarica il bundle indicato da `url` in un task o in una sua versione
+///
+/// Etichetta, nome del task, versione e nome del file vengono da cataloghi remoti e
+/// diventano componenti del percorso nel mirror: quelli non validi come nomi di file (es.
+/// `..`) vengono rifiutati come errori del bundle.
+///
+/// # Returns
+///
+/// `true` se l'URL è stato riscritto verso il mirror
+fn mirror_bundle(config: &Config, options: &MirrorOptions, label: &str, task: &str, entry: &mut Value, report: &mut MirrorReport) -> bool {
+    let Some(url) = entry.get("url").and_then(Value::as_str).map(str::to_string) else {
+        return false;
+    };
+    let version = match entry.get("version") {
+        Some(Value::String(version)) => version.clone(),
+        Some(Value::Number(version)) => version.to_string(),
+        _ => UNVERSIONED_DIR.to_string(),
+    };
+    let sha256 = entry.get("sha256").and_then(Value::as_str).map(str::to_string);
+
+    let relative = Path::new(BUNDLES_DIR).join(label).join(task).join(&version);
+    let outcome = [label, task, version.as_str()].into_iter()
+        .try_for_each(locks::validate_name)
+        .context(format!("Invalid bundle directory {:?}", relative))
+        .and_then(|_| fetch_bundle(config, &url, sha256.as_deref(), &options.dest.join(&relative)));
+    let file = match outcome {
+        Ok((file, downloaded)) => {
+            if downloaded {
+                report.downloaded += 1;
+            } else {
+                report.unchanged += 1;
+            }
+            file
+        },
+        Err(e) => {
+            warn!("Failed to mirror bundle of task {} from {}: {:#}", task, url, e);
+            report.failures.push((format!("{} ({})", task, url), format!("{:#}", e)));
+            return false;
+        },
+    };
+
+    let Some(base_url) = &options.base_url else {
+        return false;
+    };
+    let mirrored = format!("{}/{}/{}", base_url.trim_end_matches('/'), relative.to_string_lossy(), file);
+    if let Some(fields) = entry.as_mapping_mut() {
+        fields.insert(Value::from("url"), Value::from(mirrored));
+    }
+    true
+}
+
+/// Scarica un bundle nella sua directory del mirror, se non è già presente e invariato
+///
+/// # Returns
+///
+/// Il nome del file e se è stato scaricato
+fn fetch_bundle(config: &Config, url: &str, sha256: Option<&str>, dir: &Path) -> Result<(String, bool)> {
+    let file = url.split('/').next_back()
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| anyhow!("Invalid bundle URL: {}", url))?
+        .to_string();
+    locks::validate_name(&file)?;
+
+    let mut source = Source::new(url);
+    source.sha256 = sha256.map(str::to_string);
+
+    // Senza checksum dichiarato il bundle viene sempre scaricato di nuovo
+    let existing = dir.join(&file);
+    if source.sha256.is_some() && existing.is_file()
+        && source.verify_file(&existing, config.download_timeout, &config.download_hosts).is_ok() {
+        return Ok((file, false));
+    }
+
+    let token = release::token_for(config, url)?;
+    let downloaded = downloader::download_file_pinned(url, dir, config.download_timeout, None, token.as_deref(), &config.download_hosts)?;
+    if let Err(e) = source.verify_file(&downloaded, config.download_timeout, &config.download_hosts) {
+        let _ = fs::remove_file(&downloaded);
+        return Err(e);
+    }
+    Ok((file, true))
+}
+
+/// Scrive il catalogo di una sorgente nella directory del mirror
+///
+/// Un catalogo scaricato come singolo `.conf` resta un `.conf`; gli altri diventano un
+/// archivio `<etichetta>.tar.gz` con i file del catalogo.
+fn write_catalog(source: &Source, catalog_dir: &Path, dest_dir: &Path) -> Result<PathBuf> {
+    fs::create_dir_all(dest_dir)
+        .context(format!("Failed to create directory: {:?}", dest_dir))?;
+
+    let files = catalog_files(catalog_dir)?;
+    let single_conf = source.file_name().filter(|name| name.ends_with(".conf"));
+    if let (Some(name), [file]) = (single_conf, files.as_slice()) {
+        let path = dest_dir.join(name);
+        fs::copy(file, &path)
+            .context(format!("Failed to write catalog {:?}", path))?;
+        return Ok(path);
+    }
+
+    let path = dest_dir.join(format!("{}.tar.gz", source.label()));
+    let file = File::create(&path)
+        .context(format!("Failed to create catalog archive {:?}", path))?;
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    for file in &files {
+        builder.append_path_with_name(file, file.file_name().unwrap_or_default())
+            .context(format!("Failed to write catalog archive {:?}", path))?;
+    }
+    builder.into_inner()
+        .and_then(|encoder| encoder.finish())
+        .context(format!("Failed to write catalog archive {:?}", path))?;
+    Ok(path)
+}
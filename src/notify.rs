@@ -0,0 +1,142 @@
+//! Notifiche via email
+//!
+//! Questo modulo invia per email il riepilogo delle esecuzioni pianificate tramite
+//! un server SMTP configurato nella sezione `smtp` della configurazione:
+//!
+//! ```yaml
+//! smtp:
+//!   host: smtp.example.com
+//!   port: 587
+//!   security: starttls
+//!   username: galatea@example.com
+//!   password: secret
+//!   from: "Galatea <galatea@example.com>"
+//!   to: [ops@example.com]
+//!   only_on_failure: false
+//!   only_on_change: true
+//! ```
+
+use std::time::Duration;
+use anyhow::{Context, Result, anyhow};
+use lettre::message::{Mailbox, header::ContentType};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use log::info;
+use serde::{Serialize, Deserialize};
+
+/// Sicurezza della connessione al server SMTP
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// Connessione in chiaro aggiornata a TLS con STARTTLS
+    #[default]
+    Starttls,
+    /// Connessione TLS diretta (tipicamente porta 465)
+    Tls,
+    /// Connessione in chiaro, solo per relay locali
+    None,
+}
+
+/// Impostazioni del server SMTP per le notifiche
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmtpConfig {
+    /// Nome host del server SMTP
+    pub host: String,
+
+    /// Porta del server SMTP
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
+
+    /// Sicurezza della connessione (starttls, tls, none)
+    #[serde(default)]
+    pub security: SmtpSecurity,
+
+    /// Utente per l'autenticazione, se richiesta
+    #[serde(default)]
+    pub username: Option<String>,
+
+    /// Password per l'autenticazione
+    #[serde(default)]
+    pub password: Option<String>,
+
+    /// Mittente dei messaggi
+    pub from: String,
+
+    /// Destinatari dei messaggi
+    pub to: Vec<String>,
+
+    /// Invia il riepilogo solo se l'esecuzione ha rilevato errori o derive
+    #[serde(default)]
+    pub only_on_failure: bool,
+
+    /// Invia il riepilogo solo se lo stato di un ruolo, stack o task è cambiato rispetto
+    /// all'esecuzione precedente; ha la precedenza su `only_on_failure`
+    #[serde(default = "default_only_on_change")]
+    pub only_on_change: bool,
+}
+
+/// Le notifiche si limitano per impostazione predefinita ai cambiamenti di stato
+fn default_only_on_change() -> bool {
+    true
+}
+
+/// Porta SMTP predefinita (submission)
+fn default_smtp_port() -> u16 {
+    587
+}
+
+/// Timeout della connessione al server SMTP
+const SMTP_TIMEOUT_SECS: u64 = 30;
+
+/// Invia un messaggio di testo ai destinatari configurati
+///
+/// # Arguments
+///
+/// * `smtp` - Le impostazioni del server SMTP
+/// * `subject` - L'oggetto del messaggio
+/// * `body` - Il testo del messaggio
+pub fn send_email(smtp: &SmtpConfig, subject: &str, body: &str) -> Result<()> {
+    if smtp.to.is_empty() {
+        return Err(anyhow!("No recipients configured for email notifications"));
+    }
+
+    let from: Mailbox = smtp.from.parse()
+        .context(format!("Invalid sender address: {}", smtp.from))?;
+
+    let mut builder = Message::builder()
+        .from(from)
+        .subject(subject)
+        .header(ContentType::TEXT_PLAIN);
+    for recipient in &smtp.to {
+        let mailbox: Mailbox = recipient.parse()
+            .context(format!("Invalid recipient address: {}", recipient))?;
+        builder = builder.to(mailbox);
+    }
+
+    let message = builder.body(body.to_string())
+        .context("Failed to build email message")?;
+
+    let transport = match smtp.security {
+        SmtpSecurity::Starttls => SmtpTransport::starttls_relay(&smtp.host)
+            .context(format!("Failed to configure SMTP relay {}", smtp.host))?,
+        SmtpSecurity::Tls => SmtpTransport::relay(&smtp.host)
+            .context(format!("Failed to configure SMTP relay {}", smtp.host))?,
+        SmtpSecurity::None => SmtpTransport::builder_dangerous(&smtp.host),
+    };
+
+    let mut transport = transport
+        .port(smtp.port)
+        .timeout(Some(Duration::from_secs(SMTP_TIMEOUT_SECS)));
+
+    if let Some(username) = &smtp.username {
+        let password = smtp.password.clone().unwrap_or_default();
+        transport = transport.credentials(Credentials::new(username.clone(), password));
+    }
+
+    transport.build()
+        .send(&message)
+        .context(format!("Failed to send email through {}:{}", smtp.host, smtp.port))?;
+
+    info!("Email notification sent to {}", smtp.to.join(", "));
+    Ok(())
+}
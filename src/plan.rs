@@ -0,0 +1,400 @@
+//! Piani di installazione degli stack
+//!
+//! Un piano separa la decisione dall'esecuzione: `galatea plan --stack web -o plan.json`
+//! calcola quali task dello stack verranno installati, in che ordine e in quale versione,
+//! e lo salva in un file che può essere rivisto e approvato. `galatea apply plan.json`
+//! esegue poi esattamente quel piano, rifiutandosi di procedere se il catalogo o la
+//! macchina non sono più quelli per cui il piano è stato calcolato.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use anyhow::{Context, Result, anyhow};
+use chrono::Local;
+use log::{info, warn, error};
+use serde::{Serialize, Deserialize};
+
+use crate::config::Config;
+use crate::events::{self, Operation};
+use crate::hooks;
+use crate::snapshot;
+use crate::source;
+use crate::stack::{Stack, load_stacks};
+use crate::task::{Task, load_tasks};
+use crate::utils;
+
+/// Versione del formato dei file di piano
+pub const PLAN_FORMAT: u32 = 1;
+
+/// Azione prevista per un task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlanAction {
+    /// Il task verrà installato
+    Install,
+
+    /// Il task è già installato e non verrà toccato
+    Skip,
+
+    /// La condizione `when` del task non è soddisfatta su questa macchina
+    Excluded,
+}
+
+/// Passo del piano, uno per task dello stack
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanStep {
+    /// Riferimento qualificato del task (`sorgente:nome`)
+    pub task: String,
+
+    /// Azione prevista
+    pub action: PlanAction,
+
+    /// Versione scelta secondo pins e canale dello stack
+    pub version: Option<String>,
+
+    /// URL del bundle che verrà installato
+    pub url: String,
+
+    /// Indica se il task è un canary dello stack
+    #[serde(default)]
+    pub canary: bool,
+
+    /// Impronta della definizione del task al momento del piano
+    pub digest: String,
+}
+
+/// Piano di installazione di uno stack
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Plan {
+    /// Versione del formato del file
+    pub format: u32,
+
+    /// Momento della creazione del piano (RFC 3339)
+    pub created_at: String,
+
+    /// Macchina per cui è stato calcolato il piano
+    pub host: String,
+
+    /// Riferimento qualificato dello stack
+    pub stack: String,
+
+    /// Impronta della definizione dello stack al momento del piano
+    pub stack_digest: String,
+
+    /// Variabili dello stack con un valore (i valori non vengono salvati nel piano)
+    #[serde(default)]
+    pub variables: Vec<String>,
+
+    /// Passi del piano, nell'ordine di esecuzione
+    pub steps: Vec<PlanStep>,
+}
+
+impl Plan {
+    /// Restituisce il numero di task da installare
+    pub fn install_count(&self) -> usize {
+        self.steps.iter().filter(|s| s.action == PlanAction::Install).count()
+    }
+
+    /// Carica un piano da file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .context(format!("Failed to read plan file {:?}", path))?;
+        let plan: Plan = serde_json::from_str(&content)
+            .context(format!("Invalid plan file {:?}", path))?;
+
+        if plan.format != PLAN_FORMAT {
+            return Err(anyhow!("Unsupported plan format {} in {:?} (expected {})", plan.format, path, PLAN_FORMAT));
+        }
+
+        Ok(plan)
+    }
+
+    /// Salva il piano in un file
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_json()?)
+            .context(format!("Failed to write plan file {:?}", path))
+    }
+
+    /// Serializza il piano in JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize plan")
+    }
+
+    /// Restituisce una descrizione leggibile del piano
+    pub fn to_text(&self) -> String {
+        let mut text = format!("Piano per lo stack {} su {} ({})\n", self.stack, self.host, self.created_at);
+
+        for (i, step) in self.steps.iter().enumerate() {
+            let action = match step.action {
+                PlanAction::Install => "installa",
+                PlanAction::Skip => "salta (già installato)",
+                PlanAction::Excluded => "salta (condizione non soddisfatta)",
+            };
+            let version = step.version.as_deref().map(|v| format!(" {}", v)).unwrap_or_default();
+            let canary = if step.canary { " [canary]" } else { "" };
+            text.push_str(&format!("  {:>2}. {}{}{}: {}\n", i + 1, step.task, version, canary, action));
+        }
+
+        if !self.variables.is_empty() {
+            text.push_str(&format!("Variabili: {}\n", self.variables.join(", ")));
+        }
+
+        let excluded = self.steps.iter().filter(|s| s.action == PlanAction::Excluded).count();
+        text.push_str(&format!("{} task da installare, {} già installati, {} esclusi\n",
+                               self.install_count(), self.steps.len() - self.install_count() - excluded, excluded));
+        text
+    }
+}
+
+/// Esito dell'applicazione di un piano
+#[derive(Debug, Clone, Default)]
+pub struct PlanReport {
+    /// Task installati
+    pub installed: Vec<String>,
+
+    /// Task saltati perché già installati
+    pub skipped: Vec<String>,
+
+    /// Task saltati perché la loro condizione `when` non è soddisfatta
+    pub excluded: Vec<String>,
+
+    /// Task la cui installazione è fallita, con il relativo errore
+    pub failed: Vec<(String, String)>,
+
+    /// Canary fallito che ha interrotto il piano
+    pub aborted_by: Option<String>,
+}
+
+impl PlanReport {
+    /// Verifica se il piano è stato applicato per intero
+    pub fn is_ok(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+/// Calcola il piano di installazione di uno stack
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+/// * `stack_ref` - Il riferimento dello stack (`nome` o `sorgente:nome`)
+///
+/// # Returns
+///
+/// Il piano, con un passo per ogni task dello stack nell'ordine di installazione
+pub fn create_plan(config: &Config, stack_ref: &str) -> Result<Plan> {
+    let mut tasks = load_tasks(config)?;
+    let stacks = load_stacks(config, &tasks)?;
+    let stack = find_stack(&stacks, stack_ref)?;
+    stack.check_denied_tasks(config, &tasks)?;
+
+    let values = stack.variable_values(config);
+    let mut steps = Vec::new();
+
+    for task_ref in stack.install_order() {
+        let task = tasks.iter_mut()
+            .find(|t| t.matches_ref(&task_ref))
+            .ok_or_else(|| anyhow!("Task {} of stack {} not found", task_ref, stack.name))?;
+
+        stack.select_task_version(&task_ref, task)?;
+        let version = task.effective_version().map(String::from);
+        let url = task.pinned.as_ref().map(|p| p.url.clone()).unwrap_or_else(|| task.url.clone());
+        task.pinned = None;
+
+        task.variables = stack.task_variables_for(&task_ref, &values);
+        let condition_met = task.condition_met(config);
+        task.variables.clear();
+
+        let action = if task.installed {
+            PlanAction::Skip
+        } else if !condition_met? {
+            PlanAction::Excluded
+        } else {
+            PlanAction::Install
+        };
+
+        steps.push(PlanStep {
+            task: task.qualified_name(),
+            action,
+            version,
+            url,
+            canary: stack.is_canary(&task_ref),
+            digest: digest(task)?,
+        });
+    }
+
+    let mut variables: Vec<String> = values.into_keys().collect();
+    variables.sort();
+
+    info!("Created plan for stack {}: {} steps", stack.name, steps.len());
+
+    Ok(Plan {
+        format: PLAN_FORMAT,
+        created_at: Local::now().to_rfc3339(),
+        host: utils::get_hostname(),
+        stack: stack.qualified_name(),
+        stack_digest: digest(stack)?,
+        variables,
+        steps,
+    })
+}
+
+/// Esegue un piano approvato
+///
+/// Prima di installare qualsiasi cosa verifica che il piano sia stato calcolato per
+/// questa macchina e che le definizioni dello stack e dei task non siano cambiate.
+/// I task del piano vengono installati nell'ordine e nella versione previsti; un
+/// canary fallito interrompe il piano.
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+/// * `plan` - Il piano da eseguire
+pub fn apply_plan(config: &Config, plan: &Plan) -> Result<PlanReport> {
+    let host = utils::get_hostname();
+    if plan.host != host {
+        return Err(anyhow!("Plan was created for host {}, not {}", plan.host, host));
+    }
+
+    let mut tasks = load_tasks(config)?;
+    let stacks = load_stacks(config, &tasks)?;
+    let stack = find_stack(&stacks, &plan.stack)?.clone();
+
+    // Il piano vale solo per il catalogo su cui è stato calcolato
+    if digest(&stack)? != plan.stack_digest {
+        return Err(anyhow!("Stack {} changed since the plan was created: create a new plan", plan.stack));
+    }
+    for step in &plan.steps {
+        let task = tasks.iter()
+            .find(|t| t.matches_ref(&step.task))
+            .ok_or_else(|| anyhow!("Task {} of the plan is no longer in the catalog", step.task))?;
+        if digest(task)? != step.digest {
+            return Err(anyhow!("Task {} changed since the plan was created: create a new plan", step.task));
+        }
+    }
+
+    // L'evento dello stack riporta l'errore, il report i singoli task falliti
+    let mut report = PlanReport::default();
+    let result = hooks::track(config, Operation::Install, &stack.name, || run_plan(config, plan, &stack, &mut tasks, &mut report));
+
+    match result {
+        Err(e) if report.failed.is_empty() => Err(e),
+        _ => Ok(report),
+    }
+}
+
+/// Esegue i passi del piano senza pubblicare eventi dello stack
+fn run_plan(config: &Config, plan: &Plan, stack: &Stack, tasks: &mut [Task], report: &mut PlanReport) -> Result<()> {
+    let values = stack.variable_values(config);
+    stack.check_required_variables(&values)?;
+
+    if plan.install_count() > 0 && stack.snapshot {
+        let snap = snapshot::create_snapshot(config, &stack.name)
+            .context(format!("Snapshot required by stack {} failed, plan aborted", stack.name))?;
+        snapshot::save_restore_point(config, &stack.name, &snap)?;
+    }
+
+    // I comandi di orchestrazione dello stack servono solo se c'è qualcosa da installare
+    if plan.install_count() == 0 {
+        return run_steps(config, plan, stack, tasks, report, &values);
+    }
+    let result = stack.run_pre_install(config, &values)
+        .and_then(|_| run_steps(config, plan, stack, tasks, report, &values));
+    stack.run_post_install(config, &values, result)
+}
+
+/// Installa i task del piano nell'ordine previsto
+fn run_steps(config: &Config, plan: &Plan, stack: &Stack, tasks: &mut [Task], report: &mut PlanReport,
+             values: &HashMap<String, String>) -> Result<()> {
+    let total = plan.steps.len();
+    for (i, step) in plan.steps.iter().enumerate() {
+        events::progress(
+            format!("Stack {}", stack.name),
+            format!("piano: {} ({}/{})", step.task, i + 1, total),
+            Some(i as f64 * 100.0 / total as f64),
+            false,
+        );
+
+        let Some(task) = tasks.iter_mut().find(|t| t.matches_ref(&step.task)) else {
+            continue;
+        };
+
+        if step.action == PlanAction::Excluded {
+            report.excluded.push(step.task.clone());
+            continue;
+        }
+
+        // Un task installato dopo la creazione del piano non viene reinstallato
+        if step.action == PlanAction::Skip || task.installed {
+            report.skipped.push(step.task.clone());
+            continue;
+        }
+
+        // Le variabili dei task sono indicate nello stack con il riferimento usato in `tasks`
+        let task_ref = stack.task_names.iter()
+            .find(|r| task.matches_ref(r))
+            .map(String::as_str)
+            .unwrap_or(&task.name);
+        task.variables = stack.task_variables_for(task_ref, values);
+        let result = task.resolve_version(step.version.as_deref(), None)
+            .and_then(|pinned| {
+                task.pinned = pinned;
+                task.install(config)
+            });
+        task.variables.clear();
+        task.pinned = None;
+
+        match result {
+            Ok(()) => {
+                info!("Installed task {} from the plan of stack {}", step.task, stack.name);
+                report.installed.push(step.task.clone());
+            },
+            Err(e) => {
+                error!("Failed to install task {} from the plan of stack {}: {:#}", step.task, stack.name, e);
+                report.failed.push((step.task.clone(), format!("{:#}", e)));
+
+                if step.canary {
+                    warn!("Canary task {} failed, plan of stack {} aborted", step.task, stack.name);
+                    report.aborted_by = Some(step.task.clone());
+                    break;
+                }
+            },
+        }
+    }
+
+    events::progress(format!("Stack {}", stack.name), format!("{} passi elaborati", total), Some(100.0), true);
+
+    if !report.is_ok() {
+        return Err(anyhow!(
+            "Failed to install {} tasks of the plan for stack {}: {:?}",
+            report.failed.len(),
+            stack.name,
+            report.failed.iter().map(|(task, _)| task).collect::<Vec<_>>()
+        ));
+    }
+
+    if plan.install_count() > 0 && stack.snapshot {
+        snapshot::clear_restore_point(config, &stack.name)?;
+    }
+
+    Ok(())
+}
+
+/// Cerca uno stack nel catalogo
+fn find_stack<'a>(stacks: &'a [Stack], reference: &str) -> Result<&'a Stack> {
+    let matching: Vec<&Stack> = stacks.iter().filter(|s| s.matches_ref(reference)).collect();
+    match matching.as_slice() {
+        [stack] => Ok(stack),
+        [] => Err(anyhow!("Stack not found: {}", reference)),
+        _ => Err(anyhow!("Stack {} is defined in more than one catalog: use source:name", reference)),
+    }
+}
+
+/// Calcola l'impronta di una definizione del catalogo
+///
+/// La definizione passa da un valore JSON, le cui mappe hanno le chiavi ordinate, così
+/// che l'impronta non dipenda dall'ordine delle mappe in memoria.
+fn digest<T: Serialize>(definition: &T) -> Result<String> {
+    let value = serde_json::to_value(definition).context("Failed to serialize definition")?;
+    Ok(source::sha256_hex(value.to_string().as_bytes()))
+}
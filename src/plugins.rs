@@ -0,0 +1,142 @@
+//! Plugin come sottocomandi esterni
+//!
+//! Come per git, un eseguibile `galatea-<nome>` presente nel `PATH` diventa il sottocomando
+//! `galatea <nome>`: gli argomenti successivi gli vengono passati così come sono e il suo
+//! codice di uscita diventa quello di Galatea. I sottocomandi predefiniti hanno la
+//! precedenza sui plugin con lo stesso nome.
+//!
+//! Il plugin riceve nell'ambiente i riferimenti per interrogare Galatea ([`PLUGIN_API_VERSION`]
+//! ne identifica il formato): il percorso dell'eseguibile, da usare con i sottocomandi che
+//! producono JSON (`report`, `drift --format json`, `apply --dry-run --format json`), il file
+//! di configurazione e le directory di task, stack e stato.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use anyhow::{Context, Result};
+use log::{info, warn};
+
+use crate::config::Config;
+use crate::utils;
+
+/// Prefisso degli eseguibili dei plugin
+pub const PLUGIN_PREFIX: &str = "galatea-";
+
+/// Versione dell'interfaccia offerta ai plugin (`GALATEA_PLUGIN_API`)
+pub const PLUGIN_API_VERSION: u32 = 1;
+
+/// Restituisce il percorso del plugin che fornisce un sottocomando, se presente nel `PATH`
+///
+/// # Arguments
+///
+/// * `name` - Il nome del sottocomando
+pub fn find(name: &str) -> Option<PathBuf> {
+    // Un nome con separatori potrebbe uscire dalle directory del PATH
+    if name.is_empty() || name.contains(std::path::MAIN_SEPARATOR) || name.contains('/') {
+        return None;
+    }
+
+    let file_name = format!("{}{}", PLUGIN_PREFIX, name);
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(&file_name))
+        .find(|path| utils::is_executable(path))
+}
+
+/// Restituisce i plugin disponibili nel `PATH`, per nome del sottocomando
+///
+/// A parità di nome vale il primo trovato nel `PATH`, come per l'esecuzione.
+pub fn discover() -> BTreeMap<String, PathBuf> {
+    let mut plugins = BTreeMap::new();
+    let Some(path) = env::var_os("PATH") else {
+        return plugins;
+    };
+
+    for dir in env::split_paths(&path) {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let Some(name) = file_name.strip_prefix(PLUGIN_PREFIX).filter(|name| !name.is_empty()) else {
+                continue;
+            };
+            let path = entry.path();
+            if utils::is_executable(&path) && !plugins.contains_key(name) {
+                plugins.insert(name.to_string(), path);
+            }
+        }
+    }
+    plugins
+}
+
+/// Esegue un plugin
+///
+/// # Arguments
+///
+/// * `name` - Il nome del sottocomando
+/// * `args` - Gli argomenti che seguono il sottocomando
+/// * `config_path` - Il percorso del file di configurazione specificato con --config
+/// * `read_only` - Indica se è stato specificato --read-only
+///
+/// # Returns
+///
+/// Il codice di uscita del plugin
+pub fn run(name: &str, args: &[OsString], config_path: Option<&str>, read_only: bool) -> Result<i32> {
+    let Some(plugin) = find(name) else {
+        return Err(anyhow::anyhow!("Unknown subcommand: {} (no {}{} plugin found in PATH)", name, PLUGIN_PREFIX, name));
+    };
+    info!("Running plugin {} for subcommand {}", plugin.display(), name);
+
+    let mut command = Command::new(&plugin);
+    command.args(args);
+    for (key, value) in plugin_env(config_path, read_only) {
+        command.env(key, value);
+    }
+
+    let status = command.status()
+        .context(format!("Failed to run plugin {}", plugin.display()))?;
+
+    // Un plugin terminato da un segnale esce come farebbe la shell (128 + segnale)
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            warn!("Plugin {} terminated by signal {}", plugin.display(), signal);
+            return Ok(128 + signal);
+        }
+    }
+
+    Ok(status.code().unwrap_or(1))
+}
+
+/// Restituisce le variabili d'ambiente passate ai plugin
+///
+/// Le variabili che corrispondono a opzioni di configurazione valgono anche come override
+/// (vedi `config`) per i sottocomandi di Galatea avviati dal plugin, che vedono così la
+/// stessa configurazione e restano in sola lettura se lo è il comando di partenza.
+fn plugin_env(config_path: Option<&str>, read_only: bool) -> Vec<(&'static str, String)> {
+    let mut vars = vec![("GALATEA_PLUGIN_API", PLUGIN_API_VERSION.to_string())];
+
+    if let Ok(exe) = env::current_exe() {
+        vars.push(("GALATEA_BIN", exe.to_string_lossy().into_owned()));
+    }
+    if let Some(path) = config_path {
+        vars.push(("GALATEA_CONFIG", path.to_string()));
+    }
+
+    // Senza configurazione valida il plugin riceve solo l'eseguibile e le opzioni
+    let config = Config::load(config_path)
+        .map_err(|e| warn!("Configuration not available to plugins: {:#}", e))
+        .ok();
+    if let Some(config) = &config {
+        vars.push(("GALATEA_TASKS_DIR", config.tasks_dir.clone()));
+        vars.push(("GALATEA_STACKS_DIR", config.stacks_dir.clone()));
+        vars.push(("GALATEA_STATE_DIR", config.state_dir.clone()));
+    }
+    let read_only = read_only || config.is_some_and(|config| config.read_only);
+    vars.push(("GALATEA_READ_ONLY", read_only.to_string()));
+    vars
+}
@@ -0,0 +1,140 @@
+//! Controlli preliminari delle directory di Galatea
+//!
+//! Prima delle operazioni che modificano il sistema viene verificato che le directory dei
+//! task, dello stato, delle copie di lavoro e dei log siano scrivibili e non si trovino su
+//! un file system montato in sola lettura (immagini immutabili, container con il file
+//! system radice in sola lettura, dischi rimontati `ro` dopo un errore). Il problema viene
+//! così segnalato subito, con la directory e il punto di montaggio coinvolti, invece che
+//! come errore di scrittura a metà di un'installazione.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use log::{debug, warn};
+
+use crate::config::Config;
+use crate::error::GalateaError;
+use crate::logger;
+
+/// Tabella dei file system montati
+const MOUNTS_FILE: &str = "/proc/self/mounts";
+
+/// File creato e rimosso per verificare che una directory sia scrivibile
+const PROBE_FILE: &str = ".galatea_preflight";
+
+/// Verifica che le directory in cui Galatea scrive siano utilizzabili
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+///
+/// # Returns
+///
+/// Un errore di stato con tutte le directory non scrivibili
+pub fn check(config: &Config) -> Result<()> {
+    let mut dirs = vec![
+        ("tasks_dir", PathBuf::from(&config.tasks_dir)),
+        ("state_dir", PathBuf::from(&config.state_dir)),
+    ];
+    let runs_dir = config.runs_dir();
+    if !runs_dir.starts_with(&config.state_dir) {
+        dirs.push(("runs_dir", runs_dir));
+    }
+    if let Some(log_dir) = logger::get_log_directory() {
+        dirs.push(("log_dir", PathBuf::from(log_dir)));
+    }
+
+    let problems: Vec<String> = dirs.iter()
+        .filter_map(|(name, path)| check_dir(path).map(|problem| format!("{} {}", name, problem)))
+        .collect();
+    if problems.is_empty() {
+        debug!("Preflight check passed for {}", dirs.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", "));
+        return Ok(());
+    }
+
+    for problem in &problems {
+        warn!("Preflight check failed: {}", problem);
+    }
+    Err(GalateaError::State(format!("Preflight check failed: {}", problems.join("; "))).into())
+}
+
+/// Verifica che una directory sia scrivibile
+///
+/// Una directory non ancora creata viene verificata sulla prima directory esistente che
+/// la contiene, dove verrebbe creata.
+///
+/// # Arguments
+///
+/// * `path` - La directory da verificare
+///
+/// # Returns
+///
+/// La descrizione del problema, o `None` se la directory è scrivibile
+pub fn check_dir(path: &Path) -> Option<String> {
+    let Some(existing) = path.ancestors().find(|dir| dir.exists()) else {
+        return Some(format!("{:?} cannot be created: no existing parent directory", path));
+    };
+    if !existing.is_dir() {
+        return Some(format!("{:?} is not a directory", existing));
+    }
+
+    if let Some(mount_point) = read_only_mount(existing) {
+        return Some(format!("{:?} is on the read-only mount {:?}", path, mount_point));
+    }
+
+    let probe = existing.join(PROBE_FILE);
+    let written = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe);
+    match written {
+        Ok(_) => {
+            let _ = fs::remove_file(&probe);
+            None
+        },
+        // Un file rimasto da un controllo interrotto dimostra comunque che si può scrivere
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => None,
+        Err(e) => Some(format!("{:?} is not writable: {}", existing, e)),
+    }
+}
+
+/// Restituisce il punto di montaggio in sola lettura che contiene un percorso, se presente
+fn read_only_mount(path: &Path) -> Option<PathBuf> {
+    let path = fs::canonicalize(path).ok()?;
+    let mounts = fs::read_to_string(MOUNTS_FILE).ok()?;
+
+    // Vale il montaggio più specifico; a parità, l'ultimo montato
+    let (mount_point, options) = mounts.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            (fields.len() >= 4).then(|| (PathBuf::from(unescape(fields[1])), fields[3]))
+        })
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .fold(None::<(PathBuf, &str)>, |best, candidate| match best {
+            Some(best) if best.0.components().count() > candidate.0.components().count() => Some(best),
+            _ => Some(candidate),
+        })?;
+
+    options.split(',').any(|option| option == "ro").then_some(mount_point)
+}
+
+/// Decodifica i caratteri speciali dei percorsi nella tabella dei montaggi (es. `\040`)
+fn unescape(field: &str) -> String {
+    let mut result = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        let code: String = chars.clone().take(3).collect();
+        match u8::from_str_radix(&code, 8) {
+            Ok(byte) if code.len() == 3 => {
+                result.push(byte as char);
+                chars.nth(2);
+            },
+            _ => result.push(c),
+        }
+    }
+    result
+}
@@ -0,0 +1,285 @@
+//! Esecuzione degli script in uno pseudo-terminale
+//!
+//! Alcuni installer pongono domande e si rifiutano di partire senza un terminale. I task
+//! con `interactive: true` eseguono lo script in uno pseudo-terminale (PTY) di cui lo
+//! script è il processo di controllo; il terminale viene collegato all'operatore:
+//!
+//! - nell'interfaccia TUI, con la finestra registrata con [`set_attach`], che mostra
+//!   l'output e invia quanto digitato
+//! - da linea di comando, al terminale stesso, messo in modalità raw per la durata dello
+//!   script
+//!
+//! L'output viene comunque catturato, con i valori segreti mascherati, per i log e le
+//! registrazioni delle esecuzioni.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use log::{debug, warn};
+
+use crate::redact;
+
+/// Dimensione del terminale quando non è nota quella dell'operatore
+const DEFAULT_SIZE: (u16, u16) = (24, 80);
+
+/// Carattere che segnala la fine dell'input sul terminale (Ctrl-D)
+const EOF_CHAR: u8 = 0x04;
+
+/// Intervallo con cui il collegamento da linea di comando controlla la fine dello script
+const INPUT_POLL_MS: i32 = 100;
+
+/// Finestra che collega un terminale all'operatore (es. quella dell'interfaccia TUI);
+/// restituisce `false` se non può mostrarlo
+pub type Attach = Box<dyn Fn(Terminal) -> bool + Send + Sync>;
+
+lazy_static! {
+    static ref ATTACH: Mutex<Option<Attach>> = Mutex::new(None);
+}
+
+/// Registra la finestra con cui collegare i terminali degli script interattivi
+pub fn set_attach(attach: Attach) {
+    if let Ok(mut current) = ATTACH.lock() {
+        *current = Some(attach);
+    }
+}
+
+/// Terminale di uno script interattivo, da collegare all'operatore
+pub struct Terminal {
+    /// Titolo della finestra, es. il nome del task
+    pub title: String,
+
+    /// Output dello script, a blocchi; il canale si chiude quando lo script termina
+    pub output: Receiver<String>,
+
+    /// Input dello script
+    pub input: TerminalInput,
+}
+
+/// Input di uno pseudo-terminale, condivisibile tra thread
+#[derive(Clone)]
+pub struct TerminalInput {
+    master: Arc<File>,
+}
+
+impl TerminalInput {
+    /// Invia allo script quanto digitato (anche caratteri di controllo, es. `\x03` per Ctrl-C)
+    pub fn send(&self, data: &[u8]) -> io::Result<()> {
+        (&*self.master).write_all(data)
+    }
+}
+
+/// Script avviato in uno pseudo-terminale
+pub struct PtyChild {
+    /// Il processo dello script
+    pub child: Child,
+
+    /// L'input dello script, es. per passare la password a sudo
+    pub input: TerminalInput,
+
+    /// Il thread che inoltra l'output e lo restituisce, mascherato, al termine
+    pub output: JoinHandle<String>,
+}
+
+/// Avvia un comando in un nuovo pseudo-terminale e lo collega all'operatore
+///
+/// Il processo diventa leader di una nuova sessione, come con
+/// [`executor::spawn_in_session`](crate::executor::spawn_in_session), e lo pseudo-terminale
+/// ne è il terminale di controllo.
+///
+/// # Arguments
+///
+/// * `command` - Il comando da eseguire; stdin, stdout e stderr vengono sostituiti
+/// * `title` - Il titolo della finestra del terminale
+///
+/// # Returns
+///
+/// Il processo avviato e il thread che ne legge l'output
+pub fn spawn(command: &mut Command, title: &str) -> Result<PtyChild> {
+    let (master, slave) = open_pty().context("Failed to open a pseudo-terminal")?;
+    set_size(master.as_raw_fd(), operator_size());
+
+    command
+        .stdin(Stdio::from(slave.try_clone()?))
+        .stdout(Stdio::from(slave.try_clone()?))
+        .stderr(Stdio::from(slave));
+
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setsid() == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            // stdin è già il lato slave: diventa il terminale di controllo della sessione
+            if libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+
+    let child = command.spawn();
+    // Il comando conserva il lato slave finché esiste: senza chiuderlo la lettura dal
+    // lato master non terminerebbe mai
+    command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null());
+    let child = child?;
+
+    let master = Arc::new(master);
+    let input = TerminalInput { master: Arc::clone(&master) };
+    let window = match ATTACH.lock().ok().as_ref().and_then(|attach| attach.as_ref()) {
+        Some(attach) => {
+            let (sender, receiver) = mpsc::channel();
+            if !attach(Terminal { title: title.to_string(), output: receiver, input: input.clone() }) {
+                // Senza finestra nessuno può rispondere: lo script trova la fine dell'input
+                let _ = input.send(&[EOF_CHAR]);
+            }
+            Some(sender)
+        },
+        None => {
+            bridge_console(input.clone());
+            None
+        },
+    };
+    let output = forward_output(master, window);
+
+    Ok(PtyChild { child, input, output })
+}
+
+/// Apre un nuovo pseudo-terminale
+///
+/// # Returns
+///
+/// Il lato master, usato da Galatea, e il lato slave, che diventa il terminale dello script
+fn open_pty() -> io::Result<(File, File)> {
+    unsafe {
+        let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC);
+        if master == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let master = File::from_raw_fd(master);
+        if libc::grantpt(master.as_raw_fd()) == -1 || libc::unlockpt(master.as_raw_fd()) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut name = [0 as libc::c_char; 128];
+        if libc::ptsname_r(master.as_raw_fd(), name.as_mut_ptr(), name.len()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let slave = libc::open(name.as_ptr(), libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC);
+        if slave == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok((master, File::from_raw_fd(slave)))
+    }
+}
+
+/// Restituisce righe e colonne del terminale dell'operatore, se Galatea ne ha uno
+fn operator_size() -> (u16, u16) {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0 && size.ws_row > 0 {
+        (size.ws_row, size.ws_col)
+    } else {
+        DEFAULT_SIZE
+    }
+}
+
+/// Imposta le dimensioni di uno pseudo-terminale
+fn set_size(fd: RawFd, (rows, cols): (u16, u16)) {
+    let size = libc::winsize { ws_row: rows, ws_col: cols, ws_xpixel: 0, ws_ypixel: 0 };
+    if unsafe { libc::ioctl(fd, libc::TIOCSWINSZ, &size) } == -1 {
+        debug!("Failed to set the pseudo-terminal size: {}", io::Error::last_os_error());
+    }
+}
+
+/// Legge l'output dello script e lo inoltra alla finestra collegata o al terminale
+///
+/// # Returns
+///
+/// Il thread di lettura, che restituisce l'output con i valori segreti mascherati
+fn forward_output(master: Arc<File>, window: Option<Sender<String>>) -> JoinHandle<String> {
+    thread::spawn(move || {
+        let mut captured = Vec::new();
+        let mut buffer = [0u8; 4096];
+        loop {
+            // Alla fine dello script la lettura dal lato master fallisce con EIO
+            let read = match (&*master).read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(read) => read,
+            };
+            captured.extend_from_slice(&buffer[..read]);
+
+            // Anche l'output mostrato è mascherato, es. la password di sudo scritta sul terminale
+            let chunk = redact::mask(&String::from_utf8_lossy(&buffer[..read])).to_string();
+            match &window {
+                Some(window) => {
+                    let _ = window.send(chunk);
+                },
+                None => {
+                    let mut stdout = io::stdout();
+                    let _ = stdout.write_all(chunk.as_bytes());
+                    let _ = stdout.flush();
+                },
+            }
+        }
+        redact::mask(&String::from_utf8_lossy(&captured)).to_string()
+    })
+}
+
+/// Collega il terminale dell'operatore all'input dello script
+///
+/// Il terminale viene messo in modalità raw, così che ogni tasto arrivi subito allo
+/// script e che l'eco sia quella dello pseudo-terminale; viene ripristinato quando lo
+/// script chiude il proprio terminale. Senza un terminale (es. in cron) lo script riceve
+/// lo standard input di Galatea seguito dalla fine dell'input.
+fn bridge_console(input: TerminalInput) {
+    let stdin = libc::STDIN_FILENO;
+    if unsafe { libc::isatty(stdin) } != 1 {
+        warn!("Interactive script without a terminal: answers are read from standard input");
+        thread::spawn(move || {
+            let _ = io::copy(&mut io::stdin(), &mut &*input.master);
+            // Ctrl-D: chi legge dal terminale trova la fine dell'input invece di attendere
+            let _ = input.send(&[EOF_CHAR]);
+        });
+        return;
+    }
+
+    let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(stdin, &mut saved) } != 0 {
+        return;
+    }
+    let mut raw = saved;
+    unsafe {
+        libc::cfmakeraw(&mut raw);
+        libc::tcsetattr(stdin, libc::TCSANOW, &raw);
+    }
+
+    thread::spawn(move || {
+        let mut buffer = [0u8; 1024];
+        loop {
+            let mut poll = libc::pollfd { fd: stdin, events: libc::POLLIN, revents: 0 };
+            match unsafe { libc::poll(&mut poll, 1, INPUT_POLL_MS) } {
+                // Senza input: controlla se lo script ha chiuso il terminale
+                0 if is_open(&input) => continue,
+                0 | -1 => break,
+                _ => {},
+            }
+            let read = unsafe { libc::read(stdin, buffer.as_mut_ptr() as *mut libc::c_void, buffer.len()) };
+            if read <= 0 || input.send(&buffer[..read as usize]).is_err() {
+                break;
+            }
+        }
+        unsafe { libc::tcsetattr(stdin, libc::TCSANOW, &saved) };
+    });
+}
+
+/// Verifica se qualche processo ha ancora aperto il lato slave dello pseudo-terminale
+fn is_open(input: &TerminalInput) -> bool {
+    let mut poll = libc::pollfd { fd: input.master.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+    unsafe { libc::poll(&mut poll, 1, 0) };
+    poll.revents & libc::POLLHUP == 0
+}
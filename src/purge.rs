@@ -0,0 +1,155 @@
+//! Rimozione completa dei dati gestiti da Galatea
+//!
+//! Questo modulo implementa la pulizia della macchina in fase di dismissione:
+//! disinstallazione opzionale dei task installati (in ordine sicuro rispetto alle
+//! dipendenze) e rimozione di stato, bundle scaricati, catalogo e log.
+
+use std::path::{Path, PathBuf};
+use std::fs;
+use anyhow::{Context, Result, anyhow};
+use log::{info, warn, error};
+
+use crate::config::Config;
+use crate::logger;
+use crate::task::{self, load_tasks};
+
+/// Opzioni per la rimozione dei dati gestiti
+#[derive(Debug, Clone, Default)]
+pub struct PurgeOptions {
+    /// Disinstalla i task installati prima di rimuovere i dati
+    pub uninstall_tasks: bool,
+
+    /// Mantiene i file di log
+    pub keep_logs: bool,
+
+    /// Rimuove i dati anche se alcune disinstallazioni falliscono
+    pub force: bool,
+}
+
+/// Risultato della rimozione
+#[derive(Debug, Clone, Default)]
+pub struct PurgeReport {
+    /// Task disinstallati con successo
+    pub uninstalled: Vec<String>,
+
+    /// Task la cui disinstallazione è fallita, con il relativo errore
+    pub failed: Vec<(String, String)>,
+
+    /// Directory rimosse
+    pub removed_dirs: Vec<PathBuf>,
+}
+
+/// Restituisce le directory che verranno rimosse
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+/// * `keep_logs` - Se `true` la directory dei log viene esclusa
+///
+/// # Returns
+///
+/// L'elenco delle directory gestite esistenti
+pub fn purge_targets(config: &Config, keep_logs: bool) -> Vec<PathBuf> {
+    let mut targets = vec![
+        PathBuf::from(&config.tasks_dir),
+        PathBuf::from(&config.stacks_dir),
+        PathBuf::from(&config.state_dir),
+    ];
+
+    // Le copie di lavoro rimaste da esecuzioni interrotte, se fuori dalla directory di stato
+    let runs_dir = config.runs_dir();
+    if !runs_dir.starts_with(&config.state_dir) {
+        targets.push(runs_dir);
+    }
+
+    if !keep_logs {
+        if let Some(log_dir) = logger::get_log_directory() {
+            targets.push(PathBuf::from(log_dir));
+        }
+    }
+
+    targets.retain(|p| p.exists());
+    targets.dedup();
+    targets
+}
+
+/// Rimuove i dati gestiti da Galatea
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+/// * `options` - Le opzioni di rimozione
+///
+/// # Returns
+///
+/// Il report delle operazioni eseguite
+pub fn purge(config: &Config, options: &PurgeOptions) -> Result<PurgeReport> {
+    info!("Purging Galatea-managed data (uninstall tasks: {})", options.uninstall_tasks);
+
+    let mut report = PurgeReport::default();
+
+    if options.uninstall_tasks {
+        uninstall_all(config, &mut report)?;
+
+        if !report.failed.is_empty() && !options.force {
+            return Err(anyhow!(
+                "Failed to uninstall {} tasks, managed data has been kept: {:?}",
+                report.failed.len(),
+                report.failed.iter().map(|(name, _)| name).collect::<Vec<_>>()
+            ));
+        }
+    }
+
+    for dir in purge_targets(config, options.keep_logs) {
+        remove_managed_dir(&dir)?;
+        report.removed_dirs.push(dir);
+    }
+
+    info!("Purge completed, removed {} directories", report.removed_dirs.len());
+    Ok(report)
+}
+
+/// Disinstalla tutti i task installati, dai dipendenti verso le dipendenze
+fn uninstall_all(config: &Config, report: &mut PurgeReport) -> Result<()> {
+    let mut tasks = load_tasks(config)?;
+
+    // Ordine inverso rispetto all'installazione: prima i task che dipendono da altri
+    let order: Vec<usize> = task::sort_by_dependencies(&tasks).into_iter().rev().collect();
+
+    for idx in order {
+        let task = &mut tasks[idx];
+        if !task.installed {
+            continue;
+        }
+
+        match task.uninstall(config) {
+            Ok(_) => {
+                info!("Task {} uninstalled during purge", task.name);
+                report.uninstalled.push(task.name.clone());
+            },
+            Err(e) => {
+                error!("Failed to uninstall task {} during purge: {}", task.name, e);
+                report.failed.push((task.name.clone(), e.to_string()));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Rimuove una directory gestita, rifiutando percorsi di sistema evidentemente errati
+fn remove_managed_dir(dir: &Path) -> Result<()> {
+    let canonical = dir.canonicalize()
+        .context(format!("Failed to resolve directory: {:?}", dir))?;
+
+    // Protezione contro configurazioni che puntano a "/", alla home o a directory di primo livello
+    let is_home = crate::utils::get_home_dir().map_or(false, |home| home == canonical);
+    if canonical.components().count() < 3 || is_home {
+        warn!("Refusing to remove unsafe directory: {:?}", canonical);
+        return Err(anyhow!("Refusing to remove unsafe directory: {:?}", canonical));
+    }
+
+    info!("Removing directory: {:?}", canonical);
+    fs::remove_dir_all(&canonical)
+        .context(format!("Failed to remove directory: {:?}", canonical))
+}
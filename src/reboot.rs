@@ -0,0 +1,150 @@
+//! Rilevamento dei riavvii in sospeso
+//!
+//! Un riavvio può essere richiesto dal sistema (aggiornamento del kernel o di librerie
+//! di base) o dai task installati da Galatea (`requires_reboot` o `reboot_required`
+//! nell'esito dello script). Questo modulo raccoglie entrambe le indicazioni e permette
+//! di riavviare la macchina.
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use anyhow::{Context, Result, anyhow};
+use log::{debug, info};
+
+use crate::config::Config;
+use crate::executor;
+use crate::task::Task;
+
+/// File creato da Debian e Ubuntu quando un aggiornamento richiede un riavvio
+const REBOOT_REQUIRED_FILE: &str = "/var/run/reboot-required";
+
+/// Pacchetti che hanno richiesto il riavvio (Debian e Ubuntu)
+const REBOOT_REQUIRED_PKGS_FILE: &str = "/var/run/reboot-required.pkgs";
+
+/// Motivi per cui la macchina deve essere riavviata
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PendingReboot {
+    /// Motivi segnalati dal sistema operativo
+    pub system: Vec<String>,
+
+    /// Task installati dopo l'ultimo avvio che richiedono un riavvio
+    pub tasks: Vec<String>,
+}
+
+impl PendingReboot {
+    /// Verifica se non c'è alcun riavvio in sospeso
+    pub fn is_empty(&self) -> bool {
+        self.system.is_empty() && self.tasks.is_empty()
+    }
+
+    /// Restituisce una descrizione breve dei motivi
+    pub fn summary(&self) -> String {
+        let mut parts = self.system.clone();
+        if !self.tasks.is_empty() {
+            parts.push(format!("task {}", self.tasks.join(", ")));
+        }
+        parts.join("; ")
+    }
+}
+
+/// Verifica se la macchina deve essere riavviata
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+/// * `tasks` - I task del catalogo
+///
+/// # Returns
+///
+/// I motivi del riavvio in sospeso (vuoti se non serve riavviare)
+pub fn pending_reboot(config: &Config, tasks: &[Task]) -> PendingReboot {
+    let mut pending = PendingReboot::default();
+
+    if Path::new(REBOOT_REQUIRED_FILE).exists() {
+        let mut packages: Vec<String> = fs::read_to_string(REBOOT_REQUIRED_PKGS_FILE)
+            .map(|content| content.lines().map(str::trim).filter(|l| !l.is_empty()).map(str::to_string).collect())
+            .unwrap_or_default();
+        packages.sort();
+        packages.dedup();
+        if packages.is_empty() {
+            pending.system.push("aggiornamenti di sistema".to_string());
+        } else {
+            pending.system.push(format!("aggiornamenti di sistema ({})", packages.join(", ")));
+        }
+    } else if needs_restarting() {
+        pending.system.push("aggiornamenti di sistema (needs-restarting)".to_string());
+    }
+
+    // Solo i task applicati dopo l'ultimo avvio attendono ancora il riavvio
+    let boot = boot_time();
+    pending.tasks = tasks.iter()
+        .filter(|task| task.reboot_required())
+        .filter(|task| match (boot, installed_at(config, task)) {
+            (Some(boot), Some(changed)) => changed > boot,
+            _ => false,
+        })
+        .map(|task| task.name.clone())
+        .collect();
+
+    pending
+}
+
+/// Verifica con `needs-restarting -r` (RHEL, Fedora) se serve un riavvio
+fn needs_restarting() -> bool {
+    if !executor::is_command_available("needs-restarting") {
+        return false;
+    }
+
+    // Il comando termina con 1 se il riavvio è necessario, con 0 altrimenti
+    match Command::new("needs-restarting").arg("-r").stdout(Stdio::null()).stderr(Stdio::null()).status() {
+        Ok(status) => status.code() == Some(1),
+        Err(e) => {
+            debug!("Failed to run needs-restarting: {}", e);
+            false
+        },
+    }
+}
+
+/// Restituisce l'istante dell'ultimo avvio, letto da /proc/stat
+fn boot_time() -> Option<SystemTime> {
+    let stat = fs::read_to_string("/proc/stat").ok()?;
+    let secs = stat.lines()
+        .find_map(|line| line.strip_prefix("btime "))
+        .and_then(|value| value.trim().parse::<u64>().ok())?;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// Restituisce l'istante dell'ultima installazione o dell'ultimo esito di un task installato
+///
+/// Lo stato è letto dai file e non dal task in memoria, che le installazioni di uno stack
+/// non aggiornano.
+fn installed_at(config: &Config, task: &Task) -> Option<SystemTime> {
+    let installed = fs::metadata(task.state_file(config, "state")).ok()?.modified().ok()?;
+    let result = fs::metadata(task.state_file(config, "result.json")).ok()
+        .and_then(|metadata| metadata.modified().ok());
+    Some(result.map_or(installed, |result| result.max(installed)))
+}
+
+/// Riavvia la macchina
+///
+/// Usa `systemctl reboot` se disponibile, altrimenti `shutdown -r now`.
+pub fn reboot_now() -> Result<()> {
+    let (program, args): (&str, &[&str]) = if executor::is_command_available("systemctl") {
+        ("systemctl", &["reboot"])
+    } else {
+        ("shutdown", &["-r", "now"])
+    };
+
+    info!("Rebooting the system with {} {}", program, args.join(" "));
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .context(format!("Failed to run {}", program))?;
+
+    if !output.status.success() {
+        return Err(anyhow!("{} failed: {}", program, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+
+    Ok(())
+}
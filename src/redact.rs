@@ -0,0 +1,72 @@
+//! Mascheramento dei valori segreti
+//!
+//! I valori segreti (variabili `secret` degli stack, valori cifrati, password e token)
+//! vengono registrati nel momento in cui sono disponibili in chiaro. Da quel momento il
+//! filtro li sostituisce con `********` nei log, negli eventi mostrati da TUI e CLI,
+//! nell'output catturato dagli script e nei report.
+
+use std::borrow::Cow;
+use std::sync::RwLock;
+use lazy_static::lazy_static;
+
+/// Testo che sostituisce i valori segreti
+pub const MASK: &str = "********";
+
+/// Lunghezza minima di un valore da mascherare: valori più corti (es. "1", "yes")
+/// comparirebbero ovunque e renderebbero illeggibili i log senza proteggere nulla
+const MIN_SECRET_LEN: usize = 4;
+
+lazy_static! {
+    static ref SECRETS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+}
+
+/// Registra un valore segreto da mascherare
+///
+/// # Arguments
+///
+/// * `value` - Il valore in chiaro
+pub fn register(value: &str) {
+    let value = value.trim();
+    if value.chars().count() < MIN_SECRET_LEN {
+        return;
+    }
+
+    if let Ok(mut secrets) = SECRETS.write() {
+        if !secrets.iter().any(|s| s == value) {
+            secrets.push(value.to_string());
+            // I valori più lunghi prima, così un segreto che ne contiene un altro viene
+            // mascherato per intero
+            secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+        }
+    }
+}
+
+/// Verifica se è stato registrato almeno un valore segreto
+///
+/// Finché non ci sono segreti l'output degli script non ha bisogno di essere filtrato.
+pub fn is_active() -> bool {
+    SECRETS.read().map(|secrets| !secrets.is_empty()).unwrap_or(false)
+}
+
+/// Maschera i valori segreti registrati in un testo
+///
+/// # Arguments
+///
+/// * `text` - Il testo da filtrare
+///
+/// # Returns
+///
+/// Il testo con i valori segreti sostituiti da [`MASK`]
+pub fn mask(text: &str) -> Cow<'_, str> {
+    let Ok(secrets) = SECRETS.read() else {
+        return Cow::Borrowed(text);
+    };
+
+    let mut masked = Cow::Borrowed(text);
+    for secret in secrets.iter() {
+        if masked.contains(secret.as_str()) {
+            masked = Cow::Owned(masked.replace(secret.as_str(), MASK));
+        }
+    }
+    masked
+}
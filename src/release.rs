@@ -0,0 +1,295 @@
+//! Asset delle release di GitHub e GitLab
+//!
+//! Gli URL di download degli asset di una release vengono riconosciuti e, per i
+//! repository privati, risolti con l'API della piattaforma usando un token:
+//!
+//! ```text
+//! https://github.com/<owner>/<repo>/releases/download/<tag>/<asset>
+//! https://github.com/<owner>/<repo>/releases/latest/download/<asset>
+//! https://<gitlab>/<group>/<project>/-/releases/<tag>/downloads/<asset>
+//! https://<gitlab>/<group>/<project>/-/releases/permalink/latest/downloads/<asset>
+//! ```
+//!
+//! I token sono indicati per host in `release_tokens` (anche cifrati) oppure nelle
+//! variabili d'ambiente `GITHUB_TOKEN` e `GITLAB_TOKEN`. Con `latest` viene scaricato
+//! l'asset dell'ultima release pubblicata. Senza token gli URL delle release pubbliche
+//! vengono scaricati così come sono, salvo `latest` su GitLab che va sempre risolto.
+
+use std::env;
+use anyhow::{Context, Result, anyhow};
+use log::{debug, info};
+use reqwest::Url;
+use reqwest::header::ACCEPT;
+use serde_json::Value;
+
+use crate::config::Config;
+use crate::downloader;
+use crate::redact;
+use crate::secrets;
+use crate::source::{HostPolicy, Source};
+
+/// User-Agent delle richieste, obbligatorio per l'API di GitHub
+const CLIENT_USER_AGENT: &str = concat!("galatea/", env!("CARGO_PKG_VERSION"));
+
+/// Piattaforma che ospita la release
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Forge {
+    GitHub,
+    GitLab,
+}
+
+impl Forge {
+    /// Restituisce la variabile d'ambiente con il token predefinito della piattaforma
+    fn token_env(&self) -> &'static str {
+        match self {
+            Forge::GitHub => "GITHUB_TOKEN",
+            Forge::GitLab => "GITLAB_TOKEN",
+        }
+    }
+}
+
+/// Asset di una release indicato da un URL di download
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReleaseAsset {
+    /// Piattaforma della release
+    pub forge: Forge,
+
+    /// Host della piattaforma, es. `github.com`
+    pub host: String,
+
+    /// Schema, host e porta della piattaforma, es. `https://gitlab.example.com`
+    pub origin: String,
+
+    /// Percorso del progetto, es. `owner/repo` o `group/subgroup/project`
+    pub project: String,
+
+    /// Tag della release; `None` indica l'ultima release pubblicata
+    pub tag: Option<String>,
+
+    /// Nome (o percorso, su GitLab) dell'asset
+    pub asset: String,
+}
+
+/// Indirizzo effettivo da cui scaricare un file, con gli header della richiesta
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Download {
+    /// URL da richiedere
+    pub url: String,
+
+    /// Header aggiuntivi (autenticazione)
+    pub headers: Vec<(&'static str, String)>,
+}
+
+impl Download {
+    /// Download diretto dell'URL, senza header aggiuntivi
+    pub fn direct(url: &str) -> Self {
+        Download { url: url.to_string(), headers: Vec::new() }
+    }
+}
+
+impl ReleaseAsset {
+    /// Riconosce l'URL di download di un asset di una release
+    ///
+    /// # Returns
+    ///
+    /// L'asset indicato dall'URL, oppure `None` se non è l'URL di una release
+    pub fn parse(url: &str) -> Option<Self> {
+        let parsed = Url::parse(url).ok()?;
+        let host = parsed.host_str()?.to_lowercase();
+        let path = parsed.path().trim_matches('/');
+
+        if host == "github.com" || host == "www.github.com" {
+            let segments: Vec<&str> = path.split('/').collect();
+            let (tag, asset) = match segments.as_slice() {
+                [_, _, "releases", "latest", "download", asset] => (None, *asset),
+                [_, _, "releases", "download", tag, asset] => (Some(tag.to_string()), *asset),
+                _ => return None,
+            };
+            return Some(ReleaseAsset {
+                forge: Forge::GitHub,
+                host: "github.com".to_string(),
+                origin: "https://github.com".to_string(),
+                project: format!("{}/{}", segments[0], segments[1]),
+                tag,
+                asset: asset.to_string(),
+            });
+        }
+
+        // Su GitLab (anche installato in proprio) le pagine del progetto sono sotto `/-/`
+        let (project, rest) = path.split_once("/-/releases/")?;
+        let (tag, asset) = match rest.strip_prefix("permalink/latest/downloads/") {
+            Some(asset) => (None, asset),
+            None => {
+                let (tag, asset) = rest.split_once("/downloads/")?;
+                (Some(tag.to_string()), asset)
+            },
+        };
+        if project.is_empty() || asset.is_empty() {
+            return None;
+        }
+
+        Some(ReleaseAsset {
+            forge: Forge::GitLab,
+            host,
+            origin: parsed.origin().ascii_serialization(),
+            project: project.to_string(),
+            tag,
+            asset: asset.to_string(),
+        })
+    }
+
+    /// Restituisce l'URL dell'API con i dati della release
+    fn api_url(&self) -> String {
+        match self.forge {
+            Forge::GitHub => match &self.tag {
+                Some(tag) => format!("https://api.github.com/repos/{}/releases/tags/{}", self.project, tag),
+                None => format!("https://api.github.com/repos/{}/releases/latest", self.project),
+            },
+            Forge::GitLab => format!("{}/api/v4/projects/{}/releases/{}",
+                                     self.origin, self.project.replace('/', "%2F"),
+                                     self.tag.as_deref().map_or("permalink/latest".to_string(), |tag| tag.replace('/', "%2F"))),
+        }
+    }
+
+    /// Cerca nei dati della release l'indirizzo di download dell'asset
+    fn asset_location(&self, release: &Value) -> Option<String> {
+        match self.forge {
+            // L'URL dell'API dell'asset restituisce il file con `Accept: application/octet-stream`
+            Forge::GitHub => release.get("assets")?.as_array()?.iter()
+                .find(|asset| asset.get("name").and_then(Value::as_str) == Some(self.asset.as_str()))
+                .and_then(|asset| asset.get("url"))
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            Forge::GitLab => release.get("assets")?.get("links")?.as_array()?.iter()
+                .find(|link| {
+                    let path = link.get("direct_asset_path").and_then(Value::as_str).map(|p| p.trim_start_matches('/'));
+                    let name = link.get("name").and_then(Value::as_str);
+                    path == Some(self.asset.as_str()) || name == Some(self.asset.as_str())
+                })
+                .and_then(|link| link.get("direct_asset_url").or_else(|| link.get("url")))
+                .and_then(Value::as_str)
+                .map(str::to_string),
+        }
+    }
+
+    /// Risolve l'asset nell'indirizzo da cui scaricarlo
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - L'URL di download indicato nel catalogo
+    /// * `token` - Il token per l'API della piattaforma, se disponibile
+    /// * `timeout_secs` - Il timeout in secondi per la richiesta all'API
+    /// * `tls_pin` - L'impronta SHA-256 attesa del certificato del server, se indicata
+    /// * `hosts` - Gli host da cui è consentito scaricare
+    ///
+    /// # Returns
+    ///
+    /// L'indirizzo dell'asset e gli header con cui richiederlo
+    pub fn resolve(&self, url: &str, token: Option<&str>, timeout_secs: u64, tls_pin: Option<&str>, hosts: &HostPolicy) -> Result<Download> {
+        // Le release pubbliche si scaricano dall'URL indicato (GitHub risolve da sé `latest`)
+        let needs_api = token.is_some() || (self.forge == Forge::GitLab && self.tag.is_none());
+        if !needs_api {
+            return Ok(Download::direct(url));
+        }
+
+        // `Authorization` (e non `PRIVATE-TOKEN` su GitLab) perché il client lo rimuove nei
+        // reindirizzamenti verso altri host, come lo storage degli asset
+        let mut headers = vec![("User-Agent", CLIENT_USER_AGENT.to_string())];
+        if let Some(token) = token {
+            headers.push(("Authorization", format!("Bearer {}", token)));
+        }
+
+        let release_ref = self.tag.as_deref().unwrap_or("latest");
+        info!("Resolving release asset {} of {} ({}) via the {:?} API", self.asset, self.project, release_ref, self.forge);
+
+        let api_url = self.api_url();
+        hosts.check(&api_url)?;
+        let mut request = downloader::http_client(timeout_secs, tls_pin, hosts)?.get(&api_url);
+        for (name, value) in &headers {
+            request = request.header(*name, value);
+        }
+        if self.forge == Forge::GitHub {
+            request = request.header(ACCEPT, "application/vnd.github+json");
+        }
+
+        let response = request.send()
+            .context(format!("Failed to query release {} of {}", release_ref, self.project))?;
+        if !response.status().is_success() {
+            return Err(anyhow!("Release {} of {} not available: HTTP {}{}", release_ref, self.project, response.status(),
+                               if token.is_none() { format!(" (private repositories need a token in release_tokens or {})", self.forge.token_env()) } else { String::new() }));
+        }
+        let release: Value = serde_json::from_str(&response.text().context("Failed to read release metadata")?)
+            .context(format!("Invalid release metadata for {}", self.project))?;
+
+        let location = self.asset_location(&release)
+            .ok_or_else(|| anyhow!("Asset {} not found in release {} of {}", self.asset, release_ref, self.project))?;
+        debug!("Release asset {} resolved to {}", self.asset, location);
+        hosts.check(&location)?;
+
+        if self.forge == Forge::GitHub {
+            headers.push(("Accept", "application/octet-stream".to_string()));
+        }
+        Ok(Download { url: location, headers })
+    }
+}
+
+/// Restituisce l'indirizzo da cui scaricare un URL del catalogo
+///
+/// # Arguments
+///
+/// * `url` - L'URL indicato nel catalogo
+/// * `token` - Il token per l'API della piattaforma, se l'URL è quello di una release
+/// * `timeout_secs` - Il timeout in secondi per le richieste
+/// * `tls_pin` - L'impronta SHA-256 attesa del certificato del server, se indicata
+/// * `hosts` - Gli host da cui è consentito scaricare
+///
+/// # Returns
+///
+/// L'indirizzo effettivo: quello dell'asset per gli URL delle release, altrimenti l'URL stesso
+pub fn locate(url: &str, token: Option<&str>, timeout_secs: u64, tls_pin: Option<&str>, hosts: &HostPolicy) -> Result<Download> {
+    match ReleaseAsset::parse(url) {
+        Some(asset) => asset.resolve(url, token, timeout_secs, tls_pin, hosts),
+        None => Ok(Download::direct(url)),
+    }
+}
+
+/// Restituisce il token da usare per un URL di release
+///
+/// Il token indicato in `release_tokens` per l'host dell'URL ha la precedenza sulla
+/// variabile d'ambiente della piattaforma.
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+/// * `url` - L'URL da scaricare
+///
+/// # Returns
+///
+/// Il token in chiaro, oppure `None` se l'URL non è di una release o non c'è un token
+pub fn token_for(config: &Config, url: &str) -> Result<Option<String>> {
+    let Some(asset) = ReleaseAsset::parse(url) else {
+        return Ok(None);
+    };
+
+    let token = match config.release_tokens.get(&asset.host) {
+        Some(token) => Some(secrets::reveal(config, token)
+            .context(format!("Failed to decrypt the release token for {}", asset.host))?),
+        None => env::var(asset.forge.token_env()).ok().filter(|token| !token.trim().is_empty()),
+    };
+    if let Some(token) = &token {
+        redact::register(token);
+    }
+    Ok(token)
+}
+
+/// Restituisce una copia della sorgente con il token per il suo URL, se è una release
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+/// * `source` - La sorgente da scaricare
+pub fn with_token(config: &Config, source: &Source) -> Result<Source> {
+    let mut source = source.clone();
+    source.token = token_for(config, &source.url)?;
+    Ok(source)
+}
@@ -0,0 +1,397 @@
+//! Registrazione e riproduzione delle esecuzioni
+//!
+//! Con `galatea record` ogni script, playbook e controllo di salute eseguito viene
+//! registrato come un passo, con il comando, le variabili d'ambiente impostate da Galatea,
+//! l'output e il codice di uscita. Al termine i passi, un manifesto e il log
+//! dell'esecuzione vengono raccolti in un archivio (bundle) che `galatea replay` permette
+//! di esaminare passo per passo, anche su una macchina diversa da quella del problema.
+//!
+//! I valori segreti registrati vengono mascherati prima di essere scritti nel bundle. Con
+//! `encrypt_state` i file del bundle vengono anche cifrati con la chiave dello stato, che
+//! serve poi a `galatea replay` (`--key` su un'altra macchina).
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Local};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use lazy_static::lazy_static;
+use log::{info, warn};
+use serde::{Serialize, Deserialize};
+
+use crate::error;
+use crate::events;
+use crate::logger;
+use crate::redact;
+use crate::state_crypt;
+use crate::utils;
+
+/// Manifesto del bundle
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Directory dei passi nel bundle
+const STEPS_DIR: &str = "steps";
+
+/// Copia del log dell'esecuzione nel bundle
+const LOG_FILE: &str = "galatea.log";
+
+/// Informazioni generali su un'esecuzione registrata
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Versione di Galatea che ha registrato l'esecuzione
+    pub version: String,
+
+    /// Nome della macchina
+    pub hostname: String,
+
+    /// Riga di comando registrata
+    pub command: Vec<String>,
+
+    /// Inizio della registrazione (RFC 3339)
+    pub started_at: String,
+
+    /// Fine della registrazione (RFC 3339)
+    pub finished_at: String,
+
+    /// Codice di uscita del sottocomando, se è terminato senza errori
+    pub exit_code: Option<i32>,
+
+    /// Errore del sottocomando
+    pub error: Option<String>,
+
+    /// Tipo dell'errore del sottocomando (download, script, state, config, other)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<String>,
+
+    /// Numero di passi registrati
+    pub steps: usize,
+}
+
+/// Comando eseguito durante un'esecuzione registrata
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Step {
+    /// Numero del passo, a partire da 1
+    pub index: usize,
+
+    /// Tipo di comando (script, playbook, command)
+    pub kind: String,
+
+    /// Identificativo dell'esecuzione del task, se disponibile
+    pub run_id: Option<String>,
+
+    /// Programma eseguito
+    pub program: String,
+
+    /// Argomenti del programma
+    pub args: Vec<String>,
+
+    /// Directory di lavoro
+    pub cwd: Option<String>,
+
+    /// Variabili d'ambiente impostate da Galatea (quelle ereditate non sono registrate)
+    pub env: BTreeMap<String, String>,
+
+    /// Extra vars passate a un playbook
+    #[serde(default)]
+    pub extra_vars: BTreeMap<String, String>,
+
+    /// Avvio del comando (RFC 3339)
+    pub started_at: String,
+
+    /// Durata in millisecondi
+    pub duration_ms: u64,
+
+    /// Codice di uscita, se il processo è terminato normalmente
+    pub exit_code: Option<i32>,
+
+    /// Output standard catturato
+    pub stdout: String,
+
+    /// Errori standard catturati
+    pub stderr: String,
+
+    /// Errore riportato da Galatea per il passo
+    pub error: Option<String>,
+
+    /// Tipo dell'errore del passo
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<String>,
+}
+
+impl Step {
+    /// Restituisce la riga di comando del passo
+    pub fn command_line(&self) -> String {
+        std::iter::once(self.program.as_str())
+            .chain(self.args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Indica se il passo è riuscito
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Registrazione in corso
+struct Recording {
+    /// Directory in cui vengono raccolti i file del bundle
+    staging: PathBuf,
+
+    /// Archivio da creare al termine
+    output: PathBuf,
+
+    /// Riga di comando registrata
+    command: Vec<String>,
+
+    /// Inizio della registrazione
+    started_at: DateTime<Local>,
+
+    /// Passi registrati finora
+    steps: usize,
+}
+
+lazy_static! {
+    static ref RECORDING: Mutex<Option<Recording>> = Mutex::new(None);
+}
+
+/// Avvia la registrazione dei comandi eseguiti
+///
+/// # Arguments
+///
+/// * `output` - L'archivio del bundle da creare al termine
+/// * `command` - La riga di comando registrata
+pub fn start(output: &Path, command: Vec<String>) -> Result<()> {
+    let staging = utils::get_temp_dir().join(format!("galatea-record-{}", std::process::id()));
+    if staging.exists() {
+        fs::remove_dir_all(&staging)
+            .context(format!("Failed to clean the recording directory {:?}", staging))?;
+    }
+    fs::create_dir_all(staging.join(STEPS_DIR))
+        .context(format!("Failed to create the recording directory {:?}", staging))?;
+
+    let mut recording = RECORDING.lock().map_err(|_| anyhow!("Recording state is poisoned"))?;
+    if recording.is_some() {
+        return Err(anyhow!("A recording is already in progress"));
+    }
+
+    info!("Recording the run into {:?}", output);
+    *recording = Some(Recording {
+        staging,
+        output: output.to_path_buf(),
+        command,
+        started_at: Local::now(),
+        steps: 0,
+    });
+    Ok(())
+}
+
+/// Verifica se è in corso una registrazione
+pub fn is_recording() -> bool {
+    RECORDING.lock().map(|recording| recording.is_some()).unwrap_or(false)
+}
+
+/// Esito di un comando eseguito, da registrare
+pub struct CommandOutcome<'a> {
+    /// Codice di uscita, se disponibile
+    pub exit_code: Option<i32>,
+
+    /// Output standard catturato
+    pub stdout: &'a str,
+
+    /// Errori standard catturati
+    pub stderr: &'a str,
+
+    /// Errore del passo, se fallito
+    pub error: Option<&'a anyhow::Error>,
+}
+
+/// Registra un comando eseguito, se è in corso una registrazione
+///
+/// # Arguments
+///
+/// * `kind` - Il tipo di comando (script, playbook, command)
+/// * `command` - Il comando eseguito
+/// * `extra_vars` - Le extra vars passate al playbook, se presenti
+/// * `started` - L'istante di avvio
+/// * `outcome` - L'esito del comando
+pub fn record_command(kind: &str, command: &Command, extra_vars: Option<&HashMap<String, String>>, started: SystemTime,
+                      outcome: CommandOutcome) {
+    let CommandOutcome { exit_code, stdout, stderr, error } = outcome;
+    let Ok(mut guard) = RECORDING.lock() else {
+        return;
+    };
+    let Some(recording) = guard.as_mut() else {
+        return;
+    };
+
+    let text = |value: &std::ffi::OsStr| redact::mask(&value.to_string_lossy()).into_owned();
+    recording.steps += 1;
+    let step = Step {
+        index: recording.steps,
+        kind: kind.to_string(),
+        run_id: events::current_run_id(),
+        program: text(command.get_program()),
+        args: command.get_args().map(text).collect(),
+        cwd: command.get_current_dir().map(|dir| dir.to_string_lossy().to_string()),
+        env: command.get_envs()
+            .filter_map(|(name, value)| Some((name.to_string_lossy().to_string(), text(value?))))
+            .collect(),
+        extra_vars: extra_vars.into_iter().flatten()
+            .map(|(name, value)| (name.clone(), redact::mask(value).into_owned()))
+            .collect(),
+        started_at: DateTime::<Local>::from(started).to_rfc3339(),
+        duration_ms: started.elapsed().map(|d| d.as_millis() as u64).unwrap_or(0),
+        exit_code,
+        stdout: redact::mask(stdout).into_owned(),
+        stderr: redact::mask(stderr).into_owned(),
+        error: error.map(|e| redact::mask(&format!("{:#}", e)).into_owned()),
+        error_kind: error.map(|e| error::kind_of(e).to_string()),
+    };
+
+    let path = recording.staging.join(STEPS_DIR).join(format!("{:04}.json", step.index));
+    let written = serde_json::to_string_pretty(&step)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| state_crypt::write(&path, json).map_err(anyhow::Error::from));
+    if let Err(e) = written {
+        warn!("Failed to record step {}: {}", step.index, e);
+    }
+}
+
+/// Termina la registrazione e crea il bundle
+///
+/// # Arguments
+///
+/// * `result` - L'esito del sottocomando registrato
+///
+/// # Returns
+///
+/// Il percorso del bundle creato
+pub fn finish(result: &Result<i32>) -> Result<PathBuf> {
+    let recording = RECORDING.lock()
+        .map_err(|_| anyhow!("Recording state is poisoned"))?
+        .take()
+        .ok_or_else(|| anyhow!("No recording in progress"))?;
+
+    let manifest = Manifest {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        hostname: utils::get_hostname(),
+        command: recording.command.iter().map(|arg| redact::mask(arg).into_owned()).collect(),
+        started_at: recording.started_at.to_rfc3339(),
+        finished_at: Local::now().to_rfc3339(),
+        exit_code: result.as_ref().ok().copied(),
+        error: result.as_ref().err().map(|e| redact::mask(&format!("{:#}", e)).into_owned()),
+        error_kind: result.as_ref().err().map(|e| error::kind_of(e).to_string()),
+        steps: recording.steps,
+    };
+    let json = serde_json::to_string_pretty(&manifest)
+        .context("Failed to serialize the recording manifest")?;
+    state_crypt::write(&recording.staging.join(MANIFEST_FILE), json)
+        .context("Failed to write the recording manifest")?;
+
+    // Il log è già filtrato dai segreti noti al momento della scrittura
+    if let Some(log_path) = logger::get_current_log_path() {
+        let copied = fs::read(&log_path)
+            .and_then(|log| state_crypt::write(&recording.staging.join(LOG_FILE), log));
+        if let Err(e) = copied {
+            warn!("Failed to copy the log file {:?} into the recording: {}", log_path, e);
+        }
+    }
+
+    let packed = pack(&recording.staging, &recording.output);
+    if let Err(e) = fs::remove_dir_all(&recording.staging) {
+        warn!("Failed to remove the recording directory {:?}: {}", recording.staging, e);
+    }
+    packed?;
+
+    info!("Recorded {} steps into {:?}", manifest.steps, recording.output);
+    Ok(recording.output)
+}
+
+/// Crea l'archivio tar.gz del bundle
+fn pack(staging: &Path, output: &Path) -> Result<()> {
+    if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .context(format!("Failed to create directory: {:?}", parent))?;
+    }
+
+    let file = File::create(output)
+        .context(format!("Failed to create the replay bundle {:?}", output))?;
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    builder.append_dir_all(".", staging)
+        .context(format!("Failed to write the replay bundle {:?}", output))?;
+    builder.into_inner()
+        .and_then(|encoder| encoder.finish())
+        .context(format!("Failed to write the replay bundle {:?}", output))?;
+    Ok(())
+}
+
+/// Contenuto di un bundle registrato
+#[derive(Debug, Clone)]
+pub struct Bundle {
+    /// Informazioni generali sull'esecuzione
+    pub manifest: Manifest,
+
+    /// Passi registrati, in ordine
+    pub steps: Vec<Step>,
+
+    /// Log dell'esecuzione, se presente
+    pub log: Option<String>,
+}
+
+/// Legge un bundle registrato con `galatea record`
+///
+/// # Arguments
+///
+/// * `path` - Il percorso dell'archivio
+///
+/// # Returns
+///
+/// Il contenuto del bundle
+pub fn load(path: &Path) -> Result<Bundle> {
+    let file = File::open(path)
+        .context(format!("Failed to open the replay bundle {:?}", path))?;
+    let mut archive = tar::Archive::new(GzDecoder::new(file));
+
+    let mut manifest = None;
+    let mut steps = Vec::new();
+    let mut log = None;
+
+    let entries = archive.entries()
+        .context(format!("Failed to read the replay bundle {:?}", path))?;
+    for entry in entries {
+        let mut entry = entry.context(format!("Failed to read the replay bundle {:?}", path))?;
+        let name = entry.path()?.to_string_lossy().trim_start_matches("./").to_string();
+
+        let mut content = Vec::new();
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        entry.read_to_end(&mut content)
+            .context(format!("Failed to read {} from the replay bundle", name))?;
+        let content = state_crypt::decrypt(content)
+            .and_then(|content| String::from_utf8(content).map_err(anyhow::Error::from))
+            .context(format!("Failed to read {} from the replay bundle", name))?;
+
+        if name == MANIFEST_FILE {
+            manifest = Some(serde_json::from_str::<Manifest>(&content)
+                .context("Invalid manifest in the replay bundle")?);
+        } else if name == LOG_FILE {
+            log = Some(content);
+        } else if name.starts_with(STEPS_DIR) && name.ends_with(".json") {
+            steps.push(serde_json::from_str::<Step>(&content)
+                .context(format!("Invalid step {} in the replay bundle", name))?);
+        }
+    }
+
+    let manifest = manifest.ok_or_else(|| anyhow!("{:?} is not a replay bundle: {} not found", path, MANIFEST_FILE))?;
+    steps.sort_by_key(|step| step.index);
+    Ok(Bundle { manifest, steps, log })
+}
@@ -0,0 +1,224 @@
+//! Limiti di risorse per l'esecuzione dei task
+//!
+//! Un task può dichiarare la priorità di CPU e I/O e i limiti di memoria e CPU con cui
+//! eseguire i propri script, così che un'installazione pesante non sottragga risorse ai
+//! servizi in produzione:
+//!
+//! ```yaml
+//! resources:
+//!   nice: 10              # priorità di CPU, da -20 a 19
+//!   io_class: idle        # classe di I/O: realtime, best-effort, idle
+//!   io_priority: 7        # priorità nella classe, da 0 a 7
+//!   memory_max: 2G        # limite di memoria (MemoryMax di systemd)
+//!   cpu_quota: 50%        # quota di CPU (CPUQuota di systemd)
+//!   timeout: 1800         # durata massima di ogni esecuzione, in secondi
+//! ```
+//!
+//! La priorità viene applicata con `nice` e `ionice`; i limiti di memoria e CPU richiedono
+//! un cgroup e vengono applicati eseguendo lo script in uno scope systemd transitorio
+//! (`systemd-run --scope`). Allo scadere del timeout lo script viene terminato dall'executor.
+
+use std::process::Command;
+use anyhow::{Result, anyhow};
+use log::{debug, warn};
+use serde::{Serialize, Deserialize};
+
+use crate::executor;
+use crate::utils;
+
+/// Classe di scheduling dell'I/O
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IoClass {
+    /// Accesso prioritario al disco (solo root)
+    Realtime,
+
+    /// Classe predefinita dei processi
+    BestEffort,
+
+    /// Accesso al disco solo quando nessun altro lo usa
+    Idle,
+}
+
+impl IoClass {
+    /// Restituisce il nome della classe
+    pub fn to_str(self) -> &'static str {
+        match self {
+            IoClass::Realtime => "realtime",
+            IoClass::BestEffort => "best-effort",
+            IoClass::Idle => "idle",
+        }
+    }
+
+    /// Restituisce il numero della classe usato da `ionice -c`
+    fn ionice_class(&self) -> &'static str {
+        match self {
+            IoClass::Realtime => "1",
+            IoClass::BestEffort => "2",
+            IoClass::Idle => "3",
+        }
+    }
+}
+
+/// Limiti di risorse dichiarati da un task
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResourceLimits {
+    /// Priorità di CPU (nice), da -20 a 19
+    pub nice: Option<i32>,
+
+    /// Classe di scheduling dell'I/O
+    pub io_class: Option<IoClass>,
+
+    /// Priorità dell'I/O nella classe, da 0 (massima) a 7
+    pub io_priority: Option<u8>,
+
+    /// Limite di memoria, nel formato di systemd (es. `512M`, `2G`)
+    pub memory_max: Option<String>,
+
+    /// Quota di CPU, nel formato di systemd (es. `50%`, `200%` per due core)
+    pub cpu_quota: Option<String>,
+
+    /// Durata massima di uno script, playbook o comando, in secondi
+    pub timeout: Option<u64>,
+}
+
+impl ResourceLimits {
+    /// Verifica se non è stato dichiarato alcun limite
+    pub fn is_empty(&self) -> bool {
+        *self == ResourceLimits::default()
+    }
+
+    /// Verifica se sono dichiarati limiti che richiedono un cgroup
+    pub fn needs_cgroup(&self) -> bool {
+        self.memory_max.is_some() || self.cpu_quota.is_some()
+    }
+
+    /// Verifica che i valori dichiarati siano nell'intervallo ammesso
+    pub fn validate(&self) -> Result<()> {
+        if let Some(nice) = self.nice {
+            if !(-20..=19).contains(&nice) {
+                return Err(anyhow!("nice must be between -20 and 19, got {}", nice));
+            }
+        }
+
+        if let Some(priority) = self.io_priority {
+            if priority > 7 {
+                return Err(anyhow!("io_priority must be between 0 and 7, got {}", priority));
+            }
+            if self.io_class == Some(IoClass::Idle) {
+                return Err(anyhow!("io_priority has no effect with io_class idle"));
+            }
+        }
+
+        if self.timeout == Some(0) {
+            return Err(anyhow!("timeout must be greater than 0"));
+        }
+
+        for (name, value) in [("memory_max", &self.memory_max), ("cpu_quota", &self.cpu_quota)] {
+            if let Some(value) = value {
+                // I valori finiscono in un argomento di systemd-run: niente spazi né '='
+                if value.is_empty() || value.contains(|c: char| c.is_whitespace() || c == '=') {
+                    return Err(anyhow!("Invalid {} value: {:?}", name, value));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Restituisce una descrizione breve dei limiti, per i dettagli del task
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(nice) = self.nice {
+            parts.push(format!("nice {}", nice));
+        }
+        match (self.io_class, self.io_priority) {
+            (Some(class), Some(priority)) => parts.push(format!("I/O {} {}", class.to_str(), priority)),
+            (Some(class), None) => parts.push(format!("I/O {}", class.to_str())),
+            (None, Some(priority)) => parts.push(format!("I/O priorità {}", priority)),
+            (None, None) => {},
+        }
+        if let Some(memory) = &self.memory_max {
+            parts.push(format!("memoria max {}", memory));
+        }
+        if let Some(quota) = &self.cpu_quota {
+            parts.push(format!("CPU {}", quota));
+        }
+        if let Some(timeout) = self.timeout {
+            parts.push(format!("timeout {}s", timeout));
+        }
+        parts.join(", ")
+    }
+
+    /// Crea il comando che esegue un programma entro i limiti
+    ///
+    /// Senza limiti il comando è quello del programma. Altrimenti il programma viene
+    /// preceduto da `systemd-run --scope` (limiti di memoria e CPU), `ionice` e `nice`.
+    /// Gli strumenti non disponibili vengono saltati con un avviso: il task viene eseguito
+    /// comunque, senza il limite corrispondente.
+    ///
+    /// # Arguments
+    ///
+    /// * `program` - Il programma da eseguire
+    /// * `elevated` - Se il comando verrà eseguito tramite sudo
+    ///
+    /// # Returns
+    ///
+    /// Il comando, a cui aggiungere gli argomenti del programma
+    pub fn command(&self, program: impl AsRef<std::ffi::OsStr>, elevated: bool) -> Command {
+        let mut prefix: Vec<String> = Vec::new();
+
+        if self.needs_cgroup() {
+            if executor::is_command_available("systemd-run") {
+                prefix.extend(["systemd-run", "--scope", "--quiet", "--collect"].map(String::from));
+                // Da utente normale lo scope viene creato nel gestore systemd dell'utente; con
+                // sudo systemd-run viene eseguito da root e usa il gestore di sistema
+                if !elevated && !utils::is_running_as_root() {
+                    prefix.push("--user".to_string());
+                }
+                if let Some(memory) = &self.memory_max {
+                    prefix.push(format!("--property=MemoryMax={}", memory));
+                }
+                if let Some(quota) = &self.cpu_quota {
+                    prefix.push(format!("--property=CPUQuota={}", quota));
+                }
+                prefix.push("--".to_string());
+            } else {
+                warn!("systemd-run not available: memory and CPU limits are not applied");
+            }
+        }
+
+        if self.io_class.is_some() || self.io_priority.is_some() {
+            if executor::is_command_available("ionice") {
+                prefix.push("ionice".to_string());
+                if let Some(class) = self.io_class {
+                    prefix.push(format!("--class={}", class.ionice_class()));
+                }
+                if let Some(priority) = self.io_priority {
+                    prefix.push(format!("--classdata={}", priority));
+                }
+            } else {
+                warn!("ionice not available: I/O priority is not applied");
+            }
+        }
+
+        if let Some(nice) = self.nice {
+            if executor::is_command_available("nice") {
+                prefix.push("nice".to_string());
+                prefix.push(format!("--adjustment={}", nice));
+            } else {
+                warn!("nice not available: CPU priority is not applied");
+            }
+        }
+
+        let Some((first, rest)) = prefix.split_first() else {
+            return Command::new(program);
+        };
+
+        debug!("Applying resource limits: {}", prefix.join(" "));
+        let mut command = Command::new(first);
+        command.args(rest).arg(program);
+        command
+    }
+}
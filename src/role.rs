@@ -0,0 +1,168 @@
+//! Ruoli della macchina e convergenza allo stato desiderato
+//!
+//! Un ruolo è un insieme di stack definito in configurazione (`roles`) e assegnato
+//! alla macchina con `role`. L'applicazione del ruolo installa gli stack mancanti e
+//! segnala gli stack e i task installati che il ruolo non prevede.
+
+use anyhow::{Context, Result, anyhow};
+use log::{info, warn, error};
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::source;
+use crate::stack::{StackReport, load_stacks};
+use crate::task::load_tasks;
+
+/// Risultato dell'applicazione di un ruolo
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ApplyReport {
+    /// Nome del ruolo applicato
+    pub role: String,
+
+    /// Stack installati (o da installare, in modalità di prova)
+    pub installed: Vec<String>,
+
+    /// Stack del ruolo già completamente installati
+    pub already_installed: Vec<String>,
+
+    /// Stack la cui installazione è fallita, con il relativo errore
+    pub failed: Vec<(String, String)>,
+
+    /// Riferimenti del ruolo che non corrispondono ad alcuno stack del catalogo
+    pub unknown: Vec<String>,
+
+    /// Stack installati, anche parzialmente, che non fanno parte del ruolo
+    pub extra_stacks: Vec<String>,
+
+    /// Task installati che non fanno parte di alcuno stack del ruolo
+    pub extra_tasks: Vec<String>,
+
+    /// Task installati che la configurazione esclude da questa macchina (`denied_tasks`, `denied_tags`)
+    pub denied_installed: Vec<String>,
+
+    /// Risultati per task degli stack installati
+    pub stack_reports: Vec<StackReport>,
+}
+
+impl ApplyReport {
+    /// Verifica se la macchina è conforme al ruolo (gli extra non sono considerati errori)
+    pub fn is_converged(&self) -> bool {
+        self.failed.is_empty() && self.unknown.is_empty()
+    }
+
+    /// Serializza il report in JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize apply report")
+    }
+}
+
+/// Restituisce il ruolo da applicare e gli stack che lo compongono
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+/// * `role` - Il ruolo richiesto; se assente viene usato quello assegnato in configurazione
+///
+/// # Returns
+///
+/// Il nome del ruolo e i riferimenti agli stack
+pub fn role_stacks(config: &Config, role: Option<&str>) -> Result<(String, Vec<String>)> {
+    let role = role
+        .or(config.role.as_deref())
+        .ok_or_else(|| anyhow!("No role assigned to this machine: set 'role' in the configuration or use --role"))?;
+
+    let stacks = config.roles.get(role)
+        .ok_or_else(|| anyhow!("Unknown role: {} (not defined in 'roles')", role))?;
+
+    Ok((role.to_string(), stacks.clone()))
+}
+
+/// Porta la macchina allo stato previsto dal suo ruolo
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+/// * `role` - Il ruolo da applicare; se assente viene usato quello assegnato in configurazione
+/// * `dry_run` - Se `true` non installa nulla e riporta solo le differenze
+///
+/// # Returns
+///
+/// Il report delle operazioni eseguite
+pub fn apply_role(config: &Config, role: Option<&str>, dry_run: bool) -> Result<ApplyReport> {
+    let (role, stack_refs) = role_stacks(config, role)?;
+    info!("Applying role {} ({} stacks, dry run: {})", role, stack_refs.len(), dry_run);
+
+    let mut tasks = load_tasks(config)?;
+    let mut stacks = load_stacks(config, &tasks)?;
+
+    let mut report = ApplyReport {
+        role: role.clone(),
+        ..Default::default()
+    };
+
+    for stack_ref in &stack_refs {
+        let Some(stack) = stacks.iter_mut().find(|s| s.matches_ref(stack_ref)) else {
+            warn!("Stack {} required by role {} not found", stack_ref, role);
+            report.unknown.push(stack_ref.clone());
+            continue;
+        };
+
+        if stack.fully_installed {
+            report.already_installed.push(stack.name.clone());
+            continue;
+        }
+
+        // Anche in prova uno stack con task esclusi risulta fallito, come nell'installazione
+        if let Err(e) = stack.check_denied_tasks(config, &tasks) {
+            report.failed.push((stack.name.clone(), format!("{:#}", e)));
+            continue;
+        }
+
+        if dry_run {
+            report.installed.push(stack.name.clone());
+            continue;
+        }
+
+        match stack.install(config, &mut tasks) {
+            Ok(_) => report.installed.push(stack.name.clone()),
+            Err(e) => {
+                error!("Failed to install stack {} for role {}: {}", stack.name, role, e);
+                report.failed.push((stack.name.clone(), format!("{:#}", e)));
+            }
+        }
+        report.stack_reports.extend(stack.last_report.take());
+    }
+
+    // Gli stack del ruolo e i loro task definiscono lo stato desiderato
+    let is_role_stack = |name: &str, source: &str| {
+        stack_refs.iter().any(|r| source::matches_reference(r, source, name))
+    };
+
+    for stack in &mut stacks {
+        stack.check_installation_status(&tasks)?;
+        if (stack.fully_installed || stack.partially_installed) && !is_role_stack(&stack.name, &stack.source) {
+            report.extra_stacks.push(stack.qualified_name());
+        }
+    }
+
+    let role_tasks: Vec<&String> = stacks.iter()
+        .filter(|s| is_role_stack(&s.name, &s.source))
+        .flat_map(|s| s.task_names.iter())
+        .collect();
+
+    for task in &tasks {
+        if task.installed && !role_tasks.iter().any(|r| task.matches_ref(r)) {
+            report.extra_tasks.push(task.qualified_name());
+        }
+        if task.installed && task.denied.is_some() {
+            report.denied_installed.push(task.qualified_name());
+        }
+    }
+
+    info!(
+        "Role {} applied: {} installed, {} already installed, {} failed, {} unknown",
+        role, report.installed.len(), report.already_installed.len(), report.failed.len(), report.unknown.len()
+    );
+
+    Ok(report)
+}
@@ -0,0 +1,535 @@
+//! Rollout degli stack su più host
+//!
+//! Questo modulo applica gli stack a un elenco di host via SSH, a lotti: gli host di un
+//! lotto vengono gestiti in parallelo, tra un lotto e l'altro si può attendere una pausa e
+//! il rollout si interrompe quando la percentuale di host falliti supera la soglia.
+//! Su ogni host viene eseguito lo script di bootstrap, che installa Galatea se manca e
+//! lancia `galatea apply`.
+//!
+//! Prima del rollout un controllo preliminare verifica su ogni host la raggiungibilità via
+//! SSH, i permessi sudo e la presenza di python e ansible; gli host che non lo superano
+//! interrompono il rollout o vengono saltati, secondo la politica scelta.
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use anyhow::{Context, Result, anyhow};
+use log::{debug, info, warn, error};
+
+use crate::bootstrap::{self, BootstrapFormat, BootstrapOptions};
+use crate::config::Config;
+use crate::events;
+use crate::stack::load_stacks;
+use crate::task::{ScriptType, load_tasks};
+
+/// Righe finali dell'output di un host riportate in caso di errore
+const ERROR_OUTPUT_LINES: usize = 5;
+
+/// Timeout della connessione SSH del controllo preliminare, in secondi
+const PREFLIGHT_CONNECT_TIMEOUT: u64 = 10;
+
+/// Script eseguito sugli host dal controllo preliminare
+const PREFLIGHT_SCRIPT: &str = "echo \"python=$(command -v python3 || command -v python || true)\"; \
+    echo \"ansible=$(command -v ansible-playbook || true)\"; \
+    if [ \"$(id -u)\" = 0 ] || sudo -n true 2>/dev/null; then echo sudo=yes; else echo sudo=no; fi";
+
+/// Comportamento del rollout verso gli host che non superano il controllo preliminare
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreflightPolicy {
+    /// Non esegue il rollout se un host non supera il controllo
+    Abort,
+
+    /// Salta gli host che non superano il controllo
+    Skip,
+
+    /// Non esegue il controllo preliminare
+    Off,
+}
+
+impl PreflightPolicy {
+    /// Converte una stringa nella politica corrispondente
+    pub fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "abort" => Ok(PreflightPolicy::Abort),
+            "skip" => Ok(PreflightPolicy::Skip),
+            "off" | "none" => Ok(PreflightPolicy::Off),
+            _ => Err(anyhow!("Invalid preflight policy: {}", s)),
+        }
+    }
+}
+
+/// Risultato del controllo preliminare su un host
+#[derive(Debug, Clone, Default)]
+pub struct HostPreflight {
+    /// Nome dell'host
+    pub host: String,
+
+    /// L'host risponde via SSH
+    pub reachable: bool,
+
+    /// L'utente può usare sudo senza password (o è root)
+    pub sudo: bool,
+
+    /// Percorso dell'interprete python, se presente
+    pub python: Option<String>,
+
+    /// Percorso di ansible-playbook, se presente
+    pub ansible: Option<String>,
+
+    /// Problemi che impediscono il rollout sull'host
+    pub problems: Vec<String>,
+}
+
+impl HostPreflight {
+    /// Verifica se l'host ha superato il controllo
+    pub fn passed(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Dimensione di un lotto
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchSize {
+    /// Numero fisso di host
+    Hosts(usize),
+
+    /// Percentuale degli host totali
+    Percent(u8),
+}
+
+impl BatchSize {
+    /// Converte una stringa ("5" o "25%") nella dimensione del lotto
+    pub fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let size = match s.strip_suffix('%') {
+            Some(percent) => BatchSize::Percent(percent.trim().parse::<u8>()
+                .ok()
+                .filter(|p| (1..=100).contains(p))
+                .ok_or_else(|| anyhow!("Invalid batch percentage: {}", s))?),
+            None => BatchSize::Hosts(s.parse::<usize>()
+                .ok()
+                .filter(|n| *n > 0)
+                .ok_or_else(|| anyhow!("Invalid batch size: {}", s))?),
+        };
+        Ok(size)
+    }
+
+    /// Restituisce il numero di host per lotto, almeno uno
+    pub fn hosts_per_batch(&self, total: usize) -> usize {
+        match self {
+            BatchSize::Hosts(n) => *n,
+            BatchSize::Percent(p) => (total * *p as usize).div_ceil(100),
+        }.max(1)
+    }
+}
+
+/// Opzioni del rollout
+#[derive(Debug, Clone)]
+pub struct RolloutOptions {
+    /// Host di destinazione, nella forma accettata da ssh ([utente@]host)
+    pub hosts: Vec<String>,
+
+    /// Stack da applicare
+    pub stacks: Vec<String>,
+
+    /// Dimensione dei lotti
+    pub batch_size: BatchSize,
+
+    /// Percentuale massima di host falliti prima di interrompere il rollout
+    pub max_fail_percent: u8,
+
+    /// Pausa tra un lotto e il successivo
+    pub pause: Duration,
+
+    /// URL dell'archivio di Galatea installato sugli host che non lo hanno
+    pub release_url: String,
+
+    /// Opzioni aggiuntive passate a ssh (es. "-p 2222")
+    pub ssh_options: Vec<String>,
+
+    /// Politica del controllo preliminare
+    pub preflight: PreflightPolicy,
+}
+
+/// Esito del rollout su un host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostStatus {
+    Succeeded,
+    Failed,
+    /// L'host non è stato raggiunto: rollout interrotto o controllo preliminare fallito
+    Skipped,
+}
+
+impl HostStatus {
+    /// Restituisce l'etichetta da mostrare all'utente
+    pub fn label(&self) -> &'static str {
+        match self {
+            HostStatus::Succeeded => "completato",
+            HostStatus::Failed => "fallito",
+            HostStatus::Skipped => "saltato",
+        }
+    }
+}
+
+/// Risultato del rollout su un host
+#[derive(Debug, Clone)]
+pub struct HostResult {
+    /// Nome dell'host
+    pub host: String,
+
+    /// Lotto in cui l'host è stato gestito (da 1; 0 se escluso dal controllo preliminare)
+    pub batch: usize,
+
+    /// Esito
+    pub status: HostStatus,
+
+    /// Durata dell'applicazione sull'host
+    pub duration: Duration,
+
+    /// Motivo del fallimento, con le ultime righe dell'output
+    pub error: Option<String>,
+}
+
+/// Risultato complessivo del rollout
+#[derive(Debug, Clone, Default)]
+pub struct RolloutReport {
+    /// Risultati del controllo preliminare, se eseguito
+    pub preflight: Vec<HostPreflight>,
+
+    /// Risultati per host, nell'ordine dell'elenco
+    pub hosts: Vec<HostResult>,
+
+    /// Indica se il rollout è stato interrotto (soglia di fallimenti o controllo preliminare)
+    pub aborted: bool,
+}
+
+impl RolloutReport {
+    /// Conta gli host con l'esito indicato
+    pub fn count(&self, status: HostStatus) -> usize {
+        self.hosts.iter().filter(|h| h.status == status).count()
+    }
+
+    /// Verifica se tutti gli host sono stati configurati
+    pub fn is_ok(&self) -> bool {
+        self.hosts.iter().all(|h| h.status == HostStatus::Succeeded)
+    }
+}
+
+/// Legge un file di host: un host per riga, ignorando commenti e intestazioni di gruppo
+///
+/// Sono accettati anche gli inventari INI di Ansible: per ogni riga viene usato solo il
+/// primo campo e `ansible_host=` sostituisce il nome se presente.
+pub fn read_hosts_file(path: &Path) -> Result<Vec<String>> {
+    let content = fs::read_to_string(path)
+        .context(format!("Failed to read hosts file: {:?}", path))?;
+
+    let mut hosts = Vec::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') || line.starts_with('[') {
+            continue;
+        }
+
+        let mut fields = line.split_whitespace();
+        let name = fields.next().unwrap_or_default();
+        let host = fields
+            .find_map(|f| f.strip_prefix("ansible_host="))
+            .unwrap_or(name);
+        if !hosts.iter().any(|h| h == host) {
+            hosts.push(host.to_string());
+        }
+    }
+
+    Ok(hosts)
+}
+
+/// Esegue il rollout degli stack sugli host
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente, da cui vengono riprese le sorgenti del catalogo
+/// * `options` - Le opzioni del rollout
+///
+/// # Returns
+///
+/// Il report con l'esito di ogni host
+pub fn rollout(config: &Config, options: &RolloutOptions) -> Result<RolloutReport> {
+    if options.hosts.is_empty() {
+        return Err(anyhow!("No hosts to roll out to"));
+    }
+
+    let script = bootstrap::bootstrap_script(config, &BootstrapOptions {
+        stacks: options.stacks.clone(),
+        format: BootstrapFormat::Shell,
+        release_url: options.release_url.clone(),
+        install_service: false,
+    })?;
+
+    let mut report = RolloutReport::default();
+    let mut hosts = options.hosts.clone();
+
+    if options.preflight != PreflightPolicy::Off {
+        let requires_ansible = stacks_require_ansible(config, &options.stacks)?;
+        report.preflight = preflight(&options.hosts, &options.ssh_options, requires_ansible);
+
+        let failed: Vec<String> = report.preflight.iter()
+            .filter(|p| !p.passed())
+            .map(|p| p.host.clone())
+            .collect();
+
+        if !failed.is_empty() {
+            if options.preflight == PreflightPolicy::Abort {
+                error!("Rollout aborted: {} hosts failed the preflight check", failed.len());
+                report.aborted = true;
+                report.hosts = options.hosts.iter()
+                    .map(|host| skipped_by_preflight(host, &report.preflight))
+                    .collect();
+                return Ok(report);
+            }
+
+            warn!("Skipping {} hosts that failed the preflight check", failed.len());
+            report.hosts = failed.iter()
+                .map(|host| skipped_by_preflight(host, &report.preflight))
+                .collect();
+            hosts.retain(|host| !failed.contains(host));
+        }
+    }
+
+    let total = hosts.len();
+    if total == 0 {
+        return Ok(report);
+    }
+    let per_batch = options.batch_size.hosts_per_batch(total);
+    let batches: Vec<&[String]> = hosts.chunks(per_batch).collect();
+    let label = format!("Rollout {}", options.stacks.join(", "));
+    info!("Rolling out {} to {} hosts in {} batches of {}", options.stacks.join(", "), total, batches.len(), per_batch);
+
+    // Gli host saltati dal controllo preliminare non contano per la soglia dei fallimenti
+    let offset = report.hosts.len();
+
+    for (index, batch) in batches.iter().enumerate() {
+        let number = index + 1;
+
+        if report.aborted {
+            report.hosts.extend(batch.iter().map(|host| HostResult {
+                host: host.clone(),
+                batch: number,
+                status: HostStatus::Skipped,
+                duration: Duration::ZERO,
+                error: None,
+            }));
+            continue;
+        }
+
+        if index > 0 && !options.pause.is_zero() {
+            info!("Pausing {}s before batch {}", options.pause.as_secs(), number);
+            thread::sleep(options.pause);
+        }
+
+        info!("Starting batch {}/{}: {}", number, batches.len(), batch.join(", "));
+        events::log_line(log::Level::Info, format!("Lotto {}/{}: {}", number, batches.len(), batch.join(", ")));
+
+        // Gli host del lotto vengono configurati in parallelo
+        let results: Vec<HostResult> = thread::scope(|scope| {
+            let handles: Vec<_> = batch.iter()
+                .map(|host| scope.spawn(|| apply_on_host(host, number, &script, &options.ssh_options)))
+                .collect();
+            handles.into_iter()
+                .zip(batch.iter())
+                .map(|(handle, host)| handle.join().unwrap_or_else(|_| HostResult {
+                    host: host.clone(),
+                    batch: number,
+                    status: HostStatus::Failed,
+                    duration: Duration::ZERO,
+                    error: Some("rollout thread panicked".to_string()),
+                }))
+                .collect()
+        });
+        report.hosts.extend(results);
+
+        let done = report.hosts.len() - offset;
+        events::progress(label.as_str(), format!("{}/{} host", done, total), Some(done as f64 * 100.0 / total as f64), done == total);
+
+        // La soglia si applica agli host gestiti finora
+        let failed = report.count(HostStatus::Failed);
+        let fail_percent = failed * 100 / done;
+        if failed > 0 && fail_percent > options.max_fail_percent as usize {
+            error!("Rollout aborted after batch {}: {}% of hosts failed (max {}%)", number, fail_percent, options.max_fail_percent);
+            report.aborted = true;
+        }
+    }
+
+    if report.aborted {
+        events::progress(label.as_str(), "interrotto", None, true);
+    }
+
+    info!("Rollout finished: {} succeeded, {} failed, {} skipped",
+          report.count(HostStatus::Succeeded), report.count(HostStatus::Failed), report.count(HostStatus::Skipped));
+
+    Ok(report)
+}
+
+/// Costruisce il risultato di un host non gestito a causa del controllo preliminare
+fn skipped_by_preflight(host: &str, preflight: &[HostPreflight]) -> HostResult {
+    let error = preflight.iter()
+        .find(|p| p.host == host && !p.passed())
+        .map(|p| format!("controllo preliminare: {}", p.problems.join(", ")));
+
+    HostResult {
+        host: host.to_string(),
+        batch: 0,
+        status: HostStatus::Skipped,
+        duration: Duration::ZERO,
+        error,
+    }
+}
+
+/// Verifica se gli stack contengono task che richiedono ansible sull'host
+pub fn stacks_require_ansible(config: &Config, stack_refs: &[String]) -> Result<bool> {
+    let tasks = load_tasks(config)?;
+    let stacks = load_stacks(config, &tasks)?;
+
+    let requires = stacks.iter()
+        .filter(|stack| stack_refs.iter().any(|r| stack.matches_ref(r)))
+        .flat_map(|stack| stack.task_names.iter())
+        .filter_map(|name| tasks.iter().find(|t| t.matches_ref(name)))
+        .any(|task| task.script_type != ScriptType::Bash);
+    Ok(requires)
+}
+
+/// Esegue il controllo preliminare su tutti gli host, in parallelo
+///
+/// # Arguments
+///
+/// * `hosts` - Gli host da verificare
+/// * `ssh_options` - Le opzioni aggiuntive per ssh
+/// * `requires_ansible` - Indica se gli stack richiedono ansible-playbook sugli host
+///
+/// # Returns
+///
+/// Il risultato del controllo per ogni host, nell'ordine dell'elenco
+pub fn preflight(hosts: &[String], ssh_options: &[String], requires_ansible: bool) -> Vec<HostPreflight> {
+    info!("Running preflight check on {} hosts", hosts.len());
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = hosts.iter()
+            .map(|host| scope.spawn(move || preflight_host(host, ssh_options, requires_ansible)))
+            .collect();
+        handles.into_iter()
+            .zip(hosts.iter())
+            .map(|(handle, host)| handle.join().unwrap_or_else(|_| HostPreflight {
+                host: host.clone(),
+                problems: vec!["controllo interrotto".to_string()],
+                ..Default::default()
+            }))
+            .collect()
+    })
+}
+
+/// Verifica raggiungibilità, sudo, python e ansible su un host
+fn preflight_host(host: &str, ssh_options: &[String], requires_ansible: bool) -> HostPreflight {
+    let mut result = HostPreflight { host: host.to_string(), ..Default::default() };
+
+    let output = Command::new("ssh")
+        .args(["-o", "BatchMode=yes", "-o", &format!("ConnectTimeout={}", PREFLIGHT_CONNECT_TIMEOUT)])
+        .args(ssh_options)
+        .arg(host)
+        .arg(PREFLIGHT_SCRIPT)
+        .stdin(Stdio::null())
+        .output();
+
+    let output = match output {
+        Ok(output) => output,
+        Err(e) => {
+            result.problems.push(format!("ssh non eseguibile: {}", e));
+            return result;
+        }
+    };
+
+    // ssh restituisce 255 quando la connessione fallisce
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("").trim().to_string();
+        result.problems.push(format!("non raggiungibile via SSH ({})", reason));
+        return result;
+    }
+    result.reachable = true;
+
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let Some((key, value)) = line.split_once('=') else { continue };
+        let value = value.trim();
+        match key.trim() {
+            "python" if !value.is_empty() => result.python = Some(value.to_string()),
+            "ansible" if !value.is_empty() => result.ansible = Some(value.to_string()),
+            "sudo" => result.sudo = value == "yes",
+            _ => {}
+        }
+    }
+
+    if !result.sudo {
+        result.problems.push("sudo richiede una password".to_string());
+    }
+    if requires_ansible && result.ansible.is_none() {
+        result.problems.push("ansible-playbook non installato".to_string());
+    }
+    if requires_ansible && result.python.is_none() {
+        result.problems.push("python non installato".to_string());
+    }
+
+    debug!("Preflight on {}: {:?}", host, result);
+    result
+}
+
+/// Applica gli stack a un host eseguendo lo script di bootstrap via SSH
+fn apply_on_host(host: &str, batch: usize, script: &str, ssh_options: &[String]) -> HostResult {
+    let started = Instant::now();
+    info!("Applying stacks on {}", host);
+
+    let result = run_ssh_script(host, script, ssh_options);
+    let duration = started.elapsed();
+
+    match result {
+        Ok(()) => {
+            info!("Host {} configured in {}s", host, duration.as_secs());
+            HostResult { host: host.to_string(), batch, status: HostStatus::Succeeded, duration, error: None }
+        },
+        Err(e) => {
+            warn!("Rollout failed on {}: {:#}", host, e);
+            HostResult { host: host.to_string(), batch, status: HostStatus::Failed, duration, error: Some(format!("{:#}", e)) }
+        },
+    }
+}
+
+/// Invia lo script a `sudo sh -s` sull'host e ne attende la fine
+fn run_ssh_script(host: &str, script: &str, ssh_options: &[String]) -> Result<()> {
+    let mut child = Command::new("ssh")
+        .args(["-o", "BatchMode=yes"])
+        .args(ssh_options)
+        .arg(host)
+        .arg("sudo -n sh -s")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run ssh")?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(script.as_bytes())
+            .context(format!("Failed to send bootstrap script to {}", host))?;
+    }
+
+    let output = child.wait_with_output()
+        .context(format!("Failed to wait for ssh on {}", host))?;
+
+    if !output.status.success() {
+        let combined = format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+        let lines: Vec<&str> = combined.lines().filter(|l| !l.trim().is_empty()).collect();
+        let tail = lines[lines.len().saturating_sub(ERROR_OUTPUT_LINES)..].join(" | ");
+        return Err(anyhow!("exit status {}: {}", output.status.code().unwrap_or(-1), tail));
+    }
+
+    Ok(())
+}
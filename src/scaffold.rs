@@ -0,0 +1,188 @@
+//! Generazione dello scheletro di nuovi bundle di task
+//!
+//! Questo modulo crea la struttura di base di un bundle (script bash e/o playbook
+//! ansible con i punti di ingresso standard) e la relativa voce di catalogo.
+
+use std::path::{Path, PathBuf};
+use std::fs;
+use anyhow::{Context, Result, anyhow};
+use log::info;
+
+use crate::task::ScriptType;
+
+/// Segnaposto sostituito con il nome del task nei template
+const NAME_PLACEHOLDER: &str = "__TASK_NAME__";
+
+/// Segnaposto sostituito con il tipo di script nei template
+const TYPE_PLACEHOLDER: &str = "__TASK_TYPE__";
+
+/// Template dello script bash con i punti di ingresso standard
+const BASH_TEMPLATE: &str = r#"#!/bin/bash
+# Bundle Galatea: __TASK_NAME__
+# Punti di ingresso standard: install, uninstall, reset, remediate
+# Esito facoltativo: scrivi in "$GALATEA_RESULT_FILE" un JSON con status (ok, skipped,
+# failed), changed, message e reboot_required
+# Contesto: GALATEA_TASK_NAME, GALATEA_ACTION, GALATEA_RUN_ID, GALATEA_STATE_DIR
+set -euo pipefail
+
+ACTION="${1:-install}"
+
+do_install() {
+    echo "[__TASK_NAME__] install"
+}
+
+do_uninstall() {
+    echo "[__TASK_NAME__] uninstall"
+}
+
+do_reset() {
+    echo "[__TASK_NAME__] reset"
+}
+
+do_remediate() {
+    echo "[__TASK_NAME__] remediate"
+}
+
+case "$ACTION" in
+    install) do_install ;;
+    uninstall) do_uninstall ;;
+    reset) do_reset ;;
+    remediate) do_remediate ;;
+    *)
+        echo "Azione non supportata: $ACTION" >&2
+        echo "Uso: $0 {install|uninstall|reset|remediate}" >&2
+        exit 2
+        ;;
+esac
+"#;
+
+/// Template del playbook ansible con un blocco per ogni tag standard
+const ANSIBLE_TEMPLATE: &str = r#"---
+# Bundle Galatea: __TASK_NAME__
+# Galatea seleziona l'azione tramite --tags (install, uninstall, reset, remediate).
+# Il tag "never" impedisce l'esecuzione dei blocchi quando nessun tag è specificato.
+- name: __TASK_NAME__
+  hosts: all
+  become: yes
+  tasks:
+    - name: Install __TASK_NAME__
+      ansible.builtin.debug:
+        msg: "[__TASK_NAME__] install"
+      tags: [install, never]
+
+    - name: Uninstall __TASK_NAME__
+      ansible.builtin.debug:
+        msg: "[__TASK_NAME__] uninstall"
+      tags: [uninstall, never]
+
+    - name: Reset __TASK_NAME__
+      ansible.builtin.debug:
+        msg: "[__TASK_NAME__] reset"
+      tags: [reset, never]
+
+    - name: Remediate __TASK_NAME__
+      ansible.builtin.debug:
+        msg: "[__TASK_NAME__] remediate"
+      tags: [remediate, never]
+"#;
+
+/// Template della voce di catalogo per il nuovo task
+const CATALOG_TEMPLATE: &str = r#"# Voce di catalogo per il task __TASK_NAME__
+# Aggiorna l'URL con la posizione in cui verrà pubblicato il bundle
+
+tasks:
+  - name: __TASK_NAME__
+    type: __TASK_TYPE__
+    description: "Descrizione del task __TASK_NAME__"
+    url: "https://example.com/tasks/__TASK_NAME__.tar.gz"
+    requires_reboot: false
+    tags:
+      - __TASK_TYPE__
+"#;
+
+/// Crea lo scheletro di un bundle di task
+///
+/// # Arguments
+///
+/// * `name` - Il nome del task
+/// * `script_type` - Il tipo di script del task
+/// * `output_dir` - La directory in cui creare il bundle
+///
+/// # Returns
+///
+/// Il percorso della directory del bundle creato
+pub fn create_task_bundle(name: &str, script_type: ScriptType, output_dir: &Path) -> Result<PathBuf> {
+    validate_task_name(name)?;
+
+    // Il runner di un task wasm è un modulo compilato fuori da Galatea
+    if script_type == ScriptType::Wasm {
+        return Err(anyhow!("No template for wasm tasks: ship the runner module as runner.wasm in the bundle"));
+    }
+
+    let bundle_dir = output_dir.join(name);
+    if bundle_dir.exists() {
+        return Err(anyhow!("Bundle directory already exists: {:?}", bundle_dir));
+    }
+
+    fs::create_dir_all(&bundle_dir)
+        .context(format!("Failed to create bundle directory: {:?}", bundle_dir))?;
+
+    info!("Creating {} task bundle {} in {:?}", script_type.to_str(), name, bundle_dir);
+
+    // Script bash per i task bash e misti
+    if matches!(script_type, ScriptType::Bash | ScriptType::Mixed) {
+        let script_path = bundle_dir.join("install.sh");
+        write_template(&script_path, BASH_TEMPLATE, name, script_type)?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let mut perms = fs::metadata(&script_path)
+                .context(format!("Failed to get file permissions: {:?}", script_path))?
+                .permissions();
+
+            perms.set_mode(0o755); // rwx r-x r-x
+
+            fs::set_permissions(&script_path, perms)
+                .context(format!("Failed to set file permissions: {:?}", script_path))?;
+        }
+    }
+
+    // Playbook ansible per i task ansible e misti
+    if matches!(script_type, ScriptType::Ansible | ScriptType::Mixed) {
+        write_template(&bundle_dir.join("playbook.yml"), ANSIBLE_TEMPLATE, name, script_type)?;
+    }
+
+    // Voce di catalogo da copiare nella directory dei task o nella sorgente
+    write_template(&bundle_dir.join(format!("{}.conf", name)), CATALOG_TEMPLATE, name, script_type)?;
+
+    info!("Task bundle {} created in {:?}", name, bundle_dir);
+    Ok(bundle_dir)
+}
+
+/// Verifica che il nome del task sia utilizzabile come nome di directory e di file
+fn validate_task_name(name: &str) -> Result<()> {
+    if name.is_empty() {
+        return Err(anyhow!("Task name cannot be empty"));
+    }
+
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        return Err(anyhow!(
+            "Invalid task name: {} (allowed characters: letters, digits, '_' and '-')",
+            name
+        ));
+    }
+
+    Ok(())
+}
+
+/// Scrive un template sostituendo i segnaposto
+fn write_template(path: &Path, template: &str, name: &str, script_type: ScriptType) -> Result<()> {
+    let content = template
+        .replace(NAME_PLACEHOLDER, name)
+        .replace(TYPE_PLACEHOLDER, script_type.to_str());
+
+    fs::write(path, content)
+        .context(format!("Failed to write file: {:?}", path))
+}
@@ -0,0 +1,606 @@
+//! Esecuzioni pianificate
+//!
+//! Un'esecuzione pianificata (da cron, timer systemd o modalità demone) porta la
+//! macchina allo stato del suo ruolo, se assegnato, e verifica le derive dei task
+//! installati. Al termine il riepilogo può essere inviato per email; gli stati di ruolo,
+//! stack e task vengono confrontati con quelli dell'esecuzione precedente, così che le
+//! notifiche possano limitarsi ai cambiamenti (es. da conforme a in deriva, da fallito
+//! a installato).
+//!
+//! In modalità demone le esecuzioni si ripetono ogni `schedule_interval` minuti e lo
+//! stato viene comunicato a systemd (readiness, stato corrente e watchdog) e, se
+//! configurato, all'endpoint centrale `phone_home`. Gli stack che dichiarano
+//! `auto_remediate` vengono inoltre ripristinati secondo la propria pianificazione,
+//! indipendente da quella globale.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Local, TimeDelta};
+use log::{info, warn, error};
+use serde::{Serialize, Deserialize};
+
+use crate::config::Config;
+use crate::cron::CronExpr;
+use crate::drift::{self, ComplianceStatus, DriftReport};
+use crate::events;
+use crate::fleet;
+use crate::notify;
+use crate::role::{self, ApplyReport};
+use crate::redact;
+use crate::secrets;
+use crate::source;
+use crate::stack::{Stack, load_stacks};
+use crate::state_crypt;
+use crate::systemd;
+use crate::task::load_tasks;
+use crate::utils;
+
+/// Pianificazione del ripristino automatico di uno stack
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum RemediationSchedule {
+    /// Al più una volta all'ora
+    Hourly,
+
+    /// Al più una volta al giorno
+    Daily,
+
+    /// Nei minuti indicati da un'espressione cron
+    Cron(CronExpr),
+}
+
+impl RemediationSchedule {
+    /// Interpreta una pianificazione: `hourly`, `daily` o un'espressione cron
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim() {
+            "hourly" => Ok(RemediationSchedule::Hourly),
+            "daily" => Ok(RemediationSchedule::Daily),
+            expr => CronExpr::parse(expr)
+                .map(RemediationSchedule::Cron)
+                .map_err(|e| anyhow!("Expected hourly, daily or a cron expression: {:#}", e)),
+        }
+    }
+
+    /// Verifica se il ripristino deve essere eseguito
+    ///
+    /// # Arguments
+    ///
+    /// * `last_run` - L'ultimo ripristino automatico eseguito, se presente
+    /// * `now` - L'istante corrente
+    pub fn is_due(&self, last_run: Option<DateTime<Local>>, now: DateTime<Local>) -> bool {
+        match self {
+            RemediationSchedule::Hourly => last_run.is_none_or(|last| now - last >= TimeDelta::hours(1)),
+            RemediationSchedule::Daily => last_run.is_none_or(|last| now - last >= TimeDelta::days(1)),
+            // Al più un'esecuzione per ogni minuto che corrisponde all'espressione
+            RemediationSchedule::Cron(expr) => expr.matches(&now)
+                && last_run.is_none_or(|last| last.format("%Y%m%d%H%M").to_string() != now.format("%Y%m%d%H%M").to_string()),
+        }
+    }
+}
+
+impl fmt::Display for RemediationSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RemediationSchedule::Hourly => write!(f, "hourly"),
+            RemediationSchedule::Daily => write!(f, "daily"),
+            RemediationSchedule::Cron(expr) => write!(f, "{}", expr),
+        }
+    }
+}
+
+impl TryFrom<String> for RemediationSchedule {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        RemediationSchedule::parse(&value)
+    }
+}
+
+impl From<RemediationSchedule> for String {
+    fn from(schedule: RemediationSchedule) -> Self {
+        schedule.to_string()
+    }
+}
+
+/// Risultato di un'esecuzione pianificata
+#[derive(Debug, Clone)]
+pub struct ScheduledRunReport {
+    /// Nome host della macchina
+    pub host: String,
+
+    /// Data e ora di inizio dell'esecuzione
+    pub started_at: String,
+
+    /// Esito dell'applicazione del ruolo, se la macchina ne ha uno
+    pub apply: Option<ApplyReport>,
+
+    /// Errore che ha impedito l'applicazione del ruolo
+    pub apply_error: Option<String>,
+
+    /// Rapporto di conformità dei task installati
+    pub drift: DriftReport,
+
+    /// Cambiamenti di stato rispetto all'esecuzione precedente
+    pub transitions: Vec<Transition>,
+}
+
+impl ScheduledRunReport {
+    /// Numero di stack installati con successo durante l'esecuzione
+    pub fn success_count(&self) -> usize {
+        self.apply.as_ref().map_or(0, |apply| apply.installed.len())
+    }
+
+    /// Numero di operazioni fallite durante l'esecuzione
+    pub fn failure_count(&self) -> usize {
+        let apply_failures = self.apply.as_ref()
+            .map_or(0, |apply| apply.failed.len() + apply.unknown.len());
+        apply_failures + usize::from(self.apply_error.is_some())
+    }
+
+    /// Verifica se l'esecuzione è terminata senza errori né derive
+    pub fn is_ok(&self) -> bool {
+        self.failure_count() == 0 && !self.drift.has_drift()
+    }
+
+    /// Oggetto del messaggio di riepilogo
+    pub fn subject(&self) -> String {
+        // Un'esecuzione senza problemi dopo un fallimento segnala la risoluzione
+        let outcome = if !self.is_ok() {
+            "ATTENZIONE"
+        } else if self.transitions.iter().any(Transition::is_recovery) {
+            "RISOLTO"
+        } else {
+            "OK"
+        };
+        format!(
+            "[galatea] {}: {} ({} riusciti, {} falliti, {} in deriva)",
+            self.host,
+            outcome,
+            self.success_count(),
+            self.failure_count(),
+            self.drift.drifted
+        )
+    }
+
+    /// Stato di ogni elemento verificato dall'esecuzione, per il confronto con la precedente
+    ///
+    /// Le chiavi identificano il ruolo, gli stack e i task (`stack web`, `task local:nginx`);
+    /// i valori sono gli stati di [`Transition`].
+    pub fn statuses(&self) -> BTreeMap<String, String> {
+        let mut statuses = BTreeMap::new();
+
+        match (&self.apply, &self.apply_error) {
+            (Some(apply), _) => {
+                let role_status = if apply.is_converged() { STATUS_HEALTHY } else { STATUS_FAILED };
+                statuses.insert(format!("ruolo {}", apply.role), role_status.to_string());
+                for name in apply.installed.iter().chain(&apply.already_installed) {
+                    statuses.insert(format!("stack {}", name), STATUS_HEALTHY.to_string());
+                }
+                for (name, _) in &apply.failed {
+                    statuses.insert(format!("stack {}", name), STATUS_FAILED.to_string());
+                }
+                for name in &apply.unknown {
+                    statuses.insert(format!("stack {}", name), STATUS_MISSING.to_string());
+                }
+            },
+            (None, Some(_)) => {
+                statuses.insert("ruolo".to_string(), STATUS_FAILED.to_string());
+            },
+            (None, None) => {},
+        }
+
+        for task in &self.drift.tasks {
+            let status = match task.status {
+                ComplianceStatus::Compliant => STATUS_HEALTHY,
+                ComplianceStatus::Drifted => STATUS_DRIFTED,
+                ComplianceStatus::Unverifiable => STATUS_UNVERIFIABLE,
+            };
+            statuses.insert(format!("task {}", source::qualified_name(&task.source, &task.name)), status.to_string());
+        }
+
+        statuses
+    }
+
+    /// Testo del riepilogo dell'esecuzione
+    pub fn summary(&self) -> String {
+        let mut text = format!("Esecuzione pianificata su {} avviata il {}\n\n", self.host, self.started_at);
+
+        if !self.transitions.is_empty() {
+            text.push_str("Cambiamenti di stato:\n");
+            for transition in &self.transitions {
+                text.push_str(&format!("  {}\n", transition));
+            }
+            text.push('\n');
+        }
+
+        match (&self.apply, &self.apply_error) {
+            (Some(apply), _) => {
+                text.push_str(&format!("Ruolo {}:\n", apply.role));
+                text.push_str(&format!("  stack installati: {}\n", apply.installed.len()));
+                text.push_str(&format!("  stack già installati: {}\n", apply.already_installed.len()));
+                for (name, error) in &apply.failed {
+                    text.push_str(&format!("  FALLITO {}: {}\n", name, error));
+                }
+                for name in &apply.unknown {
+                    text.push_str(&format!("  non presente nel catalogo: {}\n", name));
+                }
+            },
+            (None, Some(error)) => text.push_str(&format!("Applicazione del ruolo fallita: {}\n", error)),
+            (None, None) => text.push_str("Nessun ruolo assegnato: solo verifica delle derive\n"),
+        }
+
+        text.push_str(&format!(
+            "\nConformità: {} conformi, {} in deriva, {} non verificabili\n",
+            self.drift.compliant, self.drift.drifted, self.drift.unverifiable
+        ));
+        for task in self.drift.tasks.iter().filter(|t| t.status == ComplianceStatus::Drifted) {
+            text.push_str(&format!(
+                "  DERIVA {}: mancante {}\n",
+                source::qualified_name(&task.source, &task.name),
+                task.missing.join(", ")
+            ));
+        }
+
+        text
+    }
+}
+
+/// Esegue un'esecuzione pianificata: applicazione del ruolo e verifica delle derive
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+///
+/// # Returns
+///
+/// Il riepilogo dell'esecuzione
+pub fn run_scheduled(config: &Config) -> Result<ScheduledRunReport> {
+    let started_at = Local::now().to_rfc3339();
+    info!("Starting scheduled run");
+
+    let (apply, apply_error) = if config.role.is_some() {
+        match role::apply_role(config, None, false) {
+            Ok(report) => (Some(report), None),
+            Err(e) => {
+                error!("Scheduled role apply failed: {:#}", e);
+                (None, Some(format!("{:#}", e)))
+            }
+        }
+    } else {
+        (None, None)
+    };
+
+    let drift = drift::check_drift(config)?;
+
+    let mut report = ScheduledRunReport {
+        host: utils::get_hostname(),
+        started_at,
+        apply,
+        apply_error,
+        drift,
+        transitions: Vec::new(),
+    };
+
+    match record_transitions(config, &report) {
+        Ok(transitions) => report.transitions = transitions,
+        Err(e) => warn!("Failed to compare with the previous scheduled run: {:#}", e),
+    }
+
+    info!("Scheduled run completed: {}", report.subject());
+    Ok(report)
+}
+
+/// Invia per email il riepilogo di un'esecuzione, se le notifiche sono configurate
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+/// * `report` - Il riepilogo dell'esecuzione
+///
+/// # Returns
+///
+/// `true` se il messaggio è stato inviato
+pub fn notify_run(config: &Config, report: &ScheduledRunReport) -> Result<bool> {
+    let Some(smtp) = &config.smtp else {
+        return Ok(false);
+    };
+
+    if smtp.only_on_change && report.transitions.is_empty() {
+        info!("No status changed since the previous scheduled run, email notification skipped (only_on_change)");
+        return Ok(false);
+    }
+
+    if !smtp.only_on_change && smtp.only_on_failure && report.is_ok() {
+        info!("Scheduled run succeeded, email notification skipped (only_on_failure)");
+        return Ok(false);
+    }
+
+    // La password può essere cifrata nella configurazione
+    let mut smtp = smtp.clone();
+    if let Some(password) = &smtp.password {
+        let password = secrets::reveal(config, password)
+            .context("Failed to decrypt SMTP password")?;
+        redact::register(&password);
+        smtp.password = Some(password);
+    }
+
+    let subject = report.subject();
+    let summary = report.summary();
+    notify::send_email(&smtp, &redact::mask(&subject), &redact::mask(&summary))?;
+    Ok(true)
+}
+
+/// Stato di un elemento senza problemi: stack installato, task conforme, ruolo applicato
+pub const STATUS_HEALTHY: &str = "healthy";
+
+/// Stato di uno stack o di un ruolo la cui installazione è fallita
+pub const STATUS_FAILED: &str = "failed";
+
+/// Stato di uno stack del ruolo assente dal catalogo
+pub const STATUS_MISSING: &str = "missing";
+
+/// Stato di un task installato che si è discostato dalle sue evidenze
+pub const STATUS_DRIFTED: &str = "drifted";
+
+/// Stato di un task installato che non dichiara evidenze
+pub const STATUS_UNVERIFIABLE: &str = "unverifiable";
+
+/// File di stato con gli stati dell'ultima esecuzione pianificata
+const STATUS_FILE: &str = "scheduled-status.json";
+
+/// Cambiamento di stato di un elemento tra due esecuzioni pianificate
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Transition {
+    /// Elemento cambiato, es. "stack web" o "task local:nginx"
+    pub item: String,
+
+    /// Stato nell'esecuzione precedente; assente per un elemento nuovo
+    pub from: Option<String>,
+
+    /// Stato nell'esecuzione corrente
+    pub to: String,
+}
+
+impl Transition {
+    /// Indica se l'elemento è tornato in salute
+    pub fn is_recovery(&self) -> bool {
+        self.to == STATUS_HEALTHY
+    }
+}
+
+impl fmt::Display for Transition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.from {
+            Some(from) => write!(f, "{}: {} -> {}", self.item, from, self.to),
+            None => write!(f, "{}: {}", self.item, self.to),
+        }
+    }
+}
+
+/// Confronta gli stati di un'esecuzione con quelli della precedente e li salva
+///
+/// Gli elementi nuovi contano come cambiamento solo se falliti o in deriva, così la prima
+/// esecuzione segnala i problemi esistenti ma non l'elenco di tutto ciò che funziona;
+/// gli elementi scomparsi (stack tolti dal ruolo, task disinstallati) vengono ignorati.
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+/// * `report` - Il riepilogo dell'esecuzione corrente
+///
+/// # Returns
+///
+/// I cambiamenti di stato, nell'ordine degli elementi
+pub fn record_transitions(config: &Config, report: &ScheduledRunReport) -> Result<Vec<Transition>> {
+    let path = config.resolve_path(STATUS_FILE, "state");
+    let previous: BTreeMap<String, String> = match state_crypt::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content)
+            .context(format!("Failed to parse {}", path.display()))?,
+        Err(_) => BTreeMap::new(),
+    };
+
+    let current = report.statuses();
+    let transitions = current.iter()
+        .filter_map(|(item, status)| {
+            let from = previous.get(item);
+            let unremarkable = status == STATUS_HEALTHY || status == STATUS_UNVERIFIABLE;
+            if from == Some(status) || (from.is_none() && unremarkable) {
+                return None;
+            }
+            Some(Transition { item: item.clone(), from: from.cloned(), to: status.clone() })
+        })
+        .collect();
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .context(format!("Failed to create directory {}", parent.display()))?;
+    }
+    state_crypt::write(&path, serde_json::to_string_pretty(&current)?)
+        .context(format!("Failed to write {}", path.display()))?;
+
+    Ok(transitions)
+}
+
+/// Intervallo con cui il demone controlla la richiesta di arresto durante l'attesa
+const DAEMON_TICK: Duration = Duration::from_secs(1);
+
+/// Esegue le esecuzioni pianificate a intervalli regolari finché non viene richiesto l'arresto
+///
+/// La configurazione viene ricaricata a ogni esecuzione, così le modifiche hanno effetto
+/// senza riavviare il servizio. Il watchdog di systemd viene alimentato durante l'attesa
+/// e, durante le esecuzioni, da ogni evento pubblicato dal core.
+///
+/// # Arguments
+///
+/// * `config_path` - Il percorso del file di configurazione specificato con --config
+/// * `stop` - Flag impostato dai gestori di segnali per richiedere l'arresto
+pub fn run_daemon(config_path: Option<&str>, stop: &AtomicBool) -> Result<()> {
+    let watchdog = systemd::watchdog_interval().map(|interval| interval / 2);
+    let last_ping = Arc::new(Mutex::new(Instant::now()));
+
+    if let Some(interval) = watchdog {
+        info!("systemd watchdog enabled, pinging every {:?}", interval);
+        let last_ping = Arc::clone(&last_ping);
+        events::subscribe(move |_| ping_watchdog(&last_ping, interval));
+    }
+
+    systemd::notify("READY=1\nSTATUS=Demone avviato")?;
+    info!("Galatea daemon started");
+
+    // Ultimo invio dello stato all'endpoint `phone_home`
+    let mut last_host_report: Option<Instant> = None;
+
+    while !stop.load(Ordering::SeqCst) {
+        systemd::notify("STATUS=Esecuzione pianificata in corso")?;
+
+        let interval_minutes = match Config::load(config_path) {
+            Ok(config) => {
+                match run_scheduled(&config) {
+                    Ok(report) => {
+                        if let Err(e) = notify_run(&config, &report) {
+                            warn!("Failed to send scheduled run summary: {:#}", e);
+                        }
+                        send_host_report(&config, &mut last_host_report);
+                        systemd::notify(&format!(
+                            "STATUS={}; prossima esecuzione tra {} minuti",
+                            report.subject(), config.schedule_interval
+                        ))?;
+                    },
+                    Err(e) => {
+                        error!("Scheduled run failed: {:#}", e);
+                        systemd::notify(&format!("STATUS=Esecuzione fallita: {:#}", e))?;
+                    }
+                }
+                config.schedule_interval
+            },
+            Err(e) => {
+                error!("Failed to load configuration for scheduled run: {:#}", e);
+                Config::default().schedule_interval
+            }
+        };
+
+        // Attende la prossima esecuzione controllando l'arresto e alimentando il watchdog;
+        // a ogni nuovo minuto vengono eseguiti i ripristini automatici degli stack
+        let deadline = Instant::now() + Duration::from_secs(interval_minutes.max(1) * 60);
+        let mut last_minute = None;
+        while Instant::now() < deadline && !stop.load(Ordering::SeqCst) {
+            if let Some(interval) = watchdog {
+                ping_watchdog(&last_ping, interval);
+            }
+
+            let minute = Local::now().format("%Y%m%d%H%M").to_string();
+            if last_minute.as_ref() != Some(&minute) {
+                last_minute = Some(minute);
+                match Config::load(config_path) {
+                    Ok(config) => {
+                        if let Err(e) = run_due_remediations(&config) {
+                            error!("Automatic remediation failed: {:#}", e);
+                        }
+                        let report_due = config.phone_home.as_ref().is_some_and(|phone_home| {
+                            last_host_report.is_none_or(|last| last.elapsed() >= Duration::from_secs(phone_home.interval.max(1) * 60))
+                        });
+                        if report_due {
+                            send_host_report(&config, &mut last_host_report);
+                        }
+                    },
+                    Err(e) => error!("Failed to load configuration for automatic remediation: {:#}", e),
+                }
+            }
+
+            thread::sleep(DAEMON_TICK);
+        }
+    }
+
+    info!("Galatea daemon stopping");
+    systemd::notify("STOPPING=1")?;
+    Ok(())
+}
+
+/// Invia lo stato della macchina all'endpoint `phone_home`, se configurato
+///
+/// Un invio fallito viene solo registrato: viene ritentato all'intervallo successivo.
+fn send_host_report(config: &Config, last_host_report: &mut Option<Instant>) {
+    if config.phone_home.is_none() {
+        return;
+    }
+    *last_host_report = Some(Instant::now());
+    if let Err(e) = fleet::phone_home(config) {
+        warn!("Failed to send host report: {:#}", e);
+    }
+}
+
+/// Ripristina gli stack installati la cui pianificazione `auto_remediate` è scaduta
+///
+/// L'istante di ogni ripristino viene salvato nello stato (`<stack>.remediated`), così
+/// un riavvio del demone non ripete subito i ripristini già eseguiti. I task in quarantena
+/// vengono saltati.
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+///
+/// # Returns
+///
+/// I nomi degli stack ripristinati, con l'esito di ciascuno
+pub fn run_due_remediations(config: &Config) -> Result<Vec<(String, Result<()>)>> {
+    let now = Local::now();
+    let mut tasks = load_tasks(config)?;
+    let mut stacks = load_stacks(config, &tasks)?;
+
+    let mut results = Vec::new();
+    for stack in stacks.iter_mut() {
+        let Some(schedule) = stack.auto_remediate.clone() else {
+            continue;
+        };
+        if !stack.fully_installed && !stack.partially_installed {
+            continue;
+        }
+        if !schedule.is_due(last_remediation(config, stack), now) {
+            continue;
+        }
+
+        info!("Automatic remediation of stack {} ({})", stack.name, schedule);
+        systemd::notify(&format!("STATUS=Ripristino automatico dello stack {}", stack.name))?;
+
+        let result = stack.remediate_scheduled(config, &mut tasks);
+        if let Err(e) = &result {
+            error!("Automatic remediation of stack {} failed: {:#}", stack.name, e);
+        }
+
+        // L'istante viene salvato anche in caso di errore, per non ripetere il ripristino a ogni minuto
+        let path = config.resolve_path(&format!("{}.remediated", stack.name), "state");
+        if let Err(e) = state_crypt::write(&path, now.to_rfc3339()) {
+            warn!("Failed to record automatic remediation of stack {}: {}", stack.name, e);
+        }
+
+        results.push((stack.name.clone(), result));
+    }
+
+    Ok(results)
+}
+
+/// Restituisce l'istante dell'ultimo ripristino automatico di uno stack
+fn last_remediation(config: &Config, stack: &Stack) -> Option<DateTime<Local>> {
+    let path = config.resolve_path(&format!("{}.remediated", stack.name), "state");
+    let content = state_crypt::read_to_string(&path).ok()?;
+    DateTime::parse_from_rfc3339(content.trim()).ok()
+        .map(|time| time.with_timezone(&Local))
+}
+
+/// Invia il segnale di vita al watchdog di systemd se è trascorso l'intervallo indicato
+fn ping_watchdog(last_ping: &Mutex<Instant>, interval: Duration) {
+    if let Ok(mut last) = last_ping.lock() {
+        if last.elapsed() >= interval {
+            if let Err(e) = systemd::notify("WATCHDOG=1") {
+                warn!("Failed to ping systemd watchdog: {:#}", e);
+            }
+            *last = Instant::now();
+        }
+    }
+}
@@ -0,0 +1,188 @@
+//! Espressioni incorporate nei cataloghi
+//!
+//! Task e stack possono contenere piccole espressioni [rhai](https://rhai.rs) che mantengono
+//! dichiarativa la logica dei cataloghi, senza doverla spostare negli script:
+//!
+//! - `when` di un task: condizione valutata prima dell'installazione; se è falsa il task
+//!   viene saltato su questa macchina;
+//! - `derived` di uno stack: variabili calcolate a partire da quelle richieste all'utente,
+//!   passate ai task come le altre;
+//! - `fact_transform` nella configurazione: script che modifica i fatti della macchina
+//!   prima che le espressioni li usino (es. ricavare il datacenter dal nome host).
+//!
+//! Le espressioni vedono le variabili in `vars` (valori stringa) e i fatti della macchina in
+//! `facts` (vedi [`facts`]). Il motore non ha accesso al file system, ai processi o alla rete
+//! e ogni valutazione ha un numero massimo di operazioni.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::thread;
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use rhai::{Dynamic, Engine, Map, Scope};
+
+use crate::config::Config;
+use crate::error::GalateaError;
+use crate::utils;
+
+/// Numero massimo di operazioni di una valutazione
+const MAX_OPERATIONS: u64 = 100_000;
+
+/// Dimensione massima di stringhe, array e mappe create dalle espressioni
+const MAX_SIZE: usize = 64 * 1024;
+
+/// Crea il motore con cui vengono valutate le espressioni
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_expr_depths(64, 32);
+    engine.set_max_string_size(MAX_SIZE);
+    engine.set_max_array_size(MAX_SIZE);
+    engine.set_max_map_size(MAX_SIZE);
+    engine.set_module_resolver(rhai::module_resolvers::DummyModuleResolver::new());
+    engine.on_print(|text| info!("Expression: {}", text));
+    engine.on_debug(|text, _, _| debug!("Expression: {}", text));
+    engine
+}
+
+/// Verifica la sintassi di un'espressione
+///
+/// # Arguments
+///
+/// * `expression` - L'espressione da verificare
+pub fn check(expression: &str) -> Result<()> {
+    engine().compile_expression(expression)
+        .map(|_| ())
+        .map_err(|e| GalateaError::Config(format!("Invalid expression `{}`: {}", expression, e)).into())
+}
+
+/// Valuta un'espressione con le variabili e i fatti indicati
+fn evaluate(expression: &str, vars: &HashMap<String, String>, facts: &Map) -> Result<Dynamic> {
+    let vars: Map = vars.iter()
+        .map(|(name, value)| (name.as_str().into(), Dynamic::from(value.clone())))
+        .collect();
+
+    let mut scope = Scope::new();
+    scope.push_constant("vars", vars);
+    scope.push_constant("facts", facts.clone());
+
+    engine().eval_expression_with_scope::<Dynamic>(&mut scope, expression)
+        .map_err(|e| GalateaError::Config(format!("Failed to evaluate `{}`: {}", expression, e)).into())
+}
+
+/// Valuta una condizione `when`
+///
+/// # Arguments
+///
+/// * `expression` - La condizione, che deve restituire un valore booleano
+/// * `vars` - Le variabili del task
+/// * `facts` - I fatti della macchina
+pub fn condition(expression: &str, vars: &HashMap<String, String>, facts: &Map) -> Result<bool> {
+    let value = evaluate(expression, vars, facts)?;
+    value.as_bool()
+        .map_err(|kind| GalateaError::Config(format!(
+            "Condition `{}` returned a {} instead of a boolean", expression, kind)).into())
+}
+
+/// Calcola le variabili derivate di uno stack
+///
+/// Le espressioni vengono valutate nell'ordine di dichiarazione: ognuna vede le variabili
+/// dello stack e quelle derivate prima di lei. I valori diversi da stringhe vengono
+/// convertiti nella loro rappresentazione testuale (es. `3306`, `true`).
+///
+/// # Arguments
+///
+/// * `derived` - Le variabili derivate, come coppie nome ed espressione
+/// * `values` - I valori delle variabili dello stack
+/// * `facts` - I fatti della macchina
+///
+/// # Returns
+///
+/// I valori delle sole variabili derivate
+pub fn derive(derived: &[(String, String)], values: &HashMap<String, String>, facts: &Map) -> Result<HashMap<String, String>> {
+    let mut vars = values.clone();
+    let mut result = HashMap::new();
+
+    for (name, expression) in derived {
+        let value = evaluate(expression, &vars, facts)
+            .context(format!("Failed to compute derived variable {}", name))?
+            .to_string();
+        vars.insert(name.clone(), value.clone());
+        result.insert(name.clone(), value);
+    }
+
+    Ok(result)
+}
+
+/// Raccoglie i fatti della macchina usati dalle espressioni
+///
+/// I fatti predefiniti sono `hostname`, `os` (nome completo), `os_id` e `os_version` (da
+/// `/etc/os-release`), `arch`, `family`, `cpus`, `memory_mb`, `user`, `root` e `role`. Se
+/// la configurazione indica `fact_transform`, lo script riceve i fatti in `facts` e la mappa
+/// che restituisce li sostituisce; in caso di errore restano quelli predefiniti.
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+pub fn facts(config: &Config) -> Map {
+    let (os_id, os_version) = os_release();
+
+    let mut facts = Map::new();
+    facts.insert("hostname".into(), utils::get_hostname().into());
+    facts.insert("os".into(), utils::get_os_name().into());
+    facts.insert("os_id".into(), os_id.into());
+    facts.insert("os_version".into(), os_version.into());
+    facts.insert("arch".into(), env::consts::ARCH.into());
+    facts.insert("family".into(), env::consts::FAMILY.into());
+    facts.insert("cpus".into(), (thread::available_parallelism().map(|n| n.get()).unwrap_or(1) as i64).into());
+    facts.insert("memory_mb".into(), memory_mb().into());
+    facts.insert("user".into(), utils::get_current_username().into());
+    facts.insert("root".into(), utils::is_running_as_root().into());
+    facts.insert("role".into(), config.role.clone().map(Dynamic::from).unwrap_or(Dynamic::UNIT));
+
+    let Some(path) = &config.fact_transform else {
+        return facts;
+    };
+
+    let transformed = fs::read_to_string(path)
+        .context(format!("Failed to read fact transform {}", path))
+        .and_then(|source| {
+            let mut scope = Scope::new();
+            scope.push("facts", facts.clone());
+            engine().eval_with_scope::<Dynamic>(&mut scope, &source)
+                .map_err(|e| anyhow::anyhow!("Fact transform {} failed: {}", path, e))
+        })
+        .and_then(|value| value.try_cast::<Map>()
+            .ok_or_else(|| anyhow::anyhow!("Fact transform {} did not return a map", path)));
+
+    match transformed {
+        Ok(transformed) => transformed,
+        Err(e) => {
+            warn!("{:#}", e);
+            facts
+        },
+    }
+}
+
+/// Restituisce l'identificativo e la versione della distribuzione da `/etc/os-release`
+fn os_release() -> (String, String) {
+    let content = fs::read_to_string("/etc/os-release").unwrap_or_default();
+    let field = |key: &str| content.lines()
+        .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+        .map(|value| value.trim_matches('"').to_string())
+        .unwrap_or_default();
+
+    (field("ID"), field("VERSION_ID"))
+}
+
+/// Restituisce la memoria totale della macchina in MiB, 0 se non determinabile
+fn memory_mb() -> i64 {
+    fs::read_to_string("/proc/meminfo")
+        .ok()
+        .and_then(|content| content.lines()
+            .find_map(|line| line.strip_prefix("MemTotal:"))
+            .and_then(|value| value.trim().trim_end_matches("kB").trim().parse::<i64>().ok()))
+        .map(|kb| kb / 1024)
+        .unwrap_or(0)
+}
@@ -0,0 +1,275 @@
+//! Segreti cifrati nella configurazione e nei cataloghi
+//!
+//! Questo modulo permette di tenere password e altri valori sensibili cifrati nei file
+//! di Galatea. Sono riconosciuti:
+//!
+//! - valori cifrati con [age](https://age-encryption.org) in formato ASCII armor;
+//! - valori Ansible Vault, anche con il tag YAML `!vault`;
+//! - interi file cifrati con [SOPS](https://github.com/getsops/sops), riconosciuti dalla
+//!   sezione `sops` e decifrati alla lettura.
+//!
+//! I valori singoli restano cifrati in memoria e su disco: vengono decifrati solo quando
+//! servono (password SMTP, variabili degli stack passate ai task).
+//!
+//! ```yaml
+//! smtp:
+//!   password: |
+//!     -----BEGIN AGE ENCRYPTED FILE-----
+//!     YWdlLWVuY3J5cHRpb24ub3JnL3YxCi0+IFgyNTUxOSBh...
+//!     -----END AGE ENCRYPTED FILE-----
+//! ```
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use anyhow::{Context, Result, anyhow};
+use log::{debug, warn};
+use serde_yaml::Value;
+
+use crate::config::Config;
+use crate::redact;
+use crate::utils;
+
+/// Intestazione dei valori cifrati con age (ASCII armor)
+pub const AGE_HEADER: &str = "-----BEGIN AGE ENCRYPTED FILE-----";
+
+/// Intestazione dei valori cifrati con Ansible Vault
+pub const VAULT_HEADER: &str = "$ANSIBLE_VAULT;";
+
+/// Tag YAML dei valori Ansible Vault
+pub const VAULT_TAG: &str = "vault";
+
+/// Variabile d'ambiente con il contenuto di un'identità age
+pub const AGE_KEY_ENV: &str = "GALATEA_AGE_KEY";
+
+/// Variabile d'ambiente con la password di Ansible Vault
+pub const VAULT_PASSWORD_ENV: &str = "GALATEA_VAULT_PASSWORD";
+
+/// Indica se un valore è cifrato
+pub fn is_encrypted(value: &str) -> bool {
+    let value = value.trim_start();
+    value.starts_with(AGE_HEADER) || value.starts_with(VAULT_HEADER)
+}
+
+/// Restituisce il valore in chiaro, decifrandolo se necessario
+///
+/// # Arguments
+///
+/// * `config` - La configurazione con le chiavi di decifratura
+/// * `value` - Il valore, cifrato o in chiaro
+///
+/// # Returns
+///
+/// Il valore in chiaro; i valori non cifrati sono restituiti invariati. I valori decifrati
+/// vengono registrati per essere mascherati nei log e nell'output
+pub fn reveal(config: &Config, value: &str) -> Result<String> {
+    let trimmed = value.trim_start();
+
+    let plain = if trimmed.starts_with(AGE_HEADER) {
+        decrypt_age(config, trimmed)?
+    } else if trimmed.starts_with(VAULT_HEADER) {
+        decrypt_vault(config, trimmed)?
+    } else {
+        return Ok(value.to_string());
+    };
+
+    redact::register(&plain);
+    Ok(plain)
+}
+
+/// Cifra un valore per i destinatari age configurati
+///
+/// # Returns
+///
+/// Il valore cifrato in ASCII armor, o `None` se non ci sono destinatari configurati
+pub fn encrypt(config: &Config, value: &str) -> Result<Option<String>> {
+    if config.age_recipients.is_empty() {
+        return Ok(None);
+    }
+
+    let mut args = vec!["--encrypt".to_string(), "--armor".to_string()];
+    for recipient in &config.age_recipients {
+        args.push("--recipient".to_string());
+        args.push(recipient.clone());
+    }
+
+    let output = run_with_input("age", &args, value)
+        .context("Failed to encrypt value with age")?;
+    Ok(Some(output))
+}
+
+/// Sostituisce i valori con tag `!vault` con il testo cifrato che contengono
+///
+/// Il testo Ansible Vault si riconosce dall'intestazione, quindi il tag non serve più e
+/// il documento può essere letto come YAML normale.
+pub fn untag(value: Value) -> Value {
+    match value {
+        Value::Tagged(tagged) if tagged.tag == VAULT_TAG => untag(tagged.value),
+        Value::Tagged(mut tagged) => {
+            tagged.value = untag(tagged.value);
+            Value::Tagged(tagged)
+        },
+        Value::Mapping(fields) => Value::Mapping(fields.into_iter()
+            .map(|(k, v)| (k, untag(v)))
+            .collect()),
+        Value::Sequence(items) => Value::Sequence(items.into_iter().map(untag).collect()),
+        other => other,
+    }
+}
+
+/// Indica se un documento è un file cifrato con SOPS
+pub fn is_sops_document(document: &Value) -> bool {
+    document.get("sops").is_some_and(|sops| sops.is_mapping())
+}
+
+/// Prepara un documento YAML letto da file: decifra i file SOPS e rimuove i tag `!vault`
+///
+/// # Arguments
+///
+/// * `path` - Il percorso del file
+/// * `document` - Il documento già letto
+/// * `age_identity` - L'identità age da usare con SOPS, se configurata
+pub fn open_document(path: &Path, document: Value, age_identity: Option<&str>) -> Result<Value> {
+    if !is_sops_document(&document) {
+        return Ok(untag(document));
+    }
+
+    debug!("Decrypting SOPS file {:?}", path);
+    let plain = sops_decrypt(path, age_identity)?;
+    let document: Value = serde_yaml::from_str(&plain)
+        .context(format!("Failed to parse decrypted SOPS file {:?}", path))?;
+    Ok(untag(document))
+}
+
+/// Decifra un file SOPS in formato YAML
+fn sops_decrypt(path: &Path, age_identity: Option<&str>) -> Result<String> {
+    let mut command = Command::new("sops");
+    command.args(["--decrypt", "--input-type", "yaml", "--output-type", "yaml"])
+        .arg(path)
+        .stdin(Stdio::null());
+
+    // SOPS usa la stessa identità age di Galatea, se non ne è già indicata una
+    if let Some(identity) = age_identity {
+        if std::env::var_os("SOPS_AGE_KEY_FILE").is_none() {
+            command.env("SOPS_AGE_KEY_FILE", identity);
+        }
+    }
+
+    let output = command.output()
+        .context("Failed to run sops (is it installed?)")?;
+    if !output.status.success() {
+        return Err(anyhow!("sops failed to decrypt {:?}: {}", path, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+
+    String::from_utf8(output.stdout)
+        .context(format!("Decrypted SOPS file {:?} is not valid UTF-8", path))
+}
+
+/// Decifra un valore age con l'identità configurata o quella in `GALATEA_AGE_KEY`
+fn decrypt_age(config: &Config, ciphertext: &str) -> Result<String> {
+    let key_file = match std::env::var(AGE_KEY_ENV).ok().filter(|key| !key.trim().is_empty()) {
+        Some(key) => Some(TempSecret::write("age-key", &key)?),
+        None => None,
+    };
+
+    let mut args = vec!["--decrypt".to_string()];
+    if let Some(identity) = &config.age_identity {
+        args.push("--identity".to_string());
+        args.push(identity.clone());
+    }
+    if let Some(key_file) = &key_file {
+        args.push("--identity".to_string());
+        args.push(key_file.path.to_string_lossy().to_string());
+    }
+    if args.len() == 1 {
+        return Err(anyhow!("Encrypted age value found but no identity is configured (age_identity or {})", AGE_KEY_ENV));
+    }
+
+    run_with_input("age", &args, ciphertext).context("Failed to decrypt age value")
+}
+
+/// Decifra un valore Ansible Vault con il file di password configurato o `GALATEA_VAULT_PASSWORD`
+fn decrypt_vault(config: &Config, ciphertext: &str) -> Result<String> {
+    let password_file = match std::env::var(VAULT_PASSWORD_ENV).ok().filter(|p| !p.is_empty()) {
+        Some(password) => Some(TempSecret::write("vault-password", &password)?),
+        None => None,
+    };
+
+    let password_path = match (&password_file, &config.vault_password_file) {
+        (Some(temp), _) => temp.path.to_string_lossy().to_string(),
+        (None, Some(path)) => path.clone(),
+        (None, None) => {
+            return Err(anyhow!("Ansible Vault value found but no password is configured (vault_password_file or {})", VAULT_PASSWORD_ENV));
+        },
+    };
+
+    // Il testo cifrato può arrivare indentato da un blocco YAML
+    let ciphertext: String = ciphertext.lines()
+        .map(|line| format!("{}\n", line.trim()))
+        .collect();
+
+    let args = ["decrypt", "--vault-password-file", &password_path, "--output", "-"]
+        .map(String::from);
+    run_with_input("ansible-vault", &args, &ciphertext).context("Failed to decrypt Ansible Vault value")
+}
+
+/// Esegue un comando passando il testo sullo standard input e ne restituisce l'output
+fn run_with_input(program: &str, args: &[String], input: &str) -> Result<String> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context(format!("Failed to run {} (is it installed?)", program))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input.as_bytes())
+            .context(format!("Failed to write to {}", program))?;
+    }
+
+    let output = child.wait_with_output()
+        .context(format!("Failed to wait for {}", program))?;
+    if !output.status.success() {
+        return Err(anyhow!("{} failed: {}", program, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+
+    String::from_utf8(output.stdout)
+        .context(format!("Output of {} is not valid UTF-8", program))
+}
+
+/// File temporaneo leggibile solo dal proprietario, rimosso quando esce di scope
+struct TempSecret {
+    path: PathBuf,
+}
+
+impl TempSecret {
+    /// Scrive una chiave o una password in un file temporaneo privato
+    fn write(name: &str, content: &str) -> Result<Self> {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let path = utils::get_temp_dir().join(format!("galatea-{}-{}", name, std::process::id()));
+
+        // Un file esistente (o un link) con lo stesso nome non viene mai riutilizzato
+        let _ = fs::remove_file(&path);
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+            .context(format!("Failed to create temporary file {:?}", path))?;
+        file.write_all(content.as_bytes())
+            .context(format!("Failed to write temporary file {:?}", path))?;
+
+        Ok(TempSecret { path })
+    }
+}
+
+impl Drop for TempSecret {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!("Failed to remove temporary secret file {:?}: {}", self.path, e);
+        }
+    }
+}
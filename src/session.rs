@@ -0,0 +1,137 @@
+//! Stato della sessione dell'interfaccia
+//!
+//! Ultima schermata aperta, filtro, ordinamento, elemento sotto il cursore e posizione di
+//! scorrimento delle liste vengono ricordati tra un avvio e l'altro, così l'operatore
+//! ritrova l'interfaccia dove l'aveva lasciata. Lo stato viene tenuto in memoria durante
+//! la sessione e salvato in `<state_dir>/ui_session.json` all'uscita; un file mancante o
+//! illeggibile fa semplicemente ripartire dal menu principale.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::state_crypt;
+
+/// Nome del file dello stato della sessione
+const SESSION_FILE: &str = "ui_session.json";
+
+lazy_static! {
+    static ref SESSION: Mutex<Session> = Mutex::new(Session::default());
+}
+
+/// Stato di una lista dell'interfaccia
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListState {
+    /// Filtro applicato alla lista
+    #[serde(default)]
+    pub filter: Option<String>,
+
+    /// Ordinamento della lista
+    #[serde(default)]
+    pub sort: Option<String>,
+
+    /// Riferimento qualificato dell'elemento sotto il cursore
+    #[serde(default)]
+    pub selected: Option<String>,
+
+    /// Prima riga visibile della lista
+    #[serde(default)]
+    pub scroll: usize,
+}
+
+/// Stato della sessione dell'interfaccia
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    /// Ultima schermata aperta dal menu principale
+    #[serde(default)]
+    pub screen: Option<String>,
+
+    /// Stato delle liste, per nome della lista
+    #[serde(default)]
+    pub lists: HashMap<String, ListState>,
+}
+
+/// Restituisce il percorso del file dello stato della sessione
+fn session_path(config: &Config) -> PathBuf {
+    config.resolve_path(SESSION_FILE, "state")
+}
+
+/// Carica lo stato della sessione salvato all'ultima uscita
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+///
+/// # Returns
+///
+/// Lo stato caricato, che diventa quello della sessione corrente
+pub fn load(config: &Config) -> Session {
+    let path = session_path(config);
+    let session = match state_crypt::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            warn!("Ignoring unreadable UI session state {}: {}", path.display(), e);
+            Session::default()
+        }),
+        Err(_) => Session::default(),
+    };
+
+    if let Ok(mut current) = SESSION.lock() {
+        *current = session.clone();
+    }
+    session
+}
+
+/// Salva lo stato della sessione corrente
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+pub fn save(config: &Config) -> Result<()> {
+    let session = current();
+    let path = session_path(config);
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .context(format!("Failed to create directory {}", parent.display()))?;
+    }
+    state_crypt::write(&path, serde_json::to_string_pretty(&session)?)
+        .context(format!("Failed to write UI session state {}", path.display()))
+}
+
+/// Restituisce una copia dello stato della sessione corrente
+pub fn current() -> Session {
+    SESSION.lock().map(|session| session.clone()).unwrap_or_default()
+}
+
+/// Registra l'ultima schermata aperta
+pub fn set_screen(screen: Option<&str>) {
+    if let Ok(mut session) = SESSION.lock() {
+        session.screen = screen.map(str::to_string);
+    }
+}
+
+/// Restituisce lo stato di una lista
+pub fn list_state(list: &str) -> ListState {
+    SESSION.lock()
+        .ok()
+        .and_then(|session| session.lists.get(list).cloned())
+        .unwrap_or_default()
+}
+
+/// Modifica lo stato di una lista
+///
+/// # Arguments
+///
+/// * `list` - Il nome della lista
+/// * `update` - La funzione che modifica lo stato
+pub fn update_list(list: &str, update: impl FnOnce(&mut ListState)) {
+    if let Ok(mut session) = SESSION.lock() {
+        update(session.lists.entry(list.to_string()).or_default());
+    }
+}
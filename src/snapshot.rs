@@ -0,0 +1,288 @@
+//! Snapshot del sistema prima dell'installazione di stack rischiosi
+//!
+//! Questo modulo crea e ripristina snapshot del filesystem di root tramite
+//! LVM, btrfs, ZFS o timeshift, e mantiene nello stato il punto di ripristino
+//! associato all'ultima installazione di ogni stack.
+
+use std::fs;
+use std::process::Command;
+use anyhow::{Context, Result, anyhow};
+use chrono::Local;
+use serde::{Serialize, Deserialize};
+use log::{info, warn};
+
+use crate::config::Config;
+use crate::executor;
+use crate::state_crypt;
+
+/// Directory in cui vengono creati gli snapshot btrfs
+const BTRFS_SNAPSHOT_DIR: &str = "/.snapshots";
+
+/// Backend di snapshot supportati
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SnapshotBackend {
+    /// Snapshot di volumi logici LVM
+    Lvm,
+    /// Snapshot di subvolume btrfs
+    Btrfs,
+    /// Snapshot di dataset ZFS
+    Zfs,
+    /// Snapshot gestiti da timeshift
+    Timeshift,
+}
+
+impl SnapshotBackend {
+    /// Converte una stringa nel backend corrispondente
+    pub fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "lvm" => Ok(SnapshotBackend::Lvm),
+            "btrfs" => Ok(SnapshotBackend::Btrfs),
+            "zfs" => Ok(SnapshotBackend::Zfs),
+            "timeshift" => Ok(SnapshotBackend::Timeshift),
+            _ => Err(anyhow!("Unknown snapshot backend: {}", s)),
+        }
+    }
+
+    /// Converte il backend in una stringa
+    pub fn to_str(self) -> &'static str {
+        match self {
+            SnapshotBackend::Lvm => "lvm",
+            SnapshotBackend::Btrfs => "btrfs",
+            SnapshotBackend::Zfs => "zfs",
+            SnapshotBackend::Timeshift => "timeshift",
+        }
+    }
+}
+
+/// Snapshot creato prima di un'installazione
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Backend che ha creato lo snapshot
+    pub backend: SnapshotBackend,
+
+    /// Identificativo dello snapshot (volume, percorso, dataset@nome o nome timeshift)
+    pub id: String,
+
+    /// Etichetta associata (nome dello stack)
+    pub label: String,
+
+    /// Data e ora di creazione
+    pub created_at: String,
+}
+
+/// Determina il backend di snapshot da usare in base alla configurazione
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+///
+/// # Returns
+///
+/// Il backend configurato o rilevato, oppure `None` se gli snapshot sono disabilitati o non disponibili
+pub fn resolve_backend(config: &Config) -> Result<Option<SnapshotBackend>> {
+    match config.snapshot_backend.to_lowercase().as_str() {
+        "none" | "" => Ok(None),
+        "auto" => Ok(detect_backend()),
+        other => SnapshotBackend::from_str(other).map(Some),
+    }
+}
+
+/// Rileva il backend di snapshot disponibile per il filesystem di root
+pub fn detect_backend() -> Option<SnapshotBackend> {
+    let (fstype, source) = root_filesystem().unwrap_or_default();
+
+    if fstype == "btrfs" && executor::is_command_available("btrfs") {
+        return Some(SnapshotBackend::Btrfs);
+    }
+
+    if fstype == "zfs" && executor::is_command_available("zfs") {
+        return Some(SnapshotBackend::Zfs);
+    }
+
+    if source.starts_with("/dev/mapper/") && executor::is_command_available("lvcreate") {
+        return Some(SnapshotBackend::Lvm);
+    }
+
+    if executor::is_command_available("timeshift") {
+        return Some(SnapshotBackend::Timeshift);
+    }
+
+    None
+}
+
+/// Crea uno snapshot del sistema
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+/// * `label` - L'etichetta dello snapshot (tipicamente il nome dello stack)
+///
+/// # Returns
+///
+/// Lo snapshot creato
+pub fn create_snapshot(config: &Config, label: &str) -> Result<Snapshot> {
+    let backend = resolve_backend(config)?
+        .ok_or_else(|| anyhow!("No snapshot backend available (snapshot_backend: {})", config.snapshot_backend))?;
+
+    let timestamp = Local::now().format("%Y%m%d_%H%M%S").to_string();
+    let name = format!("galatea-{}-{}", label, timestamp);
+
+    info!("Creating {} snapshot {} for {}", backend.to_str(), name, label);
+
+    let id = match backend {
+        SnapshotBackend::Lvm => {
+            let (_, source) = root_filesystem()?;
+            let lv = run_tool("lvs", &["--noheadings", "-o", "vg_name,lv_name", &source])?;
+            let parts: Vec<&str> = lv.split_whitespace().collect();
+            if parts.len() != 2 {
+                return Err(anyhow!("Unable to determine logical volume for {}", source));
+            }
+            let origin = format!("{}/{}", parts[0], parts[1]);
+            run_tool("lvcreate", &["-s", "-n", &name, "-L", &config.snapshot_lvm_size, &origin])?;
+            format!("{}/{}", parts[0], name)
+        },
+        SnapshotBackend::Btrfs => {
+            fs::create_dir_all(BTRFS_SNAPSHOT_DIR)
+                .context(format!("Failed to create snapshot directory: {}", BTRFS_SNAPSHOT_DIR))?;
+            let path = format!("{}/{}", BTRFS_SNAPSHOT_DIR, name);
+            run_tool("btrfs", &["subvolume", "snapshot", "-r", "/", &path])?;
+            path
+        },
+        SnapshotBackend::Zfs => {
+            let (_, dataset) = root_filesystem()?;
+            let id = format!("{}@{}", dataset, name);
+            run_tool("zfs", &["snapshot", &id])?;
+            id
+        },
+        SnapshotBackend::Timeshift => {
+            let comment = format!("galatea: {}", label);
+            let output = run_tool("timeshift", &["--create", "--scripted", "--comments", &comment])?;
+            parse_timeshift_snapshot_name(&output)
+                .ok_or_else(|| anyhow!("Unable to determine timeshift snapshot name"))?
+        },
+    };
+
+    info!("Snapshot created: {}", id);
+
+    Ok(Snapshot {
+        backend,
+        id,
+        label: label.to_string(),
+        created_at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    })
+}
+
+/// Ripristina uno snapshot
+///
+/// # Arguments
+///
+/// * `snapshot` - Lo snapshot da ripristinare
+///
+/// # Returns
+///
+/// `true` se il ripristino diventa effettivo solo dopo un riavvio
+pub fn restore_snapshot(snapshot: &Snapshot) -> Result<bool> {
+    info!("Restoring {} snapshot {}", snapshot.backend.to_str(), snapshot.id);
+
+    let requires_reboot = match snapshot.backend {
+        SnapshotBackend::Lvm => {
+            // Il merge del volume di root viene completato alla successiva attivazione
+            run_tool("lvconvert", &["--merge", &snapshot.id])?;
+            true
+        },
+        SnapshotBackend::Btrfs => {
+            // Crea un subvolume scrivibile dallo snapshot e lo imposta come default
+            let restore_path = format!("{}-restore", snapshot.id);
+            run_tool("btrfs", &["subvolume", "snapshot", &snapshot.id, &restore_path])?;
+            run_tool("btrfs", &["subvolume", "set-default", &restore_path])?;
+            true
+        },
+        SnapshotBackend::Zfs => {
+            run_tool("zfs", &["rollback", "-r", &snapshot.id])?;
+            false
+        },
+        SnapshotBackend::Timeshift => {
+            run_tool("timeshift", &["--restore", "--snapshot", &snapshot.id, "--scripted", "--yes"])?;
+            true
+        },
+    };
+
+    info!("Snapshot {} restored (reboot required: {})", snapshot.id, requires_reboot);
+    Ok(requires_reboot)
+}
+
+/// Salva il punto di ripristino associato a uno stack
+pub fn save_restore_point(config: &Config, stack_name: &str, snapshot: &Snapshot) -> Result<()> {
+    let path = config.resolve_path(&format!("{}.snapshot", stack_name), "state");
+    let yaml = serde_yaml::to_string(snapshot)
+        .context("Failed to serialize snapshot")?;
+
+    state_crypt::write(&path, yaml)
+        .context(format!("Failed to write restore point for stack {}", stack_name))
+}
+
+/// Carica il punto di ripristino associato a uno stack, se presente
+pub fn load_restore_point(config: &Config, stack_name: &str) -> Option<Snapshot> {
+    let path = config.resolve_path(&format!("{}.snapshot", stack_name), "state");
+    let content = state_crypt::read_to_string(&path).ok()?;
+
+    match serde_yaml::from_str(&content) {
+        Ok(snapshot) => Some(snapshot),
+        Err(e) => {
+            warn!("Invalid restore point for stack {}: {}", stack_name, e);
+            None
+        }
+    }
+}
+
+/// Rimuove il punto di ripristino associato a uno stack
+pub fn clear_restore_point(config: &Config, stack_name: &str) -> Result<()> {
+    let path = config.resolve_path(&format!("{}.snapshot", stack_name), "state");
+    if path.exists() {
+        fs::remove_file(&path)
+            .context(format!("Failed to remove restore point for stack {}", stack_name))?;
+    }
+    Ok(())
+}
+
+/// Restituisce tipo e sorgente del filesystem montato su /
+fn root_filesystem() -> Result<(String, String)> {
+    let output = run_tool("findmnt", &["-n", "-o", "FSTYPE,SOURCE", "/"])?;
+    let mut parts = output.split_whitespace();
+
+    let fstype = parts.next().unwrap_or_default().to_string();
+    // I subvolume btrfs sono riportati come /dev/sdX[/subvol]
+    let source = parts.next().unwrap_or_default()
+        .split('[').next().unwrap_or_default()
+        .to_string();
+
+    Ok((fstype, source))
+}
+
+/// Estrae il nome dello snapshot dall'output di `timeshift --create`
+fn parse_timeshift_snapshot_name(output: &str) -> Option<String> {
+    output.lines()
+        .find(|line| line.contains("Tagged snapshot"))
+        .and_then(|line| line.split('\'').nth(1))
+        .map(|name| name.to_string())
+}
+
+/// Esegue uno strumento di sistema e ne restituisce lo standard output
+fn run_tool(program: &str, args: &[&str]) -> Result<String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .context(format!("Failed to execute {}", program))?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{} failed with exit code {}: {}",
+            program,
+            output.status.code().unwrap_or(-1),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
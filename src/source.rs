@@ -0,0 +1,642 @@
+//! Sorgenti remote del catalogo di task e stack
+//!
+//! Questo modulo definisce le sorgenti da cui vengono scaricati i file di configurazione
+//! dei task e degli stack, con le opzioni di verifica che proteggono il catalogo da
+//! manomissioni: checksum SHA-256, firma GPG separata e pinning del certificato TLS.
+//!
+//! Una sorgente può essere indicata in configurazione come semplice URL oppure come
+//! mappa con un'etichetta e le opzioni di verifica:
+//!
+//! ```yaml
+//! stack_sources:
+//!   - https://example.com/stacks/base.conf
+//!   - url: https://example.com/stacks/web_server.zip
+//!     name: acme
+//!     sha256: 9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08
+//!     signature: https://example.com/stacks/web_server.zip.sig
+//!     keyring: /etc/galatea/trusted.gpg
+//!     tls_pin: "sha256:2c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae"
+//! ```
+//!
+//! Ogni sorgente può avere una propria directory del catalogo (`directory`, relativa alla
+//! directory dei task o degli stack oppure assoluta) e un proprio spazio dei nomi
+//! (`namespace`) per i bundle e i file di stato dei suoi task, così cataloghi diversi non
+//! si sovrascrivono a vicenda. Con `read_only: true` la directory è un catalogo condiviso
+//! già presente (ad esempio un mount in sola lettura) e non viene scaricato nulla:
+//!
+//! ```yaml
+//! task_sources:
+//!   - url: https://example.com/tasks/shared.zip
+//!     name: shared
+//!     directory: /mnt/catalog/tasks
+//!     namespace: shared
+//!     read_only: true
+//! ```
+
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use anyhow::{Context, Result, anyhow};
+use log::{info, debug};
+use openssl::sha::Sha256;
+use serde::{Serialize, Deserialize, Serializer};
+
+use crate::downloader;
+use crate::error::GalateaError;
+use crate::executor;
+use crate::release;
+
+/// Sorgente remota di task o stack
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "SourceEntry")]
+pub struct Source {
+    /// URL del file o dell'archivio
+    pub url: String,
+
+    /// Etichetta del catalogo, usata nei riferimenti `sorgente:nome`
+    pub name: Option<String>,
+
+    /// Checksum SHA-256 atteso del file scaricato (esadecimale)
+    pub sha256: Option<String>,
+
+    /// URL della firma GPG separata del file
+    pub signature: Option<String>,
+
+    /// Keyring con le chiavi fidate per la verifica della firma
+    pub keyring: Option<String>,
+
+    /// Impronta SHA-256 del certificato TLS atteso dal server
+    pub tls_pin: Option<String>,
+
+    /// Directory del catalogo della sorgente, relativa alla directory dei task o degli
+    /// stack oppure assoluta
+    pub directory: Option<String>,
+
+    /// Sottodirectory dei bundle e dei file di stato dei task della sorgente
+    pub namespace: Option<String>,
+
+    /// Catalogo già presente nella directory, da leggere senza scaricare nulla
+    pub read_only: bool,
+
+    /// Token per scaricare un asset di una release privata, ricavato da `release_tokens`
+    /// al momento del download e mai salvato in configurazione
+    pub token: Option<String>,
+}
+
+/// Forme accettate in configurazione per una sorgente
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SourceEntry {
+    Url(String),
+    Detailed {
+        url: String,
+        #[serde(default)]
+        name: Option<String>,
+        #[serde(default)]
+        sha256: Option<String>,
+        #[serde(default)]
+        signature: Option<String>,
+        #[serde(default)]
+        keyring: Option<String>,
+        #[serde(default)]
+        tls_pin: Option<String>,
+        #[serde(default)]
+        directory: Option<String>,
+        #[serde(default)]
+        namespace: Option<String>,
+        #[serde(default)]
+        read_only: bool,
+    },
+}
+
+impl From<SourceEntry> for Source {
+    fn from(entry: SourceEntry) -> Self {
+        match entry {
+            SourceEntry::Url(url) => Source::new(&url),
+            SourceEntry::Detailed { url, name, sha256, signature, keyring, tls_pin, directory, namespace, read_only } => Source {
+                url,
+                name,
+                sha256,
+                signature,
+                keyring,
+                tls_pin,
+                directory,
+                namespace,
+                read_only,
+                token: None,
+            },
+        }
+    }
+}
+
+/// Rappresentazione serializzata di una sorgente con opzioni di verifica
+#[derive(Serialize)]
+struct DetailedSource<'a> {
+    url: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sha256: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signature: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keyring: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tls_pin: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    directory: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    namespace: Option<&'a str>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    read_only: bool,
+}
+
+impl Serialize for Source {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        // Le sorgenti senza opzioni restano un semplice URL, come nelle versioni precedenti
+        if self.name.is_none() && !self.is_verified() && !self.has_layout() {
+            return serializer.serialize_str(&self.url);
+        }
+
+        DetailedSource {
+            url: &self.url,
+            name: self.name.as_deref(),
+            sha256: self.sha256.as_deref(),
+            signature: self.signature.as_deref(),
+            keyring: self.keyring.as_deref(),
+            tls_pin: self.tls_pin.as_deref(),
+            directory: self.directory.as_deref(),
+            namespace: self.namespace.as_deref(),
+            read_only: self.read_only,
+        }.serialize(serializer)
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.url)
+    }
+}
+
+impl Source {
+    /// Crea una sorgente senza opzioni di verifica
+    pub fn new(url: &str) -> Self {
+        Source {
+            url: url.to_string(),
+            name: None,
+            sha256: None,
+            signature: None,
+            keyring: None,
+            tls_pin: None,
+            directory: None,
+            namespace: None,
+            read_only: false,
+            token: None,
+        }
+    }
+
+    /// Indica se la sorgente ha almeno un'opzione di verifica
+    pub fn is_verified(&self) -> bool {
+        self.sha256.is_some() || self.signature.is_some() || self.tls_pin.is_some()
+    }
+
+    /// Indica se la sorgente ha una directory o uno spazio dei nomi propri
+    fn has_layout(&self) -> bool {
+        self.directory.is_some() || self.namespace.is_some() || self.read_only
+    }
+
+    /// Restituisce la directory del catalogo della sorgente
+    ///
+    /// # Arguments
+    ///
+    /// * `base_dir` - La directory dei task o degli stack
+    pub fn catalog_dir(&self, base_dir: &Path) -> PathBuf {
+        match &self.directory {
+            Some(directory) => base_dir.join(directory),
+            None => base_dir.to_path_buf(),
+        }
+    }
+
+    /// Verifica che directory e spazio dei nomi siano utilizzabili
+    ///
+    /// Lo spazio dei nomi deve restare dentro le directory di Galatea e una sorgente in
+    /// sola lettura deve indicare la directory del catalogo.
+    pub fn validate_layout(&self) -> Result<()> {
+        if let Some(namespace) = &self.namespace {
+            let path = Path::new(namespace);
+            let inside = !namespace.is_empty() && path.components()
+                .all(|c| matches!(c, std::path::Component::Normal(_)));
+            if !inside {
+                return Err(anyhow!("Invalid namespace for source {}: {} (must be a relative path without ..)", self.url, namespace));
+            }
+        }
+
+        if self.read_only && self.directory.is_none() {
+            return Err(anyhow!("Read-only source {} requires a directory", self.url));
+        }
+
+        Ok(())
+    }
+
+    /// Descrive directory e spazio dei nomi, es. "directory /mnt/catalog, sola lettura"
+    pub fn layout_summary(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(directory) = &self.directory {
+            parts.push(format!("directory {}", directory));
+        }
+        if let Some(namespace) = &self.namespace {
+            parts.push(format!("spazio dei nomi {}", namespace));
+        }
+        if self.read_only {
+            parts.push("sola lettura".to_string());
+        }
+        parts.join(", ")
+    }
+
+    /// Restituisce il nome del file indicato dall'URL
+    pub fn file_name(&self) -> Option<&str> {
+        self.url.split('/').last().filter(|name| !name.is_empty())
+    }
+
+    /// Restituisce l'etichetta del catalogo: il nome indicato in configurazione oppure
+    /// il nome del file senza estensione (es. "web_server" per web_server.zip)
+    pub fn label(&self) -> String {
+        if let Some(name) = &self.name {
+            return name.clone();
+        }
+
+        self.file_name()
+            .map(file_stem)
+            .unwrap_or_else(|| self.url.clone())
+    }
+
+    /// Descrive le verifiche attive, es. "sha256, firma, pin TLS"
+    pub fn verification_summary(&self) -> String {
+        let mut checks = Vec::new();
+        if self.sha256.is_some() {
+            checks.push("sha256");
+        }
+        if self.signature.is_some() {
+            checks.push("firma");
+        }
+        if self.tls_pin.is_some() {
+            checks.push("pin TLS");
+        }
+        checks.join(", ")
+    }
+
+    /// Verifica il file scaricato dalla sorgente
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Il percorso del file scaricato
+    /// * `timeout_secs` - Il timeout in secondi per il download della firma
+    /// * `hosts` - Gli host da cui è consentito scaricare la firma
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` se tutte le verifiche configurate hanno esito positivo, altrimenti un errore
+    pub fn verify_file(&self, path: &Path, timeout_secs: u64, hosts: &HostPolicy) -> Result<()> {
+        if let Some(expected) = &self.sha256 {
+            let actual = sha256_file(path)?;
+            if actual != normalize_fingerprint(expected) {
+                return Err(GalateaError::download(&self.url, None, format!(
+                    "Checksum mismatch for {}: expected {}, got {}",
+                    self.url, normalize_fingerprint(expected), actual
+                )).into());
+            }
+            debug!("Checksum verified for {}", self.url);
+        }
+
+        if let Some(signature_url) = &self.signature {
+            self.verify_signature(path, signature_url, timeout_secs, hosts)?;
+        }
+
+        Ok(())
+    }
+
+    /// Scarica la firma separata e la verifica con gpgv
+    fn verify_signature(&self, path: &Path, signature_url: &str, timeout_secs: u64, hosts: &HostPolicy) -> Result<()> {
+        let dir = path.parent()
+            .ok_or_else(|| anyhow!("Invalid downloaded file path: {:?}", path))?;
+
+        // La firma pubblicata come asset della stessa release richiede lo stesso token
+        let signature_token = self.token.as_deref().filter(|_| release::ReleaseAsset::parse(signature_url).is_some());
+        let signature_path = downloader::download_file_pinned(
+            signature_url,
+            dir,
+            timeout_secs,
+            self.tls_pin.as_deref(),
+            signature_token,
+            hosts,
+        ).context(format!("Failed to download signature from {}", signature_url))?;
+
+        let result = run_gpg_verify(path, &signature_path, self.keyring.as_deref());
+
+        if let Err(e) = std::fs::remove_file(&signature_path) {
+            debug!("Failed to remove signature file {:?}: {}", signature_path, e);
+        }
+
+        result.context(format!("Signature verification failed for {}", self.url))?;
+        info!("Signature verified for {}", self.url);
+        Ok(())
+    }
+}
+
+/// Host da cui è consentito scaricare bundle, cataloghi e firme
+///
+/// Protegge da voci del catalogo che puntano a server arbitrari: con `allow` non vuoto
+/// sono ammessi solo gli host indicati, mentre quelli in `deny` sono sempre rifiutati.
+/// `*.example.com` indica tutti i sottodomini di example.com (ma non example.com stesso).
+///
+/// ```yaml
+/// download_hosts:
+///   allow: [example.com, "*.example.com", github.com, objects.githubusercontent.com]
+///   deny: [untrusted.example.com]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HostPolicy {
+    /// Host consentiti; se vuoto sono consentiti tutti gli host non esclusi
+    pub allow: Vec<String>,
+
+    /// Host esclusi, anche se compaiono tra quelli consentiti
+    pub deny: Vec<String>,
+}
+
+impl HostPolicy {
+    /// Verifica se non è stata indicata alcuna restrizione
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// Verifica che un URL possa essere scaricato
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - L'URL da scaricare
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` se l'host dell'URL è consentito, altrimenti un errore di download
+    pub fn check(&self, url: &str) -> Result<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        let host = reqwest::Url::parse(url).ok()
+            .and_then(|parsed| parsed.host_str().map(|host| host.trim_end_matches('.').to_lowercase()));
+        let Some(host) = host else {
+            return Err(GalateaError::download(url, None, format!(
+                "Download of {} refused: no host to check against download_hosts", url)).into());
+        };
+
+        if let Some(rule) = self.deny.iter().find(|rule| host_matches(rule, &host)) {
+            return Err(GalateaError::download(url, None, format!(
+                "Download of {} refused: host {} is denied by download_hosts ({})", url, host, rule)).into());
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(|rule| host_matches(rule, &host)) {
+            return Err(GalateaError::download(url, None, format!(
+                "Download of {} refused: host {} is not in the download_hosts allowlist", url, host)).into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Verifica se un host corrisponde a una regola di `download_hosts`
+fn host_matches(rule: &str, host: &str) -> bool {
+    let rule = rule.trim().trim_end_matches('.').to_lowercase();
+    match rule.strip_prefix("*.") {
+        Some(domain) => host.strip_suffix(domain).is_some_and(|prefix| prefix.ends_with('.')),
+        None => host == rule,
+    }
+}
+
+/// Separatore tra etichetta del catalogo e nome nei riferimenti qualificati
+pub const REFERENCE_SEPARATOR: char = ':';
+
+/// Determina l'etichetta del catalogo di un file di configurazione
+///
+/// Se il file proviene direttamente da una delle sorgenti configurate viene usata
+/// l'etichetta della sorgente, altrimenti il nome del file senza estensione.
+///
+/// # Arguments
+///
+/// * `sources` - Le sorgenti configurate
+/// * `conf_path` - Il percorso del file di configurazione
+///
+/// # Returns
+///
+/// L'etichetta del catalogo
+pub fn catalog_label(sources: &[Source], conf_path: &Path) -> String {
+    let file_name = conf_path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    source_for_file(sources, conf_path)
+        .map(|source| source.label())
+        .unwrap_or_else(|| file_stem(&file_name))
+}
+
+/// Restituisce la sorgente da cui proviene direttamente un file di configurazione
+pub fn source_for_file<'a>(sources: &'a [Source], conf_path: &Path) -> Option<&'a Source> {
+    let file_name = conf_path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    sources.iter()
+        .find(|source| source.file_name() == Some(file_name.as_str()))
+}
+
+/// Directory da cui leggere un catalogo
+///
+/// La prima è la directory principale dei task o degli stack; seguono le directory proprie
+/// delle sorgenti, ognuna con la sorgente a cui appartiene.
+///
+/// # Arguments
+///
+/// * `sources` - Le sorgenti configurate
+/// * `base_dir` - La directory dei task o degli stack
+pub fn catalog_dirs<'a>(sources: &'a [Source], base_dir: &Path) -> Vec<(PathBuf, Option<&'a Source>)> {
+    let mut dirs = vec![(base_dir.to_path_buf(), None)];
+
+    for source in sources.iter().filter(|source| source.directory.is_some()) {
+        let dir = source.catalog_dir(base_dir);
+        if !dirs.iter().any(|(existing, _)| *existing == dir) {
+            dirs.push((dir, Some(source)));
+        }
+    }
+
+    dirs
+}
+
+/// Verifica se un riferimento (`nome` oppure `sorgente:nome`) indica un elemento
+///
+/// # Arguments
+///
+/// * `reference` - Il riferimento da verificare
+/// * `source` - L'etichetta del catalogo dell'elemento
+/// * `name` - Il nome dell'elemento
+pub fn matches_reference(reference: &str, source: &str, name: &str) -> bool {
+    match reference.split_once(REFERENCE_SEPARATOR) {
+        Some((ref_source, ref_name)) => ref_source == source && ref_name == name,
+        None => reference == name,
+    }
+}
+
+/// Restituisce il riferimento qualificato `sorgente:nome` di un elemento
+pub fn qualified_name(source: &str, name: &str) -> String {
+    if source.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}{}{}", source, REFERENCE_SEPARATOR, name)
+    }
+}
+
+/// Rimuove le estensioni note da un nome di file
+fn file_stem(file_name: &str) -> String {
+    [".tar.gz", ".tgz", ".zip", ".conf"].iter()
+        .find_map(|ext| file_name.strip_suffix(ext))
+        .unwrap_or(file_name)
+        .to_string()
+}
+
+/// Esegue gpgv (o gpg in sua assenza) per verificare una firma separata
+fn run_gpg_verify(path: &Path, signature_path: &Path, keyring: Option<&str>) -> Result<()> {
+    let use_gpgv = executor::is_command_available("gpgv");
+    if !use_gpgv && !executor::is_command_available("gpg") {
+        return Err(anyhow!("Neither gpgv nor gpg is available to verify signatures"));
+    }
+
+    let mut command = Command::new(if use_gpgv { "gpgv" } else { "gpg" });
+    if !use_gpgv {
+        command.arg("--batch");
+    }
+    if let Some(keyring) = keyring {
+        command.args(["--keyring", keyring]);
+    }
+    if !use_gpgv {
+        command.arg("--verify");
+    }
+
+    let output = command
+        .arg(signature_path)
+        .arg(path)
+        .output()
+        .context("Failed to run signature verification")?;
+
+    if !output.status.success() {
+        return Err(anyhow!("{}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+
+    Ok(())
+}
+
+/// Calcola il checksum SHA-256 di un file
+///
+/// # Arguments
+///
+/// * `path` - Il percorso del file
+///
+/// # Returns
+///
+/// Il checksum in esadecimale minuscolo
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path)
+        .context(format!("Failed to open file for checksum: {:?}", path))?;
+
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)
+            .context(format!("Failed to read file for checksum: {:?}", path))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(to_hex(&hasher.finish()))
+}
+
+/// Calcola il checksum SHA-256 di un buffer in esadecimale minuscolo
+pub fn sha256_hex(data: &[u8]) -> String {
+    to_hex(&openssl::sha::sha256(data))
+}
+
+/// Normalizza un'impronta o un checksum: rimuove il prefisso "sha256:" e i due punti
+/// e converte in minuscolo
+pub fn normalize_fingerprint(value: &str) -> String {
+    let value = value.trim();
+    let value = value.strip_prefix("sha256:")
+        .or_else(|| value.strip_prefix("SHA256:"))
+        .unwrap_or(value);
+
+    value.chars()
+        .filter(|c| *c != ':' && !c.is_whitespace())
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// Converte dei byte in esadecimale minuscolo
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(allow: &[&str], deny: &[&str]) -> HostPolicy {
+        HostPolicy {
+            allow: allow.iter().map(|rule| rule.to_string()).collect(),
+            deny: deny.iter().map(|rule| rule.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn empty_policy_allows_every_host() {
+        assert!(policy(&[], &[]).check("https://anywhere.example.org/file.zip").is_ok());
+    }
+
+    #[test]
+    fn allowlist_restricts_hosts() {
+        let hosts = policy(&["example.com"], &[]);
+        assert!(hosts.check("https://example.com/file.zip").is_ok());
+        assert!(hosts.check("https://other.com/file.zip").is_err());
+        assert!(hosts.check("https://sub.example.com/file.zip").is_err());
+    }
+
+    #[test]
+    fn deny_wins_over_allow() {
+        let hosts = policy(&["*.example.com"], &["untrusted.example.com"]);
+        assert!(hosts.check("https://cdn.example.com/file.zip").is_ok());
+        assert!(hosts.check("https://untrusted.example.com/file.zip").is_err());
+    }
+
+    #[test]
+    fn wildcard_matches_subdomains_only() {
+        let hosts = policy(&["*.example.com"], &[]);
+        assert!(hosts.check("https://a.example.com/x").is_ok());
+        assert!(hosts.check("https://a.b.example.com/x").is_ok());
+        assert!(hosts.check("https://example.com/x").is_err());
+        assert!(hosts.check("https://badexample.com/x").is_err());
+    }
+
+    #[test]
+    fn hosts_and_rules_ignore_case_and_trailing_dot() {
+        let hosts = policy(&["Example.COM."], &[]);
+        assert!(hosts.check("https://EXAMPLE.com/x").is_ok());
+        assert!(hosts.check("https://example.com./x").is_ok());
+    }
+
+    #[test]
+    fn urls_without_host_are_refused() {
+        let hosts = policy(&["example.com"], &[]);
+        assert!(hosts.check("file:///etc/passwd").is_err());
+        assert!(hosts.check("not a url").is_err());
+    }
+}
@@ -7,13 +7,201 @@ use std::path::Path;
 use std::fs;
 use std::collections::HashMap;
 use std::fmt::Display;
+use std::sync::{Condvar, Mutex, PoisonError};
+use std::thread;
+use std::time::Instant;
 use anyhow::{Context, Result, anyhow};
 use serde::{Serialize, Deserialize};
-use log::{info, warn, error};
+use log::{Level, info, warn, error};
 
+use crate::catalog_schema::{self, CatalogKind};
 use crate::config::Config;
-use crate::task::Task;
+use crate::task::{self, Task, Channel};
 use crate::downloader;
+use crate::error::{self, GalateaError};
+use crate::hooks;
+use crate::events::{self, Operation};
+use crate::executor;
+use crate::jobs;
+use crate::maintenance;
+use crate::redact;
+use crate::release;
+use crate::scheduler::RemediationSchedule;
+use crate::script;
+use crate::secrets;
+use crate::snapshot;
+use crate::source::{self, Source};
+use crate::state_crypt;
+
+/// Durata massima di ogni comando `pre_install` e `post_install` in secondi
+const ORCHESTRATION_TIMEOUT: u64 = 600;
+
+/// Variabile di uno stack, richiesta all'utente una volta per installazione
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StackVariable {
+    /// Nome della variabile, usato nei riferimenti `{{ nome }}`
+    pub name: String,
+
+    /// Testo mostrato all'utente quando viene richiesto il valore
+    pub prompt: Option<String>,
+
+    /// Valore predefinito; le variabili senza valore predefinito sono obbligatorie
+    pub default: Option<String>,
+
+    /// Variabile segreta: il valore viene nascosto e salvato cifrato
+    pub secret: bool,
+}
+
+impl StackVariable {
+    /// Restituisce il testo da mostrare nella richiesta del valore
+    pub fn prompt_text(&self) -> &str {
+        self.prompt.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// Esito di un task in un'operazione sullo stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskStatus {
+    /// Operazione riuscita
+    Succeeded,
+    /// Operazione fallita
+    Failed,
+    /// Task non presente nel catalogo
+    NotFound,
+    /// Task non eseguito perché un canary o una sua dipendenza è fallita
+    Skipped,
+}
+
+impl TaskStatus {
+    /// Restituisce l'etichetta dell'esito da mostrare all'utente
+    pub fn label(&self) -> &'static str {
+        match self {
+            TaskStatus::Succeeded => "ok",
+            TaskStatus::Failed => "fallito",
+            TaskStatus::NotFound => "non trovato",
+            TaskStatus::Skipped => "saltato",
+        }
+    }
+}
+
+/// Risultato di un singolo task in un'operazione sullo stack
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskResult {
+    /// Riferimento del task nello stack
+    pub task: String,
+
+    /// Azione eseguita (install, uninstall, ...)
+    pub action: &'static str,
+
+    /// Esito
+    pub status: TaskStatus,
+
+    /// Durata dell'operazione in millisecondi
+    pub duration_ms: u64,
+
+    /// Motivo del fallimento
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    /// Tipo dell'errore (download, script, state, config, other)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<&'static str>,
+
+    /// Codice di uscita dello script fallito
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+}
+
+/// Risultati per task dell'ultima operazione su uno stack
+#[derive(Debug, Clone, Serialize)]
+pub struct StackReport {
+    /// Nome dello stack
+    pub stack: String,
+
+    /// Azione eseguita sullo stack
+    pub action: &'static str,
+
+    /// Risultati dei task, nell'ordine di esecuzione
+    pub results: Vec<TaskResult>,
+}
+
+impl StackReport {
+    /// Crea un report vuoto per un'operazione sullo stack
+    fn new(stack: &str, operation: Operation) -> Self {
+        StackReport { stack: stack.to_string(), action: operation.to_str(), results: Vec::new() }
+    }
+
+    /// Registra l'esito dell'operazione su un task
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - Il riferimento del task
+    /// * `started` - L'istante di inizio dell'operazione sul task
+    /// * `result` - L'esito dell'operazione
+    fn record<T>(&mut self, task: &str, started: Instant, result: &Result<T>) {
+        self.results.push(TaskResult {
+            task: task.to_string(),
+            action: self.action,
+            status: if result.is_ok() { TaskStatus::Succeeded } else { TaskStatus::Failed },
+            duration_ms: started.elapsed().as_millis() as u64,
+            error: result.as_ref().err().map(|e| redact::mask(&format!("{:#}", e)).into_owned()),
+            error_kind: result.as_ref().err().map(error::kind_of),
+            exit_code: result.as_ref().err().and_then(error::classify).and_then(GalateaError::exit_code),
+        });
+    }
+
+    /// Registra un task senza operazione (non trovato o saltato)
+    fn record_status(&mut self, task: &str, status: TaskStatus) {
+        self.results.push(TaskResult {
+            task: task.to_string(),
+            action: self.action,
+            status,
+            duration_ms: 0,
+            error: None,
+            error_kind: None,
+            exit_code: None,
+        });
+    }
+
+    /// Restituisce i task falliti o non trovati
+    pub fn failed(&self) -> Vec<String> {
+        self.results.iter()
+            .filter(|r| matches!(r.status, TaskStatus::Failed | TaskStatus::NotFound))
+            .map(|r| r.task.clone())
+            .collect()
+    }
+
+    /// Formatta i risultati come tabella di testo
+    pub fn to_table(&self) -> String {
+        let width = self.results.iter()
+            .map(|r| r.task.chars().count())
+            .chain(std::iter::once("Task".len()))
+            .max()
+            .unwrap_or(0);
+
+        let mut table = format!("{:<width$}  {:<10}  {:<11}  {:>8}  Errore\n", "Task", "Azione", "Esito", "Durata");
+        for result in &self.results {
+            let error = match (&result.error_kind, &result.error, result.exit_code) {
+                (Some(kind), Some(error), Some(code)) => format!("[{} {}] {}", kind, code, error),
+                (Some(kind), Some(error), None) => format!("[{}] {}", kind, error),
+                _ => String::new(),
+            };
+            let line = format!(
+                "{:<width$}  {:<10}  {:<11}  {:>7.1}s  {}",
+                result.task,
+                result.action,
+                result.status.label(),
+                result.duration_ms as f64 / 1000.0,
+                error,
+            );
+            table.push_str(line.trim_end());
+            table.push('\n');
+        }
+        table
+    }
+}
 
 /// Definizione di uno stack
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,6 +221,50 @@ pub struct Stack {
     /// Tag per categorizzare lo stack
     pub tags: Vec<String>,
 
+    /// Flag che indica se creare uno snapshot del sistema prima dell'installazione
+    pub snapshot: bool,
+
+    /// Flag che indica se togliere la macchina dal bilanciatore di carico durante l'installazione
+    pub maintenance: bool,
+
+    /// Comandi eseguiti una volta prima di installare i task (es. attivare la pagina di manutenzione)
+    pub pre_install: Vec<String>,
+
+    /// Comandi eseguiti una volta dopo l'installazione dei task, anche se è fallita
+    pub post_install: Vec<String>,
+
+    /// Variabili dello stack, richieste una volta per installazione
+    pub variables: Vec<StackVariable>,
+
+    /// Variabili dei singoli task ricavate da quelle dello stack (es. `server_name: "{{ domain }}"`)
+    pub task_variables: HashMap<String, HashMap<String, String>>,
+
+    /// Variabili calcolate con espressioni rhai dalle variabili dello stack e dai fatti
+    /// della macchina, nell'ordine di dichiarazione
+    pub derived: Vec<(String, String)>,
+
+    /// Canale da cui prendere la versione più recente dei task
+    pub channel: Option<Channel>,
+
+    /// Versioni fissate dei singoli task (riferimento del task -> versione)
+    pub pins: HashMap<String, String>,
+
+    /// Task canary, installati per primi: se uno fallisce lo stack si interrompe
+    pub canary: Vec<String>,
+
+    /// Numero massimo di task installati contemporaneamente (1: uno alla volta)
+    pub parallel: usize,
+
+    /// Pianificazione del ripristino automatico eseguito dal demone
+    pub auto_remediate: Option<RemediationSchedule>,
+
+    /// Stack base da cui lo stack eredita la definizione
+    pub extends: Option<String>,
+
+    /// Etichetta del catalogo da cui proviene lo stack (calcolata al caricamento)
+    #[serde(skip)]
+    pub source: String,
+
     /// Flag che indica se lo stack è completamente installato
     #[serde(skip)]
     pub fully_installed: bool,
@@ -40,6 +272,10 @@ pub struct Stack {
     /// Flag che indica se lo stack è parzialmente installato
     #[serde(skip)]
     pub partially_installed: bool,
+
+    /// Risultati per task dell'ultima operazione eseguita sullo stack
+    #[serde(skip)]
+    pub last_report: Option<StackReport>,
 }
 
 impl Stack {
@@ -85,17 +321,163 @@ impl Stack {
             }
         }
 
+        // Estrai il flag snapshot
+        let snapshot = values.get("snapshot")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        // Estrai il flag maintenance
+        let maintenance = values.get("maintenance")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        // Estrai i comandi eseguiti prima e dopo l'installazione dei task, indicati come
+        // singolo comando o come lista
+        let pre_install = orchestration_commands(values, "pre_install", &name)?;
+        let post_install = orchestration_commands(values, "post_install", &name)?;
+
+        // Estrai le variabili dello stack e la loro espansione nei task
+        let variables = match values.get("variables") {
+            Some(value) => serde_yaml::from_value::<Vec<StackVariable>>(value.clone())
+                .context(format!("Invalid 'variables' section for stack {}", name))?,
+            None => Vec::new(),
+        };
+
+        // I predefiniti in chiaro delle variabili segrete vanno mascherati già nei log di caricamento
+        for var in variables.iter().filter(|var| var.secret) {
+            if let Some(default) = var.default.as_deref().filter(|value| !secrets::is_encrypted(value)) {
+                redact::register(default);
+            }
+        }
+
+        let task_variables = match values.get("task_variables") {
+            Some(value) => serde_yaml::from_value::<HashMap<String, HashMap<String, String>>>(value.clone())
+                .context(format!("Invalid 'task_variables' section for stack {}", name))?,
+            None => HashMap::new(),
+        };
+
+        // Estrai le variabili derivate, verificando che le espressioni siano valide
+        let mut derived = Vec::new();
+        if let Some(derived_values) = values.get("derived") {
+            let mapping = derived_values.as_mapping()
+                .ok_or_else(|| anyhow!("Invalid 'derived' section for stack {}: expected a mapping", name))?;
+            for (var, expression) in mapping {
+                let (Some(var), Some(expression)) = (var.as_str(), expression.as_str()) else {
+                    return Err(anyhow!("Invalid 'derived' section for stack {}: expected expressions as strings", name));
+                };
+                script::check(expression)
+                    .context(format!("Invalid derived variable {} for stack {}", var, name))?;
+                derived.push((var.to_string(), expression.to_string()));
+            }
+        }
+
+        // Estrai il canale e le versioni fissate dei task
+        let channel = match values.get("channel") {
+            Some(value) => Some(serde_yaml::from_value::<Channel>(value.clone())
+                .context(format!("Invalid 'channel' for stack {}", name))?),
+            None => None,
+        };
+
+        let mut pins = HashMap::new();
+        if let Some(pin_values) = values.get("pins").and_then(|v| v.as_mapping()) {
+            for (task, version) in pin_values {
+                let task = task.as_str()
+                    .ok_or_else(|| anyhow!("Invalid task reference in 'pins' for stack {}", name))?;
+                let version = match version {
+                    serde_yaml::Value::String(s) => s.clone(),
+                    serde_yaml::Value::Number(n) => n.to_string(),
+                    _ => return Err(anyhow!("Invalid version for task {} in 'pins' for stack {}", task, name)),
+                };
+                pins.insert(task.to_string(), version);
+            }
+        }
+
+        // Estrai i task canary, indicati come singolo riferimento o come lista
+        let canary: Vec<String> = match values.get("canary") {
+            Some(serde_yaml::Value::String(task)) => vec![task.clone()],
+            Some(value) => serde_yaml::from_value(value.clone())
+                .context(format!("Invalid 'canary' for stack {}", name))?,
+            None => Vec::new(),
+        };
+
+        if let Some(unknown) = canary.iter().find(|c| !task_names.contains(c)) {
+            return Err(anyhow!("Canary task {} is not part of stack {}", unknown, name));
+        }
+
+        // Estrai il numero di task da installare contemporaneamente
+        let parallel = match values.get("parallel") {
+            Some(value) => match value.as_u64() {
+                Some(n) if n > 0 => n as usize,
+                _ => return Err(anyhow!("Invalid 'parallel' for stack {}: expected a positive integer", name)),
+            },
+            None => 1,
+        };
+
+        // Estrai la pianificazione del ripristino automatico (hourly, daily o espressione cron)
+        let auto_remediate = match values.get("auto_remediate") {
+            Some(value) => {
+                let schedule = value.as_str()
+                    .ok_or_else(|| anyhow!("Invalid 'auto_remediate' for stack {}: expected a string", name))?;
+                Some(RemediationSchedule::parse(schedule)
+                    .context(format!("Invalid 'auto_remediate' for stack {}", name))?)
+            },
+            None => None,
+        };
+
+        let extends = values.get("extends")
+            .and_then(|v| v.as_str())
+            .map(|v| v.to_string());
+
         Ok(Stack {
             name,
             description,
             task_names,
             requires_reboot,
             tags,
+            snapshot,
+            maintenance,
+            pre_install,
+            post_install,
+            variables,
+            task_variables,
+            derived,
+            channel,
+            pins,
+            canary,
+            parallel,
+            auto_remediate,
+            extends,
+            source: String::new(),
             fully_installed: false,
             partially_installed: false,
+            last_report: None,
         })
     }
 
+    /// Restituisce il riferimento qualificato dello stack (`sorgente:nome`)
+    pub fn qualified_name(&self) -> String {
+        source::qualified_name(&self.source, &self.name)
+    }
+
+    /// Verifica se un riferimento (`nome` oppure `sorgente:nome`) indica questo stack
+    pub fn matches_ref(&self, reference: &str) -> bool {
+        source::matches_reference(reference, &self.source, &self.name)
+    }
+
+    /// Verifica se un task dello stack è un canary
+    pub fn is_canary(&self, task_name: &str) -> bool {
+        self.canary.iter().any(|c| c == task_name)
+    }
+
+    /// Restituisce i task nell'ordine di installazione: prima i canary, poi gli altri
+    pub fn install_order(&self) -> Vec<String> {
+        let (mut order, rest): (Vec<String>, Vec<String>) = self.task_names.iter()
+            .cloned()
+            .partition(|name| self.is_canary(name));
+        order.extend(rest);
+        order
+    }
+
     /// Verifica lo stato di installazione dello stack
     pub fn check_installation_status(&mut self, tasks: &[Task]) -> Result<()> {
         let mut installed_count = 0;
@@ -109,7 +491,7 @@ impl Stack {
 
         // Conta quanti task sono installati
         for task_name in &self.task_names {
-            if let Some(task) = tasks.iter().find(|t| &t.name == task_name) {
+            if let Some(task) = tasks.iter().find(|t| t.matches_ref(task_name)) {
                 if task.installed {
                     installed_count += 1;
                 }
@@ -125,31 +507,108 @@ impl Stack {
 
     /// Installa tutti i task dello stack
     pub fn install(&mut self, config: &Config, all_tasks: &mut [Task]) -> Result<()> {
+        let name = self.name.clone();
+        hooks::track(config, Operation::Install, &name, || self.run_install(config, all_tasks))
+    }
+
+    /// Esegue l'installazione dello stack senza pubblicare eventi
+    fn run_install(&mut self, config: &Config, all_tasks: &mut [Task]) -> Result<()> {
         info!("Installing stack: {}", self.name);
+        self.last_report = None;
 
-        let mut failed_tasks = Vec::new();
+        self.check_denied_tasks(config, all_tasks)?;
+        let values = self.variable_values(config);
+        self.check_required_variables(&values)?;
 
-        // Installa ogni task dello stack
-        for task_name in &self.task_names {
-            if let Some(task) = all_tasks.iter_mut().find(|t| &t.name == task_name) {
-                match task.install(config) {
+        // Crea uno snapshot prima di procedere, se richiesto dallo stack
+        if self.snapshot {
+            let snap = snapshot::create_snapshot(config, &self.name)
+                .context(format!("Snapshot required by stack {} failed, installation aborted", self.name))?;
+            snapshot::save_restore_point(config, &self.name, &snap)?;
+        }
+
+        let result = self.run_pre_install(config, &values)
+            .and_then(|_| self.install_tasks(config, all_tasks, &values));
+        self.run_post_install(config, &values, result)
+    }
+
+    /// Installa i task dello stack, a partire dai canary
+    fn install_tasks(&mut self, config: &Config, all_tasks: &mut [Task], values: &HashMap<String, String>) -> Result<()> {
+        let mut report = StackReport::new(&self.name, Operation::Install);
+        let mut failed_canary = None;
+
+        // Installa ogni task dello stack, a partire dai canary; con `parallel` i canary
+        // vengono installati da soli e gli altri task insieme
+        let order = self.install_order();
+        let total = order.len();
+        for (i, task_name) in order.iter().enumerate() {
+            if self.parallel > 1 && !self.is_canary(task_name) {
+                self.install_parallel(config, all_tasks, &order[i..], values, &mut report);
+                break;
+            }
+
+            events::progress(
+                format!("Stack {}", self.name),
+                format!("installazione di {} ({}/{})", task_name, i + 1, total),
+                Some(i as f64 * 100.0 / total as f64),
+                false,
+            );
+
+            if let Some(task) = all_tasks.iter_mut().find(|t| t.matches_ref(task_name)) {
+                if self.is_canary(task_name) && task.healthcheck.is_none() {
+                    warn!("Canary task {} of stack {} has no healthcheck, only its installation is checked", task_name, self.name);
+                }
+
+                task.variables = self.task_variables_for(task_name, values);
+                let started = Instant::now();
+                let result = self.select_task_version(task_name, task)
+                    .and_then(|_| task.install(config));
+                task.variables.clear();
+                task.pinned = None;
+
+                match &result {
                     Ok(_) => {
                         info!("Successfully installed task {} as part of stack {}", task_name, self.name);
                     },
                     Err(e) => {
                         error!("Failed to install task {} as part of stack {}: {}", task_name, self.name, e);
-                        failed_tasks.push(task_name.clone());
                     }
                 }
+                report.record(task_name, started, &result);
             } else {
                 warn!("Task {} not found for stack {}", task_name, self.name);
-                failed_tasks.push(task_name.clone());
+                report.record_status(task_name, TaskStatus::NotFound);
             }
+
+            // Un canary fallito interrompe lo stack prima di toccare gli altri task
+            if self.is_canary(task_name) && report.failed().contains(task_name) {
+                failed_canary = Some(task_name.clone());
+                break;
+            }
+        }
+
+        // I task successivi al canary fallito non sono stati eseguiti
+        for task_name in order.iter().skip(report.results.len()) {
+            report.record_status(task_name, TaskStatus::Skipped);
         }
 
+        events::progress(format!("Stack {}", self.name), format!("{} task elaborati", total), Some(100.0), true);
+
+        let failed_tasks = report.failed();
+        self.last_report = Some(report);
+
         // Aggiorna lo stato
         self.check_installation_status(all_tasks)?;
 
+        if let Some(canary) = failed_canary {
+            events::log_line(Level::Error, format!("{}: canary {} fallito, stack interrotto", self.name, canary));
+            return Err(anyhow!(
+                "Canary task {} of stack {} failed, the remaining tasks were not installed",
+                canary,
+                self.name
+            ));
+        }
+
         // Se ci sono stati fallimenti, restituisci un errore
         if !failed_tasks.is_empty() {
             return Err(anyhow!(
@@ -161,35 +620,292 @@ impl Stack {
             ));
         }
 
+        // Installazione riuscita: il punto di ripristino non è più necessario
+        if self.snapshot {
+            snapshot::clear_restore_point(config, &self.name)?;
+        }
+
         info!("Stack {} installed successfully", self.name);
 
         Ok(())
     }
 
+    /// Installa i task indicati, al massimo `parallel` alla volta
+    ///
+    /// Un task parte quando nessun task in corso dello stack blocca una delle sue risorse
+    /// (`locks`) e quando le sue dipendenze presenti nello stack sono terminate; se una
+    /// dipendenza fallisce il task viene saltato. I thread di installazione fanno parte
+    /// dell'esecuzione e del job del chiamante.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - La configurazione corrente
+    /// * `all_tasks` - Tutti i task disponibili
+    /// * `task_names` - I task da installare, nell'ordine dello stack
+    /// * `values` - I valori delle variabili dello stack
+    /// * `report` - Il report in cui registrare gli esiti, nell'ordine in cui terminano
+    fn install_parallel(&self, config: &Config, all_tasks: &mut [Task], task_names: &[String],
+                        values: &HashMap<String, String>, report: &mut StackReport) {
+        let mut names: Vec<&String> = Vec::new();
+        for task_name in task_names {
+            if names.contains(&task_name) {
+                continue;
+            }
+            if all_tasks.iter().any(|t| t.matches_ref(task_name)) {
+                names.push(task_name);
+            } else {
+                warn!("Task {} not found for stack {}", task_name, self.name);
+                report.record_status(task_name, TaskStatus::NotFound);
+            }
+        }
+
+        // Task dello stack da cui dipende ciascun task
+        let members: Vec<(&String, &Task)> = names.iter()
+            .filter_map(|name| all_tasks.iter().find(|t| t.matches_ref(name)).map(|task| (*name, task)))
+            .collect();
+        let mut after: HashMap<String, Vec<String>> = members.iter()
+            .map(|(name, task)| {
+                let dependencies = task.dependencies.iter()
+                    .filter_map(|dependency| members.iter().find(|(other, t)| other != name && t.matches_ref(dependency)))
+                    .map(|(other, _)| other.to_string())
+                    .collect();
+                (name.to_string(), dependencies)
+            })
+            .collect();
+
+        // Ogni task dello stack viene assegnato a un solo thread alla volta
+        let mut free: Vec<&mut Task> = all_tasks.iter_mut().collect();
+        let mut pending = Vec::new();
+        for task_name in &names {
+            let Some(pos) = free.iter().position(|t| t.matches_ref(task_name)) else {
+                continue;
+            };
+            let task = free.swap_remove(pos);
+            let after = after.remove(task_name.as_str()).unwrap_or_default();
+            pending.push(ScheduledTask { name: task_name.to_string(), locks: task.locks.clone(), after, task });
+        }
+
+        let total = pending.len();
+        let schedule = Mutex::new(Schedule { pending, running: Vec::new(), started: 0 });
+        let changed = Condvar::new();
+        let report = Mutex::new(report);
+        let run_id = events::current_run_id();
+        let job = jobs::current();
+
+        info!("Installing {} tasks of stack {}, {} at a time", total, self.name, self.parallel);
+        thread::scope(|scope| {
+            for _ in 0..self.parallel.min(total) {
+                scope.spawn(|| events::join_run(run_id.clone(), || jobs::join(job, || {
+                    while let Some((scheduled, number)) = next_task(&schedule, &changed) {
+                        events::progress(
+                            format!("Stack {}", self.name),
+                            format!("installazione di {} ({}/{})", scheduled.name, number, total),
+                            Some((number - 1) as f64 * 100.0 / total as f64),
+                            false,
+                        );
+
+                        let mut running = RunningTask {
+                            schedule: &schedule,
+                            changed: &changed,
+                            report: &report,
+                            name: scheduled.name.clone(),
+                            succeeded: false,
+                        };
+                        let task = scheduled.task;
+                        task.variables = self.task_variables_for(&scheduled.name, values);
+                        let started = Instant::now();
+                        let result = self.select_task_version(&scheduled.name, task)
+                            .and_then(|_| task.install(config));
+                        task.variables.clear();
+                        task.pinned = None;
+
+                        match &result {
+                            Ok(_) => info!("Successfully installed task {} as part of stack {}", scheduled.name, self.name),
+                            Err(e) => error!("Failed to install task {} as part of stack {}: {}", scheduled.name, self.name, e),
+                        }
+                        report.lock().unwrap_or_else(PoisonError::into_inner).record(&scheduled.name, started, &result);
+                        running.succeeded = result.is_ok();
+                    }
+                })));
+            }
+        });
+    }
+
+    /// Reinstalla tutti i task dello stack anche se risultano già installati
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - La configurazione corrente
+    /// * `all_tasks` - Tutti i task disponibili
+    /// * `uninstall_first` - Se `true` disinstalla i task (in ordine inverso) prima di reinstallarli
+    pub fn reinstall(&mut self, config: &Config, all_tasks: &mut [Task], uninstall_first: bool) -> Result<()> {
+        let name = self.name.clone();
+        hooks::track(config, Operation::Reinstall, &name, || self.run_reinstall(config, all_tasks, uninstall_first))
+    }
+
+    /// Esegue la reinstallazione dello stack senza pubblicare eventi
+    fn run_reinstall(&mut self, config: &Config, all_tasks: &mut [Task], uninstall_first: bool) -> Result<()> {
+        info!("Reinstalling stack: {} (uninstall first: {})", self.name, uninstall_first);
+        self.last_report = None;
+
+        self.check_denied_tasks(config, all_tasks)?;
+        let values = self.variable_values(config);
+        self.check_required_variables(&values)?;
+
+        let result = self.run_pre_install(config, &values)
+            .and_then(|_| self.reinstall_tasks(config, all_tasks, &values, uninstall_first));
+        self.run_post_install(config, &values, result)
+    }
+
+    /// Reinstalla i task dello stack, a partire dai canary
+    fn reinstall_tasks(&mut self, config: &Config, all_tasks: &mut [Task], values: &HashMap<String, String>,
+                       uninstall_first: bool) -> Result<()> {
+        // Disinstalla prima tutti i task, dai dipendenti verso le dipendenze
+        if uninstall_first {
+            for task_name in self.task_names.iter().rev() {
+                if let Some(task) = all_tasks.iter_mut().find(|t| t.matches_ref(task_name)) {
+                    if task.installed {
+                        task.variables = self.task_variables_for(task_name, values);
+                        let result = self.select_task_version(task_name, task)
+                            .and_then(|_| task.uninstall(config));
+                        if let Err(e) = result {
+                            warn!("Uninstall of task {} failed during reinstall of stack {}, continuing: {}", task_name, self.name, e);
+                        }
+                        task.variables.clear();
+                        task.pinned = None;
+                    }
+                }
+            }
+        }
+
+        let mut report = StackReport::new(&self.name, Operation::Reinstall);
+        let mut failed_canary = None;
+
+        // Reinstalla ogni task dello stack, a partire dai canary
+        let order = self.install_order();
+        let total = order.len();
+        for (i, task_name) in order.iter().enumerate() {
+            events::progress(
+                format!("Stack {}", self.name),
+                format!("reinstallazione di {} ({}/{})", task_name, i + 1, total),
+                Some(i as f64 * 100.0 / total as f64),
+                false,
+            );
+
+            if let Some(task) = all_tasks.iter_mut().find(|t| t.matches_ref(task_name)) {
+                if self.is_canary(task_name) && task.healthcheck.is_none() {
+                    warn!("Canary task {} of stack {} has no healthcheck, only its installation is checked", task_name, self.name);
+                }
+
+                task.variables = self.task_variables_for(task_name, values);
+                let started = Instant::now();
+                let result = self.select_task_version(task_name, task)
+                    .and_then(|_| task.reinstall(config, false));
+                task.variables.clear();
+                task.pinned = None;
+
+                match &result {
+                    Ok(_) => {
+                        info!("Successfully reinstalled task {} as part of stack {}", task_name, self.name);
+                    },
+                    Err(e) => {
+                        error!("Failed to reinstall task {} as part of stack {}: {}", task_name, self.name, e);
+                    }
+                }
+                report.record(task_name, started, &result);
+            } else {
+                warn!("Task {} not found for stack {}", task_name, self.name);
+                report.record_status(task_name, TaskStatus::NotFound);
+            }
+
+            // Un canary fallito interrompe lo stack prima di toccare gli altri task
+            if self.is_canary(task_name) && report.failed().contains(task_name) {
+                failed_canary = Some(task_name.clone());
+                break;
+            }
+        }
+
+        // I task successivi al canary fallito non sono stati eseguiti
+        for task_name in order.iter().skip(report.results.len()) {
+            report.record_status(task_name, TaskStatus::Skipped);
+        }
+
+        events::progress(format!("Stack {}", self.name), format!("{} task elaborati", total), Some(100.0), true);
+
+        let failed_tasks = report.failed();
+        self.last_report = Some(report);
+
+        // Aggiorna lo stato
+        self.check_installation_status(all_tasks)?;
+
+        if let Some(canary) = failed_canary {
+            events::log_line(Level::Error, format!("{}: canary {} fallito, stack interrotto", self.name, canary));
+            return Err(anyhow!(
+                "Canary task {} of stack {} failed, the remaining tasks were not reinstalled",
+                canary,
+                self.name
+            ));
+        }
+
+        // Se ci sono stati fallimenti, restituisci un errore
+        if !failed_tasks.is_empty() {
+            return Err(anyhow!(
+                "Failed to reinstall {} out of {} tasks in stack {}: {:?}",
+                failed_tasks.len(),
+                self.task_names.len(),
+                self.name,
+                failed_tasks
+            ));
+        }
+
+        info!("Stack {} reinstalled successfully", self.name);
+
+        Ok(())
+    }
+
     /// Disinstalla tutti i task dello stack
     pub fn uninstall(&mut self, config: &Config, all_tasks: &mut [Task]) -> Result<()> {
+        let name = self.name.clone();
+        hooks::track(config, Operation::Uninstall, &name, || self.run_uninstall(config, all_tasks))
+    }
+
+    /// Esegue la disinstallazione dello stack senza pubblicare eventi
+    fn run_uninstall(&mut self, config: &Config, all_tasks: &mut [Task]) -> Result<()> {
         info!("Uninstalling stack: {}", self.name);
+        self.last_report = None;
 
-        let mut failed_tasks = Vec::new();
+        let values = self.variable_values(config);
+
+        let mut report = StackReport::new(&self.name, Operation::Uninstall);
 
         // Disinstalla ogni task dello stack in ordine inverso
         for task_name in self.task_names.iter().rev() {
-            if let Some(task) = all_tasks.iter_mut().find(|t| &t.name == task_name) {
-                match task.uninstall(config) {
+            if let Some(task) = all_tasks.iter_mut().find(|t| t.matches_ref(task_name)) {
+                task.variables = self.task_variables_for(task_name, &values);
+                let started = Instant::now();
+                let result = self.select_task_version(task_name, task)
+                    .and_then(|_| task.uninstall(config));
+                task.variables.clear();
+                task.pinned = None;
+
+                match &result {
                     Ok(_) => {
                         info!("Successfully uninstalled task {} as part of stack {}", task_name, self.name);
                     },
                     Err(e) => {
                         error!("Failed to uninstall task {} as part of stack {}: {}", task_name, self.name, e);
-                        failed_tasks.push(task_name.clone());
                     }
                 }
+                report.record(task_name, started, &result);
             } else {
                 warn!("Task {} not found for stack {}", task_name, self.name);
-                failed_tasks.push(task_name.clone());
+                report.record_status(task_name, TaskStatus::NotFound);
             }
         }
 
+        let failed_tasks = report.failed();
+        self.last_report = Some(report);
+
         // Aggiorna lo stato
         self.check_installation_status(all_tasks)?;
 
@@ -211,28 +927,47 @@ impl Stack {
 
     /// Reset di tutti i task dello stack
     pub fn reset(&mut self, config: &Config, all_tasks: &mut [Task]) -> Result<()> {
+        let name = self.name.clone();
+        hooks::track(config, Operation::Reset, &name, || self.run_reset(config, all_tasks))
+    }
+
+    /// Esegue il reset dello stack senza pubblicare eventi
+    fn run_reset(&mut self, config: &Config, all_tasks: &mut [Task]) -> Result<()> {
         info!("Resetting stack: {}", self.name);
+        self.last_report = None;
 
-        let mut failed_tasks = Vec::new();
+        let values = self.variable_values(config);
+
+        let mut report = StackReport::new(&self.name, Operation::Reset);
 
         // Resetta ogni task dello stack
         for task_name in &self.task_names {
-            if let Some(task) = all_tasks.iter_mut().find(|t| &t.name == task_name) {
-                match task.reset(config) {
+            if let Some(task) = all_tasks.iter_mut().find(|t| t.matches_ref(task_name)) {
+                task.variables = self.task_variables_for(task_name, &values);
+                let started = Instant::now();
+                let result = self.select_task_version(task_name, task)
+                    .and_then(|_| task.reset(config));
+                task.variables.clear();
+                task.pinned = None;
+
+                match &result {
                     Ok(_) => {
                         info!("Successfully reset task {} as part of stack {}", task_name, self.name);
                     },
                     Err(e) => {
                         error!("Failed to reset task {} as part of stack {}: {}", task_name, self.name, e);
-                        failed_tasks.push(task_name.clone());
                     }
                 }
+                report.record(task_name, started, &result);
             } else {
                 warn!("Task {} not found for stack {}", task_name, self.name);
-                failed_tasks.push(task_name.clone());
+                report.record_status(task_name, TaskStatus::NotFound);
             }
         }
 
+        let failed_tasks = report.failed();
+        self.last_report = Some(report);
+
         // Se ci sono stati fallimenti, restituisci un errore
         if !failed_tasks.is_empty() {
             return Err(anyhow!(
@@ -249,30 +984,103 @@ impl Stack {
         Ok(())
     }
 
+    /// Ripete le operazioni fallite dei task dello stack
+    ///
+    /// Ogni task ripete la propria ultima operazione fallita; i task senza fallimenti
+    /// registrati vengono ignorati.
+    pub fn retry_failed(&mut self, config: &Config, all_tasks: &mut [Task]) -> Result<()> {
+        info!("Retrying failed tasks of stack: {}", self.name);
+
+        let values = self.variable_values(config);
+        let mut failed_tasks = Vec::new();
+
+        for task_name in &self.task_names {
+            if let Some(task) = all_tasks.iter_mut().find(|t| t.matches_ref(task_name) && t.has_failed()) {
+                task.variables = self.task_variables_for(task_name, &values);
+                let result = self.select_task_version(task_name, task)
+                    .and_then(|_| task.retry_failed(config));
+                task.variables.clear();
+                task.pinned = None;
+
+                if let Err(e) = result {
+                    error!("Retry of task {} as part of stack {} failed: {}", task_name, self.name, e);
+                    failed_tasks.push(task_name.clone());
+                }
+            }
+        }
+
+        self.check_installation_status(all_tasks)?;
+
+        if !failed_tasks.is_empty() {
+            return Err(anyhow!(
+                "Retry failed for {} tasks in stack {}: {:?}",
+                failed_tasks.len(),
+                self.name,
+                failed_tasks
+            ));
+        }
+
+        Ok(())
+    }
+
     /// Riavvia i servizi di tutti i task dello stack
     pub fn remediate(&mut self, config: &Config, all_tasks: &mut [Task]) -> Result<()> {
+        let name = self.name.clone();
+        hooks::track(config, Operation::Remediate, &name, || self.run_remediate(config, all_tasks, false))
+    }
+
+    /// Riavvia i servizi dei task dello stack per il ripristino automatico
+    ///
+    /// A differenza di [`Stack::remediate`] salta i task in quarantena, che resterebbero
+    /// altrimenti ritentati a ogni pianificazione.
+    pub fn remediate_scheduled(&mut self, config: &Config, all_tasks: &mut [Task]) -> Result<()> {
+        let name = self.name.clone();
+        hooks::track(config, Operation::Remediate, &name, || self.run_remediate(config, all_tasks, true))
+    }
+
+    /// Esegue il ripristino dei servizi dello stack senza pubblicare eventi
+    fn run_remediate(&mut self, config: &Config, all_tasks: &mut [Task], skip_quarantined: bool) -> Result<()> {
         info!("Remediating stack: {}", self.name);
+        self.last_report = None;
 
-        let mut failed_tasks = Vec::new();
+        let values = self.variable_values(config);
+
+        let mut report = StackReport::new(&self.name, Operation::Remediate);
 
         // Riavvia i servizi di ogni task dello stack
         for task_name in &self.task_names {
-            if let Some(task) = all_tasks.iter_mut().find(|t| &t.name == task_name) {
-                match task.remediate(config) {
+            if let Some(task) = all_tasks.iter_mut().find(|t| t.matches_ref(task_name)) {
+                if skip_quarantined && task.is_quarantined() {
+                    warn!("Task {} is quarantined, skipped by remediation of stack {}", task_name, self.name);
+                    report.record_status(task_name, TaskStatus::Skipped);
+                    continue;
+                }
+
+                task.variables = self.task_variables_for(task_name, &values);
+                let started = Instant::now();
+                let result = self.select_task_version(task_name, task)
+                    .and_then(|_| task.remediate(config));
+                task.variables.clear();
+                task.pinned = None;
+
+                match &result {
                     Ok(_) => {
                         info!("Successfully remediated task {} as part of stack {}", task_name, self.name);
                     },
                     Err(e) => {
                         error!("Failed to remediate task {} as part of stack {}: {}", task_name, self.name, e);
-                        failed_tasks.push(task_name.clone());
                     }
                 }
+                report.record(task_name, started, &result);
             } else {
                 warn!("Task {} not found for stack {}", task_name, self.name);
-                failed_tasks.push(task_name.clone());
+                report.record_status(task_name, TaskStatus::NotFound);
             }
         }
 
+        let failed_tasks = report.failed();
+        self.last_report = Some(report);
+
         // Se ci sono stati fallimenti, restituisci un errore
         if !failed_tasks.is_empty() {
             return Err(anyhow!(
@@ -288,6 +1096,272 @@ impl Stack {
 
         Ok(())
     }
+
+    /// Sceglie la versione del task in base alle versioni fissate e al canale dello stack
+    ///
+    /// # Arguments
+    ///
+    /// * `task_name` - Il riferimento del task nello stack
+    /// * `task` - Il task su cui impostare la versione scelta
+    pub fn select_task_version(&self, task_name: &str, task: &mut Task) -> Result<()> {
+        let pin = self.pins.get(task_name)
+            .or_else(|| self.pins.get(&task.name))
+            .map(|version| version.as_str());
+
+        task.pinned = task.resolve_version(pin, self.channel)
+            .context(format!("Failed to resolve the version of task {} for stack {}", task_name, self.name))?;
+
+        if let Some(version) = task.effective_version() {
+            info!("Using version {} of task {} for stack {}", version, task_name, self.name);
+        }
+
+        Ok(())
+    }
+
+    /// Restituisce i valori delle variabili: quelli salvati all'ultima richiesta o, in mancanza, i predefiniti
+    ///
+    /// Ai valori si aggiungono le variabili derivate (`derived`), calcolate a partire da questi.
+    pub fn variable_values(&self, config: &Config) -> HashMap<String, String> {
+        let saved = self.load_variable_values(config);
+
+        let mut values: HashMap<String, String> = self.variables.iter()
+            .filter_map(|var| {
+                let value = saved.get(&var.name).or(var.default.as_ref())?;
+
+                // I valori cifrati vengono decifrati solo qui, al momento dell'uso
+                match secrets::reveal(config, value) {
+                    Ok(value) => {
+                        // Anche i segreti salvati in chiaro vanno mascherati
+                        if var.secret {
+                            redact::register(&value);
+                        }
+                        Some((var.name.clone(), value))
+                    },
+                    Err(e) => {
+                        warn!("Failed to decrypt variable {} of stack {}: {}", var.name, self.name, e);
+                        None
+                    },
+                }
+            })
+            .collect();
+
+        if !self.derived.is_empty() {
+            match script::derive(&self.derived, &values, &script::facts(config)) {
+                Ok(derived) => values.extend(derived),
+                Err(e) => warn!("Failed to compute derived variables of stack {}: {:#}", self.name, e),
+            }
+        }
+
+        values
+    }
+
+    /// Salva i valori delle variabili inseriti dall'utente
+    pub fn save_variable_values(&self, config: &Config, values: &HashMap<String, String>) -> Result<()> {
+        let path = config.resolve_path(&format!("{}.vars", self.name), "state");
+
+        // I valori delle variabili segrete vengono cifrati per i destinatari age
+        let mut values = values.clone();
+        for var in self.variables.iter().filter(|var| var.secret) {
+            let Some(value) = values.get_mut(&var.name) else {
+                continue;
+            };
+            if secrets::is_encrypted(value) {
+                continue;
+            }
+            redact::register(value);
+            match secrets::encrypt(config, value)? {
+                Some(encrypted) => *value = encrypted,
+                None => warn!("No age_recipients configured: secret variable {} of stack {} is stored in clear text", var.name, self.name),
+            }
+        }
+
+        let yaml = serde_yaml::to_string(&values)
+            .context(format!("Failed to serialize variables for stack {}", self.name))?;
+
+        state_crypt::write(&path, yaml)
+            .context(format!("Failed to write variables for stack {}", self.name))
+    }
+
+    /// Carica i valori delle variabili salvati, se presenti
+    fn load_variable_values(&self, config: &Config) -> HashMap<String, String> {
+        let path = config.resolve_path(&format!("{}.vars", self.name), "state");
+
+        match state_crypt::read_to_string(&path) {
+            Ok(content) => serde_yaml::from_str(&content).unwrap_or_else(|e| {
+                warn!("Invalid variables file for stack {}: {}", self.name, e);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        }
+    }
+
+    /// Verifica che nessun task dello stack sia escluso da questa macchina
+    ///
+    /// Lo stack viene rifiutato prima di installare qualsiasi task, invece di fermarsi a metà
+    /// sul primo task escluso.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - La configurazione corrente
+    /// * `all_tasks` - Tutti i task disponibili
+    pub fn check_denied_tasks(&self, config: &Config, all_tasks: &[Task]) -> Result<()> {
+        let denied: Vec<String> = self.task_names.iter()
+            .filter_map(|task_name| {
+                let task = all_tasks.iter().find(|t| t.matches_ref(task_name))?;
+                task.denied_by(config).map(|rule| format!("{} ({})", task_name, rule))
+            })
+            .collect();
+
+        if denied.is_empty() {
+            Ok(())
+        } else {
+            Err(GalateaError::Config(format!("Stack {} includes tasks denied on this host: {}",
+                                                  self.name, denied.join(", "))).into())
+        }
+    }
+
+    /// Restituisce i task dello stack che non esistono nel catalogo dei task
+    pub fn missing_tasks(&self, all_tasks: &[Task]) -> Vec<String> {
+        self.task_names.iter()
+            .filter(|task_name| !all_tasks.iter().any(|t| t.matches_ref(task_name)))
+            .cloned()
+            .collect()
+    }
+
+    /// Prepara la macchina all'installazione dei task dello stack
+    ///
+    /// Con `maintenance` la macchina viene prima tolta dal bilanciatore di carico, poi vengono
+    /// eseguiti i comandi `pre_install`. Il primo passo che fallisce interrompe l'installazione
+    /// prima di toccare i task.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - La configurazione corrente
+    /// * `values` - I valori delle variabili dello stack, usabili nei comandi come `{{ nome }}`
+    pub fn run_pre_install(&self, config: &Config, values: &HashMap<String, String>) -> Result<()> {
+        if self.maintenance {
+            maintenance::drain(config, &self.name)?;
+        }
+        self.run_orchestration(config, "pre_install", &self.pre_install, values)
+    }
+
+    /// Esegue i comandi `post_install` dello stack dopo l'installazione dei task
+    ///
+    /// I comandi vengono eseguiti anche se i comandi `pre_install` o i task sono falliti, per
+    /// annullare ciò che è stato preparato (es. togliere la pagina di manutenzione): in quel
+    /// caso un loro errore viene solo riportato nel log e resta l'errore dell'installazione.
+    /// Con `maintenance` la macchina torna poi nel bilanciatore solo se tutto è riuscito.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - La configurazione corrente
+    /// * `values` - I valori delle variabili dello stack, usabili nei comandi come `{{ nome }}`
+    /// * `outcome` - L'esito dell'installazione
+    pub fn run_post_install(&self, config: &Config, values: &HashMap<String, String>, outcome: Result<()>) -> Result<()> {
+        let post = self.run_orchestration(config, "post_install", &self.post_install, values);
+        let outcome = match (outcome, post) {
+            (Ok(()), post) => post,
+            (Err(e), Err(post_error)) => {
+                error!("{:#}", post_error);
+                Err(e)
+            },
+            (Err(e), Ok(())) => Err(e),
+        };
+
+        if self.maintenance {
+            maintenance::restore(config, &self.name, outcome)
+        } else {
+            outcome
+        }
+    }
+
+    /// Esegue in ordine i comandi di orchestrazione dello stack
+    fn run_orchestration(&self, config: &Config, step: &str, commands: &[String], values: &HashMap<String, String>) -> Result<()> {
+        for (i, command) in commands.iter().enumerate() {
+            let command = expand_variables(command, values);
+            info!("Running {} command {}/{} of stack {}: {}", step, i + 1, commands.len(), self.name, command);
+            events::log_line(Level::Info, format!("{}: {} {}/{}", self.name, step, i + 1, commands.len()));
+
+            executor::run_command_with_timeout(&command, ORCHESTRATION_TIMEOUT, &config.child_env)
+                .context(format!("The {} command of stack {} failed: {}", step, self.name, command))?;
+        }
+
+        Ok(())
+    }
+
+    /// Verifica che tutte le variabili abbiano un valore
+    pub fn check_required_variables(&self, values: &HashMap<String, String>) -> Result<()> {
+        let missing: Vec<&str> = self.variables.iter()
+            .filter(|var| !values.contains_key(&var.name))
+            .map(|var| var.name.as_str())
+            .collect();
+
+        if !missing.is_empty() {
+            return Err(anyhow!(
+                "Stack {} requires a value for variables {:?}: install it individually to be prompted",
+                self.name,
+                missing
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Calcola le variabili da passare a un task dello stack
+    ///
+    /// Il task riceve tutte le variabili dello stack più le proprie variabili definite in
+    /// `task_variables`, in cui i riferimenti `{{ nome }}` vengono sostituiti con i valori.
+    pub fn task_variables_for(&self, task_name: &str, values: &HashMap<String, String>) -> HashMap<String, String> {
+        let mut vars = values.clone();
+
+        if let Some(mapping) = self.task_variables.get(task_name) {
+            for (name, template) in mapping {
+                vars.insert(name.clone(), expand_variables(template, values));
+            }
+        }
+
+        vars
+    }
+}
+
+/// Estrae i comandi di orchestrazione di uno stack, indicati come singolo comando o come lista
+fn orchestration_commands(values: &HashMap<String, serde_yaml::Value>, key: &str, stack: &str) -> Result<Vec<String>> {
+    match values.get(key) {
+        Some(serde_yaml::Value::String(command)) => Ok(vec![command.clone()]),
+        Some(value) => serde_yaml::from_value(value.clone())
+            .context(format!("Invalid '{}' for stack {}", key, stack)),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Sostituisce i riferimenti `{{ nome }}` con i valori delle variabili
+///
+/// I riferimenti a variabili sconosciute vengono lasciati invariati.
+pub fn expand_variables(template: &str, values: &HashMap<String, String>) -> String {
+    let mut result = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+
+        let name = rest[start + 2..start + end].trim();
+        result.push_str(&rest[..start]);
+
+        match values.get(name) {
+            Some(value) => result.push_str(value),
+            None => {
+                warn!("Unknown variable in template: {}", name);
+                result.push_str(&rest[start..start + end + 2]);
+            }
+        }
+
+        rest = &rest[start + end + 2..];
+    }
+
+    result.push_str(rest);
+    result
 }
 
 impl Display for Stack {
@@ -300,7 +1374,6 @@ impl Display for Stack {
 pub fn load_stacks(config: &Config, tasks: &[Task]) -> Result<Vec<Stack>> {
     info!("Loading stacks from configuration files");
 
-    let mut stacks = Vec::new();
     let stacks_dir = Path::new(&config.stacks_dir);
 
     // Verifica che la directory esista
@@ -330,9 +1403,214 @@ pub fn load_stacks(config: &Config, tasks: &[Task]) -> Result<Vec<Stack>> {
         create_example_stack_config(stacks_dir)?;
     }
 
+    read_stacks(config, tasks)
+}
+
+/// Legge gli stack dai file di configurazione già presenti, senza scaricare le sorgenti
+///
+/// Oltre alla directory degli stack vengono lette le directory proprie delle sorgenti.
+pub fn read_stacks(config: &Config, tasks: &[Task]) -> Result<Vec<Stack>> {
+    let mut definitions = Vec::new();
+
+    for (dir, dir_source) in source::catalog_dirs(&config.stack_sources, Path::new(&config.stacks_dir)) {
+        if dir_source.is_some() && !dir.exists() {
+            warn!("Stack catalog directory does not exist: {:?}", dir);
+            continue;
+        }
+        read_stack_dir(config, &dir, dir_source, &mut definitions)?;
+    }
+
+    // Gli stack vengono creati dopo aver letto tutti i cataloghi: lo stack base indicato
+    // da `extends` può trovarsi in un altro file o in un'altra sorgente
+    let mut stacks: Vec<Stack> = Vec::new();
+    for idx in 0..definitions.len() {
+        let result = resolve_extends(&definitions, idx, &mut Vec::new())
+            .and_then(|values| Stack::from_hashmap(&values));
+
+        match result {
+            Ok(mut stack) => {
+                stack.source = definitions[idx].catalog.clone();
+
+                if let Some(other) = stacks.iter().find(|s: &&Stack| s.name == stack.name) {
+                    warn!("Stack {} is defined in catalogs {} and {}; use source:name to disambiguate",
+                          stack.name, other.source, stack.source);
+                }
+
+                // Verifica lo stato di installazione
+                stack.check_installation_status(tasks)?;
+                info!("Successfully loaded stack: {:?}", stack.clone());
+                stacks.push(stack);
+            },
+            Err(e) => {
+                warn!("Failed to create stack from config: {:#}", e);
+            }
+        }
+    }
+
+    // Uno stack con task inesistenti viene caricato comunque, ma non potrà essere installato
+    for (stack, missing) in missing_task_references(&stacks, tasks) {
+        warn!("Stack {} references tasks not found in the catalog: {}", stack, missing.join(", "));
+    }
+
+    info!("Loaded {} stacks", stacks.len());
+    Ok(stacks)
+}
+
+/// Task in attesa di essere installato in parallelo
+struct ScheduledTask<'a> {
+    /// Riferimento del task nello stack
+    name: String,
+
+    /// Risorse bloccate dal task
+    locks: Vec<String>,
+
+    /// Task dello stack che devono terminare prima di questo
+    after: Vec<String>,
+
+    /// Il task da installare
+    task: &'a mut Task,
+}
+
+/// Stato dell'installazione parallela di uno stack
+struct Schedule<'a> {
+    /// Task non ancora avviati, nell'ordine dello stack
+    pending: Vec<ScheduledTask<'a>>,
+
+    /// Task in corso, con le risorse che bloccano
+    running: Vec<(String, Vec<String>)>,
+
+    /// Numero di task avviati
+    started: usize,
+}
+
+impl Schedule<'_> {
+    /// Restituisce la posizione del primo task in attesa che può partire
+    fn startable(&self) -> Option<usize> {
+        let ready = |scheduled: &ScheduledTask| {
+            let waits_for = |name: &String| self.pending.iter().any(|p| p.name == *name)
+                || self.running.iter().any(|(running, _)| running == name);
+            let busy = scheduled.locks.iter()
+                .any(|lock| self.running.iter().any(|(_, locks)| locks.contains(lock)));
+            !busy && !scheduled.after.iter().any(waits_for)
+        };
+        match self.pending.iter().position(ready) {
+            Some(pos) => Some(pos),
+            // Dipendenze circolari: senza task in corso nessuno le sbloccherebbe
+            None if self.running.is_empty() && !self.pending.is_empty() => {
+                warn!("Circular dependencies between tasks {:?}, installing them in stack order",
+                      self.pending.iter().map(|p| p.name.as_str()).collect::<Vec<_>>());
+                Some(0)
+            },
+            None => None,
+        }
+    }
+
+    /// Segna come terminato un task in corso
+    ///
+    /// Se il task non è riuscito, i task in attesa che ne dipendono, anche indirettamente,
+    /// vengono tolti dalla coda.
+    ///
+    /// # Returns
+    ///
+    /// I task tolti dalla coda, da registrare come saltati
+    fn finish(&mut self, name: &str, succeeded: bool) -> Vec<String> {
+        self.running.retain(|(running, _)| running != name);
+
+        let mut skipped = Vec::new();
+        let mut failed = if succeeded { Vec::new() } else { vec![name.to_string()] };
+        while let Some(failed_name) = failed.pop() {
+            let (dependents, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.pending)
+                .into_iter()
+                .partition(|p| p.after.contains(&failed_name));
+            self.pending = pending;
+            for dependent in dependents {
+                warn!("Skipping task {}: its dependency {} did not install", dependent.name, failed_name);
+                failed.push(dependent.name.clone());
+                skipped.push(dependent.name);
+            }
+        }
+        skipped
+    }
+}
+
+/// Task in corso nell'installazione parallela di uno stack
+///
+/// Alla fine dell'installazione, anche se va in panic, toglie il task da quelli in
+/// corso, salta i suoi dipendenti se non è riuscito e sveglia gli altri thread, che
+/// altrimenti attenderebbero per sempre le sue risorse.
+struct RunningTask<'s, 'a, 'r> {
+    /// Lo stato dell'installazione parallela
+    schedule: &'s Mutex<Schedule<'a>>,
+
+    /// La condizione su cui attendono gli altri thread
+    changed: &'s Condvar,
+
+    /// Il report in cui registrare i task saltati
+    report: &'s Mutex<&'r mut StackReport>,
+
+    /// Riferimento del task nello stack
+    name: String,
+
+    /// Se l'installazione del task è riuscita
+    succeeded: bool,
+}
+
+impl Drop for RunningTask<'_, '_, '_> {
+    fn drop(&mut self) {
+        let skipped = self.schedule.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .finish(&self.name, self.succeeded);
+        self.changed.notify_all();
+
+        let mut report = self.report.lock().unwrap_or_else(PoisonError::into_inner);
+        for name in skipped {
+            report.record_status(&name, TaskStatus::Skipped);
+        }
+    }
+}
+
+/// Attende e prenota il prossimo task da installare
+///
+/// # Returns
+///
+/// Il task e il suo numero d'ordine di avvio, oppure `None` se non ne restano
+fn next_task<'a>(schedule: &Mutex<Schedule<'a>>, changed: &Condvar) -> Option<(ScheduledTask<'a>, usize)> {
+    let mut schedule = schedule.lock().unwrap_or_else(PoisonError::into_inner);
+    loop {
+        if schedule.pending.is_empty() {
+            return None;
+        }
+        if let Some(pos) = schedule.startable() {
+            let scheduled = schedule.pending.remove(pos);
+            schedule.running.push((scheduled.name.clone(), scheduled.locks.clone()));
+            schedule.started += 1;
+            let number = schedule.started;
+            return Some((scheduled, number));
+        }
+        schedule = changed.wait(schedule).unwrap_or_else(PoisonError::into_inner);
+    }
+}
+
+/// Definizione di uno stack letta dal catalogo, prima della risoluzione di `extends`
+struct StackDefinition {
+    /// Etichetta del catalogo da cui proviene la definizione
+    catalog: String,
+
+    /// Valori della definizione
+    values: HashMap<String, serde_yaml::Value>,
+}
+
+impl StackDefinition {
+    fn name(&self) -> &str {
+        self.values.get("name").and_then(|v| v.as_str()).unwrap_or("")
+    }
+}
+
+/// Legge i file .conf di una directory del catalogo degli stack
+fn read_stack_dir(config: &Config, dir: &Path, dir_source: Option<&Source>, definitions: &mut Vec<StackDefinition>) -> Result<()> {
     // Leggi tutti i file di configurazione (con estensione .conf)
-    for entry in fs::read_dir(stacks_dir)
-        .context(format!("Failed to read stacks directory: {}", config.stacks_dir))? {
+    for entry in fs::read_dir(dir)
+        .context(format!("Failed to read stacks directory: {:?}", dir))? {
 
         let entry = entry.context("Failed to read directory entry")?;
         let path = entry.path();
@@ -340,14 +1618,24 @@ pub fn load_stacks(config: &Config, tasks: &[Task]) -> Result<Vec<Stack>> {
         // Processa solo i file con estensione .conf
         if path.is_file() && path.extension().map_or(false, |ext| ext == "conf") {
             info!("Processing stack configuration file: {:?}", path);
+            let catalog = match dir_source {
+                Some(dir_source) => dir_source.label(),
+                None => source::catalog_label(&config.stack_sources, &path),
+            };
 
             // Leggi il contenuto del file
             let content = fs::read_to_string(&path)
                 .context(format!("Failed to read stack config file: {:?}", path))?;
 
-            // Parse del YAML
+            // Parse del YAML, decifrando i file SOPS
             let yaml_value: serde_yaml::Value = serde_yaml::from_str(&content)
                 .context(format!("Failed to parse YAML from: {:?}", path))?;
+            let yaml_value = secrets::open_document(&path, yaml_value, config.age_identity.as_deref())?;
+
+            // Le opzioni sconosciute o del tipo sbagliato verrebbero ignorate: vanno segnalate
+            for issue in catalog_schema::validate(CatalogKind::Stacks, &yaml_value) {
+                warn!("Stack catalog {:?} does not match the schema: {}", path, issue);
+            }
 
             // Estrai gli stack dal documento YAML
             if let Some(stacks_value) = yaml_value.get("stacks") {
@@ -362,18 +1650,7 @@ pub fn load_stacks(config: &Config, tasks: &[Task]) -> Result<Vec<Stack>> {
                                 }
                             }
 
-                            // Crea lo stack
-                            match Stack::from_hashmap(&hashmap) {
-                                Ok(mut stack) => {
-                                    // Verifica lo stato di installazione
-                                    stack.check_installation_status(tasks)?;
-                                    info!("Successfully loaded stack: {:?}", stack.clone());
-                                    stacks.push(stack); // Push to stacks vector
-                                },
-                                Err(e) => {
-                                    warn!("Failed to create stack from config: {}", e);
-                                }
-                            }
+                            definitions.push(StackDefinition { catalog: catalog.clone(), values: hashmap });
                         }
                     }
                 }
@@ -381,11 +1658,208 @@ pub fn load_stacks(config: &Config, tasks: &[Task]) -> Result<Vec<Stack>> {
         }
     }
 
-    info!("Loaded {} stacks", stacks.len());
-    Ok(stacks)
+    Ok(())
+}
+
+/// Risolve la catena di `extends` di una definizione
+///
+/// # Arguments
+///
+/// * `definitions` - Tutte le definizioni lette dai cataloghi
+/// * `idx` - L'indice della definizione da risolvere
+/// * `chain` - Le definizioni già attraversate, per riconoscere i cicli
+///
+/// # Returns
+///
+/// I valori dello stack con quelli ereditati dagli stack base
+fn resolve_extends(definitions: &[StackDefinition], idx: usize, chain: &mut Vec<usize>) -> Result<HashMap<String, serde_yaml::Value>> {
+    let definition = &definitions[idx];
+    let Some(reference) = definition.values.get("extends") else {
+        return Ok(definition.values.clone());
+    };
+    let reference = reference.as_str()
+        .ok_or_else(|| anyhow!("Invalid 'extends' for stack {}: expected a stack reference", definition.name()))?;
+
+    // A parità di nome viene preferito lo stack base dello stesso catalogo
+    let candidates: Vec<usize> = (0..definitions.len())
+        .filter(|&other| other != idx)
+        .filter(|&other| source::matches_reference(reference, &definitions[other].catalog, definitions[other].name()))
+        .collect();
+    let base = candidates.iter()
+        .find(|&&other| definitions[other].catalog == definition.catalog)
+        .or_else(|| candidates.first())
+        .copied()
+        .ok_or_else(|| anyhow!("Base stack {} of stack {} not found", reference, definition.name()))?;
+
+    chain.push(idx);
+    if chain.contains(&base) {
+        let names: Vec<&str> = chain.iter().map(|&i| definitions[i].name()).collect();
+        return Err(anyhow!("Stack {} has circular 'extends': {} -> {}", definition.name(), names.join(" -> "), reference));
+    }
+    let base_values = resolve_extends(definitions, base, chain)
+        .context(format!("Failed to resolve base stack {} of stack {}", reference, definition.name()))?;
+    chain.pop();
+
+    Ok(merge_stack_values(base_values, &definition.values))
+}
+
+/// Applica a uno stack base i valori di uno stack che lo estende
+///
+/// I valori indicati dallo stack derivato sostituiscono quelli dello stack base, tranne:
+/// - `variables`, unite per nome;
+/// - `task_variables` e `pins`, unite per task (e per variabile);
+/// - `derived`, unite per variabile;
+/// - `add_tasks` e `remove_tasks`, che aggiungono e tolgono task all'elenco ereditato
+///   (o a quello indicato da `tasks`). I canary ereditati rimossi dall'elenco decadono.
+fn merge_stack_values(mut merged: HashMap<String, serde_yaml::Value>, child: &HashMap<String, serde_yaml::Value>) -> HashMap<String, serde_yaml::Value> {
+    use serde_yaml::Value;
+
+    merged.remove("extends");
+    merged.remove("add_tasks");
+    merged.remove("remove_tasks");
+
+    for (key, value) in child {
+        match (key.as_str(), merged.get_mut(key)) {
+            ("add_tasks" | "remove_tasks", _) => {},
+            ("variables", Some(Value::Sequence(base_vars))) => {
+                for var in value.as_sequence().into_iter().flatten() {
+                    let existing = base_vars.iter_mut()
+                        .find(|base_var| base_var.get("name").is_some() && base_var.get("name") == var.get("name"));
+                    match existing {
+                        Some(base_var) => *base_var = var.clone(),
+                        None => base_vars.push(var.clone()),
+                    }
+                }
+            },
+            ("task_variables", Some(Value::Mapping(base_tasks))) => {
+                for (task, vars) in value.as_mapping().into_iter().flatten() {
+                    match (base_tasks.get_mut(task), vars) {
+                        (Some(Value::Mapping(base_vars)), Value::Mapping(vars)) => {
+                            for (name, var) in vars {
+                                base_vars.insert(name.clone(), var.clone());
+                            }
+                        },
+                        _ => { base_tasks.insert(task.clone(), vars.clone()); },
+                    }
+                }
+            },
+            ("derived", Some(Value::Mapping(base_derived))) => {
+                for (var, expression) in value.as_mapping().into_iter().flatten() {
+                    base_derived.insert(var.clone(), expression.clone());
+                }
+            },
+            ("pins", Some(Value::Mapping(base_pins))) => {
+                for (task, version) in value.as_mapping().into_iter().flatten() {
+                    base_pins.insert(task.clone(), version.clone());
+                }
+            },
+            _ => { merged.insert(key.clone(), value.clone()); },
+        }
+    }
+
+    let mut tasks: Vec<Value> = merged.get("tasks")
+        .and_then(|v| v.as_sequence())
+        .cloned()
+        .unwrap_or_default();
+    if let Some(remove) = child.get("remove_tasks").and_then(|v| v.as_sequence()) {
+        tasks.retain(|task| !remove.contains(task));
+    }
+    for task in child.get("add_tasks").and_then(|v| v.as_sequence()).into_iter().flatten() {
+        if !tasks.contains(task) {
+            tasks.push(task.clone());
+        }
+    }
+
+    if !child.contains_key("canary") {
+        match merged.get("canary") {
+            Some(Value::String(canary)) if !tasks.contains(&Value::String(canary.clone())) => {
+                merged.remove("canary");
+            },
+            Some(Value::Sequence(canary)) => {
+                let kept: Vec<Value> = canary.iter().filter(|c| tasks.contains(c)).cloned().collect();
+                merged.insert("canary".to_string(), Value::Sequence(kept));
+            },
+            _ => {},
+        }
+    }
+
+    merged.insert("tasks".to_string(), Value::Sequence(tasks));
+    merged
+}
+
+
+
+/// Verifica che lo stack richiesto da un task (`requires_stack`) sia completamente installato
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+/// * `task_name` - Il nome del task che richiede lo stack
+/// * `reference` - Il riferimento allo stack richiesto (`nome` oppure `sorgente:nome`)
+///
+/// # Returns
+///
+/// Un errore che elenca i task mancanti se lo stack non è installato
+pub fn check_required_stack(config: &Config, task_name: &str, reference: &str) -> Result<()> {
+    // I cataloghi sono già stati scaricati al caricamento: basta rileggere lo stato
+    let tasks = task::read_tasks(config)?;
+    let stacks = read_stacks(config, &tasks)?;
+
+    let stack = stacks.iter()
+        .find(|s| s.matches_ref(reference))
+        .ok_or_else(|| anyhow!("Stack {} required by task {} not found", reference, task_name))?;
+
+    if stack.fully_installed {
+        return Ok(());
+    }
+
+    let missing: Vec<&str> = stack.task_names.iter()
+        .filter(|name| !tasks.iter().any(|t| t.matches_ref(name) && t.installed))
+        .map(|name| name.as_str())
+        .collect();
+
+    Err(anyhow!(
+        "Task {} requires stack {} to be fully installed; missing tasks: {}",
+        task_name,
+        stack.name,
+        missing.join(", ")
+    ))
 }
 
+/// Segna i task il cui stack prerequisito (`requires_stack`) non è ancora completamente installato
+pub fn mark_blocked_tasks(tasks: &mut [Task], stacks: &[Stack]) {
+    let blocked: Vec<Option<String>> = tasks.iter()
+        .map(|task| {
+            let required = task.requires_stack.as_ref()?;
+            let complete = stacks.iter()
+                .find(|s| s.matches_ref(required))
+                .is_some_and(|stack| !stack.task_names.is_empty() && stack.task_names.iter()
+                    .all(|name| tasks.iter().any(|t| t.matches_ref(name) && t.installed)));
+            (!complete).then(|| required.clone())
+        })
+        .collect();
+
+    for (task, blocked_by) in tasks.iter_mut().zip(blocked) {
+        task.blocked_by = blocked_by;
+    }
+}
 
+/// Restituisce gli stack che fanno riferimento a task non presenti nel catalogo
+///
+/// # Arguments
+///
+/// * `stacks` - Gli stack caricati
+/// * `tasks` - I task caricati
+///
+/// # Returns
+///
+/// Il riferimento qualificato di ogni stack con i task non trovati, nell'ordine del catalogo
+pub fn missing_task_references(stacks: &[Stack], tasks: &[Task]) -> Vec<(String, Vec<String>)> {
+    stacks.iter()
+        .map(|stack| (stack.qualified_name(), stack.missing_tasks(tasks)))
+        .filter(|(_, missing)| !missing.is_empty())
+        .collect()
+}
 
 /// Scarica gli stack dalle sorgenti configurate
 pub fn download_stacks_from_sources(config: &Config) -> Result<()> {
@@ -393,21 +1867,32 @@ pub fn download_stacks_from_sources(config: &Config) -> Result<()> {
 
     for source in &config.stack_sources {
         info!("Processing stack source: {}", source);
+        source.validate_layout()?;
+
+        let dest_dir = source.catalog_dir(Path::new(&config.stacks_dir));
+
+        // Un catalogo in sola lettura è già presente nella sua directory
+        if source.read_only {
+            info!("Stack source {} is read-only, using {:?}", source, dest_dir);
+            continue;
+        }
 
         // Determina il nome del file dalla URL
-        let file_name = source.split('/').last()
+        let file_name = source.file_name()
             .ok_or_else(|| anyhow!("Invalid stack source URL: {}", source))?;
 
         // Crea il percorso di destinazione
-        let dest_path = config.resolve_path(file_name, "stacks");
+        let dest_path = dest_dir.join(file_name);
 
         // Scarica il file se non esiste già
         if !dest_path.exists() {
             info!("Downloading stack from: {}", source);
-            downloader::download_and_extract(
-                source,
-                &Path::new(&config.stacks_dir),
+            downloader::download_and_extract_source(
+                &release::with_token(config, source)?,
+                &dest_dir,
                 config.download_timeout,
+                config.download_cache_dir().as_deref(),
+                &config.download_hosts,
             ).context(format!("Failed to download stack from: {}", source))?;
         } else {
             info!("Stack source already downloaded: {}", file_name);
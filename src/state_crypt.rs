@@ -0,0 +1,258 @@
+//! Cifratura a riposo dello stato
+//!
+//! Lo stato di Galatea e gli output catturati possono contenere dati sensibili: percorsi,
+//! nomi utente, token stampati dagli script. Con `encrypt_state: true` vengono cifrati con
+//! AES-256-GCM e una chiave della macchina:
+//!
+//! - i file di stato dei task (stato, versione, esiti, tentativi, fallimenti, checkpoint);
+//! - i valori delle variabili degli stack, i punti di ripristino e lo stato delle
+//!   esecuzioni pianificate;
+//! - la sessione dell'interfaccia;
+//! - i file dei bundle di `galatea record`, con l'output dei comandi eseguiti.
+//!
+//! La chiave viene creata alla prima scrittura nel file indicato da `state_key` (predefinito
+//! `state.key` accanto al file di configurazione), leggibile solo dal proprietario: una copia
+//! della directory di stato, o un suo backup, non basta a leggerne il contenuto. Senza la
+//! chiave i file cifrati non possono essere recuperati.
+//!
+//! I file cifrati si riconoscono dall'intestazione e vengono decifrati alla lettura anche se
+//! l'opzione viene disattivata; i file in chiaro restano leggibili e vengono cifrati alla
+//! scrittura successiva.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use anyhow::{Context, Result, anyhow};
+use lazy_static::lazy_static;
+use log::{info, warn};
+use openssl::symm::{Cipher, decrypt_aead, encrypt_aead};
+
+use crate::config::Config;
+use crate::layout::Layout;
+
+/// Intestazione dei file cifrati
+const MAGIC: &[u8] = b"GALATEA-ENCRYPTED-1\n";
+
+/// Lunghezza della chiave in byte
+const KEY_LEN: usize = 32;
+
+/// Lunghezza del nonce in byte
+const NONCE_LEN: usize = 12;
+
+/// Lunghezza del tag di autenticazione in byte
+const TAG_LEN: usize = 16;
+
+/// Impostazioni della cifratura, lette dalla configurazione
+struct Settings {
+    /// Indica se i file scritti vanno cifrati
+    enabled: bool,
+
+    /// File della chiave
+    key_path: PathBuf,
+
+    /// Chiave già letta dal file
+    key: Option<Vec<u8>>,
+}
+
+lazy_static! {
+    static ref SETTINGS: Mutex<Option<Settings>> = Mutex::new(None);
+}
+
+/// Restituisce il file della chiave predefinito, accanto al file di configurazione
+pub fn default_key_path() -> PathBuf {
+    Layout::current().config_path()
+        .parent()
+        .map(|dir| dir.join("state.key"))
+        .unwrap_or_else(|| PathBuf::from("state.key"))
+}
+
+/// Applica le opzioni di cifratura della configurazione
+///
+/// # Arguments
+///
+/// * `config` - La configurazione caricata
+pub fn configure(config: &Config) {
+    let key_path = config.state_key_path();
+    let Ok(mut settings) = SETTINGS.lock() else {
+        return;
+    };
+
+    // La chiave già letta resta valida finché non cambia il file
+    let key = settings.take()
+        .filter(|previous| previous.key_path == key_path)
+        .and_then(|previous| previous.key);
+    *settings = Some(Settings { enabled: config.encrypt_state, key_path, key });
+}
+
+/// Usa un altro file della chiave, es. per leggere un bundle registrato su un'altra macchina
+///
+/// # Arguments
+///
+/// * `key_path` - Il file della chiave
+pub fn set_key_path(key_path: PathBuf) {
+    if let Ok(mut settings) = SETTINGS.lock() {
+        let enabled = settings.as_ref().is_some_and(|settings| settings.enabled);
+        *settings = Some(Settings { enabled, key_path, key: None });
+    }
+}
+
+/// Indica se i file di stato scritti vengono cifrati
+pub fn is_enabled() -> bool {
+    SETTINGS.lock()
+        .map(|settings| settings.as_ref().is_some_and(|settings| settings.enabled))
+        .unwrap_or(false)
+}
+
+/// Indica se un contenuto è cifrato
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Scrive un file di stato, cifrandolo se la cifratura è attiva
+///
+/// # Arguments
+///
+/// * `path` - Il percorso del file
+/// * `contents` - Il contenuto in chiaro
+pub fn write(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    if !is_enabled() {
+        return fs::write(path, contents);
+    }
+
+    let sealed = seal(contents.as_ref())
+        .map_err(|e| io::Error::other(format!("{:#}", e)))?;
+    fs::write(path, sealed)
+}
+
+/// Legge un file di stato, decifrandolo se necessario
+///
+/// # Arguments
+///
+/// * `path` - Il percorso del file
+pub fn read(path: &Path) -> io::Result<Vec<u8>> {
+    decrypt(fs::read(path)?).map_err(|e| io::Error::other(format!("{:?}: {:#}", path, e)))
+}
+
+/// Decifra un contenuto letto da un file di stato o da un bundle, se cifrato
+///
+/// # Arguments
+///
+/// * `data` - Il contenuto, cifrato o in chiaro
+pub fn decrypt(data: Vec<u8>) -> Result<Vec<u8>> {
+    if !is_encrypted(&data) {
+        return Ok(data);
+    }
+    open(&data)
+}
+
+/// Legge un file di stato di testo, decifrandolo se necessario
+///
+/// # Arguments
+///
+/// * `path` - Il percorso del file
+pub fn read_to_string(path: &Path) -> io::Result<String> {
+    String::from_utf8(read(path)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Cifra un contenuto: intestazione, nonce, tag e testo cifrato
+fn seal(plain: &[u8]) -> Result<Vec<u8>> {
+    let key = key(true)?;
+
+    let mut nonce = [0u8; NONCE_LEN];
+    openssl::rand::rand_bytes(&mut nonce).context("Failed to generate nonce")?;
+    let mut tag = [0u8; TAG_LEN];
+    let ciphertext = encrypt_aead(Cipher::aes_256_gcm(), &key, Some(&nonce), MAGIC, plain, &mut tag)
+        .context("Failed to encrypt state")?;
+
+    let mut sealed = Vec::with_capacity(MAGIC.len() + NONCE_LEN + TAG_LEN + ciphertext.len());
+    sealed.extend_from_slice(MAGIC);
+    sealed.extend_from_slice(&nonce);
+    sealed.extend_from_slice(&tag);
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+/// Decifra un contenuto scritto da [`seal`]
+fn open(data: &[u8]) -> Result<Vec<u8>> {
+    let body = &data[MAGIC.len()..];
+    if body.len() < NONCE_LEN + TAG_LEN {
+        return Err(anyhow!("Encrypted state is truncated"));
+    }
+    let (nonce, rest) = body.split_at(NONCE_LEN);
+    let (tag, ciphertext) = rest.split_at(TAG_LEN);
+
+    let key = key(false)?;
+    decrypt_aead(Cipher::aes_256_gcm(), &key, Some(nonce), MAGIC, ciphertext, tag)
+        .map_err(|_| anyhow!("Failed to decrypt state: wrong state key or corrupted file"))
+}
+
+/// Restituisce la chiave, leggendola dal file o creandola se richiesto
+fn key(create: bool) -> Result<Vec<u8>> {
+    let mut settings = SETTINGS.lock().map_err(|_| anyhow!("State encryption settings are poisoned"))?;
+    let settings = settings.get_or_insert_with(|| Settings {
+        enabled: false,
+        key_path: default_key_path(),
+        key: None,
+    });
+
+    if let Some(key) = &settings.key {
+        return Ok(key.clone());
+    }
+
+    let key = if settings.key_path.exists() || !create {
+        load_key(&settings.key_path)?
+    } else {
+        create_key(&settings.key_path)?
+    };
+    settings.key = Some(key.clone());
+    Ok(key)
+}
+
+/// Legge la chiave da un file, in base64
+fn load_key(path: &Path) -> Result<Vec<u8>> {
+    let content = fs::read_to_string(path)
+        .context(format!("State is encrypted but the state key {:?} cannot be read", path))?;
+    let key = openssl::base64::decode_block(content.trim())
+        .map_err(|_| anyhow!("Invalid state key {:?}: not base64", path))?;
+    if key.len() != KEY_LEN {
+        return Err(anyhow!("Invalid state key {:?}: expected {} bytes, found {}", path, KEY_LEN, key.len()));
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if fs::metadata(path).is_ok_and(|metadata| metadata.permissions().mode() & 0o077 != 0) {
+            warn!("State key {:?} is readable by other users", path);
+        }
+    }
+
+    Ok(key)
+}
+
+/// Crea una nuova chiave casuale, leggibile solo dal proprietario
+fn create_key(path: &Path) -> Result<Vec<u8>> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .context(format!("Failed to create directory: {:?}", parent))?;
+    }
+
+    let mut key = vec![0u8; KEY_LEN];
+    openssl::rand::rand_bytes(&mut key).context("Failed to generate state key")?;
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)
+        .context(format!("Failed to create state key {:?}", path))?;
+    file.write_all(format!("{}\n", openssl::base64::encode_block(&key)).as_bytes())
+        .context(format!("Failed to write state key {:?}", path))?;
+
+    info!("Created state encryption key {:?}", path);
+    Ok(key)
+}
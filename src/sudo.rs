@@ -0,0 +1,277 @@
+//! Elevazione dei privilegi con sudo
+//!
+//! Quando Galatea non è eseguito come root (`--no-root-check`), i task con `sudo: true`
+//! eseguono script, playbook e comandi tramite `sudo`. Se sudo non richiede la password
+//! (regola `NOPASSWD` o credenziali ancora valide) il comando viene eseguito con `sudo -n`;
+//! altrimenti la password viene chiesta una volta per sessione e passata a `sudo -S -k`:
+//!
+//! 1. con la finestra registrata dall'interfaccia TUI ([`set_prompt`]), con input mascherato
+//! 2. con il programma indicato in `SUDO_ASKPASS`, se impostato
+//! 3. dal terminale, senza eco, nei sottocomandi da linea di comando
+//!
+//! La password viene verificata prima di essere usata e resta in memoria fino all'uscita,
+//! mascherata nei log; una password rifiutata viene chiesta di nuovo. Con `-k` sudo ignora
+//! le credenziali memorizzate e legge sempre la password: non resta mai sullo stdin del
+//! comando eseguito.
+
+use std::env;
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::Mutex;
+use anyhow::{Context, Result, anyhow};
+use lazy_static::lazy_static;
+use log::{debug, info, warn};
+
+use crate::executor;
+use crate::pty::TerminalInput;
+use crate::redact;
+use crate::utils;
+
+/// Numero di tentativi concessi per inserire la password
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Finestra che chiede la password all'utente; restituisce `None` se l'utente rinuncia
+pub type Prompt = Box<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+lazy_static! {
+    static ref PASSWORD: Mutex<Option<String>> = Mutex::new(None);
+    static ref PROMPT: Mutex<Option<Prompt>> = Mutex::new(None);
+}
+
+/// Registra la finestra con cui chiedere la password (es. quella dell'interfaccia TUI)
+pub fn set_prompt(prompt: Prompt) {
+    if let Ok(mut current) = PROMPT.lock() {
+        *current = Some(prompt);
+    }
+}
+
+/// Password da passare a un comando eseguito tramite sudo
+#[derive(Default)]
+pub struct Credentials {
+    /// La password da scrivere su stdin, se sudo la richiede
+    password: Option<String>,
+}
+
+impl Credentials {
+    /// Scrive la password sullo stdin del processo avviato, se richiesta
+    ///
+    /// Va chiamata subito dopo l'avvio: lo stdin viene poi chiuso e il comando lo trova
+    /// vuoto. Se la scrittura non riesce sudo non riceve la password e termina con errore.
+    pub fn feed(&self, child: &mut Child) {
+        let Some(password) = &self.password else {
+            return;
+        };
+        match child.stdin.take() {
+            Some(mut stdin) => {
+                if let Err(e) = writeln!(stdin, "{}", password) {
+                    warn!("Failed to pass the password to sudo: {}", e);
+                }
+            },
+            None => warn!("sudo standard input not available"),
+        }
+    }
+
+    /// Scrive la password sul terminale di uno script interattivo, se richiesta
+    ///
+    /// sudo legge la password dal terminale dello script, che la maschera nell'output.
+    pub fn feed_terminal(&self, input: &TerminalInput) {
+        let Some(password) = &self.password else {
+            return;
+        };
+        if let Err(e) = input.send(format!("{}\n", password).as_bytes()) {
+            warn!("Failed to pass the password to sudo: {}", e);
+        }
+    }
+}
+
+/// Prepara l'esecuzione di un comando tramite sudo, se richiesta e se Galatea non è root
+///
+/// Il comando viene sostituito da `sudo`, con lo stesso programma, gli stessi argomenti
+/// e la stessa directory; le variabili d'ambiente impostate sul comando sono preservate
+/// con `--preserve-env`. Va chiamata dopo aver impostato argomenti, directory e ambiente,
+/// e prima di configurare stdout e stderr.
+///
+/// # Arguments
+///
+/// * `requested` - Se il task ha chiesto l'elevazione (`sudo: true`)
+/// * `command` - Il comando da eseguire con i privilegi di root
+///
+/// # Returns
+///
+/// La password da passare al processo avviato, oppure un errore se non è disponibile
+pub fn prepare(requested: bool, command: &mut Command) -> Result<Credentials> {
+    if !requested || utils::is_running_as_root() {
+        return Ok(Credentials::default());
+    }
+
+    let password = if can_run_without_password() {
+        None
+    } else {
+        Some(password()?)
+    };
+
+    let mut sudo = Command::new("sudo");
+    // Prompt vuoto: la password arriva da stdin e sudo non deve scrivere nulla
+    sudo.args(["-p", ""]);
+    match password {
+        Some(_) => sudo.args(["-S", "-k"]),
+        None => sudo.arg("-n"),
+    };
+
+    let names: Vec<String> = command.get_envs()
+        .filter(|(_, value)| value.is_some())
+        .map(|(name, _)| name.to_string_lossy().to_string())
+        .collect();
+    if !names.is_empty() {
+        sudo.arg(format!("--preserve-env={}", names.join(",")));
+    }
+    for (name, value) in command.get_envs() {
+        match value {
+            Some(value) => sudo.env(name, value),
+            None => sudo.env_remove(name),
+        };
+    }
+
+    sudo.arg("--").arg(command.get_program()).args(command.get_args());
+    if let Some(dir) = command.get_current_dir() {
+        sudo.current_dir(dir);
+    }
+    if password.is_some() {
+        sudo.stdin(Stdio::piped());
+    }
+
+    debug!("Running {:?} with sudo", command.get_program());
+    *command = sudo;
+    Ok(Credentials { password })
+}
+
+/// Verifica se sudo può essere usato senza password
+///
+/// Il controllo viene avviato in una sessione propria come i comandi veri e propri, così
+/// che sudo consideri le stesse credenziali memorizzate.
+fn can_run_without_password() -> bool {
+    let mut check = Command::new("sudo");
+    check.args(["-n", "true"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    executor::spawn_in_session(&mut check)
+        .and_then(|mut child| child.wait())
+        .is_ok_and(|status| status.success())
+}
+
+/// Restituisce la password della sessione, chiedendola se non è ancora nota
+fn password() -> Result<String> {
+    if let Some(password) = PASSWORD.lock().ok().and_then(|password| password.clone()) {
+        return Ok(password);
+    }
+
+    let user = env::var("USER").unwrap_or_else(|_| "l'utente corrente".to_string());
+    for attempt in 1..=MAX_ATTEMPTS {
+        let message = if attempt == 1 {
+            format!("Password di sudo per {}:", user)
+        } else {
+            format!("Password errata, riprova ({}/{}). Password di sudo per {}:", attempt, MAX_ATTEMPTS, user)
+        };
+        let password = ask(&message)?
+            .ok_or_else(|| anyhow!("sudo password not provided"))?;
+
+        if validate(&password)? {
+            redact::register(&password);
+            if let Ok(mut cached) = PASSWORD.lock() {
+                *cached = Some(password.clone());
+            }
+            info!("sudo password accepted, cached for this session");
+            return Ok(password);
+        }
+        warn!("sudo rejected the password (attempt {}/{})", attempt, MAX_ATTEMPTS);
+    }
+    Err(anyhow!("sudo rejected the password {} times", MAX_ATTEMPTS))
+}
+
+/// Chiede la password con il primo metodo disponibile
+fn ask(message: &str) -> Result<Option<String>> {
+    if let Ok(prompt) = PROMPT.lock() {
+        if let Some(prompt) = prompt.as_ref() {
+            return Ok(prompt(message));
+        }
+    }
+
+    if let Some(askpass) = env::var_os("SUDO_ASKPASS").filter(|value| !value.is_empty()) {
+        debug!("Asking the sudo password with {:?}", askpass);
+        let output = Command::new(&askpass)
+            .arg(message)
+            .stdin(Stdio::null())
+            .stderr(Stdio::inherit())
+            .output()
+            .context(format!("Failed to run SUDO_ASKPASS program {:?}", askpass))?;
+        if !output.status.success() {
+            return Ok(None);
+        }
+        let password = String::from_utf8_lossy(&output.stdout);
+        return Ok(Some(password.trim_end_matches(['\r', '\n']).to_string()));
+    }
+
+    ask_terminal(message)
+}
+
+/// Chiede la password dal terminale, disattivando l'eco
+fn ask_terminal(message: &str) -> Result<Option<String>> {
+    let mut tty = OpenOptions::new().read(true).write(true).open("/dev/tty")
+        .context("No terminal to ask the sudo password (set SUDO_ASKPASS or run as root)")?;
+
+    write!(tty, "{} ", message)?;
+    tty.flush()?;
+
+    #[cfg(unix)]
+    let saved = {
+        use std::os::unix::io::AsRawFd;
+        let fd = tty.as_raw_fd();
+        let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(fd, &mut termios) } == 0 {
+            let saved = termios;
+            termios.c_lflag &= !libc::ECHO;
+            unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) };
+            Some((fd, saved))
+        } else {
+            None
+        }
+    };
+
+    let mut line = String::new();
+    let read = BufReader::new(&tty).read_line(&mut line);
+
+    #[cfg(unix)]
+    if let Some((fd, saved)) = saved {
+        unsafe { libc::tcsetattr(fd, libc::TCSANOW, &saved) };
+    }
+    writeln!(tty)?;
+
+    if read.context("Failed to read the sudo password")? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()))
+}
+
+/// Verifica una password eseguendo `true` con sudo
+///
+/// Con `-k` le credenziali memorizzate vengono ignorate, così la password viene sempre
+/// controllata; il controllo viene avviato in una sessione propria come i comandi veri e
+/// propri.
+fn validate(password: &str) -> Result<bool> {
+    let mut check = Command::new("sudo");
+    check.args(["-S", "-k", "-p", "", "true"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    let mut child = executor::spawn_in_session(&mut check)
+        .context("Failed to run sudo")?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // Con una password errata sudo chiede di nuovo e trova lo stdin chiuso
+        let _ = writeln!(stdin, "{}", password);
+    }
+    let status = child.wait().context("Failed to wait for sudo")?;
+    Ok(status.success())
+}
@@ -0,0 +1,158 @@
+//! Integrazione con systemd
+//!
+//! Questo modulo genera e installa l'unità systemd della modalità demone e implementa
+//! il protocollo sd_notify (readiness, stato e watchdog) senza dipendenze esterne.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+use anyhow::{Context, Result, anyhow};
+use log::{info, debug};
+
+/// Nome dell'unità systemd installata da `galatea install-service`
+pub const SERVICE_NAME: &str = "galatea.service";
+
+/// Directory delle unità systemd amministrate localmente
+const UNIT_DIR: &str = "/etc/systemd/system";
+
+/// Opzioni per la generazione dell'unità systemd
+#[derive(Debug, Clone)]
+pub struct ServiceOptions {
+    /// Percorso dell'eseguibile di Galatea
+    pub executable: PathBuf,
+
+    /// File di configurazione da passare al demone
+    pub config_path: Option<PathBuf>,
+
+    /// Intervallo del watchdog in secondi (0 per disabilitarlo)
+    pub watchdog_secs: u64,
+}
+
+/// Invia un messaggio di stato a systemd (es. "READY=1", "WATCHDOG=1")
+///
+/// Se il processo non è stato avviato da systemd con `Type=notify` la chiamata non ha effetto.
+///
+/// # Arguments
+///
+/// * `state` - Le assegnazioni da inviare, separate da a capo
+pub fn notify(state: &str) -> Result<()> {
+    let Some(socket_path) = env::var_os("NOTIFY_SOCKET") else {
+        return Ok(());
+    };
+
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::linux::net::SocketAddrExt;
+        use std::os::unix::ffi::OsStrExt;
+        use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+        let bytes = socket_path.as_bytes();
+        let address = match bytes.strip_prefix(b"@") {
+            // I socket nel namespace astratto sono indicati con '@'
+            Some(name) => SocketAddr::from_abstract_name(name),
+            None => SocketAddr::from_pathname(Path::new(&socket_path)),
+        }.context(format!("Invalid NOTIFY_SOCKET: {:?}", socket_path))?;
+
+        let socket = UnixDatagram::unbound()
+            .context("Failed to create notification socket")?;
+        socket.send_to_addr(state.as_bytes(), &address)
+            .context(format!("Failed to notify systemd: {}", state))?;
+
+        debug!("systemd notified: {}", state.replace('\n', " "));
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (socket_path, state);
+    }
+
+    Ok(())
+}
+
+/// Restituisce l'intervallo del watchdog richiesto da systemd, se attivo per questo processo
+pub fn watchdog_interval() -> Option<Duration> {
+    let usec = env::var("WATCHDOG_USEC").ok()?.parse::<u64>().ok()?;
+
+    // WATCHDOG_PID, se presente, indica il processo a cui è destinato il watchdog
+    if let Ok(pid) = env::var("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok()? != std::process::id() {
+            return None;
+        }
+    }
+
+    (usec > 0).then(|| Duration::from_micros(usec))
+}
+
+/// Genera il contenuto dell'unità systemd per la modalità demone
+pub fn unit_file(options: &ServiceOptions) -> String {
+    let mut exec_start = format!("{}", options.executable.display());
+    if let Some(config_path) = &options.config_path {
+        exec_start.push_str(&format!(" --config {}", config_path.display()));
+    }
+    exec_start.push_str(" daemon");
+
+    let mut unit = String::new();
+    unit.push_str("[Unit]\n");
+    unit.push_str("Description=Galatea configuration daemon\n");
+    unit.push_str("Documentation=https://github.com/matteokutufa/galatea\n");
+    unit.push_str("Wants=network-online.target\n");
+    unit.push_str("After=network-online.target\n");
+    unit.push_str("\n[Service]\n");
+    unit.push_str("Type=notify\n");
+    unit.push_str("NotifyAccess=main\n");
+    unit.push_str(&format!("ExecStart={}\n", exec_start));
+    unit.push_str("Restart=on-failure\n");
+    unit.push_str("RestartSec=30\n");
+    if options.watchdog_secs > 0 {
+        unit.push_str(&format!("WatchdogSec={}\n", options.watchdog_secs));
+    }
+    unit.push_str("\n[Install]\n");
+    unit.push_str("WantedBy=multi-user.target\n");
+    unit
+}
+
+/// Installa l'unità systemd e, se richiesto, la abilita e la avvia
+///
+/// # Arguments
+///
+/// * `options` - Le opzioni dell'unità
+/// * `enable` - Se `true` esegue `systemctl enable --now`
+///
+/// # Returns
+///
+/// Il percorso del file dell'unità installato
+pub fn install_service(options: &ServiceOptions, enable: bool) -> Result<PathBuf> {
+    let unit_path = Path::new(UNIT_DIR).join(SERVICE_NAME);
+
+    fs::write(&unit_path, unit_file(options))
+        .context(format!("Failed to write systemd unit: {:?}", unit_path))?;
+    info!("systemd unit written to {:?}", unit_path);
+
+    run_systemctl(&["daemon-reload"])?;
+    if enable {
+        run_systemctl(&["enable", "--now", SERVICE_NAME])?;
+        info!("{} enabled and started", SERVICE_NAME);
+    }
+
+    Ok(unit_path)
+}
+
+/// Esegue systemctl e restituisce un errore se fallisce
+fn run_systemctl(args: &[&str]) -> Result<()> {
+    let output = Command::new("systemctl")
+        .args(args)
+        .output()
+        .context("Failed to run systemctl")?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "systemctl {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(())
+}
@@ -5,15 +5,35 @@
 
 use std::path::{Path, PathBuf};
 use std::fs;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Display;
+use std::thread;
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Local};
 use anyhow::{Context, Result, anyhow};
 use serde::{Serialize, Deserialize};
-use log::{info, warn, error};
+use log::{Level, info, warn, error};
 
+use crate::catalog_schema::{self, CatalogKind};
+use crate::changes::{self, ChangeSummary, SystemSnapshot};
 use crate::config::Config;
 use crate::downloader;
-use crate::executor;
+use crate::error::{GalateaError, ResultExt};
+use crate::hooks::{self, HookPayload};
+use crate::events::{self, Operation};
+use crate::executor::{self, ScriptResult, ScriptStatus};
+use crate::jobs;
+use crate::locks;
+use crate::mac;
+use crate::release;
+use crate::resources::ResourceLimits;
+use crate::script;
+use crate::secrets;
+use crate::source::{self, Source};
+use crate::stack;
+use crate::state_crypt;
+use crate::wasm_runner;
+use crate::workspace::Workspace;
 
 /// Tipi di script supportati
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -24,6 +44,8 @@ pub enum ScriptType {
     Ansible,
     /// Mix di entrambi
     Mixed,
+    /// Runner WebAssembly che implementa un tipo di task personalizzato
+    Wasm,
 }
 
 impl ScriptType {
@@ -33,7 +55,8 @@ impl ScriptType {
             "bash" | "b" => Ok(ScriptType::Bash),
             "ansible" | "a" => Ok(ScriptType::Ansible),
             "mixed" | "m" => Ok(ScriptType::Mixed),
-            _ => Err(anyhow!("Unknown script type: {}", s)),
+            "wasm" | "w" => Ok(ScriptType::Wasm),
+            _ => Err(GalateaError::Config(format!("Unknown script type: {}", s)).into()),
         }
     }
 
@@ -43,6 +66,7 @@ impl ScriptType {
             ScriptType::Bash => "bash",
             ScriptType::Ansible => "ansible",
             ScriptType::Mixed => "mixed",
+            ScriptType::Wasm => "wasm",
         }
     }
 
@@ -52,10 +76,209 @@ impl ScriptType {
             ScriptType::Bash => 'B',
             ScriptType::Ansible => 'A',
             ScriptType::Mixed => 'M',
+            ScriptType::Wasm => 'W',
         }
     }
 }
 
+/// Evidenze che indicano che un task è già stato applicato sul sistema
+///
+/// Usate dall'importazione dello stato per adottare Galatea su server già configurati.
+/// Tutte le evidenze indicate devono essere soddisfatte.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Detection {
+    /// Pacchetti che devono risultare installati
+    pub packages: Vec<String>,
+
+    /// Servizi systemd che devono risultare attivi o abilitati
+    pub services: Vec<String>,
+
+    /// File o directory che devono esistere
+    pub files: Vec<String>,
+
+    /// Comando shell che deve terminare con successo
+    pub command: Option<String>,
+}
+
+impl Detection {
+    /// Verifica se non è stata definita alcuna evidenza
+    pub fn is_empty(&self) -> bool {
+        self.packages.is_empty() && self.services.is_empty() && self.files.is_empty() && self.command.is_none()
+    }
+}
+
+/// Canale di rilascio delle versioni di un task
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    /// Versioni stabili
+    #[default]
+    Stable,
+    /// Versioni in prova; comprende anche le versioni stabili
+    Testing,
+}
+
+impl Channel {
+    /// Converte il canale in una stringa
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            Channel::Stable => "stable",
+            Channel::Testing => "testing",
+        }
+    }
+
+    /// Verifica se una versione pubblicata su `channel` è disponibile su questo canale
+    pub fn includes(&self, channel: Channel) -> bool {
+        match self {
+            Channel::Stable => channel == Channel::Stable,
+            Channel::Testing => true,
+        }
+    }
+}
+
+/// Versione di un task pubblicata nell'indice della sorgente
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskVersion {
+    /// Numero di versione, es. "1.4.2"
+    pub version: String,
+
+    /// URL da cui scaricare il bundle di questa versione
+    pub url: String,
+
+    /// Canale su cui è pubblicata la versione
+    #[serde(default)]
+    pub channel: Channel,
+
+    /// Checksum SHA-256 atteso del bundle di questa versione
+    #[serde(default)]
+    pub sha256: Option<String>,
+
+    /// Dimensione attesa del bundle in byte
+    #[serde(default)]
+    pub size: Option<u64>,
+}
+
+/// Attesa predefinita tra due tentativi di installazione, in secondi
+const DEFAULT_RETRY_DELAY: u64 = 10;
+
+/// Timeout predefinito del controllo di salute, in secondi
+const DEFAULT_HEALTHCHECK_TIMEOUT: u64 = 30;
+
+/// Controllo di salute eseguito dopo l'installazione di un task
+///
+/// ```yaml
+/// healthcheck:
+///   command: "curl -fsS http://localhost/health"
+///   timeout: 30
+///   rollback: true
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthCheck {
+    /// Comando shell che deve terminare con successo
+    pub command: String,
+
+    /// Tempo massimo di esecuzione del comando, in secondi
+    #[serde(default = "default_healthcheck_timeout")]
+    pub timeout: u64,
+
+    /// Se il controllo fallisce, disinstalla il task
+    #[serde(default = "default_rollback")]
+    pub rollback: bool,
+}
+
+impl HealthCheck {
+    /// Crea un controllo di salute con timeout e rollback predefiniti
+    pub fn new(command: impl Into<String>) -> Self {
+        HealthCheck {
+            command: command.into(),
+            timeout: DEFAULT_HEALTHCHECK_TIMEOUT,
+            rollback: true,
+        }
+    }
+}
+
+fn default_healthcheck_timeout() -> u64 {
+    DEFAULT_HEALTHCHECK_TIMEOUT
+}
+
+fn default_rollback() -> bool {
+    true
+}
+
+/// Errore di un'installazione riuscita che non ha superato il controllo di salute
+#[derive(Debug)]
+pub struct HealthCheckFailed {
+    /// Nome del task
+    pub task: String,
+
+    /// Motivo del fallimento del controllo
+    pub reason: String,
+
+    /// Indica se il task è stato disinstallato
+    pub rolled_back: bool,
+}
+
+impl Display for HealthCheckFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Health check of task {} failed: {}", self.task, self.reason)?;
+        if self.rolled_back {
+            write!(f, " (rolled back)")
+        } else {
+            write!(f, " (not rolled back, the task is still installed)")
+        }
+    }
+}
+
+impl std::error::Error for HealthCheckFailed {}
+
+/// Tentativo di installazione di un task
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskAttempt {
+    /// Numero del tentativo (da 1)
+    pub attempt: u32,
+
+    /// Inizio del tentativo (RFC 3339)
+    pub started_at: String,
+
+    /// Durata del tentativo in secondi
+    pub duration_secs: u64,
+
+    /// Motivo del fallimento; assente se il tentativo è riuscito
+    pub error: Option<String>,
+}
+
+/// Ultima operazione fallita di un task, conservata finché un'operazione non riesce
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskFailure {
+    /// Operazione fallita (install, reinstall, uninstall, reset, remediate)
+    pub operation: String,
+
+    /// Momento del fallimento (RFC 3339)
+    pub failed_at: String,
+
+    /// Motivo del fallimento
+    pub error: String,
+
+    /// Indica se l'installazione è riuscita ma il controllo di salute è fallito
+    #[serde(default)]
+    pub unhealthy: bool,
+
+    /// Numero di fallimenti consecutivi del task
+    #[serde(default = "default_consecutive_failures")]
+    pub consecutive: u32,
+
+    /// Indica se il task è in quarantena: il ripristino automatico non lo ritenta finché
+    /// un operatore non rimuove la quarantena o un'operazione manuale non riesce
+    #[serde(default)]
+    pub quarantined: bool,
+}
+
+/// Numero di fallimenti consecutivi dei file di fallimento che non lo riportano
+fn default_consecutive_failures() -> u32 {
+    1
+}
+
 /// Definizione di un task
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Task {
@@ -71,25 +294,136 @@ pub struct Task {
     /// URL da cui scaricare il task
     pub url: String,
 
+    /// Checksum SHA-256 atteso del bundle indicato da `url`
+    pub sha256: Option<String>,
+
+    /// Dimensione attesa in byte del bundle indicato da `url`
+    pub size: Option<u64>,
+
     /// Comando per la pulizia/disinstallazione
     pub cleanup_command: Option<String>,
 
+    /// Esegue script, playbook e comando di pulizia tramite sudo quando Galatea non è root
+    pub sudo: bool,
+
+    /// Esegue lo script bash in un terminale collegato all'operatore, per gli installer che pongono domande
+    pub interactive: bool,
+
+    /// Modulo WebAssembly che esegue le azioni dei task `wasm`, relativo alla directory dei
+    /// task (predefinito: `runner.wasm` nel bundle)
+    pub runner: Option<String>,
+
+    /// Condizione rhai valutata prima dell'installazione: se è falsa il task viene saltato
+    pub when: Option<String>,
+
     /// Dipendenze (altri task che devono essere eseguiti prima)
     pub dependencies: Vec<String>,
 
+    /// Stack che deve essere completamente installato prima del task
+    pub requires_stack: Option<String>,
+
     /// Tag per categorizzare il task
     pub tags: Vec<String>,
 
+    /// Risorse usate in modo esclusivo (es. `apt`, `docker`): i task che ne condividono una
+    /// non vengono mai eseguiti insieme
+    pub locks: Vec<String>,
+
     /// Flag che indica se è richiesto il riavvio
     pub requires_reboot: bool,
 
+    /// Evidenze per rilevare un'installazione esistente (importazione dello stato)
+    pub detect: Option<Detection>,
+
+    /// Flag che indica se il task è deprecato
+    pub deprecated: bool,
+
+    /// Riferimento al task che sostituisce quello deprecato
+    pub replaced_by: Option<String>,
+
+    /// Versione del bundle indicato da `url`
+    pub version: Option<String>,
+
+    /// Indice delle versioni pubblicate dalla sorgente
+    pub versions: Vec<TaskVersion>,
+
+    /// Espressione regolare che riconosce le righe di avanzamento nell'output degli script
+    pub progress_pattern: Option<String>,
+
+    /// Numero di nuovi tentativi se l'installazione fallisce
+    pub retries: u32,
+
+    /// Attesa in secondi tra un tentativo e il successivo
+    pub retry_delay: u64,
+
+    /// Intervallo minimo in secondi tra due esecuzioni riuscite di remediate o della verifica
+    pub min_interval: u64,
+
+    /// Priorità e limiti di risorse con cui vengono eseguiti gli script del task
+    pub resources: ResourceLimits,
+
+    /// Controllo eseguito dopo l'installazione; se fallisce il task viene disinstallato
+    pub healthcheck: Option<HealthCheck>,
+
+    /// Tentativi dell'ultima installazione
+    #[serde(skip)]
+    pub attempts: Vec<TaskAttempt>,
+
+    /// Ultima operazione fallita, se nessuna operazione successiva è riuscita
+    #[serde(skip)]
+    pub last_failure: Option<TaskFailure>,
+
+    /// Ultimo checkpoint raggiunto da un'installazione non completata
+    #[serde(skip)]
+    pub checkpoint: Option<String>,
+
+    /// Esito strutturato dell'ultima azione eseguita, se riportato dallo script
+    #[serde(skip)]
+    pub last_result: Option<ScriptResult>,
+
+    /// Modifiche al sistema rilevate durante l'ultima installazione
+    #[serde(skip)]
+    pub last_changes: Option<ChangeSummary>,
+
+    /// Stack prerequisito non ancora installato (calcolato all'apertura della vista dei task)
+    #[serde(skip)]
+    pub blocked_by: Option<String>,
+
+    /// Regola della configurazione che esclude il task da questa macchina (calcolata al caricamento)
+    #[serde(skip)]
+    pub denied: Option<String>,
+
+    /// Versione scelta dallo stack in esecuzione (impostata a runtime)
+    #[serde(skip)]
+    pub pinned: Option<TaskVersion>,
+
     /// Percorso locale dove è stato scaricato il task (calcolato a runtime)
     #[serde(skip)]
     pub local_path: Option<PathBuf>,
 
+    /// Copia del bundle nella cache dei download, se presente (calcolata al caricamento)
+    #[serde(skip)]
+    pub cached_bundle: Option<PathBuf>,
+
     /// Flag che indica se il task è installato
     #[serde(skip)]
     pub installed: bool,
+
+    /// Versione installata, se nota (registrata dalle installazioni)
+    #[serde(skip)]
+    pub installed_version: Option<String>,
+
+    /// Variabili passate agli script del task (impostate a runtime dallo stack)
+    #[serde(skip)]
+    pub variables: HashMap<String, String>,
+
+    /// Etichetta del catalogo da cui proviene il task (calcolata al caricamento)
+    #[serde(skip)]
+    pub source: String,
+
+    /// Sottodirectory dei bundle e dello stato, dallo spazio dei nomi della sorgente
+    #[serde(skip)]
+    pub namespace: Option<String>,
 }
 
 impl Task {
@@ -98,15 +432,15 @@ impl Task {
         // Estrai i valori richiesti
         let name = values.get("name")
             .and_then(|v| v.as_str())
-            .ok_or_else(|| anyhow!("Task missing 'name' field"))?
+            .ok_or_else(|| GalateaError::Config("Task missing 'name' field".to_string()))?
             .to_string();
 
         let type_str = values.get("type")
             .and_then(|v| v.as_str())
-            .ok_or_else(|| anyhow!("Task missing 'type' field"))?;
+            .ok_or_else(|| GalateaError::Config("Task missing 'type' field".to_string()))?;
 
         let script_type = ScriptType::from_str(type_str)
-            .context(format!("Invalid script type for task {}: {}", name, type_str))?;
+            .config_context(format!("Invalid script type for task {}: {}", name, type_str))?;
 
         let description = values.get("description")
             .and_then(|v| v.as_str())
@@ -115,9 +449,17 @@ impl Task {
 
         let url = values.get("url")
             .and_then(|v| v.as_str())
-            .ok_or_else(|| anyhow!("Task missing 'url' field"))?
+            .ok_or_else(|| GalateaError::Config("Task missing 'url' field".to_string()))?
             .to_string();
 
+        // Estrai checksum e dimensione attesi del bundle
+        let sha256 = values.get("sha256")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
+        let size = values.get("size")
+            .and_then(|v| v.as_u64());
+
         let cleanup_command = values.get("cleanup_command")
             .and_then(|v| v.as_str())
             .map(|s| s.to_string());
@@ -134,6 +476,10 @@ impl Task {
             }
         }
 
+        let requires_stack = values.get("requires_stack")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
         // Estrai i tag
         let mut tags = Vec::new();
         if let Some(tag_values) = values.get("tags") {
@@ -146,32 +492,366 @@ impl Task {
             }
         }
 
+        // Estrai le risorse bloccate dal task, verificando che i nomi siano validi
+        let locks: Vec<String> = match values.get("locks") {
+            Some(value) => serde_yaml::from_value(value.clone())
+                .config_context(format!("Invalid 'locks' for task {}", name))?,
+            None => Vec::new(),
+        };
+        for lock in &locks {
+            locks::validate_name(lock)
+                .config_context(format!("Invalid 'locks' for task {}", name))?;
+        }
+
         // Estrai il flag requires_reboot
         let requires_reboot = values.get("requires_reboot")
             .and_then(|v| v.as_bool())
             .unwrap_or(false);
 
+        // Estrai le evidenze per l'importazione dello stato
+        let detect = match values.get("detect") {
+            Some(value) => Some(serde_yaml::from_value::<Detection>(value.clone())
+                .config_context(format!("Invalid 'detect' section for task {}", name))?),
+            None => None,
+        };
+
+        let sudo = values.get("sudo")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        let interactive = values.get("interactive")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        let runner = values.get("runner")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
+        // Estrai la condizione di installazione, verificando che l'espressione sia valida
+        let when = values.get("when")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
+        if let Some(expression) = &when {
+            script::check(expression)
+                .config_context(format!("Invalid 'when' for task {}", name))?;
+        }
+
+        // Estrai i metadati di deprecazione
+        let deprecated = values.get("deprecated")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        let replaced_by = values.get("replaced_by")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
+        // Estrai la versione e l'indice delle versioni pubblicate
+        let version = values.get("version")
+            .and_then(|v| match v {
+                serde_yaml::Value::String(s) => Some(s.clone()),
+                serde_yaml::Value::Number(n) => Some(n.to_string()),
+                _ => None,
+            });
+
+        let versions = match values.get("versions") {
+            Some(value) => serde_yaml::from_value::<Vec<TaskVersion>>(value.clone())
+                .config_context(format!("Invalid 'versions' section for task {}", name))?,
+            None => Vec::new(),
+        };
+
+        // Estrai il marcatore di avanzamento, verificando che l'espressione sia valida
+        let progress_pattern = values.get("progress_pattern")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
+        if let Some(pattern) = &progress_pattern {
+            executor::ProgressMarker::new(name.as_str(), pattern)
+                .config_context(format!("Invalid 'progress_pattern' for task {}", name))?;
+        }
+
+        // Estrai la politica di ripetizione dei tentativi
+        let retries = values.get("retries")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0) as u32;
+
+        let retry_delay = values.get("retry_delay")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(DEFAULT_RETRY_DELAY);
+
+        // Estrai l'intervallo minimo tra le esecuzioni di remediate e verifica
+        let min_interval = values.get("min_interval")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0);
+
+        // Estrai i limiti di risorse, verificando che i valori siano ammessi
+        let resources = match values.get("resources") {
+            Some(value) => serde_yaml::from_value::<ResourceLimits>(value.clone())
+                .config_context(format!("Invalid 'resources' section for task {}", name))?,
+            None => ResourceLimits::default(),
+        };
+        resources.validate()
+            .config_context(format!("Invalid 'resources' section for task {}", name))?;
+
+        // Estrai il controllo di salute, indicato come comando o come mappa con le opzioni
+        let healthcheck = match values.get("healthcheck") {
+            Some(serde_yaml::Value::String(command)) => Some(HealthCheck::new(command.as_str())),
+            Some(value) => Some(serde_yaml::from_value::<HealthCheck>(value.clone())
+                .config_context(format!("Invalid 'healthcheck' section for task {}", name))?),
+            None => None,
+        };
+
         Ok(Task {
             name,
             script_type,
             description,
             url,
+            sha256,
+            size,
             cleanup_command,
+            sudo,
+            interactive,
+            runner,
+            when,
             dependencies,
+            requires_stack,
             tags,
+            locks,
             requires_reboot,
+            detect,
+            deprecated,
+            replaced_by,
+            version,
+            versions,
+            progress_pattern,
+            retries,
+            retry_delay,
+            min_interval,
+            resources,
+            healthcheck,
+            attempts: Vec::new(),
+            blocked_by: None,
+            denied: None,
+            last_failure: None,
+            checkpoint: None,
+            last_result: None,
+            last_changes: None,
+            pinned: None,
             local_path: None,
+            cached_bundle: None,
             installed: false,
+            installed_version: None,
+            variables: HashMap::new(),
+            source: String::new(),
+            namespace: None,
         })
     }
 
+    /// Restituisce il marcatore di avanzamento dichiarato dal task
+    fn progress_marker(&self) -> Option<executor::ProgressMarker> {
+        let pattern = self.progress_pattern.as_deref()?;
+        match executor::ProgressMarker::new(format!("Task {}", self.name), pattern) {
+            Ok(marker) => Some(marker),
+            Err(e) => {
+                warn!("Ignoring progress pattern of task {}: {}", self.name, e);
+                None
+            }
+        }
+    }
+
+    /// Restituisce il contesto con cui eseguire un'azione del task
+    fn run_context(&self, config: &Config, action: &str) -> executor::RunContext {
+        let mut context = executor::RunContext::new(&self.name, action, &config.state_dir);
+        context.ansible_env = config.ansible_env.clone();
+        context.sudo = self.sudo;
+        context.interactive = self.interactive;
+        context.child_env = config.child_env.clone();
+        context.watchdog = config.watchdog.clone();
+        // Solo l'installazione può essere ripresa da un checkpoint
+        if action == "install" {
+            context.checkpoint_state = Some(self.state_file(config, "checkpoint"));
+        }
+        context
+    }
+
+    /// Legge l'ultimo checkpoint raggiunto dallo script di installazione, se registrato
+    fn read_checkpoint(&self, config: &Config) -> Option<String> {
+        state_crypt::read_to_string(&self.state_file(config, "checkpoint")).ok()
+            .map(|content| content.trim().to_string())
+            .filter(|checkpoint| !checkpoint.is_empty())
+    }
+
+    /// Dimentica il checkpoint dell'installazione, che al prossimo avvio riparte da capo
+    fn clear_checkpoint(&mut self, config: &Config) -> Result<()> {
+        let checkpoint_file = self.state_file(config, "checkpoint");
+        if checkpoint_file.exists() {
+            fs::remove_file(&checkpoint_file)
+                .state_context(format!("Failed to remove checkpoint file for task {}", self.name))?;
+        }
+        self.checkpoint = None;
+        Ok(())
+    }
+
+    /// Blocca le risorse del task per la durata di un'azione, attendendo che si liberino
+    fn lock_resources(&self, config: &Config) -> Result<locks::ResourceGuard> {
+        locks::acquire(Path::new(&config.state_dir), &self.name, &self.locks)
+    }
+
+    /// Esegue il comando di pulizia del task, tramite sudo se il task lo richiede
+    fn run_cleanup_command(&self, config: &Config, command: &str) -> Result<()> {
+        if self.sudo {
+            executor::run_with_sudo(command, self.resources.timeout, &config.child_env)
+        } else {
+            executor::run_command(command, self.resources.timeout, &config.child_env)
+        }
+    }
+
+    /// Restituisce il riferimento qualificato del task (`sorgente:nome`)
+    pub fn qualified_name(&self) -> String {
+        source::qualified_name(&self.source, &self.name)
+    }
+
+    /// Verifica se un riferimento (`nome` oppure `sorgente:nome`) indica questo task
+    pub fn matches_ref(&self, reference: &str) -> bool {
+        source::matches_reference(reference, &self.source, &self.name)
+    }
+
+    /// Restituisce la regola di `denied_tasks` o `denied_tags` che esclude il task da questa macchina
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - La configurazione corrente
+    ///
+    /// # Returns
+    ///
+    /// Il riferimento o il tag (`tag:<nome>`) che esclude il task, oppure `None` se è consentito
+    pub fn denied_by(&self, config: &Config) -> Option<String> {
+        if let Some(reference) = config.denied_tasks.iter().find(|reference| self.matches_ref(reference)) {
+            return Some(reference.clone());
+        }
+        config.denied_tags.iter()
+            .find(|tag| self.tags.contains(tag))
+            .map(|tag| format!("tag:{}", tag))
+    }
+
+    /// Verifica se la condizione `when` del task è soddisfatta su questa macchina
+    ///
+    /// La condizione vede le variabili del task in `vars` e i fatti della macchina in `facts`;
+    /// i task senza condizione sono sempre installabili.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - La configurazione corrente
+    pub fn condition_met(&self, config: &Config) -> Result<bool> {
+        let Some(expression) = &self.when else {
+            return Ok(true);
+        };
+
+        script::condition(expression, &self.variables, &script::facts(config))
+            .context(format!("Failed to evaluate the condition of task {}", self.name))
+    }
+
+    /// Sceglie la versione del bundle dall'indice della sorgente
+    ///
+    /// # Arguments
+    ///
+    /// * `pin` - La versione richiesta, se fissata
+    /// * `channel` - Il canale da cui prendere la versione più recente, se indicato
+    ///
+    /// # Returns
+    ///
+    /// La versione scelta, oppure `None` se va usato il bundle predefinito (`url`)
+    pub fn resolve_version(&self, pin: Option<&str>, channel: Option<Channel>) -> Result<Option<TaskVersion>> {
+        if let Some(pin) = pin {
+            if let Some(found) = self.versions.iter().find(|v| v.version == pin) {
+                return Ok(Some(found.clone()));
+            }
+            if self.version.as_deref() == Some(pin) {
+                return Ok(None);
+            }
+            return Err(GalateaError::Config(format!("Version {} of task {} not found in the source index", pin, self.name)).into());
+        }
+
+        let Some(channel) = channel else {
+            return Ok(None);
+        };
+
+        let latest = self.versions.iter()
+            .filter(|v| channel.includes(v.channel))
+            .max_by(|a, b| compare_versions(&a.version, &b.version));
+
+        if latest.is_none() && !self.versions.is_empty() {
+            warn!("No {} version of task {} in the source index, using the default bundle", channel.to_str(), self.name);
+        }
+
+        Ok(latest.cloned())
+    }
+
+    /// Restituisce la versione del bundle che verrà usato
+    pub fn effective_version(&self) -> Option<&str> {
+        match &self.pinned {
+            Some(pinned) => Some(&pinned.version),
+            None => self.version.as_deref(),
+        }
+    }
+
+    /// Restituisce la directory locale del bundle in uso
+    fn bundle_dir(&self, config: &Config) -> PathBuf {
+        match &self.pinned {
+            Some(pinned) => config.resolve_path(&self.namespaced(&format!("{}-{}", self.name, pinned.version)), "tasks"),
+            None => config.resolve_path(&self.namespaced(&self.name), "tasks"),
+        }
+    }
+
+    /// Prepara la copia di lavoro del bundle in cui eseguire un'azione
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - La configurazione corrente
+    /// * `context` - Il contesto dell'azione
+    ///
+    /// # Returns
+    ///
+    /// La copia di lavoro, rimossa al termine dell'azione
+    fn workspace(&self, config: &Config, context: &executor::RunContext) -> Result<Workspace> {
+        let local_path = self.local_path.as_ref()
+            .ok_or_else(|| GalateaError::State(format!("Task not downloaded: {}", self.name)))?;
+        Workspace::create(config, &self.bundle_dir(config), local_path, context)
+    }
+
+    /// Restituisce il percorso di un file di stato del task, es. `<nome>.state`
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - La configurazione corrente
+    /// * `extension` - L'estensione del file di stato
+    pub fn state_file(&self, config: &Config, extension: &str) -> PathBuf {
+        config.resolve_path(&self.namespaced(&format!("{}.{}", self.name, extension)), "state")
+    }
+
+    /// Antepone lo spazio dei nomi della sorgente a un percorso relativo
+    fn namespaced(&self, path: &str) -> String {
+        match &self.namespace {
+            Some(namespace) => format!("{}/{}", namespace, path),
+            None => path.to_string(),
+        }
+    }
+
+    /// Scrive un file di stato del task, creando la directory dello spazio dei nomi
+    fn write_state(path: &Path, content: &str) -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        state_crypt::write(path, content)
+    }
+
     /// Verifica se il task è installato
     pub fn check_installed(&mut self, config: &Config) -> Result<bool> {
-        let state_file = config.resolve_path(&format!("{}.state", self.name), "state");
+        let state_file = self.state_file(config, "state");
 
         if state_file.exists() {
-            let content = fs::read_to_string(&state_file)
-                .context(format!("Failed to read state file for task {}", self.name))?;
+            let content = state_crypt::read_to_string(&state_file)
+                .state_context(format!("Failed to read state file for task {}", self.name))?;
 
             // Se il file esiste e contiene "installed", il task è installato
             self.installed = content.trim() == "installed";
@@ -179,13 +859,352 @@ impl Task {
             self.installed = false;
         }
 
+        self.denied = self.denied_by(config);
+
+        let version_file = self.state_file(config, "version");
+        self.installed_version = state_crypt::read_to_string(&version_file).ok()
+            .map(|content| content.trim().to_string())
+            .filter(|version| !version.is_empty());
+
+        // Esito dell'ultima azione, se lo script lo ha riportato
+        let result_file = self.state_file(config, "result.json");
+        self.last_result = state_crypt::read_to_string(&result_file).ok()
+            .and_then(|content| serde_json::from_str(&content).ok());
+
+        let changes_file = self.state_file(config, "changes.json");
+        self.last_changes = state_crypt::read_to_string(&changes_file).ok()
+            .and_then(|content| serde_json::from_str(&content).ok());
+
+        let attempts_file = self.state_file(config, "attempts.json");
+        self.attempts = state_crypt::read_to_string(&attempts_file).ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+
+        let failure_file = self.state_file(config, "failure.json");
+        self.last_failure = state_crypt::read_to_string(&failure_file).ok()
+            .and_then(|content| serde_json::from_str(&content).ok());
+
+        self.checkpoint = self.read_checkpoint(config);
+
+        self.cached_bundle = config.download_cache_dir()
+            .and_then(|cache_dir| downloader::cached_file(self.download_url(), &cache_dir));
+
         Ok(self.installed)
     }
 
+    /// Esegue lo script di installazione secondo il tipo del task
+    fn run_install_script(&self, config: &Config, local_path: &Path, context: &executor::RunContext, progress: Option<&executor::ProgressMarker>) -> Result<Option<ScriptResult>> {
+        let result = match self.script_type {
+            ScriptType::Bash => {
+                executor::run_bash_script(local_path, &["install"], &self.variables, &context, progress, &self.resources)
+                    .context(format!("Failed to run bash install script for task {}", self.name))?
+            },
+            ScriptType::Ansible => {
+                executor::run_ansible_playbook(local_path, "install", &self.variables, &context, progress, &self.resources)
+                    .context(format!("Failed to run ansible playbook for task {}", self.name))?
+            },
+            ScriptType::Mixed => {
+                // Per i task mixed, prova prima ansible e poi bash se necessario
+                match executor::run_ansible_playbook(local_path, "install", &self.variables, &context, progress, &self.resources) {
+                    Ok(result) => result,
+                    Err(e) => {
+                        warn!("Ansible playbook failed for mixed task {}, trying bash: {}", self.name, e);
+                        executor::run_bash_script(local_path, &["install"], &self.variables, &context, progress, &self.resources)
+                            .context(format!("Both ansible and bash failed for mixed task {}", self.name))?
+                    }
+                }
+            },
+            ScriptType::Wasm => {
+                self.run_wasm_action(config, local_path, "install", context, progress)?
+            },
+        };
+
+        Ok(result)
+    }
+
+    /// Esegue un'azione di un task `wasm` con il suo runner
+    fn run_wasm_action(&self, config: &Config, local_path: &Path, action: &str, context: &executor::RunContext, progress: Option<&executor::ProgressMarker>) -> Result<Option<ScriptResult>> {
+        let module = match &self.runner {
+            Some(runner) => config.resolve_path(runner, "tasks"),
+            None => local_path.join(wasm_runner::DEFAULT_RUNNER),
+        };
+        wasm_runner::run(&module, local_path, action, &self.variables, context, progress, &self.resources)
+            .context(format!("Failed to run WASM runner for {} of task {}", action, self.name))
+    }
+
+    /// Conserva lo storico dei tentativi dell'ultima installazione nella directory di stato
+    fn record_attempts(&mut self, config: &Config, attempts: Vec<TaskAttempt>) -> Result<()> {
+        let attempts_file = self.state_file(config, "attempts.json");
+        let json = serde_json::to_string_pretty(&attempts)
+            .state_context(format!("Failed to serialize attempts of task {}", self.name))?;
+        Self::write_state(&attempts_file, &json)
+            .state_context(format!("Failed to write attempts file for task {}", self.name))?;
+
+        self.attempts = attempts;
+        Ok(())
+    }
+
+    /// Riporta le modifiche al sistema dell'ultima installazione e le conserva nella directory di stato
+    fn record_changes(&mut self, config: &Config, summary: ChangeSummary) -> Result<()> {
+        if summary.is_empty() {
+            info!("Task {} made no visible change to packages, services or ports", self.name);
+        }
+        for line in summary.lines() {
+            info!("Task {} changes: {}", self.name, line);
+            events::log_line(Level::Info, format!("{}: {}", self.name, line));
+        }
+        changes::record(&self.name, &summary);
+
+        let changes_file = self.state_file(config, "changes.json");
+        let json = serde_json::to_string_pretty(&summary)
+            .state_context(format!("Failed to serialize changes of task {}", self.name))?;
+        Self::write_state(&changes_file, &json)
+            .state_context(format!("Failed to write changes file for task {}", self.name))?;
+
+        self.last_changes = Some(summary);
+        Ok(())
+    }
+
+    /// Registra l'esito strutturato di un'azione e lo conserva nella directory di stato
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - La configurazione corrente
+    /// * `action` - L'azione eseguita (install, uninstall, reset, remediate)
+    /// * `result` - L'esito riportato dallo script, se presente
+    fn record_result(&mut self, config: &Config, action: &str, result: Option<ScriptResult>) -> Result<()> {
+        let result_file = self.state_file(config, "result.json");
+
+        let Some(result) = result else {
+            // Un esito precedente non descrive più lo stato del task
+            if result_file.exists() {
+                fs::remove_file(&result_file)
+                    .state_context(format!("Failed to remove result file for task {}", self.name))?;
+            }
+            self.last_result = None;
+            return Ok(());
+        };
+
+        info!("Task {} {} result: {} (changed: {}){}", self.name, action, result.status.label(), result.changed,
+              result.message.as_deref().map(|m| format!(": {}", m)).unwrap_or_default());
+
+        if let Some(message) = &result.message {
+            events::log_line(Level::Info, format!("{}: {}", self.name, message));
+        }
+        if result.status == ScriptStatus::Skipped {
+            events::log_line(Level::Info, format!("{}: {} non necessario", self.name, action));
+        }
+        if result.reboot_required {
+            warn!("Task {} requires a reboot to complete {}", self.name, action);
+            events::log_line(Level::Warn, format!("{}: è necessario un riavvio", self.name));
+        }
+
+        let json = serde_json::to_string_pretty(&result)
+            .state_context(format!("Failed to serialize result of task {}", self.name))?;
+        Self::write_state(&result_file, &json)
+            .state_context(format!("Failed to write result file for task {}", self.name))?;
+
+        self.last_result = Some(result);
+        Ok(())
+    }
+
+    /// Restituisce l'ultima esecuzione riuscita di un'azione, se più recente di `min_interval`
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - La configurazione corrente
+    /// * `action` - L'azione (remediate, verify)
+    ///
+    /// # Returns
+    ///
+    /// La data dell'ultima esecuzione riuscita se l'azione può essere saltata
+    pub fn ran_recently(&self, config: &Config, action: &str) -> Option<DateTime<Local>> {
+        if self.min_interval == 0 {
+            return None;
+        }
+
+        let last = self.load_runs(config).get(action)
+            .and_then(|date| DateTime::parse_from_rfc3339(date).ok())?
+            .with_timezone(&Local);
+        let elapsed = Local::now().signed_duration_since(last);
+
+        (elapsed.num_seconds() >= 0 && (elapsed.num_seconds() as u64) < self.min_interval).then_some(last)
+    }
+
+    /// Registra l'esecuzione riuscita di un'azione nella directory di stato
+    pub fn record_success(&self, config: &Config, action: &str) -> Result<()> {
+        let mut runs = self.load_runs(config);
+        runs.insert(action.to_string(), Local::now().to_rfc3339());
+
+        let runs_file = self.state_file(config, "runs.json");
+        let json = serde_json::to_string_pretty(&runs)
+            .state_context(format!("Failed to serialize runs of task {}", self.name))?;
+        Self::write_state(&runs_file, &json)
+            .state_context(format!("Failed to write runs file for task {}", self.name))
+    }
+
+    /// Legge le date delle ultime esecuzioni riuscite per azione
+    fn load_runs(&self, config: &Config) -> BTreeMap<String, String> {
+        let runs_file = self.state_file(config, "runs.json");
+        state_crypt::read_to_string(&runs_file).ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Verifica se l'ultima operazione sul task è fallita
+    pub fn has_failed(&self) -> bool {
+        self.last_failure.is_some()
+    }
+
+    /// Verifica se il task è in quarantena dopo troppi fallimenti consecutivi
+    pub fn is_quarantined(&self) -> bool {
+        self.last_failure.as_ref().is_some_and(|f| f.quarantined)
+    }
+
+    /// Rimuove la quarantena del task e azzera il conteggio dei fallimenti consecutivi
+    ///
+    /// Il fallimento resta registrato finché un'operazione sul task non riesce.
+    ///
+    /// # Returns
+    ///
+    /// `true` se il task era in quarantena
+    pub fn clear_quarantine(&mut self, config: &Config) -> Result<bool> {
+        let Some(failure) = self.last_failure.as_mut().filter(|f| f.quarantined) else {
+            return Ok(false);
+        };
+        failure.quarantined = false;
+        failure.consecutive = 0;
+
+        let json = serde_json::to_string_pretty(&failure)
+            .state_context(format!("Failed to serialize failure of task {}", self.name))?;
+        Self::write_state(&self.state_file(config, "failure.json"), &json)
+            .state_context(format!("Failed to write failure file for task {}", self.name))?;
+
+        info!("Quarantine of task {} cleared", self.name);
+        Ok(true)
+    }
+
+    /// Ripete l'ultima operazione fallita del task
+    pub fn retry_failed(&mut self, config: &Config) -> Result<()> {
+        let failure = self.last_failure.clone()
+            .ok_or_else(|| GalateaError::State(format!("Task {} has no failed operation to retry", self.name)))?;
+
+        info!("Retrying failed {} of task {}", failure.operation, self.name);
+        match failure.operation.as_str() {
+            "reinstall" => self.reinstall(config, false),
+            "uninstall" => self.uninstall(config),
+            "reset" => self.reset(config),
+            "remediate" => self.remediate(config),
+            _ => self.install(config),
+        }
+    }
+
+    /// Esegue un'operazione pubblicandone gli eventi e registra se è fallita
+    ///
+    /// Un'operazione riuscita rimuove il fallimento registrato in precedenza. Al termine
+    /// vengono eseguiti gli hook dell'operazione (vedi [`hooks`]).
+    fn tracked<F>(&mut self, config: &Config, operation: Operation, body: F) -> Result<()>
+    where
+        F: FnOnce(&mut Self) -> Result<()>,
+    {
+        let name = self.name.clone();
+        let started = Local::now();
+        let mut run_id = None;
+        let result = events::track(operation, &name, || {
+            run_id = events::current_run_id();
+            body(self)
+        });
+
+        if let Err(e) = self.record_failure(config, operation, result.as_ref().err()) {
+            warn!("Failed to record outcome of task {}: {}", self.name, e);
+        }
+
+        let mut payload = HookPayload::new("task", operation, &name, run_id, started, result.as_ref().err());
+        payload.result = self.last_result.clone();
+        if matches!(operation, Operation::Install | Operation::Reinstall) {
+            payload.changes = self.last_changes.clone();
+        }
+        hooks::run(config, &payload);
+
+        result
+    }
+
+    /// Conserva nella directory di stato l'ultima operazione fallita, o la rimuove se è riuscita
+    fn record_failure(&mut self, config: &Config, operation: Operation, error: Option<&anyhow::Error>) -> Result<()> {
+        let failure_file = self.state_file(config, "failure.json");
+
+        let Some(error) = error else {
+            if failure_file.exists() {
+                fs::remove_file(&failure_file)
+                    .state_context(format!("Failed to remove failure file for task {}", self.name))?;
+            }
+            self.last_failure = None;
+            return Ok(());
+        };
+
+        // Dopo `quarantine_after` fallimenti consecutivi il task va in quarantena
+        let consecutive = self.last_failure.as_ref().map_or(0, |f| f.consecutive) + 1;
+        let quarantined = self.is_quarantined()
+            || (config.quarantine_after > 0 && consecutive >= config.quarantine_after);
+        if quarantined && !self.is_quarantined() {
+            warn!("Task {} quarantined after {} consecutive failures", self.name, consecutive);
+        }
+
+        let failure = TaskFailure {
+            operation: operation.to_str().to_string(),
+            failed_at: Local::now().to_rfc3339(),
+            error: format!("{:#}", error),
+            unhealthy: error.downcast_ref::<HealthCheckFailed>().is_some(),
+            consecutive,
+            quarantined,
+        };
+        let json = serde_json::to_string_pretty(&failure)
+            .state_context(format!("Failed to serialize failure of task {}", self.name))?;
+        Self::write_state(&failure_file, &json)
+            .state_context(format!("Failed to write failure file for task {}", self.name))?;
+
+        self.last_failure = Some(failure);
+        Ok(())
+    }
+
+    /// Verifica se il task richiede un riavvio, per definizione o per l'esito dell'ultima azione
+    pub fn reboot_required(&self) -> bool {
+        self.requires_reboot || self.last_result.as_ref().is_some_and(|r| r.reboot_required)
+    }
+
     /// Installa il task
     pub fn install(&mut self, config: &Config) -> Result<()> {
+        self.tracked(config, Operation::Install, |task| task.run_install(config))
+    }
+
+    /// Esegue l'installazione del task senza pubblicare eventi
+    fn run_install(&mut self, config: &Config) -> Result<()> {
         info!("Installing task: {}", self.name);
 
+        if self.deprecated {
+            match &self.replaced_by {
+                Some(replacement) => warn!("Task {} is deprecated, use {} instead", self.name, replacement),
+                None => warn!("Task {} is deprecated", self.name),
+            }
+        }
+
+        // I task esclusi dalla configurazione non vengono mai installati su questa macchina
+        if let Some(rule) = self.denied_by(config) {
+            return Err(GalateaError::Config(format!("Task {} is denied on this host ({})", self.name, rule)).into());
+        }
+
+        // Un task la cui condizione non è soddisfatta su questa macchina viene saltato
+        if !self.condition_met(config)? {
+            info!("Skipping task {}: condition `{}` not met", self.name, self.when.as_deref().unwrap_or_default());
+            events::log_line(Level::Info, format!("{}: condizione non soddisfatta, task saltato", self.name));
+            return Ok(());
+        }
+
+        // Lo stack prerequisito deve essere completo prima di scaricare ed eseguire il task
+        if let Some(required) = &self.requires_stack {
+            stack::check_required_stack(config, &self.name, required)?;
+        }
+
         // Scarica il task se necessario
         self.download(config)?;
 
@@ -196,98 +1215,300 @@ impl Task {
             // Per ora, avvisiamo solo e procediamo
         }
 
-        // Esegui il task
-        let local_path = self.local_path.as_ref()
-            .ok_or_else(|| anyhow!("Task not downloaded: {}", self.name))?;
-
-        match self.script_type {
-            ScriptType::Bash => {
-                executor::run_bash_script(local_path, &["install"])
-                    .context(format!("Failed to run bash install script for task {}", self.name))?;
-            },
-            ScriptType::Ansible => {
-                executor::run_ansible_playbook(local_path, "install")
-                    .context(format!("Failed to run ansible playbook for task {}", self.name))?;
-            },
-            ScriptType::Mixed => {
-                // Per i task mixed, prova prima ansible e poi bash se necessario
-                if let Err(e) = executor::run_ansible_playbook(local_path, "install") {
-                    warn!("Ansible playbook failed for mixed task {}, trying bash: {}", self.name, e);
-                    executor::run_bash_script(local_path, &["install"])
-                        .context(format!("Both ansible and bash failed for mixed task {}", self.name))?;
-                }
+        // Esegui il task, ripetendo i tentativi falliti secondo la politica del task
+        let progress = self.progress_marker();
+        let context = self.run_context(config, "install");
+        let _resources = self.lock_resources(config)?;
+        let before = config.change_summary.then(SystemSnapshot::capture);
+
+        let max_attempts = self.retries + 1;
+        let mut attempts = Vec::new();
+        let result = loop {
+            let attempt = attempts.len() as u32 + 1;
+            let started = Instant::now();
+            let started_at = Local::now().to_rfc3339();
+            // Ogni tentativo parte da una copia pulita del bundle e riprende dall'ultimo
+            // checkpoint raggiunto, anche da un'esecuzione precedente interrotta
+            let mut context = context.clone();
+            context.resume_from = self.read_checkpoint(config);
+            let outcome = self.workspace(config, &context)
+                .and_then(|workspace| self.run_install_script(config, workspace.path(), &context, progress.as_ref()));
+
+            attempts.push(TaskAttempt {
+                attempt,
+                started_at,
+                duration_secs: started.elapsed().as_secs(),
+                error: outcome.as_ref().err().map(|e| format!("{:#}", e)),
+            });
+
+            match outcome {
+                Ok(result) => break Ok(result),
+                // Un job annullato non va ripetuto
+                Err(e) if attempt < max_attempts && !jobs::cancel_requested() => {
+                    warn!("Attempt {}/{} of task {} failed, retrying in {}s: {:#}",
+                          attempt, max_attempts, self.name, self.retry_delay, e);
+                    events::log_line(Level::Warn, format!("{}: tentativo {}/{} fallito, nuovo tentativo tra {}s",
+                                                          self.name, attempt, max_attempts, self.retry_delay));
+                    thread::sleep(Duration::from_secs(self.retry_delay));
+                },
+                Err(e) => break Err(e),
             }
-        }
+        };
+
+        self.record_attempts(config, attempts)?;
+        self.checkpoint = self.read_checkpoint(config);
+        let result = result?;
+        self.clear_checkpoint(config)?;
+
+        self.record_result(config, "install", result)?;
 
         // Segna come installato
-        let state_file = config.resolve_path(&format!("{}.state", self.name), "state");
-        fs::write(&state_file, "installed")
-            .context(format!("Failed to write state file for task {}", self.name))?;
+        self.mark_installed(config)?;
+
+        // I file installati nelle directory gestite riprendono le etichette SELinux attese
+        mac::restore_labels(config);
+
+        if let Some(before) = before {
+            self.record_changes(config, before.diff(&SystemSnapshot::capture()))?;
+        }
+
+        if let Some(check) = self.healthcheck.clone() {
+            self.run_healthcheck(config, &check)?;
+        }
 
-        self.installed = true;
         info!("Task {} installed successfully", self.name);
 
         Ok(())
     }
 
+    /// Esegue il controllo di salute dopo l'installazione, tramite sudo se il task lo richiede,
+    /// e se fallisce disinstalla il task
+    fn run_healthcheck(&mut self, config: &Config, check: &HealthCheck) -> Result<()> {
+        info!("Running health check of task {}: {}", self.name, check.command);
+
+        // Il controllo ha gli stessi privilegi dello script che ha installato il task
+        let result = if self.sudo {
+            executor::run_with_sudo(&check.command, Some(check.timeout), &config.child_env)
+        } else {
+            executor::run_command_with_timeout(&check.command, check.timeout, &config.child_env)
+        };
+        let Err(e) = result else {
+            info!("Health check of task {} passed", self.name);
+            return Ok(());
+        };
+
+        warn!("Health check of task {} failed: {:#}", self.name, e);
+        events::log_line(Level::Warn, format!("{}: controllo di salute fallito", self.name));
+
+        let rolled_back = check.rollback && match self.run_uninstall(config) {
+            Ok(()) => {
+                events::log_line(Level::Warn, format!("{}: installazione annullata", self.name));
+                true
+            },
+            Err(uninstall_error) => {
+                error!("Rollback of task {} failed: {:#}", self.name, uninstall_error);
+                false
+            },
+        };
+
+        Err(HealthCheckFailed {
+            task: self.name.clone(),
+            reason: format!("{:#}", e),
+            rolled_back,
+        }.into())
+    }
+
+    /// Registra il task come installato senza eseguirlo
+    pub fn mark_installed(&mut self, config: &Config) -> Result<()> {
+        let state_file = self.state_file(config, "state");
+        Self::write_state(&state_file, "installed")
+            .state_context(format!("Failed to write state file for task {}", self.name))?;
+
+        // La versione installata serve a riconoscere gli aggiornamenti disponibili
+        let version_file = self.state_file(config, "version");
+        self.installed_version = self.effective_version().map(str::to_string);
+        match &self.installed_version {
+            Some(version) => Self::write_state(&version_file, version)
+                .state_context(format!("Failed to write version file for task {}", self.name))?,
+            None if version_file.exists() => fs::remove_file(&version_file)
+                .state_context(format!("Failed to remove version file for task {}", self.name))?,
+            None => {},
+        }
+
+        self.installed = true;
+        Ok(())
+    }
+
+    /// Restituisce la versione stabile più recente del catalogo, se successiva a quella installata
+    ///
+    /// I task installati senza una versione registrata non hanno aggiornamenti disponibili.
+    pub fn available_update(&self) -> Option<String> {
+        if !self.installed {
+            return None;
+        }
+        let installed = self.installed_version.as_deref()?;
+
+        let latest = self.versions.iter()
+            .filter(|v| Channel::Stable.includes(v.channel))
+            .map(|v| v.version.as_str())
+            .chain(self.version.as_deref())
+            .max_by(|a, b| compare_versions(a, b))?;
+
+        (compare_versions(latest, installed) == std::cmp::Ordering::Greater).then(|| latest.to_string())
+    }
+
+    /// Aggiorna il task installato alla versione indicata da [`Task::available_update`]
+    ///
+    /// Il bundle della nuova versione viene scaricato e installato sopra quello attuale,
+    /// senza disinstallarlo.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - La configurazione corrente
+    ///
+    /// # Returns
+    ///
+    /// La versione installata, oppure `None` se il task era già aggiornato
+    pub fn update(&mut self, config: &Config) -> Result<Option<String>> {
+        let Some(version) = self.available_update() else {
+            return Ok(None);
+        };
+        info!("Updating task {} from {} to {}", self.name,
+              self.installed_version.as_deref().unwrap_or("-"), version);
+
+        self.pinned = self.resolve_version(Some(&version), None)?;
+        let result = self.reinstall(config, false);
+        self.pinned = None;
+
+        result.map(|_| Some(version))
+    }
+
+    /// Reinstalla il task anche se risulta già installato
+    ///
+    /// Il bundle locale viene rimosso e riscaricato, in modo da applicare eventuali
+    /// modifiche agli script o ripristinare file danneggiati manualmente.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - La configurazione corrente
+    /// * `uninstall_first` - Se `true` esegue la disinstallazione prima della nuova installazione
+    pub fn reinstall(&mut self, config: &Config, uninstall_first: bool) -> Result<()> {
+        self.tracked(config, Operation::Reinstall, |task| task.run_reinstall(config, uninstall_first))
+    }
+
+    /// Esegue la reinstallazione del task senza pubblicare eventi
+    fn run_reinstall(&mut self, config: &Config, uninstall_first: bool) -> Result<()> {
+        info!("Reinstalling task: {} (uninstall first: {})", self.name, uninstall_first);
+
+        // Un'installazione danneggiata potrebbe non disinstallarsi correttamente:
+        // in quel caso si prosegue comunque con la nuova installazione
+        if uninstall_first && self.check_installed(config)? {
+            if let Err(e) = self.uninstall(config) {
+                warn!("Uninstall of task {} failed during reinstall, continuing: {}", self.name, e);
+            }
+        }
+
+        // Rimuove il bundle scaricato per forzarne un nuovo download
+        let task_dir = self.bundle_dir(config);
+        if task_dir.exists() {
+            fs::remove_dir_all(&task_dir)
+                .context(format!("Failed to remove local bundle for task {}", self.name))?;
+        }
+        self.local_path = None;
+
+        self.install(config)
+            .context(format!("Failed to reinstall task {}", self.name))
+    }
+
     /// Disinstalla il task
     pub fn uninstall(&mut self, config: &Config) -> Result<()> {
+        self.tracked(config, Operation::Uninstall, |task| task.run_uninstall(config))
+    }
+
+    /// Esegue la disinstallazione del task senza pubblicare eventi
+    fn run_uninstall(&mut self, config: &Config) -> Result<()> {
         info!("Uninstalling task: {}", self.name);
 
         // Verifica che il task sia installato
         if !self.check_installed(config)? {
-            return Err(anyhow!("Task is not installed: {}", self.name));
+            return Err(GalateaError::State(format!("Task is not installed: {}", self.name)).into());
         }
 
         // Scarica il task se necessario
         self.download(config)?;
 
-        // Esegui il comando di cleanup
-        let local_path = self.local_path.as_ref()
-            .ok_or_else(|| anyhow!("Task not downloaded: {}", self.name))?;
+        // Esegui il comando di cleanup in una copia di lavoro del bundle
+        let progress = self.progress_marker();
+        let context = self.run_context(config, "uninstall");
+        let _resources = self.lock_resources(config)?;
+        let workspace = self.workspace(config, &context)?;
+        let local_path = workspace.path();
 
-        match self.script_type {
+        let result = match self.script_type {
             ScriptType::Bash => {
                 if let Some(cmd) = &self.cleanup_command {
-                    executor::run_command(cmd)
+                    self.run_cleanup_command(config, cmd)
                         .context(format!("Failed to run cleanup command for task {}", self.name))?;
+                    None
                 } else {
-                    executor::run_bash_script(local_path, &["uninstall"])
-                        .context(format!("Failed to run bash uninstall script for task {}", self.name))?;
+                    executor::run_bash_script(local_path, &["uninstall"], &self.variables, &context, progress.as_ref(), &self.resources)
+                        .context(format!("Failed to run bash uninstall script for task {}", self.name))?
                 }
             },
             ScriptType::Ansible => {
                 if let Some(cmd) = &self.cleanup_command {
-                    executor::run_command(cmd)
+                    self.run_cleanup_command(config, cmd)
                         .context(format!("Failed to run cleanup command for task {}", self.name))?;
+                    None
                 } else {
-                    executor::run_ansible_playbook(local_path, "uninstall")
-                        .context(format!("Failed to run ansible uninstall playbook for task {}", self.name))?;
+                    executor::run_ansible_playbook(local_path, "uninstall", &self.variables, &context, progress.as_ref(), &self.resources)
+                        .context(format!("Failed to run ansible uninstall playbook for task {}", self.name))?
                 }
             },
             ScriptType::Mixed => {
                 if let Some(cmd) = &self.cleanup_command {
-                    executor::run_command(cmd)
+                    self.run_cleanup_command(config, cmd)
                         .context(format!("Failed to run cleanup command for task {}", self.name))?;
+                    None
                 } else {
                     // Per i task mixed, prova prima ansible e poi bash se necessario
-                    if let Err(e) = executor::run_ansible_playbook(local_path, "uninstall") {
-                        warn!("Ansible playbook failed for mixed task {}, trying bash: {}", self.name, e);
-                        executor::run_bash_script(local_path, &["uninstall"])
-                            .context(format!("Both ansible and bash failed for mixed task {}", self.name))?;
+                    match executor::run_ansible_playbook(local_path, "uninstall", &self.variables, &context, progress.as_ref(), &self.resources) {
+                        Ok(result) => result,
+                        Err(e) => {
+                            warn!("Ansible playbook failed for mixed task {}, trying bash: {}", self.name, e);
+                            executor::run_bash_script(local_path, &["uninstall"], &self.variables, &context, progress.as_ref(), &self.resources)
+                                .context(format!("Both ansible and bash failed for mixed task {}", self.name))?
+                        }
                     }
                 }
-            }
-        }
+            },
+            ScriptType::Wasm => {
+                if let Some(cmd) = &self.cleanup_command {
+                    self.run_cleanup_command(config, cmd)
+                        .context(format!("Failed to run cleanup command for task {}", self.name))?;
+                    None
+                } else {
+                    self.run_wasm_action(config, local_path, "uninstall", &context, progress.as_ref())?
+                }
+            },
+        };
+        self.record_result(config, "uninstall", result)?;
+        self.clear_checkpoint(config)?;
 
         // Rimuovi il file di stato
-        let state_file = config.resolve_path(&format!("{}.state", self.name), "state");
+        let state_file = self.state_file(config, "state");
         if state_file.exists() {
             fs::remove_file(&state_file)
-                .context(format!("Failed to remove state file for task {}", self.name))?;
+                .state_context(format!("Failed to remove state file for task {}", self.name))?;
+        }
+        let version_file = self.state_file(config, "version");
+        if version_file.exists() {
+            fs::remove_file(&version_file)
+                .state_context(format!("Failed to remove version file for task {}", self.name))?;
         }
 
         self.installed = false;
+        self.installed_version = None;
         info!("Task {} uninstalled successfully", self.name);
 
         Ok(())
@@ -295,38 +1516,53 @@ impl Task {
 
     /// Reset del task alle impostazioni iniziali
     pub fn reset(&mut self, config: &Config) -> Result<()> {
+        self.tracked(config, Operation::Reset, |task| task.run_reset(config))
+    }
+
+    /// Esegue il reset del task senza pubblicare eventi
+    fn run_reset(&mut self, config: &Config) -> Result<()> {
         info!("Resetting task: {}", self.name);
 
         // Verifica che il task sia installato
         if !self.check_installed(config)? {
-            return Err(anyhow!("Task is not installed: {}", self.name));
+            return Err(GalateaError::State(format!("Task is not installed: {}", self.name)).into());
         }
 
         // Scarica il task se necessario
         self.download(config)?;
 
-        // Esegui il comando di reset
-        let local_path = self.local_path.as_ref()
-            .ok_or_else(|| anyhow!("Task not downloaded: {}", self.name))?;
+        // Esegui il comando di reset in una copia di lavoro del bundle
+        let progress = self.progress_marker();
+        let context = self.run_context(config, "reset");
+        let _resources = self.lock_resources(config)?;
+        let workspace = self.workspace(config, &context)?;
+        let local_path = workspace.path();
 
-        match self.script_type {
+        let result = match self.script_type {
             ScriptType::Bash => {
-                executor::run_bash_script(local_path, &["reset"])
-                    .context(format!("Failed to run bash reset script for task {}", self.name))?;
+                executor::run_bash_script(local_path, &["reset"], &self.variables, &context, progress.as_ref(), &self.resources)
+                    .context(format!("Failed to run bash reset script for task {}", self.name))?
             },
             ScriptType::Ansible => {
-                executor::run_ansible_playbook(local_path, "reset")
-                    .context(format!("Failed to run ansible reset playbook for task {}", self.name))?;
+                executor::run_ansible_playbook(local_path, "reset", &self.variables, &context, progress.as_ref(), &self.resources)
+                    .context(format!("Failed to run ansible reset playbook for task {}", self.name))?
             },
             ScriptType::Mixed => {
                 // Per i task mixed, prova prima ansible e poi bash se necessario
-                if let Err(e) = executor::run_ansible_playbook(local_path, "reset") {
-                    warn!("Ansible playbook failed for mixed task {}, trying bash: {}", self.name, e);
-                    executor::run_bash_script(local_path, &["reset"])
-                        .context(format!("Both ansible and bash failed for mixed task {}", self.name))?;
+                match executor::run_ansible_playbook(local_path, "reset", &self.variables, &context, progress.as_ref(), &self.resources) {
+                    Ok(result) => result,
+                    Err(e) => {
+                        warn!("Ansible playbook failed for mixed task {}, trying bash: {}", self.name, e);
+                        executor::run_bash_script(local_path, &["reset"], &self.variables, &context, progress.as_ref(), &self.resources)
+                            .context(format!("Both ansible and bash failed for mixed task {}", self.name))?
+                    }
                 }
-            }
-        }
+            },
+            ScriptType::Wasm => {
+                self.run_wasm_action(config, local_path, "reset", &context, progress.as_ref())?
+            },
+        };
+        self.record_result(config, "reset", result)?;
 
         info!("Task {} reset successfully", self.name);
 
@@ -335,66 +1571,115 @@ impl Task {
 
     /// Riavvia i servizi del task
     pub fn remediate(&mut self, config: &Config) -> Result<()> {
+        self.tracked(config, Operation::Remediate, |task| task.run_remediate(config))
+    }
+
+    /// Esegue il ripristino dei servizi del task senza pubblicare eventi
+    fn run_remediate(&mut self, config: &Config) -> Result<()> {
         info!("Remediating task: {}", self.name);
 
         // Verifica che il task sia installato
         if !self.check_installed(config)? {
-            return Err(anyhow!("Task is not installed: {}", self.name));
+            return Err(GalateaError::State(format!("Task is not installed: {}", self.name)).into());
+        }
+
+        // Gli script costosi non vengono ripetuti prima di min_interval
+        if let Some(last) = self.ran_recently(config, "remediate") {
+            info!("Skipping remediate of task {}: last successful run at {}", self.name, last.to_rfc3339());
+            events::log_line(Level::Info, format!("{}: ripristino saltato, l'ultimo è riuscito alle {}",
+                                                  self.name, last.format("%d/%m/%Y %H:%M")));
+            return Ok(());
         }
 
         // Scarica il task se necessario
         self.download(config)?;
 
-        // Esegui il comando di remediation
-        let local_path = self.local_path.as_ref()
-            .ok_or_else(|| anyhow!("Task not downloaded: {}", self.name))?;
+        // Esegui il comando di remediation in una copia di lavoro del bundle
+        let progress = self.progress_marker();
+        let context = self.run_context(config, "remediate");
+        let _resources = self.lock_resources(config)?;
+        let workspace = self.workspace(config, &context)?;
+        let local_path = workspace.path();
 
-        match self.script_type {
+        let result = match self.script_type {
             ScriptType::Bash => {
-                executor::run_bash_script(local_path, &["remediate"])
-                    .context(format!("Failed to run bash remediate script for task {}", self.name))?;
+                executor::run_bash_script(local_path, &["remediate"], &self.variables, &context, progress.as_ref(), &self.resources)
+                    .context(format!("Failed to run bash remediate script for task {}", self.name))?
             },
             ScriptType::Ansible => {
-                executor::run_ansible_playbook(local_path, "remediate")
-                    .context(format!("Failed to run ansible remediate playbook for task {}", self.name))?;
+                executor::run_ansible_playbook(local_path, "remediate", &self.variables, &context, progress.as_ref(), &self.resources)
+                    .context(format!("Failed to run ansible remediate playbook for task {}", self.name))?
             },
             ScriptType::Mixed => {
                 // Per i task mixed, prova prima ansible e poi bash se necessario
-                if let Err(e) = executor::run_ansible_playbook(local_path, "remediate") {
-                    warn!("Ansible playbook failed for mixed task {}, trying bash: {}", self.name, e);
-                    executor::run_bash_script(local_path, &["remediate"])
-                        .context(format!("Both ansible and bash failed for mixed task {}", self.name))?;
+                match executor::run_ansible_playbook(local_path, "remediate", &self.variables, &context, progress.as_ref(), &self.resources) {
+                    Ok(result) => result,
+                    Err(e) => {
+                        warn!("Ansible playbook failed for mixed task {}, trying bash: {}", self.name, e);
+                        executor::run_bash_script(local_path, &["remediate"], &self.variables, &context, progress.as_ref(), &self.resources)
+                            .context(format!("Both ansible and bash failed for mixed task {}", self.name))?
+                    }
                 }
-            }
-        }
+            },
+            ScriptType::Wasm => {
+                self.run_wasm_action(config, local_path, "remediate", &context, progress.as_ref())?
+            },
+        };
+        self.record_result(config, "remediate", result)?;
+        self.record_success(config, "remediate")?;
 
         info!("Task {} remediated successfully", self.name);
 
         Ok(())
     }
 
+    /// Restituisce l'URL del bundle, della versione scelta dallo stack se presente
+    pub fn download_url(&self) -> &str {
+        self.pinned.as_ref().map_or(self.url.as_str(), |pinned| pinned.url.as_str())
+    }
+
+    /// Restituisce il checksum SHA-256 atteso del bundle da scaricare, se dichiarato
+    pub fn expected_sha256(&self) -> Option<&str> {
+        match &self.pinned {
+            Some(pinned) => pinned.sha256.as_deref(),
+            None => self.sha256.as_deref(),
+        }
+    }
+
     /// Scarica il task e lo estrae nella directory appropriata
     pub fn download(&mut self, config: &Config) -> Result<PathBuf> {
-        // Se il task è già stato scaricato, restituisci il percorso
+        // Crea il percorso di destinazione, distinto per le versioni scelte da uno stack
+        let task_dir = self.bundle_dir(config);
+
+        // Se il task è già stato scaricato nella stessa versione, restituisci il percorso
         if let Some(path) = &self.local_path {
-            if path.exists() {
+            if path.exists() && path.starts_with(&task_dir) {
                 return Ok(path.clone());
             }
         }
 
-        info!("Downloading task: {} from {}", self.name, self.url);
+        let url = self.download_url().to_string();
+        match self.effective_version() {
+            Some(version) => info!("Downloading task: {} {} from {}", self.name, version, url),
+            None => info!("Downloading task: {} from {}", self.name, url),
+        }
 
-        // Crea il percorso di destinazione
-        let task_dir = config.resolve_path(&self.name, "tasks");
+        // Il checksum dichiarato viene verificato prima dell'estrazione
+        let mut source = release::with_token(config, &Source::new(&url))?;
+        source.sha256 = self.expected_sha256().map(str::to_string);
 
         // Scarica e/o estrai il task
-        let downloaded_path = downloader::download_and_extract(
-            &self.url,
+        let downloaded_path = downloader::download_and_extract_source(
+            &source,
             &task_dir,
             config.download_timeout,
+            config.download_cache_dir().as_deref(),
+            &config.download_hosts,
         ).context(format!("Failed to download task: {}", self.name))?;
 
         self.local_path = Some(downloaded_path.clone());
+        self.cached_bundle = config.download_cache_dir()
+            .and_then(|cache_dir| downloader::cached_file(&url, &cache_dir));
 
         info!("Task {} downloaded successfully to {:?}", self.name, downloaded_path);
 
@@ -408,11 +1693,143 @@ impl Display for Task {
     }
 }
 
+/// Ordina i task in modo che ogni task segua le proprie dipendenze
+///
+/// Le dipendenze non presenti nell'elenco vengono ignorate; in caso di dipendenze
+/// circolari il ciclo viene interrotto mantenendo l'ordine originale.
+///
+/// # Returns
+///
+/// Gli indici dei task in ordine di installazione
+pub fn sort_by_dependencies(tasks: &[Task]) -> Vec<usize> {
+    fn visit(idx: usize, tasks: &[Task], visited: &mut Vec<u8>, order: &mut Vec<usize>) {
+        // 0 = non visitato, 1 = in visita, 2 = completato
+        match visited[idx] {
+            2 => return,
+            1 => {
+                warn!("Circular dependency detected involving task {}", tasks[idx].name);
+                return;
+            },
+            _ => {}
+        }
+
+        visited[idx] = 1;
+        for dep in &tasks[idx].dependencies {
+            if let Some(dep_idx) = tasks.iter().position(|t| t.matches_ref(dep)) {
+                visit(dep_idx, tasks, visited, order);
+            }
+        }
+        visited[idx] = 2;
+        order.push(idx);
+    }
+
+    let mut visited = vec![0u8; tasks.len()];
+    let mut order = Vec::with_capacity(tasks.len());
+
+    for idx in 0..tasks.len() {
+        visit(idx, tasks, &mut visited, &mut order);
+    }
+
+    order
+}
+
+/// Confronta due numeri di versione componente per componente
+///
+/// Le componenti numeriche sono confrontate come numeri, le altre come testo
+/// (es. "1.10.0" è successiva a "1.9.2" e "1.0.0-rc1" precede "1.0.0").
+pub fn compare_versions(a: &str, b: &str) -> std::cmp::Ordering {
+    let split = |v: &str| -> Vec<String> {
+        v.split(['.', '-', '+'])
+            .map(|part| part.to_string())
+            .collect()
+    };
+
+    let (a_parts, b_parts) = (split(a), split(b));
+    for (a_part, b_part) in a_parts.iter().zip(b_parts.iter()) {
+        let ordering = match (a_part.parse::<u64>(), b_part.parse::<u64>()) {
+            (Ok(a_num), Ok(b_num)) => a_num.cmp(&b_num),
+            _ => a_part.cmp(b_part),
+        };
+        if ordering != std::cmp::Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    // A parità di prefisso una componente testuale in più indica una pre-release
+    // ("1.0.0-rc1" precede "1.0.0"), una numerica una versione successiva
+    match (a_parts.get(b_parts.len()), b_parts.get(a_parts.len())) {
+        (Some(extra), None) if extra.parse::<u64>().is_err() => std::cmp::Ordering::Less,
+        (None, Some(extra)) if extra.parse::<u64>().is_err() => std::cmp::Ordering::Greater,
+        _ => a_parts.len().cmp(&b_parts.len()),
+    }
+}
+
+/// Migra un task deprecato al task che lo sostituisce
+///
+/// Installa il sostituto indicato in `replaced_by` (se non è già installato) e
+/// disinstalla il task deprecato.
+///
+/// # Arguments
+///
+/// * `tasks` - Tutti i task caricati
+/// * `idx` - L'indice del task deprecato
+/// * `config` - La configurazione
+pub fn migrate_deprecated(tasks: &mut [Task], idx: usize, config: &Config) -> Result<()> {
+    let task = tasks.get(idx)
+        .ok_or_else(|| anyhow!("Task index out of range: {}", idx))?;
+    let name = task.name.clone();
+    let replacement = task.replaced_by.clone()
+        .ok_or_else(|| anyhow!("Task {} has no replacement", name))?;
+
+    events::track(Operation::Migrate, &name, || {
+        let replacement_idx = tasks.iter()
+            .position(|t| t.matches_ref(&replacement))
+            .filter(|replacement_idx| *replacement_idx != idx)
+            .ok_or_else(|| anyhow!("Replacement task {} for {} not found", replacement, name))?;
+
+        info!("Migrating task {} to {}", name, replacement);
+
+        if !tasks[replacement_idx].installed {
+            tasks[replacement_idx].install(config)
+                .context(format!("Failed to install replacement task {}", replacement))?;
+        }
+
+        if tasks[idx].installed {
+            tasks[idx].uninstall(config)
+                .context(format!("Failed to uninstall deprecated task {}", name))?;
+        }
+
+        info!("Task {} migrated to {}", name, replacement);
+        Ok(())
+    })
+}
+
+/// Restituisce gli indici dei task installati per cui è disponibile una versione più recente
+///
+/// Sono esclusi i task di cui uno stack installato fissa la versione: l'aggiornamento
+/// annullerebbe la scelta dello stack.
+///
+/// # Arguments
+///
+/// * `tasks` - I task del catalogo
+/// * `stacks` - Gli stack del catalogo
+pub fn available_updates(tasks: &[Task], stacks: &[stack::Stack]) -> Vec<usize> {
+    let pinned_by_stack = |task: &Task| stacks.iter()
+        .filter(|stack| stack.fully_installed || stack.partially_installed)
+        .any(|stack| stack.task_names.iter().any(|reference| {
+            task.matches_ref(reference) && (stack.pins.contains_key(reference) || stack.pins.contains_key(&task.name))
+        }));
+
+    tasks.iter().enumerate()
+        .filter(|(_, task)| task.available_update().is_some() && !pinned_by_stack(task))
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
 /// Carica i task da tutti i file di configurazione disponibili
 pub fn load_tasks(config: &Config) -> Result<Vec<Task>> {
     info!("Loading tasks from configuration files");
 
-    let mut tasks = Vec::new();
     let tasks_dir = Path::new(&config.tasks_dir);
 
     // Verifica che la directory esista
@@ -442,9 +1859,39 @@ pub fn load_tasks(config: &Config) -> Result<Vec<Task>> {
         create_example_task_config(tasks_dir)?;
     }
 
+    read_tasks(config)
+}
+
+/// Legge i task dai file di configurazione già presenti, senza scaricare le sorgenti
+///
+/// Oltre alla directory dei task vengono lette le directory proprie delle sorgenti.
+pub fn read_tasks(config: &Config) -> Result<Vec<Task>> {
+    let mut tasks = Vec::new();
+
+    for (dir, dir_source) in source::catalog_dirs(&config.task_sources, Path::new(&config.tasks_dir)) {
+        if dir_source.is_some() && !dir.exists() {
+            warn!("Task catalog directory does not exist: {:?}", dir);
+            continue;
+        }
+        read_task_dir(config, &dir, dir_source, &mut tasks)?;
+    }
+
+    info!("Loaded {} tasks", tasks.len());
+    Ok(tasks)
+}
+
+/// Legge i file .conf di una directory del catalogo dei task
+///
+/// # Arguments
+///
+/// * `config` - La configurazione corrente
+/// * `dir` - La directory da leggere
+/// * `dir_source` - La sorgente a cui appartiene la directory, se non è quella principale
+/// * `tasks` - I task già letti, a cui aggiungere quelli della directory
+fn read_task_dir(config: &Config, dir: &Path, dir_source: Option<&Source>, tasks: &mut Vec<Task>) -> Result<()> {
     // Leggi tutti i file di configurazione (con estensione .conf)
-    for entry in fs::read_dir(tasks_dir)
-        .context(format!("Failed to read tasks directory: {}", config.tasks_dir))? {
+    for entry in fs::read_dir(dir)
+        .context(format!("Failed to read tasks directory: {:?}", dir))? {
 
         let entry = entry.context("Failed to read directory entry")?;
         let path = entry.path();
@@ -452,14 +1899,26 @@ pub fn load_tasks(config: &Config) -> Result<Vec<Task>> {
         // Processa solo i file con estensione .conf
         if path.is_file() && path.extension().map_or(false, |ext| ext == "conf") {
             info!("Processing task configuration file: {:?}", path);
+            let file_source = dir_source.or_else(|| source::source_for_file(&config.task_sources, &path));
+            let catalog = match dir_source {
+                Some(dir_source) => dir_source.label(),
+                None => source::catalog_label(&config.task_sources, &path),
+            };
+            let namespace = file_source.and_then(|s| s.namespace.clone());
 
             // Leggi il contenuto del file
             let content = fs::read_to_string(&path)
-                .context(format!("Failed to read task config file: {:?}", path))?;
+                .config_context(format!("Failed to read task config file: {:?}", path))?;
 
-            // Parse del YAML
+            // Parse del YAML, decifrando i file SOPS
             let yaml_value: serde_yaml::Value = serde_yaml::from_str(&content)
-                .context(format!("Failed to parse YAML from: {:?}", path))?;
+                .config_context(format!("Failed to parse YAML from: {:?}", path))?;
+            let yaml_value = secrets::open_document(&path, yaml_value, config.age_identity.as_deref())?;
+
+            // Le opzioni sconosciute o del tipo sbagliato verrebbero ignorate: vanno segnalate
+            for issue in catalog_schema::validate(CatalogKind::Tasks, &yaml_value) {
+                warn!("Task catalog {:?} does not match the schema: {}", path, issue);
+            }
 
             // Estrai i task dal documento YAML
             if let Some(tasks_value) = yaml_value.get("tasks") {
@@ -477,6 +1936,14 @@ pub fn load_tasks(config: &Config) -> Result<Vec<Task>> {
                             // Crea il task
                             match Task::from_hashmap(&hashmap) {
                                 Ok(mut task) => {
+                                    task.source = catalog.clone();
+                                    task.namespace = namespace.clone();
+
+                                    if let Some(other) = tasks.iter().find(|t: &&Task| t.name == task.name) {
+                                        warn!("Task {} is defined in catalogs {} and {}; use source:name to disambiguate",
+                                              task.name, other.source, task.source);
+                                    }
+
                                     // Verifica lo stato di installazione
                                     task.check_installed(config)?;
                                     info!("Successfully loaded task: {:?}", task.clone());
@@ -494,8 +1961,7 @@ pub fn load_tasks(config: &Config) -> Result<Vec<Task>> {
         }
     }
 
-    info!("Loaded {} tasks", tasks.len());
-    Ok(tasks)
+    Ok(())
 }
 
 pub fn download_tasks_from_sources(config: &Config) -> Result<()> {
@@ -503,12 +1969,21 @@ pub fn download_tasks_from_sources(config: &Config) -> Result<()> {
 
     for source in &config.task_sources {
         info!("Processing task source: {}", source);
+        source.validate_layout()?;
 
-        // Scarica direttamente nella directory dei task
-        match downloader::download_and_extract(
-            source,
-            &Path::new(&config.tasks_dir),
+        // Un catalogo in sola lettura è già presente nella sua directory
+        if source.read_only {
+            info!("Task source {} is read-only, using {:?}", source, source.catalog_dir(Path::new(&config.tasks_dir)));
+            continue;
+        }
+
+        // Scarica nella directory della sorgente, o direttamente in quella dei task
+        match downloader::download_and_extract_source(
+            &release::with_token(config, source)?,
+            &source.catalog_dir(Path::new(&config.tasks_dir)),
             config.download_timeout,
+            config.download_cache_dir().as_deref(),
+            &config.download_hosts,
         ) {
             Ok(path) => {
                 info!("Successfully downloaded task to: {:?}", path);
@@ -575,3 +2050,29 @@ tasks:
     info!("Created example task configuration file: {:?}", example_file_path);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cmp::Ordering;
+
+    #[test]
+    fn numeric_components_compare_as_numbers() {
+        assert_eq!(compare_versions("1.10.0", "1.9.2"), Ordering::Greater);
+        assert_eq!(compare_versions("2.0", "10.0"), Ordering::Less);
+        assert_eq!(compare_versions("1.2.3", "1.2.3"), Ordering::Equal);
+    }
+
+    #[test]
+    fn text_components_compare_lexically() {
+        assert_eq!(compare_versions("1.0.0-beta", "1.0.0-alpha"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0.0-rc10", "1.0.0-rc9"), Ordering::Less);
+    }
+
+    #[test]
+    fn prereleases_precede_releases() {
+        assert_eq!(compare_versions("1.0.0-rc1", "1.0.0"), Ordering::Less);
+        assert_eq!(compare_versions("1.0.0", "1.0.0-rc1"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0.0.1", "1.0.0"), Ordering::Greater);
+    }
+}
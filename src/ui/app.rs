@@ -17,12 +17,29 @@ use cursive::event::{Event, Key};
 
 use crate::config::{Config, get_binary_config_path};
 use crate::task::{Task, load_tasks, ScriptType};
-use crate::stack::{Stack, load_stacks};
+use crate::stack::{self, Stack, load_stacks};
 use crate::ui::theme;
 use crate::ui::task_view;
 use crate::ui::stack_view;
+use crate::ui::matrix_view;
+use crate::ui::inventory_view;
 use crate::ui::log_view;
+use crate::ui::jobs_view;
+use crate::ui::palette;
+use crate::ui::components::reboot_banner;
+use crate::ui::components::reload;
+use crate::ui::components::stall_alert;
+use crate::ui::components::status_bar;
+use crate::ui::components::sudo_prompt;
+use crate::ui::components::terminal_window;
+use crate::ui::components::toast;
+use crate::ui::components::updates;
+use crate::events;
+use crate::jobs;
 use crate::logger;
+use crate::preflight;
+use crate::session;
+use crate::mac;
 
 // Dimensioni standard per le finestre
 pub const WINDOW_WIDTH: usize = 80;
@@ -35,7 +52,7 @@ pub const LOG_HEIGHT: usize = 10;
 pub struct App;
 
 /// Avvia l'applicazione TUI
-pub fn run_app(config: Config) -> Result<()> {
+pub fn run_app(mut config: Config) -> Result<()> {
     // Crea l'oggetto Cursive per la TUI
     let mut siv = cursive::default();
 
@@ -46,6 +63,15 @@ pub fn run_app(config: Config) -> Result<()> {
     // Carica i task e gli stack
     let tasks = load_tasks(&config)?;
     let stacks = load_stacks(&config, &tasks)?;
+    let invalid_references = stack::missing_task_references(&stacks, &tasks);
+    let last_session = session::load(&config);
+
+    // Con directory non scrivibili l'interfaccia resta utilizzabile per consultare lo stato
+    let preflight = if config.read_only { Ok(()) } else { preflight::check(&config) };
+    if preflight.is_err() {
+        log::warn!("Starting the UI in read-only mode: Galatea directories are not writable");
+        config.read_only = true;
+    }
 
     // Condividi i dati tra i thread
     let config = Arc::new(Mutex::new(config));
@@ -57,12 +83,110 @@ pub fn run_app(config: Config) -> Result<()> {
         log_view::create_log_view(s);
     });
 
+    // Palette dei comandi: raggiunge task, stack e schermate da qualsiasi vista
+    let palette_config = Arc::clone(&config);
+    let palette_tasks = Arc::clone(&tasks);
+    let palette_stacks = Arc::clone(&stacks);
+    siv.add_global_callback(Event::CtrlChar('p'), move |s| {
+        palette::show_palette(s, Arc::clone(&palette_config), Arc::clone(&palette_tasks), Arc::clone(&palette_stacks));
+    });
+
+    // Ricaricamento dei cataloghi senza riavviare l'interfaccia
+    let reload_config = Arc::clone(&config);
+    let reload_tasks = Arc::clone(&tasks);
+    let reload_stacks = Arc::clone(&stacks);
+    siv.add_global_callback(Event::Key(Key::F5), move |s| {
+        reload::reload_catalogs(s, Arc::clone(&reload_config), Arc::clone(&reload_tasks), Arc::clone(&reload_stacks));
+    });
+
+    // Registro dei job: raccoglie stato e output delle operazioni per la schermata dei job
+    jobs::init();
+    siv.add_global_callback(Event::Key(Key::F2), jobs_view::create_jobs_view);
+
+    // La password di sudo dei task che la richiedono viene chiesta con una finestra
+    sudo_prompt::register(&mut siv);
+
+    // Gli script dei task interattivi usano un terminale mostrato in una finestra
+    terminal_window::register(&mut siv);
+
+    // Gli script rimasti a lungo senza output vengono segnalati, per terminarli o attenderli
+    stall_alert::register(&mut siv);
+
+    // Banner dei riavvii in sospeso e barra di stato, aggiornati all'inizio e al termine
+    // di ogni operazione
+    reboot_banner::refresh(&config, &tasks);
+    status_bar::refresh(&config, &tasks);
+    let banner_sink = siv.cb_sink().clone();
+    let banner_config = Arc::clone(&config);
+    let banner_tasks = Arc::clone(&tasks);
+    let banner_stacks = Arc::clone(&stacks);
+    events::subscribe(move |event| {
+        if let events::Event::OperationStarted { .. } | events::Event::Finished { .. } = event {
+            let config = Arc::clone(&banner_config);
+            let tasks = Arc::clone(&banner_tasks);
+            let stacks = Arc::clone(&banner_stacks);
+            let _ = banner_sink.send(Box::new(move |s| {
+                reboot_banner::refresh(&config, &tasks);
+                status_bar::refresh(&config, &tasks);
+                updates::refresh(s, &tasks, &stacks);
+            }));
+        } else {
+            // Gli eventi dei job in background arrivano da altri thread: una callback
+            // vuota basta a far ridisegnare lo schermo
+            let _ = banner_sink.send(Box::new(|_| {}));
+        }
+    });
+
     // Crea la schermata principale
     create_main_screen(&mut siv, Arc::clone(&config), Arc::clone(&tasks), Arc::clone(&stacks))?;
+    updates::refresh(&mut siv, &tasks, &stacks);
+
+    // Riapre la schermata lasciata aperta all'ultima uscita
+    if let Some(screen) = last_session.screen.as_deref() {
+        let result = match screen {
+            "tasks" => task_view::create_task_view(&mut siv, Arc::clone(&config), Arc::clone(&tasks), Arc::clone(&stacks)),
+            "stacks" => stack_view::create_stack_view(&mut siv, Arc::clone(&config), Arc::clone(&stacks), Arc::clone(&tasks)),
+            "matrix" => matrix_view::create_matrix_view(&mut siv, Arc::clone(&config), Arc::clone(&stacks), Arc::clone(&tasks)),
+            "inventory" => {
+                inventory_view::create_inventory_view(&mut siv);
+                Ok(())
+            },
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            log::warn!("Failed to restore screen {} of the last session: {}", screen, e);
+        }
+    }
+
+    // Gli stack con task inesistenti vengono segnalati all'avvio, non solo nei dettagli
+    if !invalid_references.is_empty() {
+        let lines: Vec<String> = invalid_references.iter()
+            .map(|(stack, missing)| format!("{}: {}", stack, missing.join(", ")))
+            .collect();
+        siv.add_layer(Dialog::info(format!("Stack che fanno riferimento a task non presenti nel catalogo:\n\n{}",
+                                           lines.join("\n")))
+                      .title("Avvisi sui cataloghi")
+                      .max_height(20)
+                      .fixed_width(70));
+    }
+
+    if let Err(e) = preflight {
+        siv.add_layer(Dialog::info(format!("{}\n\nL'interfaccia è stata avviata in modalità sola lettura.", e))
+                      .title("Directory non scrivibili")
+                      .max_height(20)
+                      .fixed_width(70));
+    }
 
     // Esegui il loop principale
     siv.run();
 
+    // Lo stato della sessione viene salvato anche uscendo da una schermata aperta (Ctrl+C)
+    if let Ok(config_guard) = config.lock() {
+        if let Err(e) = session::save(&config_guard) {
+            log::warn!("Failed to save UI session state: {:#}", e);
+        }
+    }
+
     Ok(())
 }
 
@@ -74,7 +198,12 @@ fn create_main_screen(siv: &mut Cursive, config: Arc<Mutex<Config>>, tasks: Arc<
         .with_name("title");
 
     // Mostra una descrizione
-    let description = TextView::new("Strumento di installazione e configurazione server e workstation")
+    let read_only = config.lock().map(|c| c.read_only).unwrap_or(false);
+    let description = TextView::new(if read_only {
+            "Strumento di installazione e configurazione server e workstation\n[modalità sola lettura]"
+        } else {
+            "Strumento di installazione e configurazione server e workstation"
+        })
         .h_align(HAlign::Center)
         .with_name("description");
 
@@ -83,6 +212,18 @@ fn create_main_screen(siv: &mut Cursive, config: Arc<Mutex<Config>>, tasks: Arc<
     let stats_view = TextView::new(stats)
         .with_name("stats");
 
+    // La schermata principale resta aperta: le statistiche seguono ogni ricaricamento dei cataloghi
+    reload::register({
+        let tasks = Arc::clone(&tasks);
+        let stacks = Arc::clone(&stacks);
+        move |s| {
+            if let Ok(stats) = get_statistics(&tasks, &stacks) {
+                s.call_on_name("stats", |view: &mut TextView| view.set_content(stats));
+            }
+            updates::refresh(s, &tasks, &stacks);
+        }
+    });
+
     // Crea il menu principale
     let mut main_menu = SelectView::new()
         .h_align(HAlign::Center)
@@ -91,8 +232,14 @@ fn create_main_screen(siv: &mut Cursive, config: Arc<Mutex<Config>>, tasks: Arc<
     // Aggiungi le voci di menu
     main_menu.add_item("Gestione Task", "tasks");
     main_menu.add_item("Gestione Stack", "stacks");
+    main_menu.add_item("Matrice Stack", "matrix");
+    main_menu.add_item("Inventario di sistema", "inventory");
     main_menu.add_item("Visualizza Log", "logs");
+    main_menu.add_item("Job", "jobs");
+    main_menu.add_item("Ricarica cataloghi", "reload");
+    main_menu.add_item(updates::menu_label(0), updates::MENU_ITEM);
     main_menu.add_item("Impostazioni", "settings");
+    main_menu.add_item("Riavvia ora", "reboot");
     main_menu.add_item("Informazioni", "about");
     main_menu.add_item("Esci", "quit");
 
@@ -104,7 +251,7 @@ fn create_main_screen(siv: &mut Cursive, config: Arc<Mutex<Config>>, tasks: Arc<
     main_menu.set_on_submit(move |s, item: &str| {
         match item {
             "tasks" => {
-                let result = task_view::create_task_view(s, Arc::clone(&config_clone), Arc::clone(&tasks_clone));
+                let result = task_view::create_task_view(s, Arc::clone(&config_clone), Arc::clone(&tasks_clone), Arc::clone(&stacks_clone));
                 if let Err(e) = result {
                     s.add_layer(Dialog::info(format!("Errore durante il caricamento della vista dei task: {}", e))
                                  .fixed_width(50)
@@ -119,12 +266,35 @@ fn create_main_screen(siv: &mut Cursive, config: Arc<Mutex<Config>>, tasks: Arc<
                                  .fixed_height(10));
                 }
             },
+            "matrix" => {
+                let result = matrix_view::create_matrix_view(s, Arc::clone(&config_clone), Arc::clone(&stacks_clone), Arc::clone(&tasks_clone));
+                if let Err(e) = result {
+                    s.add_layer(Dialog::info(format!("Errore durante il caricamento della matrice degli stack: {}", e))
+                                 .fixed_width(50)
+                                 .fixed_height(10));
+                }
+            },
+            "inventory" => {
+                inventory_view::create_inventory_view(s);
+            },
             "logs" => {
                 log_view::create_log_view(s);
             },
+            "jobs" => {
+                jobs_view::create_jobs_view(s);
+            },
+            "reload" => {
+                reload::reload_catalogs(s, Arc::clone(&config_clone), Arc::clone(&tasks_clone), Arc::clone(&stacks_clone));
+            },
+            updates::MENU_ITEM => {
+                updates::show_updates(s, Arc::clone(&config_clone), Arc::clone(&tasks_clone), Arc::clone(&stacks_clone));
+            },
             "settings" => {
                 create_settings_screen(s, Arc::clone(&config_clone));
             },
+            "reboot" => {
+                reboot_banner::confirm_reboot(s, &config_clone, &tasks_clone);
+            },
             "about" => {
                 s.add_layer(Dialog::info(
                     "Galatea v0.1.0\n\n\
@@ -149,11 +319,12 @@ fn create_main_screen(siv: &mut Cursive, config: Arc<Mutex<Config>>, tasks: Arc<
     });
 
     // Aiuto per i tasti funzione
-    let help_text = TextView::new("F1: Visualizza Log | F10: Menu")
+    let help_text = TextView::new("F1: Log | F2: Job | F5: Ricarica cataloghi | Ctrl+P: Vai a... | F10: Menu")
         .h_align(HAlign::Center);
 
     // Layout principale
     let layout = LinearLayout::vertical()
+        .child(reboot_banner::banner_view())
         .child(title)
         .child(DummyView.fixed_height(1))
         .child(description)
@@ -162,12 +333,14 @@ fn create_main_screen(siv: &mut Cursive, config: Arc<Mutex<Config>>, tasks: Arc<
             .title("Statistiche")
             .fixed_width(PANEL_WIDTH))
         .child(DummyView.fixed_height(1))
-        .child(Panel::new(main_menu.scrollable())
+        .child(Panel::new(main_menu.with_name(updates::MAIN_MENU).scrollable())
             .title("Menu principale")
             .fixed_width(PANEL_WIDTH)
             .fixed_height(10))
         .child(DummyView.fixed_height(1))
-        .child(help_text);
+        .child(help_text)
+        .child(toast::toast_view())
+        .child(status_bar::status_bar_view());
 
     // Aggiungi la vista alla UI
     siv.add_layer(Dialog::around(layout)
@@ -187,7 +360,7 @@ fn create_main_screen(siv: &mut Cursive, config: Arc<Mutex<Config>>, tasks: Arc<
 }
 
 /// Crea la schermata delle impostazioni
-fn create_settings_screen(siv: &mut Cursive, config: Arc<Mutex<Config>>) {
+pub fn create_settings_screen(siv: &mut Cursive, config: Arc<Mutex<Config>>) {
     // Ottieni la configurazione attuale
     let config_guard = config.lock().unwrap();
 
@@ -198,14 +371,33 @@ fn create_settings_screen(siv: &mut Cursive, config: Arc<Mutex<Config>>) {
     content.push_str(&format!("Directory stack: {}\n", config_guard.stacks_dir));
     content.push_str(&format!("Directory stato: {}\n", config_guard.state_dir));
     content.push_str(&format!("Timeout download: {} sec\n", config_guard.download_timeout));
+    content.push_str(&format!("Cache download: {}\n", if config_guard.download_cache { "attiva" } else { "disattivata" }));
     content.push_str(&format!("Tema UI: {}\n", config_guard.ui_theme));
+    content.push_str(&format!("Visualizzazione liste: {}\n", config_guard.list_mode.to_str()));
+    content.push_str(&format!("Backend snapshot: {}\n", config_guard.snapshot_backend));
+    content.push_str(&format!("Conferma azioni: {}\n", config_guard.confirm_actions.to_str()));
+    if !config_guard.env_overrides.is_empty() {
+        let keys: Vec<&str> = config_guard.env_overrides.keys().map(|k| k.as_str()).collect();
+        content.push_str(&format!("Sovrascritte dall'ambiente (GALATEA_*): {}\n", keys.join(", ")));
+    }
+    if let Some(role) = &config_guard.role {
+        content.push_str(&format!("Ruolo: {}\n", role));
+    }
     content.push_str("\nSorgenti Task:\n");
 
     if config_guard.task_sources.is_empty() {
         content.push_str("  Nessuna sorgente di task configurata\n");
     } else {
-        for (i, url) in config_guard.task_sources.iter().enumerate() {
-            content.push_str(&format!("  {}. {}\n", i + 1, url));
+        for (i, source) in config_guard.task_sources.iter().enumerate() {
+            if source.is_verified() {
+                content.push_str(&format!("  {}. {} (verifica: {})\n", i + 1, source, source.verification_summary()));
+            } else {
+                content.push_str(&format!("  {}. {}\n", i + 1, source));
+            }
+            let layout = source.layout_summary();
+            if !layout.is_empty() {
+                content.push_str(&format!("     {}\n", layout));
+            }
         }
     }
 
@@ -213,11 +405,52 @@ fn create_settings_screen(siv: &mut Cursive, config: Arc<Mutex<Config>>) {
     if config_guard.stack_sources.is_empty() {
         content.push_str("  Nessuna sorgente di stack configurata\n");
     } else {
-        for (i, url) in config_guard.stack_sources.iter().enumerate() {
-            content.push_str(&format!("  {}. {}\n", i + 1, url));
+        for (i, source) in config_guard.stack_sources.iter().enumerate() {
+            if source.is_verified() {
+                content.push_str(&format!("  {}. {} (verifica: {})\n", i + 1, source, source.verification_summary()));
+            } else {
+                content.push_str(&format!("  {}. {}\n", i + 1, source));
+            }
+            let layout = source.layout_summary();
+            if !layout.is_empty() {
+                content.push_str(&format!("     {}\n", layout));
+            }
         }
     }
 
+    // Modulo di sicurezza attivo e directory di cui ripristinare le etichette
+    match mac::detect() {
+        Some(status) => content.push_str(&format!("\nSicurezza: {}\n", status)),
+        None => content.push_str("\nSicurezza: nessun modulo SELinux/AppArmor attivo\n"),
+    }
+    if !config_guard.restorecon_dirs.is_empty() {
+        content.push_str(&format!("Directory con restorecon: {}\n", config_guard.restorecon_dirs.join(", ")));
+    }
+
+    // Variabili dell'ambiente di Galatea passate agli script oltre a quelle predefinite
+    let child_env = &config_guard.child_env;
+    content.push_str(&format!("Ambiente degli script: predefinito{}{}\n",
+        if child_env.allow.is_empty() { String::new() } else { format!(", consentite {}", child_env.allow.join(", ")) },
+        if child_env.deny.is_empty() { String::new() } else { format!(", escluse {}", child_env.deny.join(", ")) }));
+    content.push_str(&format!("Riepilogo delle modifiche al sistema: {}\n",
+        if config_guard.change_summary { "attivo" } else { "disattivato" }));
+    content.push_str(&format!("Hook dopo le operazioni: {} (durata massima {} secondi)\n",
+        config_guard.hooks_dir().display(), config_guard.hook_timeout));
+    content.push_str(&format!("Manutenzione con il bilanciatore: {}\n",
+        match &config_guard.maintenance {
+            Some(maintenance) if maintenance.settle > 0 => format!("configurata, attesa di {} secondi dopo drain", maintenance.settle),
+            Some(_) => "configurata".to_string(),
+            None => "non configurata".to_string(),
+        }));
+    content.push_str(&format!("Cifratura dello stato: {}\n",
+        if config_guard.encrypt_state { format!("attiva, chiave {}", config_guard.state_key_path().display()) } else { "disattivata".to_string() }));
+    content.push_str(&format!("Trasformazione dei fatti: {}\n",
+        config_guard.fact_transform.as_deref().unwrap_or("nessuna")));
+    let watchdog = &config_guard.watchdog;
+    content.push_str(&format!("Sorveglianza degli script: {}, battito nel log {}\n",
+        if watchdog.stall_after > 0 { format!("segnalati dopo {} secondi senza output", watchdog.stall_after) } else { "disattivata".to_string() },
+        if watchdog.heartbeat > 0 { format!("ogni {} secondi", watchdog.heartbeat) } else { "disattivato".to_string() }));
+
     // Lista dei temi disponibili
     content.push_str("\nTemi disponibili:\n");
     for theme_name in theme::get_available_themes() {
@@ -231,11 +464,19 @@ fn create_settings_screen(siv: &mut Cursive, config: Arc<Mutex<Config>>) {
         content.push_str("\nFile di configurazione: usando valori predefiniti\n");
     }
 
+    if config_guard.read_only {
+        content.push_str("\nModalità sola lettura: la configurazione non può essere modificata\n");
+    }
+    let read_only = config_guard.read_only;
+
     // Rilascia il lock prima di procedere
     drop(config_guard);
 
     // Aggiungi la vista alla UI
-    siv.add_layer(Dialog::around(TextView::new(content).scrollable())
+    let mut dialog = Dialog::around(LinearLayout::vertical()
+            .child(TextView::new(content).scrollable())
+            .child(toast::toast_view())
+            .child(status_bar::status_bar_view()))
         .title("Impostazioni")
         .button("Cambia tema", {
             let config = Arc::clone(&config);
@@ -256,8 +497,8 @@ fn create_settings_screen(siv: &mut Cursive, config: Arc<Mutex<Config>>) {
                         let mut config_guard = config_clone.lock().unwrap();
                         config_guard.ui_theme = theme_name.to_string();
 
-                        // Salva la configurazione aggiornata
-                        if let Some(config_path) = &config_guard.config_file_path {
+                        // Salva la configurazione aggiornata (in sola lettura il tema vale solo per la sessione)
+                        if let Some(config_path) = config_guard.config_file_path.as_ref().filter(|_| !config_guard.read_only) {
                             match config_guard.save(config_path) {
                                 Ok(_) => {},
                                 Err(e) => {
@@ -274,11 +515,9 @@ fn create_settings_screen(siv: &mut Cursive, config: Arc<Mutex<Config>>) {
                     let new_theme = theme::get_theme(theme_name);
                     s.set_theme(new_theme);
 
-                    // Notifica l'utente
-                    s.add_layer(Dialog::info(format!("Tema cambiato a: {}", theme_name))
-                                 .fixed_width(50)
-                                 .fixed_height(10));
+                    // Chiudi la selezione e notifica l'utente
                     s.pop_layer();
+                    toast::show(s, format!("Tema cambiato a: {}", theme_name));
                 });
 
                 // Mostra la vista di selezione del tema
@@ -322,9 +561,7 @@ fn create_settings_screen(siv: &mut Cursive, config: Arc<Mutex<Config>>) {
                                         match config_guard.save(config_path) {
                                             Ok(_) => {
                                                 s.pop_layer();
-                                                s.add_layer(Dialog::info(format!("Sorgente Task aggiunta: {}", url))
-                                                             .fixed_width(50)
-                                                             .fixed_height(10));
+                                                toast::show(s, format!("Sorgente Task aggiunta: {}", url));
                                             },
                                             Err(e) => {
                                                 s.add_layer(Dialog::info(format!("Errore nel salvataggio della configurazione: {}", e))
@@ -334,9 +571,7 @@ fn create_settings_screen(siv: &mut Cursive, config: Arc<Mutex<Config>>) {
                                         }
                                     } else {
                                         s.pop_layer();
-                                        s.add_layer(Dialog::info(format!("Sorgente Task aggiunta: {}", url))
-                                                     .fixed_width(50)
-                                                     .fixed_height(10));
+                                        toast::show(s, format!("Sorgente Task aggiunta: {}", url));
                                     }
                                 } else {
                                     s.add_layer(Dialog::info(format!("La sorgente {} esiste già", url))
@@ -383,9 +618,7 @@ fn create_settings_screen(siv: &mut Cursive, config: Arc<Mutex<Config>>) {
                                         match config_guard.save(config_path) {
                                             Ok(_) => {
                                                 s.pop_layer();
-                                                s.add_layer(Dialog::info(format!("Sorgente Stack aggiunta: {}", url))
-                                                             .fixed_width(50)
-                                                             .fixed_height(10));
+                                                toast::show(s, format!("Sorgente Stack aggiunta: {}", url));
                                             },
                                             Err(e) => {
                                                 s.add_layer(Dialog::info(format!("Errore nel salvataggio della configurazione: {}", e))
@@ -395,9 +628,7 @@ fn create_settings_screen(siv: &mut Cursive, config: Arc<Mutex<Config>>) {
                                         }
                                     } else {
                                         s.pop_layer();
-                                        s.add_layer(Dialog::info(format!("Sorgente Stack aggiunta: {}", url))
-                                                     .fixed_width(50)
-                                                     .fixed_height(10));
+                                        toast::show(s, format!("Sorgente Stack aggiunta: {}", url));
                                     }
                                 } else {
                                     s.add_layer(Dialog::info(format!("La sorgente {} esiste già", url))
@@ -458,9 +689,7 @@ fn create_settings_screen(siv: &mut Cursive, config: Arc<Mutex<Config>>) {
                                         // Aggiorna il percorso nella configurazione
                                         config_guard.config_file_path = Some(PathBuf::from(&path));
                                         s.pop_layer();
-                                        s.add_layer(Dialog::info(format!("Configurazione salvata in: {}", path))
-                                                     .fixed_width(50)
-                                                     .fixed_height(10));
+                                        toast::show(s, format!("Configurazione salvata in: {}", path));
                                     },
                                     Err(e) => {
                                         s.add_layer(Dialog::info(format!("Errore nel salvataggio della configurazione: {}", e))
@@ -473,7 +702,18 @@ fn create_settings_screen(siv: &mut Cursive, config: Arc<Mutex<Config>>) {
                     }));
             }
         })
-        .button("Back", |s| { s.pop_layer(); }));
+        .button("Back", |s| { s.pop_layer(); });
+
+    // In sola lettura restano attivi solo il cambio del tema e il ritorno
+    if read_only {
+        for button in dialog.buttons_mut() {
+            if !matches!(button.label().trim_matches(['<', '>']), "Cambia tema" | "Back") {
+                button.disable();
+            }
+        }
+    }
+
+    siv.add_layer(dialog);
 }
 
 /// Ottiene le statistiche sui task e gli stack
@@ -0,0 +1,101 @@
+// File: src/ui/components/error_dialog.rs
+
+//! Finestra di errore con azioni di recupero
+//!
+//! Questo modulo fornisce la finestra mostrata quando un'operazione fallisce, con i
+//! pulsanti per riprovare, aprire i log relativi e salvare l'errore su file.
+
+use cursive::Cursive;
+use cursive::views::{Dialog, TextView};
+use cursive::view::Scrollable;
+use cursive::traits::*;
+
+use crate::error;
+use crate::logger;
+use crate::redact;
+use crate::ui::log_view;
+
+/// Crea la finestra di errore di un'operazione fallita
+///
+/// # Arguments
+///
+/// * `title` - Il titolo della finestra
+/// * `item_name` - Il nome dell'elemento coinvolto, usato per filtrare i log (vuoto se non applicabile)
+/// * `message` - Il messaggio di errore da mostrare
+/// * `retry` - L'operazione da ripetere con il pulsante "Riprova", se disponibile
+///
+/// # Returns
+///
+/// La finestra di dialogo, a cui il chiamante può aggiungere altri pulsanti
+pub fn create_error_dialog<F>(title: &str, item_name: &str, message: &str, retry: Option<F>) -> Dialog
+where
+    F: Fn(&mut Cursive) + Send + Sync + 'static,
+{
+    let message = redact::mask(message).into_owned();
+    let mut dialog = Dialog::around(TextView::new(message.clone()).scrollable())
+        .title(title);
+
+    if let Some(retry) = retry {
+        dialog.add_button("Riprova", move |s| {
+            s.pop_layer();
+            retry(s);
+        });
+    }
+
+    let search = item_name.to_string();
+    dialog.add_button("Mostra log", move |s| {
+        log_view::show_log_for(s, &search);
+    });
+
+    let report_title = title.to_string();
+    let report_item = item_name.to_string();
+    let report_message = message;
+    dialog.add_button("Copia su file", move |s| {
+        match logger::write_error_report(&report_title, &report_item, &report_message) {
+            Ok(path) => {
+                s.add_layer(Dialog::info(format!("Errore salvato in:\n{}", path.display()))
+                             .fixed_width(60)
+                             .fixed_height(8));
+            },
+            Err(e) => {
+                s.add_layer(Dialog::info(format!("Impossibile salvare l'errore: {}", e))
+                             .fixed_width(60)
+                             .fixed_height(8));
+            }
+        }
+    });
+
+    dialog.add_button("Chiudi", |s| { s.pop_layer(); });
+
+    dialog
+}
+
+/// Crea la finestra di errore di un'operazione fallita, in base al tipo di errore
+///
+/// Al messaggio viene aggiunto il suggerimento del tipo di errore; per gli errori di
+/// configurazione e di stato il pulsante "Riprova" non viene mostrato, perché l'operazione
+/// fallirebbe di nuovo finché il problema non viene corretto.
+///
+/// # Arguments
+///
+/// * `title` - Il titolo della finestra
+/// * `item_name` - Il nome dell'elemento coinvolto (vuoto se non applicabile)
+/// * `message` - Il messaggio di errore da mostrare
+/// * `err` - L'errore dell'operazione
+/// * `retry` - L'operazione da ripetere con il pulsante "Riprova"
+///
+/// # Returns
+///
+/// La finestra di dialogo, a cui il chiamante può aggiungere altri pulsanti
+pub fn create_operation_error_dialog<F>(title: &str, item_name: &str, message: &str, err: &anyhow::Error, retry: F) -> Dialog
+where
+    F: Fn(&mut Cursive) + Send + Sync + 'static,
+{
+    match error::classify(err) {
+        Some(classified) => {
+            let message = format!("{}\n\n{}", message, classified.hint());
+            create_error_dialog(title, item_name, &message, Some(retry).filter(|_| classified.is_retryable()))
+        },
+        None => create_error_dialog(title, item_name, message, Some(retry)),
+    }
+}
@@ -8,3 +8,12 @@ pub mod selection;
 pub mod selectable_view;
 pub mod task_impl;
 pub mod stack_impl;
+pub mod error_dialog;
+pub mod reboot_banner;
+pub mod status_bar;
+pub mod toast;
+pub mod reload;
+pub mod updates;
+pub mod sudo_prompt;
+pub mod terminal_window;
+pub mod stall_alert;
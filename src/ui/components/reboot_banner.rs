@@ -0,0 +1,92 @@
+// File: src/ui/components/reboot_banner.rs
+
+//! Banner dei riavvii in sospeso
+//!
+//! Il banner è condiviso da tutte le schermate che lo mostrano e viene aggiornato al
+//! termine di ogni operazione, così che un riavvio richiesto resti visibile finché la
+//! macchina non viene riavviata.
+
+use std::sync::{Arc, Mutex};
+use cursive::Cursive;
+use cursive::align::HAlign;
+use cursive::theme::{BaseColor, Color, Effect, Style};
+use cursive::traits::*;
+use cursive::utils::markup::StyledString;
+use cursive::views::{Dialog, TextContent, TextView};
+use lazy_static::lazy_static;
+
+use crate::config::Config;
+use crate::reboot::{self, PendingReboot};
+use crate::task::Task;
+
+lazy_static! {
+    static ref BANNER: TextContent = TextContent::new("");
+}
+
+/// Crea la vista del banner
+pub fn banner_view() -> TextView {
+    TextView::new_with_content(BANNER.clone())
+        .h_align(HAlign::Center)
+}
+
+/// Aggiorna il banner con i riavvii in sospeso
+///
+/// # Arguments
+///
+/// * `config` - La configurazione condivisa
+/// * `tasks` - I task del catalogo
+pub fn refresh(config: &Arc<Mutex<Config>>, tasks: &Arc<Mutex<Vec<Task>>>) {
+    let pending = match (config.lock(), tasks.lock()) {
+        (Ok(config_guard), Ok(tasks_guard)) => reboot::pending_reboot(&config_guard, &tasks_guard),
+        _ => return,
+    };
+
+    if pending.is_empty() {
+        BANNER.set_content("");
+    } else {
+        let style = Style::from(Color::Light(BaseColor::Yellow)).combine(Effect::Bold);
+        BANNER.set_content(StyledString::styled(
+            format!("Riavvio necessario: {} (menu principale > Riavvia ora)", pending.summary()),
+            style));
+    }
+}
+
+/// Chiede conferma e riavvia la macchina
+///
+/// # Arguments
+///
+/// * `s` - L'istanza di Cursive
+/// * `config` - La configurazione condivisa
+/// * `tasks` - I task del catalogo
+pub fn confirm_reboot(s: &mut Cursive, config: &Arc<Mutex<Config>>, tasks: &Arc<Mutex<Vec<Task>>>) {
+    if config.lock().map(|c| c.read_only).unwrap_or(false) {
+        s.add_layer(Dialog::info("Il riavvio non è disponibile in modalità sola lettura")
+                     .fixed_width(50)
+                     .fixed_height(7));
+        return;
+    }
+
+    let pending = match (config.lock(), tasks.lock()) {
+        (Ok(config_guard), Ok(tasks_guard)) => reboot::pending_reboot(&config_guard, &tasks_guard),
+        _ => PendingReboot::default(),
+    };
+    let message = if pending.is_empty() {
+        "Nessun riavvio in sospeso. Riavviare comunque il sistema?".to_string()
+    } else {
+        format!("Riavviare ora il sistema?\n\nMotivo: {}", pending.summary())
+    };
+
+    s.add_layer(Dialog::around(TextView::new(message))
+        .title("Conferma Riavvio")
+        .button("No", |s| { s.pop_layer(); })
+        .button("Riavvia", |s| {
+            s.pop_layer();
+            if let Err(e) = reboot::reboot_now() {
+                s.add_layer(Dialog::info(format!("Errore durante il riavvio: {}", e))
+                             .fixed_width(50)
+                             .fixed_height(7));
+            }
+        })
+        .fixed_width(60)
+        .fixed_height(10));
+}
@@ -0,0 +1,130 @@
+// File: src/ui/components/reload.rs
+
+//! Ricaricamento dei cataloghi dall'interfaccia
+//!
+//! Il ricaricamento viene accodato come job, così non si sovrappone alle installazioni in
+//! background che scrivono i risultati negli stessi elenchi. Letti i cataloghi, task e
+//! stack vengono sostituiti insieme e tutte le schermate aperte che li mostrano vengono
+//! aggiornate: ogni schermata si registra con [`register`] all'apertura e si cancella con
+//! [`unregister`] alla chiusura, come per gli iscritti al canale degli eventi.
+
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use anyhow::anyhow;
+use cursive::Cursive;
+use cursive::views::Dialog;
+use cursive::traits::*;
+use lazy_static::lazy_static;
+use log::Level;
+
+use crate::catalog;
+use crate::config::Config;
+use crate::events;
+use crate::jobs;
+use crate::stack::Stack;
+use crate::task::Task;
+use crate::ui::components::reboot_banner;
+use crate::ui::components::status_bar;
+use crate::ui::components::toast;
+
+/// Identificativo di una schermata registrata
+pub type RefresherId = u64;
+
+/// Funzione che aggiorna una schermata dopo il ricaricamento
+type Refresher = Arc<dyn Fn(&mut Cursive) + Send + Sync>;
+
+lazy_static! {
+    static ref OPEN_VIEWS: Mutex<Vec<(RefresherId, Refresher)>> = Mutex::new(Vec::new());
+    static ref NEXT_REFRESHER: AtomicU64 = AtomicU64::new(1);
+}
+
+/// Registra una schermata da aggiornare dopo ogni ricaricamento dei cataloghi
+///
+/// # Arguments
+///
+/// * `refresh` - La funzione che aggiorna la schermata
+///
+/// # Returns
+///
+/// L'identificativo da passare a [`unregister`] alla chiusura della schermata
+pub fn register<F>(refresh: F) -> RefresherId
+where
+    F: Fn(&mut Cursive) + Send + Sync + 'static,
+{
+    let id = NEXT_REFRESHER.fetch_add(1, Ordering::SeqCst);
+    if let Ok(mut views) = OPEN_VIEWS.lock() {
+        views.push((id, Arc::new(refresh)));
+    }
+    id
+}
+
+/// Cancella la registrazione di una schermata chiusa
+pub fn unregister(id: RefresherId) {
+    if let Ok(mut views) = OPEN_VIEWS.lock() {
+        views.retain(|(view_id, _)| *view_id != id);
+    }
+}
+
+/// Aggiorna le schermate aperte
+fn refresh_open_views(s: &mut Cursive) {
+    // Le funzioni vengono eseguite fuori dal lock: possono registrare nuove schermate
+    let refreshers: Vec<Refresher> = match OPEN_VIEWS.lock() {
+        Ok(views) => views.iter().map(|(_, refresh)| Arc::clone(refresh)).collect(),
+        Err(_) => return,
+    };
+
+    for refresh in refreshers {
+        refresh(s);
+    }
+}
+
+/// Accoda il ricaricamento dei cataloghi e aggiorna l'interfaccia al termine
+///
+/// # Arguments
+///
+/// * `s` - L'istanza di Cursive
+/// * `config` - La configurazione condivisa
+/// * `tasks` - I task condivisi
+/// * `stacks` - Gli stack condivisi
+pub fn reload_catalogs(s: &mut Cursive, config: Arc<Mutex<Config>>, tasks: Arc<Mutex<Vec<Task>>>, stacks: Arc<Mutex<Vec<Stack>>>) {
+    let cb_sink = s.cb_sink().clone();
+    toast::show(s, "Ricaricamento dei cataloghi in coda");
+
+    jobs::submit("Ricaricamento dei cataloghi", move || {
+        let config_snapshot = config.lock()
+            .map_err(|_| anyhow!("Failed to lock config"))?
+            .clone();
+
+        // I cataloghi vengono letti senza lock: con le sorgenti remote può richiedere tempo
+        let result = catalog::load(&config_snapshot).and_then(|(new_tasks, new_stacks)| {
+            // Un'operazione avviata fuori dalla coda userebbe gli elenchi durante la sostituzione
+            if events::active_runs() > 0 {
+                return Err(anyhow!("Operazioni in corso: riprova al loro termine"));
+            }
+            let mut tasks_guard = tasks.lock().map_err(|_| anyhow!("Failed to lock tasks"))?;
+            let mut stacks_guard = stacks.lock().map_err(|_| anyhow!("Failed to lock stacks"))?;
+            Ok(catalog::replace(&mut tasks_guard, &mut stacks_guard, new_tasks, new_stacks))
+        });
+
+        let outcome = result.as_ref().map(|_| ()).map_err(|e| anyhow!("{:#}", e));
+        let _ = cb_sink.send(Box::new(move |s: &mut Cursive| {
+            match result {
+                Ok(changes) => {
+                    events::log_line(Level::Info, changes.to_string());
+                    if !changes.is_empty() {
+                        events::log_line(Level::Info, changes.details());
+                    }
+                    refresh_open_views(s);
+                    reboot_banner::refresh(&config, &tasks);
+                    status_bar::refresh(&config, &tasks);
+                    toast::show(s, changes.to_string());
+                },
+                Err(e) => {
+                    s.add_layer(Dialog::info(format!("Ricaricamento dei cataloghi non riuscito, restano quelli precedenti:\n\n{:#}", e))
+                                 .fixed_width(60));
+                },
+            }
+        }));
+        outcome
+    });
+}
@@ -1,24 +1,55 @@
 // Soluzione completa: Ristrutturazione del file src/ui/components/selectable_view.rs
 
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
-use anyhow::{Result, anyhow};
+use std::sync::atomic::{AtomicU64, Ordering};
+use anyhow::{Context, Result, anyhow};
+use chrono::Local;
+use log::Level;
 
 use cursive::Cursive;
-use cursive::views::{Dialog, SelectView, TextView, LinearLayout, DummyView, Panel, TextContent, Button, OnEventView, ScrollView};
-use cursive::view::Scrollable;
+use cursive::views::{Dialog, SelectView, TextView, LinearLayout, DummyView, Panel, TextContent, Button, OnEventView, ScrollView, EditView, ProgressBar, NamedView};
+use cursive::utils::Counter;
+use cursive::utils::markup::StyledString;
+use cursive::view::{Scrollable, ScrollStrategy};
 use cursive::traits::*;
 use cursive::align::HAlign;
 use cursive::event::{Event, Key};
 
 use crate::config::Config;
+use crate::events;
+use crate::jobs;
+use crate::session;
+use crate::utils;
+use crate::snapshot::{self, Snapshot};
+use crate::stack::{StackReport, StackVariable};
 use crate::ui::log_view;
-use crate::ui::components::selection::{SelectableItem, SharedSelection};
+use crate::ui::components::error_dialog;
+use crate::ui::components::reboot_banner;
+use crate::ui::components::reload;
+use crate::ui::components::status_bar;
+use crate::ui::components::toast;
+use crate::ui::components::selection::{self, ItemStatus, ListFilter, ListRow, SelectableItem, SharedSelection, SortOrder};
+
+// Contatore dei nomi delle finestre di avanzamento dei job
+static PROGRESS_LAYERS: AtomicU64 = AtomicU64::new(0);
+
+/// Nome della vista di scorrimento della lista
+const ITEM_LIST_SCROLL: &str = "item_list_scroll";
+
+/// Vista di scorrimento della lista degli elementi
+type ItemListScroll = ScrollView<OnEventView<NamedView<SelectView<usize>>>>;
 
 /// Trait per implementare le operazioni eseguibili su un tipo
 pub trait Executable<T: SelectableItem> {
     /// Installa l'elemento
     fn install(&mut self, config: &Config) -> Result<()>;
     
+    /// Reinstalla l'elemento anche se risulta già installato
+    fn reinstall(&mut self, config: &Config, uninstall_first: bool) -> Result<()>;
+    
     /// Disinstalla l'elemento
     fn uninstall(&mut self, config: &Config) -> Result<()>;
     
@@ -27,6 +58,41 @@ pub trait Executable<T: SelectableItem> {
     
     /// Ripara l'elemento
     fn remediate(&mut self, config: &Config) -> Result<()>;
+
+    /// Ripete l'ultima operazione fallita sull'elemento
+    fn retry(&mut self, config: &Config) -> Result<()> {
+        self.install(config)
+    }
+
+    /// Restituisce lo snapshot da cui ripristinare il sistema dopo un'installazione fallita
+    fn restore_point(&self, _config: &Config) -> Option<Snapshot> {
+        None
+    }
+
+    /// Restituisce i risultati per task dell'ultima operazione, per gli elementi composti
+    fn last_report(&self) -> Option<StackReport> {
+        None
+    }
+
+    /// Restituisce le variabili da richiedere prima dell'installazione, con il valore attuale
+    fn prompt_variables(&self, _config: &Config) -> Vec<(StackVariable, Option<String>)> {
+        Vec::new()
+    }
+
+    /// Salva i valori delle variabili inseriti dall'utente
+    fn set_variable_values(&mut self, _config: &Config, _values: &HashMap<String, String>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Restituisce il riferimento all'elemento che sostituisce quello deprecato
+    fn replacement(&self) -> Option<String> {
+        None
+    }
+
+    /// Migra l'elemento deprecato all'elemento che lo sostituisce
+    fn migrate(_items: &mut [T], _idx: usize, _config: &Config) -> Result<()> {
+        Err(anyhow!("Migration is not supported for this item"))
+    }
 }
 
 /// Crea una vista per gestire una collezione di elementi selezionabili
@@ -35,6 +101,7 @@ pub fn create_selectable_view<T, E>(
     config: Arc<Mutex<Config>>,
     items: Arc<Mutex<Vec<E>>>, 
     selection: SharedSelection<T>,
+    list_name: &str,
     view_title: &str,
     _can_modify_items: bool, // Se gli elementi possono essere modificati (es: task installati)
 ) -> Result<()> 
@@ -50,9 +117,28 @@ where
         .h_align(HAlign::Left)
         .autojump();
 
-    // Popola la vista con gli elementi
-    for (idx, item) in items_guard.iter().enumerate() {
-        select_view.add_item(item.format_for_list(), idx);
+    // Filtro, ordinamento, cursore e scorrimento ripartono da quelli dell'ultima sessione
+    let list_name = list_name.to_string();
+    session::set_screen(Some(&list_name));
+    let saved_state = session::list_state(&list_name);
+    let (filter, sort) = match selection.lock() {
+        Ok(mut sel) => {
+            if let Some(filter) = saved_state.filter.as_deref().and_then(ListFilter::from_str) {
+                sel.set_filter(filter);
+            }
+            if let Some(sort) = saved_state.sort.as_deref().and_then(SortOrder::from_str) {
+                sel.set_sort(sort);
+            }
+            (sel.filter(), sel.sort())
+        },
+        Err(_) => (ListFilter::default(), SortOrder::default()),
+    };
+
+    // Popola la vista con gli elementi, nella modalità scelta in configurazione
+    let list_mode = config.lock().map(|c| c.list_mode).unwrap_or_default();
+    let rows = visible_rows(&items_guard, filter, sort);
+    for (row, idx) in &rows {
+        select_view.add_item(row.render(list_mode, false), *idx);
     }
 
     // Dettagli dell'elemento selezionato
@@ -60,16 +146,30 @@ where
     let item_detail_view = TextView::new_with_content(item_detail.clone())
         .scrollable();
 
+    // Il cursore torna sull'elemento su cui si trovava all'ultima chiusura
+    let restored = saved_state.selected.as_ref().and_then(|selected| {
+        rows.iter().position(|(_, idx)| items_guard.get(*idx).is_some_and(|item| item.item_ref() == *selected))
+    });
+    if let Some(position) = restored {
+        select_view.set_selection(position);
+        if let Some(item) = items_guard.get(rows[position].1) {
+            item_detail.set_content(item.format_details());
+        }
+    }
+
     // Gestisci la selezione degli elementi (prima di avvolgere in OnEventView)
     let items_clone = Arc::clone(&items);
     let item_detail_clone = item_detail.clone();
-    select_view.set_on_select(move |_siv, idx| {
+    let list_name_for_select = list_name.clone();
+    select_view.set_on_select(move |siv, idx| {
         if let Ok(items_guard) = items_clone.lock() {
             if let Some(item) = items_guard.get(*idx) {
                 // Aggiorna il testo dei dettagli
                 item_detail_clone.set_content(item.format_details());
+                session::update_list(&list_name_for_select, |state| state.selected = Some(item.item_ref()));
             }
         }
+        remember_scroll(siv, &list_name_for_select);
     });
 
     // Rilascia il lock prima di creare le closure
@@ -81,13 +181,14 @@ where
     
     // Clone items for the on_event closure
     let items_for_event = Arc::clone(&items);
+    let config_for_event = Arc::clone(&config);
     
     // Avvolgi con OnEventView per gestire gli eventi
     let select_view_with_events = OnEventView::new(select_view)
     .on_event(Event::Key(Key::Enter), move |s| {
         // Ottieni l'indice selezionato dalla vista originale
         if let Some(idx) = s.call_on_name("item_list", |view: &mut SelectView<usize>| {
-            view.selected_id()
+            view.selection().map(|idx| *idx)
         }).unwrap_or(None) {
             if let Ok(mut sel) = selection_clone.lock() {
                 sel.toggle(idx);
@@ -95,67 +196,37 @@ where
                 // Aggiorna l'interfaccia utente per mostrare la selezione
                 let is_selected = sel.is_selected(idx);
                 
-                // Modifica l'etichetta nella vista
-                s.call_on_name("item_list", |view: &mut SelectView<usize>| {
-                    if let Some((item_label, _)) = view.get_item(idx) {
-                        let item_label = item_label.to_string();
-                        
-                        // Aggiorna l'etichetta basata sulla selezione - CORREZIONE
-                        let new_label = if is_selected {
-                            if item_label.starts_with("[ ]") {
-                                item_label.replacen("[ ]", "[*]", 1)
-                            } else if item_label.starts_with("[✓]") {
-                                item_label.replacen("[✓]", "[*]", 1)
-                            } else if item_label.starts_with("[!]") {
-                                item_label.replacen("[!]", "[*]", 1)
-                            } else {
-                                format!("[*]{}", &item_label[3..])
-                            }
-                        } else {
-                            // Ripristina lo stato originale
-                            if item_label.contains("[✓]") {
-                                item_label.replacen("[*]", "[✓]", 1)
-                            } else if item_label.contains("[!]") {
-                                item_label.replacen("[*]", "[!]", 1)
-                            } else {
-                                item_label.replacen("[*]", "[ ]", 1)
-                            }
-                        };
-                        
-                        // Aggiorna l'item nella vista
-                        let value = view.selection().map(|i| *i);
-                        view.remove_item(idx);
-                        view.insert_item(idx, new_label, idx);
-                        
-                        // Ripristina la selezione
-                        if let Some(val) = value {
-                            view.set_selection(val);
+                // Modifica l'etichetta nella vista, ripartendo dallo stato attuale dell'elemento
+                let list_mode = config_for_event.lock().map(|c| c.list_mode).unwrap_or_default();
+                let item_row = items_for_event.lock().ok()
+                    .and_then(|items_guard| items_guard.get(idx).map(|item| item.list_row()));
+                if let Some(item_row) = item_row {
+                    // Sostituisce solo l'etichetta, senza spostare il cursore
+                    s.call_on_name("item_list", |view: &mut SelectView<usize>| {
+                        let row = view.iter().position(|(_, value)| *value == idx);
+                        if let Some((label, _)) = row.and_then(|row| view.get_item_mut(row)) {
+                            *label = item_row.render(list_mode, is_selected);
                         }
-                    }
-                });
-                
+                    });
+                }
+
                 // Aggiorna l'area dei log
-                s.call_on_name("log_scroll_view", |view: &mut ScrollView<TextView>| {
-                    let current_text = view.get_inner().get_content().source().to_string();
-                    let item_name = if let Ok(items_guard) = items_for_event.lock() { // Use items_for_event here
-                        if let Some(item) = items_guard.get(idx) {
-                            format!("{}", item)
-                        } else {
-                            "elemento sconosciuto".to_string()
-                        }
+                let item_name = if let Ok(items_guard) = items_for_event.lock() {
+                    if let Some(item) = items_guard.get(idx) {
+                        format!("{}", item)
                     } else {
                         "elemento sconosciuto".to_string()
-                    };
-                    
-                    let msg = if is_selected {
-                        format!("Elemento selezionato: {}", item_name)
-                    } else {
-                        format!("Elemento deselezionato: {}", item_name)
-                    };
-                    
-                    view.get_inner_mut().set_content(format!("{}\n{}", current_text, msg));
-                    view.scroll_to_bottom();
-                });
+                    }
+                } else {
+                    "elemento sconosciuto".to_string()
+                };
+
+                let msg = if is_selected {
+                    format!("Elemento selezionato: {}", item_name)
+                } else {
+                    format!("Elemento deselezionato: {}", item_name)
+                };
+                events::log_line(Level::Info, msg);
             }
         }
     });
@@ -165,82 +236,80 @@ where
     let selection_info_view = TextView::new_with_content(selection_info.clone())
         .h_align(HAlign::Center);
 
-    // Funzione di aggiornamento UI
-    fn update_ui<T: Send + Sync + 'static, E: SelectableItem + Clone + 'static>(
-        items: &Arc<Mutex<Vec<E>>>,
-        selection: &SharedSelection<T>,
-        selection_info_content: &TextContent,
-        cb_sink: &cursive::CbSink,
-    ) {
-        if let Ok(items_guard) = items.lock() {
-            let items_data: Vec<(String, usize)> = items_guard.iter().enumerate()
-                .map(|(idx, item)| (item.format_for_list(), idx))
-                .collect();
+    // Il tasto 'v' alterna la vista compatta e quella dettagliata, ricordando la scelta
+    let select_view_with_events = select_view_with_events.on_event('v', {
+        let items = Arc::clone(&items);
+        let config = Arc::clone(&config);
+        let selection = Arc::clone(&selection);
+        let selection_info = selection_info.clone();
 
-            let items_data = items_data.clone();
-            let selection = Arc::clone(selection);
-            let selection_info_content = selection_info_content.clone();
-            
-            if let Err(_) = cb_sink.send(Box::new(move |s: &mut Cursive| {
-                let selection_count = {
-                    if let Ok(sel) = selection.lock() {
-                        sel.count()
-                    } else {
-                        0
+        move |s| {
+            let list_mode = match config.lock() {
+                Ok(mut config_guard) => {
+                    config_guard.list_mode = config_guard.list_mode.toggle();
+                    if let Some(config_path) = config_guard.config_file_path.as_ref().filter(|_| !config_guard.read_only) {
+                        if let Err(e) = config_guard.save(config_path) {
+                            events::log_line(Level::Warn, format!("Errore nel salvataggio della configurazione: {}", e));
+                        }
                     }
-                };
+                    config_guard.list_mode
+                },
+                Err(_) => return,
+            };
 
-                if selection_count > 0 {
-                    selection_info_content.set_content(format!("Premi 'Invio' per selezionare/deselezionare. {} elementi selezionati.", selection_count));
-                } else {
-                    selection_info_content.set_content("Premi 'Invio' per selezionare/deselezionare. Nessun elemento selezionato.".to_string());
-                }
+            events::log_line(Level::Info, format!("Modalità di visualizzazione: {}", list_mode.to_str()));
+            update_ui(&items, &config, &selection, &selection_info, &s.cb_sink().clone());
+        }
+    });
 
-                s.call_on_name("item_list", |view: &mut SelectView<usize>| {
-                    view.clear();
-
-                    for (item_str, idx) in &items_data {
-                        let is_selected = {
-                            if let Ok(sel) = selection.lock() {
-                                sel.is_selected(*idx)
-                            } else {
-                                false
-                            }
-                        };
-
-                        // CORREZIONE: Preserva l'etichetta completa
-                        let display_str = if is_selected {
-                            if item_str.starts_with("[ ]") {
-                                item_str.replacen("[ ]", "[*]", 1)
-                            } else if item_str.starts_with("[✓]") {
-                                item_str.replacen("[✓]", "[*]", 1)
-                            } else if item_str.starts_with("[!]") {
-                                item_str.replacen("[!]", "[*]", 1)
-                            } else {
-                                format!("[*]{}", &item_str[3..])
-                            }
-                        } else {
-                            item_str.clone()
-                        };
-
-                        view.add_item(display_str, *idx);
-                    }
-                });
-            })) {
-                eprintln!("Errore nell'aggiornamento della vista");
+    // Il tasto 'f' mostra solo gli elementi falliti, o di nuovo tutti
+    let select_view_with_events = select_view_with_events.on_event('f', {
+        let items = Arc::clone(&items);
+        let config = Arc::clone(&config);
+        let selection = Arc::clone(&selection);
+        let selection_info = selection_info.clone();
+        let list_name = list_name.clone();
+
+        move |s| {
+            if let Ok(mut sel) = selection.lock() {
+                let filter = sel.filter().toggle();
+                sel.set_filter(filter);
+                session::update_list(&list_name, |state| state.filter = Some(filter.to_str().to_string()));
             }
+            update_ui(&items, &config, &selection, &selection_info, &s.cb_sink().clone());
         }
-    }
+    });
+
+    // Il tasto 'o' passa all'ordinamento successivo: catalogo, nome, stato
+    let select_view_with_events = select_view_with_events.on_event('o', {
+        let items = Arc::clone(&items);
+        let config = Arc::clone(&config);
+        let selection = Arc::clone(&selection);
+        let selection_info = selection_info.clone();
+        let list_name = list_name.clone();
+
+        move |s| {
+            if let Ok(mut sel) = selection.lock() {
+                let sort = sel.sort().next();
+                sel.set_sort(sort);
+                session::update_list(&list_name, |state| state.sort = Some(sort.to_str().to_string()));
+                events::log_line(Level::Info, format!("Ordinamento: {}", sort.label()));
+            }
+            update_ui(&items, &config, &selection, &selection_info, &s.cb_sink().clone());
+        }
+    });
 
     // BOTTONI PER LE AZIONI
-    
+
+    // In sola lettura le azioni che modificano il sistema sono disabilitate
+    let read_only = config.lock().map(|c| c.read_only).unwrap_or(false);
+
     // Install All Button
     let install_all_button = Button::new("Install Selezionati", {
         let items = Arc::clone(&items);
         let config = Arc::clone(&config);
         let selection = Arc::clone(&selection);
         let selection_info = selection_info.clone();
-        let cb_sink = siv.cb_sink().clone();
         
         move |s| {
             let selected_indices = {
@@ -252,150 +321,107 @@ where
             };
 
             if selected_indices.is_empty() {
-                s.add_layer(Dialog::info("Nessun elemento selezionato")
-                             .fixed_width(50)
-                             .fixed_height(7));
+                toast::show(s, "Nessun elemento selezionato");
+                return;
+            }
+
+            let message = format!("Sei sicuro di voler installare {} elementi selezionati?", selected_indices.len());
+            let items = Arc::clone(&items);
+            let config_for_action = Arc::clone(&config);
+            let selection = Arc::clone(&selection);
+            let selection_info = selection_info.clone();
+
+            confirm_action(s, &config, false, "Conferma Installazione", &message, move |s| {
+                install_items(s, &items, &config_for_action, &selection, &selection_info, &selected_indices);
+            });
+        }
+    });
+
+    // Install Button
+    let install_button = Button::new("Install", {
+        let items = Arc::clone(&items);
+        let config = Arc::clone(&config);
+        let selection = Arc::clone(&selection);
+        let selection_info = selection_info.clone();
+        
+        move |s| {
+            let idx = match s.call_on_name("item_list", |view: &mut SelectView<usize>| view.selection().map(|idx| *idx)) {
+                Some(Some(idx)) => idx,
+                _ => return,
+            };
+
+            let item_name = match items.lock() {
+                Ok(items_guard) => match items_guard.get(idx) {
+                    Some(item) => item.to_string(),
+                    None => return,
+                },
+                Err(_) => return,
+            };
+
+            let items = Arc::clone(&items);
+            let config_for_action = Arc::clone(&config);
+            let selection = Arc::clone(&selection);
+            let selection_info = selection_info.clone();
+
+            confirm_action(s, &config, false, "Conferma Installazione", &format!("Installare {}?", item_name), move |s| {
+                prompt_and_install_item(s, &items, &config_for_action, &selection, &selection_info, idx);
+            });
+        }
+    });
+
+    // Reinstall Button
+    let reinstall_button = Button::new("Reinstall", {
+        let items = Arc::clone(&items);
+        let config = Arc::clone(&config);
+        let selection = Arc::clone(&selection);
+        let selection_info = selection_info.clone();
+        
+        move |s| {
+            let idx = match s.call_on_name("item_list", |view: &mut SelectView<usize>| view.selection().map(|idx| *idx)) {
+                Some(Some(idx)) => idx,
+                _ => return,
+            };
+
+            let item_name = match items.lock() {
+                Ok(items_guard) => match items_guard.get(idx) {
+                    Some(item) => item.to_string(),
+                    None => return,
+                },
+                Err(_) => return,
+            };
+
+            // Senza conferma la reinstallazione non disinstalla prima l'elemento
+            let needs_confirmation = config.lock()
+                .map(|c| c.confirm_actions.requires_confirmation(true))
+                .unwrap_or(true);
+            if !needs_confirmation {
+                reinstall_item(s, &items, &config, &selection, &selection_info, idx, false);
                 return;
             }
 
-            s.add_layer(Dialog::around(TextView::new(format!("Sei sicuro di voler installare {} elementi selezionati?", 
-                                                           selected_indices.len())))
-                .title("Conferma Installazione")
-                .button("No", |s| { s.pop_layer(); })
-                .button("Sì", {
+            s.add_layer(Dialog::around(TextView::new(format!(
+                    "Reinstallare {}?\n\nIl bundle verrà scaricato di nuovo e l'installazione eseguita anche se risulta già installato.",
+                    item_name)))
+                .title("Conferma Reinstallazione")
+                .button("Annulla", |s| { s.pop_layer(); })
+                .button("Disinstalla e reinstalla", {
                     let items = Arc::clone(&items);
                     let config = Arc::clone(&config);
-                    let selected_indices = selected_indices.clone();
+                    let selection = Arc::clone(&selection);
                     let selection_info = selection_info.clone();
-                    let cb_sink = cb_sink.clone();
-                    let outer_selection = Arc::clone(&selection);
-                    let selection_clone = Arc::clone(&selection);
-                    let selection_for_update = Arc::clone(&selection);
-                    
                     move |s| {
                         s.pop_layer();
-                        
-                        let progress_text = TextContent::new("Inizializzazione installazione...");
-                        let progress_view = Dialog::around(TextView::new_with_content(progress_text.clone()))
-                            .title("Installazione in corso")
-                            .fixed_width(60)
-                            .fixed_height(10);
-                        
-                        s.add_layer(progress_view);
-                        
-                        // Aggiorna l'area dei log
-                        s.call_on_name("log_scroll_view", |view: &mut ScrollView<TextView>| {
-                            let current_text = view.get_inner().get_content().source().to_string();
-                            view.get_inner_mut().set_content(format!("{}\nAvvio installazione elementi selezionati...", current_text));
-                            view.scroll_to_bottom();
-                        });
-                        
-                        let mut success_count = 0;
-                        let mut error_messages = Vec::new();
-                        
-                        for (i, idx) in selected_indices.iter().enumerate() {
-                            let result = {
-                                let mut items_guard = match items.lock() {
-                                    Ok(guard) => guard,
-                                    Err(e) => {
-                                        error_messages.push(format!("Errore nel blocco degli elementi: {}", e));
-                                        continue;
-                                    }
-                                };
-                                
-                                let item = match items_guard.get_mut(*idx) {
-                                    Some(item) => item,
-                                    None => {
-                                        error_messages.push(format!("Elemento con indice {} non trovato", idx));
-                                        continue;
-                                    }
-                                };
-                                
-                                if !item.can_install() {
-                                    continue;
-                                }
-                                
-                                progress_text.set_content(format!("Installazione dell'elemento {} ({}/{})...", 
-                                                                item, i+1, selected_indices.len()));
-                                
-                                // Aggiorna l'area dei log
-                                s.call_on_name("log_scroll_view", |view: &mut ScrollView<TextView>| {
-                                    let current_text = view.get_inner().get_content().source().to_string();
-                                    let msg = format!("Installazione dell'elemento {} ({}/{})...", 
-                                                    item, i+1, selected_indices.len());
-                                    view.get_inner_mut().set_content(format!("{}\n{}", current_text, msg));
-                                    view.scroll_to_bottom();
-                                });
-                                
-                                let config_guard = match config.lock() {
-                                    Ok(guard) => guard,
-                                    Err(e) => {
-                                        error_messages.push(format!("Errore nel blocco della configurazione: {}", e));
-                                        continue;
-                                    }
-                                };
-                                
-                                item.install(&config_guard)
-                            };
-                            
-                            match result {
-                                Ok(_) => {
-                                    success_count += 1;
-                                    // Aggiorna l'area dei log
-                                    s.call_on_name("log_scroll_view", |view: &mut ScrollView<TextView>| {
-                                        let current_text = view.get_inner().get_content().source().to_string();
-                                        view.get_inner_mut().set_content(format!("{}\nCompletato con successo", current_text));
-                                        view.scroll_to_bottom();
-                                    });
-                                },
-                                Err(e) => {
-                                    error_messages.push(format!("Errore nell'operazione su {}: {}", idx, e));
-                                    // Aggiorna l'area dei log
-                                    s.call_on_name("log_scroll_view", |view: &mut ScrollView<TextView>| {
-                                        let current_text = view.get_inner().get_content().source().to_string();
-                                        view.get_inner_mut().set_content(format!("{}\nErrore: {}", current_text, e));
-                                        view.scroll_to_bottom();
-                                    });
-                                }
-                            }
-                        }
-                        
+                        reinstall_item(s, &items, &config, &selection, &selection_info, idx, true);
+                    }
+                })
+                .button("Reinstalla", {
+                    let items = Arc::clone(&items);
+                    let config = Arc::clone(&config);
+                    let selection = Arc::clone(&selection);
+                    let selection_info = selection_info.clone();
+                    move |s| {
                         s.pop_layer();
-                        
-                        if error_messages.is_empty() {
-                            s.add_layer(Dialog::info(format!("Tutti i {} elementi sono stati elaborati con successo", success_count))
-                                         .fixed_width(60)
-                                         .fixed_height(10));
-                                         
-                            // Aggiorna l'area dei log
-                            s.call_on_name("log_scroll_view", |view: &mut ScrollView<TextView>| {
-                                let current_text = view.get_inner().get_content().source().to_string();
-                                view.get_inner_mut().set_content(format!("{}\nInstallazione completata con successo per tutti gli elementi", current_text));
-                                view.scroll_to_bottom();
-                            });
-                        } else {
-                            let mut result_message = format!("Operazioni completate con successo: {}/{}\n\nErrori:\n", 
-                                                          success_count, selected_indices.len());
-                            for error in &error_messages {
-                                result_message.push_str(&format!("- {}\n", error));
-                            }
-                            
-                            s.add_layer(Dialog::around(TextView::new(result_message).scrollable())
-                                .title("Risultato Installazione")
-                                .button("OK", |s| { s.pop_layer(); })
-                                .fixed_width(70)
-                                .fixed_height(15));
-                                
-                            // Aggiorna l'area dei log
-                            s.call_on_name("log_scroll_view", |view: &mut ScrollView<TextView>| {
-                                let current_text = view.get_inner().get_content().source().to_string();
-                                view.get_inner_mut().set_content(format!("{}\nInstallazione completata con errori. Successi: {}/{}",
-                                                     current_text, success_count, selected_indices.len()));
-                                view.scroll_to_bottom();
-                            });
-                        }
-                        
-                        update_ui(&items, &selection_for_update, &selection_info, &cb_sink);
+                        reinstall_item(s, &items, &config, &selection, &selection_info, idx, false);
                     }
                 })
                 .fixed_width(60)
@@ -403,120 +429,96 @@ where
         }
     });
 
-    // Install Button
-    let install_button = Button::new("Install", {
+    // Migrate Button
+    let migrate_button = Button::new("Migra", {
         let items = Arc::clone(&items);
         let config = Arc::clone(&config);
         let selection = Arc::clone(&selection);
         let selection_info = selection_info.clone();
-        let cb_sink = siv.cb_sink().clone();
-        
+
         move |s| {
-            let idx = match s.call_on_name("item_list", |view: &mut SelectView<usize>| view.selected_id()) {
+            let idx = match s.call_on_name("item_list", |view: &mut SelectView<usize>| view.selection().map(|idx| *idx)) {
                 Some(Some(idx)) => idx,
                 _ => return,
             };
 
-            // Ottieni il nome dell'elemento per il log
-            let item_name = {
-                if let Ok(items_guard) = items.lock() {
-                    if let Some(item) = items_guard.get(idx) {
-                        format!("{}", item)
-                    } else {
-                        "elemento sconosciuto".to_string()
-                    }
-                } else {
-                    "elemento sconosciuto".to_string()
-                }
+            let (item_name, replacement) = match items.lock() {
+                Ok(items_guard) => match items_guard.get(idx) {
+                    Some(item) => (item.to_string(), item.replacement()),
+                    None => return,
+                },
+                Err(_) => return,
             };
-            
-            // Aggiorna l'area dei log
-            s.call_on_name("log_scroll_view", |view: &mut ScrollView<TextView>| {
-                let current_text = view.get_inner().get_content().source().to_string();
-                let msg = format!("Installazione di {}...", item_name);
-                view.get_inner_mut().set_content(format!("{}\n{}", current_text, msg));
-                view.scroll_to_bottom();
-            });
-
-            let item_result = {
-                let mut items_guard = match items.lock() {
-                    Ok(guard) => guard,
-                    Err(e) => {
-                        s.add_layer(Dialog::info(format!("Errore nel blocco degli elementi: {}", e))
-                                     .fixed_width(50)
-                                     .fixed_height(7));
-                        return;
-                    }
-                };
-
-                let item = match items_guard.get_mut(idx) {
-                    Some(item) => item,
-                    None => {
-                        s.add_layer(Dialog::info("Elemento non trovato")
-                                     .fixed_width(50)
-                                     .fixed_height(7));
-                        return;
-                    }
-                };
 
-                if !item.can_install() {
-                    s.add_layer(Dialog::info("L'elemento non può essere installato")
+            let replacement = match replacement {
+                Some(replacement) => replacement,
+                None => {
+                    s.add_layer(Dialog::info(format!("{} non è deprecato o non indica un sostituto", item_name))
                                  .fixed_width(50)
                                  .fixed_height(7));
                     return;
                 }
+            };
 
-                let config_guard = match config.lock() {
-                    Ok(guard) => guard,
-                    Err(e) => {
-                        s.add_layer(Dialog::info(format!("Errore nel blocco della configurazione: {}", e))
-                                     .fixed_width(50)
-                                     .fixed_height(7));
-                        return;
-                    }
-                };
+            let message = format!(
+                "Migrare {} a {}?\n\n{} verrà installato (se necessario) e {} disinstallato.",
+                item_name, replacement, replacement, item_name);
+            let items = Arc::clone(&items);
+            let config_for_action = Arc::clone(&config);
+            let selection = Arc::clone(&selection);
+            let selection_info = selection_info.clone();
+
+            confirm_action(s, &config, true, "Conferma Migrazione", &message, move |s| {
+                migrate_item(s, &items, &config_for_action, &selection, &selection_info, idx);
+            });
+        }
+    });
+
+    // Retry Failed Button
+    let retry_failed_button = Button::new("Riprova Falliti", {
+        let items = Arc::clone(&items);
+        let config = Arc::clone(&config);
+        let selection = Arc::clone(&selection);
+        let selection_info = selection_info.clone();
 
-                item.install(&config_guard)
+        move |s| {
+            let failed_indices: Vec<usize> = match items.lock() {
+                Ok(items_guard) => items_guard.iter().enumerate()
+                    .filter(|(_, item)| item.status() == ItemStatus::Failed)
+                    .map(|(idx, _)| idx)
+                    .collect(),
+                Err(_) => return,
             };
 
-            match item_result {
-                Ok(_) => {
-                    s.add_layer(Dialog::info("Operazione installazione completata con successo")
-                                 .fixed_width(50)
-                                 .fixed_height(7));
-                    
-                    // Aggiorna l'area dei log
-                    s.call_on_name("log_scroll_view", |view: &mut ScrollView<TextView>| {
-                        let current_text = view.get_inner().get_content().source().to_string();
-                        let msg = format!("Operazione completata con successo per {}", item_name);
-                        view.get_inner_mut().set_content(format!("{}\n{}", current_text, msg));
-                        view.scroll_to_bottom();
-                    });
-                    
-                    update_ui(&items, &selection, &selection_info, &cb_sink);
-                    log_view::show_recent_logs_popup(s);
-                },
-                Err(e) => {
-                    s.add_layer(Dialog::info(format!("Errore durante l'operazione installazione: {}", e))
-                                 .fixed_width(50)
-                                 .fixed_height(7));
-                    
-                    // Aggiorna l'area dei log
-                    s.call_on_name("log_scroll_view", |view: &mut ScrollView<TextView>| {
-                        let current_text = view.get_inner().get_content().source().to_string();
-                        let msg = format!("Errore durante l'installazione di {}: {}", item_name, e);
-                        view.get_inner_mut().set_content(format!("{}\n{}", current_text, msg));
-                        view.scroll_to_bottom();
-                    });
-                }
+            if failed_indices.is_empty() {
+                toast::show(s, "Nessun elemento fallito");
+                return;
             }
+
+            let message = format!("Ripetere l'ultima operazione fallita di {} elementi?", failed_indices.len());
+            let items = Arc::clone(&items);
+            let config_for_action = Arc::clone(&config);
+            let selection = Arc::clone(&selection);
+            let selection_info = selection_info.clone();
+
+            confirm_action(s, &config, false, "Conferma Nuovo Tentativo", &message, move |s| {
+                retry_items(s, &items, &config_for_action, &selection, &selection_info, &failed_indices);
+            });
         }
     });
 
+    // Export CSV Button
+    let export_button = Button::new("Esporta CSV", {
+        let items = Arc::clone(&items);
+        let selection = Arc::clone(&selection);
+        move |s| export_csv(s, &items, &selection)
+    });
+
     // Clear Selection Button
     let clear_selection_button = {
         let selection = Arc::clone(&selection);
         let items = Arc::clone(&items);
+        let config = Arc::clone(&config);
         let selection_info = selection_info.clone();
         let cb_sink = siv.cb_sink().clone();
         
@@ -527,29 +529,78 @@ where
                 }
             }
             
-            // Aggiorna l'area dei log
-            s.call_on_name("log_scroll_view", |view: &mut ScrollView<TextView>| {
-                let current_text = view.get_inner().get_content().source().to_string();
-                let msg = "Selezione elementi pulita";
-                view.get_inner_mut().set_content(format!("{}\n{}", current_text, msg));
-                view.scroll_to_bottom();
-            });
-            
-            update_ui(&items, &selection, &selection_info, &cb_sink);
+            events::log_line(Level::Info, "Selezione elementi pulita");
+            toast::show(s, "Selezione pulita");
+
+            update_ui(&items, &config, &selection, &selection_info, &cb_sink);
         })
     };
 
-    // Area di log nella parte inferiore - CORREZIONE: Aggiunto ScrollView con nome
-    let log_text = TextView::new("Log operazioni:");
-    let log_scroll_view = ScrollView::new(log_text)
+    // Area di log nella parte inferiore, alimentata dagli eventi del core
+    let log_content = TextContent::new("Log operazioni:");
+    let log_scroll_view = ScrollView::new(TextView::new_with_content(log_content.clone()))
+        .scroll_strategy(ScrollStrategy::StickToBottom)
         .with_name("log_scroll_view")
         .fixed_height(5);  // Altezza fissa di 5 righe
 
+    // Avanzamento di download e stack
+    let download_text = TextContent::new("");
+    let download_counter = Counter::new(0);
+    let subscription = events::subscribe({
+        let download_text = download_text.clone();
+        let download_counter = download_counter.clone();
+        move |event: &events::Event| {
+            match event {
+                events::Event::OperationStarted { operation, target, .. } => {
+                    log_content.append(format!("\nAvvio {} di {}...", operation.label(), target));
+                },
+                events::Event::Finished { operation, target, error: None, .. } => {
+                    log_content.append(format!("\nCompletato: {} di {}", operation.label(), target));
+                },
+                events::Event::Finished { operation, target, error: Some(e), .. } => {
+                    log_content.append(format!("\nErrore durante {} di {}: {}", operation.label(), target, e));
+                },
+                events::Event::LogLine { level, message } => {
+                    match level {
+                        Level::Error | Level::Warn => log_content.append(format!("\n{}: {}", level, message)),
+                        _ => log_content.append(format!("\n{}", message)),
+                    }
+                },
+                events::Event::Progress(progress) => {
+                    download_text.set_content(format!("{}: {}", progress.label, progress.detail));
+                    download_counter.set(progress.percent.map_or(0, |p| p as usize));
+                },
+            }
+        }
+    });
+    // Dopo il ricaricamento dei cataloghi gli indici selezionati non indicano più gli stessi elementi
+    let reload_id = reload::register({
+        let items = Arc::clone(&items);
+        let config = Arc::clone(&config);
+        let selection = Arc::clone(&selection);
+        let selection_info = selection_info.clone();
+        let item_detail = item_detail.clone();
+        move |s| {
+            if let Ok(mut sel) = selection.lock() {
+                sel.clear();
+            }
+            item_detail.set_content("Seleziona un elemento per vedere i dettagli");
+            update_ui(&items, &config, &selection, &selection_info, &s.cb_sink().clone());
+        }
+    });
+    let download_view = LinearLayout::horizontal()
+        .child(ProgressBar::new()
+            .max(100)
+            .with_value(download_counter)
+            .fixed_width(20))
+        .child(DummyView.fixed_width(1))
+        .child(TextView::new_with_content(download_text));
+
     // NUOVO LAYOUT RISTRUTTURATO
     
     // 1. Contenitore principale diviso in due parti: lista e dettagli
     let main_container = LinearLayout::horizontal()
-        .child(Panel::new(select_view_with_events.scrollable().min_size((40, 15)))
+        .child(Panel::new(select_view_with_events.scrollable().with_name(ITEM_LIST_SCROLL).min_size((40, 15)))
             .title("Elementi")
             .full_width())
         .child(DummyView.fixed_width(1))
@@ -557,20 +608,32 @@ where
             .title("Dettagli")
             .full_width());
     
-    // 2. Barra inferiore con info sulla selezione
+    // 2. Barra inferiore con la legenda dei marcatori e le info sulla selezione
+    let mut legend = selection::legend();
+    legend.append_plain("   v: vista compatta/dettagliata   f: solo falliti   o: ordinamento");
     let selection_bar = LinearLayout::vertical()
+        .child(TextView::new(legend).h_align(HAlign::Center))
         .child(selection_info_view);
     
     // 3. Barra dei pulsanti posizionata orizzontalmente
     let buttons_bar = LinearLayout::horizontal()
-        .child(install_all_button)
+        .child(install_all_button.with_enabled(!read_only))
+        .child(DummyView.fixed_width(1))
+        .child(install_button.with_enabled(!read_only))
+        .child(DummyView.fixed_width(1))
+        .child(reinstall_button.with_enabled(!read_only))
         .child(DummyView.fixed_width(1))
-        .child(install_button)
+        .child(migrate_button.with_enabled(!read_only))
+        .child(DummyView.fixed_width(1))
+        .child(retry_failed_button.with_enabled(!read_only))
+        .child(DummyView.fixed_width(1))
+        .child(export_button)
         .child(DummyView.fixed_width(1))
         .child(clear_selection_button);
     
     // 4. Layout principale con allineamento verticale - AGGIUNTO PANNELLO LOG
     let layout = LinearLayout::vertical()
+        .child(reboot_banner::banner_view())
         .child(main_container)
         .child(DummyView.fixed_height(1))
         .child(selection_bar)
@@ -579,18 +642,897 @@ where
             .title("Azioni"))
         .child(DummyView.fixed_height(1))
         .child(Panel::new(log_scroll_view)
-            .title("Log operazioni"));
+            .title("Log operazioni"))
+        .child(download_view)
+        .child(toast::toast_view())
+        .child(status_bar::status_bar_view());
 
     // Dialog esterno con dimensioni fisse
+    let title = if read_only {
+        format!("{} [sola lettura]", view_title)
+    } else {
+        view_title.to_string()
+    };
     siv.add_layer(Dialog::around(layout)
-        .title(view_title)
+        .title(title)
         .button("Log", |s| {
             log_view::show_recent_logs_popup(s);
         })
-        .button("Back", |s| {
+        .button("Back", move |s| {
+            events::unsubscribe(subscription);
+            reload::unregister(reload_id);
+            remember_scroll(s, &list_name);
+            session::set_screen(None);
             s.pop_layer();
         })
         .full_screen());
 
+    // La posizione di scorrimento può essere applicata solo dopo il primo layout della lista
+    if saved_state.scroll > 0 {
+        let scroll = saved_state.scroll;
+        let _ = siv.cb_sink().send(Box::new(move |s: &mut Cursive| {
+            s.call_on_name(ITEM_LIST_SCROLL, |view: &mut ItemListScroll| {
+                view.set_offset((0, scroll));
+            });
+        }));
+    }
+
     Ok(())
 }
+
+/// Salva nello stato della sessione la posizione di scorrimento della lista
+fn remember_scroll(s: &mut Cursive, list_name: &str) {
+    if let Some(top) = s.call_on_name(ITEM_LIST_SCROLL, |view: &mut ItemListScroll| view.content_viewport().top()) {
+        session::update_list(list_name, |state| state.scroll = top);
+    }
+}
+
+/// Restituisce le righe mostrate nella lista, filtrate e ordinate
+fn visible_rows<E: SelectableItem>(items: &[E], filter: ListFilter, sort: SortOrder) -> Vec<(ListRow, usize)> {
+    let mut rows: Vec<(ListRow, usize)> = items.iter().enumerate()
+        .map(|(idx, item)| (item.list_row(), idx))
+        .filter(|(row, _)| filter.matches(row.status))
+        .collect();
+    sort.apply(&mut rows);
+    rows
+}
+
+/// Installa gli elementi indicati mostrando l'avanzamento e il riepilogo finale
+///
+/// L'installazione viene eseguita come job in background: la finestra di avanzamento può
+/// essere chiusa e il job seguito dalla schermata dei job. In caso di errori la finestra di
+/// riepilogo permette di riprovare i soli elementi falliti.
+fn install_items<T, E>(
+    s: &mut Cursive,
+    items: &Arc<Mutex<Vec<E>>>,
+    config: &Arc<Mutex<Config>>,
+    selection: &SharedSelection<T>,
+    selection_info: &TextContent,
+    selected_indices: &[usize],
+)
+where
+    T: 'static + Send + Sync,
+    E: SelectableItem + Executable<E> + Clone + 'static + Send + Sync,
+{
+    let cb_sink = s.cb_sink().clone();
+    let total = selected_indices.len();
+    let layer_name = format!("job_progress_{}", PROGRESS_LAYERS.fetch_add(1, Ordering::SeqCst));
+
+    let progress_text = TextContent::new("In attesa dell'avvio dell'installazione...");
+    s.add_layer(Dialog::around(TextView::new_with_content(progress_text.clone()))
+        .title("Installazione in corso")
+        .button("In background", |s| {
+            s.pop_layer();
+            toast::show(s, "L'installazione prosegue in background (F2: Job)");
+        })
+        .with_name(layer_name.clone())
+        .fixed_width(60)
+        .fixed_height(10));
+
+    let items = Arc::clone(items);
+    let config = Arc::clone(config);
+    let selection = Arc::clone(selection);
+    let selection_info = selection_info.clone();
+    let selected_indices = selected_indices.to_vec();
+
+    jobs::submit(format!("Installazione di {} elementi", total), move || {
+        events::log_line(Level::Info, "Avvio installazione elementi selezionati...");
+
+        let mut success_count = 0;
+        let mut error_messages = Vec::new();
+        let mut failed_indices = Vec::new();
+
+        for (i, idx) in selected_indices.iter().enumerate() {
+            // L'elemento viene installato su una copia, senza tenere bloccata la lista
+            // che l'interfaccia continua a mostrare
+            let mut item = match items.lock() {
+                Ok(items_guard) => match items_guard.get(*idx) {
+                    Some(item) => item.clone(),
+                    None => {
+                        error_messages.push(format!("Elemento con indice {} non trovato", idx));
+                        continue;
+                    }
+                },
+                Err(e) => {
+                    error_messages.push(format!("Errore nel blocco degli elementi: {}", e));
+                    failed_indices.push(*idx);
+                    continue;
+                }
+            };
+
+            if !item.can_install() {
+                continue;
+            }
+
+            progress_text.set_content(format!("Installazione dell'elemento {} ({}/{})...",
+                                            item, i+1, total));
+
+            let config_snapshot = match config.lock() {
+                Ok(config_guard) => config_guard.clone(),
+                Err(e) => {
+                    error_messages.push(format!("Errore nel blocco della configurazione: {}", e));
+                    failed_indices.push(*idx);
+                    continue;
+                }
+            };
+
+            let result = item.install(&config_snapshot);
+            let item_name = item.to_string();
+            let report = item.last_report();
+            if let Ok(mut items_guard) = items.lock() {
+                if let Some(slot) = items_guard.get_mut(*idx) {
+                    *slot = item;
+                }
+            }
+
+            match result {
+                Ok(_) => {
+                    success_count += 1;
+                },
+                Err(e) => {
+                    // Per gli stack si riporta l'esito di ogni task
+                    let table = report.map(|report| format!("\n{}", report.to_table())).unwrap_or_default();
+                    error_messages.push(format!("Errore nell'operazione su {}: {}{}", item_name, e, table));
+                    failed_indices.push(*idx);
+                }
+            }
+        }
+
+        let outcome = if error_messages.is_empty() {
+            events::log_line(Level::Info, "Installazione completata con successo per tutti gli elementi");
+            Ok(())
+        } else {
+            events::log_line(Level::Warn, format!("Installazione completata con errori. Successi: {}/{}",
+                                               success_count, total));
+            Err(anyhow!("Installazione completata con errori. Successi: {}/{}", success_count, total))
+        };
+
+        // Il riepilogo viene mostrato dal thread dell'interfaccia, al posto della finestra
+        // di avanzamento se è ancora aperta
+        let _ = cb_sink.send(Box::new(move |s: &mut Cursive| {
+            if let Some(position) = s.screen_mut().find_layer_from_name(&layer_name) {
+                s.screen_mut().remove_layer(position);
+            }
+
+            if error_messages.is_empty() {
+                toast::show(s, format!("Tutti i {} elementi sono stati elaborati con successo", success_count));
+            } else {
+                let mut result_message = format!("Operazioni completate con successo: {}/{}\n\nErrori:\n",
+                                              success_count, total);
+                for error in &error_messages {
+                    result_message.push_str(&format!("- {}\n", error));
+                }
+
+                // "Riprova" ripete l'installazione dei soli elementi falliti
+                let retry = if failed_indices.is_empty() {
+                    None
+                } else {
+                    let items = Arc::clone(&items);
+                    let config = Arc::clone(&config);
+                    let selection = Arc::clone(&selection);
+                    let selection_info = selection_info.clone();
+                    Some(move |s: &mut Cursive| {
+                        install_items(s, &items, &config, &selection, &selection_info, &failed_indices);
+                    })
+                };
+
+                s.add_layer(error_dialog::create_error_dialog("Risultato Installazione", "", &result_message, retry)
+                    .fixed_width(70)
+                    .fixed_height(15));
+            }
+
+            let cb_sink = s.cb_sink().clone();
+            update_ui(&items, &config, &selection, &selection_info, &cb_sink);
+        }));
+
+        outcome
+    });
+}
+
+/// Chiede il percorso del file ed esporta in CSV gli elementi mostrati nella lista
+fn export_csv<T, E>(s: &mut Cursive, items: &Arc<Mutex<Vec<E>>>, selection: &SharedSelection<T>)
+where
+    T: 'static + Send + Sync,
+    E: SelectableItem + Clone + 'static + Send + Sync,
+{
+    let default_path = format!("galatea-{}.csv", Local::now().format("%Y%m%d-%H%M"));
+    let items = Arc::clone(items);
+    let selection = Arc::clone(selection);
+
+    s.add_layer(Dialog::around(LinearLayout::vertical()
+            .child(TextView::new("File in cui esportare gli elementi mostrati:"))
+            .child(DummyView.fixed_height(1))
+            .child(EditView::new()
+                .content(default_path)
+                .with_name("csv_path")
+                .fixed_width(50)))
+        .title("Esporta CSV")
+        .button("Annulla", |s| { s.pop_layer(); })
+        .button("Esporta", move |s| {
+            let path = s.call_on_name("csv_path", |view: &mut EditView| view.get_content().trim().to_string())
+                .unwrap_or_default();
+            if path.is_empty() {
+                s.add_layer(Dialog::info("Il percorso non può essere vuoto")
+                             .fixed_width(50)
+                             .fixed_height(7));
+                return;
+            }
+
+            match write_csv(&items, &selection, Path::new(&path)) {
+                Ok(count) => {
+                    s.pop_layer();
+                    events::log_line(Level::Info, format!("Esportati {} elementi in {}", count, path));
+                    toast::show(s, format!("Esportati {} elementi in {}", count, path));
+                },
+                Err(e) => {
+                    s.add_layer(Dialog::info(format!("Errore durante l'esportazione: {}", e))
+                                 .fixed_width(60)
+                                 .fixed_height(10));
+                },
+            }
+        }));
+}
+
+/// Scrive in CSV gli elementi che passano il filtro della lista
+///
+/// # Returns
+///
+/// Il numero di elementi esportati
+fn write_csv<T, E>(items: &Arc<Mutex<Vec<E>>>, selection: &SharedSelection<T>, path: &Path) -> Result<usize>
+where
+    E: SelectableItem,
+{
+    let filter = selection.lock().map(|sel| sel.filter()).unwrap_or_default();
+    let items_guard = items.lock().map_err(|_| anyhow!("Failed to lock items mutex"))?;
+
+    let mut csv = utils::csv_line(&E::csv_header());
+    let mut count = 0;
+    for item in items_guard.iter().filter(|item| filter.matches(item.status())) {
+        csv.push_str(&utils::csv_line(&item.csv_row()));
+        count += 1;
+    }
+
+    fs::write(path, csv).context(format!("Failed to write CSV file {:?}", path))?;
+    Ok(count)
+}
+
+/// Ripete l'ultima operazione fallita sugli elementi indicati e mostra il riepilogo
+fn retry_items<T, E>(
+    s: &mut Cursive,
+    items: &Arc<Mutex<Vec<E>>>,
+    config: &Arc<Mutex<Config>>,
+    selection: &SharedSelection<T>,
+    selection_info: &TextContent,
+    failed_indices: &[usize],
+)
+where
+    T: 'static + Send + Sync,
+    E: SelectableItem + Executable<E> + Clone + 'static + Send + Sync,
+{
+    let cb_sink = s.cb_sink().clone();
+
+    events::log_line(Level::Info, format!("Nuovo tentativo per {} elementi falliti...", failed_indices.len()));
+
+    let mut success_count = 0;
+    let mut error_messages = Vec::new();
+    let mut still_failed = Vec::new();
+
+    for idx in failed_indices {
+        let result = {
+            let (Ok(mut items_guard), Ok(config_guard)) = (items.lock(), config.lock()) else {
+                error_messages.push("Errore nel blocco degli elementi o della configurazione".to_string());
+                break;
+            };
+            let Some(item) = items_guard.get_mut(*idx) else {
+                continue;
+            };
+            (item.to_string(), item.retry(&config_guard))
+        };
+
+        match result {
+            (_, Ok(_)) => success_count += 1,
+            (item_name, Err(e)) => {
+                error_messages.push(format!("- {}: {}", item_name, e));
+                still_failed.push(*idx);
+            },
+        }
+    }
+
+    if error_messages.is_empty() {
+        toast::show(s, format!("Tutti i {} elementi sono stati ripristinati", success_count));
+        events::log_line(Level::Info, "Nuovo tentativo completato con successo per tutti gli elementi");
+    } else {
+        let result_message = format!("Operazioni riuscite: {}/{}\n\nErrori:\n{}\n",
+                                     success_count, failed_indices.len(), error_messages.join("\n"));
+
+        // "Riprova" ripete solo gli elementi ancora falliti
+        let retry = if still_failed.is_empty() {
+            None
+        } else {
+            let items = Arc::clone(items);
+            let config = Arc::clone(config);
+            let selection = Arc::clone(selection);
+            let selection_info = selection_info.clone();
+            Some(move |s: &mut Cursive| {
+                retry_items(s, &items, &config, &selection, &selection_info, &still_failed);
+            })
+        };
+
+        s.add_layer(error_dialog::create_error_dialog("Risultato Nuovo Tentativo", "", &result_message, retry)
+            .fixed_width(70)
+            .fixed_height(15));
+        events::log_line(Level::Warn, format!("Nuovo tentativo completato con errori. Successi: {}/{}",
+                                              success_count, failed_indices.len()));
+    }
+
+    update_ui(items, config, selection, selection_info, &cb_sink);
+}
+
+/// Richiede i valori delle variabili dell'elemento (se presenti) e poi lo installa
+fn prompt_and_install_item<T, E>(
+    s: &mut Cursive,
+    items: &Arc<Mutex<Vec<E>>>,
+    config: &Arc<Mutex<Config>>,
+    selection: &SharedSelection<T>,
+    selection_info: &TextContent,
+    idx: usize,
+)
+where
+    T: 'static + Send + Sync,
+    E: SelectableItem + Executable<E> + Clone + 'static + Send + Sync,
+{
+    let variables = match (items.lock(), config.lock()) {
+        (Ok(items_guard), Ok(config_guard)) => items_guard.get(idx)
+            .map(|item| item.prompt_variables(&config_guard))
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    };
+
+    if variables.is_empty() {
+        install_item(s, items, config, selection, selection_info, idx);
+        return;
+    }
+
+    // Un campo per ogni variabile, precompilato con l'ultimo valore o con il predefinito
+    let mut form = LinearLayout::vertical();
+    for (var, value) in &variables {
+        form.add_child(TextView::new(var.prompt_text()));
+        let mut field = EditView::new().content(value.clone().unwrap_or_default());
+        field.set_secret(var.secret);
+        form.add_child(field
+            .with_name(format!("stack_var_{}", var.name))
+            .fixed_width(50));
+        form.add_child(DummyView.fixed_height(1));
+    }
+
+    let items = Arc::clone(items);
+    let config = Arc::clone(config);
+    let selection = Arc::clone(selection);
+    let selection_info = selection_info.clone();
+
+    s.add_layer(Dialog::around(form.scrollable())
+        .title("Parametri dell'installazione")
+        .button("Annulla", |s| { s.pop_layer(); })
+        .button("Installa", move |s| {
+            let mut values = HashMap::new();
+            for (var, _) in &variables {
+                let value = s.call_on_name(&format!("stack_var_{}", var.name), |view: &mut EditView| {
+                    view.get_content().trim().to_string()
+                }).unwrap_or_default();
+
+                // Un campo vuoto usa il valore predefinito, se presente
+                let value = match (value.is_empty(), &var.default) {
+                    (false, _) => value,
+                    (true, Some(default)) => default.clone(),
+                    (true, None) => {
+                        s.add_layer(Dialog::info(format!("Il valore di '{}' è obbligatorio", var.prompt_text()))
+                                     .fixed_width(50)
+                                     .fixed_height(7));
+                        return;
+                    }
+                };
+                values.insert(var.name.clone(), value);
+            }
+
+            let saved = match (items.lock(), config.lock()) {
+                (Ok(mut items_guard), Ok(config_guard)) => match items_guard.get_mut(idx) {
+                    Some(item) => item.set_variable_values(&config_guard, &values),
+                    None => Err(anyhow!("Item not found")),
+                },
+                _ => Err(anyhow!("Failed to lock items or configuration")),
+            };
+
+            s.pop_layer();
+
+            match saved {
+                Ok(_) => install_item(s, &items, &config, &selection, &selection_info, idx),
+                Err(e) => {
+                    s.add_layer(Dialog::info(format!("Errore nel salvataggio dei parametri: {}", e))
+                                 .fixed_width(50)
+                                 .fixed_height(7));
+                }
+            }
+        })
+        .fixed_width(60));
+}
+
+/// Installa un singolo elemento mostrando l'esito
+///
+/// In caso di errore la finestra permette di riprovare l'installazione e, per gli
+/// stack con snapshot, di ripristinare il sistema.
+fn install_item<T, E>(
+    s: &mut Cursive,
+    items: &Arc<Mutex<Vec<E>>>,
+    config: &Arc<Mutex<Config>>,
+    selection: &SharedSelection<T>,
+    selection_info: &TextContent,
+    idx: usize,
+)
+where
+    T: 'static + Send + Sync,
+    E: SelectableItem + Executable<E> + Clone + 'static + Send + Sync,
+{
+    let cb_sink = s.cb_sink().clone();
+
+    // Ottieni il nome dell'elemento per il log
+    let item_name = {
+        if let Ok(items_guard) = items.lock() {
+            if let Some(item) = items_guard.get(idx) {
+                format!("{}", item)
+            } else {
+                "elemento sconosciuto".to_string()
+            }
+        } else {
+            "elemento sconosciuto".to_string()
+        }
+    };
+    
+
+    let item_result = {
+        let mut items_guard = match items.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                s.add_layer(Dialog::info(format!("Errore nel blocco degli elementi: {}", e))
+                             .fixed_width(50)
+                             .fixed_height(7));
+                return;
+            }
+        };
+
+        let item = match items_guard.get_mut(idx) {
+            Some(item) => item,
+            None => {
+                s.add_layer(Dialog::info("Elemento non trovato")
+                             .fixed_width(50)
+                             .fixed_height(7));
+                return;
+            }
+        };
+
+        if !item.can_install() {
+            s.add_layer(Dialog::info("L'elemento non può essere installato")
+                         .fixed_width(50)
+                         .fixed_height(7));
+            return;
+        }
+
+        let config_guard = match config.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                s.add_layer(Dialog::info(format!("Errore nel blocco della configurazione: {}", e))
+                             .fixed_width(50)
+                             .fixed_height(7));
+                return;
+            }
+        };
+
+        let result = item.install(&config_guard);
+        let restore_point = if result.is_err() { item.restore_point(&config_guard) } else { None };
+        (result, restore_point, item.last_report())
+    };
+
+    let (item_result, restore_point, report) = item_result;
+    let report_table = report.map(|report| format!("\n\n{}", report.to_table())).unwrap_or_default();
+
+    match item_result {
+        Ok(_) => {
+            // Per gli stack l'esito dei singoli task resta in una finestra, da leggere con calma
+            if report_table.is_empty() {
+                toast::show(s, "Installazione completata con successo");
+            } else {
+                s.add_layer(Dialog::info(format!("Operazione installazione completata con successo{}", report_table))
+                             .fixed_width(90)
+                             .max_height(20));
+            }
+            
+            
+            update_ui(items, config, selection, selection_info, &cb_sink);
+            log_view::show_recent_logs_popup(s);
+        },
+        Err(e) => {
+            let message = match &restore_point {
+                // Lo stack ha creato uno snapshot: segnala la possibilità di ripristino
+                Some(snap) => format!(
+                    "Errore durante l'operazione installazione: {}{}\n\nÈ disponibile lo snapshot {} ({}) creato il {}.",
+                    e, report_table, snap.id, snap.backend.to_str(), snap.created_at),
+                None => format!("Errore durante l'operazione installazione: {}{}", e, report_table),
+            };
+
+            let retry = {
+                let items = Arc::clone(items);
+                let config = Arc::clone(config);
+                let selection = Arc::clone(selection);
+                let selection_info = selection_info.clone();
+                move |s: &mut Cursive| {
+                    install_item(s, &items, &config, &selection, &selection_info, idx);
+                }
+            };
+
+            let mut dialog = error_dialog::create_operation_error_dialog("Installazione fallita", &item_name, &message, &e, retry);
+
+            if let Some(snap) = restore_point {
+                let config = Arc::clone(config);
+                dialog.add_button("Ripristina snapshot", move |s| {
+                    s.pop_layer();
+
+                    let message = format!("Ripristinare lo snapshot {} ({})?\nLe modifiche successive andranno perse.",
+                                          snap.id, snap.backend.to_str());
+                    let snap = snap.clone();
+                    let config_for_action = Arc::clone(&config);
+                    confirm_action(s, &config, true, "Conferma Ripristino", &message, move |s| {
+                        restore_snapshot(s, &config_for_action, &snap);
+                    });
+                });
+            }
+
+            s.add_layer(dialog
+                .fixed_width(60)
+                .fixed_height(12));
+            
+        }
+    }
+}
+
+/// Ripristina lo snapshot creato prima di un'installazione fallita
+fn restore_snapshot(s: &mut Cursive, config: &Arc<Mutex<Config>>, snap: &Snapshot) {
+    match snapshot::restore_snapshot(snap) {
+        Ok(requires_reboot) => {
+            if let Ok(config_guard) = config.lock() {
+                let _ = snapshot::clear_restore_point(&config_guard, &snap.label);
+            }
+            let msg = if requires_reboot {
+                "Snapshot ripristinato. Riavvia il sistema per completare il ripristino."
+            } else {
+                "Snapshot ripristinato con successo."
+            };
+            s.add_layer(Dialog::info(msg)
+                         .fixed_width(50)
+                         .fixed_height(7));
+        },
+        Err(e) => {
+            s.add_layer(Dialog::info(format!("Errore durante il ripristino dello snapshot: {}", e))
+                         .fixed_width(50)
+                         .fixed_height(7));
+        }
+    }
+}
+
+/// Esegue un'azione chiedendo conferma se richiesto dalla politica configurata
+///
+/// # Arguments
+///
+/// * `s` - L'istanza di Cursive
+/// * `config` - La configurazione condivisa
+/// * `destructive` - Indica se l'azione è distruttiva
+/// * `title` - Il titolo della finestra di conferma
+/// * `message` - La domanda da mostrare
+/// * `action` - L'azione da eseguire
+fn confirm_action<F>(s: &mut Cursive, config: &Arc<Mutex<Config>>, destructive: bool, title: &str, message: &str, action: F)
+where
+    F: Fn(&mut Cursive) + Send + Sync + 'static,
+{
+    let needs_confirmation = config.lock()
+        .map(|c| c.confirm_actions.requires_confirmation(destructive))
+        .unwrap_or(true);
+
+    if !needs_confirmation {
+        action(s);
+        return;
+    }
+
+    s.add_layer(Dialog::around(TextView::new(message))
+        .title(title)
+        .button("No", |s| { s.pop_layer(); })
+        .button("Sì", move |s| {
+            s.pop_layer();
+            action(s);
+        })
+        .fixed_width(60)
+        .fixed_height(10));
+}
+
+/// Reinstalla un singolo elemento mostrando l'esito
+fn reinstall_item<T, E>(
+    s: &mut Cursive,
+    items: &Arc<Mutex<Vec<E>>>,
+    config: &Arc<Mutex<Config>>,
+    selection: &SharedSelection<T>,
+    selection_info: &TextContent,
+    idx: usize,
+    uninstall_first: bool,
+)
+where
+    T: 'static + Send + Sync,
+    E: SelectableItem + Executable<E> + Clone + 'static + Send + Sync,
+{
+    let cb_sink = s.cb_sink().clone();
+
+    let (item_name, item_result, report) = {
+        let mut items_guard = match items.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                s.add_layer(Dialog::info(format!("Errore nel blocco degli elementi: {}", e))
+                             .fixed_width(50)
+                             .fixed_height(7));
+                return;
+            }
+        };
+
+        let item = match items_guard.get_mut(idx) {
+            Some(item) => item,
+            None => {
+                s.add_layer(Dialog::info("Elemento non trovato")
+                             .fixed_width(50)
+                             .fixed_height(7));
+                return;
+            }
+        };
+
+        let config_guard = match config.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                s.add_layer(Dialog::info(format!("Errore nel blocco della configurazione: {}", e))
+                             .fixed_width(50)
+                             .fixed_height(7));
+                return;
+            }
+        };
+
+        let result = item.reinstall(&config_guard, uninstall_first);
+        (item.to_string(), result, item.last_report())
+    };
+    let report_table = report.map(|report| format!("\n\n{}", report.to_table())).unwrap_or_default();
+
+    match item_result {
+        Ok(_) => {
+            if report_table.is_empty() {
+                toast::show(s, "Reinstallazione completata con successo");
+            } else {
+                s.add_layer(Dialog::info(format!("Reinstallazione completata con successo{}", report_table))
+                             .fixed_width(90)
+                             .max_height(20));
+            }
+
+        },
+        Err(e) => {
+            let retry = {
+                let items = Arc::clone(items);
+                let config = Arc::clone(config);
+                let selection = Arc::clone(selection);
+                let selection_info = selection_info.clone();
+                move |s: &mut Cursive| {
+                    reinstall_item(s, &items, &config, &selection, &selection_info, idx, uninstall_first);
+                }
+            };
+
+            let message = format!("Errore durante la reinstallazione: {}{}", e, report_table);
+            s.add_layer(error_dialog::create_operation_error_dialog("Reinstallazione fallita", &item_name, &message, &e, retry)
+                .fixed_width(60)
+                .fixed_height(12));
+
+        }
+    }
+
+    update_ui(items, config, selection, selection_info, &cb_sink);
+}
+
+/// Migra un elemento deprecato al suo sostituto mostrando l'esito
+fn migrate_item<T, E>(
+    s: &mut Cursive,
+    items: &Arc<Mutex<Vec<E>>>,
+    config: &Arc<Mutex<Config>>,
+    selection: &SharedSelection<T>,
+    selection_info: &TextContent,
+    idx: usize,
+)
+where
+    T: 'static + Send + Sync,
+    E: SelectableItem + Executable<E> + Clone + 'static + Send + Sync,
+{
+    let cb_sink = s.cb_sink().clone();
+
+    let (item_name, item_result) = {
+        let mut items_guard = match items.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                s.add_layer(Dialog::info(format!("Errore nel blocco degli elementi: {}", e))
+                             .fixed_width(50)
+                             .fixed_height(7));
+                return;
+            }
+        };
+
+        let item_name = match items_guard.get(idx) {
+            Some(item) => item.to_string(),
+            None => {
+                s.add_layer(Dialog::info("Elemento non trovato")
+                             .fixed_width(50)
+                             .fixed_height(7));
+                return;
+            }
+        };
+
+        let config_guard = match config.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                s.add_layer(Dialog::info(format!("Errore nel blocco della configurazione: {}", e))
+                             .fixed_width(50)
+                             .fixed_height(7));
+                return;
+            }
+        };
+
+        (item_name, E::migrate(&mut items_guard, idx, &config_guard))
+    };
+
+    match item_result {
+        Ok(_) => {
+            toast::show(s, "Migrazione completata con successo");
+        },
+        Err(e) => {
+            let retry = {
+                let items = Arc::clone(items);
+                let config = Arc::clone(config);
+                let selection = Arc::clone(selection);
+                let selection_info = selection_info.clone();
+                move |s: &mut Cursive| {
+                    migrate_item(s, &items, &config, &selection, &selection_info, idx);
+                }
+            };
+
+            let message = format!("Errore durante la migrazione: {}", e);
+            s.add_layer(error_dialog::create_error_dialog("Migrazione fallita", &item_name, &message, Some(retry))
+                .fixed_width(60)
+                .fixed_height(12));
+        }
+    }
+
+    update_ui(items, config, selection, selection_info, &cb_sink);
+}
+
+/// Aggiorna la lista sostituendo solo le righe cambiate
+///
+/// Con cataloghi molto grandi ricostruire la lista a ogni aggiornamento è lento e riporta
+/// il cursore in cima: la lista viene ricostruita solo se cambiano gli elementi mostrati,
+/// mantenendo comunque la posizione del cursore.
+fn refresh_rows(view: &mut SelectView<usize>, rows: &[(StyledString, usize)]) {
+    let same_items = view.len() == rows.len()
+        && view.iter().zip(rows).all(|((_, value), (_, idx))| value == idx);
+
+    if !same_items {
+        let cursor = view.selected_id();
+        let cursor_item = view.selection().map(|idx| *idx);
+        view.clear();
+        for (row, idx) in rows {
+            view.add_item(row.clone(), *idx);
+        }
+        // Il cursore resta sull'elemento su cui si trovava, anche se la lista è stata riordinata
+        let position = cursor_item.and_then(|item| rows.iter().position(|(_, idx)| *idx == item));
+        if let Some(position) = position {
+            view.set_selection(position);
+        } else if let Some(cursor) = cursor.filter(|_| !rows.is_empty()) {
+            view.set_selection(cursor.min(rows.len() - 1));
+        }
+        return;
+    }
+
+    for (position, (row, _)) in rows.iter().enumerate() {
+        if let Some((label, _)) = view.get_item_mut(position) {
+            if label != row {
+                *label = row.clone();
+            }
+        }
+    }
+}
+
+/// Aggiorna la lista degli elementi e le informazioni sulla selezione
+fn update_ui<T: Send + Sync + 'static, E: SelectableItem + Clone + 'static>(
+    items: &Arc<Mutex<Vec<E>>>,
+    config: &Arc<Mutex<Config>>,
+    selection: &SharedSelection<T>,
+    selection_info_content: &TextContent,
+    cb_sink: &cursive::CbSink,
+) {
+    let list_mode = config.lock().map(|c| c.list_mode).unwrap_or_default();
+    let (filter, sort) = selection.lock().map(|sel| (sel.filter(), sel.sort())).unwrap_or_default();
+
+    if let Ok(items_guard) = items.lock() {
+        let items_data = visible_rows(&items_guard, filter, sort);
+
+        let selection = Arc::clone(selection);
+        let selection_info_content = selection_info_content.clone();
+        
+        if let Err(_) = cb_sink.send(Box::new(move |s: &mut Cursive| {
+            let selection_count = {
+                if let Ok(sel) = selection.lock() {
+                    sel.count()
+                } else {
+                    0
+                }
+            };
+
+            let mut filter_info = match filter {
+                ListFilter::All => String::new(),
+                ListFilter::Failed => format!("Filtro: solo falliti ({}). ", items_data.len()),
+            };
+            if sort != SortOrder::Catalog {
+                filter_info.push_str(&format!("Ordinamento: {}. ", sort.label()));
+            }
+
+            if selection_count > 0 {
+                selection_info_content.set_content(format!("{}Premi 'Invio' per selezionare/deselezionare. {} elementi selezionati.", filter_info, selection_count));
+            } else {
+                selection_info_content.set_content(format!("{}Premi 'Invio' per selezionare/deselezionare. Nessun elemento selezionato.", filter_info));
+            }
+
+            s.call_on_name("item_list", |view: &mut SelectView<usize>| {
+                let sel = selection.lock().ok();
+                let rows: Vec<(StyledString, usize)> = items_data.iter()
+                    .map(|(row, idx)| {
+                        let selected = sel.as_ref().is_some_and(|sel| sel.is_selected(*idx));
+                        (row.render(list_mode, selected), *idx)
+                    })
+                    .collect();
+
+                refresh_rows(view, &rows);
+            });
+        })) {
+            eprintln!("Errore nell'aggiornamento della vista");
+        }
+    }
+}
+
+/// Porta il cursore della lista aperta sull'elemento indicato e ne mostra i dettagli
+///
+/// Se l'elemento non è tra quelli mostrati (ad esempio per il filtro dei falliti) la
+/// lista resta invariata.
+pub fn focus_item(s: &mut Cursive, idx: usize) {
+    let callback = s.call_on_name("item_list", |view: &mut SelectView<usize>| {
+        let row = view.iter().position(|(_, value)| *value == idx)?;
+        Some(view.set_selection(row))
+    }).flatten();
+
+    if let Some(callback) = callback {
+        callback(s);
+    }
+}
@@ -3,11 +3,20 @@
 use std::collections::HashSet;
 use std::sync::{Arc, Mutex};
 use std::fmt::Display;
+use cursive::theme::{BaseColor, Color};
+use cursive::utils::markup::StyledString;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::config::ListMode;
 
 /// Componente generico per gestire la selezione multipla di elementi in una lista
 pub struct MultiSelection<T> {
     /// Indici degli elementi selezionati
     selected_indices: HashSet<usize>,
+    /// Filtro applicato alla lista
+    filter: ListFilter,
+    /// Ordinamento della lista
+    sort: SortOrder,
     /// Tipo di marker per consentire la parametrizzazione
     _marker: std::marker::PhantomData<T>,
 }
@@ -17,6 +26,8 @@ impl<T> MultiSelection<T> {
     pub fn new() -> Self {
         MultiSelection {
             selected_indices: HashSet::new(),
+            filter: ListFilter::All,
+            sort: SortOrder::Catalog,
             _marker: std::marker::PhantomData,
         }
     }
@@ -45,6 +56,26 @@ impl<T> MultiSelection<T> {
         self.selected_indices.len()
     }
 
+    /// Restituisce il filtro applicato alla lista
+    pub fn filter(&self) -> ListFilter {
+        self.filter
+    }
+
+    /// Imposta il filtro applicato alla lista
+    pub fn set_filter(&mut self, filter: ListFilter) {
+        self.filter = filter;
+    }
+
+    /// Restituisce l'ordinamento della lista
+    pub fn sort(&self) -> SortOrder {
+        self.sort
+    }
+
+    /// Imposta l'ordinamento della lista
+    pub fn set_sort(&mut self, sort: SortOrder) {
+        self.sort = sort;
+    }
+
     /// Restituisce un vettore ordinato di indici selezionati
     pub fn get_selected_indices(&self) -> Vec<usize> {
         let mut indices: Vec<usize> = self.selected_indices.iter().cloned().collect();
@@ -53,14 +84,350 @@ impl<T> MultiSelection<T> {
     }
 }
 
+/// Marcatore degli elementi selezionati
+pub const SELECTED_MARKER: &str = "[*]";
+
+/// Stato di un elemento della lista, mostrato con un marcatore colorato
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemStatus {
+    /// Installato
+    Installed,
+    /// Installato solo in parte
+    Partial,
+    /// Ultima installazione fallita
+    Failed,
+    /// Installazione bloccata da un prerequisito mancante
+    Locked,
+    /// Non installato
+    NotInstalled,
+}
+
+impl ItemStatus {
+    /// Tutti gli stati, nell'ordine della legenda
+    pub const ALL: [ItemStatus; 5] = [
+        ItemStatus::Installed,
+        ItemStatus::Partial,
+        ItemStatus::Failed,
+        ItemStatus::Locked,
+        ItemStatus::NotInstalled,
+    ];
+
+    /// Restituisce il marcatore mostrato all'inizio della riga
+    pub fn marker(&self) -> &'static str {
+        match self {
+            ItemStatus::Installed => "[✓]",
+            ItemStatus::Partial => "[!]",
+            ItemStatus::Failed => "[✗]",
+            ItemStatus::Locked => "[#]",
+            ItemStatus::NotInstalled => "[ ]",
+        }
+    }
+
+    /// Restituisce la descrizione dello stato per la legenda
+    pub fn label(&self) -> &'static str {
+        match self {
+            ItemStatus::Installed => "installato",
+            ItemStatus::Partial => "parziale",
+            ItemStatus::Failed => "fallito",
+            ItemStatus::Locked => "bloccato",
+            ItemStatus::NotInstalled => "non installato",
+        }
+    }
+
+    /// Restituisce il colore del marcatore, se lo stato ne ha uno
+    pub fn color(&self) -> Option<Color> {
+        match self {
+            ItemStatus::Installed => Some(Color::Dark(BaseColor::Green)),
+            ItemStatus::Partial => Some(Color::Dark(BaseColor::Yellow)),
+            ItemStatus::Failed => Some(Color::Dark(BaseColor::Red)),
+            ItemStatus::Locked => Some(Color::Dark(BaseColor::Magenta)),
+            ItemStatus::NotInstalled => None,
+        }
+    }
+}
+
+/// Filtro degli elementi mostrati nella lista
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ListFilter {
+    /// Tutti gli elementi
+    #[default]
+    All,
+    /// Solo gli elementi la cui ultima operazione è fallita
+    Failed,
+}
+
+impl ListFilter {
+    /// Verifica se un elemento con lo stato indicato va mostrato
+    pub fn matches(&self, status: ItemStatus) -> bool {
+        match self {
+            ListFilter::All => true,
+            ListFilter::Failed => status == ItemStatus::Failed,
+        }
+    }
+
+    /// Restituisce l'altro filtro
+    pub fn toggle(&self) -> Self {
+        match self {
+            ListFilter::All => ListFilter::Failed,
+            ListFilter::Failed => ListFilter::All,
+        }
+    }
+
+    /// Restituisce il nome del filtro salvato nello stato della sessione
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            ListFilter::All => "all",
+            ListFilter::Failed => "failed",
+        }
+    }
+
+    /// Interpreta il nome di un filtro salvato nello stato della sessione
+    pub fn from_str(name: &str) -> Option<Self> {
+        match name {
+            "all" => Some(ListFilter::All),
+            "failed" => Some(ListFilter::Failed),
+            _ => None,
+        }
+    }
+}
+
+/// Ordinamento degli elementi della lista
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Nell'ordine dei cataloghi
+    #[default]
+    Catalog,
+    /// Per nome
+    Name,
+    /// Per stato, nell'ordine della legenda, e poi per nome
+    Status,
+}
+
+impl SortOrder {
+    /// Restituisce l'ordinamento successivo
+    pub fn next(&self) -> Self {
+        match self {
+            SortOrder::Catalog => SortOrder::Name,
+            SortOrder::Name => SortOrder::Status,
+            SortOrder::Status => SortOrder::Catalog,
+        }
+    }
+
+    /// Restituisce la descrizione dell'ordinamento
+    pub fn label(&self) -> &'static str {
+        match self {
+            SortOrder::Catalog => "catalogo",
+            SortOrder::Name => "nome",
+            SortOrder::Status => "stato",
+        }
+    }
+
+    /// Restituisce il nome dell'ordinamento salvato nello stato della sessione
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            SortOrder::Catalog => "catalog",
+            SortOrder::Name => "name",
+            SortOrder::Status => "status",
+        }
+    }
+
+    /// Interpreta il nome di un ordinamento salvato nello stato della sessione
+    pub fn from_str(name: &str) -> Option<Self> {
+        match name {
+            "catalog" => Some(SortOrder::Catalog),
+            "name" => Some(SortOrder::Name),
+            "status" => Some(SortOrder::Status),
+            _ => None,
+        }
+    }
+
+    /// Ordina le righe della lista
+    pub fn apply(&self, rows: &mut [(ListRow, usize)]) {
+        let status_rank = |row: &ListRow| ItemStatus::ALL.iter().position(|status| *status == row.status);
+        match self {
+            SortOrder::Catalog => rows.sort_by_key(|(_, idx)| *idx),
+            SortOrder::Name => rows.sort_by_cached_key(|(row, _)| row.name.to_lowercase()),
+            SortOrder::Status => rows.sort_by_cached_key(|(row, _)| (status_rank(row), row.name.to_lowercase())),
+        }
+    }
+}
+
+/// Larghezza della colonna del nome nella vista dettagliata
+const NAME_WIDTH: usize = 24;
+
+/// Riga di una lista selezionabile
+///
+/// Stato, nome e colonne restano separati fino alla visualizzazione: l'etichetta viene
+/// composta da [`ListRow::render`] con il marcatore di stato oppure di selezione, senza
+/// modificare etichette già composte. Le colonne sono allineate in base alla larghezza a
+/// schermo dei caratteri, così nomi accentati o con caratteri larghi non le spostano.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListRow {
+    /// Stato dell'elemento
+    pub status: ItemStatus,
+
+    /// Tipo dell'elemento, mostrato prima del nome nella vista dettagliata
+    pub kind: Option<String>,
+
+    /// Nome dell'elemento
+    pub name: String,
+
+    /// Colonne della vista dettagliata, con la rispettiva larghezza
+    pub columns: Vec<(String, usize)>,
+
+    /// Indicazioni mostrate dopo il nome (es. "deprecato")
+    pub flags: Vec<String>,
+
+    /// Descrizione, in fondo alla riga della vista dettagliata
+    pub description: String,
+}
+
+impl ListRow {
+    /// Crea una riga con lo stato e il nome indicati
+    pub fn new(status: ItemStatus, name: impl Into<String>) -> Self {
+        ListRow {
+            status,
+            kind: None,
+            name: name.into(),
+            columns: Vec::new(),
+            flags: Vec::new(),
+            description: String::new(),
+        }
+    }
+
+    /// Imposta il tipo dell'elemento
+    pub fn kind(mut self, kind: impl Into<String>) -> Self {
+        self.kind = Some(kind.into());
+        self
+    }
+
+    /// Aggiunge una colonna della vista dettagliata
+    pub fn column(mut self, text: impl Into<String>, width: usize) -> Self {
+        self.columns.push((text.into(), width));
+        self
+    }
+
+    /// Aggiunge un'indicazione mostrata dopo il nome
+    pub fn flag(mut self, flag: impl Into<String>) -> Self {
+        self.flags.push(flag.into());
+        self
+    }
+
+    /// Imposta la descrizione
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// Compone l'etichetta della riga
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - La modalità della lista (compatta o in colonne)
+    /// * `selected` - Se l'elemento è selezionato: il marcatore di selezione sostituisce quello di stato
+    pub fn render(&self, mode: ListMode, selected: bool) -> StyledString {
+        let mut label = if selected {
+            styled_marker(SELECTED_MARKER, Some(Color::Light(BaseColor::Cyan)))
+        } else {
+            styled_marker(self.status.marker(), self.status.color())
+        };
+        let flags: String = self.flags.iter().map(|flag| format!(" [{}]", flag)).collect();
+
+        if mode == ListMode::Compact {
+            label.append_plain(format!(" {}{}", self.name, flags));
+            return label;
+        }
+
+        let mut text = String::new();
+        if let Some(kind) = &self.kind {
+            text.push_str(&format!(" [{}]", kind));
+        }
+        text.push(' ');
+        text.push_str(&fit(&self.name, NAME_WIDTH));
+        for (column, width) in &self.columns {
+            text.push(' ');
+            text.push_str(&fit(column, *width));
+        }
+        text.push_str(&flags);
+        text.push_str(" - ");
+        text.push_str(&self.description);
+
+        label.append_plain(text);
+        label
+    }
+}
+
+/// Adatta un testo alla larghezza a schermo indicata
+///
+/// Il testo più corto viene completato con spazi, quello più lungo troncato con "…" senza
+/// spezzare i caratteri che occupano due colonne.
+pub fn fit(text: &str, width: usize) -> String {
+    let text_width = text.width();
+    if text_width <= width {
+        return format!("{}{}", text, " ".repeat(width - text_width));
+    }
+    if width == 0 {
+        return String::new();
+    }
+
+    // Una colonna resta riservata al carattere di troncamento
+    let mut fitted = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let char_width = c.width().unwrap_or(0);
+        if used + char_width > width - 1 {
+            break;
+        }
+        fitted.push(c);
+        used += char_width;
+    }
+    fitted.push('…');
+    fitted.push_str(&" ".repeat(width - 1 - used));
+    fitted
+}
+
+/// Colora un marcatore, se ha un colore
+fn styled_marker(marker: &str, color: Option<Color>) -> StyledString {
+    match color {
+        Some(color) => StyledString::styled(marker, color),
+        None => StyledString::plain(marker),
+    }
+}
+
+/// Costruisce la legenda dei marcatori di stato
+pub fn legend() -> StyledString {
+    let mut legend = StyledString::new();
+    for status in ItemStatus::ALL {
+        legend.append(styled_marker(status.marker(), status.color()));
+        legend.append_plain(format!(" {}   ", status.label()));
+    }
+    legend.append(styled_marker(SELECTED_MARKER, Some(Color::Light(BaseColor::Cyan))));
+    legend.append_plain(" selezionato");
+    legend
+}
+
 /// Trait per elementi che possono essere visualizzati in una lista selezionabile
 pub trait SelectableItem: Display {
     /// Determina lo stato dell'elemento per visualizzazione
-    fn get_status_marker(&self) -> &'static str;
+    fn status(&self) -> ItemStatus;
+
+    /// Restituisce il marcatore dello stato dell'elemento
+    fn get_status_marker(&self) -> &'static str {
+        self.status().marker()
+    }
     
-    /// Formatta l'elemento per la visualizzazione nella lista
-    fn format_for_list(&self) -> String;
+    /// Restituisce il riferimento qualificato dell'elemento (`sorgente:nome`)
+    fn item_ref(&self) -> String;
+
+    /// Restituisce la riga dell'elemento nella lista
+    fn list_row(&self) -> ListRow;
     
+    /// Restituisce l'intestazione delle colonne dell'esportazione CSV
+    fn csv_header() -> Vec<&'static str> where Self: Sized;
+
+    /// Restituisce i valori delle colonne dell'esportazione CSV
+    fn csv_row(&self) -> Vec<String>;
+
     /// Formatta l'elemento per la visualizzazione dettagliata
     fn format_details(&self) -> String;
     
@@ -1,36 +1,74 @@
 // File: src/ui/components/stack_impl.rs
 
-use crate::stack::Stack;
+use crate::stack::{Stack, StackReport, StackVariable};
 use crate::task::Task;
 use crate::config::Config;
-use crate::ui::components::selection::SelectableItem;
+use crate::snapshot::{self, Snapshot};
+use crate::ui::components::selection::{ItemStatus, ListRow, SelectableItem};
 use crate::ui::components::selectable_view::Executable;
 use anyhow::Result;
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
+impl Stack {
+    /// Compone la riga della lista con lo stato indicato
+    fn row(&self, status: ItemStatus) -> ListRow {
+        ListRow::new(status, self.name.as_str())
+            .column(self.source.as_str(), 12)
+            .column(format!("{} task", self.task_names.len()), 8)
+            .column(if self.tags.is_empty() { "-".to_string() } else { self.tags.join(",") }, 20)
+            .description(self.description.as_str())
+    }
+}
+
 /// Implementazione del trait SelectableItem per gli Stack
 impl SelectableItem for Stack {
-    /// Restituisce un marcatore di stato per gli stack
-    fn get_status_marker(&self) -> &'static str {
+    /// Restituisce lo stato dello stack
+    fn status(&self) -> ItemStatus {
         if self.fully_installed {
-            "[✓]"
+            ItemStatus::Installed
         } else if self.partially_installed {
-            "[!]"
+            ItemStatus::Partial
         } else {
-            "[ ]"
+            ItemStatus::NotInstalled
         }
     }
     
-    /// Formatta lo stack per la visualizzazione nella lista
-    fn format_for_list(&self) -> String {
-        let status = self.get_status_marker();
-        format!("{} {} - {}", status, self.name, self.description)
+    /// Restituisce il riferimento qualificato dello stack
+    fn item_ref(&self) -> String {
+        self.qualified_name()
+    }
+
+    /// Restituisce la riga dello stack nella lista
+    fn list_row(&self) -> ListRow {
+        self.row(self.status())
     }
     
+    /// Colonne dell'esportazione CSV degli stack
+    fn csv_header() -> Vec<&'static str> {
+        vec!["nome", "sorgente", "stato", "task", "tag", "descrizione"]
+    }
+
+    /// Valori dell'esportazione CSV dello stack
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.name.clone(),
+            self.source.clone(),
+            self.status().label().to_string(),
+            self.task_names.join(" "),
+            self.tags.join(" "),
+            self.description.clone(),
+        ]
+    }
+
     /// Formatta i dettagli dello stack
     fn format_details(&self) -> String {
         let mut details = format!("Nome: {}\n", self.name);
+        details.push_str(&format!("Sorgente: {} ({})\n", self.source, self.qualified_name()));
         details.push_str(&format!("Descrizione: {}\n", self.description));
+        if let Some(base) = &self.extends {
+            details.push_str(&format!("Estende: {}\n", base));
+        }
         details.push_str(&format!("Stato: {}\n",
                                  if self.fully_installed {
                                      "Completamente installato"
@@ -47,10 +85,46 @@ impl SelectableItem for Stack {
         details.push_str(&format!("Richiede riavvio: {}\n", 
                                  if self.requires_reboot { "Sì" } else { "No" }));
 
+        if self.snapshot {
+            details.push_str("Snapshot prima dell'installazione: Sì\n");
+        }
+
+        if self.maintenance {
+            details.push_str("Fuori dal bilanciatore durante l'installazione: Sì\n");
+        }
+
+        for command in &self.pre_install {
+            details.push_str(&format!("Prima dell'installazione: {}\n", command));
+        }
+        for command in &self.post_install {
+            details.push_str(&format!("Dopo l'installazione: {}\n", command));
+        }
+
+        if !self.variables.is_empty() {
+            let names: Vec<&str> = self.variables.iter().map(|v| v.name.as_str()).collect();
+            details.push_str(&format!("Variabili: {}\n", names.join(", ")));
+        }
+
+        if let Some(channel) = &self.channel {
+            details.push_str(&format!("Canale: {}\n", channel.to_str()));
+        }
+
+        if let Some(schedule) = &self.auto_remediate {
+            details.push_str(&format!("Ripristino automatico: {}\n", schedule));
+        }
+
+        if self.parallel > 1 {
+            details.push_str(&format!("Installazione parallela: fino a {} task alla volta\n", self.parallel));
+        }
+
         // Aggiungi l'elenco dei task inclusi
         details.push_str("\nTask inclusi:\n");
         for task_name in &self.task_names {
-            details.push_str(&format!("  - {}\n", task_name));
+            let canary = if self.is_canary(task_name) { " [canary]" } else { "" };
+            match self.pins.get(task_name) {
+                Some(version) => details.push_str(&format!("  - {} (versione {}){}\n", task_name, version, canary)),
+                None => details.push_str(&format!("  - {}{}\n", task_name, canary)),
+            }
         }
         
         details
@@ -85,6 +159,11 @@ impl Stack {
         self.install(config, tasks)
     }
     
+    /// Implementazione della reinstallazione che accetta tasks come parametro
+    pub fn reinstall_with_tasks(&mut self, config: &Config, tasks: &mut [Task], uninstall_first: bool) -> Result<()> {
+        self.reinstall(config, tasks, uninstall_first)
+    }
+    
     /// Implementazione della disinstallazione che accetta tasks come parametro
     pub fn uninstall_with_tasks(&mut self, config: &Config, tasks: &mut [Task]) -> Result<()> {
         self.uninstall(config, tasks)
@@ -126,14 +205,51 @@ impl std::fmt::Display for StackWithTasks {
 
 /// Implementazione di SelectableItem per StackWithTasks (delega a Stack)
 impl SelectableItem for StackWithTasks {
-    fn get_status_marker(&self) -> &'static str {
-        self.stack.get_status_marker()
+    fn status(&self) -> ItemStatus {
+        // Un task fallito rende fallito lo stack che lo contiene
+        let failed = self.tasks.lock().is_ok_and(|tasks| self.stack.task_names.iter()
+            .any(|name| tasks.iter().any(|t| t.matches_ref(name) && t.has_failed())));
+
+        if failed {
+            ItemStatus::Failed
+        } else {
+            self.stack.status()
+        }
     }
     
-    fn format_for_list(&self) -> String {
-        self.stack.format_for_list()
+    fn item_ref(&self) -> String {
+        self.stack.qualified_name()
+    }
+
+    fn list_row(&self) -> ListRow {
+        self.stack.row(self.status())
     }
     
+    fn csv_header() -> Vec<&'static str> {
+        vec!["nome", "sorgente", "stato", "task", "task_mancanti", "tag", "descrizione"]
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        // I task mancanti sono quelli non installati o non presenti nel catalogo
+        let missing: Vec<&str> = match self.tasks.lock() {
+            Ok(tasks) => self.stack.task_names.iter()
+                .filter(|name| !tasks.iter().any(|t| t.matches_ref(name) && t.installed))
+                .map(|name| name.as_str())
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+
+        vec![
+            self.stack.name.clone(),
+            self.stack.source.clone(),
+            self.status().label().to_string(),
+            self.stack.task_names.join(" "),
+            missing.join(" "),
+            self.stack.tags.join(" "),
+            self.stack.description.clone(),
+        ]
+    }
+
     fn format_details(&self) -> String {
         let mut details = self.stack.format_details();
         
@@ -143,9 +259,12 @@ impl SelectableItem for StackWithTasks {
             details.push_str(&task_details);
             
             for task_name in &self.stack.task_names {
-                if let Some(task) = tasks_guard.iter().find(|t| &t.name == task_name) {
-                    let status = if task.installed { "[✓]" } else { "[ ]" };
-                    details.push_str(&format!("  {} {}\n", status, task_name));
+                if let Some(task) = tasks_guard.iter().find(|t| t.matches_ref(task_name)) {
+                    let status = task.get_status_marker();
+                    match &task.denied {
+                        Some(rule) => details.push_str(&format!("  {} {} (escluso su questa macchina: {})\n", status, task_name, rule)),
+                        None => details.push_str(&format!("  {} {}\n", status, task_name)),
+                    }
                 } else {
                     details.push_str(&format!("  [?] {} (non trovato)\n", task_name));
                 }
@@ -180,6 +299,12 @@ impl Executable<StackWithTasks> for StackWithTasks {
         self.stack.install_with_tasks(config, &mut tasks_guard)
     }
     
+    /// Implementazione della reinstallazione dello stack
+    fn reinstall(&mut self, config: &Config, uninstall_first: bool) -> Result<()> {
+        let mut tasks_guard = self.tasks.lock().map_err(|_| anyhow::anyhow!("Failed to lock tasks"))?;
+        self.stack.reinstall_with_tasks(config, &mut tasks_guard, uninstall_first)
+    }
+    
     /// Implementazione della disinstallazione dello stack
     fn uninstall(&mut self, config: &Config) -> Result<()> {
         let mut tasks_guard = self.tasks.lock().map_err(|_| anyhow::anyhow!("Failed to lock tasks"))?;
@@ -197,4 +322,38 @@ impl Executable<StackWithTasks> for StackWithTasks {
         let mut tasks_guard = self.tasks.lock().map_err(|_| anyhow::anyhow!("Failed to lock tasks"))?;
         self.stack.remediate_with_tasks(config, &mut tasks_guard)
     }
+
+    /// Ripete le operazioni fallite dei task dello stack
+    fn retry(&mut self, config: &Config) -> Result<()> {
+        let mut tasks_guard = self.tasks.lock().map_err(|_| anyhow::anyhow!("Failed to lock tasks"))?;
+        self.stack.retry_failed(config, &mut tasks_guard)
+    }
+
+    /// Variabili dello stack con l'ultimo valore inserito o il predefinito
+    fn prompt_variables(&self, config: &Config) -> Vec<(StackVariable, Option<String>)> {
+        let values = self.stack.variable_values(config);
+
+        self.stack.variables.iter()
+            .map(|var| (var.clone(), values.get(&var.name).cloned()))
+            .collect()
+    }
+
+    /// Salva i valori delle variabili dello stack
+    fn set_variable_values(&mut self, config: &Config, values: &HashMap<String, String>) -> Result<()> {
+        self.stack.save_variable_values(config, values)
+    }
+
+    /// Risultati per task dell'ultima operazione sullo stack
+    fn last_report(&self) -> Option<StackReport> {
+        self.stack.last_report.clone()
+    }
+
+    /// Punto di ripristino creato prima dell'ultima installazione dello stack
+    fn restore_point(&self, config: &Config) -> Option<Snapshot> {
+        if self.stack.snapshot {
+            snapshot::load_restore_point(config, &self.stack.name)
+        } else {
+            None
+        }
+    }
 }
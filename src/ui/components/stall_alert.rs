@@ -0,0 +1,51 @@
+// File: src/ui/components/stall_alert.rs
+
+//! Segnalazione delle operazioni bloccate
+//!
+//! Quando uno script o un playbook resta senza output oltre `watchdog.stall_after`, il
+//! thread che lo sorveglia chiede a questa finestra di avvisare l'operatore, che può
+//! terminare l'operazione o continuare ad attendere. Il supervisore non resta in attesa
+//! della risposta: se lo script riprende o termina nel frattempo, la finestra lo indica.
+
+use std::sync::Arc;
+use cursive::Cursive;
+use cursive::views::Dialog;
+
+use crate::watchdog::{self, Watch};
+
+/// Registra la finestra come segnalazione delle operazioni bloccate
+///
+/// # Arguments
+///
+/// * `siv` - L'interfaccia in cui mostrare la finestra
+pub fn register(siv: &mut Cursive) {
+    let cb_sink = siv.cb_sink().clone();
+    watchdog::set_alert(Box::new(move |watch| {
+        let _ = cb_sink.send(Box::new(move |s| show(s, watch)));
+    }));
+}
+
+/// Mostra la finestra di un'operazione bloccata
+fn show(s: &mut Cursive, watch: Arc<Watch>) {
+    let message = format!(
+        "{} non produce output da {} minuti.\n\n\
+         Potrebbe essere in attesa di una risposta, di un lock o della rete. \
+         Terminare l'operazione?",
+        watch.label(), watch.idle().as_secs() / 60);
+    let terminate = Arc::clone(&watch);
+
+    s.add_layer(Dialog::text(message)
+        .title("Operazione bloccata?")
+        .button("Continua ad attendere", move |s| {
+            s.pop_layer();
+            watch.keep_waiting();
+        })
+        .button("Termina", move |s| {
+            s.pop_layer();
+            if terminate.is_finished() {
+                s.add_layer(Dialog::info(format!("{} è già terminato", terminate.label())));
+            } else {
+                terminate.terminate();
+            }
+        }));
+}
@@ -0,0 +1,78 @@
+// File: src/ui/components/status_bar.rs
+
+//! Barra di stato
+//!
+//! La barra mostra in fondo alle schermate il contesto in cui Galatea sta lavorando:
+//! file di configurazione e ruolo della macchina, nome host, privilegi, riavvii in
+//! sospeso, esecuzioni in corso e job in coda. Come il banner dei riavvii è condivisa
+//! da tutte le schermate e viene aggiornata al termine di ogni operazione.
+
+use std::sync::{Arc, Mutex};
+use cursive::theme::{BaseColor, Color, Effect, Style};
+use cursive::utils::markup::StyledString;
+use cursive::views::{TextContent, TextView};
+use lazy_static::lazy_static;
+
+use crate::config::Config;
+use crate::events;
+use crate::jobs;
+use crate::reboot;
+use crate::task::Task;
+use crate::utils;
+
+lazy_static! {
+    static ref STATUS: TextContent = TextContent::new("");
+}
+
+/// Crea la vista della barra di stato
+pub fn status_bar_view() -> TextView {
+    TextView::new_with_content(STATUS.clone())
+}
+
+/// Aggiorna la barra di stato
+///
+/// # Arguments
+///
+/// * `config` - La configurazione condivisa
+/// * `tasks` - I task del catalogo
+pub fn refresh(config: &Arc<Mutex<Config>>, tasks: &Arc<Mutex<Vec<Task>>>) {
+    let (config_name, role, read_only, pending) = match (config.lock(), tasks.lock()) {
+        (Ok(config_guard), Ok(tasks_guard)) => (
+            config_guard.config_file_path.as_ref()
+                .and_then(|path| path.file_name())
+                .map_or_else(|| "predefinita".to_string(), |name| name.to_string_lossy().to_string()),
+            config_guard.role.clone(),
+            config_guard.read_only,
+            reboot::pending_reboot(&config_guard, &tasks_guard),
+        ),
+        _ => return,
+    };
+
+    let mut status = StyledString::plain(format!(" Config: {}", config_name));
+    status.append_plain(format!(" | Ruolo: {}", role.as_deref().unwrap_or("nessuno")));
+    status.append_plain(format!(" | Host: {}", utils::get_hostname()));
+
+    if utils::is_running_as_root() {
+        status.append_plain(" | root");
+    } else {
+        status.append_styled(" | non root", Style::from(Color::Light(BaseColor::Yellow)).combine(Effect::Bold));
+    }
+    if read_only {
+        status.append_plain(" | sola lettura");
+    }
+
+    if pending.is_empty() {
+        status.append_plain(" | Riavvii: nessuno");
+    } else {
+        let reasons = pending.system.len() + pending.tasks.len();
+        status.append_styled(format!(" | Riavvii in sospeso: {}", reasons),
+                             Style::from(Color::Light(BaseColor::Yellow)).combine(Effect::Bold));
+    }
+
+    status.append_plain(format!(" | Esecuzioni attive: {}", events::active_runs()));
+    let (_, queued) = jobs::counts();
+    if queued > 0 {
+        status.append_plain(format!(" | Job in coda: {}", queued));
+    }
+    STATUS.set_content(status);
+}
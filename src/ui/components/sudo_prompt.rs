@@ -0,0 +1,72 @@
+// File: src/ui/components/sudo_prompt.rs
+
+//! Richiesta della password di sudo
+//!
+//! I task con `sudo: true` eseguiti da un utente normale chiedono la password di sudo
+//! con una finestra dall'input mascherato. La richiesta arriva dal thread del job, che
+//! resta in attesa della risposta mentre l'interfaccia continua a funzionare.
+
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, ThreadId};
+use cursive::{CbSink, Cursive};
+use cursive::traits::*;
+use cursive::views::{Dialog, EditView, LinearLayout, TextView};
+use log::warn;
+
+use crate::sudo;
+
+/// Registra la finestra come metodo per chiedere la password di sudo
+///
+/// # Arguments
+///
+/// * `siv` - L'interfaccia in cui mostrare la finestra
+pub fn register(siv: &mut Cursive) {
+    let cb_sink = siv.cb_sink().clone();
+    let ui_thread = thread::current().id();
+    sudo::set_prompt(Box::new(move |message| ask(&cb_sink, ui_thread, message)));
+}
+
+/// Mostra la finestra e attende la password inserita
+fn ask(cb_sink: &CbSink, ui_thread: ThreadId, message: &str) -> Option<String> {
+    // Il thread dell'interfaccia non può attendere una finestra che deve disegnare lui stesso
+    if thread::current().id() == ui_thread {
+        warn!("sudo password requested from the UI thread: run the operation as a job");
+        return None;
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let message = message.to_string();
+    cb_sink.send(Box::new(move |s| show(s, &message, sender))).ok()?;
+    receiver.recv().ok().flatten()
+}
+
+/// Mostra la finestra della password
+fn show(s: &mut Cursive, message: &str, sender: Sender<Option<String>>) {
+    let cancel = sender.clone();
+
+    let submit = move |s: &mut Cursive| {
+        let password = s.call_on_name("sudo_password", |view: &mut EditView| view.get_content().to_string())
+            .unwrap_or_default();
+        s.pop_layer();
+        let _ = sender.send(Some(password));
+    };
+
+    let layout = LinearLayout::vertical()
+        .child(TextView::new(message))
+        .child(EditView::new()
+            .secret()
+            .on_submit({
+                let submit = submit.clone();
+                move |s, _| submit(s)
+            })
+            .with_name("sudo_password")
+            .fixed_width(40));
+
+    s.add_layer(Dialog::around(layout)
+        .title("Password di sudo")
+        .button("Annulla", move |s| {
+            s.pop_layer();
+            let _ = cancel.send(None);
+        })
+        .button("OK", submit));
+}
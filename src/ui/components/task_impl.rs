@@ -1,37 +1,110 @@
 // File: src/ui/components/task_impl.rs
 
-use crate::task::{Task, ScriptType};
+use std::fs;
+use crate::downloader;
+use crate::source;
+use crate::task::{self, Task, ScriptType};
 use crate::config::Config;
-use crate::ui::components::selection::SelectableItem;
+use crate::ui::components::selection::{ItemStatus, ListRow, SelectableItem};
 use crate::ui::components::selectable_view::Executable;
 use anyhow::Result;
 
 /// Implementazione del trait SelectableItem per i Task
 impl SelectableItem for Task {
-    /// Restituisce un marcatore di stato per i task
-    fn get_status_marker(&self) -> &'static str {
-        if self.installed {
-            "[✓]"
+    /// Restituisce lo stato del task
+    fn status(&self) -> ItemStatus {
+        if self.has_failed() {
+            ItemStatus::Failed
+        } else if self.installed {
+            ItemStatus::Installed
+        } else if self.blocked_by.is_some() || self.denied.is_some() {
+            ItemStatus::Locked
         } else {
-            "[ ]"
+            ItemStatus::NotInstalled
         }
     }
     
-    /// Formatta il task per la visualizzazione nella lista
-    fn format_for_list(&self) -> String {
-        let status = self.get_status_marker();
-        let task_type = format!("[{}]", self.script_type.get_letter());
-        
-        format!("{} {} {} - {}", status, task_type, self.name, self.description)
+    /// Restituisce il riferimento qualificato del task
+    fn item_ref(&self) -> String {
+        self.qualified_name()
+    }
+
+    /// Restituisce la riga del task nella lista
+    fn list_row(&self) -> ListRow {
+        let mut row = ListRow::new(self.status(), self.name.as_str())
+            .kind(self.script_type.get_letter())
+            .column(self.source.as_str(), 12)
+            .column(self.version.as_deref().unwrap_or("-"), 10)
+            .column(if self.tags.is_empty() { "-".to_string() } else { self.tags.join(",") }, 20)
+            .description(self.description.as_str());
+        if self.deprecated {
+            row = row.flag("deprecato");
+        }
+        row
     }
     
+    /// Colonne dell'esportazione CSV dei task
+    fn csv_header() -> Vec<&'static str> {
+        vec!["nome", "sorgente", "tipo", "versione", "stato", "operazione_fallita", "errore", "tag", "descrizione"]
+    }
+
+    /// Valori dell'esportazione CSV del task
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.name.clone(),
+            self.source.clone(),
+            self.script_type.to_str().to_string(),
+            self.version.clone().unwrap_or_default(),
+            self.status().label().to_string(),
+            self.last_failure.as_ref().map(|f| f.operation.clone()).unwrap_or_default(),
+            self.last_failure.as_ref().map(|f| f.error.clone()).unwrap_or_default(),
+            self.tags.join(" "),
+            self.description.clone(),
+        ]
+    }
+
     /// Formatta i dettagli del task
     fn format_details(&self) -> String {
         let mut details = format!("Nome: {}\n", self.name);
+        details.push_str(&format!("Sorgente: {} ({})\n", self.source, self.qualified_name()));
         details.push_str(&format!("Tipo: {} ({})\n", self.script_type.to_str(), 
                                  self.script_type.get_letter()));
         details.push_str(&format!("Descrizione: {}\n", self.description));
         details.push_str(&format!("URL: {}\n", self.url));
+
+        if let Some(size) = self.size {
+            details.push_str(&format!("Dimensione attesa: {}\n", downloader::format_bytes(size)));
+        }
+        if let Some(sha256) = &self.sha256 {
+            details.push_str(&format!("SHA-256: {}\n", source::normalize_fingerprint(sha256)));
+        }
+        match &self.cached_bundle {
+            Some(path) => {
+                let size = fs::metadata(path).map(|m| downloader::format_bytes(m.len())).unwrap_or_default();
+                details.push_str(&format!("Copia in cache: sì, {} ({:?})\n", size, path));
+            },
+            None => details.push_str("Copia in cache: no\n"),
+        }
+
+        if let Some(version) = &self.version {
+            details.push_str(&format!("Versione: {}\n", version));
+        }
+
+        if !self.versions.is_empty() {
+            let versions: Vec<String> = self.versions.iter()
+                .map(|v| match v.size {
+                    Some(size) => format!("{} ({}, {})", v.version, v.channel.to_str(), downloader::format_bytes(size)),
+                    None => format!("{} ({})", v.version, v.channel.to_str()),
+                })
+                .collect();
+            details.push_str(&format!("Versioni disponibili: {}\n", versions.join(", ")));
+        }
+        if let Some(installed) = &self.installed_version {
+            match self.available_update() {
+                Some(update) => details.push_str(&format!("Versione installata: {} (aggiornamento disponibile: {})\n", installed, update)),
+                None => details.push_str(&format!("Versione installata: {}\n", installed)),
+            }
+        }
         details.push_str(&format!("Stato: {}\n", 
                                  if self.installed { "Installato" } else { "Non installato" }));
 
@@ -39,12 +112,87 @@ impl SelectableItem for Task {
             details.push_str(&format!("Dipendenze: {}\n", self.dependencies.join(", ")));
         }
 
+        if let Some(rule) = &self.denied {
+            details.push_str(&format!("Escluso su questa macchina: {} (denied_tasks/denied_tags)\n", rule));
+        }
+
+        if let Some(required) = &self.requires_stack {
+            let state = if self.blocked_by.is_some() { "non ancora installato" } else { "installato" };
+            details.push_str(&format!("Richiede lo stack: {} ({})\n", required, state));
+        }
+
         if !self.tags.is_empty() {
             details.push_str(&format!("Tag: {}\n", self.tags.join(", ")));
         }
 
+        if !self.locks.is_empty() {
+            details.push_str(&format!("Risorse esclusive: {}\n", self.locks.join(", ")));
+        }
+
         details.push_str(&format!("Richiede riavvio: {}\n", 
-                                 if self.requires_reboot { "Sì" } else { "No" }));
+                                 if self.reboot_required() { "Sì" } else { "No" }));
+
+        if self.retries > 0 {
+            details.push_str(&format!("Nuovi tentativi: {} (ogni {}s)\n", self.retries, self.retry_delay));
+        }
+
+        if self.min_interval > 0 {
+            details.push_str(&format!("Intervallo minimo: {}s tra remediate e verifiche\n", self.min_interval));
+        }
+
+        if let Some(check) = &self.healthcheck {
+            details.push_str(&format!("Controllo di salute: {} (timeout {}s{})\n", check.command, check.timeout,
+                                     if check.rollback { ", annulla se fallisce" } else { "" }));
+        }
+
+        if !self.resources.is_empty() {
+            details.push_str(&format!("Risorse: {}\n", self.resources.summary()));
+        }
+
+        if self.attempts.len() > 1 {
+            let failed = self.attempts.iter().filter(|a| a.error.is_some()).count();
+            details.push_str(&format!("Ultima installazione: {} tentativi, {} falliti\n", self.attempts.len(), failed));
+            if let Some(error) = self.attempts.iter().rev().find_map(|a| a.error.as_ref()) {
+                details.push_str(&format!("Ultimo errore: {}\n", error));
+            }
+        }
+
+        if let Some(checkpoint) = &self.checkpoint {
+            details.push_str(&format!("Installazione interrotta al checkpoint {}: la prossima riprende da lì\n", checkpoint));
+        }
+
+        if let Some(failure) = &self.last_failure {
+            let unhealthy = if failure.unhealthy { ", controllo di salute non superato" } else { "" };
+            details.push_str(&format!("Operazione fallita: {}{} ({})\n", failure.operation, unhealthy, failure.failed_at));
+            details.push_str(&format!("Errore: {}\n", failure.error));
+            if failure.quarantined {
+                details.push_str(&format!("In quarantena dopo {} fallimenti consecutivi: escluso dal ripristino automatico\n",
+                                         failure.consecutive));
+            }
+        }
+
+        if let Some(summary) = self.last_changes.as_ref().filter(|summary| !summary.is_empty()) {
+            details.push_str("Modifiche al sistema dell'ultima installazione:\n");
+            for line in summary.lines() {
+                details.push_str(&format!("  {}\n", line));
+            }
+        }
+
+        if let Some(result) = &self.last_result {
+            details.push_str(&format!("Ultimo esito: {}{}\n",
+                                     result.status.label(),
+                                     if result.changed { ", con modifiche" } else { ", nessuna modifica" }));
+            if let Some(message) = &result.message {
+                details.push_str(&format!("Messaggio: {}\n", message));
+            }
+        }
+
+        if self.deprecated {
+            match &self.replaced_by {
+                Some(replacement) => details.push_str(&format!("Deprecato: Sì, sostituito da {} (usa 'Migra')\n", replacement)),
+                None => details.push_str("Deprecato: Sì\n"),
+            }
+        }
 
         if let Some(cmd) = &self.cleanup_command {
             details.push_str(&format!("Comando di pulizia: {}\n", cmd));
@@ -59,7 +207,7 @@ impl SelectableItem for Task {
     
     /// Verifica se il task può essere installato
     fn can_install(&self) -> bool {
-        !self.installed
+        !self.installed && self.denied.is_none()
     }
     
     /// Verifica se il task può essere disinstallato
@@ -85,6 +233,11 @@ impl Executable<Task> for Task {
         self.install(config)
     }
     
+    /// Implementazione della reinstallazione del task
+    fn reinstall(&mut self, config: &Config, uninstall_first: bool) -> Result<()> {
+        self.reinstall(config, uninstall_first)
+    }
+    
     /// Implementazione della disinstallazione del task
     fn uninstall(&mut self, config: &Config) -> Result<()> {
         self.uninstall(config)
@@ -99,4 +252,19 @@ impl Executable<Task> for Task {
     fn remediate(&mut self, config: &Config) -> Result<()> {
         self.remediate(config)
     }
+
+    /// Ripete l'ultima operazione fallita del task
+    fn retry(&mut self, config: &Config) -> Result<()> {
+        self.retry_failed(config)
+    }
+
+    /// Restituisce il sostituto del task, se deprecato
+    fn replacement(&self) -> Option<String> {
+        self.replaced_by.clone().filter(|_| self.deprecated)
+    }
+
+    /// Implementazione della migrazione del task deprecato
+    fn migrate(items: &mut [Task], idx: usize, config: &Config) -> Result<()> {
+        task::migrate_deprecated(items, idx, config)
+    }
 }
@@ -0,0 +1,157 @@
+// File: src/ui/components/terminal_window.rs
+
+//! Finestra del terminale degli script interattivi
+//!
+//! I task con `interactive: true` eseguono lo script in uno pseudo-terminale; questa
+//! finestra ne mostra l'output e invia allo script le righe digitate, oltre a Ctrl-C e
+//! Ctrl-D. L'input può essere nascosto per le password. Lo script viene avviato dal thread
+//! del job, quindi l'interfaccia resta utilizzabile mentre attende le risposte.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, ThreadId};
+use cursive::{CbSink, Cursive};
+use cursive::traits::*;
+use cursive::view::ScrollStrategy;
+use cursive::views::{Dialog, EditView, LinearLayout, ScrollView, TextContent, TextView};
+use lazy_static::lazy_static;
+use log::warn;
+use regex::Regex;
+
+use crate::pty::{self, Terminal, TerminalInput};
+use crate::ui::app::{WINDOW_WIDTH, WINDOW_HEIGHT};
+
+/// Numero massimo di righe di output conservate nella finestra
+const MAX_LINES: usize = 2000;
+
+lazy_static! {
+    /// Sequenze di controllo del terminale (colori, movimenti del cursore, titoli)
+    static ref ESCAPE_SEQUENCE: Regex = Regex::new(r"\x1b(\[[0-?]*[ -/]*[@-~]|\][^\x07\x1b]*(\x07|\x1b\\)|[()][0-9A-Za-z]|[=>])").unwrap();
+}
+
+/// Registra la finestra come terminale degli script interattivi
+///
+/// # Arguments
+///
+/// * `siv` - L'interfaccia in cui mostrare la finestra
+pub fn register(siv: &mut Cursive) {
+    let cb_sink = siv.cb_sink().clone();
+    let ui_thread = thread::current().id();
+    pty::set_attach(Box::new(move |terminal| attach(&cb_sink, ui_thread, terminal)));
+}
+
+/// Apre la finestra e inoltra l'output dello script finché non termina
+fn attach(cb_sink: &CbSink, ui_thread: ThreadId, terminal: Terminal) -> bool {
+    // Il thread dell'interfaccia è occupato dallo script e non potrebbe disegnare la finestra
+    if thread::current().id() == ui_thread {
+        warn!("Interactive script started from the UI thread: run the operation as a job");
+        return false;
+    }
+
+    let Terminal { title, output, input } = terminal;
+    let content = TextContent::new("");
+    let window_content = content.clone();
+    if cb_sink.send(Box::new(move |s| show(s, &title, window_content, input))).is_err() {
+        return false;
+    }
+
+    let cb_sink = cb_sink.clone();
+    thread::spawn(move || {
+        let mut screen = Screen::default();
+        for chunk in output {
+            screen.render(&chunk);
+            content.set_content(&screen.text);
+            // Ridisegna l'interfaccia con il nuovo output
+            let _ = cb_sink.send(Box::new(|_| {}));
+        }
+        screen.render("\n--- Script terminato ---");
+        content.set_content(&screen.text);
+        let _ = cb_sink.send(Box::new(|_| {}));
+    });
+    true
+}
+
+/// Mostra la finestra del terminale
+fn show(s: &mut Cursive, title: &str, content: TextContent, input: TerminalInput) {
+    let submit = input.clone();
+    let interrupt = input.clone();
+    let end = input;
+    let secret = Arc::new(AtomicBool::new(false));
+
+    let layout = LinearLayout::vertical()
+        .child(ScrollView::new(TextView::new_with_content(content))
+            .scroll_strategy(ScrollStrategy::StickToBottom)
+            .full_height())
+        .child(EditView::new()
+            .on_submit(move |s, line| {
+                send(s, &submit, format!("{}\n", line).as_bytes());
+                s.call_on_name("terminal_input", |view: &mut EditView| { view.set_content(""); });
+            })
+            .with_name("terminal_input")
+            .full_width());
+
+    s.add_layer(Dialog::around(layout)
+        .title(format!("Terminale: {}", title))
+        .button("Ctrl-C", move |s| send(s, &interrupt, b"\x03"))
+        .button("Ctrl-D", move |s| send(s, &end, b"\x04"))
+        .button("Nascondi input", move |s| toggle_secret(s, &secret))
+        .button("Chiudi", |s| { s.pop_layer(); })
+        .fixed_width(WINDOW_WIDTH)
+        .fixed_height(WINDOW_HEIGHT));
+    s.focus_name("terminal_input").ok();
+}
+
+/// Invia dati allo script, segnalando se ha già chiuso il terminale
+fn send(s: &mut Cursive, input: &TerminalInput, data: &[u8]) {
+    if input.send(data).is_err() {
+        s.add_layer(Dialog::info("Lo script è terminato e non accetta più input"));
+    }
+}
+
+/// Nasconde o mostra il testo digitato, es. mentre lo script chiede una password
+fn toggle_secret(s: &mut Cursive, secret: &AtomicBool) {
+    let hidden = !secret.fetch_xor(true, Ordering::SeqCst);
+    s.call_on_name("terminal_input", |view: &mut EditView| view.set_secret(hidden));
+}
+
+/// Testo mostrato nella finestra, ricostruito dall'output dello script
+#[derive(Default)]
+struct Screen {
+    /// Le righe di output
+    text: String,
+
+    /// Ritorno a capo ricevuto e non ancora applicato: se segue `\n` è un fine riga
+    carriage_return: bool,
+}
+
+impl Screen {
+    /// Aggiunge l'output dello script
+    ///
+    /// Le sequenze di controllo vengono rimosse; un ritorno a capo isolato riscrive la
+    /// riga corrente, come fanno le barre di avanzamento.
+    fn render(&mut self, chunk: &str) {
+        for c in ESCAPE_SEQUENCE.replace_all(chunk, "").chars() {
+            if std::mem::take(&mut self.carriage_return) && c != '\n' {
+                let line_start = self.text.rfind('\n').map_or(0, |i| i + 1);
+                self.text.truncate(line_start);
+            }
+            match c {
+                '\r' => self.carriage_return = true,
+                '\x08' => {
+                    if !self.text.ends_with('\n') {
+                        self.text.pop();
+                    }
+                },
+                '\n' | '\t' => self.text.push(c),
+                c if c.is_control() => {},
+                c => self.text.push(c),
+            }
+        }
+
+        let lines = self.text.matches('\n').count();
+        if lines > MAX_LINES {
+            let cut = self.text.match_indices('\n').nth(lines - MAX_LINES).map_or(0, |(i, _)| i + 1);
+            self.text.drain(..cut);
+        }
+    }
+}
@@ -0,0 +1,61 @@
+// File: src/ui/components/toast.rs
+
+//! Notifiche non bloccanti
+//!
+//! Gli eventi minori (sorgente aggiunta, selezione pulita, esportazione completata, ...)
+//! vengono mostrati per qualche secondo in una riga di notifica in fondo alle schermate,
+//! senza aprire una finestra da chiudere: le finestre modali restano riservate agli
+//! errori e alle conferme.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+use cursive::Cursive;
+use cursive::align::HAlign;
+use cursive::theme::{BaseColor, Color, Effect, Style};
+use cursive::utils::markup::StyledString;
+use cursive::views::{TextContent, TextView};
+use lazy_static::lazy_static;
+use log::info;
+
+/// Durata di una notifica
+const TOAST_DURATION: Duration = Duration::from_secs(4);
+
+lazy_static! {
+    static ref TOAST: TextContent = TextContent::new("");
+}
+
+// Numero dell'ultima notifica mostrata: una notifica scaduta non cancella quelle successive
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Crea la riga delle notifiche, condivisa da tutte le schermate che la mostrano
+pub fn toast_view() -> TextView {
+    TextView::new_with_content(TOAST.clone())
+        .h_align(HAlign::Center)
+}
+
+/// Mostra una notifica per qualche secondo
+///
+/// # Arguments
+///
+/// * `s` - L'istanza di Cursive
+/// * `message` - Il messaggio da mostrare
+pub fn show(s: &mut Cursive, message: impl Into<String>) {
+    let message = message.into();
+    info!("{}", message);
+
+    let generation = GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    let style = Style::from(Color::Light(BaseColor::Green)).combine(Effect::Bold);
+    TOAST.set_content(StyledString::styled(format!("✓ {}", message), style));
+
+    // La notifica viene rimossa dal thread dell'interfaccia allo scadere della durata
+    let cb_sink = s.cb_sink().clone();
+    thread::spawn(move || {
+        thread::sleep(TOAST_DURATION);
+        let _ = cb_sink.send(Box::new(move |_| {
+            if GENERATION.load(Ordering::SeqCst) == generation {
+                TOAST.set_content("");
+            }
+        }));
+    });
+}
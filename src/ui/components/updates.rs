@@ -0,0 +1,167 @@
+// File: src/ui/components/updates.rs
+
+//! Aggiornamenti disponibili
+//!
+//! La voce del menu principale indica quanti task installati hanno nel catalogo una
+//! versione stabile più recente di quella installata. Viene aggiornata all'avvio, dopo
+//! ogni ricaricamento dei cataloghi e al termine di ogni operazione; selezionandola si
+//! vedono gli aggiornamenti e li si installa tutti con un solo job.
+
+use std::sync::{Arc, Mutex};
+use anyhow::anyhow;
+use log::Level;
+use cursive::Cursive;
+use cursive::traits::*;
+use cursive::views::{Dialog, SelectView, TextView};
+
+use crate::config::Config;
+use crate::events;
+use crate::jobs;
+use crate::stack::Stack;
+use crate::task::{self, Task};
+use crate::ui::components::reboot_banner;
+use crate::ui::components::status_bar;
+use crate::ui::components::toast;
+
+/// Nome della lista del menu principale
+pub const MAIN_MENU: &str = "main_menu";
+
+/// Valore della voce del menu principale
+pub const MENU_ITEM: &str = "updates";
+
+/// Restituisce l'etichetta della voce del menu principale
+///
+/// # Arguments
+///
+/// * `count` - Il numero di aggiornamenti disponibili
+pub fn menu_label(count: usize) -> String {
+    if count > 0 {
+        format!("Aggiornamenti disponibili ({})", count)
+    } else {
+        "Aggiornamenti: nessuno".to_string()
+    }
+}
+
+/// Aggiorna la voce del menu principale con il numero di aggiornamenti disponibili
+///
+/// # Arguments
+///
+/// * `s` - L'istanza di Cursive
+/// * `tasks` - I task del catalogo
+/// * `stacks` - Gli stack del catalogo
+pub fn refresh(s: &mut Cursive, tasks: &Arc<Mutex<Vec<Task>>>, stacks: &Arc<Mutex<Vec<Stack>>>) {
+    let count = match (tasks.lock(), stacks.lock()) {
+        (Ok(tasks_guard), Ok(stacks_guard)) => task::available_updates(&tasks_guard, &stacks_guard).len(),
+        _ => return,
+    };
+
+    s.call_on_name(MAIN_MENU, |view: &mut SelectView<&'static str>| {
+        let row = view.iter().position(|(_, value)| *value == MENU_ITEM);
+        if let Some((label, _)) = row.and_then(|row| view.get_item_mut(row)) {
+            *label = menu_label(count).into();
+        }
+    });
+}
+
+/// Mostra gli aggiornamenti disponibili e chiede conferma per installarli tutti
+///
+/// # Arguments
+///
+/// * `s` - L'istanza di Cursive
+/// * `config` - La configurazione condivisa
+/// * `tasks` - I task del catalogo
+/// * `stacks` - Gli stack del catalogo
+pub fn show_updates(s: &mut Cursive, config: Arc<Mutex<Config>>, tasks: Arc<Mutex<Vec<Task>>>, stacks: Arc<Mutex<Vec<Stack>>>) {
+    let updates: Vec<(usize, String)> = match (tasks.lock(), stacks.lock()) {
+        (Ok(tasks_guard), Ok(stacks_guard)) => task::available_updates(&tasks_guard, &stacks_guard).into_iter()
+            .filter_map(|idx| {
+                let task = &tasks_guard[idx];
+                let available = task.available_update()?;
+                Some((idx, format!("{}: {} -> {}", task.qualified_name(),
+                                   task.installed_version.as_deref().unwrap_or("-"), available)))
+            })
+            .collect(),
+        _ => return,
+    };
+
+    if updates.is_empty() {
+        toast::show(s, "Nessun aggiornamento disponibile");
+        return;
+    }
+
+    let read_only = config.lock().map(|c| c.read_only).unwrap_or(false);
+    let lines: Vec<&str> = updates.iter().map(|(_, line)| line.as_str()).collect();
+    let indices: Vec<usize> = updates.iter().map(|(idx, _)| *idx).collect();
+
+    let mut dialog = Dialog::around(TextView::new(lines.join("\n")).scrollable())
+        .title(format!("Aggiornamenti disponibili ({})", updates.len()));
+    if !read_only {
+        dialog.add_button("Aggiorna tutti", move |s| {
+            s.pop_layer();
+            update_all(s, Arc::clone(&config), Arc::clone(&tasks), Arc::clone(&stacks), indices.clone());
+        });
+    }
+    s.add_layer(dialog
+        .button("Chiudi", |s| { s.pop_layer(); })
+        .max_height(20)
+        .fixed_width(70));
+}
+
+/// Accoda l'aggiornamento dei task indicati
+fn update_all(s: &mut Cursive, config: Arc<Mutex<Config>>, tasks: Arc<Mutex<Vec<Task>>>, stacks: Arc<Mutex<Vec<Stack>>>, indices: Vec<usize>) {
+    let cb_sink = s.cb_sink().clone();
+    toast::show(s, format!("Aggiornamento di {} task in coda", indices.len()));
+
+    jobs::submit(format!("Aggiornamento di {} task", indices.len()), move || {
+        let config_snapshot = config.lock()
+            .map_err(|_| anyhow!("Failed to lock config"))?
+            .clone();
+
+        let mut updated = Vec::new();
+        let mut errors = Vec::new();
+        for idx in indices {
+            // Il task viene aggiornato su una copia, senza tenere bloccato il catalogo
+            let Some(mut item) = tasks.lock().ok().and_then(|tasks_guard| tasks_guard.get(idx).cloned()) else {
+                continue;
+            };
+            let name = item.qualified_name();
+
+            match item.update(&config_snapshot) {
+                Ok(Some(version)) => updated.push(format!("{} {}", name, version)),
+                Ok(None) => {},
+                Err(e) => errors.push(format!("{}: {:#}", name, e)),
+            }
+
+            // Lo stato del task (installato, fallimenti) va riportato nel catalogo anche in caso di errore
+            if let Ok(mut tasks_guard) = tasks.lock() {
+                if let Some(slot) = tasks_guard.get_mut(idx).filter(|slot| slot.qualified_name() == name) {
+                    *slot = item;
+                }
+            }
+        }
+
+        let summary = format!("Task aggiornati: {}", updated.len());
+        events::log_line(Level::Info, format!("{}{}", summary,
+            if updated.is_empty() { String::new() } else { format!(" ({})", updated.join(", ")) }));
+
+        let failed = !errors.is_empty();
+        let error_text = errors.join("\n");
+        let _ = cb_sink.send(Box::new(move |s: &mut Cursive| {
+            refresh(s, &tasks, &stacks);
+            reboot_banner::refresh(&config, &tasks);
+            status_bar::refresh(&config, &tasks);
+            if failed {
+                s.add_layer(Dialog::info(format!("{}\n\nErrori:\n{}", summary, error_text))
+                             .fixed_width(70));
+            } else {
+                toast::show(s, summary);
+            }
+        }));
+
+        if failed {
+            Err(anyhow!("{} aggiornamenti non riusciti", errors.len()))
+        } else {
+            Ok(())
+        }
+    });
+}
@@ -0,0 +1,167 @@
+//! Inventario di pacchetti e servizi
+//!
+//! Questo modulo mostra, in sola lettura, i pacchetti installati e i servizi in esecuzione
+//! sulla macchina (raccolti con le funzioni di [`utils`](crate::utils)), filtrabili per tipo
+//! e per testo: aiuta a capire cosa è già presente prima di scegliere gli stack da installare.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+
+use cursive::Cursive;
+use cursive::views::{Dialog, TextView, TextContent, ScrollView, LinearLayout, DummyView, Panel, SelectView, EditView};
+use cursive::theme::{BaseColor, Color};
+use cursive::utils::markup::StyledString;
+use cursive::traits::*;
+use unicode_width::UnicodeWidthStr;
+
+use crate::session;
+use crate::ui::components::selection;
+use crate::utils;
+
+/// Tipo di voci mostrate nell'inventario
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Kind {
+    #[default]
+    All,
+    Packages,
+    Services,
+}
+
+/// Stato della schermata dell'inventario
+#[derive(Debug, Default)]
+struct InventoryState {
+    /// Pacchetti installati con la loro versione
+    packages: BTreeMap<String, String>,
+
+    /// Servizi systemd in esecuzione
+    services: BTreeSet<String>,
+
+    /// Tipo di voci mostrate
+    kind: Kind,
+
+    /// Testo da cercare nei nomi (senza distinzione tra maiuscole e minuscole)
+    filter: String,
+}
+
+impl InventoryState {
+    /// Raccoglie pacchetti e servizi della macchina
+    fn collect(&mut self) {
+        self.packages = utils::get_installed_packages();
+        self.services = utils::get_running_services();
+    }
+
+    /// Verifica se un tipo di voci è mostrato
+    fn shows(&self, kind: Kind) -> bool {
+        self.kind == Kind::All || self.kind == kind
+    }
+
+    /// Compone l'elenco filtrato: prima i servizi, poi i pacchetti
+    fn render(&self) -> StyledString {
+        let filter = self.filter.to_lowercase();
+        let matches = |name: &str| filter.is_empty() || name.to_lowercase().contains(&filter);
+
+        let services: Vec<&String> = self.services.iter()
+            .filter(|name| self.shows(Kind::Services) && matches(name))
+            .collect();
+        let packages: Vec<(&String, &String)> = self.packages.iter()
+            .filter(|(name, _)| self.shows(Kind::Packages) && matches(name))
+            .collect();
+
+        let mut content = StyledString::new();
+        if self.shows(Kind::Services) {
+            content.append_styled(format!("Servizi in esecuzione ({}/{})\n", services.len(), self.services.len()),
+                                  Color::Dark(BaseColor::Cyan));
+            if self.services.is_empty() {
+                content.append_plain("  systemd non disponibile\n");
+            }
+            for name in services {
+                content.append_plain(format!("  {}\n", name));
+            }
+            content.append_plain("\n");
+        }
+
+        if self.shows(Kind::Packages) {
+            content.append_styled(format!("Pacchetti installati ({}/{})\n", packages.len(), self.packages.len()),
+                                  Color::Dark(BaseColor::Cyan));
+            if self.packages.is_empty() {
+                content.append_plain("  Nessun gestore di pacchetti supportato (dpkg, rpm, pacman)\n");
+            }
+            let width = packages.iter().map(|(name, _)| name.width()).max().unwrap_or(0).min(40);
+            for (name, version) in packages {
+                content.append_plain(format!("  {}  {}\n", selection::fit(name, width), version));
+            }
+        }
+
+        content
+    }
+}
+
+/// Crea la vista dell'inventario di pacchetti e servizi
+pub fn create_inventory_view(siv: &mut Cursive) {
+    let mut inventory = InventoryState::default();
+    inventory.collect();
+    let content = TextContent::new(inventory.render());
+    let state = Arc::new(Mutex::new(inventory));
+    session::set_screen(Some("inventory"));
+
+    // Selettore del tipo di voci
+    let mut kind_selector = SelectView::<Kind>::new().popup();
+    kind_selector.add_item("Tutti", Kind::All);
+    kind_selector.add_item("Pacchetti", Kind::Packages);
+    kind_selector.add_item("Servizi", Kind::Services);
+    kind_selector.set_on_submit({
+        let state = Arc::clone(&state);
+        let content = content.clone();
+        move |_, kind: &Kind| {
+            if let Ok(mut state_guard) = state.lock() {
+                state_guard.kind = *kind;
+                content.set_content(state_guard.render());
+            }
+        }
+    });
+
+    // Campo di ricerca, applicato durante la digitazione
+    let filter_field = EditView::new()
+        .on_edit({
+            let state = Arc::clone(&state);
+            let content = content.clone();
+            move |_, text, _| {
+                if let Ok(mut state_guard) = state.lock() {
+                    state_guard.filter = text.to_string();
+                    content.set_content(state_guard.render());
+                }
+            }
+        })
+        .fixed_width(30);
+
+    let toolbar = LinearLayout::horizontal()
+        .child(TextView::new("Mostra: "))
+        .child(kind_selector)
+        .child(DummyView.fixed_width(2))
+        .child(TextView::new("Cerca: "))
+        .child(filter_field);
+
+    let list = ScrollView::new(TextView::new_with_content(content.clone()))
+        .scroll_x(true);
+
+    let layout = LinearLayout::vertical()
+        .child(toolbar)
+        .child(DummyView.fixed_height(1))
+        .child(Panel::new(list)
+            .title("Pacchetti e servizi")
+            .full_screen());
+
+    siv.add_fullscreen_layer(Dialog::around(layout)
+        .title("Inventario di sistema")
+        .button("Aggiorna", move |_| {
+            // Raccoglie di nuovo pacchetti e servizi, es. dopo un'installazione
+            if let Ok(mut state_guard) = state.lock() {
+                state_guard.collect();
+                content.set_content(state_guard.render());
+            }
+        })
+        .button("Chiudi", |s| {
+            session::set_screen(None);
+            s.pop_layer();
+        }));
+}
@@ -0,0 +1,208 @@
+//! Schermata dei job
+//!
+//! Mostra i job in corso, in coda e terminati con il loro avanzamento, e permette di
+//! collegarsi all'output dal vivo di un job o annullarlo. Le schermate si aggiornano da sole finché
+//! restano aperte.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use cursive::Cursive;
+use cursive::views::{Dialog, LinearLayout, ScrollView, SelectView, TextContent, TextView};
+use cursive::view::{Scrollable, ScrollStrategy};
+use cursive::traits::*;
+
+use crate::jobs::{self, Job, JobId, JobState};
+use crate::ui::app::{WINDOW_WIDTH, WINDOW_HEIGHT};
+
+/// Intervallo di aggiornamento delle schermate
+const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Apre la schermata dei job
+pub fn create_jobs_view(s: &mut Cursive) {
+    let mut list = SelectView::<JobId>::new();
+    fill_list(&mut list);
+    list.set_on_submit(|s, id: &JobId| attach(s, *id));
+
+    let summary = TextContent::new(summary_text());
+
+    let layout = LinearLayout::vertical()
+        .child(TextView::new_with_content(summary.clone()))
+        .child(list.with_name("jobs_list").scrollable().full_height());
+
+    s.add_layer(Dialog::around(layout)
+        .title("Job")
+        .button("Output", |s| {
+            let selected = s.call_on_name("jobs_list", |view: &mut SelectView<JobId>| view.selection())
+                .flatten();
+            if let Some(id) = selected {
+                attach(s, *id);
+            }
+        })
+        .button("Annulla job", |s| {
+            let selected = s.call_on_name("jobs_list", |view: &mut SelectView<JobId>| view.selection())
+                .flatten();
+            if let Some(id) = selected {
+                confirm_cancel(s, *id);
+            }
+        })
+        .button("Chiudi", |s| { s.pop_layer(); })
+        .fixed_width(WINDOW_WIDTH)
+        .fixed_height(WINDOW_HEIGHT));
+
+    auto_refresh(s, move |s| {
+        summary.set_content(summary_text());
+        s.call_on_name("jobs_list", |view: &mut SelectView<JobId>| {
+            // I job nuovi compaiono in cima: la selezione segue il job, non la posizione
+            let selected = view.selection().map(|id| *id);
+            fill_list(view);
+            if let Some(position) = selected.and_then(|id| view.iter().position(|(_, item)| *item == id)) {
+                view.set_selection(position);
+            }
+        }).is_some()
+    });
+}
+
+/// Mostra l'output di un job, aggiornato finché il job è in corso
+fn attach(s: &mut Cursive, id: JobId) {
+    let job = match jobs::get(id) {
+        Some(job) => job,
+        None => {
+            s.add_layer(Dialog::info("Job non più presente nel registro"));
+            return;
+        },
+    };
+
+    let header = TextContent::new(job_header(&job));
+    let output = TextContent::new(job.output.join("\n"));
+
+    let layout = LinearLayout::vertical()
+        .child(TextView::new_with_content(header.clone()))
+        .child(ScrollView::new(TextView::new_with_content(output.clone()))
+            .scroll_strategy(ScrollStrategy::StickToBottom)
+            .with_name("job_output")
+            .full_height());
+
+    s.add_layer(Dialog::around(layout)
+        .title(format!("Job #{}: {}", job.id, job.label))
+        .button("Annulla job", move |s| confirm_cancel(s, id))
+        .button("Chiudi", |s| { s.pop_layer(); })
+        .fixed_width(WINDOW_WIDTH)
+        .fixed_height(WINDOW_HEIGHT));
+
+    auto_refresh(s, move |s| {
+        let job = match jobs::get(id) {
+            Some(job) => job,
+            None => return false,
+        };
+        header.set_content(job_header(&job));
+        output.set_content(job.output.join("\n"));
+
+        // Dopo la chiusura della schermata non serve più aggiornarla
+        s.find_name::<ScrollView<TextView>>("job_output").is_some() && !job.state.is_finished()
+    });
+}
+
+/// Chiede conferma e annulla un job in coda o in corso
+fn confirm_cancel(s: &mut Cursive, id: JobId) {
+    let job = match jobs::get(id) {
+        Some(job) if !job.state.is_finished() => job,
+        _ => {
+            s.add_layer(Dialog::info("Il job è già terminato"));
+            return;
+        },
+    };
+
+    let message = match job.state {
+        JobState::Queued => format!("Annullare il job #{} ({})?\nIl job non verrà avviato.", job.id, job.label),
+        _ => format!("Annullare il job #{} ({})?\nLo script in esecuzione riceverà SIGTERM e, se non termina, SIGKILL.", job.id, job.label),
+    };
+
+    s.add_layer(Dialog::around(TextView::new(message))
+        .title("Conferma annullamento")
+        .button("No", |s| { s.pop_layer(); })
+        .button("Sì", move |s| {
+            s.pop_layer();
+            if !jobs::cancel(id) {
+                s.add_layer(Dialog::info("Il job è già terminato o è già stato annullato"));
+            }
+        })
+        .fixed_width(60));
+}
+
+/// Aggiorna periodicamente una schermata finché `refresh` restituisce `true`
+fn auto_refresh<F>(s: &mut Cursive, refresh: F)
+where
+    F: Fn(&mut Cursive) -> bool + Send + Sync + 'static,
+{
+    let cb_sink = s.cb_sink().clone();
+    let active = Arc::new(AtomicBool::new(true));
+    let refresh = Arc::new(refresh);
+
+    thread::spawn(move || {
+        while active.load(Ordering::SeqCst) {
+            thread::sleep(REFRESH_INTERVAL);
+
+            let active = Arc::clone(&active);
+            let refresh = Arc::clone(&refresh);
+            let sent = cb_sink.send(Box::new(move |s| {
+                if !refresh(s) {
+                    active.store(false, Ordering::SeqCst);
+                }
+            }));
+            if sent.is_err() {
+                break; // Interrompi il loop se l'interfaccia è stata chiusa
+            }
+        }
+    });
+}
+
+/// Riempie la lista con i job del registro
+fn fill_list(list: &mut SelectView<JobId>) {
+    list.clear();
+    for job in jobs::list() {
+        list.add_item(job_row(&job), job.id);
+    }
+}
+
+/// Formatta la riga di un job per la lista
+fn job_row(job: &Job) -> String {
+    let progress = match (job.state.is_finished(), job.percent) {
+        (false, Some(percent)) => format!(" {:>3.0}%", percent),
+        _ => String::new(),
+    };
+    format!("#{:<4} {} {:<11} {}{}{}", job.id, job.queued_at.format("%H:%M:%S"), job.state.label(), job.label, progress, format_elapsed(job))
+}
+
+/// Formatta l'intestazione della schermata di output di un job
+fn job_header(job: &Job) -> String {
+    let mut header = format!("Stato: {}{} | accodato alle {}", job.state.label(), format_elapsed(job), job.queued_at.format("%H:%M:%S"));
+    if let Some(percent) = job.percent.filter(|_| !job.state.is_finished()) {
+        header.push_str(&format!(" | {:.0}%", percent));
+    }
+    if !job.detail.is_empty() && !job.state.is_finished() {
+        header.push_str(&format!("\n{}", job.detail));
+    }
+    if job.cancel_requested() && !job.state.is_finished() {
+        header.push_str(" | annullamento in corso");
+    }
+    if let Some(error) = &job.error {
+        header.push_str(&format!("\nErrore: {}", error));
+    }
+    header
+}
+
+/// Formatta la durata di un job, se avviato
+fn format_elapsed(job: &Job) -> String {
+    job.elapsed()
+        .map(|elapsed| format!(" ({}s)", elapsed.num_seconds()))
+        .unwrap_or_default()
+}
+
+/// Riassume il numero di job in corso e in coda
+fn summary_text() -> String {
+    let (running, queued) = jobs::counts();
+    format!("In corso: {} | In coda: {} | Invio sul job per vederne l'output\n", running, queued)
+}
@@ -1,6 +1,8 @@
 //! Visualizzazione e gestione dei log nell'interfaccia utente
 //!
-//! Questo modulo fornisce la visualizzazione dei log di sistema.
+//! Questo modulo fornisce la visualizzazione dei log di sistema: una schermata
+//! completa con filtro per livello, ricerca testuale, modalità follow e salto
+//! al prossimo errore, oltre al popup con i log recenti.
 
 use std::fs;
 use std::path::Path;
@@ -9,26 +11,46 @@ use std::time::Duration;
 use std::thread;
 
 use cursive::Cursive;
-use cursive::views::{Dialog, TextView, LinearLayout, DummyView, Panel, Button, ScrollView};
+use cursive::views::{Dialog, TextView, LinearLayout, DummyView, Panel, ScrollView, SelectView, EditView, NamedView, OnEventView};
 use cursive::view::Scrollable;
 use cursive::traits::*;
-use cursive::theme::{BaseColor, Color, ColorStyle};
-use cursive::align::HAlign;
+use cursive::theme::{BaseColor, Color};
+use cursive::utils::markup::StyledString;
+use log::Level;
 
-use crate::config::Config;
 use crate::logger;
 
 // Dimensioni standard per le finestre
 const WINDOW_WIDTH: usize = 80;
 const WINDOW_HEIGHT: usize = 24;
-const PANEL_WIDTH: usize = 78;
-const LOG_HEIGHT: usize = 10;
+
+/// Directory dei log usata se il logger non è stato inizializzato
+const DEFAULT_LOG_DIR: &str = "/var/log/galatea";
+
+/// Intervallo di aggiornamento in modalità follow
+const FOLLOW_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Livelli selezionabili nel filtro, dal più dettagliato al più grave
+const FILTER_LEVELS: &[Level] = &[Level::Trace, Level::Debug, Level::Info, Level::Warn, Level::Error];
+
+/// Vista con le righe del log; il valore di ogni riga è il suo livello
+type LogLinesView = SelectView<Option<Level>>;
 
 /// Struttura per contenere lo stato della visualizzazione dei log
 pub struct LogState {
     pub log_dir: String,
+    /// File selezionato; `None` indica sempre il file più recente
     pub current_log_file: Option<String>,
-    pub auto_refresh: bool,
+    /// Modalità follow: aggiorna periodicamente e mostra le ultime righe
+    pub follow: bool,
+    /// Livello minimo mostrato (`None` per tutti i livelli)
+    pub min_level: Option<Level>,
+    /// Testo da cercare nelle righe (senza distinzione tra maiuscole e minuscole)
+    pub search: String,
+    /// Generazione del thread di follow, usata per fermare i thread precedenti
+    follow_generation: u64,
+    /// Indica se la schermata dei log è ancora aperta
+    open: bool,
 }
 
 impl LogState {
@@ -36,7 +58,11 @@ impl LogState {
         LogState {
             log_dir,
             current_log_file: None,
-            auto_refresh: false,
+            follow: false,
+            min_level: None,
+            search: String::new(),
+            follow_generation: 0,
+            open: true,
         }
     }
 
@@ -70,123 +96,345 @@ impl LogState {
             Err(e) => format!("Errore nella lettura del file di log: {}", e),
         }
     }
+
+    /// Restituisce il file attualmente visualizzato
+    pub fn active_log_file(&self) -> Option<String> {
+        match &self.current_log_file {
+            Some(file) => Some(file.clone()),
+            None => self.get_log_files().into_iter().next(),
+        }
+    }
+
+    /// Restituisce le righe del file attivo che soddisfano i filtri, con il relativo livello
+    ///
+    /// # Returns
+    ///
+    /// Le righe filtrate e il numero totale di righe del file
+    pub fn filtered_lines(&self) -> (Vec<(Option<Level>, String)>, usize) {
+        let content = match self.active_log_file() {
+            Some(file) => self.get_log_content(&file),
+            None => return (Vec::new(), 0),
+        };
+
+        let search = self.search.to_lowercase();
+        let mut total = 0;
+        let mut current_level = None;
+        let mut lines = Vec::new();
+
+        for line in content.lines() {
+            total += 1;
+
+            // Le righe senza intestazione (es. output multilinea) ereditano il livello precedente
+            if let Some(level) = parse_line_level(line) {
+                current_level = Some(level);
+            }
+
+            if let (Some(min), Some(level)) = (self.min_level, current_level) {
+                if level > min {
+                    continue;
+                }
+            }
+
+            if !search.is_empty() && !line.to_lowercase().contains(&search) {
+                continue;
+            }
+
+            lines.push((current_level, line.to_string()));
+        }
+
+        (lines, total)
+    }
+}
+
+/// Restituisce la directory dei log corrente
+fn log_directory() -> String {
+    logger::get_log_directory().unwrap_or_else(|| DEFAULT_LOG_DIR.to_string())
+}
+
+/// Estrae il livello da una riga di log nel formato "[timestamp] LIVELLO modulo: messaggio"
+fn parse_line_level(line: &str) -> Option<Level> {
+    let rest = line.strip_prefix('[')?.split_once("] ")?.1;
+    let token = rest.split(|c: char| c == ' ' || c == ':').next()?;
+    token.parse::<Level>().ok()
+}
+
+/// Colora una riga di log in base al livello
+fn styled_line(level: Option<Level>, line: &str) -> StyledString {
+    match level {
+        Some(Level::Error) => StyledString::styled(line, Color::Dark(BaseColor::Red)),
+        Some(Level::Warn) => StyledString::styled(line, Color::Dark(BaseColor::Yellow)),
+        _ => StyledString::plain(line),
+    }
 }
 
 /// Crea la vista per la visualizzazione dei log
 pub fn create_log_view(siv: &mut Cursive) {
-    // Crea uno stato iniziale per la vista dei log
-    let log_state = LogState::new("/var/log/galatea".to_string());
-    
-    // Ottieni l'elenco dei file di log
-    let log_files = log_state.get_log_files();
-    
-    // Contenuto iniziale
-    let initial_content = if let Some(first_log) = log_files.first() {
-        log_state.get_log_content(first_log)
-    } else {
-        "Nessun file di log trovato".to_string()
-    };
+    open_log_view(siv, "");
+}
 
-    // Crea la vista di testo per i log
-    let log_text = TextView::new(initial_content)
-        .with_name("log_content")
-        .scrollable();
-
-    // Crea il selettore dei file di log
-    let mut log_selector = LinearLayout::horizontal()
-        .child(TextView::new("File: "));
-
-    // Aggiungi pulsanti per ogni file di log
-    for log_file in &log_files {
-        let file_name = log_file.clone();
-        let file_name_for_button = file_name.clone(); // Clone for button label
-        let file_name_for_closure = file_name.clone(); // Clone for closure
-        log_selector = log_selector.child(Button::new_raw(&file_name_for_button, move |s| {
-            let log_content = {
-                let log_dir = "/var/log/galatea".to_string();
-                let log_state = LogState::new(log_dir);
-                log_state.get_log_content(&file_name_for_closure)
-            };
-            
-            s.call_on_name("log_content", |view: &mut TextView| {
-                view.set_content(log_content);
-            });
-        }));
-        log_selector = log_selector.child(DummyView.fixed_width(1));
+/// Apre la vista dei log filtrata sul testo indicato, posizionata sulle righe più recenti
+///
+/// Usata dalle finestre di errore per mostrare la sezione di log relativa a un elemento.
+pub fn show_log_for(siv: &mut Cursive, search: &str) {
+    open_log_view(siv, search);
+
+    siv.call_on_name("log_lines", |view: &mut LogLinesView| {
+        let last = view.len().saturating_sub(1);
+        view.set_selection(last);
+    });
+
+    siv.call_on_name("log_scroll", |view: &mut ScrollView<NamedView<LogLinesView>>| {
+        view.scroll_to_bottom();
+    });
+}
+
+/// Costruisce la schermata dei log con una ricerca iniziale
+fn open_log_view(siv: &mut Cursive, search: &str) {
+    let mut log_state = LogState::new(log_directory());
+    log_state.search = search.to_string();
+    let state = Arc::new(Mutex::new(log_state));
+
+    // Selettore del file di log
+    let mut file_selector = SelectView::<Option<String>>::new().popup();
+    file_selector.add_item("Più recente", None);
+    if let Ok(state_guard) = state.lock() {
+        for log_file in state_guard.get_log_files() {
+            file_selector.add_item(log_file.clone(), Some(log_file));
+        }
     }
+    file_selector.set_on_submit({
+        let state = Arc::clone(&state);
+        move |s, file: &Option<String>| {
+            if let Ok(mut state_guard) = state.lock() {
+                state_guard.current_log_file = file.clone();
+            }
+            refresh_log_lines(s, &state);
+        }
+    });
+
+    // Selettore del livello minimo
+    let mut level_selector = SelectView::<Option<Level>>::new().popup();
+    level_selector.add_item("Tutti", None);
+    for level in FILTER_LEVELS {
+        level_selector.add_item(format!(">= {}", level), Some(*level));
+    }
+    level_selector.set_on_submit({
+        let state = Arc::clone(&state);
+        move |s, level: &Option<Level>| {
+            if let Ok(mut state_guard) = state.lock() {
+                state_guard.min_level = *level;
+            }
+            refresh_log_lines(s, &state);
+        }
+    });
+
+    // Campo di ricerca
+    let search_field = EditView::new()
+        .content(search)
+        .on_submit({
+            let state = Arc::clone(&state);
+            move |s, text| {
+                if let Ok(mut state_guard) = state.lock() {
+                    state_guard.search = text.to_string();
+                }
+                refresh_log_lines(s, &state);
+                s.focus_name("log_lines").ok();
+            }
+        })
+        .with_name("log_search")
+        .fixed_width(24);
+
+    let toolbar = LinearLayout::horizontal()
+        .child(TextView::new("File: "))
+        .child(file_selector)
+        .child(DummyView.fixed_width(2))
+        .child(TextView::new("Livello: "))
+        .child(level_selector)
+        .child(DummyView.fixed_width(2))
+        .child(TextView::new("Cerca: "))
+        .child(search_field);
+
+    // Righe del log
+    let log_lines = LogLinesView::new()
+        .with_name("log_lines")
+        .scrollable()
+        .with_name("log_scroll");
 
-    // Layout principale
     let layout = LinearLayout::vertical()
-        .child(log_selector)
+        .child(toolbar)
         .child(DummyView.fixed_height(1))
-        .child(Panel::new(log_text)
+        .child(Panel::new(log_lines)
             .title("Contenuto del log")
-            .fixed_width(PANEL_WIDTH)
-            .fixed_height(LOG_HEIGHT * 2));
+            .full_screen())
+        .child(TextView::new("").with_name("log_status"))
+        .child(TextView::new("Tasti: / cerca, e prossimo errore, f follow, r aggiorna"));
+
+    // Scorciatoie da tastiera (attive quando il focus non è sul campo di ricerca)
+    let layout = OnEventView::new(layout)
+        .on_event('/', |s| { s.focus_name("log_search").ok(); })
+        .on_event('e', jump_to_next_error)
+        .on_event('f', {
+            let state = Arc::clone(&state);
+            move |s| toggle_follow(s, &state)
+        })
+        .on_event('r', {
+            let state = Arc::clone(&state);
+            move |s| refresh_log_lines(s, &state)
+        });
 
-    // Aggiungi la vista alla UI
-    siv.add_layer(Dialog::around(layout)
+    siv.add_fullscreen_layer(Dialog::around(layout)
         .title("Visualizzazione Log")
-        .button("Aggiorna", |s| {
-            // Ricarica il contenuto del log corrente
-            if let Some(first_log) = LogState::new("/var/log/galatea".to_string()).get_log_files().first() {
-                let file_name = first_log.clone();
-                let log_content = {
-                    let log_dir = "/var/log/galatea".to_string();
-                    let log_state = LogState::new(log_dir);
-                    log_state.get_log_content(&file_name)
-                };
-                
-                s.call_on_name("log_content", |view: &mut TextView| {
-                    view.set_content(log_content);
-                });
-            }
+        .button("Prossimo errore", jump_to_next_error)
+        .button("Follow", {
+            let state = Arc::clone(&state);
+            move |s| toggle_follow(s, &state)
         })
-        .button("Attiva Auto-Refresh", |s| {
-            // Configura un timer che aggiorna i log ogni 2 secondi
-            let cb_sink = s.cb_sink().clone();
-            thread::spawn(move || {
-                loop {
-                    thread::sleep(Duration::from_secs(2));
-                    
-                    // Invia un callback per aggiornare i log
-                    if let Some(first_log) = LogState::new("/var/log/galatea".to_string()).get_log_files().first() {
-                        let file_name = first_log.clone();
-                        let log_content = {
-                            let log_dir = "/var/log/galatea".to_string();
-                            let log_state = LogState::new(log_dir);
-                            log_state.get_log_content(&file_name)
-                        };
-                        
-                        // Aggiorna la vista dei log
-                        let content = log_content.clone();
-                        if let Err(e) = cb_sink.send(Box::new(move |s| {
-                            s.call_on_name("log_content", |view: &mut TextView| {
-                                view.set_content(content.clone());
-                            });
-                        })) {
-                            break; // Interrompi il loop se c'è un errore
-                        }
-                    }
-                }
-            });
-            
-            s.add_layer(Dialog::info("Auto-refresh dei log attivato")
-                         .fixed_width(50)
-                         .fixed_height(7));
+        .button("Aggiorna", {
+            let state = Arc::clone(&state);
+            move |s| refresh_log_lines(s, &state)
         })
-        .button("Chiudi", |s| { s.pop_layer(); })
-        .fixed_width(WINDOW_WIDTH)
-        .fixed_height(WINDOW_HEIGHT));
+        .button("Chiudi", {
+            let state = Arc::clone(&state);
+            move |s| {
+                // Ferma l'eventuale thread di follow
+                if let Ok(mut state_guard) = state.lock() {
+                    state_guard.open = false;
+                }
+                s.pop_layer();
+            }
+        }));
+
+    refresh_log_lines(siv, &state);
+    siv.focus_name("log_lines").ok();
+}
+
+/// Ricarica le righe del log applicando i filtri correnti
+fn refresh_log_lines(s: &mut Cursive, state: &Arc<Mutex<LogState>>) {
+    let (lines, total, status, follow) = match state.lock() {
+        Ok(state_guard) => {
+            let (lines, total) = state_guard.filtered_lines();
+            let status = format!(
+                "File: {} | Livello: {} | Ricerca: {} | Righe: {}/{} | Follow: {}",
+                state_guard.active_log_file().unwrap_or_else(|| "nessuno".to_string()),
+                state_guard.min_level.map_or("tutti".to_string(), |l| format!(">= {}", l)),
+                if state_guard.search.is_empty() { "-".to_string() } else { format!("'{}'", state_guard.search) },
+                lines.len(),
+                total,
+                if state_guard.follow { "attivo" } else { "disattivo" }
+            );
+            (lines, total, status, state_guard.follow)
+        },
+        Err(_) => return,
+    };
+
+    s.call_on_name("log_lines", |view: &mut LogLinesView| {
+        // Mantiene la posizione corrente quando non si è in modalità follow
+        let selected = view.selected_id();
+        view.clear();
+
+        if total == 0 {
+            view.add_item("Nessun file di log trovato", None);
+            return;
+        }
+
+        for (level, line) in lines {
+            view.add_item(styled_line(level, &line), level);
+        }
+
+        let last = view.len().saturating_sub(1);
+        let target = if follow { last } else { selected.unwrap_or(0).min(last) };
+        view.set_selection(target);
+    });
+
+    s.call_on_name("log_status", |view: &mut TextView| {
+        view.set_content(status);
+    });
+
+    s.call_on_name("log_scroll", |view: &mut ScrollView<NamedView<LogLinesView>>| {
+        if follow {
+            view.scroll_to_bottom();
+        } else {
+            view.scroll_to_important_area();
+        }
+    });
+}
+
+/// Seleziona la prossima riga di errore dopo quella corrente
+fn jump_to_next_error(s: &mut Cursive) {
+    let found = s.call_on_name("log_lines", |view: &mut LogLinesView| {
+        let len = view.len();
+        let start = view.selected_id().map_or(0, |idx| idx + 1);
+
+        let next = (0..len)
+            .map(|offset| (start + offset) % len.max(1))
+            .find(|&idx| matches!(view.get_item(idx), Some((_, Some(Level::Error)))));
+
+        if let Some(idx) = next {
+            view.set_selection(idx);
+        }
+        next.is_some()
+    }).unwrap_or(false);
+
+    if found {
+        s.focus_name("log_lines").ok();
+        s.call_on_name("log_scroll", |view: &mut ScrollView<NamedView<LogLinesView>>| {
+            view.scroll_to_important_area();
+        });
+    } else {
+        s.call_on_name("log_status", |view: &mut TextView| {
+            view.set_content("Nessun errore trovato con i filtri correnti");
+        });
+    }
+}
+
+/// Attiva o disattiva la modalità follow
+fn toggle_follow(s: &mut Cursive, state: &Arc<Mutex<LogState>>) {
+    let generation = match state.lock() {
+        Ok(mut state_guard) => {
+            state_guard.follow = !state_guard.follow;
+            state_guard.follow_generation += 1;
+            if !state_guard.follow {
+                None
+            } else {
+                Some(state_guard.follow_generation)
+            }
+        },
+        Err(_) => return,
+    };
+
+    refresh_log_lines(s, state);
+
+    let generation = match generation {
+        Some(generation) => generation,
+        None => return,
+    };
+
+    // Aggiorna periodicamente finché il follow resta attivo e la schermata è aperta
+    let cb_sink = s.cb_sink().clone();
+    let state = Arc::clone(state);
+    thread::spawn(move || {
+        loop {
+            thread::sleep(FOLLOW_INTERVAL);
+
+            let active = state.lock()
+                .map(|state_guard| state_guard.open && state_guard.follow && state_guard.follow_generation == generation)
+                .unwrap_or(false);
+            if !active {
+                break;
+            }
+
+            let state = Arc::clone(&state);
+            if cb_sink.send(Box::new(move |s| refresh_log_lines(s, &state))).is_err() {
+                break; // Interrompi il loop se l'interfaccia è stata chiusa
+            }
+        }
+    });
 }
 
 /// Legge i log recenti e li formatta per la visualizzazione
 pub fn read_recent_logs() -> String {
-    // Percorso della directory dei log
-    let log_dir = "/var/log/galatea";
-    
     // Ottieni l'elenco dei file di log
-    let log_state = LogState::new(log_dir.to_string());
+    let log_state = LogState::new(log_directory());
     let log_files = log_state.get_log_files();
     
     // Se non ci sono file di log, restituisci un messaggio
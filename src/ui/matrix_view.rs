@@ -0,0 +1,219 @@
+//! Matrice di stato degli stack
+//!
+//! Questo modulo mostra i task membri degli stack sulle righe e gli stack sulle colonne:
+//! ogni cella riporta lo stato del task, così da vedere subito cosa manca agli stack
+//! installati solo in parte. La verifica delle evidenze (`detect`) è eseguita su richiesta.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use anyhow::{Result, anyhow};
+
+use cursive::Cursive;
+use cursive::views::{Dialog, TextView, TextContent, ScrollView, LinearLayout, DummyView, Panel};
+use cursive::theme::{BaseColor, Color};
+use cursive::utils::markup::StyledString;
+use cursive::traits::*;
+use unicode_width::UnicodeWidthStr;
+
+use crate::config::Config;
+use crate::drift::{self, ComplianceStatus};
+use crate::session;
+use crate::stack::Stack;
+use crate::task::Task;
+use crate::ui::components::reload;
+use crate::ui::components::selection;
+
+/// Altezza massima delle intestazioni verticali con i nomi degli stack
+const HEADER_HEIGHT: usize = 12;
+
+/// Crea la vista della matrice stack × task
+pub fn create_matrix_view(
+    siv: &mut Cursive,
+    config: Arc<Mutex<Config>>,
+    stacks: Arc<Mutex<Vec<Stack>>>,
+    tasks: Arc<Mutex<Vec<Task>>>,
+) -> Result<()> {
+    let content = TextContent::new(render_locked(&stacks, &tasks, &HashMap::new())?);
+    session::set_screen(Some("matrix"));
+
+    let matrix = ScrollView::new(TextView::new_with_content(content.clone()))
+        .scroll_x(true)
+        .scroll_y(true);
+
+    let layout = LinearLayout::vertical()
+        .child(Panel::new(matrix)
+            .title("Task × Stack")
+            .full_screen())
+        .child(DummyView.fixed_height(1))
+        .child(TextView::new(legend()));
+
+    // Dopo il ricaricamento dei cataloghi la matrice viene ridisegnata senza le verifiche
+    let reload_id = reload::register({
+        let content = content.clone();
+        let stacks = Arc::clone(&stacks);
+        let tasks = Arc::clone(&tasks);
+        move |_| {
+            if let Ok(matrix) = render_locked(&stacks, &tasks, &HashMap::new()) {
+                content.set_content(matrix);
+            }
+        }
+    });
+
+    siv.add_fullscreen_layer(Dialog::around(layout)
+        .title("Matrice Stack")
+        .button("Verifica", move |s| {
+            // Verifica le evidenze dei task installati e colora le derive
+            let result = verify_members(&config, &stacks, &tasks)
+                .and_then(|verified| render_locked(&stacks, &tasks, &verified));
+
+            match result {
+                Ok(matrix) => content.set_content(matrix),
+                Err(e) => {
+                    s.add_layer(Dialog::info(format!("Errore durante la verifica: {}", e))
+                                 .fixed_width(50)
+                                 .fixed_height(10));
+                },
+            }
+        })
+        .button("Chiudi", move |s| {
+            reload::unregister(reload_id);
+            session::set_screen(None);
+            s.pop_layer();
+        }));
+
+    Ok(())
+}
+
+/// Verifica i task installati che appartengono ad almeno uno stack
+fn verify_members(
+    config: &Arc<Mutex<Config>>,
+    stacks: &Arc<Mutex<Vec<Stack>>>,
+    tasks: &Arc<Mutex<Vec<Task>>>,
+) -> Result<HashMap<String, ComplianceStatus>> {
+    let config_guard = config.lock().map_err(|_| anyhow!("Failed to lock config"))?;
+    let stacks_guard = stacks.lock().map_err(|_| anyhow!("Failed to lock stacks"))?;
+    let tasks_guard = tasks.lock().map_err(|_| anyhow!("Failed to lock tasks"))?;
+
+    Ok(member_tasks(&stacks_guard, &tasks_guard).into_iter()
+        .filter(|task| task.installed)
+        .map(|task| (task.qualified_name(), drift::verify_task(&config_guard, task).status))
+        .collect())
+}
+
+/// Compone la matrice bloccando stack e task
+fn render_locked(
+    stacks: &Arc<Mutex<Vec<Stack>>>,
+    tasks: &Arc<Mutex<Vec<Task>>>,
+    verified: &HashMap<String, ComplianceStatus>,
+) -> Result<StyledString> {
+    let stacks_guard = stacks.lock().map_err(|_| anyhow!("Failed to lock stacks"))?;
+    let tasks_guard = tasks.lock().map_err(|_| anyhow!("Failed to lock tasks"))?;
+    Ok(render(&stacks_guard, &tasks_guard, verified))
+}
+
+/// Restituisce i task che appartengono ad almeno uno stack, nell'ordine del catalogo
+fn member_tasks<'a>(stacks: &[Stack], tasks: &'a [Task]) -> Vec<&'a Task> {
+    tasks.iter()
+        .filter(|task| stacks.iter().any(|stack| is_member(stack, task)))
+        .collect()
+}
+
+/// Verifica se un task fa parte di uno stack
+fn is_member(stack: &Stack, task: &Task) -> bool {
+    stack.task_names.iter().any(|name| task.matches_ref(name))
+}
+
+/// Compone la matrice: intestazioni verticali, una riga per task e il riepilogo degli stack
+fn render(stacks: &[Stack], tasks: &[Task], verified: &HashMap<String, ComplianceStatus>) -> StyledString {
+    let mut matrix = StyledString::new();
+
+    if stacks.is_empty() {
+        matrix.append_plain("Nessuno stack disponibile\n");
+        return matrix;
+    }
+
+    let members = member_tasks(stacks, tasks);
+    let names: Vec<String> = members.iter().map(|task| task.qualified_name()).collect();
+    let name_width = names.iter().map(|name| name.width()).max().unwrap_or(0).max(4);
+
+    // Intestazioni: il nome di ogni stack scritto in verticale sopra la sua colonna
+    let header_height = stacks.iter()
+        .map(|stack| stack.name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .min(HEADER_HEIGHT);
+    for line in 0..header_height {
+        let mut header = " ".repeat(name_width);
+        for stack in stacks {
+            header.push(' ');
+            header.push(stack.name.chars().nth(line).unwrap_or(' '));
+        }
+        matrix.append_plain(format!("{}\n", header.trim_end()));
+    }
+    matrix.append_plain(format!("{}\n", "-".repeat(name_width + 2 * stacks.len())));
+
+    for (task, name) in members.iter().zip(&names) {
+        matrix.append_plain(selection::fit(name, name_width));
+        for stack in stacks {
+            matrix.append_plain(" ");
+            match cell(stack, task, verified) {
+                (symbol, Some(color)) => matrix.append_styled(symbol, color),
+                (symbol, None) => matrix.append_plain(symbol),
+            }
+        }
+        matrix.append_plain("\n");
+    }
+
+    // Riepilogo: task installati e mancanti per ogni stack
+    matrix.append_plain("\n");
+    for stack in stacks {
+        let missing: Vec<&str> = stack.task_names.iter()
+            .filter(|name| !tasks.iter().any(|t| t.matches_ref(name) && t.installed))
+            .map(|name| name.as_str())
+            .collect();
+        let installed = stack.task_names.len() - missing.len();
+
+        matrix.append_plain(format!("{}: {}/{} installati", stack.name, installed, stack.task_names.len()));
+        if !missing.is_empty() && installed > 0 {
+            matrix.append_styled(format!(", mancano: {}", missing.join(", ")), Color::Dark(BaseColor::Yellow));
+        }
+        matrix.append_plain("\n");
+    }
+
+    matrix
+}
+
+/// Restituisce il simbolo e il colore di una cella della matrice
+fn cell(stack: &Stack, task: &Task, verified: &HashMap<String, ComplianceStatus>) -> (&'static str, Option<Color>) {
+    if !is_member(stack, task) {
+        return (" ", None);
+    }
+
+    if task.has_failed() {
+        return ("✗", Some(Color::Dark(BaseColor::Red)));
+    }
+
+    if !task.installed {
+        return ("·", None);
+    }
+
+    match verified.get(&task.qualified_name()) {
+        Some(ComplianceStatus::Drifted) => ("!", Some(Color::Dark(BaseColor::Yellow))),
+        Some(ComplianceStatus::Unverifiable) => ("~", Some(Color::Dark(BaseColor::Cyan))),
+        _ => ("✓", Some(Color::Dark(BaseColor::Green))),
+    }
+}
+
+/// Costruisce la legenda dei simboli della matrice
+fn legend() -> StyledString {
+    let mut legend = StyledString::new();
+    legend.append_styled("✓", Color::Dark(BaseColor::Green));
+    legend.append_plain(" installato   ");
+    legend.append_styled("!", Color::Dark(BaseColor::Yellow));
+    legend.append_plain(" in deriva   ");
+    legend.append_styled("~", Color::Dark(BaseColor::Cyan));
+    legend.append_plain(" non verificabile   ");
+    legend.append_styled("✗", Color::Dark(BaseColor::Red));
+    legend.append_plain(" fallito   · non installato   (Verifica controlla le evidenze dei task installati)");
+    legend
+}
@@ -5,7 +5,11 @@
 pub mod app;
 pub mod task_view;
 pub mod stack_view;
+pub mod matrix_view;
+pub mod inventory_view;
+pub mod palette;
 pub mod theme;
 pub mod log_view;
+pub mod jobs_view;
 pub mod components;
 
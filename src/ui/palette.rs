@@ -0,0 +1,285 @@
+//! Palette dei comandi
+//!
+//! Questo modulo mostra una finestra di ricerca (`Ctrl+P`) disponibile in tutta la TUI:
+//! digitando parte del nome si raggiunge un task, uno stack o una schermata, si ricaricano
+//! i cataloghi oppure si apre la vista dei log filtrata sul testo cercato.
+
+use std::sync::{Arc, Mutex};
+
+use cursive::Cursive;
+use cursive::views::{Dialog, EditView, LinearLayout, SelectView, TextView, DummyView, OnEventView};
+use cursive::traits::*;
+use cursive::event::Key;
+
+use crate::config::Config;
+use crate::stack::Stack;
+use crate::task::Task;
+use crate::ui::app;
+use crate::ui::components::reload;
+use crate::ui::components::selectable_view;
+use crate::ui::inventory_view;
+use crate::ui::jobs_view;
+use crate::ui::log_view;
+use crate::ui::matrix_view;
+use crate::ui::stack_view;
+use crate::ui::task_view;
+
+/// Numero massimo di risultati mostrati
+const MAX_RESULTS: usize = 15;
+
+/// Destinazione di una voce della palette
+#[derive(Debug, Clone)]
+enum Target {
+    /// Task da mostrare, per indice nel catalogo
+    Task(usize),
+    /// Stack da mostrare, per indice nel catalogo
+    Stack(usize),
+    Tasks,
+    Stacks,
+    Matrix,
+    Inventory,
+    Jobs,
+    Settings,
+    /// Ricaricamento dei cataloghi
+    Reload,
+    /// Vista dei log filtrata sul testo indicato
+    Logs(String),
+}
+
+/// Voce della palette
+#[derive(Debug, Clone)]
+struct Entry {
+    /// Testo su cui viene eseguita la ricerca
+    name: String,
+
+    /// Etichetta mostrata nella lista
+    label: String,
+
+    target: Target,
+}
+
+/// Apre la palette dei comandi
+///
+/// # Arguments
+///
+/// * `siv` - L'istanza Cursive
+/// * `config` - La configurazione condivisa
+/// * `tasks` - Il catalogo dei task
+/// * `stacks` - Il catalogo degli stack
+pub fn show_palette(
+    siv: &mut Cursive,
+    config: Arc<Mutex<Config>>,
+    tasks: Arc<Mutex<Vec<Task>>>,
+    stacks: Arc<Mutex<Vec<Stack>>>,
+) {
+    // Una sola palette alla volta
+    if siv.find_name::<EditView>("palette_query").is_some() {
+        return;
+    }
+
+    let entries = Arc::new(build_entries(&tasks, &stacks));
+
+    let mut results = SelectView::<Target>::new();
+    fill_results(&mut results, &entries, "");
+
+    let config_submit = Arc::clone(&config);
+    let tasks_submit = Arc::clone(&tasks);
+    let stacks_submit = Arc::clone(&stacks);
+    results.set_on_submit(move |s, target: &Target| {
+        open_target(s, target.clone(), &config_submit, &tasks_submit, &stacks_submit);
+    });
+
+    let entries_edit = Arc::clone(&entries);
+    let query = EditView::new()
+        .on_edit(move |s, query, _| {
+            s.call_on_name("palette_results", |view: &mut SelectView<Target>| {
+                fill_results(view, &entries_edit, query);
+            });
+        })
+        .on_submit(move |s, _| {
+            // Invio nel campo di ricerca apre il primo risultato
+            let target = s.call_on_name("palette_results", |view: &mut SelectView<Target>| {
+                view.selection().map(|target| (*target).clone())
+            }).flatten();
+
+            if let Some(target) = target {
+                open_target(s, target, &config, &tasks, &stacks);
+            }
+        })
+        .with_name("palette_query")
+        .fixed_width(60);
+
+    let layout = LinearLayout::vertical()
+        .child(query)
+        .child(DummyView.fixed_height(1))
+        .child(results.with_name("palette_results").scrollable().fixed_height(MAX_RESULTS))
+        .child(DummyView.fixed_height(1))
+        .child(TextView::new("Invio: apri il primo risultato   Tab: scegli dalla lista   Esc: chiudi"));
+
+    let dialog = Dialog::around(layout)
+        .title("Vai a...")
+        .button("Chiudi", |s| { s.pop_layer(); });
+
+    siv.add_layer(OnEventView::new(dialog)
+        .on_event(Key::Esc, |s| { s.pop_layer(); }));
+}
+
+/// Costruisce le voci della palette a partire dai cataloghi
+fn build_entries(tasks: &Arc<Mutex<Vec<Task>>>, stacks: &Arc<Mutex<Vec<Stack>>>) -> Vec<Entry> {
+    let mut entries = vec![
+        screen("Gestione Task", Target::Tasks),
+        screen("Gestione Stack", Target::Stacks),
+        screen("Matrice Stack", Target::Matrix),
+        screen("Inventario di sistema", Target::Inventory),
+        screen("Visualizza Log", Target::Logs(String::new())),
+        screen("Job", Target::Jobs),
+        screen("Impostazioni", Target::Settings),
+        Entry {
+            name: "Ricarica cataloghi".to_string(),
+            label: "Esegui  Ricarica cataloghi".to_string(),
+            target: Target::Reload,
+        },
+    ];
+
+    if let Ok(tasks_guard) = tasks.lock() {
+        entries.extend(tasks_guard.iter().enumerate().map(|(idx, task)| Entry {
+            name: task.qualified_name(),
+            label: format!("Task    {} - {}", task.qualified_name(), task.description),
+            target: Target::Task(idx),
+        }));
+    }
+
+    if let Ok(stacks_guard) = stacks.lock() {
+        entries.extend(stacks_guard.iter().enumerate().map(|(idx, stack)| Entry {
+            name: stack.name.clone(),
+            label: format!("Stack   {} - {}", stack.name, stack.description),
+            target: Target::Stack(idx),
+        }));
+    }
+
+    entries
+}
+
+/// Crea la voce di una schermata
+fn screen(name: &str, target: Target) -> Entry {
+    Entry {
+        name: name.to_string(),
+        label: format!("Vai a   {}", name),
+        target,
+    }
+}
+
+/// Mostra le voci che corrispondono alla ricerca, dalla più pertinente
+fn fill_results(view: &mut SelectView<Target>, entries: &[Entry], query: &str) {
+    view.clear();
+
+    let mut matches: Vec<(i64, &Entry)> = entries.iter()
+        .filter_map(|entry| fuzzy_score(query, &entry.name).map(|score| (score, entry)))
+        .collect();
+    // A parità di punteggio vale l'ordine del catalogo
+    matches.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+
+    for (_, entry) in matches.into_iter().take(MAX_RESULTS) {
+        view.add_item(entry.label.clone(), entry.target.clone());
+    }
+
+    // La ricerca nei log è sempre disponibile come ultima voce
+    let query = query.trim();
+    if !query.is_empty() {
+        view.add_item(format!("Log     cerca \"{}\"", query), Target::Logs(query.to_string()));
+    }
+}
+
+/// Calcola la pertinenza di un nome rispetto alla ricerca
+///
+/// I caratteri della ricerca devono comparire nel nome nello stesso ordine, anche non
+/// consecutivi. Le lettere consecutive e quelle a inizio parola valgono di più.
+///
+/// # Returns
+///
+/// Il punteggio, o `None` se il nome non corrisponde
+fn fuzzy_score(query: &str, name: &str) -> Option<i64> {
+    let query: Vec<char> = query.trim().to_lowercase().chars().filter(|c| !c.is_whitespace()).collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    let mut score = 0i64;
+    let mut next = 0;
+    let mut previous: Option<usize> = None;
+
+    for (position, c) in name.iter().enumerate() {
+        if next == query.len() {
+            break;
+        }
+        if *c != query[next] {
+            continue;
+        }
+
+        score += 1;
+        if previous.is_some_and(|p| p + 1 == position) {
+            score += 5;
+        }
+        if position == 0 || !name[position - 1].is_alphanumeric() {
+            score += 3;
+        }
+
+        previous = Some(position);
+        next += 1;
+    }
+
+    if next < query.len() {
+        return None;
+    }
+
+    // A parità di corrispondenze, i nomi più corti sono più pertinenti
+    Some(score * 100 - name.len() as i64)
+}
+
+/// Chiude la palette e apre la destinazione scelta
+fn open_target(
+    s: &mut Cursive,
+    target: Target,
+    config: &Arc<Mutex<Config>>,
+    tasks: &Arc<Mutex<Vec<Task>>>,
+    stacks: &Arc<Mutex<Vec<Stack>>>,
+) {
+    s.pop_layer();
+
+    let result = match target {
+        Target::Task(idx) => task_view::create_task_view(s, Arc::clone(config), Arc::clone(tasks), Arc::clone(stacks))
+            .map(|_| selectable_view::focus_item(s, idx)),
+        Target::Stack(idx) => stack_view::create_stack_view(s, Arc::clone(config), Arc::clone(stacks), Arc::clone(tasks))
+            .map(|_| selectable_view::focus_item(s, idx)),
+        Target::Tasks => task_view::create_task_view(s, Arc::clone(config), Arc::clone(tasks), Arc::clone(stacks)),
+        Target::Stacks => stack_view::create_stack_view(s, Arc::clone(config), Arc::clone(stacks), Arc::clone(tasks)),
+        Target::Matrix => matrix_view::create_matrix_view(s, Arc::clone(config), Arc::clone(stacks), Arc::clone(tasks)),
+        Target::Inventory => {
+            inventory_view::create_inventory_view(s);
+            Ok(())
+        },
+        Target::Jobs => {
+            jobs_view::create_jobs_view(s);
+            Ok(())
+        },
+        Target::Settings => {
+            app::create_settings_screen(s, Arc::clone(config));
+            Ok(())
+        },
+        Target::Reload => {
+            reload::reload_catalogs(s, Arc::clone(config), Arc::clone(tasks), Arc::clone(stacks));
+            Ok(())
+        },
+        Target::Logs(search) => {
+            log_view::show_log_for(s, &search);
+            Ok(())
+        },
+    };
+
+    if let Err(e) = result {
+        s.add_layer(Dialog::info(format!("Errore durante l'apertura della vista: {}", e))
+                     .fixed_width(50)
+                     .fixed_height(10));
+    }
+}
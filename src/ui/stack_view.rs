@@ -38,6 +38,7 @@ pub fn create_stack_view(siv: &mut Cursive, config: Arc<Mutex<Config>>, stacks:
         config,
         stacks_with_tasks,
         selection,
+        "stacks",
         "Gestione Stack",
         true, // Gli stack possono essere modificati
     )
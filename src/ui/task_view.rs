@@ -11,11 +11,19 @@ use cursive::Cursive;
 
 use crate::config::Config;
 use crate::task::Task;
+use crate::stack::{self, Stack};
 use crate::ui::components::selection;
 use crate::ui::components::selectable_view;
 
 /// Crea la vista per la gestione dei task
-pub fn create_task_view(siv: &mut Cursive, config: Arc<Mutex<Config>>, tasks: Arc<Mutex<Vec<Task>>>) -> Result<()> {
+pub fn create_task_view(siv: &mut Cursive, config: Arc<Mutex<Config>>, tasks: Arc<Mutex<Vec<Task>>>, stacks: Arc<Mutex<Vec<Stack>>>) -> Result<()> {
+    // Aggiorna i task bloccati da uno stack prerequisito, che può essere stato installato nel frattempo
+    {
+        let mut tasks_guard = tasks.lock().map_err(|_| anyhow::anyhow!("Failed to lock tasks"))?;
+        let stacks_guard = stacks.lock().map_err(|_| anyhow::anyhow!("Failed to lock stacks"))?;
+        stack::mark_blocked_tasks(&mut tasks_guard, &stacks_guard);
+    }
+
     // Inizializza la selezione condivisa
     let selection = selection::new_shared_selection::<Task>();
     
@@ -25,6 +33,7 @@ pub fn create_task_view(siv: &mut Cursive, config: Arc<Mutex<Config>>, tasks: Ar
         config,
         tasks,
         selection,
+        "tasks",
         "Gestione Task",
         true, // I task possono essere modificati (installati/disinstallati)
     )
@@ -2,6 +2,7 @@
 //!
 //! Questo modulo fornisce funzioni di supporto generali utilizzate in diverse parti dell'applicazione.
 
+use std::collections::{BTreeMap, BTreeSet};
 use std::path::{Path, PathBuf};
 use std::fs;
 use std::process::Command;
@@ -103,6 +104,27 @@ pub fn get_current_username() -> String {
     "unknown".to_string()
 }
 
+/// Restituisce il nome host della macchina
+///
+/// # Returns
+///
+/// Il nome host, o "unknown" se non determinabile
+pub fn get_hostname() -> String {
+    #[cfg(unix)]
+    {
+        let mut buffer = [0u8; 256];
+        let result = unsafe { libc::gethostname(buffer.as_mut_ptr() as *mut libc::c_char, buffer.len()) };
+        if result == 0 {
+            let len = buffer.iter().position(|b| *b == 0).unwrap_or(buffer.len());
+            if len > 0 {
+                return String::from_utf8_lossy(&buffer[..len]).to_string();
+            }
+        }
+    }
+
+    env::var("HOSTNAME").unwrap_or_else(|_| "unknown".to_string())
+}
+
 /// Ottiene la home directory dell'utente corrente
 ///
 /// # Returns
@@ -182,6 +204,33 @@ pub fn is_program_installed(program: &str) -> bool {
     }
 }
 
+/// Verifica se un percorso è un file eseguibile
+///
+/// # Arguments
+///
+/// * `path` - Il percorso da verificare
+///
+/// # Returns
+///
+/// `true` se il percorso è un file con il permesso di esecuzione
+pub fn is_executable(path: &Path) -> bool {
+    let Ok(metadata) = fs::metadata(path) else {
+        return false;
+    };
+    if !metadata.is_file() {
+        return false;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.permissions().mode() & 0o111 != 0
+    }
+
+    #[cfg(not(unix))]
+    true
+}
+
 /// Ottiene la lista dei file in una directory che corrispondono a un pattern
 ///
 /// # Arguments
@@ -238,6 +287,30 @@ pub fn format_file_size(size: u64) -> String {
     }
 }
 
+/// Compone una riga CSV (RFC 4180), racchiudendo tra virgolette i campi che lo richiedono
+///
+/// # Arguments
+///
+/// * `fields` - I valori delle colonne
+///
+/// # Returns
+///
+/// La riga CSV terminata da CRLF
+pub fn csv_line<S: AsRef<str>>(fields: &[S]) -> String {
+    let fields: Vec<String> = fields.iter()
+        .map(|field| {
+            let field = field.as_ref();
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect();
+
+    format!("{}\r\n", fields.join(","))
+}
+
 /// Restituisce il nome del sistema operativo
 ///
 /// # Returns
@@ -309,3 +382,106 @@ pub fn get_os_name() -> String {
         env::consts::OS.to_string()
     }
 }
+
+/// Restituisce i pacchetti installati con la loro versione
+///
+/// Usa il gestore di pacchetti disponibile tra dpkg, rpm e pacman.
+///
+/// # Returns
+///
+/// I pacchetti installati, per nome; vuoto se nessun gestore è disponibile
+pub fn get_installed_packages() -> BTreeMap<String, String> {
+    let (program, args, separator): (&str, &[&str], char) = if is_program_installed("dpkg-query") {
+        ("dpkg-query", &["-W", "-f", "${db:Status-Abbrev}\t${Package}\t${Version}\n"], '\t')
+    } else if is_program_installed("rpm") {
+        ("rpm", &["-qa", "--qf", "ii \t%{NAME}\t%{VERSION}-%{RELEASE}\n"], '\t')
+    } else if is_program_installed("pacman") {
+        ("pacman", &["-Q"], ' ')
+    } else {
+        return BTreeMap::new();
+    };
+
+    let output = match Command::new(program).args(args).output() {
+        Ok(output) if output.status.success() => output.stdout,
+        _ => return BTreeMap::new(),
+    };
+
+    String::from_utf8_lossy(&output).lines()
+        .filter_map(|line| {
+            let mut fields = line.split(separator);
+            // dpkg elenca anche i pacchetti rimossi di cui restano i file di configurazione
+            if separator == '\t' && !fields.next()?.starts_with("ii") {
+                return None;
+            }
+            Some((fields.next()?.to_string(), fields.next().unwrap_or_default().to_string()))
+        })
+        .collect()
+}
+
+/// Restituisce i servizi systemd in esecuzione
+///
+/// # Returns
+///
+/// I nomi delle unità dei servizi attivi; vuoto senza systemd
+pub fn get_running_services() -> BTreeSet<String> {
+    let output = match Command::new("systemctl")
+        .args(["list-units", "--type=service", "--state=running", "--no-legend", "--plain"])
+        .output() {
+        Ok(output) if output.status.success() => output.stdout,
+        _ => return BTreeSet::new(),
+    };
+
+    String::from_utf8_lossy(&output).lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Restituisce le porte in ascolto sulla macchina
+///
+/// Le porte vengono lette da `/proc/net`: TCP in stato LISTEN e UDP non connesse.
+///
+/// # Returns
+///
+/// Le porte nella forma `tcp 0.0.0.0:22`; vuoto se `/proc/net` non è disponibile
+pub fn get_listening_ports() -> BTreeSet<String> {
+    // Stati del kernel: 0A è LISTEN per TCP, 07 è un socket UDP non connesso
+    let tables = [("tcp", "tcp", "0A"), ("tcp6", "tcp", "0A"), ("udp", "udp", "07"), ("udp6", "udp", "07")];
+
+    let mut ports = BTreeSet::new();
+    for (table, protocol, listening) in tables {
+        let Ok(content) = fs::read_to_string(Path::new("/proc/net").join(table)) else {
+            continue;
+        };
+        for line in content.lines().skip(1) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 4 || fields[3] != listening {
+                continue;
+            }
+            if let Some(address) = parse_proc_address(fields[1]) {
+                ports.insert(format!("{} {}", protocol, address));
+            }
+        }
+    }
+    ports
+}
+
+/// Converte un indirizzo di `/proc/net` (es. `0100007F:0016`) in `127.0.0.1:22`
+fn parse_proc_address(field: &str) -> Option<String> {
+    let (ip, port) = field.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+
+    // L'indirizzo è scritto come parole di 32 bit nell'ordine dei byte della macchina
+    let mut bytes = Vec::new();
+    for word in 0..ip.len() / 8 {
+        let value = u32::from_str_radix(ip.get(word * 8..word * 8 + 8)?, 16).ok()?;
+        bytes.extend_from_slice(&value.to_ne_bytes());
+    }
+
+    let ip = match bytes.len() {
+        4 => std::net::IpAddr::from(<[u8; 4]>::try_from(bytes).ok()?),
+        16 => std::net::IpAddr::from(<[u8; 16]>::try_from(bytes).ok()?),
+        _ => return None,
+    };
+    Some(std::net::SocketAddr::new(ip, port).to_string())
+}
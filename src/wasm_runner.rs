@@ -0,0 +1,250 @@
+//! Runner WebAssembly per i task di tipo `wasm`
+//!
+//! Un task `wasm` non contiene uno script ma indica un runner, un modulo WebAssembly (WASI)
+//! fornito da terzi che implementa un tipo di task personalizzato (es. pacchetti flatpak,
+//! estensioni di un'applicazione). Il modulo viene caricato a runtime con wasmtime, senza
+//! ricompilare Galatea, ed eseguito in una sandbox: nessun accesso alla rete né
+//! all'ambiente, il bundle del task in sola lettura in `/bundle`, memoria e durata limitate.
+//!
+//! Il runner riceve sullo standard input una richiesta JSON ([`RunnerRequest`]) con l'azione
+//! e le variabili del task e risponde sullo standard output con i comandi da eseguire
+//! ([`RunnerResponse`]). I comandi vengono eseguiti da Galatea come uno script bash del task,
+//! con timeout, sudo, mascheramento dei segreti e limiti di risorse: il runner decide cosa
+//! fare, ma non tocca mai direttamente il sistema.
+//!
+//! Il supporto è incluso compilando Galatea con la feature `wasm`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use anyhow::{Context, Result};
+use log::info;
+use serde::{Serialize, Deserialize};
+
+use crate::error::GalateaError;
+use crate::executor::{self, ProgressMarker, RunContext, ScriptResult, ScriptStatus};
+use crate::resources::ResourceLimits;
+use crate::utils;
+
+/// Versione del formato di richiesta e risposta dei runner
+pub const RUNNER_API_VERSION: u32 = 1;
+
+/// Nome del modulo cercato nel bundle quando il task non indica un runner
+pub const DEFAULT_RUNNER: &str = "runner.wasm";
+
+/// Script generato nella copia di lavoro con i comandi restituiti dal runner
+const RUNNER_SCRIPT: &str = "galatea-runner.sh";
+
+/// Durata massima dell'esecuzione del runner in secondi
+#[cfg_attr(not(feature = "wasm"), allow(dead_code))]
+const RUNNER_TIMEOUT_SECS: u64 = 60;
+
+/// Memoria massima del runner in byte
+#[cfg_attr(not(feature = "wasm"), allow(dead_code))]
+const RUNNER_MAX_MEMORY: usize = 256 * 1024 * 1024;
+
+/// Dimensione massima della risposta e dei messaggi del runner in byte
+#[cfg_attr(not(feature = "wasm"), allow(dead_code))]
+const RUNNER_MAX_OUTPUT: usize = 1024 * 1024;
+
+/// Richiesta passata al runner sullo standard input
+#[derive(Debug, Serialize)]
+pub struct RunnerRequest<'a> {
+    /// Versione del formato ([`RUNNER_API_VERSION`])
+    pub api: u32,
+
+    /// Nome del task
+    pub task: &'a str,
+
+    /// Azione richiesta (install, uninstall, reset, remediate)
+    pub action: &'a str,
+
+    /// Identificativo dell'esecuzione
+    pub run_id: &'a str,
+
+    /// Variabili del task
+    pub variables: &'a HashMap<String, String>,
+
+    /// Nome host della macchina
+    pub host: String,
+
+    /// Sistema operativo, es. "Ubuntu 24.04"
+    pub os: String,
+}
+
+/// Risposta del runner sullo standard output
+///
+/// ```json
+/// {"commands": ["flatpak install -y flathub org.gimp.GIMP"], "message": "GIMP installato"}
+/// ```
+///
+/// Una risposta senza comandi indica che l'azione non è necessaria.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct RunnerResponse {
+    /// Comandi shell da eseguire in ordine; il primo che fallisce interrompe l'azione
+    pub commands: Vec<String>,
+
+    /// Messaggio per l'utente
+    pub message: Option<String>,
+
+    /// Indica se serve un riavvio per completare l'azione
+    pub reboot_required: bool,
+}
+
+/// Esegue un'azione di un task `wasm`
+///
+/// # Arguments
+///
+/// * `module` - Il modulo WebAssembly del runner
+/// * `bundle` - La copia di lavoro del bundle del task
+/// * `action` - L'azione da eseguire
+/// * `vars` - Le variabili del task
+/// * `context` - Il contesto dell'esecuzione
+/// * `progress` - Il marcatore di avanzamento da cercare nell'output dei comandi
+/// * `limits` - I limiti di risorse con cui eseguire i comandi
+///
+/// # Returns
+///
+/// L'esito dell'azione
+pub fn run(module: &Path, bundle: &Path, action: &str, vars: &HashMap<String, String>, context: &RunContext,
+           progress: Option<&ProgressMarker>, limits: &ResourceLimits) -> Result<Option<ScriptResult>> {
+    if !module.is_file() {
+        return Err(GalateaError::Config(format!("WASM runner not found: {:?}", module)).into());
+    }
+
+    let request = RunnerRequest {
+        api: RUNNER_API_VERSION,
+        task: &context.task,
+        action,
+        run_id: &context.run_id,
+        variables: vars,
+        host: utils::get_hostname(),
+        os: utils::get_os_name(),
+    };
+    let request = serde_json::to_string(&request).context("Failed to serialize WASM runner request")?;
+
+    info!("Running WASM runner {:?} for {} of task {}", module, action, context.task);
+    let output = evaluate(module, bundle, action, &request)?;
+    let response: RunnerResponse = serde_json::from_slice(&output)
+        .map_err(|e| GalateaError::script(None, format!("Invalid response from WASM runner {:?}: {}", module, e)))?;
+
+    if response.commands.is_empty() {
+        info!("WASM runner {:?} returned no commands for {} of task {}", module, action, context.task);
+        return Ok(Some(ScriptResult {
+            status: ScriptStatus::Skipped,
+            changed: false,
+            message: response.message,
+            reboot_required: response.reboot_required,
+        }));
+    }
+
+    // I comandi vengono eseguiti come uno script del task, con le stesse garanzie
+    let script = bundle.join(RUNNER_SCRIPT);
+    let mut content = String::from("#!/bin/bash\nset -euo pipefail\n");
+    for command in &response.commands {
+        content.push_str(command);
+        content.push('\n');
+    }
+    fs::write(&script, content).context(format!("Failed to write runner script: {:?}", script))?;
+
+    let result = executor::run_bash_script(&script, &[action], vars, context, progress, limits)?;
+    Ok(Some(result.unwrap_or(ScriptResult {
+        status: ScriptStatus::Ok,
+        changed: true,
+        message: response.message,
+        reboot_required: response.reboot_required,
+    })))
+}
+
+/// Esegue il modulo del runner e ne restituisce lo standard output
+#[cfg(feature = "wasm")]
+fn evaluate(module_path: &Path, bundle: &Path, action: &str, request: &str) -> Result<Vec<u8>> {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+    use std::time::Duration;
+    use log::warn;
+    use wasmtime::{Config as EngineConfig, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+    use wasmtime_wasi::pipe::{MemoryInputPipe, MemoryOutputPipe};
+    use wasmtime_wasi::preview1::{self, WasiP1Ctx};
+    use wasmtime_wasi::{DirPerms, FilePerms, I32Exit, WasiCtxBuilder};
+
+    struct RunnerState {
+        wasi: WasiP1Ctx,
+        limits: StoreLimits,
+    }
+
+    let mut engine_config = EngineConfig::new();
+    engine_config.epoch_interruption(true);
+    let engine = Engine::new(&engine_config)?;
+    let module = Module::from_file(&engine, module_path)
+        .map_err(|e| GalateaError::Config(format!("Invalid WASM runner {:?}: {:#}", module_path, e)))?;
+
+    let mut linker: Linker<RunnerState> = Linker::new(&engine);
+    preview1::add_to_linker_sync(&mut linker, |state: &mut RunnerState| &mut state.wasi)?;
+
+    let stdout = MemoryOutputPipe::new(RUNNER_MAX_OUTPUT);
+    let stderr = MemoryOutputPipe::new(RUNNER_MAX_OUTPUT);
+    let wasi = WasiCtxBuilder::new()
+        .stdin(MemoryInputPipe::new(request.as_bytes().to_vec()))
+        .stdout(stdout.clone())
+        .stderr(stderr.clone())
+        .args(&["runner", action])
+        .preopened_dir(bundle, "/bundle", DirPerms::READ, FilePerms::READ)?
+        .build_p1();
+
+    let mut store = Store::new(&engine, RunnerState {
+        wasi,
+        limits: StoreLimitsBuilder::new().memory_size(RUNNER_MAX_MEMORY).build(),
+    });
+    store.limiter(|state| &mut state.limits);
+
+    // Un'epoca al secondo: il runner viene interrotto allo scadere del timeout
+    store.set_epoch_deadline(RUNNER_TIMEOUT_SECS);
+    let done = Arc::new(AtomicBool::new(false));
+    let ticker = {
+        let engine = engine.clone();
+        let done = Arc::clone(&done);
+        thread::spawn(move || {
+            while !done.load(Ordering::SeqCst) {
+                thread::sleep(Duration::from_secs(1));
+                engine.increment_epoch();
+            }
+        })
+    };
+
+    let outcome = linker.instantiate(&mut store, &module)
+        .and_then(|instance| instance.get_typed_func::<(), ()>(&mut store, "_start"))
+        .and_then(|start| start.call(&mut store, ()));
+    done.store(true, Ordering::SeqCst);
+    let _ = ticker.join();
+
+    for line in String::from_utf8_lossy(&stderr.contents()).lines() {
+        warn!("WASM runner {:?}: {}", module_path, line);
+    }
+
+    match outcome {
+        Ok(()) => {},
+        Err(e) => match e.downcast_ref::<I32Exit>() {
+            Some(I32Exit(0)) => {},
+            Some(I32Exit(code)) => return Err(GalateaError::script(Some(*code), format!(
+                "WASM runner {:?} failed with exit code: {}", module_path, code)).into()),
+            None if e.downcast_ref::<wasmtime::Trap>() == Some(&wasmtime::Trap::Interrupt) => {
+                return Err(GalateaError::script(None, format!(
+                    "WASM runner {:?} timed out after {} seconds", module_path, RUNNER_TIMEOUT_SECS)).into());
+            },
+            None => return Err(GalateaError::script(None, format!(
+                "WASM runner {:?} failed: {:#}", module_path, e)).into()),
+        },
+    }
+
+    Ok(stdout.contents().to_vec())
+}
+
+/// Senza la feature `wasm` i task di tipo wasm non possono essere eseguiti
+#[cfg(not(feature = "wasm"))]
+fn evaluate(module_path: &Path, _bundle: &Path, _action: &str, _request: &str) -> Result<Vec<u8>> {
+    Err(GalateaError::Config(format!(
+        "Cannot run WASM runner {:?}: Galatea was built without the wasm feature", module_path)).into())
+}
@@ -0,0 +1,245 @@
+//! Sorveglianza delle operazioni bloccate
+//!
+//! Uno script che resta a lungo senza output è spesso fermo su una domanda a cui nessuno
+//! risponde, su un lock o su una connessione di rete. Mentre uno script o un playbook è in
+//! esecuzione il suo supervisore registra periodicamente nel log che l'operazione è ancora
+//! in corso e, dopo `watchdog.stall_after` secondi senza output né avanzamento, la segnala
+//! come bloccata: nel log, nell'output del job e, nell'interfaccia TUI, con la finestra
+//! registrata con [`set_alert`], da cui l'operatore può terminarla o continuare ad attendere.
+//!
+//! ```yaml
+//! watchdog:
+//!   stall_after: 900   # 0 disattiva la segnalazione
+//!   heartbeat: 300     # 0 disattiva il battito nel log
+//! ```
+
+use std::io::{self, Read};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use lazy_static::lazy_static;
+use log::{Level, info, warn};
+use serde::{Serialize, Deserialize};
+
+use crate::events;
+
+/// Nessuna decisione dell'operatore in attesa
+const DECISION_NONE: u8 = 0;
+
+/// L'operatore ha chiesto di terminare l'operazione
+const DECISION_KILL: u8 = 1;
+
+/// L'operatore ha scelto di continuare ad attendere
+const DECISION_WAIT: u8 = 2;
+
+/// Finestra che segnala all'operatore un'operazione bloccata (es. quella dell'interfaccia
+/// TUI); la decisione viene comunicata con [`Watch::terminate`] o [`Watch::keep_waiting`]
+pub type Alert = Box<dyn Fn(Arc<Watch>) + Send + Sync>;
+
+lazy_static! {
+    static ref ALERT: Mutex<Option<Alert>> = Mutex::new(None);
+}
+
+/// Registra la finestra con cui segnalare le operazioni bloccate
+pub fn set_alert(alert: Alert) {
+    if let Ok(mut current) = ALERT.lock() {
+        *current = Some(alert);
+    }
+}
+
+/// Impostazioni della sorveglianza (`watchdog` in configurazione)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchdogConfig {
+    /// Secondi senza output dopo cui un'operazione viene segnalata come bloccata; 0 disattiva
+    pub stall_after: u64,
+
+    /// Intervallo in secondi tra due messaggi nel log sulle operazioni in corso; 0 disattiva
+    pub heartbeat: u64,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        WatchdogConfig {
+            stall_after: 900,
+            heartbeat: 300,
+        }
+    }
+}
+
+impl WatchdogConfig {
+    /// Verifica se la sorveglianza è attiva
+    pub fn is_enabled(&self) -> bool {
+        self.stall_after > 0 || self.heartbeat > 0
+    }
+
+    /// Inizia a sorvegliare un'operazione
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - L'operazione, per i messaggi (es. `Task nginx`)
+    ///
+    /// # Returns
+    ///
+    /// Lo stato dell'operazione da aggiornare con il suo output, oppure `None` se la
+    /// sorveglianza è disattivata
+    pub fn watch(&self, label: &str) -> Option<Arc<Watch>> {
+        self.is_enabled().then(|| Arc::new(Watch {
+            label: label.to_string(),
+            started: Instant::now(),
+            stall_after: (self.stall_after > 0).then(|| Duration::from_secs(self.stall_after)),
+            heartbeat: (self.heartbeat > 0).then(|| Duration::from_secs(self.heartbeat)),
+            last_activity: AtomicU64::new(0),
+            decision: AtomicU8::new(DECISION_NONE),
+            finished: AtomicBool::new(false),
+        }))
+    }
+}
+
+/// Operazione sorvegliata, condivisa tra chi ne legge l'output, il supervisore e la finestra
+#[derive(Debug)]
+pub struct Watch {
+    /// L'operazione, per i messaggi
+    label: String,
+
+    /// Avvio dell'operazione
+    started: Instant,
+
+    /// Silenzio dopo cui l'operazione è considerata bloccata
+    stall_after: Option<Duration>,
+
+    /// Intervallo tra due messaggi nel log
+    heartbeat: Option<Duration>,
+
+    /// Ultimo output ricevuto, in millisecondi dall'avvio
+    last_activity: AtomicU64,
+
+    /// Decisione dell'operatore non ancora applicata dal supervisore
+    decision: AtomicU8,
+
+    /// L'operazione è terminata
+    finished: AtomicBool,
+}
+
+impl Watch {
+    /// Restituisce l'operazione sorvegliata
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Registra un segno di attività dell'operazione
+    pub fn touch(&self) {
+        self.last_activity.store(self.started.elapsed().as_millis() as u64, Ordering::SeqCst);
+    }
+
+    /// Restituisce da quanto tempo l'operazione non produce output
+    pub fn idle(&self) -> Duration {
+        self.started.elapsed().saturating_sub(Duration::from_millis(self.last_activity.load(Ordering::SeqCst)))
+    }
+
+    /// Verifica se l'operazione è già terminata, es. mentre la finestra era aperta
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::SeqCst)
+    }
+
+    /// Chiede al supervisore di terminare l'operazione
+    pub fn terminate(&self) {
+        self.decision.store(DECISION_KILL, Ordering::SeqCst);
+    }
+
+    /// Chiede al supervisore di continuare ad attendere, azzerando il tempo di silenzio
+    pub fn keep_waiting(&self) {
+        self.decision.store(DECISION_WAIT, Ordering::SeqCst);
+    }
+}
+
+/// Output di un processo che aggiorna l'attività dell'operazione sorvegliata
+pub struct Tracked<R> {
+    inner: R,
+    watch: Option<Arc<Watch>>,
+}
+
+impl<R: Read> Read for Tracked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        if let (true, Some(watch)) = (read > 0, &self.watch) {
+            watch.touch();
+        }
+        Ok(read)
+    }
+}
+
+/// Collega l'output di un processo all'operazione sorvegliata, se presente
+pub fn track<R: Read>(inner: R, watch: Option<&Arc<Watch>>) -> Tracked<R> {
+    Tracked { inner, watch: watch.cloned() }
+}
+
+/// Controlli periodici di un'operazione, eseguiti dal thread che ne sorveglia il processo
+pub struct Monitor {
+    watch: Arc<Watch>,
+
+    /// Momento, dall'avvio, del prossimo messaggio nel log
+    next_heartbeat: Duration,
+
+    /// L'operazione è stata segnalata come bloccata e non ha più prodotto output
+    stalled: bool,
+}
+
+impl Monitor {
+    /// Inizia i controlli di un'operazione
+    pub fn new(watch: Arc<Watch>) -> Self {
+        let next_heartbeat = watch.heartbeat.unwrap_or_default();
+        Monitor { watch, next_heartbeat, stalled: false }
+    }
+
+    /// Registra il battito dell'operazione e la segnala se bloccata
+    ///
+    /// # Returns
+    ///
+    /// Il tempo di silenzio, se l'operatore ha chiesto di terminare l'operazione
+    pub fn check(&mut self) -> Option<Duration> {
+        let watch = &self.watch;
+        let idle = watch.idle();
+
+        match watch.decision.swap(DECISION_NONE, Ordering::SeqCst) {
+            DECISION_KILL => {
+                warn!("{}: terminated by the operator after {}s without output", watch.label, idle.as_secs());
+                return Some(idle);
+            },
+            DECISION_WAIT => {
+                info!("{}: operator chose to keep waiting", watch.label);
+                watch.touch();
+                self.stalled = false;
+                return None;
+            },
+            _ => {},
+        }
+
+        let elapsed = watch.started.elapsed();
+        if let Some(heartbeat) = watch.heartbeat.filter(|_| elapsed >= self.next_heartbeat) {
+            info!("{}: still running after {}s, last output {}s ago", watch.label, elapsed.as_secs(), idle.as_secs());
+            self.next_heartbeat += heartbeat;
+        }
+
+        let stall_after = watch.stall_after?;
+        if !self.stalled && idle >= stall_after {
+            self.stalled = true;
+            warn!("{}: no output for {}s, the operation may be stuck", watch.label, idle.as_secs());
+            events::log_line(Level::Warn, format!("{}: nessun output da {} minuti, l'operazione potrebbe essere bloccata",
+                watch.label, idle.as_secs() / 60));
+            if let Some(alert) = ALERT.lock().ok().as_ref().and_then(|alert| alert.as_ref()) {
+                alert(Arc::clone(watch));
+            }
+        } else if self.stalled && idle < stall_after {
+            self.stalled = false;
+            info!("{}: output resumed", watch.label);
+        }
+        None
+    }
+}
+
+impl Drop for Monitor {
+    fn drop(&mut self) {
+        self.watch.finished.store(true, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,128 @@
+//! Directory di lavoro delle esecuzioni
+//!
+//! Gli script di un task non vengono eseguiti nel bundle scaricato in `tasks_dir`, che
+//! resta una copia intatta condivisa da tutte le esecuzioni, ma in una sua copia creata
+//! per ogni azione in `<runs_dir>/<run id>/<task>-<azione>` e rimossa al termine. Così
+//! due esecuzioni contemporanee non si contendono gli stessi file e i file lasciati da
+//! un'esecuzione (output parziali, file temporanei) non influenzano la successiva.
+//!
+//! La copia usa `fs::copy`, che su Linux sfrutta `copy_file_range`: sui filesystem che lo
+//! supportano (btrfs, XFS) i blocchi vengono condivisi invece che duplicati.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use log::{debug, warn};
+
+use crate::config::Config;
+use crate::executor::RunContext;
+
+/// Copia di lavoro del bundle di un task per una singola azione
+#[derive(Debug)]
+pub struct Workspace {
+    /// Directory della copia, rimossa al termine dell'azione
+    root: PathBuf,
+
+    /// Percorso da eseguire all'interno della copia
+    path: PathBuf,
+}
+
+impl Workspace {
+    /// Crea la copia di lavoro di un bundle
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - La configurazione corrente
+    /// * `bundle_dir` - La directory in cui il bundle è stato estratto
+    /// * `local_path` - Il percorso del bundle da eseguire (directory o script)
+    /// * `context` - Il contesto dell'azione, che identifica esecuzione, task e azione
+    ///
+    /// # Returns
+    ///
+    /// La copia di lavoro, rimossa quando il valore viene rilasciato
+    pub fn create(config: &Config, bundle_dir: &Path, local_path: &Path, context: &RunContext) -> Result<Self> {
+        // Viene copiato l'intero bundle, anche se il percorso indica uno script al suo interno
+        let source = if local_path.starts_with(bundle_dir) && bundle_dir.is_dir() {
+            bundle_dir
+        } else if local_path.is_dir() {
+            local_path
+        } else {
+            local_path.parent().unwrap_or(Path::new("."))
+        };
+
+        let root = config.runs_dir()
+            .join(sanitize(&context.run_id))
+            .join(format!("{}-{}", sanitize(&context.task), sanitize(&context.action)));
+
+        // Una directory con lo stesso nome può restare solo da un'esecuzione interrotta
+        if root.exists() {
+            fs::remove_dir_all(&root)
+                .context(format!("Failed to remove stale work directory {}", root.display()))?;
+        }
+        copy_tree(source, &root)
+            .context(format!("Failed to prepare work directory {} for task {}", root.display(), context.task))?;
+
+        let path = match local_path.strip_prefix(source) {
+            Ok(relative) if !relative.as_os_str().is_empty() => root.join(relative),
+            _ => root.clone(),
+        };
+        debug!("Task {} {} runs in {}", context.task, context.action, path.display());
+
+        Ok(Workspace { root, path })
+    }
+
+    /// Restituisce il percorso da eseguire all'interno della copia
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.root) {
+            warn!("Failed to remove work directory {}: {}", self.root.display(), e);
+        }
+
+        // La directory dell'esecuzione viene rimossa con l'ultima copia che contiene
+        if let Some(run_dir) = self.root.parent() {
+            let _ = fs::remove_dir(run_dir);
+        }
+    }
+}
+
+/// Copia ricorsivamente una directory, mantenendo permessi e collegamenti simbolici
+fn copy_tree(source: &Path, target: &Path) -> Result<()> {
+    fs::create_dir_all(target)
+        .context(format!("Failed to create directory {}", target.display()))?;
+
+    for entry in fs::read_dir(source).context(format!("Failed to read directory {}", source.display()))? {
+        let entry = entry?;
+        let from = entry.path();
+        let to = target.join(entry.file_name());
+        let file_type = entry.file_type()?;
+
+        if file_type.is_dir() {
+            copy_tree(&from, &to)?;
+        } else if file_type.is_symlink() {
+            let link = fs::read_link(&from)?;
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(&link, &to)
+                .context(format!("Failed to copy symlink {}", from.display()))?;
+            #[cfg(not(unix))]
+            fs::copy(&from, &to)
+                .context(format!("Failed to copy {}", from.display()))?;
+        } else {
+            fs::copy(&from, &to)
+                .context(format!("Failed to copy {}", from.display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Rende un identificativo utilizzabile come nome di directory
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '_' })
+        .collect()
+}